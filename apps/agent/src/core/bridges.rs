@@ -0,0 +1,105 @@
+//! The host bridges VMs can be attached to. `FC_BRIDGES` names several
+//! (`public=fcbr0,private=fcbr1`) so tenants can sit on separate L2 segments
+//! without VXLAN; `FC_BRIDGE`, else the first named bridge, is the default
+//! the manager uses when a VM asks for none in particular.
+
+use anyhow::{bail, Result};
+
+pub const BRIDGES_ENV: &str = "FC_BRIDGES";
+pub const DEFAULT_BRIDGE_ENV: &str = "FC_BRIDGE";
+const FALLBACK_BRIDGE: &str = "fcbr0";
+/// Linux interface names are at most 15 bytes.
+const MAX_IFNAME_LEN: usize = 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostBridges {
+    pub default: String,
+    /// `(name, device)` in the order configured.
+    pub named: Vec<(String, String)>,
+}
+
+impl HostBridges {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(
+            std::env::var(BRIDGES_ENV).ok().as_deref(),
+            std::env::var(DEFAULT_BRIDGE_ENV).ok().as_deref(),
+        )
+    }
+
+    pub fn parse(bridges: Option<&str>, default: Option<&str>) -> Result<Self> {
+        let mut named: Vec<(String, String)> = Vec::new();
+        for entry in bridges.unwrap_or_default().split(',').map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let Some((name, device)) = entry.split_once('=') else {
+                bail!("invalid {BRIDGES_ENV} entry `{entry}`: expected name=bridge");
+            };
+            let (name, device) = (name.trim(), device.trim());
+            if name.is_empty() || !is_ifname(device) {
+                bail!("invalid {BRIDGES_ENV} entry `{entry}`: expected name=bridge");
+            }
+            if named.iter().any(|(existing, _)| existing == name) {
+                bail!("{BRIDGES_ENV} names bridge `{name}` twice");
+            }
+            named.push((name.to_string(), device.to_string()));
+        }
+
+        let default = match default.map(str::trim).filter(|d| !d.is_empty()) {
+            Some(device) if !is_ifname(device) => {
+                bail!("invalid {DEFAULT_BRIDGE_ENV} `{device}`")
+            }
+            Some(device) => device.to_string(),
+            None => named
+                .first()
+                .map(|(_, device)| device.clone())
+                .unwrap_or_else(|| FALLBACK_BRIDGE.to_string()),
+        };
+        Ok(Self { default, named })
+    }
+}
+
+/// The `bridges` capability: name to device.
+pub fn capability(named: &[(String, String)]) -> serde_json::Value {
+    named
+        .iter()
+        .map(|(name, device)| (name.clone(), serde_json::Value::from(device.clone())))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn is_ifname(device: &str) -> bool {
+    !device.is_empty()
+        && device.len() <= MAX_IFNAME_LEN
+        && !device.contains(|c: char| c.is_whitespace() || c == '/' || c == ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_named_bridges_and_picks_a_default() {
+        let only_default = HostBridges::parse(None, Some("br-lan")).unwrap();
+        assert_eq!(only_default.default, "br-lan");
+        assert!(only_default.named.is_empty());
+        assert_eq!(HostBridges::parse(None, None).unwrap().default, "fcbr0");
+
+        let segmented = HostBridges::parse(Some("public=fcbr0, private = fcbr1,"), None).unwrap();
+        assert_eq!(segmented.default, "fcbr0");
+        assert_eq!(
+            capability(&segmented.named),
+            serde_json::json!({"public": "fcbr0", "private": "fcbr1"})
+        );
+        assert_eq!(
+            HostBridges::parse(Some("public=fcbr0"), Some("fcbr9"))
+                .unwrap()
+                .default,
+            "fcbr9"
+        );
+
+        assert!(HostBridges::parse(Some("fcbr0"), None).is_err());
+        assert!(HostBridges::parse(Some("a=fcbr0,a=fcbr1"), None).is_err());
+        assert!(HostBridges::parse(Some("a=this-name-is-too-long"), None).is_err());
+    }
+}
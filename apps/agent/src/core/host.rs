@@ -0,0 +1,89 @@
+//! Host resource readings shared by registration and the capacity endpoint.
+
+/// Total and available memory in MiB, from `/proc/meminfo`. `(0, 0)` when
+/// it cannot be read.
+pub fn get_memory_info() -> (i64, i64) {
+    std::fs::read_to_string("/proc/meminfo")
+        .map(|content| parse_meminfo(&content))
+        .unwrap_or((0, 0))
+}
+
+fn parse_meminfo(content: &str) -> (i64, i64) {
+    let field = |line: &str| -> i64 {
+        line.split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0)
+    };
+
+    let mut total_kb = 0;
+    let mut free_kb = 0;
+    let mut available_kb = 0;
+    for line in content.lines() {
+        if line.starts_with("MemTotal:") {
+            total_kb = field(line);
+        } else if line.starts_with("MemFree:") {
+            free_kb = field(line);
+        } else if line.starts_with("MemAvailable:") {
+            available_kb = field(line);
+        }
+    }
+
+    // MemAvailable accounts for reclaimable page cache; older kernels lack it
+    let free_kb = if available_kb > 0 {
+        available_kb
+    } else {
+        free_kb
+    };
+    (total_kb / 1024, free_kb / 1024)
+}
+
+/// One-minute load average from `/proc/loadavg`.
+pub fn load_average() -> Option<f64> {
+    std::fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|content| parse_loadavg(&content))
+}
+
+fn parse_loadavg(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+/// Cumulative CPU time (`usage_usec`) from a cgroup v2 `cpu.stat` file.
+pub fn read_cgroup_usage_usec(cpu_stat: &str) -> Option<u64> {
+    for line in cpu_stat.lines() {
+        if let Some(rest) = line.strip_prefix("usage_usec ") {
+            return rest.trim().parse().ok();
+        }
+    }
+    None
+}
+
+/// CPUs not busy according to the load average, rounding the load up so a
+/// partially used CPU is not offered to the scheduler.
+pub fn free_cpus(total: usize, load: f64) -> usize {
+    total.saturating_sub(load.max(0.0).ceil() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meminfo_prefers_available_over_free() {
+        let content = "MemTotal:       16384000 kB\nMemFree:         1024000 kB\nMemAvailable:    8192000 kB\n";
+        assert_eq!(parse_meminfo(content), (16000, 8000));
+
+        let old_kernel = "MemTotal:       2048000 kB\nMemFree:         512000 kB\n";
+        assert_eq!(parse_meminfo(old_kernel), (2000, 500));
+    }
+
+    #[test]
+    fn free_cpus_subtracts_rounded_load() {
+        assert_eq!(parse_loadavg("1.50 0.80 0.40 2/345 6789\n"), Some(1.5));
+        assert_eq!(parse_loadavg(""), None);
+        assert_eq!(free_cpus(8, 1.5), 6);
+        assert_eq!(free_cpus(8, 0.0), 8);
+        assert_eq!(free_cpus(4, 12.0), 0);
+    }
+}
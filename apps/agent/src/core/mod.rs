@@ -1,3 +1,7 @@
+pub mod bridges;
+pub mod host;
 pub mod net;
+pub mod request_id;
+pub mod shutdown;
 pub mod systemd;
 pub mod uds_proxy;
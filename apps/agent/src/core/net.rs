@@ -184,14 +184,15 @@ pub async fn create_tap_with_vlan(
     Ok(())
 }
 
-pub async fn delete_tap(name: &str) -> Result<()> {
+/// Delete a tap device. Returns `false` when the device was already gone.
+pub async fn delete_tap(name: &str) -> Result<bool> {
     let output = Command::new("sudo")
         .args(["-n", "ip", "link", "del", name])
         .output()
         .await?;
 
     if output.status.success() {
-        return Ok(());
+        return Ok(true);
     }
 
     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -201,7 +202,7 @@ pub async fn delete_tap(name: &str) -> Result<()> {
         || stderr_trimmed.contains("does not exist")
         || stderr_trimmed.is_empty()
     {
-        return Ok(());
+        return Ok(false);
     }
 
     Err(anyhow!("failed to delete tap {name}: {stderr_trimmed}"))
@@ -967,6 +968,203 @@ pub async fn check_network_status(bridge: &str) -> Result<serde_json::Value> {
     }))
 }
 
+// --- Firewall (security group) rules ---
+
+/// A single L3/L4 filter rule for a network, as stored by the manager.
+/// `ingress` is traffic towards the VMs on the bridge, `egress` is traffic
+/// leaving them.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FirewallRule {
+    pub direction: String,
+    pub protocol: String,
+    #[serde(default)]
+    pub port_start: Option<u16>,
+    #[serde(default)]
+    pub port_end: Option<u16>,
+    #[serde(default)]
+    pub cidr: Option<String>,
+    pub action: String,
+}
+
+/// Name of the per-network filter chain. Bridge names are at most 15 chars,
+/// so this always fits iptables' 28-char chain name limit.
+pub fn firewall_chain(bridge: &str) -> String {
+    format!("NQFW-{}", bridge)
+}
+
+/// Chain a new ruleset is built in before it replaces [`firewall_chain`].
+pub fn firewall_staging_chain(bridge: &str) -> String {
+    format!("NQFN-{}", bridge)
+}
+
+/// Translate a rule into iptables arguments for `-A <chain>`.
+///
+/// Routed networks (NAT/isolated/VXLAN) see the bridge as the in/out device
+/// in FORWARD. For bridged networks both sides of the packet are the bridge,
+/// so direction is taken from the bridge port instead (VM taps are `tap*`).
+pub fn firewall_rule_args(bridge: &str, bridged: bool, rule: &FirewallRule) -> Result<Vec<String>> {
+    let mut args: Vec<String> = Vec::new();
+    let ingress = match rule.direction.as_str() {
+        "ingress" => true,
+        "egress" => false,
+        other => bail!("unknown rule direction: {other}"),
+    };
+
+    if bridged {
+        args.extend(["-m", "physdev"].map(String::from));
+        args.push(
+            if ingress {
+                "--physdev-out"
+            } else {
+                "--physdev-in"
+            }
+            .to_string(),
+        );
+        args.push("tap+".to_string());
+    } else {
+        args.push(if ingress { "-o" } else { "-i" }.to_string());
+        args.push(bridge.to_string());
+    }
+
+    if let Some(cidr) = rule.cidr.as_deref().filter(|c| !c.is_empty()) {
+        // Ingress filters on where traffic comes from, egress on where it goes.
+        args.push(if ingress { "-s" } else { "-d" }.to_string());
+        args.push(cidr.to_string());
+    }
+
+    match rule.protocol.as_str() {
+        "all" => {}
+        "icmp" => args.extend(["-p", "icmp"].map(String::from)),
+        proto @ ("tcp" | "udp") => {
+            args.extend(["-p", proto].map(String::from));
+            if let Some(start) = rule.port_start {
+                let end = rule.port_end.unwrap_or(start);
+                args.push("--dport".to_string());
+                args.push(if end == start {
+                    start.to_string()
+                } else {
+                    format!("{start}:{end}")
+                });
+            }
+        }
+        other => bail!("unknown rule protocol: {other}"),
+    }
+
+    let target = match rule.action.as_str() {
+        "accept" => "ACCEPT",
+        "drop" => "DROP",
+        other => bail!("unknown rule action: {other}"),
+    };
+    args.extend(["-j", target].map(String::from));
+
+    Ok(args)
+}
+
+/// Replace this network's firewall chain with `rules`.
+///
+/// The new ruleset is built in a staging chain, hooked into FORWARD ahead of
+/// the NAT accept rules, and only then is the old chain unhooked and the
+/// staging chain renamed into its place. A failure part way leaves the live
+/// chain as it was, and traffic is never forwarded with no chain at all.
+/// Established flows are always let through so a drop rule only affects new
+/// connections in the filtered direction.
+pub async fn apply_firewall_rules(
+    bridge: &str,
+    network_type: &str,
+    rules: &[FirewallRule],
+) -> Result<()> {
+    if std::env::var("AGENT_TEST_MODE").is_ok() {
+        eprintln!("AGENT_TEST_MODE: Skipping firewall rules for {bridge}");
+        return Ok(());
+    }
+
+    let bridged = network_type == "bridged";
+    // Validate everything before touching the live chain.
+    let rule_args = rules
+        .iter()
+        .map(|r| firewall_rule_args(bridge, bridged, r))
+        .collect::<Result<Vec<_>>>()?;
+
+    if bridged {
+        // Bridged frames only traverse iptables with br_netfilter loaded.
+        let _ = run_cmd_ignore("modprobe", &["br_netfilter"]).await;
+        let _ = run_cmd_ignore("sysctl", &["-w", "net.bridge.bridge-nf-call-iptables=1"]).await;
+    }
+
+    let chain = firewall_chain(bridge);
+    let staging = firewall_staging_chain(bridge);
+    // A previous attempt may have died part way.
+    drop_chain(bridge, &staging).await;
+    run_cmd("iptables", &["-N", &staging]).await?;
+
+    if let Err(e) = fill_firewall_chain(&staging, &rule_args).await {
+        drop_chain(bridge, &staging).await;
+        return Err(e);
+    }
+    for dir in ["-i", "-o"] {
+        if let Err(e) = run_cmd(
+            "iptables",
+            &["-I", "FORWARD", "1", dir, bridge, "-j", &staging],
+        )
+        .await
+        {
+            drop_chain(bridge, &staging).await;
+            return Err(e);
+        }
+    }
+
+    // The staging chain now sees every packet first; retire the old one.
+    drop_chain(bridge, &chain).await;
+    run_cmd("iptables", &["-E", &staging, &chain]).await
+}
+
+async fn fill_firewall_chain(chain: &str, rule_args: &[Vec<String>]) -> Result<()> {
+    if !rule_args.is_empty() {
+        run_cmd(
+            "iptables",
+            &[
+                "-A",
+                chain,
+                "-m",
+                "conntrack",
+                "--ctstate",
+                "RELATED,ESTABLISHED",
+                "-j",
+                "RETURN",
+            ],
+        )
+        .await?;
+    }
+    for args in rule_args {
+        let mut full = vec!["-A", chain];
+        full.extend(args.iter().map(String::as_str));
+        run_cmd("iptables", &full).await?;
+    }
+    Ok(())
+}
+
+/// Unhook `chain` from FORWARD for this bridge, then flush and delete it.
+/// Missing jumps or a missing chain are not errors.
+async fn drop_chain(bridge: &str, chain: &str) {
+    for dir in ["-i", "-o"] {
+        let _ = run_cmd_ignore("iptables", &["-D", "FORWARD", dir, bridge, "-j", chain]).await;
+    }
+    let _ = run_cmd_ignore("iptables", &["-F", chain]).await;
+    let _ = run_cmd_ignore("iptables", &["-X", chain]).await;
+}
+
+/// Remove this network's firewall chain and its FORWARD jumps. Other
+/// networks' chains and the shared NAT rules are left untouched.
+pub async fn flush_firewall_rules(bridge: &str) -> Result<()> {
+    if std::env::var("AGENT_TEST_MODE").is_ok() {
+        return Ok(());
+    }
+
+    drop_chain(bridge, &firewall_chain(bridge)).await;
+    drop_chain(bridge, &firewall_staging_chain(bridge)).await;
+    Ok(())
+}
+
 // --- Helper functions ---
 
 async fn create_bridge(bridge: &str) -> Result<()> {
@@ -1348,3 +1546,68 @@ pub async fn remove_port_forward(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        direction: &str,
+        protocol: &str,
+        ports: Option<(u16, u16)>,
+        cidr: Option<&str>,
+    ) -> FirewallRule {
+        FirewallRule {
+            direction: direction.to_string(),
+            protocol: protocol.to_string(),
+            port_start: ports.map(|p| p.0),
+            port_end: ports.map(|p| p.1),
+            cidr: cidr.map(str::to_string),
+            action: "accept".to_string(),
+        }
+    }
+
+    #[test]
+    fn firewall_rule_args_routed_ingress_port_range() {
+        let r = rule("ingress", "tcp", Some((8000, 8080)), Some("10.0.0.0/8"));
+        let args = firewall_rule_args("nqbr1", false, &r).unwrap();
+        assert_eq!(
+            args.join(" "),
+            "-o nqbr1 -s 10.0.0.0/8 -p tcp --dport 8000:8080 -j ACCEPT"
+        );
+    }
+
+    #[test]
+    fn firewall_rule_args_bridged_egress_uses_physdev() {
+        let mut r = rule("egress", "udp", Some((53, 53)), None);
+        r.action = "drop".to_string();
+        let args = firewall_rule_args("nqbr2", true, &r).unwrap();
+        assert_eq!(
+            args.join(" "),
+            "-m physdev --physdev-in tap+ -p udp --dport 53 -j DROP"
+        );
+    }
+
+    #[test]
+    fn firewall_rule_args_icmp_ignores_ports() {
+        let r = rule("ingress", "icmp", Some((1, 2)), None);
+        let args = firewall_rule_args("nqbr1", false, &r).unwrap();
+        assert_eq!(args.join(" "), "-o nqbr1 -p icmp -j ACCEPT");
+    }
+
+    #[test]
+    fn firewall_rule_args_rejects_unknown_values() {
+        assert!(firewall_rule_args("b", false, &rule("sideways", "tcp", None, None)).is_err());
+        assert!(firewall_rule_args("b", false, &rule("ingress", "sctp", None, None)).is_err());
+        let mut r = rule("ingress", "tcp", None, None);
+        r.action = "reject".to_string();
+        assert!(firewall_rule_args("b", false, &r).is_err());
+    }
+
+    #[test]
+    fn firewall_chain_fits_iptables_limit() {
+        assert!(firewall_chain("abcdefghijklmno").len() <= 28);
+        assert!(firewall_staging_chain("abcdefghijklmno").len() <= 28);
+        assert_ne!(firewall_chain("br0"), firewall_staging_chain("br0"));
+    }
+}
@@ -0,0 +1,47 @@
+//! Logs each request on a `request` span carrying the manager's
+//! `X-Request-Id`, so agent and Firecracker steps line up with the manager
+//! request that caused them. Direct callers without one get a fresh id.
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::Instrument;
+
+pub const HEADER: &str = "x-request-id";
+const MAX_LEN: usize = 128;
+
+fn accept_id(raw: Option<&str>) -> Option<&str> {
+    raw.map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_graphic()))
+}
+
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let id = accept_id(req.headers().get(HEADER).and_then(|v| v.to_str().ok()))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path()
+    );
+    let mut resp = next.run(req).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(HEADER, value);
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_sane_ids_are_kept() {
+        assert_eq!(accept_id(Some("abc-123")), Some("abc-123"));
+        assert_eq!(accept_id(Some("a b")), None);
+        assert_eq!(accept_id(Some("")), None);
+    }
+}
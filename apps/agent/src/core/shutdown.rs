@@ -0,0 +1,57 @@
+//! Graceful shutdown: on SIGTERM or SIGINT the agent sends the manager a
+//! last heartbeat marking the host as draining, so no new VMs are placed
+//! here, then waits up to `AGENT_SHUTDOWN_TIMEOUT_SECS` for in-flight
+//! requests. Running VMs are left alone.
+
+use std::time::Duration;
+use tokio::signal::unix::{signal as unix_signal, SignalKind};
+
+pub const TIMEOUT_ENV: &str = "AGENT_SHUTDOWN_TIMEOUT_SECS";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long in-flight requests get to finish once shutdown starts.
+pub fn timeout_from_env() -> Duration {
+    parse_timeout(std::env::var(TIMEOUT_ENV).ok().as_deref())
+}
+
+fn parse_timeout(raw: Option<&str>) -> Duration {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+/// Resolves on the first SIGTERM or SIGINT.
+pub async fn signal() {
+    let mut terminate = match unix_signal(SignalKind::terminate()) {
+        Ok(stream) => stream,
+        Err(err) => {
+            tracing::warn!(error = ?err, "cannot listen for SIGTERM; only Ctrl-C shuts down gracefully");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("SIGTERM received"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("SIGINT received"),
+    }
+}
+
+/// Resolves once `rx` has seen shutdown start.
+pub async fn requested(rx: &mut tokio::sync::watch::Receiver<bool>) {
+    // An error means the sender is gone, which only happens on exit anyway
+    let _ = rx.wait_for(|stopping| *stopping).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_defaults_and_ignores_garbage() {
+        assert_eq!(parse_timeout(None), DEFAULT_TIMEOUT);
+        assert_eq!(parse_timeout(Some(" 5 ")), Duration::from_secs(5));
+        assert_eq!(parse_timeout(Some("0")), DEFAULT_TIMEOUT);
+        assert_eq!(parse_timeout(Some("soon")), DEFAULT_TIMEOUT);
+    }
+}
@@ -2,8 +2,9 @@ use anyhow::*;
 use tokio::process::Command;
 
 /// Spawn firecracker under a transient systemd scope so it is tracked and killed on stop.
-pub async fn spawn_fc_scope(unit: &str, sock: &str) -> Result<()> {
-    spawn_fc_scope_with_screen(unit, sock, None).await
+/// `properties` (`CPUQuota=200%`, ...) are set on the scope as it is created.
+pub async fn spawn_fc_scope(unit: &str, sock: &str, properties: &[String]) -> Result<()> {
+    spawn_fc_scope_with_screen(unit, sock, None, properties).await
 }
 
 /// Spawn firecracker inside a screen session for console access
@@ -12,22 +13,29 @@ pub async fn spawn_fc_scope_with_screen(
     unit: &str,
     sock: &str,
     screen_name: Option<&str>,
+    properties: &[String],
 ) -> Result<()> {
     // Ensure parent dir exists is done by caller.
     let session_name = screen_name.unwrap_or(unit);
 
     // Use screen to create a detached session with a PTY for interactive console
     // The screen session allows us to attach to Firecracker's stdin/stdout later
-    let status = Command::new("sudo")
+    let mut cmd = Command::new("sudo");
+    cmd.args([
+        "systemd-run",
+        "--scope",
+        "--unit",
+        unit,
+        "--property",
+        "KillMode=mixed",
+        "--property",
+        "TimeoutStopSec=5s",
+    ]);
+    for property in properties {
+        cmd.arg("--property").arg(property);
+    }
+    let status = cmd
         .args([
-            "systemd-run",
-            "--scope",
-            "--unit",
-            unit,
-            "--property",
-            "KillMode=mixed",
-            "--property",
-            "TimeoutStopSec=5s",
             "--",
             "screen",
             "-dmS", // Create detached session with name
@@ -73,20 +81,21 @@ pub async fn set_scope_properties(unit: &str, properties: &[String]) -> Result<(
     Ok(())
 }
 
-pub async fn stop_unit(unit: &str) -> Result<()> {
+/// Stop a systemd unit. Returns `false` when the unit was not loaded.
+pub async fn stop_unit(unit: &str) -> Result<bool> {
     let output = Command::new("sudo")
         .args(["-n", "systemctl", "stop", unit])
         .output()
         .await?;
 
     if output.status.success() {
-        return Ok(());
+        return Ok(true);
     }
 
     let stderr = String::from_utf8_lossy(&output.stderr);
     let stderr_trimmed = stderr.trim();
     if stderr_trimmed.contains("not loaded") || stderr_trimmed.contains("could not be found") {
-        return Ok(());
+        return Ok(false);
     }
 
     Err(anyhow!(
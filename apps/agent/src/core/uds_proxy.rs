@@ -1,4 +1,6 @@
-use axum::http::{HeaderMap, Method, StatusCode};
+use std::time::Duration;
+
+use axum::http::{header, HeaderMap, Method, StatusCode};
 use axum::response::Response;
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
@@ -6,7 +8,26 @@ use hyper::Request;
 use hyper_util::{client::legacy::Client, rt::TokioExecutor};
 use hyperlocal::UnixConnector;
 
+const TIMEOUT_ENV: &str = "AGENT_FC_API_TIMEOUT_SECS";
+/// Snapshot create/load of a large VM can take minutes; everything else
+/// answers in milliseconds.
+const DEFAULT_TIMEOUT_SECS: u64 = 300;
+
+fn timeout() -> Duration {
+    Duration::from_secs(parse_timeout_secs(
+        std::env::var(TIMEOUT_ENV).ok().as_deref(),
+    ))
+}
+
+fn parse_timeout_secs(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+}
+
 /// Forward HTTP over Unix-domain socket to Firecracker API without socat.
+/// Firecracker's status and body (its `fault_message` JSON on errors) are
+/// passed back unchanged.
 pub async fn forward(
     sock_path: &str,
     path: &str,
@@ -27,15 +48,42 @@ pub async fn forward(
         }
     }
     let req = req.body(Full::new(body)).map_err(int)?;
-    let res = client.request(req).await.map_err(int)?;
-    let status = res.status();
-    let body_bytes = res.into_body().collect().await.map_err(int)?.to_bytes();
-    let resp = Response::builder()
-        .status(status)
-        .body(axum::body::Body::from(body_bytes))
-        .map_err(int)?;
-    Ok(resp)
+    let limit = timeout();
+    let exchange = async {
+        let res = client.request(req).await.map_err(int)?;
+        let status = res.status();
+        let content_type = res.headers().get(header::CONTENT_TYPE).cloned();
+        let body_bytes = res.into_body().collect().await.map_err(int)?.to_bytes();
+        Ok::<_, (StatusCode, String)>((status, content_type, body_bytes))
+    };
+    let (status, content_type, body_bytes) =
+        tokio::time::timeout(limit, exchange).await.map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                format!(
+                    "Firecracker API did not answer {path} within {}s",
+                    limit.as_secs()
+                ),
+            )
+        })??;
+    let mut resp = Response::builder().status(status);
+    if let Some(content_type) = content_type {
+        resp = resp.header(header::CONTENT_TYPE, content_type);
+    }
+    resp.body(axum::body::Body::from(body_bytes)).map_err(int)
 }
 fn int<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
     (StatusCode::BAD_GATEWAY, e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_timeout_secs(None), 300);
+        assert_eq!(parse_timeout_secs(Some("0")), 300);
+        assert_eq!(parse_timeout_secs(Some("30")), 30);
+    }
+}
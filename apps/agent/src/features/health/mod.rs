@@ -1,4 +1,19 @@
-use axum::{response::IntoResponse, routing::get, Json, Router};
+use axum::{response::IntoResponse, routing::get, Extension, Json, Router};
+use nexus_vmm::VmmKind;
+use serde::Serialize;
+
+use crate::core::host;
+use crate::AppState;
+
+/// Firecracker features the manager cares about, with the release that
+/// introduced each.
+const FIRECRACKER_FEATURES: &[(&str, (u32, u32, u32))] = &[
+    ("snapshots", (0, 23, 0)),
+    ("balloon", (0, 24, 0)),
+    ("diff_snapshots", (0, 24, 0)),
+    ("mmds_v2", (1, 0, 0)),
+    ("entropy", (1, 4, 0)),
+];
 
 pub fn router() -> Router {
     Router::new()
@@ -6,19 +21,112 @@ pub fn router() -> Router {
         .route("/agent/v1/capacity", get(capacity))
 }
 
-async fn health() -> impl IntoResponse {
+/// The installed Firecracker, as probed at startup. `version` is `None` when
+/// the binary is missing or its `--version` output couldn't be parsed.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct FirecrackerInfo {
+    version: Option<String>,
+    features: Vec<&'static str>,
+}
+
+/// `"Firecracker v1.7.0"` (first line of `firecracker --version`) → `(1, 7, 0)`.
+fn parse_firecracker_version(raw: &str) -> Option<(u32, u32, u32)> {
+    let token = raw.split_whitespace().find(|t| {
+        t.trim_start_matches('v')
+            .starts_with(|c: char| c.is_ascii_digit())
+    })?;
+    // Drop pre-release/build suffixes like "-dev" before splitting.
+    let core = token
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default();
+    let mut parts = core.split('.').map(|p| p.parse::<u32>().ok());
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn firecracker_info(probed: Option<&str>) -> FirecrackerInfo {
+    let Some(version) = probed.and_then(parse_firecracker_version) else {
+        return FirecrackerInfo {
+            version: None,
+            features: Vec::new(),
+        };
+    };
+    let (major, minor, patch) = version;
+    FirecrackerInfo {
+        version: Some(format!("{major}.{minor}.{patch}")),
+        features: FIRECRACKER_FEATURES
+            .iter()
+            .filter(|(_, since)| version >= *since)
+            .map(|(name, _)| *name)
+            .collect(),
+    }
+}
+
+async fn health(Extension(state): Extension<AppState>) -> impl IntoResponse {
+    let firecracker = firecracker_info(state.vmm_registry.version(VmmKind::Firecracker));
     Json(serde_json::json!({
     "version": env!("CARGO_PKG_VERSION"),
     "kvm": std::path::Path::new("/dev/kvm").exists(),
+    "firecracker": firecracker,
     "time": chrono::Utc::now(),
     }))
 }
 
 async fn capacity() -> impl IntoResponse {
+    let cpu_total = num_cpus::get();
+    let cpu_free = host::load_average()
+        .map(|load| host::free_cpus(cpu_total, load))
+        .unwrap_or(cpu_total);
+    let (mem_mib_total, mem_mib_free) = host::get_memory_info();
+
     Json(serde_json::json!({
-    "cpu_total": num_cpus::get(),
-    "cpu_free": num_cpus::get(),
-    "mem_mib_total": 0,
-    "mem_mib_free": 0,
+    "cpu_total": cpu_total,
+    "cpu_free": cpu_free,
+    "mem_mib_total": mem_mib_total,
+    "mem_mib_free": mem_mib_free,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_firecracker_version_lines() {
+        assert_eq!(
+            parse_firecracker_version("Firecracker v1.7.0"),
+            Some((1, 7, 0))
+        );
+        assert_eq!(
+            parse_firecracker_version("Firecracker v1.10.1-dev"),
+            Some((1, 10, 1))
+        );
+        assert_eq!(
+            parse_firecracker_version("Firecracker v0.25"),
+            Some((0, 25, 0))
+        );
+        assert_eq!(parse_firecracker_version("firecracker: not found"), None);
+        assert_eq!(parse_firecracker_version(""), None);
+    }
+
+    #[test]
+    fn features_follow_the_version() {
+        let info = firecracker_info(Some("Firecracker v1.2.0"));
+        assert_eq!(info.version.as_deref(), Some("1.2.0"));
+        assert_eq!(
+            info.features,
+            vec!["snapshots", "balloon", "diff_snapshots", "mmds_v2"]
+        );
+        assert!(firecracker_info(Some("Firecracker v1.7.0"))
+            .features
+            .contains(&"entropy"));
+
+        let missing = firecracker_info(None);
+        assert_eq!(missing.version, None);
+        assert!(missing.features.is_empty());
+    }
+}
@@ -37,8 +37,20 @@ struct SocketInventory {
     vm_id: String,
     sockets: Vec<String>,
     logs: Vec<String>,
+    /// Resource usage of the VM's `fc-{id}.scope`; absent when it isn't running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<VmUsage>,
 }
 
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct VmUsage {
+    memory_current_bytes: u64,
+    cpu_usage_usec: u64,
+}
+
+/// Where systemd puts the cgroup of a transient `fc-{id}.scope`.
+const SCOPE_CGROUP_ROOT: &str = "/sys/fs/cgroup/system.slice";
+
 async fn list_scopes() -> anyhow::Result<Vec<String>> {
     let output = Command::new("systemctl")
         .args([
@@ -128,11 +140,14 @@ async fn list_sockets(run_dir: &str) -> anyhow::Result<Vec<SocketInventory>> {
 
         let sockets = collect_dir_files(vm_path.join("sock")).await;
         let logs = collect_dir_files(vm_path.join("logs")).await;
+        let usage =
+            read_scope_usage(&Path::new(SCOPE_CGROUP_ROOT).join(format!("fc-{vm_id}.scope"))).await;
 
         inventories.push(SocketInventory {
             vm_id,
             sockets,
             logs,
+            usage,
         });
     }
 
@@ -140,6 +155,27 @@ async fn list_sockets(run_dir: &str) -> anyhow::Result<Vec<SocketInventory>> {
     Ok(inventories)
 }
 
+/// Memory and CPU counters of a scope's cgroup. `None` if the cgroup is gone,
+/// i.e. the VM is not running.
+async fn read_scope_usage(cgroup: &Path) -> Option<VmUsage> {
+    let memory_current_bytes = tokio::fs::read_to_string(cgroup.join("memory.current"))
+        .await
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let cpu_usage_usec = tokio::fs::read_to_string(cgroup.join("cpu.stat"))
+        .await
+        .ok()
+        .and_then(|s| crate::core::host::read_cgroup_usage_usec(&s))
+        .unwrap_or(0);
+
+    Some(VmUsage {
+        memory_current_bytes,
+        cpu_usage_usec,
+    })
+}
+
 async fn collect_dir_files(path: impl AsRef<Path>) -> Vec<String> {
     let path = path.as_ref().to_path_buf();
     let mut files = Vec::new();
@@ -224,7 +260,28 @@ mod tests {
                 vm_id: "vm-01".into(),
                 sockets: vec![sock_path.to_string_lossy().into_owned()],
                 logs: vec![log_path.to_string_lossy().into_owned()],
+                usage: None,
             }]
         );
     }
+
+    #[tokio::test]
+    async fn reads_scope_usage_from_cgroup_files() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_scope_usage(dir.path()).await, None);
+
+        std::fs::write(dir.path().join("memory.current"), "268435456\n").unwrap();
+        std::fs::write(
+            dir.path().join("cpu.stat"),
+            "usage_usec 1500000\nuser_usec 1000000\nsystem_usec 500000\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_scope_usage(dir.path()).await,
+            Some(VmUsage {
+                memory_current_bytes: 268_435_456,
+                cpu_usage_usec: 1_500_000,
+            })
+        );
+    }
 }
@@ -19,8 +19,12 @@ pub fn router(state: AppState) -> Router {
         .merge(health::router())
         .merge(inventory::router())
         .nest("/agent/v1/vms", vm::router().merge(tap::router()))
+        .nest("/agent/v1/taps", tap::taps_router())
         .nest("/agent/v1/networks", networks::router())
         .nest("/agent/v1/vmm", vmm_routes::router())
         .nest("/v1/storage", storage::routes::router(storage_state))
         .layer(Extension(state))
+        .layer(axum::middleware::from_fn(
+            crate::core::request_id::middleware,
+        ))
 }
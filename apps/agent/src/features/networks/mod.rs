@@ -28,6 +28,9 @@ struct ProvisionReq {
     local_ip: Option<String>,
     #[serde(default)]
     is_gateway: bool,
+    /// Firewall rules to apply to the bridge once it is up
+    #[serde(default)]
+    rules: Vec<net::FirewallRule>,
 }
 
 fn default_true() -> bool {
@@ -46,6 +49,14 @@ struct TeardownReq {
     is_gateway: bool,
 }
 
+#[derive(Deserialize)]
+struct RulesReq {
+    network_type: String,
+    bridge_name: String,
+    #[serde(default)]
+    rules: Vec<net::FirewallRule>,
+}
+
 #[derive(Deserialize)]
 struct PeerReq {
     vni: u32,
@@ -60,6 +71,7 @@ pub fn router() -> Router {
         .route("/status/:bridge", get(status))
         .route("/peers/add", post(add_peer))
         .route("/peers/remove", post(remove_peer))
+        .route("/rules", post(apply_rules))
 }
 
 async fn provision(
@@ -136,6 +148,10 @@ async fn provision(
         }
     }
 
+    net::apply_firewall_rules(&req.bridge_name, &req.network_type, &req.rules)
+        .await
+        .map_err(internal)?;
+
     Ok(Json(serde_json::json!({
         "ok": true,
         "bridge": req.bridge_name,
@@ -146,6 +162,10 @@ async fn provision(
 async fn teardown(
     Json(req): Json<TeardownReq>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    net::flush_firewall_rules(&req.bridge_name)
+        .await
+        .map_err(internal)?;
+
     match req.network_type.as_str() {
         "bridged" => {
             net::teardown_bridged_network(&req.bridge_name)
@@ -176,6 +196,19 @@ async fn teardown(
     })))
 }
 
+async fn apply_rules(
+    Json(req): Json<RulesReq>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    net::apply_firewall_rules(&req.bridge_name, &req.network_type, &req.rules)
+        .await
+        .map_err(internal)?;
+    Ok(Json(serde_json::json!({
+        "ok": true,
+        "bridge": req.bridge_name,
+        "rules": req.rules.len(),
+    })))
+}
+
 async fn add_peer(
     Json(req): Json<PeerReq>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
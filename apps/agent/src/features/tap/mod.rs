@@ -10,20 +10,31 @@ struct TapReq {
     bridge: Option<String>,
     owner_user: Option<String>,
     vlan_id: Option<u16>,
-    tap_name: Option<String>, // Allow custom TAP device name
+    /// Chosen by the manager, which guarantees it is unique across VMs.
+    tap_name: String,
 }
 
 pub fn router() -> Router {
     Router::new().route("/:id/tap", post(create_tap))
 }
 
+/// Routes addressed by tap name rather than VM id (mounted at `/agent/v1/taps`).
+pub fn taps_router() -> Router {
+    Router::new().route("/:name/delete", post(delete_tap))
+}
+
 async fn create_tap(
     Extension(st): Extension<AppState>,
     Path(id): Path<String>,
     Json(req): Json<TapReq>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    // Use custom tap_name if provided, otherwise default to tap-{vm-short-id}
-    let tap = req.tap_name.unwrap_or_else(|| format!("tap-{}", &id[..8]));
+    let tap = req.tap_name;
+    if !is_tap_name(&tap) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("invalid tap name {tap} for vm {id}"),
+        ));
+    }
     let bridge = req.bridge.unwrap_or(st.bridge.clone());
     net::ensure_bridge(&bridge, None).await.map_err(internal)?;
     net::create_tap_with_vlan(&tap, &bridge, req.vlan_id, req.owner_user.as_deref())
@@ -38,6 +49,45 @@ async fn create_tap(
 
     Ok(Json(response))
 }
+/// Delete a tap device by name. Deleting a tap that no longer exists is a no-op.
+async fn delete_tap(
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !is_tap_name(&name) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("refusing to delete non-tap interface {name}"),
+        ));
+    }
+    net::delete_tap(&name).await.map_err(internal)?;
+    Ok(Json(serde_json::json!({"ok": true, "tap": name})))
+}
+
+/// Only interfaces the agent creates for VMs may be deleted through the API.
+fn is_tap_name(name: &str) -> bool {
+    name.starts_with("tap")
+        && name.len() <= 15
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 fn internal<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
     (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_tap_name_rejects_non_tap_interfaces() {
+        assert!(is_tap_name("tap-1a2b3c4d"));
+        assert!(is_tap_name("tap0"));
+        assert!(is_tap_name("tap-0123456789a"));
+        assert!(!is_tap_name("eth0"));
+        assert!(!is_tap_name("br0"));
+        assert!(!is_tap_name("tap-../../etc"));
+        assert!(!is_tap_name("tap-0123456789abcdef"));
+    }
+}
@@ -127,6 +127,7 @@ mod tests {
         AppState {
             run_dir: run_dir.to_string(),
             bridge: "fcbr0".into(),
+            named_bridges: Vec::new(),
             storage_registry: Default::default(),
             nfs_config: None,
             vmm_registry: crate::vmm::VmmRegistry::empty(),
@@ -39,7 +39,7 @@ async fn proxy(
     uds_proxy::forward(&sock, &forward_path, method, headers, body).await
 }
 
-async fn resolve_socket_path(
+pub(super) async fn resolve_socket_path(
     st: &AppState,
     id: &str,
     requested: &str,
@@ -94,6 +94,10 @@ fn is_allowed_endpoint(method: &Method, segments: &[&str]) -> bool {
             | (&Method::PUT, ["mmds", "config"])
             | (&Method::PUT, ["entropy"])
             | (&Method::PUT, ["serial"])
+            | (&Method::GET, ["balloon"])
+            | (&Method::PUT, ["balloon"])
+            | (&Method::PATCH, ["balloon"])
+            | (&Method::PATCH, ["balloon", "statistics"])
     )
 }
 
@@ -101,6 +105,17 @@ fn is_allowed_endpoint(method: &Method, segments: &[&str]) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn allows_balloon_endpoints() {
+        assert!(is_allowed_endpoint(&Method::GET, &["balloon"]));
+        assert!(is_allowed_endpoint(&Method::PATCH, &["balloon"]));
+        assert!(is_allowed_endpoint(
+            &Method::PATCH,
+            &["balloon", "statistics"]
+        ));
+        assert!(!is_allowed_endpoint(&Method::GET, &["machine-config"]));
+    }
+
     #[tokio::test]
     async fn allows_valid_socket() {
         let tmp = tempfile::tempdir().unwrap();
@@ -114,6 +129,7 @@ mod tests {
         let st = AppState {
             run_dir: run_dir.to_string_lossy().to_string(),
             bridge: "fcbr0".into(),
+            named_bridges: Vec::new(),
             storage_registry: Default::default(),
             nfs_config: None,
             vmm_registry: crate::vmm::VmmRegistry::empty(),
@@ -141,6 +157,7 @@ mod tests {
         let st = AppState {
             run_dir: run_dir.to_string_lossy().to_string(),
             bridge: "fcbr0".into(),
+            named_bridges: Vec::new(),
             storage_registry: Default::default(),
             nfs_config: None,
             vmm_registry: crate::vmm::VmmRegistry::empty(),
@@ -171,6 +188,7 @@ mod tests {
         let st = AppState {
             run_dir: run_dir.to_string_lossy().to_string(),
             bridge: "fcbr0".into(),
+            named_bridges: Vec::new(),
             storage_registry: Default::default(),
             nfs_config: None,
             vmm_registry: crate::vmm::VmmRegistry::empty(),
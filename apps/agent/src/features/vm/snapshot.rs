@@ -7,8 +7,22 @@ use uuid::Uuid;
 
 use crate::AppState;
 
+/// Compressed memory files get this suffix; restores decompress them to a
+/// `restore-*.fc` file beside it.
+const COMPRESSED_SUFFIX: &str = ".zst";
+const RESTORE_PREFIX: &str = "restore-";
+const ZSTD_LEVEL: i32 = 3;
+/// Zero runs this long are left as holes when decompressing.
+const PAGE_SIZE: usize = 4096;
+
 pub fn router() -> Router {
-    Router::new().route("/:id/snapshots/prepare", post(prepare))
+    Router::new()
+        .route("/:id/snapshots/prepare", post(prepare))
+        .route("/:id/snapshots/compress", post(compress))
+        .route("/:id/snapshots/decompress", post(decompress))
+        .route("/:id/snapshots/discard", post(discard))
+        .route("/:id/snapshots/stat", post(stat))
+        .route("/:id/snapshots/delete", post(delete))
 }
 
 #[derive(Deserialize)]
@@ -79,6 +93,288 @@ async fn prepare(
     }))
 }
 
+#[derive(Deserialize)]
+struct MemFileRequest {
+    snapshot_id: Uuid,
+    /// Only for `discard`: the restore file `decompress` returned.
+    #[serde(default)]
+    mem_path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MemFileResponse {
+    mem_path: String,
+    mem_size_bytes: Option<u64>,
+}
+
+fn mem_dir(run_dir: &Path, vm_id: &Uuid, snapshot_id: &Uuid) -> PathBuf {
+    snapshot_base_dir(run_dir, vm_id, snapshot_id).join("mem")
+}
+
+/// Replace a Full snapshot's `mem.fc` with `mem.fc.zst`.
+async fn compress(
+    Extension(st): Extension<AppState>,
+    AxumPath(vm_id): AxumPath<Uuid>,
+    Json(req): Json<MemFileRequest>,
+) -> Result<Json<MemFileResponse>, (StatusCode, String)> {
+    let dir = mem_dir(Path::new(&st.run_dir), &vm_id, &req.snapshot_id);
+    let raw = dir.join("mem.fc");
+    let packed = dir.join(format!("mem.fc{COMPRESSED_SUFFIX}"));
+    if !file_status(&raw).await?.0 {
+        return Err((StatusCode::NOT_FOUND, "snapshot has no memory file".into()));
+    }
+    let (src, dst) = (raw.clone(), packed.clone());
+    tokio::task::spawn_blocking(move || compress_file(&src, &dst))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+    fs::remove_file(&raw).await.map_err(internal_error)?;
+    Ok(Json(MemFileResponse {
+        mem_path: path_to_string(&packed)?,
+        mem_size_bytes: file_status(&packed).await?.1,
+    }))
+}
+
+/// Expand `mem.fc.zst` into a fresh restore file for `snapshot/load`. The
+/// caller discards it once Firecracker has mapped it.
+async fn decompress(
+    Extension(st): Extension<AppState>,
+    AxumPath(vm_id): AxumPath<Uuid>,
+    Json(req): Json<MemFileRequest>,
+) -> Result<Json<MemFileResponse>, (StatusCode, String)> {
+    let dir = mem_dir(Path::new(&st.run_dir), &vm_id, &req.snapshot_id);
+    let packed = dir.join(format!("mem.fc{COMPRESSED_SUFFIX}"));
+    if !file_status(&packed).await?.0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "snapshot has no compressed memory file".into(),
+        ));
+    }
+    let restore = dir.join(format!("{RESTORE_PREFIX}{}.fc", Uuid::new_v4()));
+    let (src, dst) = (packed, restore.clone());
+    let result = tokio::task::spawn_blocking(move || decompress_file(&src, &dst))
+        .await
+        .map_err(internal_error)?;
+    if let Err(err) = result {
+        let _ = fs::remove_file(&restore).await;
+        return Err(internal_error(err));
+    }
+    Ok(Json(MemFileResponse {
+        mem_path: path_to_string(&restore)?,
+        mem_size_bytes: file_status(&restore).await?.1,
+    }))
+}
+
+/// Remove a restore file. Anything but a `restore-*.fc` in the snapshot's
+/// memory directory is refused.
+async fn discard(
+    Extension(st): Extension<AppState>,
+    AxumPath(vm_id): AxumPath<Uuid>,
+    Json(req): Json<MemFileRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let dir = mem_dir(Path::new(&st.run_dir), &vm_id, &req.snapshot_id);
+    let path = PathBuf::from(req.mem_path.unwrap_or_default());
+    if !is_restore_file(&dir, &path) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "not a snapshot restore file".into(),
+        ));
+    }
+    match fs::remove_file(&path).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(StatusCode::NO_CONTENT),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+#[derive(Deserialize)]
+struct StatSnapshotRequest {
+    snapshot_id: Uuid,
+    snapshot_path: String,
+    #[serde(default)]
+    mem_path: Option<String>,
+    /// Kernel, rootfs and extra drives the restored VM opens; they live
+    /// outside the snapshot directory, so only plain absolute paths are taken.
+    #[serde(default)]
+    disk_paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct StatSnapshotResponse {
+    reachable: bool,
+}
+
+/// Whether this host can open a snapshot's files and the disks it refers to,
+/// i.e. whether it can restore a snapshot another host took (shared `run_dir`
+/// and image storage). Snapshot paths must be inside the snapshot's own
+/// directory.
+async fn stat(
+    Extension(st): Extension<AppState>,
+    AxumPath(vm_id): AxumPath<Uuid>,
+    Json(req): Json<StatSnapshotRequest>,
+) -> Result<Json<StatSnapshotResponse>, (StatusCode, String)> {
+    let base_dir = snapshot_base_dir(Path::new(&st.run_dir), &vm_id, &req.snapshot_id);
+    let base_dir = match fs::canonicalize(&base_dir).await {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Json(StatSnapshotResponse { reachable: false }));
+        }
+        Err(err) => return Err(internal_error(err)),
+    };
+    let mut paths = vec![PathBuf::from(&req.snapshot_path)];
+    paths.extend(
+        req.mem_path
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from),
+    );
+    if !paths.iter().all(|path| is_within(&base_dir, path)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "path is outside the snapshot directory".into(),
+        ));
+    }
+    let disks: Vec<PathBuf> = req.disk_paths.iter().map(PathBuf::from).collect();
+    if !disks.iter().all(|path| is_plain_absolute(path)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "disk paths must be absolute without '..'".into(),
+        ));
+    }
+    paths.extend(disks);
+    for path in &paths {
+        if !file_status(path).await?.0 {
+            return Ok(Json(StatSnapshotResponse { reachable: false }));
+        }
+    }
+    Ok(Json(StatSnapshotResponse { reachable: true }))
+}
+
+#[derive(Deserialize)]
+struct DeleteSnapshotRequest {
+    snapshot_id: Uuid,
+}
+
+#[derive(Serialize)]
+struct DeleteSnapshotResponse {
+    freed_bytes: u64,
+}
+
+/// Remove a snapshot's directory with everything in it. A directory that is
+/// already gone frees nothing rather than failing, so deletes can be retried.
+async fn delete(
+    Extension(st): Extension<AppState>,
+    AxumPath(vm_id): AxumPath<Uuid>,
+    Json(req): Json<DeleteSnapshotRequest>,
+) -> Result<Json<DeleteSnapshotResponse>, (StatusCode, String)> {
+    let base_dir = snapshot_base_dir(Path::new(&st.run_dir), &vm_id, &req.snapshot_id);
+    let freed_bytes = tokio::task::spawn_blocking(move || remove_dir_counting(&base_dir))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)?;
+    Ok(Json(DeleteSnapshotResponse { freed_bytes }))
+}
+
+/// Removes `dir` recursively and returns the on-disk bytes its files held.
+/// Symlinks are removed, not followed.
+fn remove_dir_counting(dir: &Path) -> std::io::Result<u64> {
+    fn on_disk_total(dir: &Path) -> std::io::Result<u64> {
+        let mut total = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                total += on_disk_total(&entry.path())?;
+            } else if meta.is_file() {
+                total += on_disk_bytes(&meta);
+            }
+        }
+        Ok(total)
+    }
+
+    match std::fs::symlink_metadata(dir) {
+        Ok(meta) if meta.is_dir() => {}
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "snapshot path is not a directory",
+            ))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    }
+    let freed = on_disk_total(dir)?;
+    std::fs::remove_dir_all(dir)?;
+    Ok(freed)
+}
+
+fn is_plain_absolute(path: &Path) -> bool {
+    path.is_absolute()
+        && path
+            .components()
+            .all(|c| !matches!(c, std::path::Component::ParentDir))
+}
+
+fn is_within(dir: &Path, path: &Path) -> bool {
+    path.starts_with(dir)
+        && !path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+}
+
+fn is_restore_file(mem_dir: &Path, path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    path.parent() == Some(mem_dir)
+        && name.starts_with(RESTORE_PREFIX)
+        && name.ends_with(".fc")
+        && !name.contains("..")
+}
+
+fn compress_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let partial = dst.with_extension("zst.part");
+    let input = std::io::BufReader::new(std::fs::File::open(src)?);
+    let output = std::fs::File::create(&partial)?;
+    zstd::stream::copy_encode(input, &output, ZSTD_LEVEL)?;
+    output.sync_all()?;
+    std::fs::rename(&partial, dst)
+}
+
+/// Zero pages are skipped rather than written, so guest memory that was never
+/// touched stays sparse on disk.
+fn decompress_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    let mut input = zstd::stream::Decoder::new(std::fs::File::open(src)?)?;
+    let mut output = std::fs::File::create(dst)?;
+    let mut buf = vec![0u8; 256 * PAGE_SIZE];
+    let mut len: u64 = 0;
+    loop {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match input.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        for page in buf[..filled].chunks(PAGE_SIZE) {
+            if page.iter().all(|b| *b == 0) {
+                output.seek(SeekFrom::Current(page.len() as i64))?;
+            } else {
+                output.write_all(page)?;
+            }
+        }
+        len += filled as u64;
+    }
+    output.set_len(len)?;
+    output.sync_all()
+}
+
 fn snapshot_base_dir(run_dir: &Path, vm_id: &Uuid, snapshot_id: &Uuid) -> PathBuf {
     run_dir
         .join("vms")
@@ -91,11 +387,19 @@ async fn canonicalize_dir(path: &Path) -> Result<PathBuf, (StatusCode, String)>
     fs::canonicalize(path).await.map_err(internal_error)
 }
 
+/// Bytes a file actually occupies on disk. Firecracker memory files are
+/// sparse (a diff snapshot only writes dirty pages), so the apparent length
+/// overstates storage use; block rounding can only overstate it the other way.
+fn on_disk_bytes(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.len().min(meta.blocks().saturating_mul(512))
+}
+
 async fn file_status(path: &Path) -> Result<(bool, Option<u64>), (StatusCode, String)> {
     match fs::metadata(path).await {
         Ok(meta) => {
             if meta.is_file() {
-                Ok((true, Some(meta.len())))
+                Ok((true, Some(on_disk_bytes(&meta))))
             } else {
                 Ok((true, None))
             }
@@ -131,6 +435,79 @@ mod tests {
         assert!(base.starts_with(Path::new("/srv/fc/vms")));
     }
 
+    #[test]
+    fn compressed_memory_round_trips_and_stays_sparse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mem = tmp.path().join("mem.fc");
+        let packed = tmp.path().join("mem.fc.zst");
+        let restored = tmp.path().join("restore-1.fc");
+
+        // 16 MiB of guest memory with a few dirty pages and an odd tail.
+        let file = std::fs::File::create(&mem).unwrap();
+        file.set_len(16 * 1024 * 1024 + 100).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&file, &[0xAB; 4096], 0).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&file, b"guest", 5 * 1024 * 1024 + 7).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&file, &[1; 100], 16 * 1024 * 1024).unwrap();
+        drop(file);
+
+        compress_file(&mem, &packed).unwrap();
+        assert!(std::fs::metadata(&packed).unwrap().len() < 64 * 1024);
+        assert!(!tmp.path().join("mem.fc.zst.part").exists());
+
+        decompress_file(&packed, &restored).unwrap();
+        assert_eq!(
+            std::fs::read(&restored).unwrap(),
+            std::fs::read(&mem).unwrap()
+        );
+        let meta = std::fs::metadata(&restored).unwrap();
+        assert!(
+            on_disk_bytes(&meta) < 1024 * 1024,
+            "{}",
+            on_disk_bytes(&meta)
+        );
+    }
+
+    #[test]
+    fn stat_only_checks_paths_in_the_snapshot_dir() {
+        let dir = Path::new("/srv/fc/vms/a/snapshots/b");
+        assert!(is_within(dir, &dir.join("snapshot.fc")));
+        assert!(is_within(dir, &dir.join("mem/mem.fc.zst")));
+        assert!(!is_within(dir, Path::new("/etc/shadow")));
+        assert!(!is_within(dir, &dir.join("../../c/snapshot.fc")));
+
+        assert!(is_plain_absolute(Path::new("/srv/images/rootfs.ext4")));
+        assert!(!is_plain_absolute(Path::new("images/rootfs.ext4")));
+        assert!(!is_plain_absolute(Path::new(
+            "/srv/images/../../etc/shadow"
+        )));
+    }
+
+    #[test]
+    fn only_restore_files_can_be_discarded() {
+        let dir = Path::new("/srv/fc/vms/a/snapshots/b/mem");
+        assert!(is_restore_file(dir, &dir.join("restore-123.fc")));
+        assert!(!is_restore_file(dir, &dir.join("mem.fc.zst")));
+        assert!(!is_restore_file(dir, &dir.join("mem.fc")));
+        assert!(!is_restore_file(
+            dir,
+            Path::new("/srv/fc/vms/a/snapshots/c/mem/restore-1.fc")
+        ));
+        assert!(!is_restore_file(dir, Path::new("restore-1.fc")));
+    }
+
+    #[test]
+    fn deleting_a_snapshot_dir_reports_the_bytes_it_held() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("snap");
+        std::fs::create_dir_all(dir.join("mem")).unwrap();
+        std::fs::write(dir.join("snapshot.fc"), vec![1u8; 64 * 1024]).unwrap();
+        std::fs::write(dir.join("mem/mem.fc"), vec![2u8; 128 * 1024]).unwrap();
+
+        assert_eq!(remove_dir_counting(&dir).unwrap(), 192 * 1024);
+        assert!(!dir.exists());
+        assert_eq!(remove_dir_counting(&dir).unwrap(), 0);
+    }
+
     #[tokio::test]
     async fn file_status_reports_sizes() {
         let tmp = tempfile::tempdir().unwrap();
@@ -140,4 +517,27 @@ mod tests {
         tokio::fs::write(&file_path, &[1u8; 8]).await.unwrap();
         assert_eq!(file_status(&file_path).await.unwrap(), (true, Some(8)));
     }
+
+    #[tokio::test]
+    async fn file_status_counts_allocated_bytes_of_sparse_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let snapshot = tmp.path().join("snapshot.fc");
+        tokio::fs::write(&snapshot, vec![7u8; 64 * 1024])
+            .await
+            .unwrap();
+        assert_eq!(
+            file_status(&snapshot).await.unwrap(),
+            (true, Some(64 * 1024))
+        );
+
+        // A 64 MiB memory image with a single dirty page written.
+        let mem = tmp.path().join("mem.fc");
+        let file = std::fs::File::create(&mem).unwrap();
+        file.set_len(64 * 1024 * 1024).unwrap();
+        std::os::unix::fs::FileExt::write_all_at(&file, &[1u8; 4096], 0).unwrap();
+        let (_, mem_size) = file_status(&mem).await.unwrap();
+        let mem_size = mem_size.unwrap();
+        assert!(mem_size >= 4096, "{mem_size}");
+        assert!(mem_size < 64 * 1024 * 1024, "{mem_size}");
+    }
 }
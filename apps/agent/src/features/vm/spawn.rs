@@ -11,6 +11,32 @@ use tokio::{fs, io::AsyncWriteExt};
 struct SpawnReq {
     sock: String,
     log_path: String,
+    /// Scope `CPUQuota=`, in percent of one CPU.
+    #[serde(default)]
+    cpu_quota: Option<u32>,
+    /// Scope `IOWeight=`, 1-10000.
+    #[serde(default)]
+    io_weight: Option<u16>,
+}
+
+impl SpawnReq {
+    /// systemd-run properties for the requested limits.
+    fn scope_properties(&self) -> Result<Vec<String>, String> {
+        let mut properties = Vec::new();
+        if let Some(quota) = self.cpu_quota {
+            if quota == 0 {
+                return Err("cpu_quota must be greater than 0".into());
+            }
+            properties.push(format!("CPUQuota={quota}%"));
+        }
+        if let Some(weight) = self.io_weight {
+            if !(1..=10000).contains(&weight) {
+                return Err("io_weight must be between 1 and 10000".into());
+            }
+            properties.push(format!("IOWeight={weight}"));
+        }
+        Ok(properties)
+    }
 }
 
 pub fn router() -> Router {
@@ -22,6 +48,9 @@ async fn spawn_fc(
     Path(id): Path<String>,
     Json(req): Json<SpawnReq>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let properties = req
+        .scope_properties()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
     if let Some(p) = std::path::Path::new(&req.log_path).parent() {
         fs::create_dir_all(p).await.map_err(int)?;
     }
@@ -57,7 +86,7 @@ async fn spawn_fc(
 
     // Attempt to spawn. If systemd-run reports failure but the socket appears,
     // consider it success to avoid flapping on duplicate unit names.
-    if let Err(err) = systemd::spawn_fc_scope(&unit, &req.sock).await {
+    if let Err(err) = systemd::spawn_fc_scope(&unit, &req.sock, &properties).await {
         // Brief grace period to see if the socket got created anyway
         for _ in 0..400 {
             if std::path::Path::new(&req.sock).exists() {
@@ -66,6 +95,14 @@ async fn spawn_fc(
             tokio::time::sleep(std::time::Duration::from_millis(50)).await;
         }
         if !std::path::Path::new(&req.sock).exists() {
+            // Launching outside the scope would silently drop the limits
+            if !properties.is_empty() {
+                tracing::warn!(vm_id = %id, error = %err, "systemd-run failed; not launching firecracker without its scope limits");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("systemd-run failed and scope limits were requested: {err}"),
+                ));
+            }
             // Fallback: try launching firecracker directly (without systemd)
             // 1) try without sudo
             let direct = Command::new("firecracker")
@@ -158,6 +195,23 @@ mod tests {
         let req: SpawnReq = serde_json::from_str(json).expect("valid SpawnReq");
         assert_eq!(req.sock, "/tmp/fc.sock");
         assert_eq!(req.log_path, "/var/log/fc.log");
+        assert_eq!(req.scope_properties().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn scope_limits_become_systemd_properties() {
+        let json = r#"{"sock":"/s","log_path":"/l","cpu_quota":150,"io_weight":500}"#;
+        let req: SpawnReq = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            req.scope_properties().unwrap(),
+            vec!["CPUQuota=150%".to_string(), "IOWeight=500".to_string()]
+        );
+        let json = r#"{"sock":"/s","log_path":"/l","io_weight":20000}"#;
+        let req: SpawnReq = serde_json::from_str(json).unwrap();
+        assert!(req.scope_properties().is_err());
+        let json = r#"{"sock":"/s","log_path":"/l","cpu_quota":0}"#;
+        let req: SpawnReq = serde_json::from_str(json).unwrap();
+        assert!(req.scope_properties().is_err());
     }
 
     #[test]
@@ -1,6 +1,7 @@
 use crate::core::{net, systemd};
 use crate::AppState;
 use axum::{extract::Extension, http::StatusCode, routing::post, Json, Router};
+use nexus_types::{ArtifactTeardown, StopVmReport};
 use serde::{Deserialize, Serialize};
 #[derive(Deserialize, Serialize)]
 struct StopReq {
@@ -9,34 +10,72 @@ struct StopReq {
     fc_unit: String,
     #[serde(default)]
     storage_path: Option<String>,
+    /// Host side of the guest vsock device; Firecracker doesn't unlink it
+    #[serde(default)]
+    vsock_uds: Option<String>,
 }
 
 pub fn router() -> Router {
     Router::new().route("/:id/stop", post(stop_vm))
 }
 
+/// Tear down a VM's host artifacts and report, per artifact, whether it was
+/// removed, already absent, or left behind. Always 200 so the manager can
+/// decide which failures matter.
 async fn stop_vm(
     Extension(_st): Extension<AppState>,
     Json(req): Json<StopReq>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if let Err(e) = systemd::stop_unit(&req.fc_unit).await {
-        tracing::warn!(error = ?e, "failed to stop systemd unit");
+) -> Result<Json<StopVmReport>, (StatusCode, String)> {
+    let scope = systemd::stop_unit(&req.fc_unit)
+        .await
+        .map_err(|e| e.to_string());
+    let tap = net::delete_tap(&req.tap).await.map_err(|e| e.to_string());
+    let socket = remove_path(tokio::fs::remove_file(&req.sock).await);
+    // Only ever beside the API socket, so this can't unlink arbitrary files
+    let vsock_uds = req.vsock_uds.as_deref().filter(|uds| {
+        std::path::Path::new(uds).parent() == std::path::Path::new(&req.sock).parent()
+    });
+    if let Some(uds) = vsock_uds {
+        if let Err(error) = remove_path(tokio::fs::remove_file(uds).await) {
+            tracing::warn!(unit = %req.fc_unit, uds, error, "failed to remove vsock socket on stop");
+        }
     }
-    if let Err(e) = net::delete_tap(&req.tap).await {
-        tracing::warn!(error = ?e, "failed to delete tap device");
+    let storage = match req.storage_path {
+        Some(path) => Some(remove_path(tokio::fs::remove_dir_all(&path).await)),
+        None => None,
+    };
+
+    let report = StopVmReport::new(
+        ArtifactTeardown::from_result(scope),
+        ArtifactTeardown::from_result(tap),
+        ArtifactTeardown::from_result(socket),
+        storage.map(ArtifactTeardown::from_result),
+    );
+    for (artifact, result) in report.failures() {
+        tracing::warn!(
+            artifact,
+            unit = %req.fc_unit,
+            tap = %req.tap,
+            error = result.error.as_deref().unwrap_or_default(),
+            "failed to remove vm artifact on stop"
+        );
     }
-    let _ = tokio::fs::remove_file(&req.sock).await;
-    if let Some(path) = req.storage_path {
-        if let Err(e) = tokio::fs::remove_dir_all(&path).await {
-            tracing::warn!(error = ?e, path = %path, "failed to cleanup storage directory");
-        }
+    Ok(Json(report))
+}
+
+/// Map a filesystem removal onto removed / already-absent / failed.
+fn remove_path(result: std::io::Result<()>) -> Result<bool, String> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.to_string()),
     }
-    Ok(Json(serde_json::json!({"ok": true})))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nexus_types::TeardownOutcome;
 
     #[test]
     fn stop_req_round_trips() {
@@ -64,5 +103,43 @@ mod tests {
         let payload = r#"{"tap":"tap-x","sock":"/x.sock","fc_unit":"fc-x.scope"}"#;
         let req: StopReq = serde_json::from_str(payload).expect("optional storage_path");
         assert!(req.storage_path.is_none());
+        assert!(req.vsock_uds.is_none());
+    }
+
+    #[test]
+    fn remove_path_distinguishes_absent_from_failed() {
+        assert_eq!(remove_path(Ok(())), Ok(true));
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(remove_path(Err(missing)), Ok(false));
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(remove_path(Err(denied)).is_err());
+    }
+
+    #[tokio::test]
+    async fn stop_report_flags_leaked_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let sock = dir.path().join("fc.sock");
+        std::fs::write(&sock, b"").unwrap();
+
+        let removed = remove_path(tokio::fs::remove_file(&sock).await);
+        let absent = remove_path(tokio::fs::remove_file(&sock).await);
+        let report = StopVmReport::new(
+            ArtifactTeardown::from_result(absent),
+            ArtifactTeardown::from_result(Err("device busy".into())),
+            ArtifactTeardown::from_result(removed),
+            None,
+        );
+
+        assert!(!report.ok);
+        assert_eq!(report.scope.outcome, TeardownOutcome::Absent);
+        assert_eq!(report.socket.outcome, TeardownOutcome::Removed);
+        let failures = report.failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "tap");
+        assert_eq!(failures[0].1.error.as_deref(), Some("device busy"));
+
+        let encoded = serde_json::to_value(&report).unwrap();
+        assert_eq!(encoded["tap"]["outcome"], "failed");
+        assert!(encoded.get("storage").is_none());
     }
 }
@@ -1,8 +1,16 @@
-use axum::extract::Path;
-use axum::{http::StatusCode, routing::put, Extension, Json, Router};
+use anyhow::{bail, Context};
+use axum::extract::{Path, Query};
+use axum::{
+    http::StatusCode,
+    routing::{get, put},
+    Extension, Json, Router,
+};
 use serde::{Deserialize, Serialize};
 use std::path::Path as StdPath;
+use std::time::Duration;
 use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
 
 use crate::AppState;
 
@@ -21,8 +29,109 @@ struct VsockResp {
     vsock_id: Option<String>,
 }
 
+/// Where the guest agent listens unless the caller says otherwise.
+const DEFAULT_GUEST_PORT: u32 = 9000;
+const GUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Guest metrics are a few hundred bytes; anything past this is not them.
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+#[derive(Deserialize)]
+struct GuestMetricsQuery {
+    uds: String,
+    #[serde(default)]
+    port: Option<u32>,
+}
+
 pub fn router() -> Router {
-    Router::new().route("/:id/vsock", put(configure_vsock))
+    Router::new()
+        .route("/:id/vsock", put(configure_vsock))
+        .route("/:id/vsock/metrics", get(guest_metrics))
+        .route("/:id/vsock/addresses", get(guest_addresses))
+}
+
+/// Relay the guest agent's `GET /metrics` over the VM's vsock device.
+async fn guest_metrics(
+    Extension(st): Extension<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<GuestMetricsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    relay_guest_get(&st, &id, &q, "/metrics").await
+}
+
+/// Relay the guest agent's `GET /addresses` (its guest IP report) over the
+/// VM's vsock device.
+async fn guest_addresses(
+    Extension(st): Extension<AppState>,
+    Path(id): Path<String>,
+    Query(q): Query<GuestMetricsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    relay_guest_get(&st, &id, &q, "/addresses").await
+}
+
+async fn relay_guest_get(
+    st: &AppState,
+    id: &str,
+    q: &GuestMetricsQuery,
+    path: &str,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let uds = super::proxy::resolve_socket_path(st, id, &q.uds).await?;
+    let port = q.port.unwrap_or(DEFAULT_GUEST_PORT);
+    let body = tokio::time::timeout(GUEST_TIMEOUT, guest_http_get(&uds, port, path))
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                "guest agent did not answer over vsock".to_string(),
+            )
+        })?
+        .map_err(|err| (StatusCode::BAD_GATEWAY, format!("{err:#}")))?;
+    let value = serde_json::from_slice(&body)
+        .map_err(|err| (StatusCode::BAD_GATEWAY, format!("guest {path}: {err}")))?;
+    Ok(Json(value))
+}
+
+/// One HTTP/1.0 GET to a guest port through Firecracker's vsock muxer: the
+/// host side writes `CONNECT <port>` and gets `OK <host port>` back before
+/// the stream is handed to the guest.
+async fn guest_http_get(uds: &str, port: u32, path: &str) -> anyhow::Result<Vec<u8>> {
+    let stream = UnixStream::connect(uds)
+        .await
+        .context("connecting to vsock socket")?;
+    let mut reader = BufReader::new(stream);
+    reader
+        .get_mut()
+        .write_all(format!("CONNECT {port}\n").as_bytes())
+        .await?;
+    let mut ack = String::new();
+    reader.read_line(&mut ack).await?;
+    if !ack.starts_with("OK ") {
+        bail!("vsock connect to port {port} refused: {:?}", ack.trim());
+    }
+
+    reader
+        .get_mut()
+        .write_all(format!("GET {path} HTTP/1.0\r\nHost: guest\r\n\r\n").as_bytes())
+        .await?;
+    let mut raw = Vec::new();
+    reader
+        .take(MAX_RESPONSE_BYTES)
+        .read_to_end(&mut raw)
+        .await?;
+    http_body(&raw)
+}
+
+/// The body of a 200 response, or the status line as an error.
+fn http_body(raw: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let split = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .context("guest response has no header terminator")?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        bail!("guest agent answered {status:?}");
+    }
+    Ok(raw[split + 4..].to_vec())
 }
 
 async fn configure_vsock(
@@ -71,3 +180,18 @@ async fn configure_vsock(
 fn internal_error<E: std::fmt::Display>(err: E) -> (StatusCode, String) {
     (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_body_takes_only_successful_responses() {
+        let raw = b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(http_body(raw).unwrap(), b"{}");
+
+        let err = http_body(b"HTTP/1.0 404 Not Found\r\n\r\n").unwrap_err();
+        assert!(err.to_string().contains("404"));
+        assert!(http_body(b"HTTP/1.0 200 OK\r\n").is_err());
+    }
+}
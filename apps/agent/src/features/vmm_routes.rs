@@ -61,15 +61,6 @@ pub struct MetricsQuery {
     pub mem_mib: u32,
 }
 
-fn read_cgroup_usage_usec(cpu_stat: &str) -> Option<u64> {
-    for line in cpu_stat.lines() {
-        if let Some(rest) = line.strip_prefix("usage_usec ") {
-            return rest.trim().parse().ok();
-        }
-    }
-    None
-}
-
 /// Host-side metrics for a QEMU VM, read from its systemd unit's cgroup
 /// (`qemu-<id>.service`). QEMU guests have no in-VM guest-agent (especially
 /// Windows), so CPU% and memory are observed from the host — exactly what
@@ -109,7 +100,7 @@ async fn qemu_metrics(
         tokio::fs::read_to_string(format!("{base}/cpu.stat"))
             .await
             .ok()
-            .and_then(|s| read_cgroup_usage_usec(&s))
+            .and_then(|s| crate::core::host::read_cgroup_usage_usec(&s))
     };
     let cpu_pct = match read_usage().await {
         Some(u1) => {
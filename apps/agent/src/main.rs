@@ -3,12 +3,16 @@ mod features;
 mod vmm;
 
 use serde_json::json;
+use std::future::IntoFuture as _;
 use tracing::{info, warn};
 
 #[derive(Clone)]
 pub struct AppState {
     pub run_dir: String,
+    /// The default bridge, used when the manager names none.
     pub bridge: String,
+    /// `FC_BRIDGES` as `(name, device)`.
+    pub named_bridges: Vec<(String, String)>,
     pub storage_registry: features::storage::registry::HostBackendRegistry,
     pub nfs_config: Option<features::storage::nfs::NfsHostConfig>,
     pub vmm_registry: vmm::VmmRegistry,
@@ -111,9 +115,12 @@ async fn main() -> anyhow::Result<()> {
         .collect();
     info!(?installed_kinds, "vmm registry probed");
 
+    let bridges = core::bridges::HostBridges::from_env()?;
+    info!(default = %bridges.default, named = ?bridges.named, "host bridges");
     let state = AppState {
         run_dir: std::env::var("FC_RUN_DIR").unwrap_or_else(|_| "/srv/fc".into()),
-        bridge: std::env::var("FC_BRIDGE").unwrap_or_else(|_| "fcbr0".into()),
+        bridge: bridges.default,
+        named_bridges: bridges.named,
         storage_registry,
         nfs_config,
         vmm_registry,
@@ -122,12 +129,15 @@ async fn main() -> anyhow::Result<()> {
     let heartbeat_state = state.clone();
     let manager_base_clone = manager_base.clone();
     let advertise_addr_clone = advertise_addr.clone();
-    tokio::spawn(async move {
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let heartbeat_shutdown = shutdown_rx.clone();
+    let heartbeat = tokio::spawn(async move {
         if let Err(err) = register_and_heartbeat(
             manager_base_clone,
             host_name,
             advertise_addr_clone,
             heartbeat_state,
+            heartbeat_shutdown,
         )
         .await
         {
@@ -138,15 +148,42 @@ async fn main() -> anyhow::Result<()> {
     let app = features::router(state);
     info!(%bind, "agent listening");
     let listener = tokio::net::TcpListener::bind(&bind).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    let mut server_shutdown = shutdown_rx;
+    let server = axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(
+            async move { core::shutdown::requested(&mut server_shutdown).await },
+        )
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result.map_err(Into::into),
+        _ = core::shutdown::signal() => {}
+    }
+
+    // Stops accepting connections and has the heartbeat task tell the
+    // manager this host is draining
+    let deadline = tokio::time::Instant::now() + core::shutdown::timeout_from_env();
+    let _ = shutdown_tx.send(true);
+    if tokio::time::timeout_at(deadline, heartbeat).await.is_err() {
+        warn!("manager heartbeat task did not stop in time");
+    }
+    match tokio::time::timeout_at(deadline, server).await {
+        Ok(result) => result?,
+        Err(_) => warn!("shutdown timeout reached with requests still in flight"),
+    }
+    info!("agent stopped");
     Ok(())
 }
 
+/// Registers with the manager and heartbeats until `shutdown` fires, retrying
+/// registration whenever the heartbeat loop gives up.
 async fn register_and_heartbeat(
     manager_base: String,
     name: String,
     addr: String,
     state: AppState,
+    mut shutdown: tokio::sync::watch::Receiver<bool>,
 ) -> anyhow::Result<()> {
     use nexus_types::{RegisterHostRequest, RegisterHostResponse};
     use tokio::time::Duration;
@@ -155,35 +192,28 @@ async fn register_and_heartbeat(
 
     loop {
         let capabilities = gather_capabilities(&state);
-        let supported_backend_kinds: Vec<String> = state
-            .storage_registry
-            .supported_kinds()
-            .iter()
-            .map(|k| k.as_db_str().to_string())
-            .collect();
-        let vmm_kinds_installed: Vec<String> = state
-            .vmm_registry
-            .installed_kinds()
-            .iter()
-            .map(|k| k.as_str().to_string())
-            .collect();
-        match client
+        let registration = client
             .post(format!("{manager_base}/v1/hosts/register"))
             .json(&RegisterHostRequest {
                 name: name.clone(),
                 addr: addr.clone(),
                 capabilities,
-                supported_backend_kinds: Some(supported_backend_kinds),
-                vmm_kinds_installed: Some(vmm_kinds_installed),
+                supported_backend_kinds: Some(supported_backend_kinds(&state)),
+                vmm_kinds_installed: Some(vmm_kinds_installed(&state)),
             })
-            .send()
-            .await
-        {
+            .send();
+        let response = tokio::select! {
+            response = registration => response,
+            _ = core::shutdown::requested(&mut shutdown) => return Ok(()),
+        };
+        match response {
             Ok(response) => match response.error_for_status() {
                 Ok(success) => match success.json::<RegisterHostResponse>().await {
                     Ok(body) => {
                         info!(host_id = %body.id, "registered host with manager");
-                        heartbeat_loop(&client, &manager_base, body.id, &state).await;
+                        heartbeat_loop(&client, &manager_base, body.id, &state, &mut shutdown)
+                            .await;
+                        return Ok(());
                     }
                     Err(err) => {
                         warn!(?err, "failed to parse register response");
@@ -197,112 +227,103 @@ async fn register_and_heartbeat(
                 warn!(?err, "error registering host");
             }
         }
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            _ = core::shutdown::requested(&mut shutdown) => return Ok(()),
+        }
     }
 }
 
+/// Heartbeats every 15s. Once `shutdown` fires it sends one last heartbeat
+/// with `draining` set, so the manager stops placing VMs here, and returns.
 async fn heartbeat_loop(
     client: &reqwest::Client,
     manager_base: &str,
     host_id: uuid::Uuid,
     state: &AppState,
+    shutdown: &mut tokio::sync::watch::Receiver<bool>,
 ) {
     use nexus_types::HostHeartbeatRequest;
     use tokio::time::{sleep, Duration};
 
+    let url = format!("{manager_base}/v1/hosts/{host_id}/heartbeat");
+    let request = |draining: bool| HostHeartbeatRequest {
+        capabilities: Some(gather_capabilities(state)),
+        supported_backend_kinds: Some(supported_backend_kinds(state)),
+        vmm_kinds_installed: Some(vmm_kinds_installed(state)),
+        draining,
+    };
+
     loop {
-        let capabilities = gather_capabilities(state);
-        let supported_backend_kinds: Vec<String> = state
-            .storage_registry
-            .supported_kinds()
-            .iter()
-            .map(|k| k.as_db_str().to_string())
-            .collect();
-        let vmm_kinds_installed: Vec<String> = state
-            .vmm_registry
-            .installed_kinds()
-            .iter()
-            .map(|k| k.as_str().to_string())
-            .collect();
-        match client
-            .post(format!("{manager_base}/v1/hosts/{host_id}/heartbeat"))
-            .json(&HostHeartbeatRequest {
-                capabilities: Some(capabilities),
-                supported_backend_kinds: Some(supported_backend_kinds),
-                vmm_kinds_installed: Some(vmm_kinds_installed),
-            })
-            .send()
-            .await
-        {
-            Ok(response) => {
+        let beat = client.post(&url).json(&request(false)).send();
+        let response = tokio::select! {
+            response = beat => Some(response),
+            _ = core::shutdown::requested(shutdown) => None,
+        };
+        match response {
+            Some(Ok(response)) => {
                 if let Err(err) = response.error_for_status() {
                     warn!(?err, "heartbeat rejected by manager");
                 }
             }
-            Err(err) => {
+            Some(Err(err)) => {
                 warn!(?err, "failed to send heartbeat");
             }
+            None => break,
         }
-        sleep(Duration::from_secs(15)).await;
+        tokio::select! {
+            _ = sleep(Duration::from_secs(15)) => {}
+            _ = core::shutdown::requested(shutdown) => break,
+        }
+    }
+
+    let result = client
+        .post(&url)
+        .json(&request(true))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => info!(%host_id, "told manager this host is draining"),
+        Err(err) => warn!(?err, "failed to send draining heartbeat"),
     }
 }
 
+fn supported_backend_kinds(state: &AppState) -> Vec<String> {
+    state
+        .storage_registry
+        .supported_kinds()
+        .iter()
+        .map(|k| k.as_db_str().to_string())
+        .collect()
+}
+
+fn vmm_kinds_installed(state: &AppState) -> Vec<String> {
+    state
+        .vmm_registry
+        .installed_kinds()
+        .iter()
+        .map(|k| k.as_str().to_string())
+        .collect()
+}
+
 fn gather_capabilities(state: &AppState) -> serde_json::Value {
-    let (total_memory_mb, _free_memory_mb) = get_memory_info();
+    let (total_memory_mb, free_memory_mb) = core::host::get_memory_info();
     let (total_disk_gb, used_disk_gb) = get_disk_info(&state.run_dir);
 
     json!({
         "bridge": state.bridge.clone(),
+        "bridges": core::bridges::capability(&state.named_bridges),
         "run_dir": state.run_dir.clone(),
         "cpus": num_cpus::get(),
         "total_memory_mb": total_memory_mb,
+        "free_memory_mb": free_memory_mb,
         "total_disk_gb": total_disk_gb,
         "used_disk_gb": used_disk_gb,
     })
 }
 
-fn get_memory_info() -> (i64, i64) {
-    // Read /proc/meminfo to get memory statistics
-    if let Ok(content) = std::fs::read_to_string("/proc/meminfo") {
-        let mut total_kb = 0;
-        let mut free_kb = 0;
-        let mut available_kb = 0;
-
-        for line in content.lines() {
-            if line.starts_with("MemTotal:") {
-                total_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-            } else if line.starts_with("MemFree:") {
-                free_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-            } else if line.starts_with("MemAvailable:") {
-                available_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-            }
-        }
-
-        let total_mb = total_kb / 1024;
-        let free_mb = if available_kb > 0 {
-            available_kb / 1024
-        } else {
-            free_kb / 1024
-        };
-
-        return (total_mb, free_mb);
-    }
-
-    (0, 0)
-}
-
 fn get_disk_info(path: &str) -> (i64, i64) {
     // Use statvfs to get disk statistics for the given path
     if let Ok(_metadata) = std::fs::metadata(path) {
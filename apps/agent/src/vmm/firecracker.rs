@@ -109,6 +109,7 @@ impl VmmDriver for FirecrackerDriver {
             api_sock
                 .to_str()
                 .ok_or_else(|| VmmError::Other(anyhow!("api-sock path is not valid UTF-8")))?,
+            &[],
         )
         .await
         .map_err(VmmError::Other)?;
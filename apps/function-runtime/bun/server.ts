@@ -15,6 +15,7 @@
  * Endpoints:
  *   GET  /health       - Health check
  *   POST /invoke       - Execute the function
+ *   POST /invoke/raw   - Execute the function, returning its result as the body
  *   POST /write-code   - Write and reload function code
  *   POST /reload       - Hot-reload function code
  */
@@ -41,6 +42,7 @@ interface HealthResponse {
   codeLoaded: boolean;
   error: string | null;
   uptime: number;
+  capabilities: string[];
 }
 
 interface InvokeResponse {
@@ -52,10 +54,26 @@ interface InvokeResponse {
   logs: string[];
 }
 
+interface FunctionVersion {
+  number: number;
+  code: string;
+  handler: string;
+}
+
+interface InvokeBody {
+  event?: unknown;
+  env?: Record<string, string>;
+  version?: FunctionVersion;
+  timeout_ms?: number;
+}
+
 let handler: HandlerFunction | null = null;
 let loadError: string | null = null;
 const startTime = Date.now();
 
+// Handlers of published versions, loaded once per version number
+const versionHandlers = new Map<number, HandlerFunction>();
+
 /**
  * Load (or reload) the function code
  */
@@ -91,6 +109,36 @@ async function loadFunction(): Promise<boolean> {
   }
 }
 
+/**
+ * Return the handler for a published version sent with an invocation, or the
+ * hot-reloadable $LATEST handler when there is none. Versions are immutable,
+ * so each is written and imported once.
+ */
+async function loadVersion(version: FunctionVersion | undefined): Promise<HandlerFunction> {
+  if (!version) {
+    if (!handler) {
+      throw new Error(loadError || "Function not loaded");
+    }
+    return handler;
+  }
+  let fn = versionHandlers.get(version.number);
+  if (!fn) {
+    const dir = FUNCTION_CODE_PATH.slice(0, FUNCTION_CODE_PATH.lastIndexOf("/"));
+    const file = `${dir}/versions/code-${version.number}.ts`;
+    const code = version.code.includes("export ")
+      ? version.code
+      : `${version.code}\n\nexport { ${version.handler} };`;
+    await Bun.write(file, code);
+    const module = await import(file);
+    fn = module[version.handler] || module.default;
+    if (typeof fn !== "function") {
+      throw new Error(`Handler "${version.handler}" of version ${version.number} is not a function`);
+    }
+    versionHandlers.set(version.number, fn);
+  }
+  return fn;
+}
+
 /**
  * Generate a unique request ID
  */
@@ -101,16 +149,113 @@ function generateRequestId(): string {
 /**
  * Create execution context for the function
  */
-function createContext(requestId: string): ExecutionContext {
+function createContext(requestId: string, timeoutMs?: number): ExecutionContext {
   return {
     functionName: FUNCTION_HANDLER,
     requestId,
     invokedAt: new Date().toISOString(),
     memoryLimitMB: 128, // Default, can be configured
-    timeoutMs: 30000,   // 30 second default timeout
+    // The invoked version's (or the draft's) timeout, sent by the manager
+    timeoutMs: timeoutMs || 30000,
   };
 }
 
+/**
+ * Run the handler, failing once the context's timeout has passed.
+ */
+async function runHandler(fn: HandlerFunction, event: unknown, context: ExecutionContext): Promise<unknown> {
+  let timer: ReturnType<typeof setTimeout> | undefined;
+  try {
+    return await Promise.race([
+      Promise.resolve(fn(event, context)),
+      new Promise((_, reject) => {
+        timer = setTimeout(
+          () => reject(new Error(`Function timed out after ${context.timeoutMs}ms`)),
+          context.timeoutMs
+        );
+      }),
+    ]);
+  } finally {
+    clearTimeout(timer);
+  }
+}
+
+// Env the runtime started with, used to put back anything an invocation
+// overrode
+const baseEnv: Record<string, string | undefined> = { ...process.env };
+let appliedEnvKeys: string[] = [];
+
+/**
+ * Set the env vars the manager sends with each invocation (plain and
+ * decrypted secret ones) before the handler runs.
+ */
+function applyEnv(env: Record<string, string> | undefined): void {
+  // Undo the previous invocation's vars first so a removed secret doesn't
+  // linger in process.env
+  for (const name of appliedEnvKeys) {
+    if (name in baseEnv) {
+      process.env[name] = baseEnv[name];
+    } else {
+      delete process.env[name];
+    }
+  }
+  appliedEnvKeys = [];
+  if (env && typeof env === "object") {
+    Object.assign(process.env, env);
+    appliedEnvKeys = Object.keys(env);
+  }
+}
+
+/**
+ * Split a request body into event, version and env. Only an explicit
+ * `event` key is treated as the envelope; a bare body is the event itself,
+ * minus `env` and `version`, so secrets never end up in the event or logs.
+ */
+function parseInvocation(body: unknown): InvokeBody {
+  if (!body || typeof body !== "object" || Array.isArray(body)) {
+    return { event: body };
+  }
+  const { env, version, timeout_ms, ...rest } = body as InvokeBody;
+  const event = "event" in body ? (body as InvokeBody).event : rest;
+  return { event, version, env, timeout_ms };
+}
+
+/**
+ * Turn a handler result into the response for /invoke/raw. A `Response` is
+ * returned as-is and `{ statusCode, headers, body, isBase64Encoded }` is
+ * honoured; bytes are sent as octet-stream, strings as text and anything
+ * else as JSON.
+ */
+function toRawResponse(result: unknown): Response {
+  if (result instanceof Response) {
+    return result;
+  }
+  if (result instanceof Uint8Array || result instanceof ArrayBuffer) {
+    return new Response(result, { headers: { "Content-Type": "application/octet-stream" } });
+  }
+  if (typeof result === "string") {
+    return new Response(result, { headers: { "Content-Type": "text/plain; charset=utf-8" } });
+  }
+  if (result && typeof result === "object" && "body" in result) {
+    const { statusCode, headers, body, isBase64Encoded } = result as {
+      statusCode?: number;
+      headers?: Record<string, string>;
+      body: unknown;
+      isBase64Encoded?: boolean;
+    };
+    const inner = isBase64Encoded && typeof body === "string"
+      ? toRawResponse(Buffer.from(body, "base64"))
+      : toRawResponse(body);
+    const contentType = headers?.["Content-Type"] ?? headers?.["content-type"]
+      ?? inner.headers.get("Content-Type") ?? "application/octet-stream";
+    return new Response(inner.body, {
+      status: statusCode ?? 200,
+      headers: { "Content-Type": contentType },
+    });
+  }
+  return Response.json(result ?? null);
+}
+
 /**
  * Bun HTTP server
  */
@@ -145,6 +290,8 @@ const server = Bun.serve({
         codeLoaded: handler !== null,
         error: loadError,
         uptime: Math.floor((Date.now() - startTime) / 1000),
+        // What the manager may send with an invocation
+        capabilities: ["versions", "timeout"],
       };
 
       return Response.json(response, {
@@ -202,20 +349,34 @@ const server = Bun.serve({
       );
     }
 
-    // Invoke endpoint
-    if (path === "/invoke" && method === "POST") {
-      if (!handler) {
+    // Raw invoke endpoint: the handler's result is the response body
+    if (path === "/invoke/raw" && method === "POST") {
+      try {
+        let event: unknown = {};
+        let version: FunctionVersion | undefined;
+        let env: Record<string, string> | undefined;
+        let timeoutMs: number | undefined;
+        const contentType = req.headers.get("content-type") || "";
+        if (contentType.includes("application/json")) {
+          ({ event, version, env, timeout_ms: timeoutMs } = parseInvocation(await req.json()));
+        }
+        applyEnv(env);
+
+        const fn = await loadVersion(version);
+        const context = createContext(generateRequestId(), timeoutMs);
+        return toRawResponse(await runHandler(fn, event, context));
+      } catch (error) {
+        const errorMessage = error instanceof Error ? error.message : String(error);
+        console.error(`[Runtime] Function failed: ${errorMessage}`);
         return Response.json(
-          {
-            status: "error",
-            error: loadError || "Function not loaded",
-            duration_ms: 0,
-            logs: [],
-          } as InvokeResponse,
+          { status: "error", error: errorMessage },
           { status: 500, headers: corsHeaders }
         );
       }
+    }
 
+    // Invoke endpoint
+    if (path === "/invoke" && method === "POST") {
       const startTime = performance.now();
       const logs: string[] = [];
       const requestId = generateRequestId();
@@ -242,26 +403,25 @@ const server = Bun.serve({
       try {
         // Parse event from request
         let event: unknown = {};
+        let version: FunctionVersion | undefined;
+        let env: Record<string, string> | undefined;
+        let timeoutMs: number | undefined;
         const contentType = req.headers.get("content-type") || "";
         
         if (contentType.includes("application/json")) {
-          const body = await req.json() as { event?: unknown };
-          event = body.event ?? body;
+          ({ event, version, env, timeout_ms: timeoutMs } = parseInvocation(await req.json()));
         }
+        applyEnv(env);
+
+        const fn = await loadVersion(version);
 
         console.info(`Invoking with requestId: ${requestId}`);
 
         // Create execution context
-        const context = createContext(requestId);
+        const context = createContext(requestId, timeoutMs);
 
         // Invoke the handler with timeout
-        const timeoutMs = context.timeoutMs;
-        const result = await Promise.race([
-          Promise.resolve(handler(event, context)),
-          new Promise((_, reject) =>
-            setTimeout(() => reject(new Error(`Function timed out after ${timeoutMs}ms`)), timeoutMs)
-          ),
-        ]);
+        const result = await runHandler(fn, event, context);
 
         // Restore console
         console.log = originalLog;
@@ -315,6 +475,7 @@ const server = Bun.serve({
         endpoints: {
           "GET /health": "Health check",
           "POST /invoke": "Execute function",
+          "POST /invoke/raw": "Execute function, returning the raw result",
           "POST /write-code": "Write and reload function code",
           "POST /reload": "Reload function code",
         },
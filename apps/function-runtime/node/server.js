@@ -9,6 +9,7 @@
  * Endpoints:
  *   GET  /health       - Health check
  *   POST /invoke       - Execute the function
+ *   POST /invoke/raw   - Execute the function, returning its result as the body
  *   POST /reload       - Hot-reload function code
  */
 
@@ -23,6 +24,9 @@ const FUNCTION_HANDLER = process.env.FUNCTION_HANDLER || 'handler';
 let handler = null;
 let loadError = null;
 
+// Handlers of published versions, loaded once per version number
+const versionHandlers = new Map();
+
 /**
  * Load (or reload) the function code
  */
@@ -54,6 +58,120 @@ function loadFunction() {
   }
 }
 
+/**
+ * Load the handler of a published version sent with an invocation
+ * (`{ number, code, handler }`). Versions are immutable, so each is written
+ * and required once; `$LATEST` invocations carry no version and use the
+ * hot-reloadable `handler`.
+ */
+function loadVersion(version) {
+  if (!version) {
+    if (!handler) {
+      throw new Error(loadError || 'Function not loaded');
+    }
+    return handler;
+  }
+  if (!versionHandlers.has(version.number)) {
+    const dir = path.join(path.dirname(FUNCTION_CODE_PATH), 'versions');
+    const file = path.join(dir, `code-${version.number}.js`);
+    fs.mkdirSync(dir, { recursive: true });
+    fs.writeFileSync(file, `${version.code}\n\nmodule.exports = { ${version.handler} };`);
+    const fn = require(file)[version.handler];
+    if (typeof fn !== 'function') {
+      throw new Error(`Handler "${version.handler}" of version ${version.number} is not a function`);
+    }
+    versionHandlers.set(version.number, fn);
+  }
+  return versionHandlers.get(version.number);
+}
+
+// Env the runtime started with, used to put back anything an invocation
+// overrode
+const baseEnv = { ...process.env };
+let appliedEnvKeys = [];
+
+/**
+ * Set the env vars the manager sends with each invocation (plain and
+ * decrypted secret ones) before the handler runs.
+ */
+function applyEnv(env) {
+  // Undo the previous invocation's vars first so a removed secret doesn't
+  // linger in process.env
+  for (const name of appliedEnvKeys) {
+    if (name in baseEnv) {
+      process.env[name] = baseEnv[name];
+    } else {
+      delete process.env[name];
+    }
+  }
+  appliedEnvKeys = [];
+  if (env && typeof env === 'object') {
+    Object.assign(process.env, env);
+    appliedEnvKeys = Object.keys(env);
+  }
+}
+
+/**
+ * Split a request body into event, version, env and timeout. Only an
+ * explicit `event` key is treated as the envelope; a bare body is the event
+ * itself, minus `env`, `version` and `timeout_ms`, so secrets never end up
+ * in the event or logs.
+ */
+function parseInvocation(parsed) {
+  if (!parsed || typeof parsed !== 'object' || Array.isArray(parsed)) {
+    return { event: parsed, version: null, env: undefined, timeoutMs: null };
+  }
+  const { env, version, timeout_ms: timeoutMs, ...rest } = parsed;
+  const event = 'event' in parsed ? parsed.event : rest;
+  return { event, version: version || null, env, timeoutMs: timeoutMs || null };
+}
+
+/**
+ * Run the handler, failing once the timeout of the invoked version (or the
+ * draft) has passed. Without one the handler runs unbounded.
+ */
+function runHandler(fn, event, timeoutMs) {
+  const run = Promise.resolve().then(() => fn(event));
+  if (!timeoutMs) {
+    return run;
+  }
+  let timer;
+  const timeout = new Promise((_, reject) => {
+    timer = setTimeout(() => reject(new Error(`Function timed out after ${timeoutMs}ms`)), timeoutMs);
+  });
+  return Promise.race([run, timeout]).finally(() => clearTimeout(timer));
+}
+
+/**
+ * Turn a handler result into the response for /invoke/raw.
+ * `{ statusCode, headers, body, isBase64Encoded }` is honoured; Buffers are
+ * sent as octet-stream, strings as text and anything else as JSON.
+ */
+function toRawResponse(result) {
+  if (Buffer.isBuffer(result) || result instanceof Uint8Array) {
+    return { status: 200, contentType: 'application/octet-stream', body: Buffer.from(result) };
+  }
+  if (typeof result === 'string') {
+    return { status: 200, contentType: 'text/plain; charset=utf-8', body: Buffer.from(result) };
+  }
+  if (result && typeof result === 'object' && 'body' in result) {
+    const headers = result.headers || {};
+    const inner = result.isBase64Encoded && typeof result.body === 'string'
+      ? { contentType: 'application/octet-stream', body: Buffer.from(result.body, 'base64') }
+      : toRawResponse(result.body);
+    return {
+      status: result.statusCode || 200,
+      contentType: headers['Content-Type'] || headers['content-type'] || inner.contentType,
+      body: inner.body,
+    };
+  }
+  return {
+    status: 200,
+    contentType: 'application/json',
+    body: Buffer.from(JSON.stringify(result === undefined ? null : result)),
+  };
+}
+
 /**
  * HTTP server request handler
  */
@@ -64,6 +182,8 @@ const server = http.createServer(async (req, res) => {
     res.end(JSON.stringify({
       status: 'healthy',
       handler: FUNCTION_HANDLER,
+      // What the manager may send with an invocation
+      capabilities: ['versions', 'timeout'],
       codeLoaded: handler !== null,
       error: loadError,
     }));
@@ -118,18 +238,41 @@ const server = http.createServer(async (req, res) => {
     return;
   }
 
+  // Raw invoke endpoint: the handler's result is the response body
+  if (req.url === '/invoke/raw' && req.method === 'POST') {
+    let body = '';
+    req.on('data', chunk => {
+      body += chunk.toString();
+    });
+
+    req.on('end', async () => {
+      try {
+        const { event, version, env, timeoutMs } = parseInvocation(body ? JSON.parse(body) : {});
+        applyEnv(env);
+
+        const fn = loadVersion(version);
+        const raw = toRawResponse(await runHandler(fn, event, timeoutMs));
+        res.writeHead(raw.status, {
+          'Content-Type': raw.contentType,
+          'Content-Length': raw.body.length,
+        });
+        res.end(raw.body);
+      } catch (error) {
+        res.writeHead(500, { 'Content-Type': 'application/json' });
+        res.end(JSON.stringify({
+          status: 'error',
+          error: error.message,
+        }));
+
+        console.error(`[Runtime] Function failed: ${error.message}`);
+      }
+    });
+
+    return;
+  }
+
   // Invoke endpoint
   if (req.url === '/invoke' && req.method === 'POST') {
-    // Check if function is loaded
-    if (!handler) {
-      res.writeHead(500, { 'Content-Type': 'application/json' });
-      res.end(JSON.stringify({
-        status: 'error',
-        error: loadError || 'Function not loaded',
-      }));
-      return;
-    }
-
     // Parse request body
     let body = '';
     req.on('data', chunk => {
@@ -142,11 +285,11 @@ const server = http.createServer(async (req, res) => {
 
       try {
         // Parse event from request
-        let event = {};
-        if (body) {
-          const parsed = JSON.parse(body);
-          event = parsed.event || parsed;
-        }
+        const { event, version, env, timeoutMs } = parseInvocation(body ? JSON.parse(body) : {});
+        applyEnv(env);
+
+        // Fails here, before console capture, if the code can't be loaded
+        const fn = loadVersion(version);
 
         console.log(`[Runtime] Invoking function with event:`, JSON.stringify(event));
 
@@ -172,7 +315,7 @@ const server = http.createServer(async (req, res) => {
         };
 
         // Invoke the handler
-        const result = await Promise.resolve(handler(event));
+        const result = await runHandler(fn, event, timeoutMs);
 
         // Restore console
         console.log = originalLog;
@@ -218,6 +361,7 @@ const server = http.createServer(async (req, res) => {
     endpoints: {
       'GET /health': 'Health check',
       'POST /invoke': 'Execute function',
+      'POST /invoke/raw': 'Execute function, returning the raw result',
       'POST /reload': 'Reload function code',
     },
   }));
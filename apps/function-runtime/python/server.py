@@ -9,12 +9,16 @@ serverless functions on demand. Each function gets its own VM.
 Endpoints:
   GET  /health       - Health check
   POST /invoke       - Execute the function
+  POST /invoke/raw   - Execute the function, returning its result as the body
   POST /reload       - Hot-reload function code
 """
 
+import base64
+
 import http.server
 import json
 import os
+import signal
 import sys
 import time
 import traceback
@@ -28,6 +32,9 @@ FUNCTION_HANDLER = os.environ.get('FUNCTION_HANDLER', 'handler')
 handler_func = None
 load_error = None
 
+# Handlers of published versions, loaded once per version number
+version_handlers = {}
+
 
 def load_function():
     """Load (or reload) the function code"""
@@ -69,6 +76,111 @@ def load_function():
         return False
 
 
+def load_version(version):
+    """Return the handler to invoke for a published version sent with an
+    invocation ({number, code, handler}), or the hot-reloadable $LATEST
+    handler when there is none. Versions are immutable, so each is written
+    and loaded once."""
+    if not version:
+        if handler_func is None:
+            raise RuntimeError(load_error or 'Function not loaded')
+        return handler_func
+
+    number = version['number']
+    if number not in version_handlers:
+        versions_dir = os.path.join(os.path.dirname(FUNCTION_CODE_PATH), 'versions')
+        os.makedirs(versions_dir, exist_ok=True)
+        path = os.path.join(versions_dir, f"code_{number}.py")
+        with open(path, 'w') as f:
+            f.write(version['code'])
+
+        spec = importlib.util.spec_from_file_location(f"user_function_v{number}", path)
+        module = importlib.util.module_from_spec(spec)
+        spec.loader.exec_module(module)
+
+        func = getattr(module, version['handler'], None)
+        if not callable(func):
+            raise TypeError(f"Handler '{version['handler']}' of version {number} is not callable")
+        version_handlers[number] = func
+    return version_handlers[number]
+
+
+# Env the runtime started with, used to put back anything an invocation
+# overrode
+base_env = dict(os.environ)
+applied_env_keys = []
+
+
+def apply_env(env):
+    """Set the env vars the manager sends with each invocation (plain and
+    decrypted secret ones) before the handler runs."""
+    global applied_env_keys
+    # Undo the previous invocation's vars first so a removed secret doesn't
+    # linger in os.environ
+    for name in applied_env_keys:
+        if name in base_env:
+            os.environ[name] = base_env[name]
+        else:
+            os.environ.pop(name, None)
+    applied_env_keys = []
+    if isinstance(env, dict):
+        os.environ.update({name: str(value) for name, value in env.items()})
+        applied_env_keys = list(env)
+
+
+def parse_invocation(parsed):
+    """Split a request body into (event, version, env, timeout_ms). Only an
+    explicit `event` key is treated as the envelope; a bare body is the event
+    itself, minus `env`, `version` and `timeout_ms`, so secrets never end up
+    in the event or logs."""
+    if not isinstance(parsed, dict):
+        return parsed, None, None, None
+    rest = {k: v for k, v in parsed.items() if k not in ('env', 'version', 'timeout_ms')}
+    event = parsed['event'] if 'event' in parsed else rest
+    return event, parsed.get('version'), parsed.get('env'), parsed.get('timeout_ms')
+
+
+def _timed_out(signum, frame):
+    raise TimeoutError('Function timed out')
+
+
+def run_handler(func, event, timeout_ms):
+    """Call the handler, raising TimeoutError once the timeout of the invoked
+    version (or the draft) has passed. The server is single-threaded, so a
+    SIGALRM on the main thread interrupts it."""
+    if not timeout_ms:
+        return func(event)
+    previous = signal.signal(signal.SIGALRM, _timed_out)
+    signal.setitimer(signal.ITIMER_REAL, timeout_ms / 1000)
+    try:
+        return func(event)
+    finally:
+        signal.setitimer(signal.ITIMER_REAL, 0)
+        signal.signal(signal.SIGALRM, previous)
+
+
+def to_raw_response(result):
+    """Turn a handler result into (status, content type, body) for /invoke/raw.
+
+    A dict with a 'body' key is read as {statusCode, headers, body,
+    isBase64Encoded}; bytes are sent as octet-stream, str as text and
+    anything else as JSON.
+    """
+    if isinstance(result, (bytes, bytearray)):
+        return 200, 'application/octet-stream', bytes(result)
+    if isinstance(result, str):
+        return 200, 'text/plain; charset=utf-8', result.encode('utf-8')
+    if isinstance(result, dict) and 'body' in result:
+        headers = result.get('headers') or {}
+        if result.get('isBase64Encoded') and isinstance(result['body'], str):
+            content_type, body = 'application/octet-stream', base64.b64decode(result['body'])
+        else:
+            _, content_type, body = to_raw_response(result['body'])
+        content_type = headers.get('Content-Type') or headers.get('content-type') or content_type
+        return result.get('statusCode', 200), content_type, body
+    return 200, 'application/json', json.dumps(result).encode('utf-8')
+
+
 class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
     """HTTP request handler for function runtime"""
 
@@ -91,6 +203,8 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
                 'handler': FUNCTION_HANDLER,
                 'codeLoaded': handler_func is not None,
                 'error': load_error,
+                # What the manager may send with an invocation
+                'capabilities': ['versions', 'timeout'],
             })
         else:
             self.send_json_response(404, {
@@ -98,6 +212,7 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
                 'endpoints': {
                     'GET /health': 'Health check',
                     'POST /invoke': 'Execute function',
+                    'POST /invoke/raw': 'Execute function, returning the raw result',
                     'POST /reload': 'Reload function code',
                 },
             })
@@ -138,15 +253,28 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
                 'error': load_error,
             })
 
-        elif self.path == '/invoke':
-            # Check if function is loaded
-            if handler_func is None:
-                self.send_json_response(500, {
-                    'status': 'error',
-                    'error': load_error or 'Function not loaded',
-                })
+        elif self.path == '/invoke/raw':
+            content_length = int(self.headers.get('Content-Length', 0))
+            body = self.rfile.read(content_length).decode('utf-8') if content_length > 0 else '{}'
+
+            try:
+                parsed = json.loads(body)
+                event, version, env, timeout_ms = parse_invocation(parsed)
+                apply_env(env)
+                func = load_version(version)
+                status, content_type, payload = to_raw_response(run_handler(func, event, timeout_ms))
+            except Exception as e:
+                self.send_json_response(500, {'status': 'error', 'error': str(e)})
+                print(f"[Runtime] Function failed: {e}", file=sys.stderr)
                 return
 
+            self.send_response(status)
+            self.send_header('Content-Type', content_type)
+            self.send_header('Content-Length', str(len(payload)))
+            self.end_headers()
+            self.wfile.write(payload)
+
+        elif self.path == '/invoke':
             # Parse request body
             content_length = int(self.headers.get('Content-Length', 0))
             body = self.rfile.read(content_length).decode('utf-8') if content_length > 0 else '{}'
@@ -157,7 +285,9 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
             try:
                 # Parse event from request
                 parsed = json.loads(body)
-                event = parsed.get('event', parsed)
+                event, version, env, timeout_ms = parse_invocation(parsed)
+                apply_env(env)
+                func = load_version(version)
 
                 print(f"[Runtime] Invoking function with event: {json.dumps(event)}")
 
@@ -169,7 +299,7 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
 
                 try:
                     # Invoke the handler
-                    result = handler_func(event)
+                    result = run_handler(func, event, timeout_ms)
 
                     # Capture logs
                     stdout_value = sys.stdout.getvalue()
@@ -217,6 +347,7 @@ class FunctionRuntimeHandler(http.server.BaseHTTPRequestHandler):
                 'endpoints': {
                     'GET /health': 'Health check',
                     'POST /invoke': 'Execute function',
+                    'POST /invoke/raw': 'Execute function, returning the raw result',
                     'POST /reload': 'Reload function code',
                 },
             })
@@ -1,21 +1,29 @@
 use axum::{
-    extract::State,
+    extract::{FromRef, Query, State},
+    http::StatusCode,
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+mod vsock;
+
 /// CPU statistics tuple: (user, nice, system, idle, iowait, irq, softirq)
 type CpuStats = (u64, u64, u64, u64, u64, u64, u64);
 
-#[derive(Debug, Clone)]
+const CONFIG_PATH: &str = "/etc/guest-agent.conf";
+
+#[derive(Debug, Clone, Serialize)]
 struct AgentConfig {
     vm_id: String,
     manager_url: String,
+    /// `REPORT_INTERFACE`: whose address is reported as the guest IP. Unset
+    /// means the interface carrying the default route (or eth0).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report_interface: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -28,6 +36,21 @@ struct GuestMetrics {
     uptime_seconds: u64,
     load_average: Option<f64>,
     process_count: Option<u32>,
+    /// Per-interface counters from /proc/net/dev (excluding `lo`)
+    network_interfaces: Vec<InterfaceStats>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct InterfaceStats {
+    name: String,
+    rx_bytes: u64,
+    rx_packets: u64,
+    rx_errors: u64,
+    rx_dropped: u64,
+    tx_bytes: u64,
+    tx_packets: u64,
+    tx_errors: u64,
+    tx_dropped: u64,
 }
 
 /// Read CPU statistics from /proc/stat
@@ -146,27 +169,165 @@ fn read_load_average() -> Option<f64> {
     load_str.parse().ok()
 }
 
-/// Count processes in /proc
-fn count_processes() -> Option<u32> {
-    let proc_entries = fs::read_dir("/proc").ok()?;
-    let count = proc_entries
-        .filter_map(|entry| {
-            entry.ok().and_then(|e| {
-                e.file_name()
-                    .to_str()
-                    .map(|s| s.chars().all(|c| c.is_ascii_digit()))
+/// Read per-interface counters from /proc/net/dev, skipping the loopback
+fn read_network_stats() -> Vec<InterfaceStats> {
+    let Ok(netdev) = fs::read_to_string("/proc/net/dev") else {
+        return Vec::new();
+    };
+
+    // Two header lines, then "  eth0: rx_bytes rx_packets rx_errs rx_drop ... tx_bytes tx_packets tx_errs tx_drop ..."
+    netdev
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            let name = name.trim();
+            if name == "lo" {
+                return None;
+            }
+            let fields: Vec<u64> = counters
+                .split_whitespace()
+                .map(|f| f.parse().unwrap_or(0))
+                .collect();
+            if fields.len() < 12 {
+                return None;
+            }
+            Some(InterfaceStats {
+                name: name.to_string(),
+                rx_bytes: fields[0],
+                rx_packets: fields[1],
+                rx_errors: fields[2],
+                rx_dropped: fields[3],
+                tx_bytes: fields[8],
+                tx_packets: fields[9],
+                tx_errors: fields[10],
+                tx_dropped: fields[11],
             })
         })
-        .count();
-    Some(count as u32)
+        .collect()
+}
+
+/// PIDs of the processes in /proc
+fn proc_pids() -> Vec<u32> {
+    let Ok(proc_entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    proc_entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect()
+}
+
+/// Count processes in /proc
+fn count_processes() -> Option<u32> {
+    Some(proc_pids().len() as u32)
+}
+
+/// Default and maximum number of processes `/processes` returns
+const DEFAULT_PROCESS_LIMIT: usize = 20;
+const MAX_PROCESS_LIMIT: usize = 200;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+struct ProcessInfo {
+    pid: u32,
+    name: String,
+    /// Resident memory (VmRSS); 0 for kernel threads
+    rss_kb: u64,
+    /// user + system time since the process started, in clock ticks
+    cpu_ticks: u64,
+    /// One-letter state from /proc/[pid]/stat (R, S, D, Z, ...)
+    state: String,
+}
+
+/// Name, state and CPU ticks from /proc/[pid]/stat:
+/// "pid (comm) state ppid ... utime stime ..."
+fn parse_proc_stat(stat: &str) -> Option<(String, String, u64)> {
+    // comm may itself contain spaces and parentheses
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    let fields: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    // Fields after comm start at state (field 3); utime and stime are 14 and 15
+    let state = fields.first()?.to_string();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some((name, state, utime + stime))
+}
+
+/// VmRSS from /proc/[pid]/status, in kB
+fn parse_vm_rss_kb(status: &str) -> u64 {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|kb| kb.parse().ok())
+        .unwrap_or(0)
+}
+
+fn read_process(pid: u32) -> Option<ProcessInfo> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (name, state, cpu_ticks) = parse_proc_stat(&stat)?;
+    let rss_kb = fs::read_to_string(format!("/proc/{}/status", pid))
+        .map(|status| parse_vm_rss_kb(&status))
+        .unwrap_or(0);
+    Some(ProcessInfo {
+        pid,
+        name,
+        rss_kb,
+        cpu_ticks,
+        state,
+    })
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ProcessSort {
+    #[default]
+    Memory,
+    Cpu,
+}
+
+/// The `limit` biggest processes by `sort`
+fn top_processes(
+    mut processes: Vec<ProcessInfo>,
+    sort: ProcessSort,
+    limit: usize,
+) -> Vec<ProcessInfo> {
+    match sort {
+        ProcessSort::Memory => processes.sort_by_key(|p| std::cmp::Reverse(p.rss_kb)),
+        ProcessSort::Cpu => processes.sort_by_key(|p| std::cmp::Reverse(p.cpu_ticks)),
+    }
+    processes.truncate(limit);
+    processes
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProcessesQuery {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: ProcessSort,
+}
+
+/// Top processes by memory (default) or CPU time. Only names, not command
+/// lines, so like `/metrics` it needs no `ALLOW_EXEC`.
+async fn get_processes(Query(q): Query<ProcessesQuery>) -> Json<Vec<ProcessInfo>> {
+    let limit = q
+        .limit
+        .unwrap_or(DEFAULT_PROCESS_LIMIT)
+        .clamp(1, MAX_PROCESS_LIMIT);
+    let processes = proc_pids().into_iter().filter_map(read_process).collect();
+    Json(top_processes(processes, q.sort, limit))
 }
 
 /// Read guest agent configuration from /etc/guest-agent.conf
 fn read_config() -> Option<AgentConfig> {
-    let config_content = fs::read_to_string("/etc/guest-agent.conf").ok()?;
+    parse_config(&fs::read_to_string(CONFIG_PATH).ok()?)
+}
 
+fn parse_config(config_content: &str) -> Option<AgentConfig> {
     let mut vm_id = None;
     let mut manager_url = None;
+    let mut report_interface = None;
 
     for line in config_content.lines() {
         let line = line.trim();
@@ -178,6 +339,9 @@ fn read_config() -> Option<AgentConfig> {
             match key.trim() {
                 "VM_ID" => vm_id = Some(value.trim().to_string()),
                 "MANAGER_URL" => manager_url = Some(value.trim().to_string()),
+                "REPORT_INTERFACE" => {
+                    report_interface = Some(value.trim().to_string()).filter(|v| !v.is_empty())
+                }
                 _ => {}
             }
         }
@@ -186,46 +350,94 @@ fn read_config() -> Option<AgentConfig> {
     Some(AgentConfig {
         vm_id: vm_id?,
         manager_url: manager_url?,
+        report_interface,
     })
 }
 
-/// Detect the VM's IP address from eth0
-fn detect_ip() -> Option<String> {
-    // Try reading from /sys/class/net/eth0/address first
-    let output = std::process::Command::new("ip")
-        .args(["addr", "show", "eth0"])
-        .output()
-        .ok()?;
+/// One address assigned to a guest interface
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct InterfaceAddr {
+    interface: String,
+    ip: String,
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+/// Interface named in /proc/net/route as carrying the default route
+fn parse_default_route_interface(route_table: &str) -> Option<String> {
+    // "Iface Destination Gateway Flags ...", destination in hex
+    route_table.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let iface = fields.next()?;
+        (fields.next()? == "00000000").then(|| iface.to_string())
+    })
+}
 
-    // Parse: "inet 192.168.18.2/24 ..."
-    for line in stdout.lines() {
-        let line = line.trim();
-        if line.starts_with("inet ") {
-            if let Some(ip_part) = line.split_whitespace().nth(1) {
-                if let Some(ip) = ip_part.split('/').next() {
-                    // Skip localhost
-                    if ip != "127.0.0.1" && !ip.is_empty() {
-                        return Some(ip.to_string());
-                    }
-                }
+/// The interface whose address is reported as the guest IP
+fn report_interface(config: Option<&AgentConfig>) -> String {
+    config
+        .and_then(|config| config.report_interface.clone())
+        .or_else(|| {
+            fs::read_to_string("/proc/net/route")
+                .ok()
+                .and_then(|table| parse_default_route_interface(&table))
+        })
+        .unwrap_or_else(|| "eth0".to_string())
+}
+
+/// Parse `ip -o addr show` into non-loopback, non-link-local addresses
+fn parse_interface_addrs(output: &str) -> Vec<InterfaceAddr> {
+    // "2: eth0    inet 192.168.18.2/24 brd 192.168.18.255 scope global eth0 ..."
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let interface = fields.next()?.split('@').next()?.to_string();
+            let family = fields.next()?;
+            let ip = fields.next()?.split('/').next()?.to_string();
+            if interface == "lo"
+                || !(family == "inet" || family == "inet6")
+                || line.contains(" scope host")
+                || line.contains(" scope link")
+            {
+                return None;
             }
-        }
-    }
+            Some(InterfaceAddr { interface, ip })
+        })
+        .collect()
+}
+
+/// Every non-loopback address on the guest
+fn detect_addrs() -> Vec<InterfaceAddr> {
+    let Ok(output) = std::process::Command::new("ip")
+        .args(["-o", "addr", "show"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    parse_interface_addrs(&String::from_utf8_lossy(&output.stdout))
+}
 
-    None
+/// The address to report as the guest IP: the first IPv4 (else IPv6)
+/// address on `interface`
+fn primary_ip(addrs: &[InterfaceAddr], interface: &str) -> Option<String> {
+    let on_iface = || addrs.iter().filter(|a| a.interface == interface);
+    on_iface()
+        .find(|a| !a.ip.contains(':'))
+        .or_else(|| on_iface().next())
+        .map(|a| a.ip.clone())
 }
 
-/// Report IP address to the manager
+/// Report the guest's IP, and all of its addresses, to the manager
 async fn report_ip_to_manager(
     config: &AgentConfig,
-    ip: &str,
+    ip: Option<&str>,
+    addrs: &[InterfaceAddr],
 ) -> Result<(), Box<dyn std::error::Error>> {
     let url = format!("{}/v1/vms/{}/guest-ip", config.manager_url, config.vm_id);
 
-    // Create JSON payload as a string to ensure proper formatting
-    let payload = format!(r#"{{"guest_ip":"{}"}}"#, ip);
+    let payload = serde_json::json!({
+        "guest_ip": ip,
+        "guest_ips": addrs,
+    });
 
     eprintln!("Reporting to: {}", url);
     eprintln!("Payload: {}", payload);
@@ -234,20 +446,45 @@ async fn report_ip_to_manager(
         .timeout(Duration::from_secs(5))
         .build()?;
 
+    let response = client.post(&url).json(&payload).send().await?;
+
+    if response.status().is_success() {
+        eprintln!(
+            "✅ Successfully reported IP {} to manager",
+            ip.unwrap_or("(none)")
+        );
+        Ok(())
+    } else {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Err(format!("Failed to report IP: {} - {}", status, body).into())
+    }
+}
+
+/// Tell the manager this guest is going away so it clears the VM's
+/// `guest_ip` now, instead of trying the dead address until the next report
+/// would have been due
+async fn clear_ip_on_manager(config: &AgentConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let url = format!("{}/v1/vms/{}/guest-ip", config.manager_url, config.vm_id);
+
+    // Shutdown won't wait long, so give up sooner than a regular report
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()?;
+
     let response = client
         .post(&url)
-        .header("Content-Type", "application/json")
-        .body(payload)
+        .json(&serde_json::json!({ "guest_ip": null }))
         .send()
         .await?;
 
     if response.status().is_success() {
-        eprintln!("✅ Successfully reported IP {} to manager", ip);
+        eprintln!("Cleared guest IP on manager");
         Ok(())
     } else {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
-        Err(format!("Failed to report IP: {} - {}", status, body).into())
+        Err(format!("Failed to clear IP: {} - {}", status, body).into())
     }
 }
 
@@ -264,6 +501,7 @@ fn get_current_metrics(prev_cpu: Option<CpuStats>) -> (GuestMetrics, Option<CpuS
     let uptime = read_uptime().unwrap_or(0);
     let load_avg = read_load_average();
     let process_count = count_processes();
+    let network_interfaces = read_network_stats();
 
     let metrics = GuestMetrics {
         cpu_usage_percent: cpu_percent,
@@ -274,49 +512,54 @@ fn get_current_metrics(prev_cpu: Option<CpuStats>) -> (GuestMetrics, Option<CpuS
         uptime_seconds: uptime,
         load_average: load_avg,
         process_count,
+        network_interfaces,
     };
 
     (metrics, Some(cpu_stats))
 }
 
-/// Health check endpoint
-async fn health_check() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
+fn health_body() -> serde_json::Value {
+    serde_json::json!({
         "status": "healthy",
         "timestamp": std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs(),
         "version": env!("CARGO_PKG_VERSION")
-    }))
+    })
 }
 
-/// Metrics endpoint
-async fn get_metrics(State(cpu_state): State<Arc<CpuState>>) -> Json<GuestMetrics> {
-    let prev_cpu = cpu_state.last_cpu.load(Ordering::Relaxed);
-    let prev_cpu_tuple = if prev_cpu == 0 {
-        None
-    } else {
-        // Convert stored u128 back to tuple
-        let user = (prev_cpu >> 48) & 0xFFFF;
-        let nice = (prev_cpu >> 32) & 0xFFFF;
-        let system = (prev_cpu >> 16) & 0xFFFF;
-        let idle = prev_cpu & 0xFFFF;
-        Some((user, nice, system, idle, 0, 0, 0))
-    };
+/// What the IP report carries, for hosts that read it over vsock instead:
+/// the manager may be unreachable from the guest network, or not up yet
+fn addresses_body() -> serde_json::Value {
+    let interface = report_interface(read_config().as_ref());
+    let addrs = detect_addrs();
+    serde_json::json!({
+        "guest_ip": primary_ip(&addrs, &interface),
+        "guest_ips": addrs,
+    })
+}
 
-    let (metrics, new_cpu) = get_current_metrics(prev_cpu_tuple);
+/// Health check endpoint
+async fn health_check() -> Json<serde_json::Value> {
+    Json(health_body())
+}
+
+fn sample_metrics(cpu_state: &CpuState) -> GuestMetrics {
+    let prev_cpu = *cpu_state.last_cpu.lock().unwrap();
 
-    // Store new CPU stats (compressed as u64 to save space)
-    if let Some(cpu) = new_cpu {
-        let compressed = ((cpu.0 & 0xFFFF) << 48)
-            | ((cpu.1 & 0xFFFF) << 32)
-            | ((cpu.2 & 0xFFFF) << 16)
-            | (cpu.3 & 0xFFFF);
-        cpu_state.last_cpu.store(compressed, Ordering::Relaxed);
+    let (metrics, new_cpu) = get_current_metrics(prev_cpu);
+
+    if new_cpu.is_some() {
+        *cpu_state.last_cpu.lock().unwrap() = new_cpu;
     }
 
-    Json(metrics)
+    metrics
+}
+
+/// Metrics endpoint
+async fn get_metrics(State(cpu_state): State<Arc<CpuState>>) -> Json<GuestMetrics> {
+    Json(sample_metrics(&cpu_state))
 }
 
 /// Request to configure a network interface
@@ -476,9 +719,423 @@ async fn configure_interface(
     }
 }
 
+/// Default cap on combined stdout+stderr returned by `/run-command`
+const DEFAULT_EXEC_MAX_OUTPUT_BYTES: usize = 64 * 1024;
+/// Default and maximum timeouts for `/run-command`
+const DEFAULT_EXEC_TIMEOUT_SECS: u64 = 10;
+const MAX_EXEC_TIMEOUT_SECS: u64 = 60;
+
+/// Command execution settings from /etc/guest-agent.conf.
+/// Read on every request so toggling `ALLOW_EXEC` doesn't need a restart.
+#[derive(Debug, Clone)]
+struct ExecConfig {
+    allow_exec: bool,
+    max_output_bytes: usize,
+}
+
+fn read_exec_config() -> ExecConfig {
+    let mut config = ExecConfig {
+        allow_exec: false,
+        max_output_bytes: DEFAULT_EXEC_MAX_OUTPUT_BYTES,
+    };
+
+    let Ok(config_content) = fs::read_to_string(CONFIG_PATH) else {
+        return config;
+    };
+
+    for line in config_content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            match key.trim() {
+                "ALLOW_EXEC" => config.allow_exec = value.trim() == "1",
+                "EXEC_MAX_OUTPUT_BYTES" => {
+                    if let Ok(bytes) = value.trim().parse() {
+                        config.max_output_bytes = bytes;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    config
+}
+
+/// Request to run a command inside the guest
+#[derive(Deserialize)]
+struct RunCommandRequest {
+    /// Program followed by its arguments; not passed through a shell
+    command: Vec<String>,
+    /// Hard timeout, capped at `MAX_EXEC_TIMEOUT_SECS`
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct RunCommandResponse {
+    stdout: String,
+    stderr: String,
+    exit_code: Option<i32>,
+}
+
+/// Run a short command for debugging without SSH.
+/// Disabled unless `ALLOW_EXEC=1` is set in /etc/guest-agent.conf.
+async fn run_command(
+    Json(req): Json<RunCommandRequest>,
+) -> Result<Json<RunCommandResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let config = read_exec_config();
+    if !config.allow_exec {
+        eprintln!("Refused run-command: ALLOW_EXEC is not enabled");
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": "command execution is disabled (set ALLOW_EXEC=1 in /etc/guest-agent.conf)"
+            })),
+        ));
+    }
+
+    let Some((program, args)) = req.command.split_first() else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "command must not be empty" })),
+        ));
+    };
+
+    let timeout_secs = req
+        .timeout_secs
+        .unwrap_or(DEFAULT_EXEC_TIMEOUT_SECS)
+        .clamp(1, MAX_EXEC_TIMEOUT_SECS);
+    eprintln!(
+        "Running command {:?} (timeout {}s)",
+        req.command, timeout_secs
+    );
+
+    let mut child = match tokio::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("❌ Failed to run {}: {}", program, e);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("failed to run {}: {}", program, e) })),
+            ));
+        }
+    };
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        unreachable!("stdout and stderr are piped");
+    };
+
+    // Output past the cap is never buffered: the child is killed (on drop)
+    // as soon as it's reached
+    let run = async {
+        let Some((stdout, stderr)) = read_capped(stdout, stderr, config.max_output_bytes).await?
+        else {
+            return Ok(None);
+        };
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>(Some((stdout, stderr, status)))
+    };
+    let (stdout, stderr, status) =
+        match tokio::time::timeout(Duration::from_secs(timeout_secs), run).await {
+            Ok(Ok(Some(output))) => output,
+            Ok(Ok(None)) => {
+                eprintln!(
+                    "❌ Command {:?} produced more than the {} byte cap",
+                    req.command, config.max_output_bytes
+                );
+                return Err((
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    Json(serde_json::json!({
+                        "error": format!(
+                            "command output exceeds the {} byte cap",
+                            config.max_output_bytes
+                        ),
+                    })),
+                ));
+            }
+            Ok(Err(e)) => {
+                eprintln!("❌ Failed to read output of {:?}: {}", req.command, e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({
+                        "error": format!("failed to read command output: {}", e)
+                    })),
+                ));
+            }
+            Err(_) => {
+                eprintln!(
+                    "❌ Command {:?} timed out after {}s",
+                    req.command, timeout_secs
+                );
+                return Err((
+                    StatusCode::GATEWAY_TIMEOUT,
+                    Json(serde_json::json!({
+                        "error": format!("command timed out after {}s", timeout_secs)
+                    })),
+                ));
+            }
+        };
+
+    eprintln!("Command {:?} exited with {:?}", req.command, status.code());
+    Ok(Json(RunCommandResponse {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code: status.code(),
+    }))
+}
+
+/// Reads `stdout` and `stderr` until both close. Returns `None` as soon as
+/// together they pass `cap` bytes, without reading the rest.
+async fn read_capped(
+    mut stdout: impl tokio::io::AsyncRead + Unpin,
+    mut stderr: impl tokio::io::AsyncRead + Unpin,
+    cap: usize,
+) -> std::io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    use tokio::io::AsyncReadExt;
+
+    let (mut out, mut err) = (Vec::new(), Vec::new());
+    let (mut out_buf, mut err_buf) = ([0u8; 8192], [0u8; 8192]);
+    let (mut out_open, mut err_open) = (true, true);
+    while out_open || err_open {
+        tokio::select! {
+            read = stdout.read(&mut out_buf), if out_open => match read? {
+                0 => out_open = false,
+                n => out.extend_from_slice(&out_buf[..n]),
+            },
+            read = stderr.read(&mut err_buf), if err_open => match read? {
+                0 => err_open = false,
+                n => err.extend_from_slice(&err_buf[..n]),
+            },
+        }
+        if out.len() + err.len() > cap {
+            return Ok(None);
+        }
+    }
+    Ok(Some((out, err)))
+}
+
+/// Periodically report the guest IP to the manager under `config`'s identity
+async fn report_ip_loop(config: AgentConfig) {
+    // Wait a bit for network to be ready
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let mut reported = false;
+
+    loop {
+        let interface = report_interface(Some(&config));
+        let addrs = detect_addrs();
+        let ip = primary_ip(&addrs, &interface);
+        if ip.is_none() {
+            eprintln!("Could not detect IP address from {}", interface);
+        }
+        if !addrs.is_empty() {
+            match report_ip_to_manager(&config, ip.as_deref(), &addrs).await {
+                Ok(_) => {
+                    if !reported {
+                        eprintln!("Initial IP report successful");
+                        reported = true;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed to report IP: {}", e);
+                }
+            }
+        }
+
+        // Use shorter interval until first successful report, then every 30s
+        if reported {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        } else {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+}
+
+/// Owns the IP-reporting task so it can be restarted under a new identity
+#[derive(Default)]
+struct IpReporter {
+    task: Mutex<Option<(tokio::task::JoinHandle<()>, AgentConfig)>>,
+}
+
+impl IpReporter {
+    fn restart(&self, config: AgentConfig) {
+        let mut task = self.task.lock().unwrap();
+        if let Some((old, _)) = task.take() {
+            old.abort();
+        }
+        *task = Some((tokio::spawn(report_ip_loop(config.clone())), config));
+    }
+
+    /// Stop reporting, returning the identity it reported under
+    fn stop(&self) -> Option<AgentConfig> {
+        let (task, config) = self.task.lock().unwrap().take()?;
+        task.abort();
+        Some(config)
+    }
+}
+
+/// Resolves on SIGTERM (what init sends on poweroff) or Ctrl-C
+async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut term) => {
+            tokio::select! {
+                _ = term.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to install SIGTERM handler: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+/// On shutdown: stop IP reporting and clear the IP on the manager first, then
+/// let the server finish requests already in flight (e.g. a `/metrics` poll)
+async fn graceful_shutdown(ip_reporter: Arc<IpReporter>) {
+    shutdown_signal().await;
+    eprintln!("Guest agent shutting down...");
+    if let Some(config) = ip_reporter.stop() {
+        if let Err(e) = clear_ip_on_manager(&config).await {
+            eprintln!("Failed to clear IP on shutdown: {}", e);
+        }
+    }
+}
+
+/// Rewrite config file contents with a new VM_ID/MANAGER_URL, keeping
+/// comments and any other settings (e.g. ALLOW_EXEC) intact
+fn render_config(existing: &str, vm_id: &str, manager_url: &str) -> String {
+    let mut lines = Vec::new();
+    let mut wrote_vm_id = false;
+    let mut wrote_manager_url = false;
+
+    for line in existing.lines() {
+        match line.split_once('=').map(|(key, _)| key.trim()) {
+            Some("VM_ID") if !line.trim_start().starts_with('#') => {
+                if !wrote_vm_id {
+                    lines.push(format!("VM_ID={}", vm_id));
+                    wrote_vm_id = true;
+                }
+            }
+            Some("MANAGER_URL") if !line.trim_start().starts_with('#') => {
+                if !wrote_manager_url {
+                    lines.push(format!("MANAGER_URL={}", manager_url));
+                    wrote_manager_url = true;
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    if !wrote_vm_id {
+        lines.push(format!("VM_ID={}", vm_id));
+    }
+    if !wrote_manager_url {
+        lines.push(format!("MANAGER_URL={}", manager_url));
+    }
+
+    let mut rendered = lines.join("\n");
+    rendered.push('\n');
+    rendered
+}
+
+/// Request to change the agent's identity (e.g. after snapshot restore)
+#[derive(Deserialize)]
+struct UpdateConfigRequest {
+    vm_id: String,
+    manager_url: String,
+}
+
+/// Update config endpoint
+/// Rewrites /etc/guest-agent.conf, reloads it and restarts IP reporting
+async fn update_config(
+    State(ip_reporter): State<Arc<IpReporter>>,
+    Json(req): Json<UpdateConfigRequest>,
+) -> Result<Json<AgentConfig>, (StatusCode, Json<serde_json::Value>)> {
+    let vm_id = req.vm_id.trim();
+    let manager_url = req.manager_url.trim().trim_end_matches('/');
+    let bad_request = |msg: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+    };
+
+    if vm_id.is_empty() || vm_id.contains(['\n', '\r', '=']) {
+        return Err(bad_request("vm_id must be a non-empty single-line value"));
+    }
+    if !(manager_url.starts_with("http://") || manager_url.starts_with("https://"))
+        || manager_url.contains(['\n', '\r'])
+    {
+        return Err(bad_request("manager_url must be an http(s) URL"));
+    }
+
+    eprintln!(
+        "Updating config: VM ID = {}, Manager URL = {}",
+        vm_id, manager_url
+    );
+
+    let existing = fs::read_to_string(CONFIG_PATH).unwrap_or_default();
+    let rendered = render_config(&existing, vm_id, manager_url);
+    let tmp_path = format!("{}.tmp", CONFIG_PATH);
+    if let Err(e) = fs::write(&tmp_path, rendered).and_then(|_| fs::rename(&tmp_path, CONFIG_PATH))
+    {
+        eprintln!("❌ Failed to write {}: {}", CONFIG_PATH, e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("failed to write config: {}", e) })),
+        ));
+    }
+
+    let Some(config) = read_config() else {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "config was written but could not be reloaded" })),
+        ));
+    };
+
+    ip_reporter.restart(config.clone());
+    eprintln!(
+        "✅ Config updated, IP reporting restarted for VM {}",
+        config.vm_id
+    );
+
+    Ok(Json(config))
+}
+
 #[derive(Clone)]
+struct AgentState {
+    cpu: Arc<CpuState>,
+    ip_reporter: Arc<IpReporter>,
+}
+
+impl FromRef<AgentState> for Arc<CpuState> {
+    fn from_ref(state: &AgentState) -> Self {
+        state.cpu.clone()
+    }
+}
+
+impl FromRef<AgentState> for Arc<IpReporter> {
+    fn from_ref(state: &AgentState) -> Self {
+        state.ip_reporter.clone()
+    }
+}
+
+/// Most recent full-width CPU sample, shared between the sampler and `/metrics`
+#[derive(Default)]
 struct CpuState {
-    last_cpu: Arc<AtomicU64>,
+    last_cpu: Mutex<Option<CpuStats>>,
 }
 
 #[tokio::main]
@@ -497,9 +1154,7 @@ async fn main() {
         eprintln!("Warning: No config found at /etc/guest-agent.conf - IP reporting disabled");
     }
 
-    let cpu_state = Arc::new(CpuState {
-        last_cpu: Arc::new(AtomicU64::new(0)),
-    });
+    let cpu_state = Arc::new(CpuState::default());
 
     // Sample CPU every second in background
     let cpu_state_clone = cpu_state.clone();
@@ -508,68 +1163,46 @@ async fn main() {
         loop {
             interval.tick().await;
             if let Ok(cpu_stats) = read_cpu_stats() {
-                let compressed = ((cpu_stats.0 & 0xFFFF) << 48)
-                    | ((cpu_stats.1 & 0xFFFF) << 32)
-                    | ((cpu_stats.2 & 0xFFFF) << 16)
-                    | (cpu_stats.3 & 0xFFFF);
-                cpu_state_clone
-                    .last_cpu
-                    .store(compressed, Ordering::Relaxed);
+                *cpu_state_clone.last_cpu.lock().unwrap() = Some(cpu_stats);
             }
         }
     });
 
     // Start IP reporting task if config is available
+    let ip_reporter = Arc::new(IpReporter::default());
     if let Some(config) = config {
-        tokio::spawn(async move {
-            // Wait a bit for network to be ready
-            tokio::time::sleep(Duration::from_secs(3)).await;
-
-            let mut reported = false;
-
-            loop {
-                if let Some(ip) = detect_ip() {
-                    match report_ip_to_manager(&config, &ip).await {
-                        Ok(_) => {
-                            if !reported {
-                                eprintln!("Initial IP report successful");
-                                reported = true;
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to report IP: {}", e);
-                        }
-                    }
-                } else {
-                    eprintln!("Could not detect IP address from eth0");
-                }
-
-                // Use shorter interval until first successful report, then every 30s
-                if reported {
-                    tokio::time::sleep(Duration::from_secs(30)).await;
-                } else {
-                    tokio::time::sleep(Duration::from_secs(5)).await;
-                }
-            }
-        });
+        ip_reporter.restart(config);
     }
 
+    // Read-only endpoints over vsock, for hosts that attach a vsock device
+    vsock::spawn(cpu_state.clone());
+
     // Create router
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/metrics", get(get_metrics))
+        .route("/processes", get(get_processes))
         .route("/configure-interface", post(configure_interface))
-        .with_state(cpu_state);
+        .route("/run-command", post(run_command))
+        .route("/update-config", post(update_config))
+        .with_state(AgentState {
+            cpu: cpu_state,
+            ip_reporter: ip_reporter.clone(),
+        });
 
     // Try to bind to port 9000 (avoid conflict with manager on 8080)
     let addr = "0.0.0.0:9000";
     match tokio::net::TcpListener::bind(addr).await {
         Ok(listener) => {
             eprintln!("Guest agent listening on {}", addr);
-            if let Err(e) = axum::serve(listener, app).await {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(graceful_shutdown(ip_reporter))
+                .await
+            {
                 eprintln!("Guest agent server error: {}", e);
                 std::process::exit(1);
             }
+            eprintln!("Guest agent stopped");
         }
         Err(e) => {
             eprintln!("Failed to bind to {}: {}", addr, e);
@@ -577,3 +1210,163 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_percent_from_realistic_samples() {
+        // Counters well past 16 bits, as on any guest up for more than a few minutes.
+        // One second on 2 vCPUs: 200 jiffies total, 50 busy.
+        let prev: CpuStats = (1_234_567, 1_000, 345_678, 9_876_543, 12_345, 0, 4_321);
+        let curr: CpuStats = (1_234_597, 1_000, 345_693, 9_876_688, 12_350, 0, 4_326);
+
+        let percent = calculate_cpu_percent(prev, curr);
+        assert!((percent - 25.0).abs() < 0.01, "got {}", percent);
+    }
+
+    #[test]
+    fn proc_stat_handles_odd_names() {
+        let stat = "1234 (my (weird) proc) S 1 1234 1234 0 -1 4194560 500 0 0 0 \
+                    150 25 0 0 20 0 1 0 100 12345678 300 18446744073709551615";
+        assert_eq!(
+            parse_proc_stat(stat),
+            Some(("my (weird) proc".to_string(), "S".to_string(), 175))
+        );
+        assert_eq!(parse_proc_stat("1234 (truncated"), None);
+        assert_eq!(parse_proc_stat("1234 (short) R 1 2"), None);
+    }
+
+    #[test]
+    fn vm_rss_is_zero_for_kernel_threads() {
+        let status = "Name:\tnginx\nState:\tS (sleeping)\nVmRSS:\t   10240 kB\nThreads:\t1\n";
+        assert_eq!(parse_vm_rss_kb(status), 10240);
+        assert_eq!(
+            parse_vm_rss_kb("Name:\tkthreadd\nState:\tS (sleeping)\n"),
+            0
+        );
+    }
+
+    #[test]
+    fn top_processes_sorts_and_limits() {
+        let proc_info = |pid, rss_kb, cpu_ticks| ProcessInfo {
+            pid,
+            name: format!("p{pid}"),
+            rss_kb,
+            cpu_ticks,
+            state: "S".to_string(),
+        };
+        let processes = vec![
+            proc_info(1, 100, 9),
+            proc_info(2, 300, 1),
+            proc_info(3, 200, 5),
+        ];
+
+        let pids = |ps: Vec<ProcessInfo>| ps.iter().map(|p| p.pid).collect::<Vec<_>>();
+        assert_eq!(
+            pids(top_processes(processes.clone(), ProcessSort::Memory, 2)),
+            vec![2, 3]
+        );
+        assert_eq!(
+            pids(top_processes(processes, ProcessSort::Cpu, 10)),
+            vec![1, 3, 2]
+        );
+    }
+
+    #[test]
+    fn parse_config_reads_optional_report_interface() {
+        let base = "# comment\nVM_ID=abc\nMANAGER_URL=http://10.0.0.1:18080\n";
+        let config = parse_config(base).unwrap();
+        assert_eq!(config.vm_id, "abc");
+        assert_eq!(config.report_interface, None);
+
+        let config = parse_config(&format!("{base}REPORT_INTERFACE= ens5 \n")).unwrap();
+        assert_eq!(config.report_interface.as_deref(), Some("ens5"));
+        let config = parse_config(&format!("{base}REPORT_INTERFACE=\n")).unwrap();
+        assert_eq!(config.report_interface, None);
+
+        assert!(parse_config("VM_ID=abc\n").is_none());
+    }
+
+    #[test]
+    fn default_route_interface_from_proc_net_route() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                     ens5\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+                     ens4\t00000000\t0100A8C0\t0003\t0\t0\t0\t00000000\n";
+        assert_eq!(
+            parse_default_route_interface(table).as_deref(),
+            Some("ens4")
+        );
+        assert_eq!(parse_default_route_interface("Iface\tDestination\n"), None);
+    }
+
+    #[test]
+    fn interface_addrs_skip_loopback_and_link_local() {
+        let output = "\
+1: lo    inet 127.0.0.1/8 scope host lo\\       valid_lft forever preferred_lft forever
+1: lo    inet6 ::1/128 scope host \\       valid_lft forever preferred_lft forever
+2: eth0    inet 10.0.0.5/24 brd 10.0.0.255 scope global eth0\\       valid_lft forever preferred_lft forever
+2: eth0    inet6 fe80::1/64 scope link \\       valid_lft forever preferred_lft forever
+3: eth1@if9    inet 10.9.0.7/24 scope global eth1\\       valid_lft forever preferred_lft forever
+3: eth1@if9    inet6 fd00::7/64 scope global \\       valid_lft forever preferred_lft forever
+";
+        let addrs = parse_interface_addrs(output);
+        let pairs: Vec<(&str, &str)> = addrs
+            .iter()
+            .map(|a| (a.interface.as_str(), a.ip.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("eth0", "10.0.0.5"),
+                ("eth1", "10.9.0.7"),
+                ("eth1", "fd00::7"),
+            ]
+        );
+
+        assert_eq!(primary_ip(&addrs, "eth1").as_deref(), Some("10.9.0.7"));
+        assert_eq!(primary_ip(&addrs, "eth0").as_deref(), Some("10.0.0.5"));
+        assert_eq!(primary_ip(&addrs, "ens5"), None);
+    }
+
+    #[tokio::test]
+    async fn ip_reporter_stop_hands_back_the_current_identity_once() {
+        let reporter = IpReporter::default();
+        assert!(reporter.stop().is_none());
+
+        let config = |vm_id: &str| AgentConfig {
+            vm_id: vm_id.to_string(),
+            manager_url: "http://10.0.0.1:18080".to_string(),
+            report_interface: None,
+        };
+        reporter.restart(config("old"));
+        reporter.restart(config("new"));
+        assert_eq!(reporter.stop().map(|c| c.vm_id).as_deref(), Some("new"));
+        assert!(reporter.stop().is_none());
+    }
+
+    #[tokio::test]
+    async fn read_capped_stops_once_output_passes_the_cap() {
+        let (out, err) = read_capped(&b"hello"[..], &b"oops"[..], 9)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (out.as_slice(), err.as_slice()),
+            (&b"hello"[..], &b"oops"[..])
+        );
+
+        assert!(read_capped(&b"hello"[..], &b"oops"[..], 8)
+            .await
+            .unwrap()
+            .is_none());
+        // A stream that never ends is cut off rather than buffered
+        assert!(
+            read_capped(tokio::io::repeat(b'x'), tokio::io::empty(), 64 * 1024)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! `/health`, `/metrics` and `/addresses` over AF_VSOCK, so the host can read
+//! metrics and the guest IP through Firecracker's vsock device before (or
+//! without) the guest network. Only read-only endpoints are served here.
+
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{addresses_body, health_body, sample_metrics, CpuState};
+
+const VSOCK_PORT: u32 = 9000;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Listen in the background; a guest without a vsock device just logs it.
+pub fn spawn(cpu_state: Arc<CpuState>) {
+    std::thread::spawn(move || {
+        let listener = match listen(VSOCK_PORT) {
+            Ok(fd) => fd,
+            Err(e) => {
+                eprintln!("vsock unavailable, serving TCP only: {}", e);
+                return;
+            }
+        };
+        eprintln!("Guest agent listening on vsock port {}", VSOCK_PORT);
+        loop {
+            match accept(&listener) {
+                Ok(stream) => {
+                    let cpu_state = cpu_state.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve(stream, &cpu_state) {
+                            eprintln!("vsock request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    eprintln!("vsock accept failed: {}", e);
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+            }
+        }
+    });
+}
+
+fn listen(port: u32) -> io::Result<OwnedFd> {
+    // SAFETY: plain socket syscalls; the fd is owned as soon as it exists.
+    unsafe {
+        let fd: RawFd = libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        let mut addr: libc::sockaddr_vm = std::mem::zeroed();
+        addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+        addr.svm_cid = libc::VMADDR_CID_ANY;
+        addr.svm_port = port;
+        let raw = fd.as_raw_fd();
+        if libc::bind(
+            raw,
+            &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+        ) < 0
+            || libc::listen(raw, 16) < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
+
+fn accept(listener: &OwnedFd) -> io::Result<UnixStream> {
+    let raw = listener.as_raw_fd();
+    // SAFETY: accept4 on a listening socket we own; the result is owned below.
+    let fd = unsafe {
+        libc::accept4(
+            raw,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            libc::SOCK_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // A connected stream socket reads and writes like any other; UnixStream
+    // only adds the std plumbing (timeouts, Read/Write).
+    // SAFETY: `fd` is a fresh descriptor nothing else owns.
+    Ok(unsafe { UnixStream::from_raw_fd(fd) })
+}
+
+fn serve(mut stream: UnixStream, cpu_state: &CpuState) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        if buf.len() > MAX_REQUEST_BYTES {
+            return respond(&mut stream, "413 Payload Too Large", b"{}");
+        }
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    match request_path(&head) {
+        Some("/health") => {
+            let body = serde_json::to_vec(&health_body()).unwrap_or_default();
+            respond(&mut stream, "200 OK", &body)
+        }
+        Some("/metrics") => {
+            let body = serde_json::to_vec(&sample_metrics(cpu_state)).unwrap_or_default();
+            respond(&mut stream, "200 OK", &body)
+        }
+        Some("/addresses") => {
+            let body = serde_json::to_vec(&addresses_body()).unwrap_or_default();
+            respond(&mut stream, "200 OK", &body)
+        }
+        Some(_) => respond(&mut stream, "404 Not Found", b"{}"),
+        None => respond(&mut stream, "400 Bad Request", b"{}"),
+    }
+}
+
+/// The path of a `GET` request line, query string dropped.
+fn request_path(head: &str) -> Option<&str> {
+    let mut parts = head.lines().next()?.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let target = parts.next()?;
+    parts.next()?.starts_with("HTTP/").then_some(())?;
+    Some(target.split('?').next().unwrap_or(target))
+}
+
+fn respond(stream: &mut UnixStream, status: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_path_reads_get_lines_only() {
+        assert_eq!(
+            request_path("GET /metrics HTTP/1.0\r\nHost: guest\r\n\r\n"),
+            Some("/metrics")
+        );
+        assert_eq!(
+            request_path("GET /health?x=1 HTTP/1.1\r\n\r\n"),
+            Some("/health")
+        );
+        assert_eq!(request_path("POST /run-command HTTP/1.0\r\n\r\n"), None);
+        assert_eq!(request_path("GET /metrics\r\n\r\n"), None);
+        assert_eq!(request_path(""), None);
+    }
+}
@@ -629,6 +629,15 @@ impl App {
     }
 
     pub fn with_config(mut self, config: InstallConfig) -> Self {
+        // Start the selection screens on the configured choices.
+        self.mode_selection = InstallMode::ALL
+            .iter()
+            .position(|m| *m == config.mode)
+            .unwrap_or(0);
+        self.network_mode_selection = NetworkMode::ALL
+            .iter()
+            .position(|m| *m == config.network_mode)
+            .unwrap_or(0);
         self.config = config;
         self
     }
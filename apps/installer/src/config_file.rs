@@ -0,0 +1,135 @@
+//! `install --config <file>`: installer settings in YAML, so a team can keep
+//! its install in version control. Every key is optional; flags given on the
+//! command line win over the file, and the file wins over the defaults.
+//!
+//! ```yaml
+//! mode: production
+//! network_mode: bridged
+//! bridge_interface: eno1
+//! db_host: db.internal
+//! with_docker: false
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::app::InstallConfig;
+use crate::{CliInstallMode, CliNetworkMode};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub mode: Option<CliInstallMode>,
+    pub install_dir: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub config_dir: Option<PathBuf>,
+    pub log_dir: Option<PathBuf>,
+    pub network_mode: Option<CliNetworkMode>,
+    pub bridge_name: Option<String>,
+    pub bridge_interface: Option<String>,
+    pub db_host: Option<String>,
+    pub db_port: Option<u16>,
+    pub db_name: Option<String>,
+    pub db_user: Option<String>,
+    pub db_password: Option<String>,
+    pub with_ui: Option<bool>,
+    pub with_container_runtime: Option<bool>,
+    pub with_docker: Option<bool>,
+    pub airgap: Option<bool>,
+    pub bundle_path: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    fn parse(raw: &str) -> Result<Self> {
+        // An empty file is an empty config, not a parse error.
+        if raw.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        // serde_yaml reports the offending key and its line.
+        let file: Self = serde_yaml::from_str(raw)?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let paths = [
+            ("install_dir", &self.install_dir),
+            ("data_dir", &self.data_dir),
+            ("config_dir", &self.config_dir),
+            ("log_dir", &self.log_dir),
+            ("bundle_path", &self.bundle_path),
+        ];
+        for (key, path) in paths {
+            match path {
+                Some(path) if path.as_os_str().is_empty() => bail!("{key}: must not be empty"),
+                Some(path) if !path.is_absolute() => {
+                    bail!("{key}: must be an absolute path, got {}", path.display())
+                }
+                _ => {}
+            }
+        }
+        let names = [
+            ("bridge_name", &self.bridge_name),
+            ("bridge_interface", &self.bridge_interface),
+            ("db_host", &self.db_host),
+            ("db_name", &self.db_name),
+            ("db_user", &self.db_user),
+        ];
+        for (key, value) in names {
+            if value.as_deref().is_some_and(|v| v.trim().is_empty()) {
+                bail!("{key}: must not be empty");
+            }
+        }
+        // Linux interface names are at most 15 bytes.
+        if let Some(bridge) = self.bridge_name.as_deref().filter(|b| b.len() > 15) {
+            bail!("bridge_name: {bridge:?} is longer than 15 characters");
+        }
+        if self.db_port == Some(0) {
+            bail!("db_port: must be greater than 0");
+        }
+        Ok(())
+    }
+
+    /// Overlay the keys the file sets onto `config`.
+    pub fn apply(self, config: &mut InstallConfig) {
+        if let Some(mode) = self.mode {
+            config.mode = mode.into();
+        }
+        if let Some(network_mode) = self.network_mode {
+            config.network_mode = network_mode.into();
+        }
+        if self.bridge_interface.is_some() {
+            config.bridge_interface = self.bridge_interface;
+        }
+        set(&mut config.install_dir, self.install_dir);
+        set(&mut config.data_dir, self.data_dir);
+        set(&mut config.config_dir, self.config_dir);
+        set(&mut config.log_dir, self.log_dir);
+        set(&mut config.bridge_name, self.bridge_name);
+        set(&mut config.db_host, self.db_host);
+        set(&mut config.db_port, self.db_port);
+        set(&mut config.db_name, self.db_name);
+        set(&mut config.db_user, self.db_user);
+        set(&mut config.db_password, self.db_password);
+        set(&mut config.with_ui, self.with_ui);
+        set(
+            &mut config.with_container_runtime,
+            self.with_container_runtime,
+        );
+        set(&mut config.with_docker, self.with_docker);
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
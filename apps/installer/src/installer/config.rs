@@ -28,8 +28,9 @@ fn resolve_external_host(config: &InstallConfig) -> String {
 }
 
 /// Generate a cryptographically-secure 32-byte random key, hex-encoded.
-/// Used for SSO_ENCRYPTION_KEY (AES-256-GCM for OIDC client secrets at rest).
-fn generate_sso_encryption_key() -> String {
+/// Used for SSO_ENCRYPTION_KEY (AES-256-GCM for OIDC client secrets at rest)
+/// and REGISTRY_ENCRYPTION_KEY (stored registry passwords).
+fn generate_encryption_key() -> String {
     let mut bytes = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut bytes);
     hex::encode(bytes)
@@ -133,7 +134,8 @@ fn generate_manager_config(config: &InstallConfig, db_password: &str) -> Result<
     let external_host = resolve_external_host(config);
     let sso_base_url = format!("http://{}:18080", external_host);
     let sso_frontend_url = format!("http://{}:3000", external_host);
-    let sso_encryption_key = generate_sso_encryption_key();
+    let sso_encryption_key = generate_encryption_key();
+    let registry_encryption_key = generate_encryption_key();
 
     let env_content = format!(
         r#"# NQR-MicroVM Manager Configuration
@@ -172,6 +174,9 @@ SSO_FRONTEND_URL={}
 # stored in the database. Generated once at install; rotating it will invalidate
 # all stored secrets.
 SSO_ENCRYPTION_KEY={}
+# Separate 32-byte key (hex) for stored container registry passwords; stored
+# registry credentials are disabled while it is unset.
+REGISTRY_ENCRYPTION_KEY={}
 # Uncomment for dev environments with self-signed IdP certs:
 # SSO_INSECURE_SKIP_TLS_VERIFY=true
 
@@ -188,6 +193,7 @@ RUST_LOG={}
         sso_base_url,
         sso_frontend_url,
         sso_encryption_key,
+        registry_encryption_key,
         license_section,
         rust_log
     );
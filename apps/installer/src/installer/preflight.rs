@@ -1,6 +1,8 @@
 //! Pre-flight system checks.
 
 use std::fs;
+use std::net::TcpListener;
+use std::os::unix::fs::FileTypeExt;
 use std::path::Path;
 
 #[allow(unused_imports)]
@@ -210,67 +212,104 @@ fn check_systemd() -> CheckItem {
 
 /// Check KVM support
 fn check_kvm_support() -> CheckItem {
-    // Check CPU flags
-    let cpu_flags = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
-    let has_vmx = cpu_flags.contains("vmx");
-    let has_svm = cpu_flags.contains("svm");
-
+    let item = CheckItem::new("KVM Support", "CPU virtualization enabled");
+
+    let cpuinfo = match fs::read_to_string("/proc/cpuinfo") {
+        Ok(cpuinfo) => cpuinfo,
+        Err(e) => {
+            return item
+                .with_status(Status::Error)
+                .with_message(format!("Could not read /proc/cpuinfo: {}", e))
+        }
+    };
+    let (has_vmx, has_svm) = virtualization_flags(&cpuinfo);
     if !has_vmx && !has_svm {
-        return CheckItem::new("KVM Support", "CPU virtualization enabled")
+        return item
             .with_status(Status::Error)
-            .with_message("No vmx/svm CPU flags found");
+            .with_message("No vmx/svm CPU flags found (virtualization disabled in BIOS?)");
     }
+    let kvm_module = if has_vmx { "kvm_intel" } else { "kvm_amd" };
 
-    // Check /dev/kvm
-    if !Path::new("/dev/kvm").exists() {
-        return CheckItem::new("KVM Support", "CPU virtualization enabled")
-            .with_status(Status::Warning)
-            .with_message("CPU supports KVM but /dev/kvm not found (KVM module not loaded?)");
+    match fs::metadata("/dev/kvm") {
+        Ok(meta) if meta.file_type().is_char_device() => {}
+        Ok(_) => {
+            return item
+                .with_status(Status::Error)
+                .with_message("/dev/kvm exists but is not a character device")
+        }
+        Err(_) => {
+            return item.with_status(Status::Warning).with_message(format!(
+                "CPU supports KVM but /dev/kvm not found ({} not loaded?)",
+                kvm_module
+            ))
+        }
     }
 
-    // Check KVM module
-    let kvm_module = if has_vmx { "kvm_intel" } else { "kvm_amd" };
-    let modules = fs::read_to_string("/proc/modules").unwrap_or_default();
-
-    if !modules.contains(kvm_module) {
-        return CheckItem::new("KVM Support", "CPU virtualization enabled")
+    // Firecracker opens /dev/kvm read-write; as root this only fails when
+    // the device is unusable.
+    if let Err(e) = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/kvm")
+    {
+        return item
             .with_status(Status::Warning)
-            .with_message(format!("{} module not loaded", kvm_module));
+            .with_message(format!("/dev/kvm not accessible: {}", e));
     }
 
-    CheckItem::new("KVM Support", "CPU virtualization enabled")
-        .with_status(Status::Success)
-        .with_message(format!(
-            "{} ({})",
-            if has_vmx { "Intel VT-x" } else { "AMD-V" },
-            kvm_module
-        ))
+    item.with_status(Status::Success).with_message(format!(
+        "{} (/dev/kvm ready)",
+        if has_vmx { "Intel VT-x" } else { "AMD-V" }
+    ))
+}
+
+/// Whether any CPU in `/proc/cpuinfo` advertises `vmx` (Intel) or `svm` (AMD).
+fn virtualization_flags(cpuinfo: &str) -> (bool, bool) {
+    let flags = cpuinfo
+        .lines()
+        .filter(|l| l.starts_with("flags"))
+        .filter_map(|l| l.split_once(':'))
+        .flat_map(|(_, flags)| flags.split_whitespace());
+    let (mut vmx, mut svm) = (false, false);
+    for flag in flags {
+        vmx |= flag == "vmx";
+        svm |= flag == "svm";
+    }
+    (vmx, svm)
 }
 
 /// Check available memory
 fn check_memory() -> CheckItem {
-    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
-    let total_kb: u64 = meminfo
-        .lines()
-        .find(|l| l.starts_with("MemTotal:"))
-        .and_then(|l| l.split_whitespace().nth(1))
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(0);
+    let item = CheckItem::new("Memory", "Minimum 2GB RAM");
+    let total_kb = fs::read_to_string("/proc/meminfo")
+        .ok()
+        .and_then(|meminfo| mem_total_kb(&meminfo));
+    let Some(total_kb) = total_kb else {
+        return item
+            .with_status(Status::Error)
+            .with_message("Could not read MemTotal from /proc/meminfo");
+    };
 
     let total_mb = total_kb / 1024;
     let total_gb = total_mb as f64 / 1024.0;
 
     if total_mb >= 2048 {
-        CheckItem::new("Memory", "Minimum 2GB RAM")
-            .with_status(Status::Success)
+        item.with_status(Status::Success)
             .with_message(format!("{:.1} GB available", total_gb))
     } else {
-        CheckItem::new("Memory", "Minimum 2GB RAM")
-            .with_status(Status::Error)
+        item.with_status(Status::Error)
             .with_message(format!("{:.1} GB available (need 2GB+)", total_gb))
     }
 }
 
+fn mem_total_kb(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find(|l| l.starts_with("MemTotal:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+}
+
 /// Check available disk space
 fn check_disk_space() -> CheckItem {
     // Check /srv or / for available space
@@ -324,22 +363,22 @@ fn check_required_commands() -> CheckItem {
     }
 }
 
-/// Check if a port is available
+/// Check if a port is available by binding it. A port held by an earlier
+/// install is expected on re-runs, so it is only a warning.
 fn check_port_available(port: u16, name: &str) -> CheckItem {
-    if let Ok(output) = run_command("ss", &["-tlnp"]) {
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let port_str = format!(":{}", port);
-
-        if output_str.lines().any(|l| l.contains(&port_str)) {
-            return CheckItem::new(format!("Port {}", port), format!("{} port", name))
-                .with_status(Status::Warning)
-                .with_message("Port already in use");
+    let item = CheckItem::new(format!("Port {}", port), format!("{} port", name));
+    match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => {
+            drop(listener);
+            item.with_status(Status::Success).with_message("Available")
         }
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => item
+            .with_status(Status::Warning)
+            .with_message("Port already in use"),
+        Err(e) => item
+            .with_status(Status::Error)
+            .with_message(format!("Cannot bind: {}", e)),
     }
-
-    CheckItem::new(format!("Port {}", port), format!("{} port", name))
-        .with_status(Status::Success)
-        .with_message("Available")
 }
 
 // Helper structs and functions
@@ -7,6 +7,7 @@
 #![allow(clippy::collapsible_match, clippy::unnecessary_sort_by)]
 
 mod app;
+mod config_file;
 mod installer;
 mod theme;
 mod ui;
@@ -26,6 +27,7 @@ use crossterm::{
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 use crate::app::{App, InstallConfig, InstallMode, InstallSource, NetworkMode, Screen};
+use crate::config_file::ConfigFile;
 
 /// NQR-MicroVM Installer - Rust Firecracker MicroVM Platform by Nexus
 #[derive(Parser)]
@@ -42,63 +44,64 @@ struct Cli {
 enum Commands {
     /// Install NQR-MicroVM
     Install {
-        /// Installation mode
-        #[arg(long, value_enum, default_value = "production")]
-        mode: CliInstallMode,
+        /// Installation mode [default: production]
+        #[arg(long, value_enum)]
+        mode: Option<CliInstallMode>,
 
-        /// Installation directory for binaries
-        #[arg(long, default_value = "/opt/nqrust-microvm")]
-        install_dir: PathBuf,
+        /// Installation directory for binaries [default: /opt/nqrust-microvm]
+        #[arg(long)]
+        install_dir: Option<PathBuf>,
 
-        /// Data directory for VMs and images
-        #[arg(long, default_value = "/srv/fc")]
-        data_dir: PathBuf,
+        /// Data directory for VMs and images [default: /srv/fc]
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
 
-        /// Configuration directory
-        #[arg(long, default_value = "/etc/nqrust-microvm")]
-        config_dir: PathBuf,
+        /// Configuration directory [default: /etc/nqrust-microvm]
+        #[arg(long)]
+        config_dir: Option<PathBuf>,
 
-        /// Network mode (nat or bridged)
-        #[arg(long, value_enum, default_value = "nat")]
-        network_mode: CliNetworkMode,
+        /// Network mode [default: nat]
+        #[arg(long, value_enum)]
+        network_mode: Option<CliNetworkMode>,
 
-        /// Bridge name
-        #[arg(long, default_value = "fcbr0")]
-        bridge_name: String,
+        /// Bridge name [default: fcbr0]
+        #[arg(long)]
+        bridge_name: Option<String>,
 
         /// Physical interface for bridged mode (auto-detected if not specified)
         #[arg(long)]
         bridge_interface: Option<String>,
 
-        /// Database host
-        #[arg(long, default_value = "localhost")]
-        db_host: String,
+        /// Database host [default: localhost]
+        #[arg(long)]
+        db_host: Option<String>,
 
-        /// Database port
-        #[arg(long, default_value = "5432")]
-        db_port: u16,
+        /// Database port [default: 5432]
+        #[arg(long)]
+        db_port: Option<u16>,
 
         /// Database password (will be generated if not provided)
         #[arg(long)]
         db_password: Option<String>,
 
-        /// Include Web UI
-        #[arg(long, default_value = "true")]
-        with_ui: bool,
+        /// Include Web UI [default: true]
+        #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+        with_ui: Option<bool>,
 
-        /// Include container runtime (Docker-in-VM support, ~500MB download)
-        #[arg(long, default_value = "true")]
-        with_container_runtime: bool,
+        /// Include container runtime (Docker-in-VM support, ~500MB download) [default: true]
+        #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+        with_container_runtime: Option<bool>,
 
-        /// Install Docker (for DockerHub image pulling and container features)
-        #[arg(long, default_value = "true")]
-        with_docker: bool,
+        /// Install Docker (for DockerHub image pulling and container features) [default: true]
+        #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+        with_docker: Option<bool>,
 
         /// Non-interactive mode
         #[arg(long)]
         non_interactive: bool,
 
-        /// Configuration file (YAML)
+        /// Configuration file (YAML). Uses the same keys as the flags, in
+        /// snake_case; flags given on the command line override it.
         #[arg(long)]
         config: Option<PathBuf>,
 
@@ -112,9 +115,9 @@ enum Commands {
         #[arg(long, alias = "iso-mode")]
         airgap: bool,
 
-        /// Path to the pre-bundled files for ISO mode (default: /opt/nqrust-bundle)
-        #[arg(long, default_value = "/opt/nqrust-bundle")]
-        bundle_path: PathBuf,
+        /// Path to the pre-bundled files for ISO mode [default: /opt/nqrust-bundle]
+        #[arg(long)]
+        bundle_path: Option<PathBuf>,
     },
     /// Uninstall NQR-MicroVM
     Uninstall {
@@ -163,7 +166,8 @@ enum Commands {
     },
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum CliInstallMode {
     Production,
     Dev,
@@ -184,7 +188,8 @@ impl From<CliInstallMode> for InstallMode {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum CliNetworkMode {
     Nat,
     Bridged,
@@ -220,41 +225,57 @@ fn main() -> Result<()> {
             with_container_runtime,
             with_docker,
             non_interactive,
-            config: _config_file,
+            config: config_file,
             debug: _debug,
             airgap,
             bundle_path,
         }) => {
+            // Defaults, then the config file, then the flags given.
+            let mut config = InstallConfig {
+                network_mode: NetworkMode::Nat,
+                non_interactive,
+                ..InstallConfig::default()
+            };
+            let file = match &config_file {
+                Some(path) => ConfigFile::load(path)?,
+                None => ConfigFile::default(),
+            };
+            let airgap = airgap || file.airgap.unwrap_or(false);
+            let bundle_path = bundle_path
+                .or_else(|| file.bundle_path.clone())
+                .unwrap_or_else(|| PathBuf::from("/opt/nqrust-bundle"));
+            file.apply(&mut config);
+
+            if let Some(mode) = mode {
+                config.mode = mode.into();
+            }
+            if let Some(network_mode) = network_mode {
+                config.network_mode = network_mode.into();
+            }
+            if bridge_interface.is_some() {
+                config.bridge_interface = bridge_interface;
+            }
+            config.install_dir = install_dir.unwrap_or(config.install_dir);
+            config.data_dir = data_dir.unwrap_or(config.data_dir);
+            config.config_dir = config_dir.unwrap_or(config.config_dir);
+            config.bridge_name = bridge_name.unwrap_or(config.bridge_name);
+            config.db_host = db_host.unwrap_or(config.db_host);
+            config.db_port = db_port.unwrap_or(config.db_port);
+            config.db_password = db_password.unwrap_or(config.db_password);
+            config.with_ui = with_ui.unwrap_or(config.with_ui);
+            config.with_container_runtime =
+                with_container_runtime.unwrap_or(config.with_container_runtime);
+            config.with_docker = with_docker.unwrap_or(config.with_docker);
+
             // Determine installation source
-            let install_source = if airgap {
+            config.install_source = if airgap {
                 InstallSource::LocalBundle(bundle_path)
-            } else if mode == CliInstallMode::Dev {
+            } else if config.mode == InstallMode::Development {
                 InstallSource::BuildFromSource
             } else {
                 InstallSource::Download
             };
 
-            let config = InstallConfig {
-                mode: mode.into(),
-                install_source,
-                install_dir,
-                data_dir,
-                config_dir,
-                log_dir: PathBuf::from("/var/log/nqrust-microvm"),
-                network_mode: network_mode.into(),
-                bridge_name,
-                bridge_interface,
-                db_host,
-                db_port,
-                db_name: "nqrust".to_string(),
-                db_user: "nqrust".to_string(),
-                db_password: db_password.unwrap_or_default(),
-                with_ui,
-                with_container_runtime,
-                with_docker,
-                non_interactive,
-            };
-
             if non_interactive {
                 run_non_interactive(config)
             } else {
@@ -502,8 +523,6 @@ fn handle_disk_select_input(app: &mut App, key: KeyCode) {
 
 /// Handle disk config screen input
 fn handle_disk_config_input(app: &mut App, key: KeyCode) {
-    const DISK_CONFIG_FIELD_COUNT: usize = 6;
-
     if app.editing {
         match key {
             KeyCode::Enter => {
@@ -531,14 +550,17 @@ fn handle_disk_config_input(app: &mut App, key: KeyCode) {
                 }
             }
             KeyCode::Down | KeyCode::Char('j') => {
-                if app.disk_config_field < DISK_CONFIG_FIELD_COUNT - 1 {
+                if app.disk_config_field < ui::screens::disk_config::DISK_CONFIG_FIELD_COUNT - 1 {
                     app.disk_config_field += 1;
                 }
             }
             KeyCode::Char('e') | KeyCode::Char(' ') => {
-                // Start editing the current field
-                app.editing = true;
-                app.input_buffer = get_disk_config_field_value(app);
+                // Choices cycle in place; the rest open for editing
+                if !ui::screens::disk_config::toggle_disk_config_field(app) {
+                    app.editing = true;
+                    app.input_buffer =
+                        ui::screens::disk_config::get_disk_field_value(app, app.disk_config_field);
+                }
             }
             KeyCode::Enter => {
                 // Continue to disk progress and start installation
@@ -552,24 +574,6 @@ fn handle_disk_config_input(app: &mut App, key: KeyCode) {
     }
 }
 
-/// Get current disk config field value for editing
-fn get_disk_config_field_value(app: &App) -> String {
-    match app.disk_config_field {
-        0 => app.disk_hostname.clone(),
-        1 => app.disk_root_password.clone(),
-        2 => app.config.network_mode.name().to_string(),
-        3 => app.config.bridge_name.clone(),
-        4 => if app.config.with_docker { "Yes" } else { "No" }.to_string(),
-        5 => if app.config.with_container_runtime {
-            "Yes"
-        } else {
-            "No"
-        }
-        .to_string(),
-        _ => String::new(),
-    }
-}
-
 fn handle_disk_progress_input(app: &mut App, key: KeyCode) {
     match key {
         KeyCode::Enter => {
@@ -766,7 +770,7 @@ fn handle_config_input(app: &mut App, key: KeyCode) {
     if app.editing {
         match key {
             KeyCode::Enter => {
-                apply_config_field(app);
+                ui::screens::config::apply_config_field(app);
                 app.editing = false;
                 app.input_buffer.clear();
             }
@@ -790,15 +794,16 @@ fn handle_config_input(app: &mut App, key: KeyCode) {
                 }
             }
             KeyCode::Down | KeyCode::Char('j') => {
-                if app.config_field < 8 {
-                    // CONFIG_FIELDS.len() - 1 (9 fields, indices 0..=8)
+                if app.config_field < ui::screens::config::CONFIG_FIELD_COUNT - 1 {
                     app.config_field += 1;
                 }
             }
             KeyCode::Char('e') | KeyCode::Char(' ') => {
-                // Start editing the selected field
-                app.editing = true;
-                app.input_buffer = get_current_field_value(app);
+                // Yes/No fields flip in place; the rest open for editing
+                if !ui::screens::config::toggle_config_field(app) {
+                    app.editing = true;
+                    app.input_buffer = ui::screens::config::get_field_value(app, app.config_field);
+                }
             }
             KeyCode::Enter => {
                 // Continue to next screen and run preflight checks
@@ -813,10 +818,11 @@ fn handle_config_input(app: &mut App, key: KeyCode) {
 }
 
 fn handle_preflight_input(app: &mut App, key: KeyCode) {
-    let can_continue = app
-        .preflight_checks
-        .iter()
-        .all(|c| c.status != app::Status::Error && c.status != app::Status::InProgress);
+    let can_continue = !app.preflight_checks.is_empty()
+        && app
+            .preflight_checks
+            .iter()
+            .all(|c| c.status != app::Status::Error && c.status != app::Status::InProgress);
 
     match key {
         KeyCode::Enter if can_continue => {
@@ -908,63 +914,6 @@ fn handle_error_input(app: &mut App, key: KeyCode) {
     }
 }
 
-fn get_current_field_value(app: &App) -> String {
-    match app.config_field {
-        0 => app.config.install_dir.display().to_string(),
-        1 => app.config.data_dir.display().to_string(),
-        2 => app.config.config_dir.display().to_string(),
-        3 => app.config.db_host.clone(),
-        4 => app.config.db_port.to_string(),
-        5 => app.config.db_name.clone(),
-        6 => app.config.db_user.clone(),
-        7 => {
-            if app.config.with_docker {
-                "Yes".to_string()
-            } else {
-                "No".to_string()
-            }
-        }
-        8 => {
-            if app.config.with_container_runtime {
-                "Yes".to_string()
-            } else {
-                "No".to_string()
-            }
-        }
-        _ => String::new(),
-    }
-}
-
-fn apply_config_field(app: &mut App) {
-    let value = app.input_buffer.clone();
-    match app.config_field {
-        0 => app.config.install_dir = PathBuf::from(&value),
-        1 => app.config.data_dir = PathBuf::from(&value),
-        2 => app.config.config_dir = PathBuf::from(&value),
-        3 => app.config.db_host = value,
-        4 => {
-            if let Ok(port) = value.parse() {
-                app.config.db_port = port;
-            }
-        }
-        5 => app.config.db_name = value,
-        6 => app.config.db_user = value,
-        7 => {
-            // Toggle Docker installation (yes/no/y/n)
-            let lower = value.to_lowercase();
-            app.config.with_docker =
-                lower == "yes" || lower == "y" || lower == "true" || lower == "1";
-        }
-        8 => {
-            // Toggle container runtime installation (yes/no/y/n)
-            let lower = value.to_lowercase();
-            app.config.with_container_runtime =
-                lower == "yes" || lower == "y" || lower == "true" || lower == "1";
-        }
-        _ => {}
-    }
-}
-
 fn run_preflight_checks(app: &mut App) {
     use installer::preflight;
 
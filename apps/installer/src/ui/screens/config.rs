@@ -7,6 +7,8 @@ use ratatui::{
     Frame,
 };
 
+use std::path::PathBuf;
+
 use crate::{app::App, theme::styles};
 
 /// Configuration fields
@@ -19,10 +21,15 @@ const CONFIG_FIELDS: &[(&str, &str)] = &[
     ("Database Port", "5432"),
     ("Database Name", "nqrust"),
     ("Database User", "nqrust"),
+    ("Database Password", "Generated when left empty"),
+    ("Install Web UI", "Yes/No"),
     ("Install Docker", "Yes/No (for DockerHub features)"),
     ("Container Runtime", "Yes/No (Docker-in-VM support)"),
 ];
 
+pub const CONFIG_FIELD_COUNT: usize = CONFIG_FIELDS.len();
+const PASSWORD_FIELD: usize = 7;
+
 pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     // Create outer block
     let block = Block::default()
@@ -137,7 +144,7 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
             Span::styled("↑/↓", styles::key_hint()),
             Span::styled(" Navigate  ", styles::muted()),
             Span::styled("e/Space", styles::key_hint()),
-            Span::styled(" Edit  ", styles::muted()),
+            Span::styled(" Edit/Toggle  ", styles::muted()),
             Span::styled("Enter", styles::key_hint()),
             Span::styled(" Continue  ", styles::muted()),
             Span::styled("Esc", styles::key_hint()),
@@ -185,6 +192,11 @@ fn render_field(
     } else {
         value.to_string()
     };
+    let display_value = if label == CONFIG_FIELDS[PASSWORD_FIELD].0 {
+        mask_password(&display_value, editing)
+    } else {
+        display_value
+    };
 
     let value_block = Block::default()
         .borders(Borders::ALL)
@@ -201,7 +213,23 @@ fn render_field(
     frame.render_widget(value_text, chunks[1]);
 }
 
-fn get_field_value(app: &App, field: usize) -> String {
+/// Never shows the password itself, while typing or after.
+fn mask_password(value: &str, editing: bool) -> String {
+    if editing {
+        let typed = value.trim_end_matches('_').chars().count();
+        format!("{}_", "*".repeat(typed))
+    } else if value.is_empty() {
+        "(generated)".to_string()
+    } else {
+        "*".repeat(8)
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "Yes" } else { "No" }.to_string()
+}
+
+pub fn get_field_value(app: &App, field: usize) -> String {
     match field {
         0 => app.config.install_dir.display().to_string(),
         1 => app.config.data_dir.display().to_string(),
@@ -210,20 +238,43 @@ fn get_field_value(app: &App, field: usize) -> String {
         4 => app.config.db_port.to_string(),
         5 => app.config.db_name.clone(),
         6 => app.config.db_user.clone(),
-        7 => {
-            if app.config.with_docker {
-                "Yes".to_string()
-            } else {
-                "No".to_string()
-            }
-        }
-        8 => {
-            if app.config.with_container_runtime {
-                "Yes".to_string()
-            } else {
-                "No".to_string()
+        7 => app.config.db_password.clone(),
+        8 => yes_no(app.config.with_ui),
+        9 => yes_no(app.config.with_docker),
+        10 => yes_no(app.config.with_container_runtime),
+        _ => String::new(),
+    }
+}
+
+/// Flip the selected field if it is a Yes/No toggle. Returns `false` for
+/// text fields, which are edited instead.
+pub fn toggle_config_field(app: &mut App) -> bool {
+    let flag = match app.config_field {
+        8 => &mut app.config.with_ui,
+        9 => &mut app.config.with_docker,
+        10 => &mut app.config.with_container_runtime,
+        _ => return false,
+    };
+    *flag = !*flag;
+    true
+}
+
+/// Apply a config field value from input buffer
+pub fn apply_config_field(app: &mut App) {
+    let value = app.input_buffer.clone();
+    match app.config_field {
+        0 => app.config.install_dir = PathBuf::from(&value),
+        1 => app.config.data_dir = PathBuf::from(&value),
+        2 => app.config.config_dir = PathBuf::from(&value),
+        3 => app.config.db_host = value,
+        4 => {
+            if let Ok(port) = value.parse() {
+                app.config.db_port = port;
             }
         }
-        _ => String::new(),
+        5 => app.config.db_name = value,
+        6 => app.config.db_user = value,
+        7 => app.config.db_password = value,
+        _ => {}
     }
 }
@@ -14,12 +14,14 @@ use crate::{app::App, theme::styles};
 const DISK_CONFIG_FIELDS: &[(&str, &str)] = &[
     ("Hostname", "Host name for the installed system"),
     ("Root Password", "Password for root user"),
-    ("Network Mode", "NAT / Bridged"),
+    ("Network Mode", "Bridged / NAT / Isolated"),
     ("Bridge Name", "Network bridge name (fcbr0)"),
     ("Install Docker", "Yes/No (for DockerHub features)"),
     ("Container Runtime", "Yes/No (Docker-in-VM support)"),
 ];
 
+pub const DISK_CONFIG_FIELD_COUNT: usize = DISK_CONFIG_FIELDS.len();
+
 pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     // Create outer block
     let block = Block::default()
@@ -136,7 +138,7 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
             Span::styled("↑/↓", styles::key_hint()),
             Span::styled(" Navigate  ", styles::muted()),
             Span::styled("e/Space", styles::key_hint()),
-            Span::styled(" Edit  ", styles::muted()),
+            Span::styled(" Edit/Toggle  ", styles::muted()),
             Span::styled("Enter", styles::key_hint()),
             Span::styled(" Start Install  ", styles::muted()),
             Span::styled("Esc", styles::key_hint()),
@@ -207,7 +209,7 @@ fn render_field(
     frame.render_widget(value_text, chunks[1]);
 }
 
-fn get_disk_field_value(app: &App, field: usize) -> String {
+pub fn get_disk_field_value(app: &App, field: usize) -> String {
     match field {
         0 => app.disk_hostname.clone(),
         1 => app.disk_root_password.clone(),
@@ -224,30 +226,32 @@ fn get_disk_field_value(app: &App, field: usize) -> String {
     }
 }
 
+/// Cycle or flip the selected field if it is a choice rather than text.
+/// Returns `false` for text fields, which are edited instead.
+pub fn toggle_disk_config_field(app: &mut App) -> bool {
+    match app.disk_config_field {
+        2 => {
+            let modes = crate::app::NetworkMode::ALL;
+            let next = modes
+                .iter()
+                .position(|m| *m == app.config.network_mode)
+                .map_or(0, |i| (i + 1) % modes.len());
+            app.config.network_mode = modes[next];
+        }
+        4 => app.config.with_docker = !app.config.with_docker,
+        5 => app.config.with_container_runtime = !app.config.with_container_runtime,
+        _ => return false,
+    }
+    true
+}
+
 /// Apply a disk config field value from input buffer
 pub fn apply_disk_config_field(app: &mut App) {
     let value = app.input_buffer.clone();
     match app.disk_config_field {
         0 => app.disk_hostname = value,
         1 => app.disk_root_password = value,
-        2 => {
-            app.config.network_mode = if value.to_lowercase() == "bridged" {
-                crate::app::NetworkMode::Bridged
-            } else {
-                crate::app::NetworkMode::Nat
-            };
-        }
         3 => app.config.bridge_name = value,
-        4 => {
-            let lower = value.to_lowercase();
-            app.config.with_docker =
-                lower == "yes" || lower == "y" || lower == "true" || lower == "1";
-        }
-        5 => {
-            let lower = value.to_lowercase();
-            app.config.with_container_runtime =
-                lower == "yes" || lower == "y" || lower == "true" || lower == "1";
-        }
         _ => {}
     }
 }
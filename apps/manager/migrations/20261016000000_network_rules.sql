@@ -0,0 +1,15 @@
+-- Per-network firewall (security group) rules, applied by the agent as an
+-- iptables chain scoped to the network's bridge.
+CREATE TABLE IF NOT EXISTS network_rule (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  network_id UUID NOT NULL REFERENCES network(id) ON DELETE CASCADE,
+  direction TEXT NOT NULL CHECK (direction IN ('ingress', 'egress')),
+  protocol TEXT NOT NULL CHECK (protocol IN ('tcp', 'udp', 'icmp', 'all')),
+  port_start INTEGER CHECK (port_start IS NULL OR (port_start BETWEEN 1 AND 65535)),
+  port_end INTEGER CHECK (port_end IS NULL OR (port_end BETWEEN 1 AND 65535)),
+  cidr TEXT,
+  action TEXT NOT NULL CHECK (action IN ('accept', 'drop')),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_network_rule_network ON network_rule(network_id);
@@ -0,0 +1,3 @@
+-- Extra kernel command-line parameters chosen at create time. Merged into
+-- the base Firecracker boot args on every (re)configure.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS boot_args TEXT;
@@ -0,0 +1,4 @@
+-- Firecracker machine-config chosen at create time, reapplied on restart.
+-- NULL means the Firecracker default (SMT off, no hugepages).
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS smt BOOLEAN;
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS huge_pages TEXT;
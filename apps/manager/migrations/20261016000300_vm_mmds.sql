@@ -0,0 +1,8 @@
+-- Last MMDS config and data pushed to each VM, so they survive a restart of
+-- the Firecracker process.
+CREATE TABLE IF NOT EXISTS vm_mmds (
+  vm_id UUID PRIMARY KEY REFERENCES vm(id) ON DELETE CASCADE,
+  config JSONB,
+  data JSONB,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
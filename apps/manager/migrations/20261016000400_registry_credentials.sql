@@ -0,0 +1,11 @@
+-- Named container registry credentials, referenced by name from image
+-- download and container create requests. The password is AES-GCM encrypted
+-- with the manager's encryption key and never leaves the manager.
+CREATE TABLE IF NOT EXISTS registry_credential (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  name TEXT NOT NULL UNIQUE,
+  server_address TEXT,
+  username TEXT NOT NULL,
+  encrypted_password TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,8 @@
+-- Per-project image quotas. A NULL limit means that dimension is unlimited;
+-- projects without a row are not limited at all.
+CREATE TABLE IF NOT EXISTS image_project_quota (
+  project TEXT PRIMARY KEY,
+  max_bytes BIGINT CHECK (max_bytes IS NULL OR max_bytes >= 0),
+  max_count BIGINT CHECK (max_count IS NULL OR max_count >= 0),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,63 @@
+-- Roll raw 10-second metric samples up into per-minute rows once they are
+-- old enough, so long retention periods stay cheap to store and query.
+-- `rolled_up` marks the per-minute rows so they are never rolled up twice.
+ALTER TABLE metrics.host_metrics ADD COLUMN IF NOT EXISTS rolled_up BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE metrics.vm_metrics ADD COLUMN IF NOT EXISTS rolled_up BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE metrics.container_metrics ADD COLUMN IF NOT EXISTS rolled_up BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS idx_host_metrics_raw ON metrics.host_metrics (recorded_at) WHERE NOT rolled_up;
+CREATE INDEX IF NOT EXISTS idx_vm_metrics_raw ON metrics.vm_metrics (recorded_at) WHERE NOT rolled_up;
+CREATE INDEX IF NOT EXISTS idx_container_metrics_raw ON metrics.container_metrics (recorded_at) WHERE NOT rolled_up;
+
+-- Gauges are averaged per minute; cumulative byte counters keep the last
+-- (largest) value of the minute. The cutoff is truncated to the minute so a
+-- bucket is always rolled up whole, in a single call.
+CREATE OR REPLACE FUNCTION metrics.downsample_metrics(older_than INTERVAL DEFAULT '24 hours')
+RETURNS void LANGUAGE plpgsql AS $$
+DECLARE
+    cutoff TIMESTAMPTZ := date_trunc('minute', now() - older_than);
+BEGIN
+    WITH raw AS (
+        DELETE FROM metrics.host_metrics
+        WHERE NOT rolled_up AND recorded_at < cutoff
+        RETURNING *
+    )
+    INSERT INTO metrics.host_metrics
+        (host_id, recorded_at, cpu_usage_percent, memory_used_mb, memory_total_mb,
+         disk_used_gb, disk_total_gb, rolled_up)
+    SELECT host_id, date_trunc('minute', recorded_at),
+           avg(cpu_usage_percent), avg(memory_used_mb), avg(memory_total_mb),
+           avg(disk_used_gb), avg(disk_total_gb), true
+    FROM raw
+    GROUP BY host_id, date_trunc('minute', recorded_at);
+
+    WITH raw AS (
+        DELETE FROM metrics.vm_metrics
+        WHERE NOT rolled_up AND recorded_at < cutoff
+        RETURNING *
+    )
+    INSERT INTO metrics.vm_metrics
+        (vm_id, recorded_at, cpu_usage_percent, memory_usage_percent, memory_used_kb,
+         memory_total_kb, load_average, rolled_up)
+    SELECT vm_id, date_trunc('minute', recorded_at),
+           avg(cpu_usage_percent), avg(memory_usage_percent), round(avg(memory_used_kb))::BIGINT,
+           round(avg(memory_total_kb))::BIGINT, avg(load_average), true
+    FROM raw
+    GROUP BY vm_id, date_trunc('minute', recorded_at);
+
+    WITH raw AS (
+        DELETE FROM metrics.container_metrics
+        WHERE NOT rolled_up AND recorded_at < cutoff
+        RETURNING *
+    )
+    INSERT INTO metrics.container_metrics
+        (container_id, recorded_at, cpu_percent, memory_used_mb, memory_limit_mb,
+         network_rx_bytes, network_tx_bytes, block_read_bytes, block_write_bytes, pids, rolled_up)
+    SELECT container_id, date_trunc('minute', recorded_at),
+           avg(cpu_percent), avg(memory_used_mb), avg(memory_limit_mb),
+           max(network_rx_bytes), max(network_tx_bytes), max(block_read_bytes), max(block_write_bytes),
+           round(avg(pids))::INTEGER, true
+    FROM raw
+    GROUP BY container_id, date_trunc('minute', recorded_at);
+END;
+$$;
@@ -0,0 +1,14 @@
+-- Idempotency-Key values seen on POST /v1/vms, so a client retrying a create
+-- after a timeout gets the VM its first attempt made instead of a second one.
+-- `scope` is the caller's user id ('anonymous' without auth); keys from
+-- different users never collide. Rows older than the TTL are pruned on insert.
+CREATE TABLE IF NOT EXISTS vm_create_idempotency (
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    vm_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_vm_create_idempotency_created_at
+    ON vm_create_idempotency (created_at);
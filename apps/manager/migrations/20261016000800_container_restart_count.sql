@@ -0,0 +1,6 @@
+-- Restarts the reconciler has made for a container under its restart_policy.
+-- `restart_count` resets when the container is started by hand or has stayed
+-- up long enough; `last_restart_at` drives the backoff between attempts.
+ALTER TABLE containers
+    ADD COLUMN IF NOT EXISTS restart_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS last_restart_at TIMESTAMPTZ;
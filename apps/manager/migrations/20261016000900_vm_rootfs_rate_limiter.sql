@@ -0,0 +1,3 @@
+-- Rate limiter for the Firecracker rootfs drive, from the create request or
+-- the template. NULL falls back to MANAGER_DEFAULT_ROOTFS_RATE_LIMITER.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS rootfs_rate_limiter JSONB;
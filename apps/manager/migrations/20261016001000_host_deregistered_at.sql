@@ -0,0 +1,4 @@
+-- `DELETE /v1/hosts/{id}?force=true` on a host that still has VMs can't drop
+-- the row (fk_vm_host restricts it), so the host is kept as a tombstone:
+-- hidden from host lists and scheduling until its agent registers again.
+ALTER TABLE host ADD COLUMN IF NOT EXISTS deregistered_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- Set by the reconciler when a host misses enough heartbeats to be declared
+-- unreachable (its VMs get a `host_unreachable` event), cleared once it
+-- heartbeats again.
+ALTER TABLE host ADD COLUMN IF NOT EXISTS unreachable_since TIMESTAMPTZ;
@@ -0,0 +1,10 @@
+-- What a Firecracker snapshot was taken with, so a restore onto a host
+-- running another Firecracker release can be refused up front instead of
+-- failing inside `snapshot/load`. NULL on snapshots that pre-date this.
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS firecracker_version TEXT;
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS vcpu INTEGER;
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS mem_mib INTEGER;
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS cpu_template TEXT;
+
+-- Last CPU template applied through PATCH /v1/vms/{id}/machine-config.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS cpu_template TEXT;
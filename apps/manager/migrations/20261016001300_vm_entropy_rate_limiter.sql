@@ -0,0 +1,3 @@
+-- Per-VM rate limiter for the entropy device configure_vm attaches on boot.
+-- Set from `entropy_rate_limiter` on the create request or the template.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS entropy_rate_limiter JSONB;
@@ -0,0 +1,6 @@
+-- Opt-in automatic balloon sizing per VM; see vms::balloon_policy.
+CREATE TABLE IF NOT EXISTS vm_balloon_policy (
+  vm_id UUID PRIMARY KEY REFERENCES vm(id) ON DELETE CASCADE,
+  policy JSONB NOT NULL,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
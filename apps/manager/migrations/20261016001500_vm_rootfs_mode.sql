@@ -0,0 +1,3 @@
+-- `shared` when the VM boots read-only from the golden image with a writable
+-- overlay drive instead of a private copy. NULL means a copy.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS rootfs_mode TEXT;
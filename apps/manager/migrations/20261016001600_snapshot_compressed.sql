@@ -0,0 +1,2 @@
+-- Whether the snapshot's memory file is stored zstd-compressed (`mem.fc.zst`).
+ALTER TABLE snapshot ADD COLUMN IF NOT EXISTS compressed BOOLEAN NOT NULL DEFAULT FALSE;
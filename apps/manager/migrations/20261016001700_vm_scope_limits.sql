@@ -0,0 +1,4 @@
+-- Host cgroup limits for the VM's systemd scope: CPUQuota= in percent of one
+-- CPU and IOWeight=. NULL leaves the scope unlimited.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS cpu_quota INT;
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS io_weight INT;
@@ -0,0 +1,3 @@
+-- Secret env vars of a function: name -> AES-256-GCM ciphertext. Never
+-- returned by the API; decrypted only to hand to the runtime at invoke time.
+ALTER TABLE function ADD COLUMN IF NOT EXISTS secret_env_vars JSONB;
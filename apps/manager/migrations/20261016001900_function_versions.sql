@@ -0,0 +1,32 @@
+-- Published, immutable snapshots of a function's code and config. The
+-- `function` row itself stays the editable draft ($LATEST).
+CREATE TABLE IF NOT EXISTS function_version (
+    function_id UUID NOT NULL REFERENCES function(id) ON DELETE CASCADE,
+    version INT NOT NULL,
+    code TEXT NOT NULL,
+    handler TEXT NOT NULL,
+    timeout_seconds INT NOT NULL,
+    env_vars JSONB,
+    secret_env_vars JSONB,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (function_id, version)
+);
+
+-- Named pointers at a published version (e.g. prod -> 3). A canary sends
+-- `canary_percent` of invocations to `canary_version` instead.
+CREATE TABLE IF NOT EXISTS function_alias (
+    function_id UUID NOT NULL REFERENCES function(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    version INT NOT NULL,
+    canary_version INT,
+    canary_percent INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (function_id, name),
+    FOREIGN KEY (function_id, version) REFERENCES function_version(function_id, version),
+    FOREIGN KEY (function_id, canary_version) REFERENCES function_version(function_id, version)
+);
+
+-- Which published version served an invocation; NULL is $LATEST.
+ALTER TABLE function_invocation ADD COLUMN IF NOT EXISTS version INT;
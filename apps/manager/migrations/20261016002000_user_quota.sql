@@ -0,0 +1,11 @@
+-- Per-user resource limits, enforced when the user creates VMs, functions or
+-- containers. A NULL limit (or no row) means unlimited.
+CREATE TABLE IF NOT EXISTS user_quota (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    max_vms INT,
+    max_vcpu INT,
+    max_mem_mib BIGINT,
+    max_containers INT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
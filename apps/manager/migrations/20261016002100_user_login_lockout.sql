@@ -0,0 +1,6 @@
+-- Consecutive failed logins per account; reaching the configured threshold
+-- sets locked_until and restarts the count. A successful login or an admin
+-- unlock clears both.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS failed_login_count INT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- The digest the container's image reference resolved to when it was
+-- created (or what the runtime reported after the pull), so a moving tag
+-- still shows exactly what ran. `image` keeps the reference as given.
+ALTER TABLE containers ADD COLUMN IF NOT EXISTS image_digest TEXT;
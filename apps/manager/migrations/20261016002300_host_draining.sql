@@ -0,0 +1,4 @@
+-- Set by the agent's last heartbeat before it shuts down, cleared when it
+-- registers or heartbeats normally again. New VMs are not placed on a
+-- draining host.
+ALTER TABLE host ADD COLUMN IF NOT EXISTS draining_since TIMESTAMPTZ;
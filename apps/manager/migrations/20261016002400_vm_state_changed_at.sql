@@ -0,0 +1,6 @@
+-- Moved by every state transition, so a running VM's uptime is
+-- now() - state_changed_at. Existing rows start from their last update.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS state_changed_at TIMESTAMPTZ;
+UPDATE vm SET state_changed_at = updated_at WHERE state_changed_at IS NULL;
+ALTER TABLE vm ALTER COLUMN state_changed_at SET DEFAULT now();
+ALTER TABLE vm ALTER COLUMN state_changed_at SET NOT NULL;
@@ -0,0 +1,6 @@
+-- When the VMM was last booted (create, start, restart, reschedule), so
+-- uptime doesn't reset on running <-> running-degraded flips. Running rows
+-- start from their last state change, the best guess available.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS started_at TIMESTAMPTZ;
+UPDATE vm SET started_at = state_changed_at
+WHERE started_at IS NULL AND state IN ('running', 'running-degraded');
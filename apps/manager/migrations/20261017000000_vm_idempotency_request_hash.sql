@@ -0,0 +1,4 @@
+-- SHA-256 of the create request a key was first used with, so reusing the
+-- key for a different body is refused instead of replaying the wrong VM.
+-- NULL on keys recorded before this column existed; those match any body.
+ALTER TABLE vm_create_idempotency ADD COLUMN IF NOT EXISTS request_hash TEXT;
@@ -0,0 +1,3 @@
+-- State a VM was in when it was paused (`running` or `running-degraded`), so
+-- resume puts it back there instead of always reporting `running`.
+ALTER TABLE vm ADD COLUMN IF NOT EXISTS paused_from TEXT;
@@ -0,0 +1,12 @@
+-- Registry credentials belong to the user who stored them, and are sealed
+-- with REGISTRY_ENCRYPTION_KEY rather than the SSO key. Rows from before
+-- this have no owner and can't be opened with the new key, so they go;
+-- they have to be stored again.
+DELETE FROM registry_credential;
+
+ALTER TABLE registry_credential
+  ADD COLUMN created_by_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  DROP CONSTRAINT IF EXISTS registry_credential_name_key;
+
+CREATE UNIQUE INDEX IF NOT EXISTS registry_credential_owner_name_idx
+  ON registry_credential (created_by_user_id, name);
@@ -0,0 +1,15 @@
+-- What in-flight creates have claimed against their user's quota. A create
+-- inserts its row under the user's quota lock, then drops the lock and
+-- deletes the row once the resource's own row is in. Rows left by a manager
+-- that died mid-create stop counting after an hour.
+CREATE TABLE IF NOT EXISTS quota_reservation (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  vms BIGINT NOT NULL DEFAULT 0,
+  vcpu BIGINT NOT NULL DEFAULT 0,
+  mem_mib BIGINT NOT NULL DEFAULT 0,
+  containers BIGINT NOT NULL DEFAULT 0,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS quota_reservation_user_idx ON quota_reservation (user_id);
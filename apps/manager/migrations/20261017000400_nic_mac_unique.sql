@@ -0,0 +1,21 @@
+-- A MAC is unique on its network. The service checks before inserting, but
+-- two concurrent attaches can both pass that check; this index is what makes
+-- the second one fail.
+--
+-- Older rows that already collide keep the MAC on the oldest NIC; the others
+-- lose it and get a generated one on their next start.
+UPDATE vm_network_interface nic
+SET guest_mac = NULL
+WHERE guest_mac IS NOT NULL
+  AND network_id IS NOT NULL
+  AND EXISTS (
+      SELECT 1
+      FROM vm_network_interface older
+      WHERE older.network_id = nic.network_id
+        AND lower(older.guest_mac) = lower(nic.guest_mac)
+        AND (older.created_at, older.id) < (nic.created_at, nic.id)
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_vm_nic_network_mac
+ON vm_network_interface(network_id, lower(guest_mac))
+WHERE network_id IS NOT NULL AND guest_mac IS NOT NULL;
@@ -0,0 +1,138 @@
+//! Calls to agents, and through them to Firecracker. Every client carries a
+//! timeout, so a hung agent fails the request instead of holding it open,
+//! and a failed call surfaces the body the agent sent back: Firecracker's
+//! `fault_message` when the agent proxied one, its own message otherwise.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+
+const TIMEOUT_ENV: &str = "MANAGER_AGENT_TIMEOUT_SECS";
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+/// Longest agent or Firecracker message passed on to the user.
+const MAX_MESSAGE_LEN: usize = 1024;
+
+fn timeout() -> Duration {
+    Duration::from_secs(parse_timeout_secs(
+        std::env::var(TIMEOUT_ENV).ok().as_deref(),
+    ))
+}
+
+fn parse_timeout_secs(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+}
+
+/// The only client for agent calls: the `MANAGER_AGENT_TIMEOUT_SECS` timeout
+/// plus the current request id. Calls that legitimately run longer set their
+/// own `.timeout()` on the request. Guests, storage appliances and registries
+/// get a plain `reqwest` client.
+pub fn client() -> reqwest::Client {
+    use super::request_id;
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(value) =
+        request_id::current().and_then(|id| reqwest::header::HeaderValue::from_str(&id).ok())
+    {
+        headers.insert(request_id::HEADER, value);
+    }
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .timeout(timeout())
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// A non-2xx answer from an agent. Survives `.context(..)`; a 400, 404 or
+/// 409 from Firecracker keeps its status in the API response.
+#[derive(Debug)]
+pub struct AgentError {
+    pub status: reqwest::StatusCode,
+    pub message: String,
+}
+
+impl std::fmt::Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "agent returned {}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+/// `error_for_status`, keeping the error body.
+pub trait FaultExt {
+    fn or_fault(self) -> impl Future<Output = Result<reqwest::Response>> + Send;
+}
+
+impl FaultExt for reqwest::Response {
+    async fn or_fault(self) -> Result<reqwest::Response> {
+        let status = self.status();
+        if status.is_success() {
+            return Ok(self);
+        }
+        let body = self.text().await.unwrap_or_default();
+        Err(AgentError {
+            status,
+            message: fault_message(&body),
+        }
+        .into())
+    }
+}
+
+/// Firecracker answers `{"fault_message": ".."}`; agent routes answer plain
+/// text or `{"error": ".."}`.
+fn fault_message(body: &str) -> String {
+    let parsed = serde_json::from_str::<serde_json::Value>(body).ok();
+    let message = parsed
+        .as_ref()
+        .and_then(|v| v.get("fault_message").or_else(|| v.get("error")))
+        .and_then(|v| v.as_str())
+        .unwrap_or(body)
+        .trim();
+    if message.is_empty() {
+        return "no error body".into();
+    }
+    let mut message = message.to_string();
+    if message.len() > MAX_MESSAGE_LEN {
+        let mut cut = MAX_MESSAGE_LEN;
+        while !message.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        message.truncate(cut);
+        message.push('…');
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_timeout_secs(None), 60);
+        assert_eq!(parse_timeout_secs(Some("0")), 60);
+        assert_eq!(parse_timeout_secs(Some(" 15 ")), 15);
+    }
+
+    #[test]
+    fn fault_message_prefers_firecrackers_description() {
+        assert_eq!(
+            fault_message(
+                r#"{"fault_message":"The requested operation is not supported after starting the microVM."}"#
+            ),
+            "The requested operation is not supported after starting the microVM."
+        );
+        assert_eq!(
+            fault_message(r#"{"error":"socket outside run_dir"}"#),
+            "socket outside run_dir"
+        );
+        assert_eq!(
+            fault_message("endpoint not allowed\n"),
+            "endpoint not allowed"
+        );
+        assert_eq!(fault_message(""), "no error body");
+        assert!(fault_message(&"x".repeat(5000)).len() < 1100);
+    }
+}
@@ -0,0 +1,187 @@
+//! Cross-origin policy for the API, built from env.
+//!
+//! `MANAGER_DEV_MODE` keeps the old allow-anything behaviour for local work.
+//! Otherwise only the origins in `MANAGER_CORS_ORIGINS` may call the API
+//! from a browser; unset, that is just the UI (`SSO_FRONTEND_URL`). Methods
+//! and headers are allowlisted too, and credentials are off unless
+//! `MANAGER_CORS_ALLOW_CREDENTIALS` is set.
+
+use anyhow::{bail, Context, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+pub const DEV_MODE_ENV: &str = "MANAGER_DEV_MODE";
+pub const ORIGINS_ENV: &str = "MANAGER_CORS_ORIGINS";
+pub const METHODS_ENV: &str = "MANAGER_CORS_METHODS";
+pub const HEADERS_ENV: &str = "MANAGER_CORS_HEADERS";
+pub const ALLOW_CREDENTIALS_ENV: &str = "MANAGER_CORS_ALLOW_CREDENTIALS";
+
+const DEFAULT_METHODS: &str = "GET,POST,PUT,PATCH,DELETE,OPTIONS";
+const DEFAULT_HEADERS: &str = "authorization,content-type,x-request-id,idempotency-key";
+const MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Either everything (`*`) or an explicit list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Allow<T> {
+    Any,
+    List(Vec<T>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    pub origins: Allow<HeaderValue>,
+    pub methods: Allow<Method>,
+    pub headers: Allow<HeaderName>,
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    /// Reads the `MANAGER_CORS_*` env. `frontend_url` is the UI's origin,
+    /// allowed when no origins are configured outside dev mode.
+    pub fn from_env(frontend_url: &str) -> Result<Self> {
+        Self::parse(|key| std::env::var(key).ok(), frontend_url)
+    }
+
+    /// Like [`Self::from_env`] with `var` standing in for the environment.
+    /// Anything set explicitly wins over the dev-mode defaults.
+    pub fn parse(var: impl Fn(&str) -> Option<String>, frontend_url: &str) -> Result<Self> {
+        let dev_mode = var(DEV_MODE_ENV).is_some_and(|v| is_truthy(&v));
+        let setting = |key: &str, default: &str| -> String {
+            match var(key) {
+                Some(value) => value,
+                None if dev_mode => "*".into(),
+                None => default.into(),
+            }
+        };
+
+        let origins = parse_list(ORIGINS_ENV, &setting(ORIGINS_ENV, frontend_url), |raw| {
+            HeaderValue::from_str(raw.trim_end_matches('/')).ok()
+        })?;
+        let methods = parse_list(METHODS_ENV, &setting(METHODS_ENV, DEFAULT_METHODS), |raw| {
+            Method::from_bytes(raw.to_ascii_uppercase().as_bytes()).ok()
+        })?;
+        let headers = parse_list(HEADERS_ENV, &setting(HEADERS_ENV, DEFAULT_HEADERS), |raw| {
+            HeaderName::from_bytes(raw.to_ascii_lowercase().as_bytes()).ok()
+        })?;
+        let allow_credentials = var(ALLOW_CREDENTIALS_ENV).is_some_and(|v| is_truthy(&v));
+
+        // Browsers refuse credentialed responses with wildcards, and
+        // tower-http panics building such a layer
+        if allow_credentials
+            && (origins == Allow::Any || methods == Allow::Any || headers == Allow::Any)
+        {
+            bail!(
+                "{ALLOW_CREDENTIALS_ENV} cannot be combined with `*` origins, methods or headers; \
+                 list them explicitly"
+            );
+        }
+
+        Ok(Self {
+            origins,
+            methods,
+            headers,
+            allow_credentials,
+        })
+    }
+
+    pub fn layer(&self) -> CorsLayer {
+        let origins = match &self.origins {
+            Allow::Any => AllowOrigin::from(Any),
+            Allow::List(list) => AllowOrigin::list(list.clone()),
+        };
+        let methods = match &self.methods {
+            Allow::Any => AllowMethods::from(Any),
+            Allow::List(list) => AllowMethods::list(list.clone()),
+        };
+        let headers = match &self.headers {
+            Allow::Any => AllowHeaders::from(Any),
+            Allow::List(list) => AllowHeaders::list(list.clone()),
+        };
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .allow_credentials(self.allow_credentials)
+            .expose_headers([HeaderName::from_static(super::request_id::HEADER)])
+            .max_age(MAX_AGE)
+    }
+}
+
+/// `*` or a comma-separated list; an entry that doesn't parse is an error so
+/// a typo can't silently lock the UI out.
+fn parse_list<T>(key: &str, raw: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Allow<T>> {
+    if raw.trim() == "*" {
+        return Ok(Allow::Any);
+    }
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| parse(entry).with_context(|| format!("invalid {key} entry `{entry}`")))
+        .collect::<Result<_>>()
+        .map(Allow::List)
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(env: &[(&str, &str)]) -> Result<CorsConfig> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        CorsConfig::parse(|key| env.get(key).cloned(), "http://ui.example:3000")
+    }
+
+    #[test]
+    fn locked_down_unless_dev_mode_or_configured() {
+        let prod = parse(&[]).unwrap();
+        assert_eq!(
+            prod.origins,
+            Allow::List(vec![HeaderValue::from_static("http://ui.example:3000")])
+        );
+        assert!(matches!(&prod.methods, Allow::List(m) if m.contains(&Method::PATCH)));
+        assert!(!prod.allow_credentials);
+
+        let dev = parse(&[(DEV_MODE_ENV, "true")]).unwrap();
+        assert_eq!(
+            (dev.origins, dev.methods, dev.headers),
+            (Allow::Any, Allow::Any, Allow::Any)
+        );
+
+        let configured = parse(&[
+            (DEV_MODE_ENV, "1"),
+            (ORIGINS_ENV, "https://a.example/, https://b.example"),
+            (METHODS_ENV, "get,post"),
+            (HEADERS_ENV, "Authorization"),
+            (ALLOW_CREDENTIALS_ENV, "yes"),
+        ])
+        .unwrap();
+        assert_eq!(
+            configured.origins,
+            Allow::List(vec![
+                HeaderValue::from_static("https://a.example"),
+                HeaderValue::from_static("https://b.example"),
+            ])
+        );
+        assert_eq!(
+            configured.methods,
+            Allow::List(vec![Method::GET, Method::POST])
+        );
+        assert!(configured.allow_credentials);
+        // Would panic on an invalid credentials combination
+        let _ = configured.layer();
+
+        assert!(parse(&[(ORIGINS_ENV, "*"), (ALLOW_CREDENTIALS_ENV, "on")]).is_err());
+        assert!(parse(&[(HEADERS_ENV, "bad header")]).is_err());
+    }
+}
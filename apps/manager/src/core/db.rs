@@ -1 +1,151 @@
-pub use sqlx::PgPool;
\ No newline at end of file
+//! Postgres connection pool sizing, from env.
+//!
+//! The pool is bounded and waiting for a connection times out, so a burst of
+//! requests fails with "pool timed out" instead of hanging every handler
+//! behind an exhausted pool.
+
+use anyhow::{bail, Context, Result};
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::time::Duration;
+
+pub const MAX_CONNECTIONS_ENV: &str = "MANAGER_DB_MAX_CONNECTIONS";
+pub const MIN_CONNECTIONS_ENV: &str = "MANAGER_DB_MIN_CONNECTIONS";
+pub const ACQUIRE_TIMEOUT_ENV: &str = "MANAGER_DB_ACQUIRE_TIMEOUT_SECS";
+pub const IDLE_TIMEOUT_ENV: &str = "MANAGER_DB_IDLE_TIMEOUT_SECS";
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 20;
+const DEFAULT_MIN_CONNECTIONS: u32 = 1;
+const DEFAULT_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// Idle connections above `min_connections` are closed after this;
+    /// `None` keeps them.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Result<Self> {
+        Self::parse(|key| std::env::var(key).ok())
+    }
+
+    /// Like [`Self::from_env`] with `var` standing in for the environment.
+    /// An idle timeout of 0 disables it.
+    pub fn parse(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let number = |key: &str, default: u64| -> Result<u64> {
+            match var(key) {
+                Some(raw) => raw
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid {key} `{raw}`")),
+                None => Ok(default),
+            }
+        };
+        let max_connections = number(MAX_CONNECTIONS_ENV, DEFAULT_MAX_CONNECTIONS.into())?;
+        let min_connections = number(MIN_CONNECTIONS_ENV, DEFAULT_MIN_CONNECTIONS.into())?;
+        let acquire_timeout = number(ACQUIRE_TIMEOUT_ENV, DEFAULT_ACQUIRE_TIMEOUT.as_secs())?;
+        let idle_timeout = number(IDLE_TIMEOUT_ENV, DEFAULT_IDLE_TIMEOUT.as_secs())?;
+
+        if max_connections == 0 || max_connections > u32::MAX.into() {
+            bail!("{MAX_CONNECTIONS_ENV} must be between 1 and {}", u32::MAX);
+        }
+        if min_connections > max_connections {
+            bail!("{MIN_CONNECTIONS_ENV} ({min_connections}) exceeds {MAX_CONNECTIONS_ENV} ({max_connections})");
+        }
+        if acquire_timeout == 0 {
+            bail!("{ACQUIRE_TIMEOUT_ENV} must be at least 1");
+        }
+        Ok(Self {
+            max_connections: max_connections as u32,
+            min_connections: min_connections as u32,
+            acquire_timeout: Duration::from_secs(acquire_timeout),
+            idle_timeout: (idle_timeout > 0).then(|| Duration::from_secs(idle_timeout)),
+        })
+    }
+
+    pub async fn connect(&self, database_url: &str) -> Result<PgPool> {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .connect(database_url)
+            .await
+            .context("connecting to the database")
+    }
+}
+
+/// Point-in-time pool usage for the Prometheus endpoint. sqlx doesn't report
+/// how many tasks are waiting for a connection; `in_use == max` is when they
+/// start to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+    pub min: u32,
+}
+
+impl PoolStats {
+    pub fn of(pool: &PgPool) -> Self {
+        let options = pool.options();
+        Self {
+            size: pool.size(),
+            idle: pool.num_idle() as u32,
+            max: options.get_max_connections(),
+            min: options.get_min_connections(),
+        }
+    }
+
+    pub fn in_use(&self) -> u32 {
+        self.size.saturating_sub(self.idle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn parse(env: &[(&str, &str)]) -> Result<PoolConfig> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        PoolConfig::parse(|key| env.get(key).cloned())
+    }
+
+    #[test]
+    fn pool_config_defaults_and_validation() {
+        assert_eq!(
+            parse(&[]).unwrap(),
+            PoolConfig {
+                max_connections: 20,
+                min_connections: 1,
+                acquire_timeout: Duration::from_secs(10),
+                idle_timeout: Some(Duration::from_secs(600)),
+            }
+        );
+
+        let tuned = parse(&[
+            (MAX_CONNECTIONS_ENV, "50"),
+            (MIN_CONNECTIONS_ENV, "5"),
+            (ACQUIRE_TIMEOUT_ENV, "3"),
+            (IDLE_TIMEOUT_ENV, "0"),
+        ])
+        .unwrap();
+        assert_eq!(tuned.max_connections, 50);
+        assert_eq!(tuned.acquire_timeout, Duration::from_secs(3));
+        assert_eq!(tuned.idle_timeout, None);
+
+        assert!(parse(&[(MAX_CONNECTIONS_ENV, "0")]).is_err());
+        assert!(parse(&[(MAX_CONNECTIONS_ENV, "4"), (MIN_CONNECTIONS_ENV, "5")]).is_err());
+        assert!(parse(&[(ACQUIRE_TIMEOUT_ENV, "soon")]).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// Machine-readable error category returned alongside the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    BadRequest,
+    Forbidden,
+    Conflict,
+    BadGateway,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::BadGateway => StatusCode::BAD_GATEWAY,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// A service-layer error that picks its own response: a failed validation, a
+/// missing resource, a conflict. Survives `.context(..)`, so the status holds
+/// however far up it travels. `bail!(bad_request(..))` and friends.
+#[derive(Debug)]
+pub struct Rejection {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+pub fn bad_request(message: impl Into<String>) -> Rejection {
+    Rejection {
+        code: ErrorCode::BadRequest,
+        message: message.into(),
+    }
+}
+
+pub fn not_found(message: impl Into<String>) -> Rejection {
+    Rejection {
+        code: ErrorCode::NotFound,
+        message: message.into(),
+    }
+}
+
+pub fn conflict(message: impl Into<String>) -> Rejection {
+    Rejection {
+        code: ErrorCode::Conflict,
+        message: message.into(),
+    }
+}
+
+/// A host agent refused or never answered, and nothing was changed.
+pub fn bad_gateway(message: impl Into<String>) -> Rejection {
+    Rejection {
+        code: ErrorCode::BadGateway,
+        message: message.into(),
+    }
+}
+
+/// Handler error rendered as `{ "error": "...", "code": "..." }`.
+///
+/// Service-layer `anyhow` errors convert with `?`; the code comes from a
+/// typed error in the chain ([`Rejection`], a missing row, an agent's
+/// 4xx, a quota or snapshot conflict). Anything else is a 500 whose cause
+/// goes to the log, not the response.
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody<'a> {
+    error: &'a str,
+    code: ErrorCode,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Forbidden, message)
+    }
+}
+
+fn classify(err: &anyhow::Error) -> ErrorCode {
+    if let Some(rejection) = err.downcast_ref::<Rejection>() {
+        return rejection.code;
+    }
+    if let Some(sqlx::Error::RowNotFound) = err.downcast_ref::<sqlx::Error>() {
+        return ErrorCode::NotFound;
+    }
+    if let Some(agent) = err.downcast_ref::<super::agent_http::AgentError>() {
+        match agent.status.as_u16() {
+            400 => return ErrorCode::BadRequest,
+            404 => return ErrorCode::NotFound,
+            409 => return ErrorCode::Conflict,
+            _ => {}
+        }
+    }
+    if crate::features::users::quota::is_exceeded(err)
+        || crate::features::snapshots::compat::is_incompatible(err)
+        || crate::features::jobs::is_cancelled(err)
+    {
+        return ErrorCode::Conflict;
+    }
+    ErrorCode::Internal
+}
+
+/// The status [`ApiError`] answers `err` with, for handlers that still build
+/// their own body.
+pub fn status_of(err: &anyhow::Error) -> StatusCode {
+    classify(err).status()
+}
+
+/// What a 500 tells the caller: nothing about the cause, just the request
+/// id to find it in the log by.
+fn internal_message() -> String {
+    match super::request_id::current() {
+        Some(id) => format!("internal error (request {id})"),
+        None => "internal error".to_string(),
+    }
+}
+
+impl<E> From<E> for ApiError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        let err = err.into();
+        let code = classify(&err);
+        if code == ErrorCode::Internal {
+            tracing::error!(error = ?err, "request failed");
+            return Self::new(code, internal_message());
+        }
+        Self::new(code, format!("{err:#}"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiErrorBody {
+            error: &self.message,
+            code: self.code,
+        };
+        (self.code.status(), Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn anyhow_errors_map_to_codes() {
+        let code = |err: anyhow::Error| ApiError::from(err).code;
+        assert_eq!(code(sqlx::Error::RowNotFound.into()), ErrorCode::NotFound);
+        assert_eq!(code(not_found("VM not found").into()), ErrorCode::NotFound);
+        assert_eq!(
+            code(anyhow::Error::from(conflict("drive eth1 already exists")).context("add drive")),
+            ErrorCode::Conflict
+        );
+        assert_eq!(
+            code(bad_request("VM must be running").into()),
+            ErrorCode::BadRequest
+        );
+        assert_eq!(code(anyhow!("agent unreachable")), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn wording_alone_never_picks_the_status() {
+        let code = |err: anyhow::Error| ApiError::from(err).code;
+        assert_eq!(
+            code(anyhow!("failed to check tap names in use")),
+            ErrorCode::Internal
+        );
+        assert_eq!(code(anyhow!("sudo password required")), ErrorCode::Internal);
+        assert_eq!(
+            code(anyhow!("template not found on disk")),
+            ErrorCode::Internal
+        );
+    }
+
+    #[test]
+    fn agent_errors_keep_firecrackers_status() {
+        let agent = |status: u16| {
+            anyhow::Error::from(crate::core::agent_http::AgentError {
+                status: reqwest::StatusCode::from_u16(status).unwrap(),
+                message: "Invalid vsock id".into(),
+            })
+            .context("put vsock")
+        };
+        let err = ApiError::from(agent(400));
+        assert_eq!(err.code, ErrorCode::BadRequest);
+        assert!(err.message.contains("Invalid vsock id"));
+        assert_eq!(ApiError::from(agent(502)).code, ErrorCode::Internal);
+    }
+
+    #[test]
+    fn message_keeps_the_context_chain() {
+        let err = ApiError::from(
+            anyhow::Error::from(bad_request("invalid mmds payload")).context("mmds request failed"),
+        );
+        assert_eq!(err.message, "mmds request failed: invalid mmds payload");
+        assert_eq!(err.code.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn internal_errors_keep_their_cause_out_of_the_body() {
+        let err = ApiError::from(
+            anyhow!("password authentication failed for user \"nexus\"").context("db"),
+        );
+        assert_eq!(err.code.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.message, "internal error");
+    }
+}
@@ -1 +1,9 @@
+pub mod agent_http;
+pub mod cors;
+pub mod db;
+pub mod error;
+pub mod pagination;
+pub mod request_id;
+pub mod shutdown;
+
 pub use sqlx::PgPool;
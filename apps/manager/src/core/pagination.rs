@@ -0,0 +1,36 @@
+//! Shared `limit`/`offset` handling for list endpoints.
+
+/// Page size used when the caller does not pass `limit`.
+pub const DEFAULT_PAGE_LIMIT: i64 = 100;
+/// Largest page a caller can request.
+pub const MAX_PAGE_LIMIT: i64 = 1000;
+
+/// Resolve a requested page size into the value bound to SQL `LIMIT`.
+/// `limit=0` asks for everything and maps to `None` (`LIMIT NULL`).
+pub fn page_limit(requested: Option<i64>) -> Option<i64> {
+    match requested {
+        Some(0) => None,
+        Some(limit) => Some(limit.clamp(1, MAX_PAGE_LIMIT)),
+        None => Some(DEFAULT_PAGE_LIMIT),
+    }
+}
+
+pub fn page_offset(requested: Option<i64>) -> i64 {
+    requested.unwrap_or(0).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_defaults_caps_and_zero_means_all() {
+        assert_eq!(page_limit(None), Some(DEFAULT_PAGE_LIMIT));
+        assert_eq!(page_limit(Some(25)), Some(25));
+        assert_eq!(page_limit(Some(50_000)), Some(MAX_PAGE_LIMIT));
+        assert_eq!(page_limit(Some(-3)), Some(1));
+        assert_eq!(page_limit(Some(0)), None);
+        assert_eq!(page_offset(Some(-10)), 0);
+        assert_eq!(page_offset(None), 0);
+    }
+}
@@ -0,0 +1,88 @@
+//! `X-Request-Id` correlation. Every API request gets an id (the client's,
+//! or a fresh UUID), logged on a `request` span around the handler, echoed in
+//! the response and forwarded to agents on the calls made while handling it,
+//! so one user action can be followed through manager, agent and Firecracker
+//! logs.
+
+use std::future::Future;
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use futures::future::Either;
+use tracing::Instrument;
+
+pub const HEADER: &str = "x-request-id";
+/// Client ids longer than this are replaced rather than logged.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request being handled, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+fn accept_client_id(raw: Option<&str>) -> Option<&str> {
+    raw.map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_graphic()))
+}
+
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let id = accept_client_id(req.headers().get(HEADER).and_then(|v| v.to_str().ok()))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path()
+    );
+    let mut resp = REQUEST_ID
+        .scope(id.clone(), next.run(req))
+        .instrument(span)
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(HEADER, value);
+    }
+    resp
+}
+
+/// Carry the current request id and span into a future that is about to be
+/// `tokio::spawn`ed, which would otherwise lose both.
+pub fn propagate<F: Future>(fut: F) -> impl Future<Output = F::Output> {
+    let fut = fut.in_current_span();
+    match current() {
+        Some(id) => Either::Left(REQUEST_ID.scope(id, fut)),
+        None => Either::Right(fut),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_ids_are_kept_only_when_sane() {
+        assert_eq!(accept_client_id(Some(" abc-123 ")), Some("abc-123"));
+        assert_eq!(accept_client_id(Some("")), None);
+        assert_eq!(accept_client_id(Some("has space")), None);
+        assert_eq!(accept_client_id(Some(&"x".repeat(129))), None);
+        assert_eq!(accept_client_id(None), None);
+    }
+
+    #[tokio::test]
+    async fn id_follows_spawned_tasks() {
+        let seen = REQUEST_ID
+            .scope("req-1".to_string(), async {
+                tokio::spawn(propagate(async { current() })).await.unwrap()
+            })
+            .await;
+        assert_eq!(seen.as_deref(), Some("req-1"));
+        assert_eq!(current(), None);
+    }
+}
@@ -0,0 +1,36 @@
+//! Graceful shutdown: on SIGTERM or SIGINT the manager stops accepting
+//! connections, cancels running jobs so half-done creates and migrations
+//! roll back, and waits up to `MANAGER_SHUTDOWN_TIMEOUT_SECS` for in-flight
+//! requests and background tasks before closing the database pool.
+
+use std::time::Duration;
+use tokio::signal::unix::{signal as unix_signal, SignalKind};
+
+pub const TIMEOUT_ENV: &str = "MANAGER_SHUTDOWN_TIMEOUT_SECS";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long in-flight requests get to finish once shutdown starts.
+pub fn timeout_from_env() -> Duration {
+    std::env::var(TIMEOUT_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+/// Resolves on the first SIGTERM or SIGINT.
+pub async fn signal() {
+    let mut terminate = match unix_signal(SignalKind::terminate()) {
+        Ok(stream) => stream,
+        Err(err) => {
+            tracing::warn!(error = ?err, "cannot listen for SIGTERM; only Ctrl-C shuts down gracefully");
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("SIGTERM received"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("SIGINT received"),
+    }
+}
@@ -1,4 +1,6 @@
-use axum::Router;
+use axum::http::{header, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
 use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
 use utoipa::openapi::OpenApi as OpenApiDoc;
 use utoipa::{Modify, OpenApi};
@@ -35,6 +37,8 @@ impl Modify for SecurityAddon {
         crate::features::hosts::routes::list,
         crate::features::hosts::routes::get,
         crate::features::hosts::routes::delete,
+        crate::features::hosts::routes::stop_all_containers,
+        crate::features::hosts::routes::snapshot_all,
         crate::features::templates::routes::create,
         crate::features::templates::routes::list,
         crate::features::templates::routes::get,
@@ -42,6 +46,7 @@ impl Modify for SecurityAddon {
         crate::features::templates::routes::delete,
         crate::features::templates::routes::instantiate,
         crate::features::vms::routes::create,
+        crate::features::vms::routes::validate,
         crate::features::vms::routes::list,
         crate::features::vms::routes::get,
         crate::features::vms::routes::stop,
@@ -55,16 +60,24 @@ impl Modify for SecurityAddon {
         crate::features::vms::routes::get_drive,
         crate::features::vms::routes::update_drive,
         crate::features::vms::routes::delete_drive,
+        crate::features::vms::routes::list_events,
+        crate::features::vms::routes::get_console,
+        crate::features::vms::routes::list_processes,
+        crate::features::vms::routes::get_mmds,
         crate::features::vms::routes::list_nics,
         crate::features::vms::routes::create_nic,
         crate::features::vms::routes::get_nic,
         crate::features::vms::routes::update_nic,
         crate::features::vms::routes::delete_nic,
         crate::features::images::routes::create,
+        crate::features::images::routes::get_quota,
+        crate::features::images::routes::set_quota,
         crate::features::images::routes::list,
         crate::features::images::routes::get,
         crate::features::images::routes::delete,
+        crate::features::images::routes::preload_manifest,
         crate::features::snapshots::routes::create,
+        crate::features::snapshots::routes::list,
         crate::features::snapshots::routes::list_for_vm,
         crate::features::snapshots::routes::get,
         crate::features::snapshots::routes::instantiate,
@@ -74,14 +87,32 @@ impl Modify for SecurityAddon {
         crate::features::functions::routes::update,
         crate::features::functions::routes::delete,
         crate::features::functions::routes::invoke,
+        crate::features::functions::routes::invoke_raw,
         crate::features::functions::routes::logs,
+        crate::features::functions::routes::publish_version,
+        crate::features::functions::routes::list_versions,
+        crate::features::functions::routes::list_aliases,
+        crate::features::functions::routes::put_alias,
+        crate::features::functions::routes::delete_alias,
+        crate::features::overview::routes::get,
+        crate::features::jobs::routes::list,
+        crate::features::jobs::routes::get,
+        crate::features::jobs::routes::cancel,
+        crate::features::registries::routes::create,
+        crate::features::registries::routes::list,
+        crate::features::registries::routes::get,
+        crate::features::registries::routes::delete,
         crate::features::containers::routes::create,
         crate::features::containers::routes::list,
         crate::features::containers::routes::get,
+        crate::features::containers::routes::inspect,
+        crate::features::containers::routes::digest,
+        crate::features::containers::routes::changes,
         crate::features::containers::routes::update,
         crate::features::containers::routes::delete,
         crate::features::containers::routes::start,
         crate::features::containers::routes::stop,
+        crate::features::containers::routes::batch,
         crate::features::containers::routes::restart,
         crate::features::containers::routes::pause,
         crate::features::containers::routes::resume,
@@ -95,6 +126,9 @@ impl Modify for SecurityAddon {
         crate::features::vms::routes::put_balloon,
         crate::features::vms::routes::patch_balloon,
         crate::features::vms::routes::patch_balloon_statistics,
+        crate::features::vms::routes::get_balloon_policy,
+        crate::features::vms::routes::put_balloon_policy,
+        crate::features::vms::routes::delete_balloon_policy,
         crate::features::users::routes::login,
         crate::features::users::routes::me,
         crate::features::users::routes::list,
@@ -102,6 +136,9 @@ impl Modify for SecurityAddon {
         crate::features::users::routes::get,
         crate::features::users::routes::update,
         crate::features::users::routes::delete,
+        crate::features::users::routes::get_quota,
+        crate::features::users::routes::put_quota,
+        crate::features::users::routes::unlock,
     ),
     components(
         schemas(
@@ -120,17 +157,28 @@ impl Modify for SecurityAddon {
             nexus_types::TemplateSpec,
             nexus_types::CreateVmReq,
             nexus_types::CreateVmResponse,
+            nexus_types::ValidateVmResponse,
             nexus_types::ListVmsResponse,
             nexus_types::GetVmResponse,
             nexus_types::Vm,
             nexus_types::CreateImageReq,
             nexus_types::CreateImageResp,
             nexus_types::ListImagesResp,
+            nexus_types::ImageQuota,
+            nexus_types::SetImageQuotaReq,
+            nexus_types::PreloadManifestEntry,
+            nexus_types::PreloadManifestReq,
+            nexus_types::PreloadEntryStatus,
+            nexus_types::PreloadEntryResult,
+            nexus_types::PreloadManifestResp,
             nexus_types::GetImageResp,
             nexus_types::Image,
             nexus_types::CreateSnapshotRequest,
             nexus_types::CreateSnapshotResponse,
+            nexus_types::HostSnapshotResult,
+            nexus_types::HostSnapshotAllResp,
             nexus_types::ListSnapshotsResponse,
+            nexus_types::DeleteSnapshotResponse,
             nexus_types::GetSnapshotResponse,
             nexus_types::Snapshot,
             nexus_types::InstantiateSnapshotReq,
@@ -144,9 +192,17 @@ impl Modify for SecurityAddon {
             nexus_types::CreateNicReq,
             nexus_types::UpdateNicReq,
             nexus_types::ListNicsResponse,
+            nexus_types::VmEvent,
+            nexus_types::ListVmEventsResponse,
+            nexus_types::VmConsoleResponse,
+            nexus_types::GuestProcess,
+            nexus_types::ListGuestProcessesResponse,
+            nexus_types::VmMmds,
             nexus_types::ListVmsResponse,
             nexus_types::LoggerUpdateReq,
             nexus_types::BalloonConfig,
+            nexus_types::BalloonPolicy,
+            nexus_types::RootfsMode,
             nexus_types::BalloonStatsConfig,
             nexus_types::Function,
             nexus_types::FunctionInvocation,
@@ -158,12 +214,36 @@ impl Modify for SecurityAddon {
             nexus_types::ListFunctionsResp,
             nexus_types::GetFunctionResp,
             nexus_types::ListInvocationsResp,
+            nexus_types::FunctionVersion,
+            nexus_types::PublishFunctionVersionReq,
+            nexus_types::ListFunctionVersionsResp,
+            nexus_types::FunctionAlias,
+            nexus_types::PutFunctionAliasReq,
+            nexus_types::ListFunctionAliasesResp,
+            nexus_types::StateCount,
+            nexus_types::ClusterOverview,
+            nexus_types::Job,
+            nexus_types::JobKind,
+            nexus_types::JobStatus,
+            nexus_types::ListJobsResponse,
+            nexus_types::RegistryCredential,
+            nexus_types::CreateRegistryReq,
+            nexus_types::ListRegistriesResp,
             nexus_types::Container,
             nexus_types::CreateContainerReq,
             nexus_types::CreateContainerResp,
             nexus_types::UpdateContainerReq,
+            nexus_types::ContainerBatchAction,
+            nexus_types::ContainerBatchReq,
+            nexus_types::ContainerBatchResult,
+            nexus_types::ContainerBatchResp,
+            nexus_types::ContainerImageDigestResp,
+            nexus_types::ContainerChangeKind,
+            nexus_types::ContainerChange,
+            nexus_types::ContainerChangesResp,
             nexus_types::ListContainersResp,
             nexus_types::GetContainerResp,
+            nexus_types::InspectContainerResp,
             nexus_types::ContainerStats,
             nexus_types::ContainerStatsResp,
             nexus_types::ContainerLog,
@@ -179,6 +259,9 @@ impl Modify for SecurityAddon {
             nexus_types::UpdateUserRequest,
             nexus_types::ListUsersResponse,
             nexus_types::GetUserResponse,
+            nexus_types::UserQuota,
+            nexus_types::UserQuotaUsage,
+            nexus_types::GetUserQuotaResponse,
         )
     ),
     tags(
@@ -189,6 +272,9 @@ impl Modify for SecurityAddon {
         (name = "Snapshots", description = "Snapshot management APIs."),
         (name = "Functions", description = "Serverless function management APIs."),
         (name = "Containers", description = "Docker container orchestration APIs."),
+        (name = "Overview", description = "Cluster-wide dashboard totals."),
+        (name = "Jobs", description = "Status and cancellation of long-running VM operations."),
+        (name = "Registries", description = "Stored container registry credentials."),
         (name = "Logs", description = "Development log utilities."),
         (name = "VM devices", description = "Block and network device management."),
         (name = "Auth", description = "Authentication APIs."),
@@ -197,8 +283,27 @@ impl Modify for SecurityAddon {
 )]
 pub struct ApiDoc;
 
+/// Swagger UI at `/docs`, plus the spec itself at `/openapi.json` and
+/// `/openapi.yaml` for SDK generators. Both are rendered from the in-memory
+/// spec, so they always match the running binary.
 pub fn router(openapi: OpenApiDoc) -> Router {
-    Router::new().merge(SwaggerUi::new("/docs").url("/docs/openapi.json", openapi))
+    let json = Json(openapi.clone());
+    let yaml = match serde_yaml::to_string(&openapi) {
+        Ok(yaml) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/yaml")],
+            yaml,
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/plain")],
+            format!("failed to render OpenAPI YAML: {err}"),
+        ),
+    };
+    Router::new()
+        .route("/openapi.json", get(move || async move { json }))
+        .route("/openapi.yaml", get(move || async move { yaml }))
+        .merge(SwaggerUi::new("/docs").url("/docs/openapi.json", openapi))
 }
 
 pub async fn write_openapi_yaml(openapi: &OpenApiDoc) -> anyhow::Result<()> {
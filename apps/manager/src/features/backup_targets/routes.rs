@@ -2,9 +2,15 @@ use crate::features::backup_targets::envelope;
 use crate::features::backup_targets::repo::{
     BackupTargetRepository, BackupTargetRow, CreateParams,
 };
+use crate::features::users::audit::{response_outcome, Actor};
 use crate::AppState;
-use axum::{extract::Path, http::StatusCode, response::IntoResponse, Extension, Json};
-use nexus_types::{BackupTarget, CreateBackupTargetRequest};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use nexus_types::{AuditAction, BackupTarget, CreateBackupTargetRequest};
 use rand::RngCore;
 use uuid::Uuid;
 
@@ -74,8 +80,23 @@ pub async fn get_one(
 
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateBackupTargetRequest>,
 ) -> impl IntoResponse {
+    let name = req.name.clone();
+    let response = create_target(st, req).await;
+    actor
+        .record_with(
+            AuditAction::CreateBackupTarget,
+            ("backup_target", None),
+            Some(serde_json::json!({"name": name})),
+            &response_outcome(&response),
+        )
+        .await;
+    response
+}
+
+async fn create_target(st: AppState, req: CreateBackupTargetRequest) -> Response {
     let repo = BackupTargetRepository::new(st.db.clone());
 
     let mut target_key = [0u8; 32];
@@ -137,19 +158,42 @@ pub async fn create(
 
 pub async fn update(
     Extension(_st): Extension<AppState>,
-    Path(_id): Path<Uuid>,
+    actor: Actor,
+    Path(id): Path<Uuid>,
     Json(_req): Json<CreateBackupTargetRequest>,
 ) -> impl IntoResponse {
-    (
+    let response = (
         StatusCode::NOT_IMPLEMENTED,
         Json(serde_json::json!({"error":"update not in v1"})),
     )
+        .into_response();
+    actor
+        .record(
+            AuditAction::UpdateBackupTarget,
+            ("backup_target", id),
+            &response_outcome(&response),
+        )
+        .await;
+    response
 }
 
 pub async fn soft_delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
+    let response = delete_target(st, id).await;
+    actor
+        .record(
+            AuditAction::DeleteBackupTarget,
+            ("backup_target", id),
+            &response_outcome(&response),
+        )
+        .await;
+    response
+}
+
+async fn delete_target(st: AppState, id: Uuid) -> Response {
     let repo = BackupTargetRepository::new(st.db.clone());
     match repo.count_backups_for_target(id).await {
         Ok(n) if n > 0 => (
@@ -206,7 +206,7 @@ pub async fn restore_backup(
     let kind_str = backend.kind().as_db_str();
     // Pick a host that supports the chosen backend.
     let candidate_host_id: Option<Uuid> = {
-        let active = st.hosts.list_healthy().await?;
+        let active = st.hosts.list_schedulable().await?;
         let mut chosen = None;
         for h in active {
             let kinds = st
@@ -252,7 +252,7 @@ impl DockerClient {
         let cpu_percent = calculate_cpu_percent(&stats);
 
         // Extract memory stats
-        let memory_used_mb = (stats.memory_stats.usage.unwrap_or(0) / 1024 / 1024) as i64;
+        let memory_used_mb = (memory_used_bytes(&stats.memory_stats) / 1024 / 1024) as i64;
         let memory_limit_mb = (stats.memory_stats.limit.unwrap_or(0) / 1024 / 1024) as i64;
 
         // Extract network stats
@@ -273,6 +273,83 @@ impl DockerClient {
         })
     }
 
+    /// Raw `docker inspect` output, or `None` if Docker doesn't know the container
+    pub async fn inspect(&self, container_id: &str) -> Result<Option<serde_json::Value>> {
+        let url = format!("{}/containers/{}/json", self.base_url, container_id);
+
+        tracing::debug!(container_id = %container_id, "Inspecting container");
+
+        let resp = self.client.get(&url).send().await?;
+
+        if resp.status().as_u16() == 404 {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            let error_text = resp
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Failed to inspect container: {}", error_text);
+        }
+
+        Ok(Some(resp.json().await?))
+    }
+
+    /// Paths the container changed against its image (`docker diff`)
+    pub async fn changes(&self, container_id: &str) -> Result<Vec<nexus_types::ContainerChange>> {
+        use nexus_types::{ContainerChange, ContainerChangeKind};
+
+        #[derive(Deserialize)]
+        struct Change {
+            #[serde(rename = "Path")]
+            path: String,
+            #[serde(rename = "Kind")]
+            kind: u8,
+        }
+
+        let url = format!("{}/containers/{}/changes", self.base_url, container_id);
+
+        tracing::debug!(container_id = %container_id, "Getting container changes");
+
+        let resp = self.client.get(&url).send().await?;
+
+        if !resp.status().is_success() {
+            let error_text = resp
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!("Failed to get container changes: {}", error_text);
+        }
+
+        // Docker answers `null` rather than `[]` when nothing changed
+        let changes: Option<Vec<Change>> = resp.json().await?;
+        changes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|change| {
+                let kind = match change.kind {
+                    0 => ContainerChangeKind::Modified,
+                    1 => ContainerChangeKind::Added,
+                    2 => ContainerChangeKind::Deleted,
+                    other => anyhow::bail!("unknown change kind {other} for {}", change.path),
+                };
+                Ok(ContainerChange {
+                    path: change.path,
+                    kind,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the container's run state, or `None` if Docker doesn't know it
+    pub async fn inspect_state(&self, container_id: &str) -> Result<Option<DockerContainerState>> {
+        let Some(raw) = self.inspect(container_id).await? else {
+            return Ok(None);
+        };
+        let inspect: DockerInspectResponse = serde_json::from_value(raw)?;
+        Ok(Some(inspect.state))
+    }
+
     /// Get container logs
     #[allow(dead_code)]
     pub async fn get_logs(
@@ -449,6 +526,27 @@ impl DockerClient {
         Ok(resp.status().is_success())
     }
 
+    /// The image's `RepoDigests` (`name@sha256:...`); empty for images that
+    /// were loaded from a tarball rather than pulled.
+    pub async fn image_repo_digests(&self, image: &str) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct ImageInspect {
+            #[serde(rename = "RepoDigests", default)]
+            repo_digests: Option<Vec<String>>,
+        }
+
+        let url = format!("{}/images/{}/json", self.base_url, image);
+        let inspect: ImageInspect = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(inspect.repo_digests.unwrap_or_default())
+    }
+
     /// Load a Docker image from a tarball file into the VM's Docker daemon
     /// Uses the Docker POST /images/load API endpoint
     pub async fn load_image_from_tarball(&self, tarball_path: &std::path::Path) -> Result<()> {
@@ -507,33 +605,53 @@ struct CreateContainerResponse {
     id: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct DockerInspectResponse {
+    #[serde(rename = "State")]
+    state: DockerContainerState,
+}
+
 #[derive(Debug, Deserialize)]
 struct DockerStatsResponse {
+    #[serde(default)]
     cpu_stats: CpuStats,
+    #[serde(default)]
     precpu_stats: CpuStats,
+    #[serde(default)]
     memory_stats: MemoryStats,
     #[serde(default)]
     networks: std::collections::HashMap<String, NetworkStats>,
     #[serde(default)]
     blkio_stats: BlkioStats,
+    #[serde(default)]
     pids_stats: PidsStats,
 }
 
 #[derive(Debug, Deserialize, Default)]
 struct CpuStats {
+    #[serde(default)]
     cpu_usage: CpuUsage,
     system_cpu_usage: Option<u64>,
+    #[serde(default)]
+    online_cpus: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Default)]
 struct CpuUsage {
+    #[serde(default)]
     total_usage: u64,
+    /// Only reported on cgroup v1; used to count CPUs when `online_cpus` is missing.
+    #[serde(default)]
+    percpu_usage: Option<Vec<u64>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct MemoryStats {
     usage: Option<u64>,
     limit: Option<u64>,
+    /// Raw cgroup `memory.stat` counters, keyed by name.
+    #[serde(default)]
+    stats: Option<std::collections::HashMap<String, u64>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -544,8 +662,9 @@ struct NetworkStats {
 
 #[derive(Debug, Deserialize, Default)]
 struct BlkioStats {
+    // Docker sends an explicit `null` here when the cgroup has no I/O yet
     #[serde(default)]
-    io_service_bytes_recursive: Vec<BlkioStatEntry>,
+    io_service_bytes_recursive: Option<Vec<BlkioStatEntry>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -554,7 +673,7 @@ struct BlkioStatEntry {
     value: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct PidsStats {
     current: Option<u64>,
 }
@@ -572,6 +691,17 @@ pub struct DockerStats {
     pub pids: Option<i32>,
 }
 
+/// The `State` object of `GET /containers/{id}/json`
+#[derive(Debug, Clone, Deserialize)]
+pub struct DockerContainerState {
+    #[serde(rename = "Running", default)]
+    pub running: bool,
+    #[serde(rename = "ExitCode", default)]
+    pub exit_code: i64,
+    #[serde(rename = "OOMKilled", default)]
+    pub oom_killed: bool,
+}
+
 #[allow(dead_code)]
 pub struct LogEntry {
     pub timestamp: chrono::DateTime<chrono::Utc>,
@@ -594,13 +724,44 @@ fn calculate_cpu_percent(stats: &DockerStatsResponse) -> f32 {
     let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0) as f64
         - stats.precpu_stats.system_cpu_usage.unwrap_or(0) as f64;
 
+    let online_cpus = stats.cpu_stats.online_cpus.unwrap_or_else(|| {
+        stats
+            .cpu_stats
+            .cpu_usage
+            .percpu_usage
+            .as_ref()
+            .map_or(1, |per_cpu| per_cpu.len() as u32)
+    });
+
+    cpu_percent(cpu_delta, system_delta, online_cpus)
+}
+
+/// CPU usage between two samples, the way `docker stats` reports it: the
+/// container's share of all host CPU time, scaled so one busy CPU is 100%.
+fn cpu_percent(cpu_delta: f64, system_delta: f64, online_cpus: u32) -> f32 {
     if system_delta > 0.0 && cpu_delta > 0.0 {
-        (cpu_delta / system_delta * 100.0) as f32
+        (cpu_delta / system_delta * online_cpus.max(1) as f64 * 100.0) as f32
     } else {
         0.0
     }
 }
 
+/// Memory in use excluding reclaimable page cache, matching `docker stats`.
+/// cgroup v2 calls the counter `inactive_file`, v1 `total_inactive_file`.
+fn memory_used_bytes(memory: &MemoryStats) -> u64 {
+    let usage = memory.usage.unwrap_or(0);
+    let inactive = memory
+        .stats
+        .as_ref()
+        .and_then(|s| {
+            s.get("inactive_file")
+                .or_else(|| s.get("total_inactive_file"))
+        })
+        .copied()
+        .unwrap_or(0);
+    usage.saturating_sub(inactive)
+}
+
 fn extract_network_stats(stats: &DockerStatsResponse) -> (i64, i64) {
     let mut total_rx = 0i64;
     let mut total_tx = 0i64;
@@ -617,13 +778,94 @@ fn extract_block_io_stats(stats: &DockerStatsResponse) -> (i64, i64) {
     let mut total_read = 0i64;
     let mut total_write = 0i64;
 
-    for entry in &stats.blkio_stats.io_service_bytes_recursive {
-        match entry.op.as_str() {
-            "Read" => total_read += entry.value as i64,
-            "Write" => total_write += entry.value as i64,
-            _ => {}
+    let entries = stats
+        .blkio_stats
+        .io_service_bytes_recursive
+        .as_deref()
+        .unwrap_or_default();
+    for entry in entries {
+        // cgroup v1 reports "Read"/"Write", v2 "read"/"write"
+        if entry.op.eq_ignore_ascii_case("read") {
+            total_read += entry.value as i64;
+        } else if entry.op.eq_ignore_ascii_case("write") {
+            total_write += entry.value as i64;
         }
     }
 
     (total_read, total_write)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_percent_scales_by_online_cpus() {
+        // Half of all host CPU time on a 4-CPU guest is two busy CPUs
+        assert_eq!(cpu_percent(500.0, 1000.0, 4), 200.0);
+        assert_eq!(cpu_percent(250.0, 1000.0, 1), 25.0);
+        // No elapsed time or no usage reads as idle, and 0 CPUs counts as 1
+        assert_eq!(cpu_percent(100.0, 0.0, 4), 0.0);
+        assert_eq!(cpu_percent(0.0, 1000.0, 4), 0.0);
+        assert_eq!(cpu_percent(100.0, 1000.0, 0), 10.0);
+    }
+
+    #[test]
+    fn parses_cgroup_v2_stats() {
+        let stats: DockerStatsResponse = serde_json::from_value(serde_json::json!({
+            "cpu_stats": {
+                "cpu_usage": {"total_usage": 3_000_000},
+                "system_cpu_usage": 20_000_000,
+                "online_cpus": 2
+            },
+            "precpu_stats": {
+                "cpu_usage": {"total_usage": 1_000_000},
+                "system_cpu_usage": 10_000_000
+            },
+            "memory_stats": {
+                "usage": 300 * 1024 * 1024,
+                "limit": 512 * 1024 * 1024,
+                "stats": {"inactive_file": 100 * 1024 * 1024}
+            },
+            "blkio_stats": {
+                "io_service_bytes_recursive": [
+                    {"major": 254, "minor": 0, "op": "read", "value": 4096},
+                    {"major": 254, "minor": 0, "op": "write", "value": 8192}
+                ]
+            },
+            "pids_stats": {"current": 7}
+        }))
+        .unwrap();
+
+        assert_eq!(calculate_cpu_percent(&stats), 40.0);
+        assert_eq!(memory_used_bytes(&stats.memory_stats), 200 * 1024 * 1024);
+        assert_eq!(extract_block_io_stats(&stats), (4096, 8192));
+    }
+
+    #[test]
+    fn tolerates_null_block_io() {
+        let stats: DockerStatsResponse = serde_json::from_value(serde_json::json!({
+            "cpu_stats": {"cpu_usage": {"total_usage": 0}},
+            "precpu_stats": {"cpu_usage": {"total_usage": 0}},
+            "memory_stats": {},
+            "blkio_stats": {"io_service_bytes_recursive": null},
+            "pids_stats": {}
+        }))
+        .unwrap();
+
+        assert_eq!(extract_block_io_stats(&stats), (0, 0));
+        assert_eq!(calculate_cpu_percent(&stats), 0.0);
+    }
+
+    #[test]
+    fn parses_inspect_state() {
+        let inspect: DockerInspectResponse = serde_json::from_value(serde_json::json!({
+            "Id": "abc",
+            "State": {"Status": "exited", "Running": false, "ExitCode": 137, "OOMKilled": true}
+        }))
+        .unwrap();
+        assert!(!inspect.state.running);
+        assert_eq!(inspect.state.exit_code, 137);
+        assert!(inspect.state.oom_killed);
+    }
+}
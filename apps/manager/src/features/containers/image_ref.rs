@@ -0,0 +1,234 @@
+//! Image references and the digests they point at. A tag like
+//! `nginx:latest` can move; `nginx@sha256:...` can't, so containers record
+//! the digest their tag resolved to when they were created.
+
+use anyhow::{anyhow, bail, Context, Result};
+use nexus_types::RegistryAuth;
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+const DOCKER_HUB: &str = "registry-1.docker.io";
+/// Kept short: an unreachable registry only delays the create.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Manifest lists first, so a multi-arch tag resolves to the same digest
+/// `docker pull` reports.
+const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.oci.image.manifest.v1+json";
+
+/// `[registry/]repository[:tag][@digest]`, normalized the way Docker does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    pub registry: String,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    pub fn parse(image: &str) -> Result<Self> {
+        let image = image.trim();
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => {
+                if !is_digest(digest) {
+                    bail!("invalid image digest '{digest}': must be sha256:<64 hex characters>");
+                }
+                (name, Some(digest.to_string()))
+            }
+            None => (image, None),
+        };
+        // A ':' after the last '/' starts the tag; before it, it's a registry port
+        let (name, tag) = match name.rfind(':') {
+            Some(i) if !name[i..].contains('/') => (&name[..i], Some(name[i + 1..].to_string())),
+            _ => (name, None),
+        };
+        if name.is_empty() || tag.as_deref() == Some("") {
+            bail!("invalid image reference '{image}'");
+        }
+
+        let (registry, repository) = match name.split_once('/') {
+            Some(("docker.io" | "index.docker.io", rest)) => {
+                (DOCKER_HUB.to_string(), rest.to_string())
+            }
+            Some((first, rest))
+                if first.contains('.') || first.contains(':') || first == "localhost" =>
+            {
+                (first.to_string(), rest.to_string())
+            }
+            _ => (DOCKER_HUB.to_string(), name.to_string()),
+        };
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{repository}")
+        } else {
+            repository
+        };
+        Ok(Self {
+            registry,
+            repository,
+            tag,
+            digest,
+        })
+    }
+
+    /// What to ask the registry for: the digest if pinned, else the tag.
+    fn reference(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or("latest")
+    }
+}
+
+pub fn is_digest(value: &str) -> bool {
+    value.strip_prefix("sha256:").is_some_and(|hex| {
+        hex.len() == 64
+            && hex
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    })
+}
+
+/// The digest in a `docker image inspect` `RepoDigests` entry
+/// (`nginx@sha256:...`).
+pub fn digest_of_repo_digest(repo_digest: &str) -> Option<&str> {
+    repo_digest
+        .rsplit_once('@')
+        .map(|(_, digest)| digest)
+        .filter(|digest| is_digest(digest))
+}
+
+/// Asks the image's registry which digest its tag points at. A pinned
+/// reference is returned as is, without a network call.
+pub async fn resolve_digest(image: &ImageRef, auth: Option<&RegistryAuth>) -> Result<String> {
+    if let Some(digest) = &image.digest {
+        return Ok(digest.clone());
+    }
+    let client = reqwest::Client::builder()
+        .timeout(RESOLVE_TIMEOUT)
+        .build()?;
+    let url = format!(
+        "https://{}/v2/{}/manifests/{}",
+        image.registry,
+        image.repository,
+        image.reference()
+    );
+    let head = |bearer: Option<&str>| {
+        let request = client.head(&url).header(ACCEPT, MANIFEST_TYPES);
+        match (bearer, auth) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some(auth)) => request.basic_auth(&auth.username, Some(&auth.password)),
+            (None, None) => request,
+        }
+    };
+
+    let mut resp = head(None).send().await?;
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        let challenge = resp
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let token = fetch_token(&client, &challenge, auth).await?;
+        resp = head(Some(&token)).send().await?;
+    }
+    if !resp.status().is_success() {
+        bail!(
+            "registry {} answered {} for {}:{}",
+            image.registry,
+            resp.status(),
+            image.repository,
+            image.reference()
+        );
+    }
+    let digest = resp
+        .headers()
+        .get("docker-content-digest")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| anyhow!("registry {} sent no Docker-Content-Digest", image.registry))?;
+    if !is_digest(digest) {
+        bail!(
+            "registry {} sent an unsupported digest '{digest}'",
+            image.registry
+        );
+    }
+    Ok(digest.to_string())
+}
+
+/// Follows a `Bearer realm=..,service=..,scope=..` challenge to a token.
+async fn fetch_token(
+    client: &reqwest::Client,
+    challenge: &str,
+    auth: Option<&RegistryAuth>,
+) -> Result<String> {
+    let params = challenge
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| anyhow!("registry requires unsupported auth '{challenge}'"))?;
+    let param = |key: &str| {
+        params.split(',').find_map(|pair| {
+            let (k, v) = pair.trim().split_once('=')?;
+            (k == key).then(|| v.trim_matches('"').to_string())
+        })
+    };
+    let realm = param("realm").context("registry auth challenge has no realm")?;
+    let query: Vec<(&str, String)> = [("service", param("service")), ("scope", param("scope"))]
+        .into_iter()
+        .filter_map(|(k, v)| Some((k, v?)))
+        .collect();
+
+    let mut request = client.get(&realm).query(&query);
+    if let Some(auth) = auth {
+        request = request.basic_auth(&auth.username, Some(&auth.password));
+    }
+    let body: serde_json::Value = request.send().await?.error_for_status()?.json().await?;
+    body.get("token")
+        .or_else(|| body.get("access_token"))
+        .and_then(|token| token.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("registry token response has no token"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_references_like_docker() {
+        let digest = format!("sha256:{}", "ab".repeat(32));
+
+        let hub = ImageRef::parse("nginx:1.25").unwrap();
+        assert_eq!(
+            (hub.registry.as_str(), hub.repository.as_str()),
+            (DOCKER_HUB, "library/nginx")
+        );
+        assert_eq!(
+            (hub.tag.as_deref(), hub.reference()),
+            (Some("1.25"), "1.25")
+        );
+        assert_eq!(
+            ImageRef::parse("grafana/grafana").unwrap().reference(),
+            "latest"
+        );
+        assert_eq!(
+            ImageRef::parse("docker.io/nginx").unwrap(),
+            ImageRef::parse("nginx").unwrap()
+        );
+
+        let private =
+            ImageRef::parse(&format!("registry.local:5000/team/app:v2@{digest}")).unwrap();
+        assert_eq!(private.registry, "registry.local:5000");
+        assert_eq!(private.repository, "team/app");
+        assert_eq!(private.tag.as_deref(), Some("v2"));
+        assert_eq!(private.reference(), digest);
+        assert_eq!(ImageRef::parse("localhost:5000/app").unwrap().tag, None);
+
+        assert!(ImageRef::parse("nginx@sha256:abc").is_err());
+        assert!(ImageRef::parse("nginx:").is_err());
+        assert_eq!(
+            digest_of_repo_digest(&format!("nginx@{digest}")),
+            Some(digest.as_str())
+        );
+    }
+}
@@ -4,6 +4,7 @@ use axum::{
 };
 
 pub mod docker;
+pub mod image_ref;
 pub mod port_forward;
 pub mod repo;
 pub mod routes;
@@ -13,10 +14,14 @@ pub mod vm;
 pub fn router() -> Router {
     Router::new()
         .route("/", post(routes::create).get(routes::list))
+        .route("/batch", post(routes::batch))
         .route(
             "/:id",
             get(routes::get).put(routes::update).delete(routes::delete),
         )
+        .route("/:id/inspect", get(routes::inspect))
+        .route("/:id/digest", get(routes::digest))
+        .route("/:id/changes", get(routes::changes))
         .route("/:id/start", post(routes::start))
         .route("/:id/stop", post(routes::stop))
         .route("/:id/restart", post(routes::restart))
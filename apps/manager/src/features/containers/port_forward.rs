@@ -23,9 +23,9 @@ pub async fn check_port_available(port: u16) -> Result<bool> {
         }
     }
 
-    // Then check with the OS using ss command
+    // Then check with the OS using ss command (TCP and UDP listeners)
     let output = Command::new("ss")
-        .args(["-tlnp"])
+        .args(["-tulnp"])
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .output()
@@ -84,22 +84,58 @@ pub fn release_port(port: u16) {
     debug!(port = %port, "Port released");
 }
 
+/// Normalize a port mapping protocol, rejecting anything iptables DNAT can't
+/// forward by port.
+pub fn normalize_protocol(protocol: &str) -> Result<String> {
+    let protocol = protocol.trim().to_lowercase();
+    match protocol.as_str() {
+        "tcp" | "udp" => Ok(protocol),
+        _ => Err(anyhow!(
+            "Invalid port mapping protocol '{}': must be tcp or udp",
+            protocol
+        )),
+    }
+}
+
+/// Build the `iptables -t nat` arguments for one DNAT rule.
+///
+/// `op` is `-A` to append or `-D` to delete; `chain` is `PREROUTING` or `OUTPUT`.
+fn dnat_rule_args(
+    op: &str,
+    chain: &str,
+    protocol: &str,
+    host_port: u16,
+    vm_ip: &str,
+    container_port: u16,
+) -> Vec<String> {
+    vec![
+        "iptables".to_string(),
+        "-t".to_string(),
+        "nat".to_string(),
+        op.to_string(),
+        chain.to_string(),
+        "-p".to_string(),
+        protocol.to_string(),
+        "--dport".to_string(),
+        host_port.to_string(),
+        "-j".to_string(),
+        "DNAT".to_string(),
+        "--to-destination".to_string(),
+        format!("{}:{}", vm_ip, container_port),
+    ]
+}
+
 /// Set up port forwarding from host to container VM using iptables
 ///
 /// This creates DNAT rules to forward traffic from the host port to the container VM's port.
+/// PREROUTING covers external traffic, OUTPUT covers traffic from the host itself.
 pub async fn setup_port_forward(
     host_port: u16,
     vm_ip: &str,
     container_port: u16,
     protocol: &str,
 ) -> Result<()> {
-    let protocol = protocol.to_lowercase();
-    if protocol != "tcp" && protocol != "udp" {
-        return Err(anyhow!(
-            "Invalid protocol: {}. Must be tcp or udp",
-            protocol
-        ));
-    }
+    let protocol = normalize_protocol(protocol)?;
 
     info!(
         host_port = %host_port,
@@ -109,69 +145,19 @@ pub async fn setup_port_forward(
         "Setting up port forwarding"
     );
 
-    // Add PREROUTING rule (for external traffic)
-    let prerouting_result = Command::new("sudo")
-        .args([
-            "iptables",
-            "-t",
-            "nat",
-            "-A",
-            "PREROUTING",
-            "-p",
-            &protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{}:{}", vm_ip, container_port),
-        ])
-        .output()
-        .await;
-
-    match prerouting_result {
-        Ok(output) if output.status.success() => {
-            debug!("PREROUTING rule added successfully");
-        }
-        Ok(output) => {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            warn!("PREROUTING rule may have failed: {}", stderr);
-        }
-        Err(e) => {
-            error!("Failed to add PREROUTING rule: {}", e);
-        }
-    }
-
-    // Add OUTPUT rule (for local traffic from host machine itself)
-    let output_result = Command::new("sudo")
-        .args([
-            "iptables",
-            "-t",
-            "nat",
-            "-A",
-            "OUTPUT",
-            "-p",
-            &protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{}:{}", vm_ip, container_port),
-        ])
-        .output()
-        .await;
-
-    match output_result {
-        Ok(output) if output.status.success() => {
-            debug!("OUTPUT rule added successfully");
-        }
-        Ok(output) => {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            warn!("OUTPUT rule may have failed: {}", stderr);
-        }
-        Err(e) => {
-            error!("Failed to add OUTPUT rule: {}", e);
+    for chain in ["PREROUTING", "OUTPUT"] {
+        let args = dnat_rule_args("-A", chain, &protocol, host_port, vm_ip, container_port);
+        match Command::new("sudo").args(&args).output().await {
+            Ok(output) if output.status.success() => {
+                debug!(chain = %chain, "DNAT rule added successfully");
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                warn!("{} rule may have failed: {}", chain, stderr);
+            }
+            Err(e) => {
+                error!("Failed to add {} rule: {}", chain, e);
+            }
         }
     }
 
@@ -195,7 +181,7 @@ pub async fn remove_port_forward(
     container_port: u16,
     protocol: &str,
 ) -> Result<()> {
-    let protocol = protocol.to_lowercase();
+    let protocol = normalize_protocol(protocol)?;
 
     info!(
         host_port = %host_port,
@@ -205,45 +191,10 @@ pub async fn remove_port_forward(
         "Removing port forwarding"
     );
 
-    // Remove PREROUTING rule
-    let _ = Command::new("sudo")
-        .args([
-            "iptables",
-            "-t",
-            "nat",
-            "-D",
-            "PREROUTING",
-            "-p",
-            &protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{}:{}", vm_ip, container_port),
-        ])
-        .output()
-        .await;
-
-    // Remove OUTPUT rule
-    let _ = Command::new("sudo")
-        .args([
-            "iptables",
-            "-t",
-            "nat",
-            "-D",
-            "OUTPUT",
-            "-p",
-            &protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{}:{}", vm_ip, container_port),
-        ])
-        .output()
-        .await;
+    for chain in ["PREROUTING", "OUTPUT"] {
+        let args = dnat_rule_args("-D", chain, &protocol, host_port, vm_ip, container_port);
+        let _ = Command::new("sudo").args(&args).output().await;
+    }
 
     // Release the port
     release_port(host_port);
@@ -253,7 +204,37 @@ pub async fn remove_port_forward(
     Ok(())
 }
 
+/// Set up port forwards for all of a container's port mappings
+///
+/// Docker inside the VM publishes each mapping on `mapping.host`, so the host
+/// forwards `host:mapping.host` to `vm_ip:mapping.host`.
+pub async fn apply_port_forwards(
+    port_mappings: &[nexus_types::PortMapping],
+    vm_ip: &str,
+) -> Result<()> {
+    for mapping in port_mappings {
+        if let Err(e) = setup_port_forward(
+            mapping.host as u16,
+            vm_ip,
+            mapping.host as u16,
+            &mapping.protocol,
+        )
+        .await
+        {
+            warn!(
+                host_port = %mapping.host,
+                error = %e,
+                "Failed to set up port forward"
+            );
+        }
+    }
+
+    Ok(())
+}
+
 /// Remove all port forwards for a container (given its port mappings and VM IP)
+///
+/// Mirrors [`apply_port_forwards`], so the DNAT target is `vm_ip:mapping.host`.
 pub async fn cleanup_port_forwards(
     port_mappings: &[nexus_types::PortMapping],
     vm_ip: &str,
@@ -262,7 +243,7 @@ pub async fn cleanup_port_forwards(
         if let Err(e) = remove_port_forward(
             mapping.host as u16,
             vm_ip,
-            mapping.container as u16,
+            mapping.host as u16,
             &mapping.protocol,
         )
         .await
@@ -302,4 +283,21 @@ mod tests {
             assert!(!used.contains(&9999));
         }
     }
+
+    #[test]
+    fn test_normalize_protocol() {
+        assert_eq!(normalize_protocol("TCP").unwrap(), "tcp");
+        assert_eq!(normalize_protocol(" udp ").unwrap(), "udp");
+        assert!(normalize_protocol("icmp").is_err());
+        assert!(normalize_protocol("sctp").is_err());
+    }
+
+    #[test]
+    fn test_udp_mapping_produces_udp_rule() {
+        let args = dnat_rule_args("-A", "PREROUTING", "udp", 5353, "10.0.0.5", 5353);
+        assert_eq!(
+            args.join(" "),
+            "iptables -t nat -A PREROUTING -p udp --dport 5353 -j DNAT --to-destination 10.0.0.5:5353"
+        );
+    }
 }
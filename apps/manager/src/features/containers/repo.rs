@@ -16,7 +16,13 @@ impl ContainerRepository {
         Self { db }
     }
 
-    pub async fn create(&self, req: CreateContainerReq, host_id: Option<Uuid>) -> Result<Uuid> {
+    pub async fn create(
+        &self,
+        req: CreateContainerReq,
+        host_id: Option<Uuid>,
+        created_by_user_id: Option<Uuid>,
+        image_digest: Option<&str>,
+    ) -> Result<Uuid> {
         let id = Uuid::new_v4();
         let now = Utc::now();
 
@@ -30,8 +36,8 @@ impl ContainerRepository {
             INSERT INTO containers (
                 id, name, image, command, args, env_vars, volumes, port_mappings,
                 cpu_limit, memory_limit_mb, restart_policy, state, host_id,
-                created_by_user_id, created_at, updated_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                created_by_user_id, created_at, updated_at, image_digest
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
             "#,
         )
         .bind(id)
@@ -47,9 +53,10 @@ impl ContainerRepository {
         .bind(&req.restart_policy)
         .bind("creating")
         .bind(host_id)
-        .bind(None::<Option<Uuid>>) // created_by_user_id - TODO: Set from authenticated user context
+        .bind(created_by_user_id)
         .bind(now)
         .bind(now)
+        .bind(image_digest)
         .execute(&self.db)
         .await
         .context("failed to insert container")?;
@@ -61,8 +68,8 @@ impl ContainerRepository {
         let row = sqlx::query_as::<_, ContainerRow>(
             r#"
             SELECT
-                c.id, c.name, c.image, c.command, c.args, c.env_vars, c.volumes, c.port_mappings,
-                c.cpu_limit, c.memory_limit_mb, c.restart_policy, c.state, c.host_id,
+                c.id, c.name, c.image, c.image_digest, c.command, c.args, c.env_vars, c.volumes, c.port_mappings,
+                c.cpu_limit, c.memory_limit_mb, c.restart_policy, c.restart_count, c.state, c.host_id,
                 c.container_runtime_id, c.error_message, c.created_by_user_id, c.created_at, c.updated_at,
                 c.started_at, c.stopped_at,
                 v.guest_ip
@@ -96,6 +103,7 @@ impl ContainerRepository {
             id: row.id,
             name: row.name,
             image: row.image,
+            image_digest: row.image_digest,
             command: row.command,
             args,
             env_vars,
@@ -104,6 +112,7 @@ impl ContainerRepository {
             cpu_limit: row.cpu_limit,
             memory_limit_mb: row.memory_limit_mb,
             restart_policy: row.restart_policy.unwrap_or_else(|| "no".to_string()),
+            restart_count: row.restart_count,
             state: row.state,
             host_id: row.host_id,
             container_runtime_id: row.container_runtime_id,
@@ -124,42 +133,30 @@ impl ContainerRepository {
         &self,
         state_filter: Option<String>,
         host_filter: Option<Uuid>,
+        limit: Option<i64>,
+        offset: i64,
     ) -> Result<Vec<Container>> {
-        let mut query_str = String::from(
+        let query = sqlx::query_as::<_, ContainerRow>(
             r#"
             SELECT
-                c.id, c.name, c.image, c.command, c.args, c.env_vars, c.volumes, c.port_mappings,
-                c.cpu_limit, c.memory_limit_mb, c.restart_policy, c.state, c.host_id,
+                c.id, c.name, c.image, c.image_digest, c.command, c.args, c.env_vars, c.volumes, c.port_mappings,
+                c.cpu_limit, c.memory_limit_mb, c.restart_policy, c.restart_count, c.state, c.host_id,
                 c.container_runtime_id, c.error_message, c.created_by_user_id, c.created_at, c.updated_at,
                 c.started_at, c.stopped_at,
                 v.guest_ip
             FROM containers c
             LEFT JOIN vm v ON c.container_runtime_id = 'vm-' || v.id::text
-            WHERE 1=1
+            WHERE ($1::text IS NULL OR c.state = $1)
+              AND ($2::uuid IS NULL OR c.host_id = $2)
+            ORDER BY c.created_at DESC
+            LIMIT $3
+            OFFSET $4
             "#,
-        );
-
-        if state_filter.is_some() {
-            query_str.push_str(" AND state = $1");
-        }
-        if host_filter.is_some() {
-            if state_filter.is_some() {
-                query_str.push_str(" AND host_id = $2");
-            } else {
-                query_str.push_str(" AND host_id = $1");
-            }
-        }
-        query_str.push_str(" ORDER BY created_at DESC");
-
-        let mut query = sqlx::query_as::<_, ContainerRow>(&query_str);
-
-        if let Some(state) = &state_filter {
-            query = query.bind(state);
-        }
-        if let Some(host_id) = host_filter {
-            query = query.bind(host_id);
-        }
-
+        )
+        .bind(state_filter)
+        .bind(host_filter)
+        .bind(limit)
+        .bind(offset);
         let rows = query.fetch_all(&self.db).await?;
 
         let containers = rows
@@ -176,6 +173,7 @@ impl ContainerRepository {
                     id: row.id,
                     name: row.name,
                     image: row.image,
+                    image_digest: row.image_digest,
                     command: row.command,
                     args: serde_json::from_value(
                         row.args.unwrap_or_else(|| serde_json::json!([])),
@@ -192,6 +190,7 @@ impl ContainerRepository {
                     cpu_limit: row.cpu_limit,
                     memory_limit_mb: row.memory_limit_mb,
                     restart_policy: row.restart_policy.unwrap_or_else(|| "no".to_string()),
+                    restart_count: row.restart_count,
                     state: row.state,
                     host_id: row.host_id,
                     container_runtime_id: row.container_runtime_id,
@@ -212,6 +211,26 @@ impl ContainerRepository {
         Ok(containers)
     }
 
+    pub async fn count(
+        &self,
+        state_filter: Option<&str>,
+        host_filter: Option<Uuid>,
+    ) -> Result<i64> {
+        let total = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM containers
+            WHERE ($1::text IS NULL OR state = $1)
+              AND ($2::uuid IS NULL OR host_id = $2)
+            "#,
+        )
+        .bind(state_filter)
+        .bind(host_filter)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(total)
+    }
+
     pub async fn update(&self, id: Uuid, req: UpdateContainerReq) -> Result<()> {
         let now = Utc::now();
 
@@ -310,6 +329,15 @@ impl ContainerRepository {
         Ok(())
     }
 
+    pub async fn set_image_digest(&self, id: Uuid, digest: &str) -> Result<()> {
+        sqlx::query("UPDATE containers SET image_digest = $1, updated_at = now() WHERE id = $2")
+            .bind(digest)
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
     pub async fn set_started(&self, id: Uuid) -> Result<()> {
         let now = Utc::now();
         sqlx::query(
@@ -327,12 +355,42 @@ impl ContainerRepository {
         Ok(())
     }
 
+    /// Move a container that is still provisioning on to `state`. False if
+    /// it was stopped meanwhile, so provisioning can give up instead of
+    /// bringing it back.
+    pub async fn advance_provisioning(&self, id: Uuid, state: &str) -> Result<bool> {
+        let now = Utc::now();
+        let updated = if state == "running" {
+            sqlx::query(
+                r#"
+                UPDATE containers
+                SET state = 'running', started_at = $1, stopped_at = NULL, updated_at = $1
+                WHERE id = $2 AND state <> 'stopped'
+                "#,
+            )
+            .bind(now)
+            .bind(id)
+            .execute(&self.db)
+            .await?
+        } else {
+            sqlx::query(
+                "UPDATE containers SET state = $1, updated_at = $2 WHERE id = $3 AND state <> 'stopped'",
+            )
+            .bind(state)
+            .bind(now)
+            .bind(id)
+            .execute(&self.db)
+            .await?
+        };
+        Ok(updated.rows_affected() > 0)
+    }
+
     pub async fn set_stopped(&self, id: Uuid) -> Result<()> {
         let now = Utc::now();
         sqlx::query(
             r#"
             UPDATE containers
-            SET state = 'stopped', stopped_at = $1, updated_at = $2
+            SET state = 'stopped', error_message = NULL, stopped_at = $1, updated_at = $2
             WHERE id = $3
             "#,
         )
@@ -344,6 +402,58 @@ impl ContainerRepository {
         Ok(())
     }
 
+    /// Containers the restart-policy pass should look at: every running one
+    /// (it may have died) plus those that died or failed to restart and still
+    /// have retries left. A clean `stopped` has no error message and is left
+    /// alone, so containers stopped by hand stay stopped.
+    pub async fn list_restart_candidates(&self, max_retries: i32) -> Result<Vec<RestartCandidate>> {
+        let rows = sqlx::query_as::<_, RestartCandidate>(
+            r#"
+            SELECT id, restart_policy, restart_count, last_restart_at, state
+            FROM containers
+            WHERE restart_policy IN ('on-failure', 'always')
+              AND container_runtime_id IS NOT NULL
+              AND (
+                state = 'running'
+                OR (state IN ('error', 'stopped')
+                    AND error_message IS NOT NULL
+                    AND restart_count < $1)
+              )
+            "#,
+        )
+        .bind(max_retries)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows)
+    }
+
+    /// Count a policy restart and return the new total.
+    pub async fn record_restart_attempt(&self, id: Uuid) -> Result<i32> {
+        let count = sqlx::query_scalar(
+            r#"
+            UPDATE containers
+            SET restart_count = restart_count + 1, last_restart_at = $1
+            WHERE id = $2
+            RETURNING restart_count
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(count)
+    }
+
+    pub async fn reset_restart_count(&self, id: Uuid) -> Result<()> {
+        sqlx::query(
+            "UPDATE containers SET restart_count = 0, last_restart_at = NULL WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
     pub async fn record_stats(&self, container_id: Uuid, stats: &ContainerStatsData) -> Result<()> {
         sqlx::query(
             r#"
@@ -471,6 +581,7 @@ struct ContainerRow {
     id: Uuid,
     name: String,
     image: String,
+    image_digest: Option<String>,
     command: Option<String>,
     args: Option<serde_json::Value>,
     env_vars: Option<serde_json::Value>,
@@ -479,6 +590,7 @@ struct ContainerRow {
     cpu_limit: Option<f32>,
     memory_limit_mb: Option<i32>,
     restart_policy: Option<String>,
+    restart_count: i32,
     state: String,
     host_id: Option<Uuid>,
     container_runtime_id: Option<String>,
@@ -491,6 +603,15 @@ struct ContainerRow {
     guest_ip: Option<String>,
 }
 
+#[derive(sqlx::FromRow)]
+pub struct RestartCandidate {
+    pub id: Uuid,
+    pub restart_policy: Option<String>,
+    pub restart_count: i32,
+    pub last_restart_at: Option<chrono::DateTime<Utc>>,
+    pub state: String,
+}
+
 // Helper structs for query results
 #[derive(sqlx::FromRow)]
 struct ContainerStatsRow {
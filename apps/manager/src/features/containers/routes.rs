@@ -1,4 +1,4 @@
-use crate::features::users::repo::AuthenticatedUser;
+use crate::features::users::audit::Actor;
 use crate::AppState;
 use axum::{
     extract::{
@@ -9,20 +9,22 @@ use axum::{
     response::IntoResponse,
     Extension, Json,
 };
+use futures::StreamExt;
 use nexus_types::{
-    ContainerLogsParams, ContainerLogsResp, ContainerPathParams, ContainerStatsResp,
-    CreateContainerReq, CreateContainerResp, ExecCommandReq, ExecCommandResp, GetContainerResp,
-    ListContainersParams, ListContainersResp, OkResponse, UpdateContainerReq,
+    AuditAction, ContainerBatchAction, ContainerBatchReq, ContainerBatchResp, ContainerBatchResult,
+    ContainerChangesResp, ContainerImageDigestResp, ContainerLogsParams, ContainerLogsResp,
+    ContainerPathParams, ContainerStatsResp, CreateContainerReq, CreateContainerResp,
+    ExecCommandReq, ExecCommandResp, GetContainerResp, InspectContainerResp, ListContainersParams,
+    ListContainersResp, OkResponse, UpdateContainerReq,
 };
 use serde::Serialize;
 use tokio::time::{interval, Duration};
+use uuid::Uuid;
 
-fn extract_user_info(user: Option<Extension<AuthenticatedUser>>) -> (Option<uuid::Uuid>, String) {
-    match user {
-        Some(Extension(u)) => (Some(u.id), u.username),
-        None => (None, "system".to_string()),
-    }
-}
+/// Most containers one batch request may name.
+const BATCH_MAX: usize = 100;
+/// Containers a batch works on at once.
+const BATCH_CONCURRENCY: usize = 4;
 
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -37,32 +39,47 @@ struct ErrorResponse {
     request_body = CreateContainerReq,
     responses(
         (status = 200, description = "Container created", body = CreateContainerResp),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Invalid request, e.g. a malformed image reference or digest"),
+        (status = 409, description = "The container would exceed the caller's quota"),
         (status = 500, description = "Failed to create container"),
     ),
     tag = "Containers"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Json(req): Json<CreateContainerReq>,
 ) -> Result<Json<CreateContainerResp>, (StatusCode, String)> {
-    let (user_id, username) = extract_user_info(user);
-    let resp = super::service::create_container(&st, req, user_id, &username)
-        .await
-        .map_err(|e| {
-            let error_msg = e.to_string();
-            eprintln!("Failed to create container: {}", error_msg);
-            // Return 400 for validation errors (port conflicts, empty name, etc.)
-            if error_msg.contains("already in use")
-                || error_msg.contains("cannot be empty")
-                || error_msg.contains("Port mapping failed")
-            {
-                (StatusCode::BAD_REQUEST, error_msg)
-            } else {
-                (StatusCode::INTERNAL_SERVER_ERROR, error_msg)
-            }
-        })?;
+    let details = serde_json::json!({"name": &req.name, "image": &req.image});
+    let result = super::service::create_container(&st, req, actor.user_id, &actor.username).await;
+    let id = result.as_ref().ok().map(|resp| resp.id);
+    actor
+        .record_with(
+            AuditAction::CreateContainer,
+            ("container", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let resp = result.map_err(|e| {
+        let error_msg = e.to_string();
+        eprintln!("Failed to create container: {}", error_msg);
+        if crate::features::users::quota::is_exceeded(&e) {
+            return (StatusCode::CONFLICT, error_msg);
+        }
+        // Return 400 for validation errors (port conflicts, empty name, etc.)
+        if error_msg.contains("already in use")
+            || error_msg.contains("cannot be empty")
+            || error_msg.contains("Port mapping failed")
+            || error_msg.contains("Invalid port mapping protocol")
+            || error_msg.contains("Registry credential")
+            || error_msg.contains("invalid image")
+        {
+            (StatusCode::BAD_REQUEST, error_msg)
+        } else {
+            (StatusCode::INTERNAL_SERVER_ERROR, error_msg)
+        }
+    })?;
     Ok(Json(resp))
 }
 
@@ -80,7 +97,7 @@ pub async fn list(
     Extension(st): Extension<AppState>,
     Query(params): Query<ListContainersParams>,
 ) -> Result<Json<ListContainersResp>, StatusCode> {
-    let resp = super::service::list_containers(&st.db, params.state, params.host_id)
+    let resp = super::service::list_containers(&st.db, params)
         .await
         .map_err(|e| {
             eprintln!("Failed to list containers: {}", e);
@@ -117,6 +134,90 @@ pub async fn get(
     Ok(Json(resp))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/containers/{id}/inspect",
+    params(ContainerPathParams),
+    responses(
+        (status = 200, description = "Stored container with the runtime's inspect output", body = InspectContainerResp),
+        (status = 404, description = "Container not found"),
+        (status = 500, description = "Failed to inspect container"),
+    ),
+    tag = "Containers"
+)]
+pub async fn inspect(
+    Extension(st): Extension<AppState>,
+    Path(ContainerPathParams { id }): Path<ContainerPathParams>,
+) -> Result<Json<InspectContainerResp>, StatusCode> {
+    let resp = super::service::inspect_container(&st.db, id)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to inspect container: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/containers/{id}/digest",
+    params(ContainerPathParams),
+    responses(
+        (status = 200, description = "The image reference as given and the digest it resolved to; no digest when neither the registry nor the runtime reported one", body = ContainerImageDigestResp),
+        (status = 404, description = "Container not found"),
+        (status = 500, description = "Failed to fetch container"),
+    ),
+    tag = "Containers"
+)]
+pub async fn digest(
+    Extension(st): Extension<AppState>,
+    Path(ContainerPathParams { id }): Path<ContainerPathParams>,
+) -> Result<Json<ContainerImageDigestResp>, StatusCode> {
+    let resp = super::service::image_digest(&st.db, id)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch container digest: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/containers/{id}/changes",
+    params(ContainerPathParams),
+    responses(
+        (status = 200, description = "Paths added, modified or deleted against the image, as `docker diff` reports them; empty with a note when the container was never started", body = ContainerChangesResp),
+        (status = 404, description = "Container not found"),
+        (status = 500, description = "Failed to get container changes"),
+    ),
+    tag = "Containers"
+)]
+pub async fn changes(
+    Extension(st): Extension<AppState>,
+    Path(ContainerPathParams { id }): Path<ContainerPathParams>,
+) -> Result<Json<ContainerChangesResp>, StatusCode> {
+    let resp = super::service::container_changes(&st.db, id)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to get container changes: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    Ok(Json(resp))
+}
+
 #[utoipa::path(
     put,
     path = "/v1/containers/{id}",
@@ -132,21 +233,24 @@ pub async fn get(
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
     Json(req): Json<UpdateContainerReq>,
 ) -> Result<Json<GetContainerResp>, StatusCode> {
-    let resp = super::service::update_container(&st, id, req)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to update container: {}", e);
-            if e.to_string().contains("not found") {
-                StatusCode::NOT_FOUND
-            } else if e.to_string().contains("running") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+    let result = super::service::update_container(&st, id, req).await;
+    actor
+        .record(AuditAction::UpdateContainer, ("container", id), &result)
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to update container: {}", e);
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if e.to_string().contains("running") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
     Ok(Json(resp))
 }
 
@@ -163,16 +267,17 @@ pub async fn update(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::delete_container(&st, id, user_id, &username)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to delete container: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let result = super::service::delete_container(&st, id).await;
+    actor
+        .record(AuditAction::DeleteContainer, ("container", id), &result)
+        .await;
+    result.map_err(|e| {
+        eprintln!("Failed to delete container: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -190,11 +295,14 @@ pub async fn delete(
 )]
 pub async fn start(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> impl IntoResponse {
-    let (user_id, username) = extract_user_info(user);
-    match super::service::start_container(&st, id, user_id, &username).await {
+    let result = super::service::start_container(&st, id).await;
+    actor
+        .record(AuditAction::StartContainer, ("container", id), &result)
+        .await;
+    match result {
         Ok(_) => (StatusCode::OK, Json(OkResponse::default())).into_response(),
         Err(e) => {
             eprintln!("Failed to start container: {}", e);
@@ -230,11 +338,14 @@ pub async fn start(
 )]
 pub async fn stop(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> impl IntoResponse {
-    let (user_id, username) = extract_user_info(user);
-    match super::service::stop_container(&st, id, user_id, &username).await {
+    let result = super::service::stop_container(&st, id).await;
+    actor
+        .record(AuditAction::StopContainer, ("container", id), &result)
+        .await;
+    match result {
         Ok(_) => (StatusCode::OK, Json(OkResponse::default())).into_response(),
         Err(e) => {
             eprintln!("Failed to stop container: {}", e);
@@ -256,6 +367,66 @@ pub async fn stop(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/containers/batch",
+    request_body = ContainerBatchReq,
+    responses(
+        (status = 200, description = "Action applied to each container; see the per-id results. Stopping a stopped container succeeds", body = ContainerBatchResp),
+        (status = 400, description = "No ids, or more than 100"),
+    ),
+    tag = "Containers"
+)]
+pub async fn batch(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Json(req): Json<ContainerBatchReq>,
+) -> Result<Json<ContainerBatchResp>, StatusCode> {
+    let mut seen = std::collections::HashSet::new();
+    let ids: Vec<Uuid> = req.ids.into_iter().filter(|id| seen.insert(*id)).collect();
+    if ids.is_empty() || ids.len() > BATCH_MAX {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(Json(run_batch(&st, &actor, req.action, ids).await))
+}
+
+/// Applies `action` to each container, [`BATCH_CONCURRENCY`] at a time,
+/// auditing each one like its single-container route.
+pub(crate) async fn run_batch(
+    st: &AppState,
+    actor: &Actor,
+    action: ContainerBatchAction,
+    ids: Vec<Uuid>,
+) -> ContainerBatchResp {
+    let results = futures::stream::iter(ids)
+        .map(|id| async move {
+            let (audit_action, result) = match action {
+                ContainerBatchAction::Start => (
+                    AuditAction::StartContainer,
+                    super::service::start_container(st, id).await,
+                ),
+                ContainerBatchAction::Stop => (
+                    AuditAction::StopContainer,
+                    super::service::ensure_stopped(st, id).await,
+                ),
+                ContainerBatchAction::Delete => (
+                    AuditAction::DeleteContainer,
+                    super::service::delete_container(st, id).await,
+                ),
+            };
+            actor.record(audit_action, ("container", id), &result).await;
+            ContainerBatchResult {
+                id,
+                ok: result.is_ok(),
+                error: result.err().map(|e| format!("{e:#}")),
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+    ContainerBatchResp { results }
+}
+
 #[utoipa::path(
     post,
     path = "/v1/containers/{id}/restart",
@@ -269,9 +440,14 @@ pub async fn stop(
 )]
 pub async fn restart(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> impl IntoResponse {
-    match super::service::restart_container(&st, id).await {
+    let result = super::service::restart_container(&st, id).await;
+    actor
+        .record(AuditAction::RestartContainer, ("container", id), &result)
+        .await;
+    match result {
         Ok(_) => (StatusCode::OK, Json(OkResponse::default())).into_response(),
         Err(e) => {
             eprintln!("Failed to restart container: {}", e);
@@ -301,18 +477,21 @@ pub async fn restart(
 )]
 pub async fn pause(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    super::service::pause_container(&st, id)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to pause container: {}", e);
-            if e.to_string().contains("not running") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+    let result = super::service::pause_container(&st, id).await;
+    actor
+        .record(AuditAction::PauseContainer, ("container", id), &result)
+        .await;
+    result.map_err(|e| {
+        eprintln!("Failed to pause container: {}", e);
+        if e.to_string().contains("not running") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -330,18 +509,21 @@ pub async fn pause(
 )]
 pub async fn resume(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    super::service::resume_container(&st, id)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to resume container: {}", e);
-            if e.to_string().contains("not paused") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+    let result = super::service::resume_container(&st, id).await;
+    actor
+        .record(AuditAction::ResumeContainer, ("container", id), &result)
+        .await;
+    result.map_err(|e| {
+        eprintln!("Failed to resume container: {}", e);
+        if e.to_string().contains("not paused") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -497,18 +679,27 @@ pub async fn stats(
 )]
 pub async fn exec(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ContainerPathParams { id }): Path<ContainerPathParams>,
     Json(req): Json<ExecCommandReq>,
 ) -> Result<Json<ExecCommandResp>, StatusCode> {
-    let resp = super::service::exec_command(&st, id, req)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to exec command: {}", e);
-            if e.to_string().contains("must be running") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+    let details = serde_json::json!({"command": req.command});
+    let result = super::service::exec_command(&st, id, req).await;
+    actor
+        .record_with(
+            AuditAction::ExecContainer,
+            ("container", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to exec command: {}", e);
+        if e.to_string().contains("must be running") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
     Ok(Json(resp))
 }
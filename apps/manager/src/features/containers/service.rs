@@ -1,7 +1,8 @@
 use anyhow::{anyhow, Result};
 use nexus_types::{
-    AuditAction, ContainerLogsResp, ContainerStatsResp, CreateContainerReq, CreateContainerResp,
-    ExecCommandReq, ExecCommandResp, GetContainerResp, ListContainersResp, OkResponse,
+    AuditAction, ContainerChangesResp, ContainerImageDigestResp, ContainerLogsResp,
+    ContainerStatsResp, CreateContainerReq, CreateContainerResp, ExecCommandReq, ExecCommandResp,
+    GetContainerResp, InspectContainerResp, ListContainersParams, ListContainersResp, OkResponse,
     UpdateContainerReq,
 };
 use sqlx::PgPool;
@@ -12,6 +13,7 @@ use serde_json::json;
 
 use super::docker::DockerClient;
 use super::repo::{ContainerRepository, ContainerStatsData};
+use crate::core::pagination::{page_limit, page_offset};
 use crate::features::users::audit;
 use crate::AppState;
 
@@ -28,7 +30,7 @@ use crate::AppState;
 ///    f. Update container state to running
 pub async fn create_container(
     st: &AppState,
-    req: CreateContainerReq,
+    mut req: CreateContainerReq,
     user_id: Option<Uuid>,
     username: &str,
 ) -> Result<CreateContainerResp> {
@@ -41,6 +43,39 @@ pub async fn create_container(
     if req.image.is_empty() {
         return Err(anyhow!("Container image cannot be empty"));
     }
+    for mapping in &mut req.port_mappings {
+        mapping.protocol = super::port_forward::normalize_protocol(&mapping.protocol)?;
+    }
+
+    // Swap a named registry credential for the decrypted auth so the pull
+    // below never needs to know where it came from
+    req.registry_auth = crate::features::registries::service::resolve_auth(
+        st,
+        user_id,
+        req.registry.as_deref(),
+        req.registry_auth.take(),
+    )
+    .await?;
+
+    let image_ref = super::image_ref::ImageRef::parse(&req.image)?;
+
+    // Determine resource allocations (use defaults if not specified)
+    let vcpu = req.cpu_limit.map(|c| c.ceil() as u8).unwrap_or(1);
+    let memory_mb = (req.memory_limit_mb.unwrap_or(512) as u32).max(512);
+
+    // Before any ports are reserved, so a rejected create leaves none behind.
+    // Held until the container row is in
+    let reservation = crate::features::users::quota::reserve(
+        &st.db,
+        user_id,
+        nexus_types::UserQuotaUsage {
+            vms: 0,
+            vcpu: vcpu.max(1) as i64,
+            mem_mib: memory_mb as i64,
+            containers: 1,
+        },
+    )
+    .await?;
 
     // Check port availability BEFORE creating the container
     if !req.port_mappings.is_empty() {
@@ -66,26 +101,32 @@ pub async fn create_container(
         }
     }
 
-    // Determine resource allocations (use defaults if not specified)
-    let vcpu = req.cpu_limit.map(|c| c.ceil() as u8).unwrap_or(1);
-    let memory_mb = (req.memory_limit_mb.unwrap_or(512) as u32).max(512);
+    // Unpinned tags can move; record where this one points now. Registries
+    // that can't be reached (air-gapped hosts using preloaded tarballs) leave
+    // it to the runtime to report the digest after the pull
+    let image_digest =
+        match super::image_ref::resolve_digest(&image_ref, req.registry_auth.as_ref()).await {
+            Ok(digest) => Some(digest),
+            Err(e) => {
+                tracing::warn!(image = %req.image, error = %e, "could not resolve image digest");
+                None
+            }
+        };
+    if image_ref.digest.is_none() {
+        tracing::info!(
+            image = %req.image,
+            digest = ?image_digest,
+            "container image is not pinned by digest"
+        );
+    }
 
     // Create container record in database (state: creating)
-    let container_id = repo.create(req.clone(), None).await?;
-
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::CreateContainer,
-        Some("container"),
-        Some(container_id),
-        Some(json!({"event": "creation_started", "name": &req.name, "image": &req.image})),
-        None,
-        true,
-        None,
-    )
-    .await;
+    let container_id = repo
+        .create(req.clone(), None, user_id, image_digest.as_deref())
+        .await?;
+    if let Err(err) = reservation.release().await {
+        tracing::warn!(container_id = %container_id, error = ?err, "failed to release quota reservation");
+    }
 
     // Spawn dedicated MicroVM for this container in the background
     let st_clone = st.clone();
@@ -93,7 +134,7 @@ pub async fn create_container(
     let spawn_username = username.to_string();
     let spawn_user_id = user_id;
 
-    tokio::spawn(async move {
+    st.background.spawn(async move {
         if let Err(e) = provision_container_vm(
             &st_clone,
             container_id,
@@ -104,6 +145,10 @@ pub async fn create_container(
         )
         .await
         {
+            if e.downcast_ref::<StoppedWhileProvisioning>().is_some() {
+                tracing::info!(container_id = %container_id, "container stopped while provisioning");
+                return;
+            }
             eprintln!("[Container {}] Failed to provision: {}", container_id, e);
             let error_msg = format!("Failed to provision container VM: {}", e);
             let _ = audit::log_action(
@@ -158,6 +203,21 @@ fn find_local_image_tarball(image: &str, image_root: &std::path::Path) -> Option
     }
 }
 
+/// A stop arrived while the container was still provisioning; not a failure.
+#[derive(Debug)]
+struct StoppedWhileProvisioning;
+
+impl std::fmt::Display for StoppedWhileProvisioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("container was stopped while provisioning")
+    }
+}
+
+impl std::error::Error for StoppedWhileProvisioning {}
+
+/// States a stop applies to: up, paused, or still provisioning.
+const ACTIVE_STATES: &[&str] = &["creating", "booting", "initializing", "running", "paused"];
+
 /// Background task to provision container VM and start Docker container
 async fn provision_container_vm(
     st: &AppState,
@@ -180,7 +240,9 @@ async fn provision_container_vm(
     // Update container with VM ID
     repo.update_runtime_id(container_id, format!("vm-{}", vm_id))
         .await?;
-    repo.update_state(container_id, "booting", None).await?;
+    if !repo.advance_provisioning(container_id, "booting").await? {
+        return Err(StoppedWhileProvisioning.into());
+    }
 
     // Wait for guest IP to be available (up to 120 seconds)
     eprintln!("[Container {}] Waiting for VM guest IP...", container_id);
@@ -230,8 +292,12 @@ async fn provision_container_vm(
     .await;
 
     // Wait for Docker daemon to be ready inside VM
-    repo.update_state(container_id, "initializing", None)
-        .await?;
+    if !repo
+        .advance_provisioning(container_id, "initializing")
+        .await?
+    {
+        return Err(StoppedWhileProvisioning.into());
+    }
 
     if let Err(e) = super::vm::wait_for_docker_ready(&guest_ip, 240).await {
         let error_msg = format!("Docker daemon not ready: {}", e);
@@ -315,6 +381,19 @@ async fn provision_container_vm(
         }
     }
 
+    // What the runtime actually pulled wins over the create-time resolution,
+    // in case the tag moved in between
+    let pulled_digest = docker
+        .image_repo_digests(&req.image)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .find_map(|repo_digest| super::image_ref::digest_of_repo_digest(repo_digest))
+        .map(str::to_string);
+    if let Some(digest) = &pulled_digest {
+        repo.set_image_digest(container_id, digest).await?;
+    }
+
     let _ = audit::log_action(
         &st.db,
         None,
@@ -322,7 +401,7 @@ async fn provision_container_vm(
         AuditAction::SystemEvent,
         Some("container"),
         Some(container_id),
-        Some(json!({"event": "image_ready", "image": &req.image})),
+        Some(json!({"event": "image_ready", "image": &req.image, "digest": pulled_digest})),
         None,
         true,
         None,
@@ -353,29 +432,16 @@ async fn provision_container_vm(
         anyhow::bail!(error_msg);
     }
 
-    // Update container state to running
-    repo.set_started(container_id).await?;
+    // Update container state to running, unless a stop came in meanwhile
+    if !repo.advance_provisioning(container_id, "running").await? {
+        let _ = docker.stop_container(&docker_container_id, Some(10)).await;
+        return Err(StoppedWhileProvisioning.into());
+    }
 
     // Set up port forwarding from host to container VM
     // The Docker container inside the VM has already been started with port mappings
     // Now we need to forward from host -> VM
-    for mapping in &req.port_mappings {
-        // The Docker container inside VM exposes on mapping.host (which Docker maps to container port)
-        // We forward host:mapping.host -> vm_ip:mapping.host
-        if let Err(e) = super::port_forward::setup_port_forward(
-            mapping.host as u16,
-            &guest_ip,
-            mapping.host as u16, // Docker inside VM maps to the same port
-            &mapping.protocol,
-        )
-        .await
-        {
-            eprintln!(
-                "[Container {}] Warning: Failed to setup port forward for {}: {}",
-                container_id, mapping.host, e
-            );
-        }
-    }
+    super::port_forward::apply_port_forwards(&req.port_mappings, &guest_ip).await?;
 
     if !req.port_mappings.is_empty() {
         let ports: Vec<_> = req.port_mappings.iter().map(|p| p.host).collect();
@@ -433,16 +499,26 @@ async fn provision_container_vm(
     Ok(())
 }
 
-/// List all containers
+/// List one page of containers, newest first
 pub async fn list_containers(
     db: &PgPool,
-    state_filter: Option<String>,
-    host_filter: Option<Uuid>,
+    params: ListContainersParams,
 ) -> Result<ListContainersResp> {
     let repo = ContainerRepository::new(db.clone());
-    let containers = repo.list(state_filter, host_filter).await?;
+    let total = repo.count(params.state.as_deref(), params.host_id).await?;
+    let containers = repo
+        .list(
+            params.state,
+            params.host_id,
+            page_limit(params.limit),
+            page_offset(params.offset),
+        )
+        .await?;
 
-    Ok(ListContainersResp { items: containers })
+    Ok(ListContainersResp {
+        items: containers,
+        total,
+    })
 }
 
 /// Get a single container
@@ -463,6 +539,75 @@ pub async fn get_container(db: &PgPool, id: Uuid) -> Result<GetContainerResp> {
     Ok(GetContainerResp { item: container })
 }
 
+/// The container's image reference and the digest it resolved to.
+pub async fn image_digest(db: &PgPool, id: Uuid) -> Result<ContainerImageDigestResp> {
+    let container = ContainerRepository::new(db.clone()).get(id).await?;
+    let pinned = super::image_ref::ImageRef::parse(&container.image)
+        .is_ok_and(|image| image.digest.is_some());
+    Ok(ContainerImageDigestResp {
+        image: container.image,
+        digest: container.image_digest,
+        pinned,
+    })
+}
+
+/// A container's stored view plus what its runtime reports. A container that
+/// was never started has no runtime id, so only the stored view comes back;
+/// an unreachable runtime is reported in `runtime_error` rather than failing.
+pub async fn inspect_container(db: &PgPool, id: Uuid) -> Result<InspectContainerResp> {
+    let container = get_container(db, id).await?.item;
+    if container.container_runtime_id.is_none() {
+        return Ok(InspectContainerResp {
+            item: container,
+            runtime: None,
+            runtime_error: None,
+        });
+    }
+
+    let runtime = async {
+        let guest_ip = get_guest_ip_from_container(db, &container).await?;
+        let docker = DockerClient::new(&guest_ip)?;
+        let name = extract_docker_container_id(&container)?;
+        docker
+            .inspect(&name)
+            .await?
+            .ok_or_else(|| anyhow!("runtime has no container named {name}"))
+    }
+    .await;
+
+    Ok(match runtime {
+        Ok(runtime) => InspectContainerResp {
+            item: container,
+            runtime: Some(runtime),
+            runtime_error: None,
+        },
+        Err(e) => InspectContainerResp {
+            item: container,
+            runtime: None,
+            runtime_error: Some(format!("{e:#}")),
+        },
+    })
+}
+
+/// Filesystem changes against the image. A container that was never started
+/// has no runtime container to compare, so it gets an empty list and a note.
+pub async fn container_changes(db: &PgPool, id: Uuid) -> Result<ContainerChangesResp> {
+    let container = ContainerRepository::new(db.clone()).get(id).await?;
+    if container.container_runtime_id.is_none() {
+        return Ok(ContainerChangesResp {
+            items: Vec::new(),
+            note: Some("container has never been started".into()),
+        });
+    }
+
+    let guest_ip = get_guest_ip_from_container(db, &container).await?;
+    let docker = DockerClient::new(&guest_ip)?;
+    let items = docker
+        .changes(&extract_docker_container_id(&container)?)
+        .await?;
+    Ok(ContainerChangesResp { items, note: None })
+}
+
 /// Update a container
 pub async fn update_container(
     st: &AppState,
@@ -487,12 +632,7 @@ pub async fn update_container(
 }
 
 /// Delete a container (stops VM and removes all resources)
-pub async fn delete_container(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<OkResponse> {
+pub async fn delete_container(st: &AppState, id: Uuid) -> Result<OkResponse> {
     let repo = ContainerRepository::new(st.db.clone());
 
     let container = repo.get(id).await?;
@@ -529,29 +669,11 @@ pub async fn delete_container(
     // Delete from database
     repo.delete(id).await?;
 
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::DeleteContainer,
-        Some("container"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
     Ok(OkResponse::default())
 }
 
 /// Start a container (VM should already be running, just start Docker container)
-pub async fn start_container(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<OkResponse> {
+pub async fn start_container(st: &AppState, id: Uuid) -> Result<OkResponse> {
     let repo = ContainerRepository::new(st.db.clone());
 
     let container = repo.get(id).await?;
@@ -560,6 +682,22 @@ pub async fn start_container(
         return Err(anyhow!("Container is already running"));
     }
 
+    launch(st, &repo, &container).await?;
+    repo.reset_restart_count(id).await?;
+
+    tracing::info!(container_id = %id, "Container started");
+
+    Ok(OkResponse::default())
+}
+
+/// Boot the container's VM if needed and start the Docker container in it
+async fn launch(
+    st: &AppState,
+    repo: &ContainerRepository,
+    container: &nexus_types::Container,
+) -> Result<()> {
+    let id = container.id;
+
     // Extract VM ID from runtime_id
     let runtime_id = container
         .container_runtime_id
@@ -576,7 +714,7 @@ pub async fn start_container(
     let vm = crate::features::vms::repo::get(&st.db, vm_id).await?;
 
     // Check if VM needs to be started
-    if vm.state != "running" || vm.guest_ip.is_none() {
+    if !crate::features::vms::service::is_running_state(&vm.state) || vm.guest_ip.is_none() {
         tracing::info!(
             container_id = %id,
             vm_id = %vm_id,
@@ -618,47 +756,90 @@ pub async fn start_container(
     }
 
     // Get guest IP from VM
-    let guest_ip = get_guest_ip_from_container(&st.db, &container).await?;
+    let guest_ip = get_guest_ip_from_container(&st.db, container).await?;
 
     // Connect to Docker inside VM
     let docker = DockerClient::new(&guest_ip)?;
 
     // Extract Docker container ID from runtime_id
-    let docker_container_id = extract_docker_container_id(&container)?;
+    let docker_container_id = extract_docker_container_id(container)?;
 
     docker.start_container(&docker_container_id).await?;
     repo.set_started(id).await?;
 
-    tracing::info!(container_id = %id, "Container started");
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::StartContainer,
-        Some("container"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
+    // Port forwards are removed on stop, so re-apply them against the current guest IP
+    super::port_forward::apply_port_forwards(&container.port_mappings, &guest_ip).await?;
 
-    Ok(OkResponse::default())
+    Ok(())
 }
 
-/// Stop a container
-pub async fn stop_container(
+/// What the runtime reports for a container the DB may think is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeState {
+    Running,
+    /// `exit_code` is `None` when the container or its VM is gone entirely.
+    Exited {
+        exit_code: Option<i64>,
+        oom_killed: bool,
+    },
+}
+
+/// Ask the container's VM and Docker daemon whether it is still running.
+/// Errors mean the runtime couldn't be reached, not that the container died.
+pub async fn runtime_state(
     st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<OkResponse> {
+    container: &nexus_types::Container,
+) -> Result<RuntimeState> {
+    let gone = RuntimeState::Exited {
+        exit_code: None,
+        oom_killed: false,
+    };
+    let vm_id = container
+        .container_runtime_id
+        .as_deref()
+        .and_then(|runtime_id| runtime_id.strip_prefix("vm-"))
+        .and_then(|vm_id| Uuid::parse_str(vm_id).ok())
+        .ok_or_else(|| anyhow!("Invalid runtime ID format"))?;
+    let vm = match crate::features::vms::repo::get(&st.db, vm_id).await {
+        Ok(vm) => vm,
+        Err(_) => return Ok(gone),
+    };
+    let Some(guest_ip) = vm
+        .guest_ip
+        .filter(|_| crate::features::vms::service::is_running_state(&vm.state))
+    else {
+        return Ok(gone);
+    };
+
+    let docker = DockerClient::new(&guest_ip)?;
+    let state = docker
+        .inspect_state(&extract_docker_container_id(container)?)
+        .await?;
+    Ok(match state {
+        Some(state) if state.running => RuntimeState::Running,
+        Some(state) => RuntimeState::Exited {
+            exit_code: Some(state.exit_code),
+            oom_killed: state.oom_killed,
+        },
+        None => gone,
+    })
+}
+
+/// Start a container again on behalf of its restart policy. Unlike
+/// [`start_container`] this keeps the restart count and writes no audit entry.
+pub async fn restart_for_policy(st: &AppState, id: Uuid) -> Result<()> {
+    let repo = ContainerRepository::new(st.db.clone());
+    let container = repo.get(id).await?;
+    launch(st, &repo, &container).await
+}
+
+/// Stop a container
+pub async fn stop_container(st: &AppState, id: Uuid) -> Result<OkResponse> {
     let repo = ContainerRepository::new(st.db.clone());
 
     let container = repo.get(id).await?;
 
-    if container.state != "running" {
+    if !ACTIVE_STATES.contains(&container.state.as_str()) {
         return Err(anyhow!("Container is not running"));
     }
 
@@ -667,9 +848,16 @@ pub async fn stop_container(
 
     match guest_ip_result {
         Ok(guest_ip) => {
+            let _ = super::port_forward::cleanup_port_forwards(&container.port_mappings, &guest_ip)
+                .await;
+
             // VM is running, try to stop container gracefully via Docker
             let docker = DockerClient::new(&guest_ip)?;
             let docker_container_id = extract_docker_container_id(&container)?;
+            if container.state == "paused" {
+                // Older Docker refuses to stop a paused container
+                let _ = docker.unpause_container(&docker_container_id).await;
+            }
 
             match docker.stop_container(&docker_container_id, Some(10)).await {
                 Ok(_) => {
@@ -696,23 +884,33 @@ pub async fn stop_container(
 
     repo.set_stopped(id).await?;
     tracing::info!(container_id = %id, "Container marked as stopped");
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::StopContainer,
-        Some("container"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
 
     Ok(OkResponse::default())
 }
 
+/// Stop a container unless it already is, so repeating a stop succeeds.
+/// Paused and still-provisioning containers are stopped too.
+pub async fn ensure_stopped(st: &AppState, id: Uuid) -> Result<OkResponse> {
+    let container = ContainerRepository::new(st.db.clone()).get(id).await?;
+    if !ACTIVE_STATES.contains(&container.state.as_str()) {
+        return Ok(OkResponse::default());
+    }
+    stop_container(st, id).await
+}
+
+/// Ids of the containers placed on `host_id` that a stop applies to
+/// (running, paused or provisioning), newest first.
+pub async fn active_on_host(st: &AppState, host_id: Uuid) -> Result<Vec<Uuid>> {
+    let containers = ContainerRepository::new(st.db.clone())
+        .list(None, Some(host_id), None, 0)
+        .await?;
+    Ok(containers
+        .into_iter()
+        .filter(|c| ACTIVE_STATES.contains(&c.state.as_str()))
+        .map(|c| c.id)
+        .collect())
+}
+
 /// Restart a container
 pub async fn restart_container(st: &AppState, id: Uuid) -> Result<OkResponse> {
     let repo = ContainerRepository::new(st.db.clone());
@@ -727,6 +925,7 @@ pub async fn restart_container(st: &AppState, id: Uuid) -> Result<OkResponse> {
         .restart_container(&docker_container_id, Some(10))
         .await?;
     repo.set_started(id).await?;
+    repo.reset_restart_count(id).await?;
 
     tracing::info!(container_id = %id, "Container restarted");
 
@@ -830,7 +1029,9 @@ pub async fn get_container_stats(st: &AppState, id: Uuid) -> Result<ContainerSta
     }
 
     // Return latest stats from database
-    get_latest_stats(&st.db, id).await
+    let mut resp = get_latest_stats(&st.db, id).await?;
+    resp.uptime_seconds = container.uptime_seconds;
+    Ok(resp)
 }
 
 /// Get latest stats from database
@@ -838,7 +1039,10 @@ async fn get_latest_stats(db: &PgPool, id: Uuid) -> Result<ContainerStatsResp> {
     let repo = ContainerRepository::new(db.clone());
     let stats = repo.get_latest_stats(id, 10).await?;
 
-    Ok(ContainerStatsResp { items: stats })
+    Ok(ContainerStatsResp {
+        items: stats,
+        uptime_seconds: None,
+    })
 }
 
 /// Execute a command in a container
@@ -1,11 +1,13 @@
 use axum::{
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
 
 pub mod repo;
 pub mod routes;
+pub mod secrets;
 pub mod service;
+pub mod versions;
 pub mod vm;
 
 pub fn router() -> Router {
@@ -16,5 +18,15 @@ pub fn router() -> Router {
             get(routes::get).put(routes::update).delete(routes::delete),
         )
         .route("/:id/invoke", post(routes::invoke))
+        .route("/:id/invoke/raw", post(routes::invoke_raw))
         .route("/:id/logs", get(routes::logs))
+        .route(
+            "/:id/versions",
+            get(routes::list_versions).post(routes::publish_version),
+        )
+        .route("/:id/aliases", get(routes::list_aliases))
+        .route(
+            "/:id/aliases/:name",
+            put(routes::put_alias).delete(routes::delete_alias),
+        )
 }
@@ -13,6 +13,8 @@ pub struct FunctionRow {
     pub memory_mb: i32,
     pub vcpu: i32,
     pub env_vars: Option<serde_json::Value>,
+    /// Name -> ciphertext; see `super::secrets`.
+    pub secret_env_vars: Option<serde_json::Value>,
     pub vm_id: Option<Uuid>,
     pub guest_ip: Option<String>,
     pub port: i32,
@@ -35,17 +37,42 @@ pub struct FunctionInvocationRow {
     pub response: Option<serde_json::Value>,
     pub logs: Vec<String>,
     pub error: Option<String>,
+    /// Published version that served the invocation; `None` is `$LATEST`.
+    pub version: Option<i32>,
     pub invoked_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Clone, Serialize, sqlx::FromRow)]
+pub struct FunctionVersionRow {
+    pub function_id: Uuid,
+    pub version: i32,
+    pub code: String,
+    pub handler: String,
+    pub timeout_seconds: i32,
+    pub env_vars: Option<serde_json::Value>,
+    pub secret_env_vars: Option<serde_json::Value>,
+    pub description: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone, Serialize, sqlx::FromRow)]
+pub struct FunctionAliasRow {
+    pub function_id: Uuid,
+    pub name: String,
+    pub version: i32,
+    pub canary_version: Option<i32>,
+    pub canary_percent: Option<i32>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
 // ========================================
 // Function CRUD
 // ========================================
 
 pub async fn insert(db: &PgPool, row: &FunctionRow) -> sqlx::Result<()> {
     sqlx::query(
-        r#"INSERT INTO function (id, name, runtime, code, handler, timeout_seconds, memory_mb, vcpu, env_vars, port, state, created_by_user_id)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+        r#"INSERT INTO function (id, name, runtime, code, handler, timeout_seconds, memory_mb, vcpu, env_vars, secret_env_vars, port, state, created_by_user_id)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
     )
     .bind(row.id)
     .bind(&row.name)
@@ -56,6 +83,7 @@ pub async fn insert(db: &PgPool, row: &FunctionRow) -> sqlx::Result<()> {
     .bind(row.memory_mb)
     .bind(row.vcpu)
     .bind(&row.env_vars)
+    .bind(&row.secret_env_vars)
     .bind(row.port)
     .bind(&row.state)
     .bind(row.created_by_user_id)
@@ -68,7 +96,7 @@ pub async fn list(db: &PgPool) -> sqlx::Result<Vec<FunctionRow>> {
     sqlx::query_as::<_, FunctionRow>(
         r#"
         SELECT id, name, runtime, code, handler, timeout_seconds, memory_mb, vcpu,
-               env_vars, vm_id, guest_ip, port, state, created_by_user_id, created_at, updated_at, last_invoked_at
+               env_vars, secret_env_vars, vm_id, guest_ip, port, state, created_by_user_id, created_at, updated_at, last_invoked_at
         FROM function
         ORDER BY created_at DESC
         "#,
@@ -81,7 +109,7 @@ pub async fn get(db: &PgPool, id: Uuid) -> sqlx::Result<Option<FunctionRow>> {
     sqlx::query_as::<_, FunctionRow>(
         r#"
         SELECT id, name, runtime, code, handler, timeout_seconds, memory_mb, vcpu,
-               env_vars, vm_id, guest_ip, port, state, created_by_user_id, created_at, updated_at, last_invoked_at
+               env_vars, secret_env_vars, vm_id, guest_ip, port, state, created_by_user_id, created_at, updated_at, last_invoked_at
         FROM function
         WHERE id = $1
         "#,
@@ -102,6 +130,7 @@ pub async fn update(
     timeout_seconds: Option<i32>,
     memory_mb: Option<i32>,
     env_vars: Option<&serde_json::Value>,
+    secret_env_vars: Option<&serde_json::Value>,
 ) -> sqlx::Result<()> {
     let mut query = String::from("UPDATE function SET updated_at = now()");
     let mut bind_count = 1;
@@ -134,6 +163,10 @@ pub async fn update(
         query.push_str(&format!(", env_vars = ${}", bind_count));
         bind_count += 1;
     }
+    if secret_env_vars.is_some() {
+        query.push_str(&format!(", secret_env_vars = ${}", bind_count));
+        bind_count += 1;
+    }
 
     query.push_str(&format!(" WHERE id = ${}", bind_count));
 
@@ -160,6 +193,9 @@ pub async fn update(
     if let Some(v) = env_vars {
         q = q.bind(v);
     }
+    if let Some(v) = secret_env_vars {
+        q = q.bind(v);
+    }
 
     q = q.bind(id);
     q.execute(db).await?;
@@ -213,8 +249,8 @@ pub async fn update_state(db: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()
 pub async fn insert_invocation(db: &PgPool, row: &FunctionInvocationRow) -> sqlx::Result<()> {
     sqlx::query(
         r#"INSERT INTO function_invocation
-           (id, function_id, status, duration_ms, memory_used_mb, request_id, event, response, logs, error, invoked_at)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
+           (id, function_id, status, duration_ms, memory_used_mb, request_id, event, response, logs, error, version, invoked_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
     )
     .bind(row.id)
     .bind(row.function_id)
@@ -226,6 +262,7 @@ pub async fn insert_invocation(db: &PgPool, row: &FunctionInvocationRow) -> sqlx
     .bind(&row.response)
     .bind(&row.logs)
     .bind(&row.error)
+    .bind(row.version)
     .bind(row.invoked_at)
     .execute(db)
     .await?;
@@ -241,7 +278,7 @@ pub async fn list_invocations(
     let mut query = String::from(
         r#"
         SELECT id, function_id, status, duration_ms, memory_used_mb, request_id,
-               event, response, logs, error, invoked_at
+               event, response, logs, error, version, invoked_at
         FROM function_invocation
         WHERE function_id = $1
         "#,
@@ -272,3 +309,151 @@ pub async fn list_invocations(
 
     q.fetch_all(db).await
 }
+
+// ========================================
+// Function Versions & Aliases
+// ========================================
+
+/// Snapshots the function's current code and config as the next version
+/// number. Returns `None` if the function does not exist.
+pub async fn publish_version(
+    db: &PgPool,
+    function_id: Uuid,
+    description: Option<&str>,
+) -> sqlx::Result<Option<FunctionVersionRow>> {
+    let mut tx = db.begin().await?;
+    // Serialise concurrent publishes of the same function on its row lock.
+    let locked = sqlx::query("SELECT id FROM function WHERE id = $1 FOR UPDATE")
+        .bind(function_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if locked.is_none() {
+        return Ok(None);
+    }
+    let row = sqlx::query_as::<_, FunctionVersionRow>(
+        r#"
+        INSERT INTO function_version
+            (function_id, version, code, handler, timeout_seconds, env_vars, secret_env_vars, description)
+        SELECT f.id,
+               COALESCE((SELECT MAX(version) FROM function_version WHERE function_id = f.id), 0) + 1,
+               f.code, f.handler, f.timeout_seconds, f.env_vars, f.secret_env_vars, $2
+        FROM function f
+        WHERE f.id = $1
+        RETURNING function_id, version, code, handler, timeout_seconds, env_vars,
+                  secret_env_vars, description, created_at
+        "#,
+    )
+    .bind(function_id)
+    .bind(description)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(row))
+}
+
+pub async fn list_versions(
+    db: &PgPool,
+    function_id: Uuid,
+) -> sqlx::Result<Vec<FunctionVersionRow>> {
+    sqlx::query_as::<_, FunctionVersionRow>(
+        r#"
+        SELECT function_id, version, code, handler, timeout_seconds, env_vars,
+               secret_env_vars, description, created_at
+        FROM function_version
+        WHERE function_id = $1
+        ORDER BY version DESC
+        "#,
+    )
+    .bind(function_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get_version(
+    db: &PgPool,
+    function_id: Uuid,
+    version: i32,
+) -> sqlx::Result<Option<FunctionVersionRow>> {
+    sqlx::query_as::<_, FunctionVersionRow>(
+        r#"
+        SELECT function_id, version, code, handler, timeout_seconds, env_vars,
+               secret_env_vars, description, created_at
+        FROM function_version
+        WHERE function_id = $1 AND version = $2
+        "#,
+    )
+    .bind(function_id)
+    .bind(version)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn list_aliases(db: &PgPool, function_id: Uuid) -> sqlx::Result<Vec<FunctionAliasRow>> {
+    sqlx::query_as::<_, FunctionAliasRow>(
+        r#"
+        SELECT function_id, name, version, canary_version, canary_percent, updated_at
+        FROM function_alias
+        WHERE function_id = $1
+        ORDER BY name
+        "#,
+    )
+    .bind(function_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get_alias(
+    db: &PgPool,
+    function_id: Uuid,
+    name: &str,
+) -> sqlx::Result<Option<FunctionAliasRow>> {
+    sqlx::query_as::<_, FunctionAliasRow>(
+        r#"
+        SELECT function_id, name, version, canary_version, canary_percent, updated_at
+        FROM function_alias
+        WHERE function_id = $1 AND name = $2
+        "#,
+    )
+    .bind(function_id)
+    .bind(name)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn upsert_alias(
+    db: &PgPool,
+    function_id: Uuid,
+    name: &str,
+    version: i32,
+    canary_version: Option<i32>,
+    canary_percent: Option<i32>,
+) -> sqlx::Result<FunctionAliasRow> {
+    sqlx::query_as::<_, FunctionAliasRow>(
+        r#"
+        INSERT INTO function_alias (function_id, name, version, canary_version, canary_percent)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (function_id, name) DO UPDATE
+        SET version = EXCLUDED.version,
+            canary_version = EXCLUDED.canary_version,
+            canary_percent = EXCLUDED.canary_percent,
+            updated_at = now()
+        RETURNING function_id, name, version, canary_version, canary_percent, updated_at
+        "#,
+    )
+    .bind(function_id)
+    .bind(name)
+    .bind(version)
+    .bind(canary_version)
+    .bind(canary_percent)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn delete_alias(db: &PgPool, function_id: Uuid, name: &str) -> sqlx::Result<bool> {
+    let res = sqlx::query("DELETE FROM function_alias WHERE function_id = $1 AND name = $2")
+        .bind(function_id)
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
@@ -1,13 +1,17 @@
+use crate::features::users::audit::Actor;
 use crate::features::users::repo::AuthenticatedUser;
 use crate::AppState;
 use axum::{
     extract::{Path, Query},
-    http::StatusCode,
+    http::{header, StatusCode},
+    response::Response,
     Extension, Json,
 };
 use nexus_types::{
-    CreateFunctionReq, CreateFunctionResp, FunctionPathParams, GetFunctionResp, InvokeFunctionReq,
-    InvokeFunctionResp, ListFunctionsResp, ListInvocationsParams, ListInvocationsResp, OkResponse,
+    AuditAction, CreateFunctionReq, CreateFunctionResp, FunctionAlias, FunctionAliasPathParams,
+    FunctionPathParams, FunctionVersion, GetFunctionResp, InvokeFunctionReq, InvokeFunctionResp,
+    ListFunctionAliasesResp, ListFunctionVersionsResp, ListFunctionsResp, ListInvocationsParams,
+    ListInvocationsResp, OkResponse, PublishFunctionVersionReq, PutFunctionAliasReq,
     UpdateFunctionReq,
 };
 
@@ -18,22 +22,37 @@ use nexus_types::{
     responses(
         (status = 200, description = "Function created", body = CreateFunctionResp),
         (status = 400, description = "Invalid request"),
+        (status = 409, description = "The function would exceed the caller's quota"),
         (status = 500, description = "Failed to create function"),
     ),
     tag = "Functions"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Json(req): Json<CreateFunctionReq>,
 ) -> Result<Json<CreateFunctionResp>, StatusCode> {
-    let (user_id, username) = extract_user_info(user);
-    let resp = super::service::create_function(&st, req, user_id, &username)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to create function: {}", e);
+    let details = serde_json::json!({"name": &req.name, "runtime": &req.runtime});
+    let result = super::service::create_function(&st, req, actor.user_id, &actor.username).await;
+    let id = result.as_ref().ok().map(|resp| resp.id);
+    actor
+        .record_with(
+            AuditAction::CreateFunction,
+            ("function", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to create function: {}", e);
+        if e.to_string().starts_with("invalid") {
+            StatusCode::BAD_REQUEST
+        } else if crate::features::users::quota::is_exceeded(&e) {
+            StatusCode::CONFLICT
+        } else {
             StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        }
+    })?;
     Ok(Json(resp))
 }
 
@@ -91,6 +110,7 @@ pub async fn get(
     request_body = UpdateFunctionReq,
     responses(
         (status = 200, description = "Function updated", body = GetFunctionResp),
+        (status = 400, description = "Invalid request"),
         (status = 404, description = "Function not found"),
         (status = 500, description = "Failed to update function"),
     ),
@@ -98,19 +118,24 @@ pub async fn get(
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(FunctionPathParams { id }): Path<FunctionPathParams>,
     Json(req): Json<UpdateFunctionReq>,
 ) -> Result<Json<GetFunctionResp>, StatusCode> {
-    let resp = super::service::update_function(&st, id, req)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to update function: {}", e);
-            if e.to_string().contains("not found") {
-                StatusCode::NOT_FOUND
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+    let result = super::service::update_function(&st, id, req).await;
+    actor
+        .record(AuditAction::UpdateFunction, ("function", id), &result)
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to update function: {}", e);
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if e.to_string().starts_with("invalid") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
     Ok(Json(resp))
 }
 
@@ -127,16 +152,17 @@ pub async fn update(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(FunctionPathParams { id }): Path<FunctionPathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::delete_function(&st, id, user_id, &username)
-        .await
-        .map_err(|e| {
-            eprintln!("Failed to delete function: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    let result = super::service::delete_function(&st, id).await;
+    actor
+        .record(AuditAction::DeleteFunction, ("function", id), &result)
+        .await;
+    result.map_err(|e| {
+        eprintln!("Failed to delete function: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -148,6 +174,7 @@ pub async fn delete(
     responses(
         (status = 200, description = "Function invoked", body = InvokeFunctionResp),
         (status = 404, description = "Function not found"),
+        (status = 409, description = "A version was requested but the function's runtime cannot run published versions"),
         (status = 500, description = "Failed to invoke function"),
     ),
     tag = "Functions"
@@ -165,6 +192,8 @@ pub async fn invoke(
             eprintln!("Failed to invoke function: {}", e);
             if e.to_string().contains("not found") {
                 StatusCode::NOT_FOUND
+            } else if super::versions::is_runtime_lacking_versions(&e) {
+                StatusCode::CONFLICT
             } else {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
@@ -172,6 +201,47 @@ pub async fn invoke(
     Ok(Json(resp))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/functions/{id}/invoke/raw",
+    params(FunctionPathParams),
+    request_body = InvokeFunctionReq,
+    responses(
+        (status = 200, description = "The function's response body, verbatim with its content type", content_type = "application/octet-stream"),
+        (status = 404, description = "Function not found"),
+        (status = 409, description = "A version was requested but the function's runtime cannot run published versions"),
+        (status = 500, description = "Failed to invoke function"),
+    ),
+    tag = "Functions"
+)]
+pub async fn invoke_raw(
+    Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(FunctionPathParams { id }): Path<FunctionPathParams>,
+    Json(req): Json<InvokeFunctionReq>,
+) -> Result<Response, StatusCode> {
+    let (user_id, username) = extract_user_info(user);
+    let raw = super::service::invoke_function_raw(&st, id, req, user_id, &username)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to invoke function: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else if super::versions::is_runtime_lacking_versions(&e) {
+                StatusCode::CONFLICT
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    let mut builder = Response::builder().status(raw.status);
+    if let Some(content_type) = raw.content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder
+        .body(raw.body)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 #[utoipa::path(
     get,
     path = "/v1/functions/{id}/logs",
@@ -197,6 +267,189 @@ pub async fn logs(
     Ok(Json(resp))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/functions/{id}/versions",
+    params(FunctionPathParams),
+    request_body = PublishFunctionVersionReq,
+    responses(
+        (status = 200, description = "Current code and config published as a new version", body = FunctionVersion),
+        (status = 404, description = "Function not found"),
+        (status = 500, description = "Failed to publish version"),
+    ),
+    tag = "Functions"
+)]
+pub async fn publish_version(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(FunctionPathParams { id }): Path<FunctionPathParams>,
+    body: Option<Json<PublishFunctionVersionReq>>,
+) -> Result<Json<FunctionVersion>, StatusCode> {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    let result = super::service::publish_version(&st, id, req).await;
+    let details = result
+        .as_ref()
+        .ok()
+        .map(|version| serde_json::json!({"version": version.version}));
+    actor
+        .record_with(
+            AuditAction::PublishFunctionVersion,
+            ("function", id),
+            details,
+            &result,
+        )
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to publish function version: {}", e);
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/functions/{id}/versions",
+    params(FunctionPathParams),
+    responses(
+        (status = 200, description = "Published versions, newest first", body = ListFunctionVersionsResp),
+        (status = 404, description = "Function not found"),
+        (status = 500, description = "Failed to list versions"),
+    ),
+    tag = "Functions"
+)]
+pub async fn list_versions(
+    Extension(st): Extension<AppState>,
+    Path(FunctionPathParams { id }): Path<FunctionPathParams>,
+) -> Result<Json<ListFunctionVersionsResp>, StatusCode> {
+    let resp = super::service::list_versions(&st.db, id)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to list function versions: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/functions/{id}/aliases",
+    params(FunctionPathParams),
+    responses(
+        (status = 200, description = "Aliases listed", body = ListFunctionAliasesResp),
+        (status = 404, description = "Function not found"),
+        (status = 500, description = "Failed to list aliases"),
+    ),
+    tag = "Functions"
+)]
+pub async fn list_aliases(
+    Extension(st): Extension<AppState>,
+    Path(FunctionPathParams { id }): Path<FunctionPathParams>,
+) -> Result<Json<ListFunctionAliasesResp>, StatusCode> {
+    let resp = super::service::list_aliases(&st.db, id)
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to list function aliases: {}", e);
+            if e.to_string().contains("not found") {
+                StatusCode::NOT_FOUND
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/functions/{id}/aliases/{name}",
+    params(FunctionAliasPathParams),
+    request_body = PutFunctionAliasReq,
+    responses(
+        (status = 200, description = "Alias created or repointed", body = FunctionAlias),
+        (status = 400, description = "Invalid alias name, canary split or unpublished version"),
+        (status = 404, description = "Function not found"),
+        (status = 500, description = "Failed to update alias"),
+    ),
+    tag = "Functions"
+)]
+pub async fn put_alias(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(FunctionAliasPathParams { id, name }): Path<FunctionAliasPathParams>,
+    Json(req): Json<PutFunctionAliasReq>,
+) -> Result<Json<FunctionAlias>, StatusCode> {
+    let details = serde_json::json!({
+        "alias": &name,
+        "version": req.version,
+        "canary_version": req.canary_version,
+        "canary_percent": req.canary_percent,
+    });
+    let result = super::service::put_alias(&st, id, &name, req).await;
+    actor
+        .record_with(
+            AuditAction::UpdateFunctionAlias,
+            ("function", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let resp = result.map_err(|e| {
+        eprintln!("Failed to update function alias: {}", e);
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else if e.to_string().starts_with("invalid") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/functions/{id}/aliases/{name}",
+    params(FunctionAliasPathParams),
+    responses(
+        (status = 200, description = "Alias deleted", body = OkResponse),
+        (status = 404, description = "Alias not found"),
+        (status = 500, description = "Failed to delete alias"),
+    ),
+    tag = "Functions"
+)]
+pub async fn delete_alias(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(FunctionAliasPathParams { id, name }): Path<FunctionAliasPathParams>,
+) -> Result<Json<OkResponse>, StatusCode> {
+    let result = super::service::delete_alias(&st, id, &name).await;
+    let details = serde_json::json!({"alias": &name});
+    actor
+        .record_with(
+            AuditAction::DeleteFunctionAlias,
+            ("function", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    result.map_err(|e| {
+        eprintln!("Failed to delete function alias: {}", e);
+        if e.to_string().contains("not found") {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    Ok(Json(OkResponse::default()))
+}
+
 fn extract_user_info(user: Option<Extension<AuthenticatedUser>>) -> (Option<uuid::Uuid>, String) {
     match user {
         Some(Extension(u)) => (Some(u.id), u.username),
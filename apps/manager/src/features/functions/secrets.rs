@@ -0,0 +1,123 @@
+//! Secret function env vars: sealed with the manager's master key
+//! (`SSO_ENCRYPTION_KEY`) before they reach the DB, shown as `***`, and only
+//! opened to build the environment a function runs with.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use crate::features::sso::crypto;
+
+/// What every secret value reads as in API responses.
+pub const REDACTED: &str = "***";
+
+/// Encrypts each value. A value of `***` (a redacted response sent back
+/// unchanged) keeps the secret already stored under that name. New values
+/// are refused while the manager runs on the insecure default key.
+pub fn seal(
+    vars: &HashMap<String, String>,
+    existing: Option<&Value>,
+    key: &[u8; 32],
+) -> Result<Value> {
+    let mut sealed = Map::new();
+    for (name, value) in vars {
+        if name.trim().is_empty() {
+            bail!("invalid secret_env_vars: names must not be empty");
+        }
+        let ciphertext = if value == REDACTED {
+            match existing.and_then(|e| e.get(name)).and_then(Value::as_str) {
+                Some(kept) => kept.to_string(),
+                None => bail!("invalid secret_env_vars: {name} has no stored value to keep"),
+            }
+        } else if crypto::is_insecure_default(key) {
+            bail!("invalid secret_env_vars: SSO_ENCRYPTION_KEY must be set to store secrets");
+        } else {
+            crypto::encrypt(value, key)?
+        };
+        sealed.insert(name.clone(), Value::String(ciphertext));
+    }
+    Ok(Value::Object(sealed))
+}
+
+/// The names of the stored secrets, each mapped to `***`.
+pub fn redact(sealed: &Value) -> HashMap<String, String> {
+    sealed
+        .as_object()
+        .map(|vars| {
+            vars.keys()
+                .map(|name| (name.clone(), REDACTED.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The environment a function is invoked with: its plain `env_vars` plus
+/// its decrypted secrets, which win on a name clash.
+pub fn invoke_env(
+    plain: Option<&Value>,
+    sealed: Option<&Value>,
+    key: &[u8; 32],
+) -> Result<Map<String, Value>> {
+    let mut env = Map::new();
+    for (name, value) in plain.and_then(Value::as_object).into_iter().flatten() {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        env.insert(name.clone(), Value::String(value));
+    }
+    for (name, value) in sealed.and_then(Value::as_object).into_iter().flatten() {
+        let ciphertext = value.as_str().unwrap_or_default();
+        let plaintext = crypto::decrypt(ciphertext, key)
+            .with_context(|| format!("failed to decrypt secret env var {name}"))?;
+        env.insert(name.clone(), Value::String(plaintext));
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_sealed_redacted_and_opened_for_invoke() {
+        let key = crypto::derive_key("test-key");
+        let vars = HashMap::from([("API_KEY".to_string(), "s3cret".to_string())]);
+        let sealed = seal(&vars, None, &key).unwrap();
+        assert!(!sealed.to_string().contains("s3cret"));
+        assert_eq!(redact(&sealed)["API_KEY"], REDACTED);
+
+        let plain = json!({"API_KEY": "overridden", "RETRIES": 3});
+        let env = invoke_env(Some(&plain), Some(&sealed), &key).unwrap();
+        assert_eq!(env["API_KEY"], "s3cret");
+        assert_eq!(env["RETRIES"], "3");
+    }
+
+    #[test]
+    fn redacted_value_keeps_the_stored_secret() {
+        let key = crypto::derive_key("test-key");
+        let stored = seal(
+            &HashMap::from([("TOKEN".to_string(), "abc".to_string())]),
+            None,
+            &key,
+        )
+        .unwrap();
+
+        let resent = HashMap::from([("TOKEN".to_string(), REDACTED.to_string())]);
+        let kept = seal(&resent, Some(&stored), &key).unwrap();
+        assert_eq!(kept, stored);
+
+        let unknown = HashMap::from([("OTHER".to_string(), REDACTED.to_string())]);
+        assert!(seal(&unknown, Some(&stored), &key).is_err());
+    }
+
+    #[test]
+    fn new_secrets_are_refused_under_the_default_key() {
+        let key = crypto::derive_key(crypto::INSECURE_DEFAULT_KEY);
+        let vars = HashMap::from([("API_KEY".to_string(), "s3cret".to_string())]);
+        let err = seal(&vars, None, &key).unwrap_err();
+        assert!(err.to_string().contains("SSO_ENCRYPTION_KEY"));
+    }
+}
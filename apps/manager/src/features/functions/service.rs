@@ -2,16 +2,19 @@ use crate::features::users::audit;
 use crate::AppState;
 use anyhow::{Context, Result};
 use nexus_types::{
-    AuditAction, CreateFunctionReq, CreateFunctionResp, Function, FunctionInvocation,
-    GetFunctionResp, InvokeFunctionReq, InvokeFunctionResp, ListFunctionsResp, ListInvocationsResp,
-    UpdateFunctionReq,
+    AuditAction, CreateFunctionReq, CreateFunctionResp, Function, FunctionAlias,
+    FunctionInvocation, FunctionVersion, GetFunctionResp, InvokeFunctionReq, InvokeFunctionResp,
+    ListFunctionAliasesResp, ListFunctionVersionsResp, ListFunctionsResp, ListInvocationsResp,
+    PublishFunctionVersionReq, PutFunctionAliasReq, UpdateFunctionReq,
 };
 use serde_json::json;
 use sqlx::PgPool;
 use std::time::Instant;
+use tokio_util::task::TaskTracker;
 use uuid::Uuid;
 
-use super::repo::{FunctionInvocationRow, FunctionRow};
+use super::repo::{FunctionAliasRow, FunctionInvocationRow, FunctionRow, FunctionVersionRow};
+use super::versions::{self, Qualifier};
 
 // ========================================
 // Function CRUD
@@ -26,6 +29,25 @@ pub async fn create_function(
     // Validate runtime
     validate_runtime(&req.runtime)?;
 
+    let secret_env_vars = req
+        .secret_env_vars
+        .as_ref()
+        .map(|vars| super::secrets::seal(vars, None, &st.sso_encryption_key))
+        .transpose()?;
+
+    // Held until the function row is in
+    let reservation = crate::features::users::quota::reserve(
+        &st.db,
+        user_id,
+        nexus_types::UserQuotaUsage {
+            vms: 0,
+            vcpu: req.vcpu as i64,
+            mem_mib: req.memory_mb as i64,
+            containers: 0,
+        },
+    )
+    .await?;
+
     let id = Uuid::new_v4();
     let row = FunctionRow {
         id,
@@ -37,31 +59,21 @@ pub async fn create_function(
         memory_mb: req.memory_mb,
         vcpu: req.vcpu,
         env_vars: req.env_vars.clone(),
+        secret_env_vars,
         vm_id: None,
         guest_ip: None,
         port: 3000,
         state: "creating".to_string(),
-        created_by_user_id: None, // TODO: Set from authenticated user context
+        created_by_user_id: user_id,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
         last_invoked_at: None,
     };
 
     super::repo::insert(&st.db, &row).await?;
-
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::CreateFunction,
-        Some("function"),
-        Some(id),
-        Some(json!({"event": "creation_started", "name": &req.name, "runtime": &req.runtime})),
-        None,
-        true,
-        None,
-    )
-    .await;
+    if let Err(err) = reservation.release().await {
+        tracing::warn!(function_id = %id, error = ?err, "failed to release quota reservation");
+    }
 
     // Spawn dedicated MicroVM for this function in the background
     let st_clone = st.clone();
@@ -76,7 +88,7 @@ pub async fn create_function(
     let spawn_username = username.to_string();
     let spawn_user_id = user_id;
 
-    tokio::spawn(async move {
+    st.background.spawn(async move {
         match super::vm::create_function_vm(
             &st_clone,
             function_id,
@@ -273,6 +285,18 @@ pub async fn update_function(
         validate_runtime(runtime)?;
     }
 
+    let secret_env_vars = req
+        .secret_env_vars
+        .as_ref()
+        .map(|vars| {
+            super::secrets::seal(
+                vars,
+                existing.secret_env_vars.as_ref(),
+                &st.sso_encryption_key,
+            )
+        })
+        .transpose()?;
+
     // Update database
     super::repo::update(
         &st.db,
@@ -284,9 +308,14 @@ pub async fn update_function(
         req.timeout_seconds,
         req.memory_mb,
         req.env_vars.as_ref(),
+        secret_env_vars.as_ref(),
     )
     .await?;
 
+    if req.publish {
+        super::repo::publish_version(&st.db, id, None).await?;
+    }
+
     // If code or handler changed, reload it in the running VM
     let code_changed = req.code.is_some();
     let handler_changed = req.handler.is_some();
@@ -305,7 +334,7 @@ pub async fn update_function(
         );
 
         // Reload code in background (don't block the response)
-        tokio::spawn(async move {
+        st.background.spawn(async move {
             if let Err(e) =
                 super::vm::update_function_code(&guest_ip, &runtime, &new_code, &new_handler).await
             {
@@ -319,12 +348,7 @@ pub async fn update_function(
     get_function(&st.db, id).await
 }
 
-pub async fn delete_function(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
+pub async fn delete_function(st: &AppState, id: Uuid) -> Result<()> {
     // Get function to find VM ID
     let func = super::repo::get(&st.db, id)
         .await?
@@ -356,19 +380,6 @@ pub async fn delete_function(
 
     // Delete function record
     super::repo::delete(&st.db, id).await?;
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::DeleteFunction,
-        Some("function"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
     Ok(())
 }
 
@@ -399,6 +410,10 @@ pub async fn invoke_function(
         .as_ref()
         .context("Function VM has no IP yet")?;
 
+    let target = resolve_target(&st.db, &func, req.qualifier.as_deref()).await?;
+    target.ensure_runnable(guest_ip, func.port).await?;
+    let payload = target.payload(&req.event, &st.sso_encryption_key)?;
+
     // Generate request ID
     let request_id = Uuid::new_v4().to_string();
 
@@ -411,9 +426,9 @@ pub async fn invoke_function(
     let client = reqwest::Client::new();
     let http_result = client
         .post(&url)
-        .json(&serde_json::json!({ "event": req.event }))
+        .json(&payload)
         .timeout(std::time::Duration::from_secs(
-            func.timeout_seconds as u64 + 5,
+            target.timeout_seconds as u64 + 5,
         ))
         .send()
         .await;
@@ -482,6 +497,7 @@ pub async fn invoke_function(
         response: response.clone(),
         logs: logs.clone(),
         error: error.clone(),
+        version: target.version(),
         invoked_at: chrono::Utc::now(),
     };
 
@@ -514,6 +530,356 @@ pub async fn invoke_function(
     })
 }
 
+/// The function's own HTTP response for a raw invocation. `body` streams
+/// straight from the function VM; the invocation is recorded once it ends.
+pub struct RawInvocation {
+    pub status: reqwest::StatusCode,
+    pub content_type: Option<String>,
+    pub body: axum::body::Body,
+}
+
+pub async fn invoke_function_raw(
+    st: &AppState,
+    id: Uuid,
+    req: InvokeFunctionReq,
+    user_id: Option<Uuid>,
+    username: &str,
+) -> Result<RawInvocation> {
+    let func = super::repo::get(&st.db, id)
+        .await?
+        .context("Function not found")?;
+
+    if func.state != "ready" {
+        anyhow::bail!("Function is not ready (state: {})", func.state);
+    }
+
+    let guest_ip = func
+        .guest_ip
+        .as_ref()
+        .context("Function VM has no IP yet")?;
+
+    let target = resolve_target(&st.db, &func, req.qualifier.as_deref()).await?;
+    target.ensure_runnable(guest_ip, func.port).await?;
+    let payload = target.payload(&req.event, &st.sso_encryption_key)?;
+
+    let url = format!("http://{}:{}/invoke/raw", guest_ip, func.port);
+    let mut record = RawInvocationRecord {
+        db: st.db.clone(),
+        background: st.background.clone(),
+        function_id: id,
+        request_id: Uuid::new_v4().to_string(),
+        event: req.event,
+        version: target.version(),
+        user_id,
+        username: username.to_string(),
+        start: Instant::now(),
+        content_type: None,
+        size_bytes: 0,
+        error: None,
+    };
+
+    let resp = match reqwest::Client::new()
+        .post(&url)
+        .json(&payload)
+        .timeout(std::time::Duration::from_secs(
+            target.timeout_seconds as u64 + 5,
+        ))
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            record.error = Some(format!("HTTP request failed: {}", e));
+            drop(record);
+            return Err(anyhow::Error::new(e).context("invoking function"));
+        }
+    };
+
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    if !status.is_success() {
+        record.error = Some(format!("HTTP {}", status));
+    }
+    record.content_type = content_type.clone();
+
+    // Each chunk is forwarded as it arrives; only its length is kept
+    let stream = futures::stream::unfold(Some((resp, record)), |state| async move {
+        let (mut resp, mut record) = state?;
+        match resp.chunk().await {
+            Ok(Some(chunk)) => {
+                record.size_bytes += chunk.len() as u64;
+                Some((Ok(chunk), Some((resp, record))))
+            }
+            Ok(None) => {
+                drop(record);
+                None
+            }
+            Err(e) => {
+                record.error = Some(format!("response stream failed: {}", e));
+                drop(record);
+                Some((Err(std::io::Error::other(e)), None))
+            }
+        }
+    });
+
+    Ok(RawInvocation {
+        status,
+        content_type,
+        body: axum::body::Body::from_stream(stream),
+    })
+}
+
+/// What a raw invocation stores: the response's content type and size, never
+/// its body. Written when dropped: at the end of the stream, or mid-stream if
+/// the caller hung up.
+struct RawInvocationRecord {
+    db: PgPool,
+    background: TaskTracker,
+    function_id: Uuid,
+    request_id: String,
+    event: serde_json::Value,
+    version: Option<i32>,
+    user_id: Option<Uuid>,
+    username: String,
+    start: Instant,
+    content_type: Option<String>,
+    size_bytes: u64,
+    error: Option<String>,
+}
+
+impl RawInvocationRecord {
+    fn row(&mut self) -> FunctionInvocationRow {
+        FunctionInvocationRow {
+            id: Uuid::new_v4(),
+            function_id: self.function_id,
+            status: if self.error.is_some() {
+                "error"
+            } else {
+                "success"
+            }
+            .to_string(),
+            duration_ms: self.start.elapsed().as_millis() as i64,
+            memory_used_mb: None,
+            request_id: std::mem::take(&mut self.request_id),
+            event: self.event.take(),
+            response: Some(json!({
+                "content_type": self.content_type,
+                "size_bytes": self.size_bytes,
+            })),
+            logs: vec![],
+            error: self.error.clone(),
+            version: self.version,
+            invoked_at: chrono::Utc::now(),
+        }
+    }
+}
+
+impl Drop for RawInvocationRecord {
+    fn drop(&mut self) {
+        let row = self.row();
+        let db = self.db.clone();
+        let user_id = self.user_id;
+        let username = std::mem::take(&mut self.username);
+        self.background.spawn(async move {
+            if let Err(e) = super::repo::insert_invocation(&db, &row).await {
+                eprintln!(
+                    "[Function {}] Failed to record invocation: {}",
+                    row.function_id, e
+                );
+                return;
+            }
+            let _ = super::repo::update_last_invoked(&db, row.function_id).await;
+            let _ = audit::log_action(
+                &db,
+                user_id,
+                &username,
+                AuditAction::InvokeFunction,
+                Some("function"),
+                Some(row.function_id),
+                None,
+                None,
+                row.error.is_none(),
+                row.error.as_deref(),
+            )
+            .await;
+        });
+    }
+}
+
+/// What an invocation runs: the `$LATEST` draft (`version: None`) or the
+/// published version its qualifier resolved to.
+struct InvokeTarget {
+    version: Option<FunctionVersionRow>,
+    timeout_seconds: i32,
+    env_vars: Option<serde_json::Value>,
+    secret_env_vars: Option<serde_json::Value>,
+}
+
+impl InvokeTarget {
+    fn version(&self) -> Option<i32> {
+        self.version.as_ref().map(|v| v.version)
+    }
+
+    /// A published version needs a runtime that can load it; an older one
+    /// would silently run the draft instead.
+    async fn ensure_runnable(&self, guest_ip: &str, port: i32) -> Result<()> {
+        if self.version.is_none() {
+            return Ok(());
+        }
+        let health: serde_json::Value = reqwest::Client::new()
+            .get(format!("http://{}:{}/health", guest_ip, port))
+            .timeout(std::time::Duration::from_secs(5))
+            .send()
+            .await
+            .context("checking the function runtime")?
+            .json()
+            .await
+            .context("reading the function runtime's health")?;
+        if !versions::runtime_supports_versions(&health) {
+            return Err(versions::RuntimeLacksVersions.into());
+        }
+        Ok(())
+    }
+
+    /// The body sent to the runtime. A published version travels with its
+    /// code so the runtime can run it beside the editable draft; the
+    /// timeout is the version's own, so republishing with a new timeout
+    /// doesn't change what an older version gets.
+    fn payload(&self, event: &serde_json::Value, key: &[u8; 32]) -> Result<serde_json::Value> {
+        let env =
+            super::secrets::invoke_env(self.env_vars.as_ref(), self.secret_env_vars.as_ref(), key)?;
+        let mut payload = json!({
+            "event": event,
+            "env": env,
+            "timeout_ms": i64::from(self.timeout_seconds) * 1000,
+        });
+        if let Some(v) = &self.version {
+            payload["version"] = json!({
+                "number": v.version,
+                "code": v.code,
+                "handler": v.handler,
+            });
+        }
+        Ok(payload)
+    }
+}
+
+async fn resolve_target(
+    db: &PgPool,
+    func: &FunctionRow,
+    qualifier: Option<&str>,
+) -> Result<InvokeTarget> {
+    let number = match Qualifier::parse(qualifier) {
+        Qualifier::Latest => {
+            return Ok(InvokeTarget {
+                version: None,
+                timeout_seconds: func.timeout_seconds,
+                env_vars: func.env_vars.clone(),
+                secret_env_vars: func.secret_env_vars.clone(),
+            })
+        }
+        Qualifier::Version(n) => n,
+        Qualifier::Alias(name) => {
+            let alias = super::repo::get_alias(db, func.id, &name)
+                .await?
+                .with_context(|| format!("Alias {} not found", name))?;
+            versions::pick_version(
+                alias.version,
+                alias.canary_version,
+                alias.canary_percent,
+                rand::random::<u32>() % 100,
+            )
+        }
+    };
+    let version = super::repo::get_version(db, func.id, number)
+        .await?
+        .with_context(|| format!("Version {} not found", number))?;
+    Ok(InvokeTarget {
+        timeout_seconds: version.timeout_seconds,
+        env_vars: version.env_vars.clone(),
+        secret_env_vars: version.secret_env_vars.clone(),
+        version: Some(version),
+    })
+}
+
+// ========================================
+// Function Versions & Aliases
+// ========================================
+
+pub async fn publish_version(
+    st: &AppState,
+    id: Uuid,
+    req: PublishFunctionVersionReq,
+) -> Result<FunctionVersion> {
+    let row = super::repo::publish_version(&st.db, id, req.description.as_deref())
+        .await?
+        .context("Function not found")?;
+    Ok(version_row_to_type(row))
+}
+
+pub async fn list_versions(db: &PgPool, id: Uuid) -> Result<ListFunctionVersionsResp> {
+    super::repo::get(db, id)
+        .await?
+        .context("Function not found")?;
+    let rows = super::repo::list_versions(db, id).await?;
+    let items = rows.into_iter().map(version_row_to_type).collect();
+    Ok(ListFunctionVersionsResp { items })
+}
+
+pub async fn list_aliases(db: &PgPool, id: Uuid) -> Result<ListFunctionAliasesResp> {
+    super::repo::get(db, id)
+        .await?
+        .context("Function not found")?;
+    let rows = super::repo::list_aliases(db, id).await?;
+    let items = rows.into_iter().map(alias_row_to_type).collect();
+    Ok(ListFunctionAliasesResp { items })
+}
+
+/// Creates or repoints an alias. Repointing `version` at an older version
+/// is a rollback; setting a canary shifts a share of traffic gradually.
+pub async fn put_alias(
+    st: &AppState,
+    id: Uuid,
+    name: &str,
+    req: PutFunctionAliasReq,
+) -> Result<FunctionAlias> {
+    versions::validate_alias_name(name)?;
+    versions::validate_alias(&req)?;
+    super::repo::get(&st.db, id)
+        .await?
+        .context("Function not found")?;
+    for version in std::iter::once(req.version).chain(req.canary_version) {
+        if super::repo::get_version(&st.db, id, version)
+            .await?
+            .is_none()
+        {
+            anyhow::bail!("invalid alias: version {} is not published", version);
+        }
+    }
+
+    let row = super::repo::upsert_alias(
+        &st.db,
+        id,
+        name,
+        req.version,
+        req.canary_version,
+        req.canary_percent,
+    )
+    .await?;
+    Ok(alias_row_to_type(row))
+}
+
+pub async fn delete_alias(st: &AppState, id: Uuid, name: &str) -> Result<()> {
+    if !super::repo::delete_alias(&st.db, id, name).await? {
+        anyhow::bail!("Alias {} not found", name);
+    }
+    Ok(())
+}
+
 pub async fn list_invocations(
     db: &PgPool,
     function_id: Uuid,
@@ -540,6 +906,7 @@ fn row_to_function(row: FunctionRow) -> Function {
         memory_mb: row.memory_mb,
         vcpu: row.vcpu,
         env_vars: row.env_vars,
+        secret_env_vars: row.secret_env_vars.as_ref().map(super::secrets::redact),
         vm_id: row.vm_id,
         guest_ip: row.guest_ip,
         port: row.port,
@@ -563,10 +930,35 @@ fn invocation_row_to_type(row: FunctionInvocationRow) -> FunctionInvocation {
         response: row.response,
         logs: row.logs,
         error: row.error,
+        version: row.version,
         invoked_at: row.invoked_at,
     }
 }
 
+fn version_row_to_type(row: FunctionVersionRow) -> FunctionVersion {
+    FunctionVersion {
+        function_id: row.function_id,
+        version: row.version,
+        code: row.code,
+        handler: row.handler,
+        timeout_seconds: row.timeout_seconds,
+        env_vars: row.env_vars,
+        secret_env_vars: row.secret_env_vars.as_ref().map(super::secrets::redact),
+        description: row.description,
+        created_at: row.created_at,
+    }
+}
+
+fn alias_row_to_type(row: FunctionAliasRow) -> FunctionAlias {
+    FunctionAlias {
+        name: row.name,
+        version: row.version,
+        canary_version: row.canary_version,
+        canary_percent: row.canary_percent,
+        updated_at: row.updated_at,
+    }
+}
+
 fn validate_runtime(runtime: &str) -> Result<()> {
     match runtime {
         "python" | "javascript" | "typescript" => Ok(()),
@@ -576,3 +968,129 @@ fn validate_runtime(runtime: &str) -> Result<()> {
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_function(db: &PgPool, timeout_seconds: i32) -> FunctionRow {
+        let row = FunctionRow {
+            id: Uuid::new_v4(),
+            name: "hello".into(),
+            runtime: "python".into(),
+            code: "def handler(event):\n    return 1\n".into(),
+            handler: "handler".into(),
+            timeout_seconds,
+            memory_mb: 128,
+            vcpu: 1,
+            env_vars: None,
+            secret_env_vars: None,
+            vm_id: None,
+            guest_ip: None,
+            port: 3000,
+            state: "ready".into(),
+            created_by_user_id: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            last_invoked_at: None,
+        };
+        super::super::repo::insert(db, &row).await.unwrap();
+        row
+    }
+
+    #[test]
+    fn payload_carries_the_timeout_and_the_version_code() {
+        let draft = InvokeTarget {
+            version: None,
+            timeout_seconds: 3,
+            env_vars: None,
+            secret_env_vars: None,
+        };
+        let payload = draft.payload(&json!({"a": 1}), &[0; 32]).unwrap();
+        assert_eq!(payload["timeout_ms"], 3_000);
+        assert_eq!(payload["event"]["a"], 1);
+        assert!(payload.get("version").is_none());
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn publish_numbers_versions_and_freezes_the_draft(pool: PgPool) {
+        let func = insert_function(&pool, 30).await;
+        let v1 = super::super::repo::publish_version(&pool, func.id, Some("first"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(v1.version, 1);
+        assert_eq!(v1.timeout_seconds, 30);
+        assert_eq!(v1.code, func.code);
+
+        super::super::repo::update(
+            &pool,
+            func.id,
+            None,
+            None,
+            Some("def handler(event):\n    return 2\n"),
+            None,
+            Some(5),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let v2 = super::super::repo::publish_version(&pool, func.id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(v2.version, 2);
+        assert_eq!(v2.timeout_seconds, 5);
+
+        let v1_again = super::super::repo::get_version(&pool, func.id, 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(v1_again.code, func.code);
+        assert!(
+            super::super::repo::publish_version(&pool, Uuid::new_v4(), None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn resolve_target_follows_qualifiers(pool: PgPool) {
+        let func = insert_function(&pool, 30).await;
+        super::super::repo::publish_version(&pool, func.id, None)
+            .await
+            .unwrap();
+        let latest = FunctionRow {
+            timeout_seconds: 9,
+            ..func.clone()
+        };
+
+        let draft = resolve_target(&pool, &latest, None).await.unwrap();
+        assert_eq!(draft.version(), None);
+        assert_eq!(draft.timeout_seconds, 9);
+
+        let pinned = resolve_target(&pool, &latest, Some("1")).await.unwrap();
+        assert_eq!(pinned.version(), Some(1));
+        // The version's own timeout, not the draft's
+        assert_eq!(pinned.timeout_seconds, 30);
+        let payload = pinned.payload(&json!({}), &[0; 32]).unwrap();
+        assert_eq!(payload["timeout_ms"], 30_000);
+        assert_eq!(payload["version"]["number"], 1);
+
+        super::super::repo::upsert_alias(&pool, func.id, "prod", 1, None, None)
+            .await
+            .unwrap();
+        let aliased = resolve_target(&pool, &latest, Some("prod")).await.unwrap();
+        assert_eq!(aliased.version(), Some(1));
+
+        let missing = resolve_target(&pool, &latest, Some("7")).await;
+        assert!(missing.err().unwrap().to_string().contains("not found"));
+        let no_alias = resolve_target(&pool, &latest, Some("beta")).await;
+        assert!(no_alias.err().unwrap().to_string().contains("not found"));
+    }
+}
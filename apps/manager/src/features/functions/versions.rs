@@ -0,0 +1,165 @@
+//! Function versions and aliases. The `function` row is the editable draft
+//! (`$LATEST`); publishing freezes its code and config as the next version
+//! number, and aliases such as `prod` point at a version, optionally sending
+//! a share of traffic to a canary version.
+
+use anyhow::{bail, Result};
+use nexus_types::PutFunctionAliasReq;
+
+/// The qualifier naming the editable draft.
+pub const LATEST: &str = "$LATEST";
+
+/// What a runtime's `/health` lists in `capabilities` once it can run a
+/// published version sent with an invocation.
+const VERSIONS_CAPABILITY: &str = "versions";
+
+/// The function VM's runtime predates versions and would run the draft
+/// instead; invoking a specific version is refused with 409.
+#[derive(Debug)]
+pub struct RuntimeLacksVersions;
+
+impl std::fmt::Display for RuntimeLacksVersions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            "the function's runtime cannot run published versions; redeploy the function to update it",
+        )
+    }
+}
+
+impl std::error::Error for RuntimeLacksVersions {}
+
+pub fn is_runtime_lacking_versions(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<RuntimeLacksVersions>().is_some()
+}
+
+/// Whether a runtime's `/health` body advertises version support.
+pub fn runtime_supports_versions(health: &serde_json::Value) -> bool {
+    health["capabilities"]
+        .as_array()
+        .is_some_and(|caps| caps.iter().any(|c| c == VERSIONS_CAPABILITY))
+}
+
+/// What an invoke's `qualifier` refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Qualifier {
+    Latest,
+    Version(i32),
+    Alias(String),
+}
+
+impl Qualifier {
+    pub fn parse(qualifier: Option<&str>) -> Qualifier {
+        match qualifier.map(str::trim) {
+            None | Some("") | Some(LATEST) => Qualifier::Latest,
+            Some(q) => match q.parse::<i32>() {
+                Ok(n) => Qualifier::Version(n),
+                Err(_) => Qualifier::Alias(q.to_string()),
+            },
+        }
+    }
+}
+
+/// Alias names are lowercase slugs that can't be mistaken for a qualifier of
+/// another kind.
+pub fn validate_alias_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        bail!("invalid alias name: must be 1-64 characters");
+    }
+    if name.parse::<i32>().is_ok() {
+        bail!("invalid alias name: {name} would read as a version number");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    {
+        bail!("invalid alias name: {name} may only contain a-z, 0-9, '-' and '_'");
+    }
+    Ok(())
+}
+
+/// Checks an alias's version/canary fields hang together; whether the
+/// versions exist is left to the caller.
+pub fn validate_alias(req: &PutFunctionAliasReq) -> Result<()> {
+    match (req.canary_version, req.canary_percent) {
+        (None, None) => Ok(()),
+        (Some(canary), Some(percent)) => {
+            if canary == req.version {
+                bail!("invalid alias: canary_version must differ from version");
+            }
+            if !(1..=99).contains(&percent) {
+                bail!("invalid alias: canary_percent must be between 1 and 99");
+            }
+            Ok(())
+        }
+        _ => bail!("invalid alias: canary_version and canary_percent must be set together"),
+    }
+}
+
+/// The version an alias routes one invocation to, given a roll in `0..100`.
+pub fn pick_version(
+    version: i32,
+    canary_version: Option<i32>,
+    canary_percent: Option<i32>,
+    roll: u32,
+) -> i32 {
+    match (canary_version, canary_percent) {
+        (Some(canary), Some(percent)) if (roll as i32) < percent => canary,
+        _ => version,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualifier_parses_latest_versions_and_aliases() {
+        assert_eq!(Qualifier::parse(None), Qualifier::Latest);
+        assert_eq!(Qualifier::parse(Some("$LATEST")), Qualifier::Latest);
+        assert_eq!(Qualifier::parse(Some("3")), Qualifier::Version(3));
+        assert_eq!(
+            Qualifier::parse(Some("prod")),
+            Qualifier::Alias("prod".into())
+        );
+
+        assert!(validate_alias_name("prod").is_ok());
+        assert!(validate_alias_name("canary_2").is_ok());
+        assert!(validate_alias_name("12").is_err());
+        assert!(validate_alias_name("$LATEST").is_err());
+        assert!(validate_alias_name("Prod").is_err());
+        assert!(validate_alias_name("").is_err());
+    }
+
+    #[test]
+    fn only_runtimes_advertising_versions_run_them() {
+        use serde_json::json;
+        assert!(runtime_supports_versions(
+            &json!({"status": "healthy", "capabilities": ["versions", "timeout"]})
+        ));
+        assert!(!runtime_supports_versions(
+            &json!({"status": "healthy", "capabilities": ["timeout"]})
+        ));
+        // Runtimes from before versions send no capabilities at all
+        assert!(!runtime_supports_versions(
+            &json!({"status": "healthy", "codeLoaded": true})
+        ));
+    }
+
+    #[test]
+    fn canary_alias_splits_by_percent() {
+        let req = |canary_version, canary_percent| PutFunctionAliasReq {
+            version: 1,
+            canary_version,
+            canary_percent,
+        };
+        assert!(validate_alias(&req(None, None)).is_ok());
+        assert!(validate_alias(&req(Some(2), Some(10))).is_ok());
+        assert!(validate_alias(&req(Some(2), None)).is_err());
+        assert!(validate_alias(&req(Some(1), Some(10))).is_err());
+        assert!(validate_alias(&req(Some(2), Some(100))).is_err());
+
+        assert_eq!(pick_version(1, Some(2), Some(10), 9), 2);
+        assert_eq!(pick_version(1, Some(2), Some(10), 10), 1);
+        assert_eq!(pick_version(1, None, None, 0), 1);
+    }
+}
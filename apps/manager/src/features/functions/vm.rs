@@ -87,6 +87,7 @@ pub async fn create_function_vm(
         tags: vec!["type:function".to_string()],
         rootfs_size_mb: None,
         network_id: None,
+        bridge: None,
         extra_network_ids: vec![],
         port_forwards: vec![],
         backend_id: None,
@@ -104,11 +105,20 @@ pub async fn create_function_vm(
         data_disks: vec![],
         vfio_devices: vec![],
         cpu_type: None,
+        boot_args: None,
+        smt: None,
+        huge_pages: None,
+        rootfs_rate_limiter: None,
+        entropy_rate_limiter: None,
+        rootfs_mode: None,
+        overlay_size_mb: None,
+        cpu_quota: None,
+        io_weight: None,
+        id: None,
     };
 
     // Create and start VM
-    crate::features::vms::service::create_and_start(st, vm_id, vm_req, None, None, "system")
-        .await?;
+    crate::features::vms::service::create_and_start(st, vm_id, vm_req, None, None).await?;
 
     // Note: Function code will be injected after VM boots and guest IP is available
     // This is done in the service layer via the update_function_code() function
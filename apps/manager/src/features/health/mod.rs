@@ -0,0 +1,167 @@
+//! Probes for load balancers and orchestrators, all unauthenticated.
+//! `/healthz` only says the process is serving; `/readyz` also checks what
+//! requests depend on, so traffic isn't routed to a half-started manager.
+
+use crate::AppState;
+use axum::{http::StatusCode, routing::get, Extension, Json, Router};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// How long `/readyz` waits for a pooled connection and its query.
+const DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Set when shutdown starts, so `/readyz` sends traffic elsewhere while
+/// in-flight requests drain.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Set once `/readyz` has seen every embedded migration applied; after that
+/// it only pings the database.
+static MIGRATIONS_COMPLETE: AtomicBool = AtomicBool::new(false);
+
+pub fn begin_shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/healthz", get(health_check))
+        .route("/readyz", get(readyz))
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+}
+
+async fn health_check() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    /// `ready` or `not_ready`
+    pub status: &'static str,
+    pub database: String,
+    pub migrations: String,
+    /// `running`, `stopped` or `disabled`
+    pub reconciler: &'static str,
+    /// Why the manager isn't ready, when it isn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+async fn readyz(Extension(st): Extension<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let mut reasons = Vec::new();
+    if SHUTTING_DOWN.load(Ordering::Relaxed) {
+        reasons.push("shutting down".to_string());
+    }
+
+    let (database, migrations) = match check_database(&st.db).await {
+        Ok(0) => ("ok".to_string(), "ok".to_string()),
+        Ok(pending) => {
+            reasons.push(format!("{pending} migration(s) not applied"));
+            ("ok".to_string(), format!("{pending} pending"))
+        }
+        Err(reason) => {
+            reasons.push(reason.clone());
+            (reason, "unknown".to_string())
+        }
+    };
+
+    let reconciler = if crate::features::reconciler::disabled_by_env() {
+        "disabled"
+    } else if crate::features::reconciler::is_running() {
+        "running"
+    } else {
+        reasons.push("reconciler is not running".to_string());
+        "stopped"
+    };
+
+    let ready = reasons.is_empty();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ready { "ready" } else { "not_ready" },
+            database,
+            migrations,
+            reconciler,
+            reason: (!ready).then(|| reasons.join("; ")),
+        }),
+    )
+}
+
+/// Pending migrations, or why the database can't serve requests.
+async fn check_database(db: &PgPool) -> Result<usize, String> {
+    let mut conn = match tokio::time::timeout(DB_TIMEOUT, db.acquire()).await {
+        Ok(Ok(conn)) => conn,
+        Ok(Err(e)) => return Err(format!("database unreachable: {e}")),
+        Err(_) if db.num_idle() == 0 && db.size() >= db.options().get_max_connections() => {
+            return Err("database connection pool exhausted".to_string())
+        }
+        Err(_) => return Err("database did not answer in time".to_string()),
+    };
+
+    if MIGRATIONS_COMPLETE.load(Ordering::Relaxed) {
+        return match tokio::time::timeout(DB_TIMEOUT, sqlx::query("SELECT 1").execute(&mut *conn))
+            .await
+        {
+            Ok(Ok(_)) => Ok(0),
+            Ok(Err(e)) => Err(format!("database query failed: {e}")),
+            Err(_) => Err("database did not answer in time".to_string()),
+        };
+    }
+
+    let applied = tokio::time::timeout(
+        DB_TIMEOUT,
+        sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(&mut *conn),
+    )
+    .await
+    .map_err(|_| "database did not answer in time".to_string())?;
+    let applied = match applied {
+        Ok(applied) => applied,
+        // No migrations table yet means none have run
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P01") => Vec::new(),
+        Err(e) => return Err(format!("database query failed: {e}")),
+    };
+    let embedded: Vec<i64> = sqlx::migrate!("./migrations")
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| m.version)
+        .collect();
+    let pending = pending_migrations(&embedded, &applied);
+    if pending == 0 {
+        MIGRATIONS_COMPLETE.store(true, Ordering::Relaxed);
+    }
+    Ok(pending)
+}
+
+fn pending_migrations(embedded: &[i64], applied: &[i64]) -> usize {
+    embedded.iter().filter(|v| !applied.contains(v)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_embedded_migrations_not_yet_applied() {
+        assert_eq!(pending_migrations(&[1, 2, 3], &[1, 2, 3]), 0);
+        assert_eq!(pending_migrations(&[1, 2, 3], &[1]), 2);
+        assert_eq!(pending_migrations(&[1, 2], &[]), 2);
+        // Applied by a newer manager sharing the database
+        assert_eq!(pending_migrations(&[1], &[1, 2]), 0);
+    }
+}
@@ -11,6 +11,11 @@ pub fn router() -> Router {
         .route("/", get(routes::list))
         .route("/:id", get(routes::get).delete(routes::delete))
         .route("/:id/pci-devices", get(routes::pci_devices))
+        .route(
+            "/:id/containers/stop-all",
+            post(routes::stop_all_containers),
+        )
+        .route("/:id/snapshot-all", post(routes::snapshot_all))
         .route("/register", post(routes::register))
         .route("/:id/heartbeat", post(routes::heartbeat))
 }
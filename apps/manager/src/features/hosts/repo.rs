@@ -2,6 +2,7 @@ use chrono::DateTime;
 use serde::Serialize;
 use serde_json::Value;
 use sqlx::PgPool;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -31,7 +32,9 @@ impl HostRepository {
             ON CONFLICT (addr) DO UPDATE
             SET name = EXCLUDED.name,
                 capabilities_json = EXCLUDED.capabilities_json,
-                last_seen_at = now()
+                last_seen_at = now(),
+                deregistered_at = NULL,
+                draining_since = NULL
             RETURNING *
             "#,
         )
@@ -43,26 +46,29 @@ impl HostRepository {
         .await
     }
 
-    pub async fn heartbeat(&self, id: Uuid, capabilities: Option<Value>) -> sqlx::Result<HostRow> {
-        match capabilities {
-            Some(value) => {
-                sqlx::query_as::<_, HostRow>(
-                    r#"UPDATE host SET capabilities_json=$2, last_seen_at=now() WHERE id=$1 RETURNING *"#,
-                )
-                .bind(id)
-                .bind(value)
-                .fetch_one(&self.pool)
-                .await
-            }
-            None => {
-                sqlx::query_as::<_, HostRow>(
-                    r#"UPDATE host SET last_seen_at=now() WHERE id=$1 RETURNING *"#,
-                )
-                .bind(id)
-                .fetch_one(&self.pool)
-                .await
-            }
-        }
+    /// `draining` is the agent's last heartbeat before it shuts down; any
+    /// other heartbeat clears the flag.
+    pub async fn heartbeat(
+        &self,
+        id: Uuid,
+        capabilities: Option<Value>,
+        draining: bool,
+    ) -> sqlx::Result<HostRow> {
+        sqlx::query_as::<_, HostRow>(
+            r#"
+            UPDATE host
+            SET capabilities_json = COALESCE($2, capabilities_json),
+                last_seen_at = now(),
+                draining_since = CASE WHEN $3 THEN COALESCE(draining_since, now()) END
+            WHERE id = $1 AND deregistered_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(capabilities)
+        .bind(draining)
+        .fetch_one(&self.pool)
+        .await
     }
 
     pub async fn get(&self, id: Uuid) -> sqlx::Result<HostRow> {
@@ -72,11 +78,14 @@ impl HostRepository {
             .await
     }
 
+    /// The most recently seen host new VMs can be placed on.
     pub async fn first_healthy(&self) -> sqlx::Result<HostRow> {
         sqlx::query_as::<_, HostRow>(
             r#"
             SELECT * FROM host
             WHERE last_seen_at > now() - INTERVAL '30 seconds'
+              AND deregistered_at IS NULL
+              AND draining_since IS NULL
             ORDER BY last_seen_at DESC
             LIMIT 1
             "#,
@@ -90,6 +99,22 @@ impl HostRepository {
             r#"
             SELECT * FROM host
             WHERE last_seen_at > now() - INTERVAL '30 seconds'
+              AND deregistered_at IS NULL
+            ORDER BY last_seen_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Healthy hosts that aren't shutting down, i.e. where new VMs may go.
+    pub async fn list_schedulable(&self) -> sqlx::Result<Vec<HostRow>> {
+        sqlx::query_as::<_, HostRow>(
+            r#"
+            SELECT * FROM host
+            WHERE last_seen_at > now() - INTERVAL '30 seconds'
+              AND deregistered_at IS NULL
+              AND draining_since IS NULL
             ORDER BY last_seen_at DESC
             "#,
         )
@@ -101,6 +126,7 @@ impl HostRepository {
         sqlx::query_as::<_, HostRow>(
             r#"
             SELECT * FROM host
+            WHERE deregistered_at IS NULL
             ORDER BY last_seen_at DESC
             "#,
         )
@@ -167,6 +193,69 @@ impl HostRepository {
         Ok(())
     }
 
+    /// Tombstone a dead host whose VMs still reference it. It drops out of
+    /// every host list until its agent registers again.
+    pub async fn deregister(&self, id: Uuid) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE host SET deregistered_at = now()
+            WHERE id = $1
+            AND last_seen_at <= now() - INTERVAL '30 seconds'
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Flag hosts whose last heartbeat is older than `stale_after` as
+    /// unreachable. Returns only the hosts that just crossed the line.
+    pub async fn mark_unreachable(&self, stale_after: Duration) -> sqlx::Result<Vec<HostRow>> {
+        sqlx::query_as::<_, HostRow>(
+            r#"
+            UPDATE host SET unreachable_since = now()
+            WHERE unreachable_since IS NULL
+              AND deregistered_at IS NULL
+              AND last_seen_at <= now() - make_interval(secs => $1)
+            RETURNING *
+            "#,
+        )
+        .bind(stale_after.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Clear the flag on unreachable hosts that have heartbeated again, i.e.
+    /// are no longer stale by [`Self::mark_unreachable`]'s `stale_after`.
+    /// Returns the hosts that recovered.
+    pub async fn clear_unreachable(&self, stale_after: Duration) -> sqlx::Result<Vec<HostRow>> {
+        sqlx::query_as::<_, HostRow>(
+            r#"
+            UPDATE host SET unreachable_since = NULL
+            WHERE unreachable_since IS NOT NULL
+              AND last_seen_at > now() - make_interval(secs => $1)
+            RETURNING *
+            "#,
+        )
+        .bind(stale_after.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_unreachable(&self) -> sqlx::Result<Vec<HostRow>> {
+        sqlx::query_as::<_, HostRow>(
+            r#"
+            SELECT * FROM host
+            WHERE unreachable_since IS NOT NULL
+              AND deregistered_at IS NULL
+            ORDER BY unreachable_since
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn is_alive(&self, id: Uuid) -> sqlx::Result<bool> {
         let result: (bool,) = sqlx::query_as(
             r#"
@@ -269,6 +358,26 @@ impl HostRepository {
         Ok(updated.is_some())
     }
 
+    /// Whether `try_reserve` would currently succeed, without reserving.
+    pub async fn has_capacity(&self, host_id: Uuid, vcpu: i32, mem_mib: i64) -> sqlx::Result<bool> {
+        sqlx::query_scalar(
+            r#"SELECT (
+                       (total_vcpu IS NULL AND total_cpus IS NULL)
+                    OR (COALESCE(total_vcpu, total_cpus, 2147483647) >= reserved_vcpu + $2)
+                  )
+                  AND (
+                       (total_mem_mib IS NULL AND total_memory_mb IS NULL)
+                    OR (COALESCE(total_mem_mib, total_memory_mb, 9223372036854775807) >= reserved_mem_mib + $3)
+                  )
+                FROM host WHERE id = $1"#,
+        )
+        .bind(host_id)
+        .bind(vcpu)
+        .bind(mem_mib)
+        .fetch_one(&self.pool)
+        .await
+    }
+
     /// Release a previously-reserved capacity (when a VM is deleted).
     pub async fn release_reservation(
         &self,
@@ -303,4 +412,6 @@ pub struct HostRow {
     pub total_disk_gb: Option<i64>,
     pub used_disk_gb: Option<i64>,
     pub last_metrics_at: Option<DateTime<chrono::Utc>>,
+    pub unreachable_since: Option<DateTime<chrono::Utc>>,
+    pub draining_since: Option<DateTime<chrono::Utc>>,
 }
@@ -1,12 +1,20 @@
 use crate::features::hosts::repo::HostRow;
+use crate::features::users::audit::Actor;
 use crate::AppState;
-use axum::{extract::Path, http::StatusCode, Extension, Json};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
 use chrono::{DateTime, Utc};
 use nexus_types::{
-    HostHeartbeatRequest, HostPathParams, OkResponse, RegisterHostRequest, RegisterHostResponse,
+    AuditAction, ContainerBatchAction, ContainerBatchResp, HostHeartbeatRequest, HostPathParams,
+    HostSnapshotAllResp, OkResponse, RegisterHostRequest, RegisterHostResponse,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tracing::error;
+use utoipa::IntoParams;
 use uuid::Uuid;
 
 /// Health status thresholds. A host is "healthy" if its last heartbeat was
@@ -27,6 +35,16 @@ pub(crate) fn compute_host_status(last_seen_at: DateTime<Utc>, now: DateTime<Utc
     }
 }
 
+/// A host the reconciler declared unreachable stays "offline" until it has
+/// heartbeated again and the flag is cleared.
+pub(crate) fn host_status(row: &HostRow, now: DateTime<Utc>) -> &'static str {
+    if row.unreachable_since.is_some() {
+        "offline"
+    } else {
+        compute_host_status(row.last_seen_at, now)
+    }
+}
+
 /// Pure-logic helper: extract the metric tuple `(cpus, total_memory_mb,
 /// total_disk_gb, used_disk_gb)` from a capabilities JSON blob. Returns
 /// `None` if any of the four numeric fields are missing or not an integer.
@@ -57,6 +75,8 @@ pub(crate) fn host_row_to_list_item(row: HostRow, status: &str, vm_count: i64) -
         vm_count,
         last_seen_at: row.last_seen_at,
         last_metrics_at: row.last_metrics_at,
+        unreachable_since: row.unreachable_since,
+        draining_since: row.draining_since,
     }
 }
 
@@ -109,6 +129,26 @@ pub async fn register(
         }
     }
 
+    // A re-registering host may come back with a new address; keep its VXLAN
+    // local IP current and push it to the rest of the mesh without waiting for
+    // the next reconciler tick.
+    if let Err(err) =
+        crate::features::networks::service::refresh_host_vtep_ip(&st, row.id, &row.addr).await
+    {
+        error!(?err, "failed to refresh VXLAN local IP on register");
+    }
+    let mesh_state = st.clone();
+    tokio::spawn(async move {
+        if let Err(err) = crate::features::networks::service::converge_vxlan_peers(
+            &mesh_state,
+            &Default::default(),
+        )
+        .await
+        {
+            error!(?err, "failed to converge VXLAN peers on register");
+        }
+    });
+
     Ok(Json(RegisterHostResponse { id: row.id }))
 }
 
@@ -131,7 +171,7 @@ pub async fn heartbeat(
 ) -> Result<Json<OkResponse>, StatusCode> {
     // Update heartbeat timestamp and capabilities
     st.hosts
-        .heartbeat(id, req.capabilities.clone())
+        .heartbeat(id, req.capabilities.clone(), req.draining)
         .await
         .map_err(|err| match err {
             sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
@@ -191,6 +231,12 @@ pub struct HostListItem {
     pub vm_count: i64,
     pub last_seen_at: chrono::DateTime<chrono::Utc>,
     pub last_metrics_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the reconciler declared the host unreachable; `None` while it
+    /// heartbeats normally.
+    pub unreachable_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the agent announced it is shutting down; no new VMs are placed
+    /// on the host while set.
+    pub draining_since: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -223,7 +269,7 @@ pub async fn list(
     let mut items = Vec::new();
     for host in hosts {
         // Determine health status based on last_seen_at
-        let status = compute_host_status(host.last_seen_at, chrono::Utc::now());
+        let status = host_status(&host, chrono::Utc::now());
 
         // Get VM count for this host
         let vm_count = st.hosts.get_vm_count(host.id).await.unwrap_or(0);
@@ -258,7 +304,7 @@ pub async fn get(
     })?;
 
     // Determine health status
-    let status = compute_host_status(host.last_seen_at, chrono::Utc::now());
+    let status = host_status(&host, chrono::Utc::now());
 
     // Get VM count
     let vm_count = st.hosts.get_vm_count(id).await.unwrap_or(0);
@@ -282,7 +328,7 @@ pub async fn pci_devices(
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let host = st.hosts.get(id).await.map_err(|_| StatusCode::NOT_FOUND)?;
     let url = format!("{}/agent/v1/vmm/pci-devices", host.addr);
-    let resp = reqwest::Client::new()
+    let resp = crate::core::agent_http::client()
         .get(&url)
         .send()
         .await
@@ -294,23 +340,106 @@ pub async fn pci_devices(
     Ok(Json(json))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteHostParams {
+    /// Delete even though VMs still reference the host: they are marked
+    /// `stopped` with an event saying why.
+    #[serde(default)]
+    pub force: bool,
+}
+
 #[utoipa::path(
     delete,
     path = "/v1/hosts/{id}",
-    params(HostPathParams),
+    params(HostPathParams, DeleteHostParams),
     responses(
         (status = 200, description = "Host deleted", body = OkResponse),
         (status = 400, description = "Cannot delete alive host"),
         (status = 404, description = "Host not found"),
+        (status = 409, description = "VMs still reference the host; retry with force=true"),
         (status = 500, description = "Failed to delete host"),
     ),
     tag = "Hosts"
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(HostPathParams { id }): Path<HostPathParams>,
+    Query(DeleteHostParams { force }): Query<DeleteHostParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    // Check if host exists
+    let result = remove_host(&st, id, force).await;
+    let details = match &result {
+        Ok(details) => details.clone(),
+        Err(_) => json!({"force": force}),
+    };
+    actor
+        .record_with(
+            AuditAction::DeleteHost,
+            ("host", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    result?;
+    Ok(Json(OkResponse::default()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/hosts/{id}/containers/stop-all",
+    params(HostPathParams),
+    responses(
+        (status = 200, description = "Every running, paused or provisioning container placed on the host stopped; one result each, none when there was none", body = ContainerBatchResp),
+        (status = 404, description = "Host not found"),
+        (status = 500, description = "Failed to list the host's containers"),
+    ),
+    tag = "Hosts"
+)]
+pub async fn stop_all_containers(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(HostPathParams { id }): Path<HostPathParams>,
+) -> Result<Json<ContainerBatchResp>, StatusCode> {
+    st.hosts.get(id).await.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        other => {
+            error!(error = ?other, "failed to get host");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    let ids = crate::features::containers::service::active_on_host(&st, id)
+        .await
+        .map_err(|err| {
+            error!(error = ?err, host_id = %id, "failed to list host containers");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let resp = crate::features::containers::routes::run_batch(
+        &st,
+        &actor,
+        ContainerBatchAction::Stop,
+        ids,
+    )
+    .await;
+    Ok(Json(resp))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/hosts/{id}/snapshot-all",
+    params(HostPathParams),
+    responses(
+        (status = 200, description = "Every running VM on the host snapshotted (each paused while it is written); one result each, none when nothing was running", body = HostSnapshotAllResp),
+        (status = 404, description = "Host not found"),
+        (status = 500, description = "Failed to list the host's VMs"),
+    ),
+    tag = "Hosts"
+)]
+pub async fn snapshot_all(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(HostPathParams { id }): Path<HostPathParams>,
+) -> Result<Json<HostSnapshotAllResp>, StatusCode> {
     st.hosts.get(id).await.map_err(|err| match err {
         sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
         other => {
@@ -318,6 +447,34 @@ pub async fn delete(
             StatusCode::INTERNAL_SERVER_ERROR
         }
     })?;
+    let vm_ids = crate::features::vms::repo::list_by_host(&st.db, id)
+        .await
+        .map_err(|err| {
+            error!(error = ?err, host_id = %id, "failed to list host vms");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .filter(|vm| crate::features::vms::service::is_running_state(&vm.state))
+        .map(|vm| vm.id)
+        .collect();
+    let resp = crate::features::snapshots::routes::snapshot_vms(&st, &actor, vm_ids).await;
+    Ok(Json(resp))
+}
+
+/// Deletes (or tombstones) the host, returning what the audit entry records.
+async fn remove_host(
+    st: &AppState,
+    id: Uuid,
+    force: bool,
+) -> Result<serde_json::Value, StatusCode> {
+    // Check if host exists
+    let host = st.hosts.get(id).await.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        other => {
+            error!(error = ?other, "failed to get host");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
 
     // Check if host is alive (last_seen_at within 30 seconds)
     let is_alive = st.hosts.is_alive(id).await.map_err(|err| {
@@ -329,13 +486,51 @@ pub async fn delete(
         return Err(StatusCode::BAD_REQUEST);
     }
 
-    // Delete the host (only dead hosts will be deleted due to SQL constraint)
-    st.hosts.delete(id).await.map_err(|err| {
+    let vms = crate::features::vms::repo::list_by_host(&st.db, id)
+        .await
+        .map_err(|err| {
+            error!(error = ?err, "failed to list host vms");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !vms.is_empty() && !force {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // The reconciler only visits healthy hosts, so without this the VMs would
+    // keep whatever state they had when the host died, forever.
+    let message = format!(
+        "host {} ({}) was removed; VM marked stopped",
+        host.name, host.addr
+    );
+    for vm in &vms {
+        crate::features::vms::repo::update_state(&st.db, vm.id, "stopped")
+            .await
+            .map_err(|err| {
+                error!(vm_id = %vm.id, error = ?err, "failed to stop vm of removed host");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        let _ = crate::features::vms::repo::insert_event(&st.db, vm.id, "warn", &message).await;
+    }
+
+    // VM rows keep their host_id (fk_vm_host restricts the delete), so a host
+    // that still has VMs is tombstoned instead of dropped.
+    let removed = if vms.is_empty() {
+        st.hosts.delete(id).await
+    } else {
+        st.hosts.deregister(id).await
+    };
+    removed.map_err(|err| {
         error!(error = ?err, "failed to delete host");
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    Ok(Json(OkResponse::default()))
+    let vm_ids: Vec<Uuid> = vms.iter().map(|vm| vm.id).collect();
+    Ok(json!({
+        "name": host.name,
+        "addr": host.addr,
+        "force": force,
+        "stopped_vm_ids": vm_ids,
+    }))
 }
 
 #[cfg(test)]
@@ -345,12 +540,6 @@ mod tests {
     use chrono::Utc;
     use serde_json::json;
 
-    async fn test_registry(pool: &sqlx::PgPool) -> crate::features::storage::registry::Registry {
-        crate::features::storage::registry::Registry::load(pool, None)
-            .await
-            .expect("registry")
-    }
-
     fn sample_row(last_seen_at: chrono::DateTime<Utc>) -> HostRow {
         HostRow {
             id: Uuid::new_v4(),
@@ -363,6 +552,8 @@ mod tests {
             total_disk_gb: Some(500),
             used_disk_gb: Some(120),
             last_metrics_at: Some(last_seen_at),
+            unreachable_since: None,
+            draining_since: None,
         }
     }
 
@@ -403,6 +594,15 @@ mod tests {
         assert_eq!(compute_host_status(last_seen_offline, now), "offline");
     }
 
+    #[test]
+    fn host_status_reports_unreachable_hosts_offline() {
+        let now = Utc::now();
+        let mut row = sample_row(now - chrono::Duration::seconds(60));
+        assert_eq!(host_status(&row, now), "degraded");
+        row.unreachable_since = Some(now);
+        assert_eq!(host_status(&row, now), "offline");
+    }
+
     // --- extract_host_metrics ---
 
     #[test]
@@ -468,39 +668,7 @@ mod tests {
     #[sqlx::test(migrations = "./migrations")]
     async fn register_creates_host(pool: sqlx::PgPool) {
         let repo = crate::features::hosts::repo::HostRepository::new(pool.clone());
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts: repo.clone(),
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
 
         let req = RegisterHostRequest {
             name: "agent-1".into(),
@@ -521,39 +689,7 @@ mod tests {
     #[sqlx::test(migrations = "./migrations")]
     async fn heartbeat_updates_last_seen_and_capabilities(pool: sqlx::PgPool) {
         let repo = crate::features::hosts::repo::HostRepository::new(pool.clone());
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts: repo.clone(),
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
 
         let req = RegisterHostRequest {
             name: "agent-2".into(),
@@ -576,7 +712,7 @@ mod tests {
         let before = repo.get(register_resp.id).await.unwrap();
 
         let Json(response) = super::heartbeat(
-            Extension(state),
+            Extension(state.clone()),
             Path(HostPathParams {
                 id: register_resp.id,
             }),
@@ -584,6 +720,7 @@ mod tests {
                 capabilities: Some(json!({"memory": 8192})),
                 supported_backend_kinds: None,
                 vmm_kinds_installed: None,
+                draining: false,
             }),
         )
         .await
@@ -594,5 +731,350 @@ mod tests {
         let after = repo.get(register_resp.id).await.unwrap();
         assert!(after.last_seen_at > before.last_seen_at);
         assert_eq!(after.capabilities_json, json!({"memory": 8192}));
+        assert_eq!(repo.first_healthy().await.unwrap().id, register_resp.id);
+
+        // The agent's last heartbeat before shutdown takes it out of placement
+        let _ = super::heartbeat(
+            Extension(state),
+            Path(HostPathParams {
+                id: register_resp.id,
+            }),
+            Json(HostHeartbeatRequest {
+                capabilities: None,
+                supported_backend_kinds: None,
+                vmm_kinds_installed: None,
+                draining: true,
+            }),
+        )
+        .await
+        .unwrap();
+        let draining = repo.get(register_resp.id).await.unwrap();
+        assert!(draining.draining_since.is_some());
+        assert_eq!(draining.capabilities_json, json!({"memory": 8192}));
+        assert!(repo.first_healthy().await.is_err());
+        assert!(repo.list_schedulable().await.unwrap().is_empty());
+        assert_eq!(repo.list_healthy().await.unwrap().len(), 1);
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn delete_refuses_hosts_with_vms_unless_forced(pool: sqlx::PgPool) {
+        let repo = crate::features::hosts::repo::HostRepository::new(pool.clone());
+        let state = crate::AppState::for_tests(pool.clone()).await;
+
+        let host = repo
+            .register("agent-3", "http://127.0.0.1:9292", json!({}))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE host SET last_seen_at = now() - interval '1 hour' WHERE id=$1")
+            .bind(host.id)
+            .execute(repo.pool())
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let vm_id = Uuid::new_v4();
+        crate::features::vms::repo::insert(
+            &pool,
+            &crate::features::vms::repo::VmRow {
+                id: vm_id,
+                name: "orphan".into(),
+                state: "running".into(),
+                host_id: host.id,
+                template_id: None,
+                host_addr: host.addr.clone(),
+                api_sock: String::new(),
+                tap: String::new(),
+                log_path: String::new(),
+                http_port: 0,
+                fc_unit: String::new(),
+                vcpu: 1,
+                mem_mib: 512,
+                kernel_path: String::new(),
+                rootfs_path: String::new(),
+                source_snapshot_id: None,
+                guest_ip: None,
+                tags: vec![],
+                created_by_user_id: None,
+                vmm_kind: None,
+                guest_os: None,
+                console_kind: None,
+                vnc_listen: None,
+                cpu_type: None,
+                boot_args: None,
+                smt: None,
+                huge_pages: None,
+                rootfs_rate_limiter: None,
+                entropy_rate_limiter: None,
+                rootfs_mode: None,
+                cpu_quota: None,
+                io_weight: None,
+                created_at: now,
+                updated_at: now,
+                state_changed_at: now,
+                started_at: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let refused = super::delete(
+            Extension(state.clone()),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+            Query(DeleteHostParams { force: false }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(refused, StatusCode::CONFLICT);
+        assert_eq!(repo.list_all().await.unwrap().len(), 1);
+
+        let Json(deleted) = super::delete(
+            Extension(state),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+            Query(DeleteHostParams { force: true }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted, OkResponse::default());
+        let vm = crate::features::vms::repo::get(&pool, vm_id).await.unwrap();
+        assert_eq!(vm.state, "stopped");
+        let events = crate::features::vms::repo::list_events(&pool, vm_id, None, 10, 0)
+            .await
+            .unwrap();
+        assert!(events[0].message.contains("was removed"));
+        assert!(repo.list_all().await.unwrap().is_empty());
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn stop_all_stops_only_the_hosts_running_containers(pool: sqlx::PgPool) {
+        let repo = crate::features::hosts::repo::HostRepository::new(pool.clone());
+        let state = crate::AppState::for_tests(pool.clone()).await;
+
+        let host = repo
+            .register("agent-4", "http://127.0.0.1:9393", json!({}))
+            .await
+            .unwrap();
+        let other = repo
+            .register("agent-5", "http://127.0.0.1:9494", json!({}))
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+        for (name, state, host_id) in [
+            ("web", "running", host.id),
+            ("idle", "stopped", host.id),
+            ("elsewhere", "running", other.id),
+        ] {
+            let id: Uuid = sqlx::query_scalar(
+                "INSERT INTO containers (name, image, state, host_id) VALUES ($1, 'nginx', $2, $3) RETURNING id",
+            )
+            .bind(name)
+            .bind(state)
+            .bind(host_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let state_of = |id: Uuid| {
+            sqlx::query_scalar::<_, String>("SELECT state FROM containers WHERE id = $1")
+                .bind(id)
+                .fetch_one(&pool)
+        };
+
+        // No runtime to reach, so the stop only marks the container stopped
+        let Json(resp) = super::stop_all_containers(
+            Extension(state.clone()),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.results.len(), 1);
+        assert_eq!((resp.results[0].id, resp.results[0].ok), (ids[0], true));
+        assert_eq!(state_of(ids[0]).await.unwrap(), "stopped");
+        assert_eq!(state_of(ids[2]).await.unwrap(), "running");
+
+        let Json(again) = super::stop_all_containers(
+            Extension(state.clone()),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+        )
+        .await
+        .unwrap();
+        assert!(again.results.is_empty());
+
+        let missing = super::stop_all_containers(
+            Extension(state),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: Uuid::new_v4() }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(missing, StatusCode::NOT_FOUND);
+    }
+
+    fn host_vm(host: &HostRow, state: &str) -> crate::features::vms::repo::VmRow {
+        let now = Utc::now();
+        crate::features::vms::repo::VmRow {
+            id: Uuid::new_v4(),
+            name: format!("vm-{state}"),
+            state: state.into(),
+            host_id: host.id,
+            template_id: None,
+            host_addr: host.addr.clone(),
+            api_sock: String::new(),
+            tap: String::new(),
+            log_path: String::new(),
+            http_port: 0,
+            fc_unit: String::new(),
+            vcpu: 1,
+            mem_mib: 512,
+            kernel_path: String::new(),
+            rootfs_path: String::new(),
+            source_snapshot_id: None,
+            guest_ip: None,
+            tags: vec![],
+            created_by_user_id: None,
+            vmm_kind: None,
+            guest_os: None,
+            console_kind: None,
+            vnc_listen: None,
+            cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
+            created_at: now,
+            updated_at: now,
+            state_changed_at: now,
+            started_at: None,
+        }
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn snapshot_all_reports_each_running_vm(pool: sqlx::PgPool) {
+        let repo = crate::features::hosts::repo::HostRepository::new(pool.clone());
+        let state = crate::AppState::for_tests(pool.clone()).await;
+
+        let host = repo
+            .register("agent-6", "http://127.0.0.1:1", json!({}))
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+        for state_name in ["running", "stopped"] {
+            let row = host_vm(&host, state_name);
+            crate::features::vms::repo::insert(&pool, &row)
+                .await
+                .unwrap();
+            ids.push(row.id);
+        }
+
+        // The agent is unreachable, so the running VM can't be paused; the
+        // stopped one isn't attempted
+        let Json(resp) = super::snapshot_all(
+            Extension(state.clone()),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+        )
+        .await
+        .unwrap();
+        assert!(resp.snapshot_ids.is_empty());
+        assert_eq!(resp.results.len(), 1);
+        assert_eq!((resp.results[0].vm_id, resp.results[0].ok), (ids[0], false));
+        assert!(resp.results[0].error.is_some());
+
+        let missing = super::snapshot_all(
+            Extension(state),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: Uuid::new_v4() }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(missing, StatusCode::NOT_FOUND);
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn snapshot_all_snapshots_running_vms_through_the_agent(pool: sqlx::PgPool) {
+        let mut agent = mockito::Server::new_async().await;
+        let state = crate::AppState::for_tests(pool.clone()).await;
+        let host = state
+            .hosts
+            .register("agent-7", &agent.url(), json!({}))
+            .await
+            .unwrap();
+        let running = host_vm(&host, "running");
+        let stopped = host_vm(&host, "stopped");
+        for row in [&running, &stopped] {
+            crate::features::vms::repo::insert(&pool, row)
+                .await
+                .unwrap();
+        }
+
+        let base = format!("^/agent/v1/vms/{}", running.id);
+        let _health = agent
+            .mock("GET", "/agent/v1/health")
+            .with_body(r#"{"firecracker":{"version":"1.7.0"}}"#)
+            .create_async()
+            .await;
+        let pause_resume = agent
+            .mock("PATCH", mockito::Matcher::Regex(format!("{base}/proxy/vm")))
+            .with_status(204)
+            .expect(2)
+            .create_async()
+            .await;
+        let prepare = agent
+            .mock("POST", mockito::Matcher::Regex(format!("{base}/snapshots/prepare$")))
+            .with_body(
+                r#"{"snapshot_path":"/srv/snap/vmstate","mem_path":"/srv/snap/mem","snapshot_size_bytes":10,"mem_size_bytes":20}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let create = agent
+            .mock(
+                "PUT",
+                mockito::Matcher::Regex(format!("{base}/proxy/snapshot/create")),
+            )
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let Json(resp) = super::snapshot_all(
+            Extension(state.clone()),
+            Actor::system(pool.clone()),
+            Path(HostPathParams { id: host.id }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.results.len(), 1);
+        assert_eq!(
+            (resp.results[0].vm_id, resp.results[0].ok),
+            (running.id, true)
+        );
+        assert_eq!(resp.snapshot_ids.len(), 1);
+        assert_eq!(resp.results[0].snapshot_id, Some(resp.snapshot_ids[0]));
+        pause_resume.assert_async().await;
+        prepare.assert_async().await;
+        create.assert_async().await;
+
+        let snapshot = state.snapshots.get(resp.snapshot_ids[0]).await.unwrap();
+        assert_eq!(snapshot.vm_id, running.id);
+        assert_eq!(snapshot.size_bytes, 30);
+        assert_eq!(
+            crate::features::vms::repo::get(&pool, running.id)
+                .await
+                .unwrap()
+                .state,
+            "running"
+        );
     }
 }
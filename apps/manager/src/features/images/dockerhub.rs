@@ -3,9 +3,14 @@ use bollard::image::CreateImageOptions;
 use bollard::Docker;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::process::Command;
 
+/// How many times an interrupted image pull is retried before giving up.
+const PULL_RETRIES_ENV: &str = "MANAGER_DOCKER_PULL_RETRIES";
+const DEFAULT_PULL_RETRIES: u32 = 3;
+
 /// Docker Hub API client for searching and downloading images
 #[derive(Clone)]
 pub struct DockerHubClient {
@@ -279,86 +284,112 @@ impl DockerHubClient {
         tracing::info!("Pulling Docker image: {}", image);
 
         // Update progress: Pulling
-        {
-            let mut progress_map = progress_tracker.lock().await;
-            if let Some(progress) = progress_map.get_mut(image) {
+        progress_tracker
+            .update(image, |progress| {
                 progress.status = "Pulling image layers...".to_string();
-            }
-        }
-
-        // Use spawn to capture output (Docker outputs progress to stderr by default)
-        let mut pull_process = Command::new("docker")
-            .args(["pull", image])
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .context("Failed to spawn docker pull command")?;
-
-        // Read stderr to capture output and errors
-        // Docker outputs progress information to stderr (though not in easily parseable format)
-        let stderr_handle = pull_process.stderr.take();
-        let mut error_output = Vec::new();
-        let image_name = image.to_string();
-
-        if let Some(stderr) = stderr_handle {
-            use tokio::io::{AsyncBufReadExt, BufReader};
-            let mut reader = BufReader::new(stderr);
-            let mut line = String::new();
-
-            // Read all lines from stderr (progress + errors)
-            while reader.read_line(&mut line).await.is_ok() && !line.is_empty() {
-                // Store line for error messages
-                error_output.push(line.clone());
-
-                // Log progress lines for monitoring (Docker outputs progress to stderr)
-                let trimmed = line.trim();
-
-                // Update progress tracker based on docker output
-                if trimmed.contains("Downloading") {
-                    let mut progress_map = progress_tracker.lock().await;
-                    if let Some(progress) = progress_map.get_mut(&image_name) {
-                        progress.status = "Downloading layers...".to_string();
-                    }
-                } else if trimmed.contains("Extracting") {
-                    let mut progress_map = progress_tracker.lock().await;
-                    if let Some(progress) = progress_map.get_mut(&image_name) {
-                        progress.status = "Extracting layers...".to_string();
+            })
+            .await;
+
+        // A failed pull is retried; the daemon keeps completed layers so each
+        // attempt only fetches what is still missing
+        let max_attempts = pull_attempts();
+        for attempt in 1..=max_attempts {
+            // Use spawn to capture output (Docker outputs progress to stderr by default)
+            let mut pull_process = Command::new("docker")
+                .args(["pull", image])
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .context("Failed to spawn docker pull command")?;
+
+            // Read stderr to capture output and errors
+            // Docker outputs progress information to stderr (though not in easily parseable format)
+            let stderr_handle = pull_process.stderr.take();
+            let mut error_output = Vec::new();
+            let image_name = image.to_string();
+
+            if let Some(stderr) = stderr_handle {
+                use tokio::io::{AsyncBufReadExt, BufReader};
+                let mut reader = BufReader::new(stderr);
+                let mut line = String::new();
+
+                // Read all lines from stderr (progress + errors)
+                while reader.read_line(&mut line).await.is_ok() && !line.is_empty() {
+                    // Store line for error messages
+                    error_output.push(line.clone());
+
+                    // Log progress lines for monitoring (Docker outputs progress to stderr)
+                    let trimmed = line.trim();
+
+                    // Update progress tracker based on docker output
+                    if trimmed.contains("Downloading") {
+                        progress_tracker
+                            .update(&image_name, |progress| {
+                                progress.status = "Downloading layers...".to_string();
+                            })
+                            .await;
+                    } else if trimmed.contains("Extracting") {
+                        progress_tracker
+                            .update(&image_name, |progress| {
+                                progress.status = "Extracting layers...".to_string();
+                            })
+                            .await;
+                    } else if trimmed.contains("Pull complete") {
+                        progress_tracker
+                            .update(&image_name, |progress| {
+                                progress.status = "Pull complete, saving...".to_string();
+                            })
+                            .await;
                     }
-                } else if trimmed.contains("Pull complete") {
-                    let mut progress_map = progress_tracker.lock().await;
-                    if let Some(progress) = progress_map.get_mut(&image_name) {
-                        progress.status = "Pull complete, saving...".to_string();
+
+                    if trimmed.contains("Downloading")
+                        || trimmed.contains("Extracting")
+                        || trimmed.contains("Pull complete")
+                    {
+                        tracing::info!("Docker pull progress: {}", trimmed);
                     }
-                }
 
-                if trimmed.contains("Downloading")
-                    || trimmed.contains("Extracting")
-                    || trimmed.contains("Pull complete")
-                {
-                    tracing::info!("Docker pull progress: {}", trimmed);
+                    line.clear();
                 }
-
-                line.clear();
             }
-        }
 
-        let pull_status = pull_process
-            .wait()
-            .await
-            .context("Failed to wait for docker pull")?;
+            let pull_status = pull_process
+                .wait()
+                .await
+                .context("Failed to wait for docker pull")?;
 
-        if !pull_status.success() {
-            let error_msg = if !error_output.is_empty() {
-                error_output.join("")
-            } else {
-                format!(
-                    "Docker pull exited with code: {}",
-                    pull_status.code().unwrap_or(-1)
-                )
-            };
+            if !pull_status.success() {
+                let error_msg = if !error_output.is_empty() {
+                    error_output.join("")
+                } else {
+                    format!(
+                        "Docker pull exited with code: {}",
+                        pull_status.code().unwrap_or(-1)
+                    )
+                };
+
+                if attempt < max_attempts {
+                    tracing::warn!(
+                        "Docker pull of {} failed (attempt {}/{}): {}",
+                        image,
+                        attempt,
+                        max_attempts,
+                        error_msg
+                    );
+                    mark_retrying(&progress_tracker, image, attempt + 1, max_attempts).await;
+                    tokio::time::sleep(retry_backoff(attempt)).await;
+                    continue;
+                }
 
-            tracing::error!("Docker pull failed for {}: {}", image, error_msg);
-            anyhow::bail!("Docker pull failed: {}", error_msg);
+                tracing::error!("Docker pull failed for {}: {}", image, error_msg);
+                anyhow::bail!(
+                    "Docker pull failed after {} attempts: {}",
+                    max_attempts,
+                    error_msg
+                );
+            }
+
+            break;
         }
 
         tracing::info!("Successfully pulled Docker image: {}", image);
@@ -369,19 +400,19 @@ impl DockerHubClient {
 
         // Save image as tarball
         tracing::info!("Saving Docker image to: {:?}", tarball_path);
+        let part = part_path(&tarball_path);
 
         // Update progress: Saving
-        {
-            let mut progress_map = progress_tracker.lock().await;
-            if let Some(progress) = progress_map.get_mut(image) {
+        progress_tracker
+            .update(image, |progress| {
                 progress.status = "Saving as tarball...".to_string();
-            }
-        }
+            })
+            .await;
 
         let mut save_process = Command::new("docker")
             .arg("save")
             .arg("-o")
-            .arg(&tarball_path)
+            .arg(&part)
             .arg(image)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -416,11 +447,10 @@ impl DockerHubClient {
             };
 
             tracing::error!("Docker save failed for {}: {}", image, error_msg);
+            let _ = tokio::fs::remove_file(&part).await;
             anyhow::bail!("Docker save failed: {}", error_msg);
         }
 
-        tracing::info!("Successfully saved Docker image to: {:?}", tarball_path);
-
         // Get image inspect data for SHA256
         let inspect_output = Command::new("docker")
             .args(["inspect", image])
@@ -439,11 +469,7 @@ impl DockerHubClient {
             .trim_start_matches("sha256:")
             .to_string();
 
-        // Get tarball size
-        let metadata = tokio::fs::metadata(&tarball_path)
-            .await
-            .context("Failed to get tarball metadata")?;
-        let size = metadata.len() as i64;
+        let size = commit_tarball(&part, &tarball_path).await?;
 
         Ok((tarball_path, sha256, size))
     }
@@ -493,128 +519,164 @@ impl DockerHubClient {
             ..Default::default()
         };
 
-        // Start pulling the image
-        let mut stream = docker.create_image(Some(options), None, auth_config);
-
-        // Track total progress across all layers
+        // Track total progress across all layers. Kept across attempts: the
+        // daemon keeps every layer it finished, so a retried pull resumes
+        // from there and the counts should not drop back to zero.
         let mut layer_progress: std::collections::HashMap<String, (u64, u64)> =
             std::collections::HashMap::new();
+        let max_attempts = pull_attempts();
+
+        for attempt in 1..=max_attempts {
+            // Start pulling the image
+            let mut stream = docker.create_image(Some(options.clone()), None, auth_config.clone());
+            let mut failure = None;
+
+            while let Some(result) = stream.next().await {
+                match result {
+                    Ok(info) => {
+                        // Log all status messages for debugging
+                        if let Some(status) = &info.status {
+                            tracing::debug!("Docker status: {} (id: {:?})", status, info.id);
+                        }
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(info) => {
-                    // Log all status messages for debugging
-                    if let Some(status) = &info.status {
-                        tracing::debug!("Docker status: {} (id: {:?})", status, info.id);
-                    }
-
-                    // Update progress based on layer information
-                    if let Some(id) = &info.id {
-                        if let Some(progress_detail) = &info.progress_detail {
-                            if let (Some(current), Some(total)) =
-                                (progress_detail.current, progress_detail.total)
-                            {
-                                tracing::debug!("Layer {} progress: {} / {}", id, current, total);
-                                layer_progress.insert(id.clone(), (current as u64, total as u64));
-                            }
-                        } else {
-                            // Log when progress_detail is missing
-                            if let Some(status) = &info.status {
-                                if status.contains("Downloading") || status.contains("Extracting") {
+                        // Update progress based on layer information
+                        if let Some(id) = &info.id {
+                            if let Some(progress_detail) = &info.progress_detail {
+                                if let (Some(current), Some(total)) =
+                                    (progress_detail.current, progress_detail.total)
+                                {
                                     tracing::debug!(
-                                        "Status '{}' but no progress_detail for layer {}",
-                                        status,
-                                        id
+                                        "Layer {} progress: {} / {}",
+                                        id,
+                                        current,
+                                        total
                                     );
+                                    layer_progress
+                                        .insert(id.clone(), (current as u64, total as u64));
+                                }
+                            } else {
+                                // Log when progress_detail is missing
+                                if let Some(status) = &info.status {
+                                    if status.contains("Downloading")
+                                        || status.contains("Extracting")
+                                    {
+                                        tracing::debug!(
+                                            "Status '{}' but no progress_detail for layer {}",
+                                            status,
+                                            id
+                                        );
+                                    }
                                 }
                             }
                         }
-                    }
 
-                    // Calculate total progress across all layers
-                    let (total_current, total_total): (u64, u64) = layer_progress
-                        .values()
-                        .fold((0, 0), |(acc_curr, acc_tot), (curr, tot)| {
-                            (acc_curr + curr, acc_tot + tot)
-                        });
+                        // Calculate total progress across all layers
+                        let (total_current, total_total): (u64, u64) = layer_progress
+                            .values()
+                            .fold((0, 0), |(acc_curr, acc_tot), (curr, tot)| {
+                                (acc_curr + curr, acc_tot + tot)
+                            });
+
+                        // Update progress tracker with real byte counts
+                        progress_tracker
+                            .update(image, |progress| {
+                                let old_total = progress.total_bytes;
+
+                                // Only update bytes if we have data
+                                if total_total > 0 {
+                                    progress.current_bytes = total_current as i64;
+                                    progress.total_bytes = total_total as i64;
+
+                                    // Log when we first get total bytes
+                                    if old_total == 0 {
+                                        tracing::info!(
+                                            "📊 Download size determined: {} MB total",
+                                            total_total / 1_000_000
+                                        );
+                                    }
+                                }
 
-                    // Update progress tracker with real byte counts
-                    {
-                        let mut progress_map = progress_tracker.lock().await;
-                        if let Some(progress) = progress_map.get_mut(image) {
-                            let old_total = progress.total_bytes;
+                                // Update status based on Docker's status message
+                                if let Some(status) = &info.status {
+                                    match status.as_str() {
+                                        "Pulling fs layer" => {
+                                            progress.status = "Pulling layers...".to_string()
+                                        }
+                                        "Downloading" => {
+                                            progress.status = "Downloading layers...".to_string();
+                                            // Force update even if no progress_detail
+                                            if total_total == 0 {
+                                                tracing::warn!(
+                                                    "Downloading but no size information available yet"
+                                                );
+                                            }
+                                        }
+                                        "Extracting" => {
+                                            progress.status = "Extracting layers...".to_string()
+                                        }
+                                        "Pull complete" => {
+                                            progress.status = "Pull complete".to_string()
+                                        }
+                                        "Already exists" => {
+                                            progress.status = "Using cached layers...".to_string();
+                                            tracing::info!("Layer already exists (cached)");
+                                        }
+                                        "Download complete" => {
+                                            progress.status = "Download complete".to_string()
+                                        }
+                                        "Status: Downloaded newer image" => {
+                                            progress.status = "Download complete".to_string()
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            })
+                            .await;
 
-                            // Only update bytes if we have data
+                        // Log progress for monitoring (every 10% to avoid spam)
+                        if let Some(status) = &info.status {
                             if total_total > 0 {
-                                progress.current_bytes = total_current as i64;
-                                progress.total_bytes = total_total as i64;
-
-                                // Log when we first get total bytes
-                                if old_total == 0 {
+                                let percentage =
+                                    (total_current as f64 / total_total as f64 * 100.0) as u32;
+                                if percentage.is_multiple_of(10) && total_current > 0 {
                                     tracing::info!(
-                                        "📊 Download size determined: {} MB total",
+                                        "📦 Docker pull progress: {} - {}% ({} MB / {} MB)",
+                                        status,
+                                        percentage,
+                                        total_current / 1_000_000,
                                         total_total / 1_000_000
                                     );
                                 }
                             }
-
-                            // Update status based on Docker's status message
-                            if let Some(status) = &info.status {
-                                match status.as_str() {
-                                    "Pulling fs layer" => {
-                                        progress.status = "Pulling layers...".to_string()
-                                    }
-                                    "Downloading" => {
-                                        progress.status = "Downloading layers...".to_string();
-                                        // Force update even if no progress_detail
-                                        if total_total == 0 {
-                                            tracing::warn!(
-                                                "Downloading but no size information available yet"
-                                            );
-                                        }
-                                    }
-                                    "Extracting" => {
-                                        progress.status = "Extracting layers...".to_string()
-                                    }
-                                    "Pull complete" => {
-                                        progress.status = "Pull complete".to_string()
-                                    }
-                                    "Already exists" => {
-                                        progress.status = "Using cached layers...".to_string();
-                                        tracing::info!("Layer already exists (cached)");
-                                    }
-                                    "Download complete" => {
-                                        progress.status = "Download complete".to_string()
-                                    }
-                                    "Status: Downloaded newer image" => {
-                                        progress.status = "Download complete".to_string()
-                                    }
-                                    _ => {}
-                                }
-                            }
                         }
                     }
-
-                    // Log progress for monitoring (every 10% to avoid spam)
-                    if let Some(status) = &info.status {
-                        if total_total > 0 {
-                            let percentage =
-                                (total_current as f64 / total_total as f64 * 100.0) as u32;
-                            if percentage.is_multiple_of(10) && total_current > 0 {
-                                tracing::info!(
-                                    "📦 Docker pull progress: {} - {}% ({} MB / {} MB)",
-                                    status,
-                                    percentage,
-                                    total_current / 1_000_000,
-                                    total_total / 1_000_000
-                                );
-                            }
-                        }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
                     }
                 }
-                Err(e) => {
+            }
+
+            match failure {
+                None => break,
+                Some(e) if attempt < max_attempts => {
+                    tracing::warn!(
+                        "Docker pull of {} interrupted (attempt {}/{}): {}",
+                        image,
+                        attempt,
+                        max_attempts,
+                        e
+                    );
+                    mark_retrying(&progress_tracker, image, attempt + 1, max_attempts).await;
+                    tokio::time::sleep(retry_backoff(attempt)).await;
+                }
+                Some(e) => {
                     tracing::error!("Docker pull error: {}", e);
-                    anyhow::bail!("Failed to pull image: {}", e);
+                    anyhow::bail!(
+                        "Failed to pull image after {} attempts: {}",
+                        max_attempts,
+                        e
+                    );
                 }
             }
         }
@@ -622,12 +684,11 @@ impl DockerHubClient {
         tracing::info!("Successfully pulled Docker image: {}", image);
 
         // Update progress: Saving
-        {
-            let mut progress_map = progress_tracker.lock().await;
-            if let Some(progress) = progress_map.get_mut(image) {
+        progress_tracker
+            .update(image, |progress| {
                 progress.status = "Saving as tarball...".to_string();
-            }
-        }
+            })
+            .await;
 
         // Create docker images directory if it doesn't exist
         let docker_dir = self.image_root.join("docker");
@@ -641,13 +702,14 @@ impl DockerHubClient {
 
         // Export image as tarball using bollard
         tracing::info!("Exporting image to tarball: {:?}", tarball_path);
+        let part = part_path(&tarball_path);
 
         // Note: Bollard doesn't have a direct "save" equivalent yet
         // We'll fall back to CLI for this part
         let mut save_process = Command::new("docker")
             .arg("save")
             .arg("-o")
-            .arg(&tarball_path)
+            .arg(&part)
             .arg(image)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -660,6 +722,7 @@ impl DockerHubClient {
             .context("Failed to wait for docker save")?;
 
         if !save_status.success() {
+            let _ = tokio::fs::remove_file(&part).await;
             anyhow::bail!(
                 "Docker save failed with code: {}",
                 save_status.code().unwrap_or(-1)
@@ -678,11 +741,7 @@ impl DockerHubClient {
             .trim_start_matches("sha256:")
             .to_string();
 
-        // Get tarball size
-        let metadata = tokio::fs::metadata(&tarball_path)
-            .await
-            .context("Failed to get tarball metadata")?;
-        let size = metadata.len() as i64;
+        let size = commit_tarball(&part, &tarball_path).await?;
 
         Ok((tarball_path, sha256, size))
     }
@@ -710,3 +769,110 @@ impl DockerHubClient {
         Ok(())
     }
 }
+
+/// Total pull attempts: the first try plus the configured retries.
+fn pull_attempts() -> u32 {
+    parse_pull_retries(std::env::var(PULL_RETRIES_ENV).ok().as_deref()) + 1
+}
+
+fn parse_pull_retries(raw: Option<&str>) -> u32 {
+    raw.and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_PULL_RETRIES)
+}
+
+/// Exponential backoff between pull attempts, capped at 30 seconds.
+fn retry_backoff(attempt: u32) -> Duration {
+    Duration::from_secs((1u64 << attempt.min(5)).min(30))
+}
+
+/// Record that a new attempt is starting. Byte counts are left alone so the
+/// download resumes from where the failed attempt got to.
+async fn mark_retrying(
+    progress_tracker: &crate::DownloadProgressTracker,
+    image: &str,
+    attempt: u32,
+    max_attempts: u32,
+) {
+    progress_tracker
+        .update(image, |progress| {
+            progress.attempt = attempt;
+            progress.status = format!("Resuming download (attempt {attempt}/{max_attempts})...");
+        })
+        .await;
+}
+
+/// `docker save` writes here first so a partial tarball never sits at the
+/// final path.
+fn part_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Atomically move a finished `.part` tarball into place and verify it.
+async fn commit_tarball(part: &Path, target: &Path) -> Result<i64> {
+    let expected = tokio::fs::metadata(part)
+        .await
+        .context("Failed to get tarball metadata")?
+        .len();
+    if expected == 0 {
+        let _ = tokio::fs::remove_file(part).await;
+        anyhow::bail!("Docker save produced an empty tarball");
+    }
+
+    tokio::fs::rename(part, target)
+        .await
+        .with_context(|| format!("Failed to move {:?} into place", part))?;
+
+    let actual = tokio::fs::metadata(target)
+        .await
+        .context("Failed to get tarball metadata")?
+        .len();
+    if actual != expected {
+        anyhow::bail!(
+            "Tarball size mismatch for {:?}: expected {} bytes, found {}",
+            target,
+            expected,
+            actual
+        );
+    }
+
+    tracing::info!("Successfully saved Docker image to: {:?}", target);
+    Ok(actual as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pull_retries_fall_back_to_default() {
+        assert_eq!(parse_pull_retries(None), DEFAULT_PULL_RETRIES);
+        assert_eq!(parse_pull_retries(Some("junk")), DEFAULT_PULL_RETRIES);
+        assert_eq!(parse_pull_retries(Some(" 5 ")), 5);
+        assert_eq!(parse_pull_retries(Some("0")), 0);
+    }
+
+    #[test]
+    fn retry_backoff_is_capped() {
+        assert_eq!(retry_backoff(1), Duration::from_secs(2));
+        assert_eq!(retry_backoff(10), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn commit_tarball_renames_part_into_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("alpine_latest.tar");
+        let part = part_path(&target);
+        assert_eq!(part, dir.path().join("alpine_latest.tar.part"));
+
+        tokio::fs::write(&part, b"layer bytes").await.unwrap();
+        assert_eq!(commit_tarball(&part, &target).await.unwrap(), 11);
+        assert!(!part.exists());
+        assert!(target.exists());
+
+        tokio::fs::write(&part, b"").await.unwrap();
+        assert!(commit_tarball(&part, &target).await.is_err());
+        assert!(!part.exists());
+    }
+}
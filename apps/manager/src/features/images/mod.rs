@@ -29,6 +29,10 @@ pub fn router() -> Router {
             "/dockerhub/download/progress/:image_name",
             get(routes::dockerhub_download_progress),
         )
+        .route(
+            "/dockerhub/download/progress/:image_name/ws",
+            get(routes::dockerhub_download_progress_ws),
+        )
         .route("/dockerhub/preload", post(routes::dockerhub_preload))
         .route("/import/vmdk", post(routes::import_vmdk))
         .route("/import/p2v", post(routes::import_p2v))
@@ -1,9 +1,11 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use std::path::{Path, PathBuf};
 
 use super::dockerhub::DockerHubClient;
 use super::repo::ImageRepository;
-use nexus_types::CreateImageReq;
+use crate::{AppState, DownloadProgressTracker};
+use nexus_types::{CreateImageReq, PreloadEntryResult, PreloadEntryStatus, PreloadManifestEntry};
 
 /// Default images to pre-load into the registry
 const DEFAULT_IMAGES: &[&str] = &[
@@ -36,6 +38,7 @@ pub async fn preload_default_images(
             kind: Some("docker".to_string()),
             name: Some(image_name.to_string()),
             project: None,
+            ..Default::default()
         };
 
         let existing = image_repo.list(&filter).await?;
@@ -142,3 +145,440 @@ pub async fn preload_custom_images(
 
     Ok(loaded_ids)
 }
+
+/// Manifest entries imported at once, so seeding a large image set neither
+/// saturates the uplink nor queues a dozen pulls on the Docker daemon.
+const MANIFEST_CONCURRENCY: usize = 3;
+
+/// Broadcast URL download progress once per this many bytes.
+const PROGRESS_STEP_BYTES: i64 = 1024 * 1024;
+
+pub const ALLOWED_HOSTS_ENV: &str = "MANAGER_PRELOAD_ALLOWED_HOSTS";
+
+/// Redirects a manifest download may follow, each checked like the source.
+const MAX_REDIRECTS: usize = 5;
+
+/// Hosts manifest URLs may point at, from `MANAGER_PRELOAD_ALLOWED_HOSTS`
+/// (comma-separated; an entry also allows its subdomains). Empty allows any
+/// public host.
+fn allowed_hosts() -> Vec<String> {
+    parse_allowed_hosts(std::env::var(ALLOWED_HOSTS_ENV).ok().as_deref())
+}
+
+fn parse_allowed_hosts(raw: Option<&str>) -> Vec<String> {
+    raw.unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// Loopback, private, link-local and other addresses a download must not
+/// reach: they belong to the manager's own network, not to an image mirror.
+fn is_internal_ip(ip: std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                // 100.64.0.0/10, carrier-grade NAT
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        }
+        std::net::IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_internal_ip(v4.into()),
+            None => {
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || (first & 0xfe00) == 0xfc00 // unique local
+                    || (first & 0xffc0) == 0xfe80 // link-local
+            }
+        },
+    }
+}
+
+/// Refuse a URL the manager shouldn't fetch: anything but https, a host
+/// outside `allowed` (when set), `localhost`, or an internal IP literal.
+fn check_source_url(url: &reqwest::Url, allowed: &[String]) -> Result<()> {
+    if url.scheme() != "https" {
+        bail!("Invalid source '{url}': only https URLs are allowed");
+    }
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                bail!("Invalid source '{url}': {domain} is not permitted");
+            }
+            domain
+        }
+        Some(url::Host::Ipv4(ip)) if is_internal_ip(ip.into()) => {
+            bail!("Invalid source '{url}': internal address {ip} is not permitted")
+        }
+        Some(url::Host::Ipv6(ip)) if is_internal_ip(ip.into()) => {
+            bail!("Invalid source '{url}': internal address {ip} is not permitted")
+        }
+        Some(host) => host.to_string(),
+        None => bail!("Invalid source '{url}': no host"),
+    };
+    if !allowed.is_empty()
+        && !allowed
+            .iter()
+            .any(|entry| host == *entry || host.ends_with(&format!(".{entry}")))
+    {
+        bail!("Invalid source '{url}': {host} is not in {ALLOWED_HOSTS_ENV}");
+    }
+    Ok(())
+}
+
+/// [`check_source_url`], plus the addresses the host resolves to, so a public
+/// name pointing into the manager's network is refused as well.
+async fn check_source(url: &reqwest::Url, allowed: &[String]) -> Result<()> {
+    check_source_url(url, allowed)?;
+    let Some(url::Host::Domain(domain)) = url.host() else {
+        return Ok(());
+    };
+    let port = url.port_or_known_default().unwrap_or(443);
+    for addr in tokio::net::lookup_host((domain, port))
+        .await
+        .with_context(|| format!("failed to resolve {domain}"))?
+    {
+        if is_internal_ip(addr.ip()) {
+            bail!(
+                "Invalid source '{url}': {domain} resolves to internal address {}",
+                addr.ip()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Client for manifest downloads: follows a few redirects, each to a URL
+/// that passes [`check_source_url`] too.
+fn download_client(allowed: Vec<String>) -> Result<reqwest::Client> {
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if let Err(err) = check_source_url(attempt.url(), &allowed) {
+            attempt.error(err.to_string())
+        } else {
+            attempt.follow()
+        }
+    });
+    Ok(reqwest::Client::builder()
+        .redirect(policy)
+        .connect_timeout(std::time::Duration::from_secs(30))
+        .build()?)
+}
+
+/// Import every entry of a preload manifest. Entries run with bounded
+/// concurrency, one failure does not stop the rest, and results come back in
+/// manifest order.
+pub async fn preload_manifest(
+    st: &AppState,
+    entries: Vec<PreloadManifestEntry>,
+) -> Vec<PreloadEntryResult> {
+    futures::stream::iter(entries)
+        .map(|entry| async move {
+            let name = entry.name.clone();
+            match import_manifest_entry(st, &entry).await {
+                Ok((id, status)) => PreloadEntryResult {
+                    name,
+                    status,
+                    id: Some(id),
+                    error: None,
+                },
+                Err(e) => {
+                    tracing::error!("Failed to preload {} from {}: {:#}", name, entry.source, e);
+                    PreloadEntryResult {
+                        name,
+                        status: PreloadEntryStatus::Failed,
+                        id: None,
+                        error: Some(format!("{e:#}")),
+                    }
+                }
+            }
+        })
+        .buffered(MANIFEST_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Check an entry and return its expected SHA-256, lowercased.
+fn validate_manifest_entry(
+    entry: &PreloadManifestEntry,
+    allowed: &[String],
+) -> Result<Option<String>> {
+    if entry.name.trim().is_empty() {
+        bail!("Invalid entry: name is required");
+    }
+    match entry.kind.as_str() {
+        "docker" => {
+            if entry.source.trim().is_empty() {
+                bail!("Invalid entry '{}': source is required", entry.name);
+            }
+        }
+        "kernel" | "rootfs" => {
+            let url = reqwest::Url::parse(&entry.source).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid source '{}': {} images must be an https URL",
+                    entry.source,
+                    entry.kind
+                )
+            })?;
+            check_source_url(&url, allowed)?;
+        }
+        other => bail!("Invalid kind '{other}': must be docker, kernel or rootfs"),
+    }
+    match &entry.sha256 {
+        Some(sha) if sha.len() != 64 || !sha.chars().all(|c| c.is_ascii_hexdigit()) => {
+            bail!("Invalid sha256 '{sha}': must be 64 hex characters")
+        }
+        Some(sha) => Ok(Some(sha.to_ascii_lowercase())),
+        None => Ok(None),
+    }
+}
+
+async fn import_manifest_entry(
+    st: &AppState,
+    entry: &PreloadManifestEntry,
+) -> Result<(uuid::Uuid, PreloadEntryStatus)> {
+    let expected_sha = validate_manifest_entry(entry, &allowed_hosts())?;
+    if let Some(sha) = &expected_sha {
+        if let Some(existing) = st.images.find_by_sha256(sha).await? {
+            return Ok((existing.id, PreloadEntryStatus::Exists));
+        }
+    }
+
+    let tracker = st.download_progress.clone();
+    tracker.start(&entry.source).await;
+    let result = fetch_and_register(st, entry, expected_sha.as_deref()).await;
+    tracker
+        .update(&entry.source, |progress| match &result {
+            Ok(_) => {
+                progress.completed = true;
+                progress.status = "Completed".to_string();
+            }
+            Err(e) => {
+                progress.completed = true;
+                progress.error = Some(format!("{e:#}"));
+                progress.status = "Failed".to_string();
+            }
+        })
+        .await;
+    result
+}
+
+async fn fetch_and_register(
+    st: &AppState,
+    entry: &PreloadManifestEntry,
+    expected_sha: Option<&str>,
+) -> Result<(uuid::Uuid, PreloadEntryStatus)> {
+    let (path, sha256, size) = if entry.kind == "docker" {
+        // `docker save` output is not byte-reproducible, so a docker entry's
+        // sha256 is only used to skip images that are already registered.
+        DockerHubClient::new(st.images.root().to_path_buf())
+            .download_image(&entry.source, None, st.download_progress.clone())
+            .await?
+    } else {
+        let staging = st.images.root().join(".staging");
+        let (staged, sha256, size) =
+            download_url(&entry.source, &staging, &st.download_progress).await?;
+        if let Some(expected) = expected_sha {
+            if sha256 != expected {
+                let _ = tokio::fs::remove_file(&staged).await;
+                bail!("Invalid download: sha256 {sha256} does not match expected {expected}");
+            }
+        }
+        let final_name = manifest_file_name(&entry.source, &sha256);
+        let dest = staged.with_file_name(final_name);
+        tokio::fs::rename(&staged, &dest).await?;
+        let path = super::upload::move_into_dir(&dest, st.images.root().to_path_buf()).await?;
+        (path, sha256, size)
+    };
+
+    // The same bytes may already be registered under another name or source
+    if let Some(existing) = st.images.find_by_sha256(&sha256).await? {
+        if Path::new(&existing.host_path) != path {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+        return Ok((existing.id, PreloadEntryStatus::Exists));
+    }
+
+    let image_req = CreateImageReq {
+        kind: entry.kind.clone(),
+        name: entry.name.clone(),
+        host_path: path.to_string_lossy().to_string(),
+        sha256,
+        size,
+        project: Some("preloaded".to_string()),
+    };
+    match st.images.insert(&image_req).await {
+        Ok(image) => Ok((image.id, PreloadEntryStatus::Imported)),
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(anyhow::anyhow!("failed to register image: {e}"))
+        }
+    }
+}
+
+/// File name for a downloaded image: the URL's last path segment, prefixed
+/// with the start of its digest so different files never collide.
+fn manifest_file_name(url: &str, sha256: &str) -> String {
+    let segment = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("image");
+    format!(
+        "{}-{}",
+        &sha256[..12.min(sha256.len())],
+        super::upload::sanitize_filename(segment)
+    )
+}
+
+/// Stream `url` into a new file under `dir`, hashing it on the way and
+/// reporting progress under the URL. Returns (path, sha256-hex, size-bytes).
+/// Downloads stop at `MANAGER_IMAGE_UPLOAD_MAX_BYTES`, like uploads.
+async fn download_url(
+    url: &str,
+    dir: &Path,
+    tracker: &DownloadProgressTracker,
+) -> Result<(PathBuf, String, i64)> {
+    use sha2::{Digest, Sha256};
+    use tokio::io::AsyncWriteExt;
+
+    let allowed = allowed_hosts();
+    let parsed = reqwest::Url::parse(url).context("Invalid source URL")?;
+    check_source(&parsed, &allowed).await?;
+    let max_bytes = super::upload::max_upload_bytes();
+
+    let mut resp = download_client(allowed)?
+        .get(parsed)
+        .send()
+        .await?
+        .error_for_status()?;
+    if resp.content_length().is_some_and(|len| len > max_bytes) {
+        return Err(super::upload::UploadTooLarge { limit: max_bytes }.into());
+    }
+    let total = resp.content_length().map_or(0, |len| len as i64);
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("Failed to create staging directory")?;
+    let path = dir.join(format!("{}.part", uuid::Uuid::new_v4()));
+    let mut file = tokio::fs::File::create(&path).await?;
+    let mut hasher = Sha256::new();
+    let mut size: i64 = 0;
+    let mut reported: i64 = 0;
+
+    let copied: Result<()> = async {
+        while let Some(chunk) = resp.chunk().await? {
+            size += chunk.len() as i64;
+            if size as u64 > max_bytes {
+                return Err(super::upload::UploadTooLarge { limit: max_bytes }.into());
+            }
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            if size - reported >= PROGRESS_STEP_BYTES {
+                reported = size;
+                tracker
+                    .update(url, |progress| {
+                        progress.status = "Downloading".to_string();
+                        progress.current_bytes = size;
+                        progress.total_bytes = total.max(size);
+                    })
+                    .await;
+            }
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = copied {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+
+    tracker
+        .update(url, |progress| {
+            progress.current_bytes = size;
+            progress.total_bytes = size;
+        })
+        .await;
+    Ok((path, format!("{:x}", hasher.finalize()), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: &str, source: &str, sha256: Option<&str>) -> PreloadManifestEntry {
+        PreloadManifestEntry {
+            kind: kind.into(),
+            name: "img".into(),
+            source: source.into(),
+            sha256: sha256.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn manifest_entries_are_validated() {
+        let sha = "AB".repeat(32);
+        let validate = |entry| validate_manifest_entry(&entry, &[]);
+        assert_eq!(
+            validate(entry("kernel", "https://x/vmlinux", Some(&sha))).unwrap(),
+            Some("ab".repeat(32))
+        );
+        assert!(validate(entry("docker", "nginx:latest", None)).is_ok());
+        assert!(validate(entry("rootfs", "/etc/passwd", None)).is_err());
+        assert!(validate(entry("iso", "https://x/a.iso", None)).is_err());
+        assert!(validate(entry("kernel", "https://x/k", Some("abc"))).is_err());
+    }
+
+    #[test]
+    fn sources_must_be_public_https_hosts() {
+        let check = |url: &str, allowed: &[String]| {
+            check_source_url(&reqwest::Url::parse(url).unwrap(), allowed)
+        };
+        assert!(check("https://cdn.example.com/vmlinux", &[]).is_ok());
+        assert!(check("http://cdn.example.com/vmlinux", &[]).is_err());
+        assert!(check("file:///etc/passwd", &[]).is_err());
+        assert!(check("https://localhost/k", &[]).is_err());
+        assert!(check("https://127.0.0.1/k", &[]).is_err());
+        assert!(check("https://169.254.169.254/latest/meta-data", &[]).is_err());
+        assert!(check("https://10.0.0.5/k", &[]).is_err());
+        assert!(check("https://[::1]/k", &[]).is_err());
+        assert!(check("https://[::ffff:192.168.1.1]/k", &[]).is_err());
+        assert!(check("https://93.184.216.34/k", &[]).is_ok());
+
+        let allowed = parse_allowed_hosts(Some(" example.com, mirror.internal. "));
+        assert_eq!(allowed, ["example.com", "mirror.internal"]);
+        assert!(check("https://example.com/k", &allowed).is_ok());
+        assert!(check("https://cdn.example.com/k", &allowed).is_ok());
+        assert!(check("https://badexample.com/k", &allowed).is_err());
+        assert!(check("https://93.184.216.34/k", &allowed).is_err());
+        assert!(parse_allowed_hosts(None).is_empty());
+    }
+
+    #[test]
+    fn manifest_file_names_are_prefixed_and_sanitized() {
+        let sha = "0123456789abcdef".repeat(4);
+        assert_eq!(
+            manifest_file_name("https://cdn.example/img/vmlinux-6.1?sig=1", &sha),
+            "0123456789ab-vmlinux-6.1"
+        );
+        assert_eq!(
+            manifest_file_name("https://cdn.example/", &sha),
+            "0123456789ab-image"
+        );
+        assert_eq!(
+            manifest_file_name("https://cdn.example/a%2F..", &sha),
+            "0123456789ab-a_2F.."
+        );
+    }
+}
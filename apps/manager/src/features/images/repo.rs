@@ -1,6 +1,6 @@
 use std::path::{Component, Path, PathBuf};
 
-use nexus_types::{CreateImageReq, Image, ImageFilter};
+use nexus_types::{CreateImageReq, Image, ImageFilter, ImageQuota, SetImageQuotaReq};
 use sqlx::PgPool;
 use thiserror::Error;
 use uuid::Uuid;
@@ -39,6 +39,12 @@ impl ImageRepository {
         if !self.is_path_allowed(Path::new(&req.host_path)) {
             return Err(ImageRepoError::InvalidPath(req.host_path.clone()));
         }
+        if let Some(project) = &req.project {
+            let quota = self.quota(project).await?;
+            if let Some(reason) = quota_violation(&quota, req.size) {
+                return Err(ImageRepoError::QuotaExceeded(reason));
+            }
+        }
 
         let row = sqlx::query_as::<_, ImageRow>(
             r#"
@@ -69,17 +75,59 @@ impl ImageRepository {
               AND ($2::text IS NULL OR project = $2)
               AND ($3::text IS NULL OR name ILIKE $3)
             ORDER BY created_at DESC
+            LIMIT $4
+            OFFSET $5
             "#,
         )
         .bind(filter.kind.as_ref())
         .bind(filter.project.as_ref())
         .bind(filter.name.as_ref().map(|name| format!("%{}%", name)))
+        .bind(filter.limit)
+        .bind(filter.offset.unwrap_or(0))
         .fetch_all(&self.pool)
         .await?;
 
         Ok(rows.into_iter().map(Into::into).collect())
     }
 
+    /// Oldest image whose stored file has the given SHA-256, if any.
+    pub async fn find_by_sha256(&self, sha256: &str) -> Result<Option<Image>, ImageRepoError> {
+        let row = sqlx::query_as::<_, ImageRow>(
+            r#"
+            SELECT id, kind, name, host_path, sha256, size, project, image_kind, nvram_template_path, guest_os_hint, disk_format, created_at, updated_at
+            FROM image
+            WHERE sha256 = $1
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(sha256)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(Into::into))
+    }
+
+    /// Number of images matching `filter`, ignoring its `limit`/`offset`.
+    pub async fn count(&self, filter: &ImageFilter) -> Result<i64, ImageRepoError> {
+        let total = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM image
+            WHERE ($1::text IS NULL OR kind = $1)
+              AND ($2::text IS NULL OR project = $2)
+              AND ($3::text IS NULL OR name ILIKE $3)
+            "#,
+        )
+        .bind(filter.kind.as_ref())
+        .bind(filter.project.as_ref())
+        .bind(filter.name.as_ref().map(|name| format!("%{}%", name)))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(total)
+    }
+
     pub async fn get(&self, id: Uuid) -> Result<Image, ImageRepoError> {
         let row = sqlx::query_as::<_, ImageRow>(
             r#"
@@ -95,6 +143,52 @@ impl ImageRepository {
         Ok(row.into())
     }
 
+    /// Limits configured for `project` alongside what its images use today.
+    pub async fn quota(&self, project: &str) -> Result<ImageQuota, ImageRepoError> {
+        let (max_bytes, max_count, used_bytes, used_count) =
+            sqlx::query_as::<_, (Option<i64>, Option<i64>, i64, i64)>(
+                r#"
+                SELECT q.max_bytes,
+                       q.max_count,
+                       COALESCE((SELECT SUM(size) FROM image WHERE project = $1), 0)::BIGINT,
+                       (SELECT COUNT(*) FROM image WHERE project = $1)
+                FROM (SELECT $1::text AS project) p
+                LEFT JOIN image_project_quota q ON q.project = p.project
+                "#,
+            )
+            .bind(project)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(ImageQuota {
+            project: project.to_string(),
+            max_bytes,
+            max_count,
+            used_bytes,
+            used_count,
+        })
+    }
+
+    pub async fn set_quota(&self, req: &SetImageQuotaReq) -> Result<ImageQuota, ImageRepoError> {
+        sqlx::query(
+            r#"
+            INSERT INTO image_project_quota (project, max_bytes, max_count)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (project) DO UPDATE
+              SET max_bytes = EXCLUDED.max_bytes,
+                  max_count = EXCLUDED.max_count,
+                  updated_at = now()
+            "#,
+        )
+        .bind(&req.project)
+        .bind(req.max_bytes)
+        .bind(req.max_count)
+        .execute(&self.pool)
+        .await?;
+
+        self.quota(&req.project).await
+    }
+
     pub async fn delete(&self, id: Uuid) -> Result<(), ImageRepoError> {
         sqlx::query("DELETE FROM image WHERE id = $1")
             .bind(id)
@@ -108,6 +202,8 @@ impl ImageRepository {
 pub enum ImageRepoError {
     #[error("image path '{0}' is not within the configured root")]
     InvalidPath(String),
+    #[error("{0}")]
+    QuotaExceeded(String),
     #[error(transparent)]
     Sql(#[from] sqlx::Error),
 }
@@ -156,6 +252,40 @@ impl From<ImageRow> for Image {
     }
 }
 
+/// How many bytes one more image may take under `quota` (`None` when bytes
+/// are unlimited), or why the project can't take another image at all.
+pub fn upload_room(quota: &ImageQuota) -> Result<Option<u64>, String> {
+    if let Some(reason) = quota_violation(quota, 0) {
+        return Err(reason);
+    }
+    Ok(quota
+        .max_bytes
+        .map(|max| max.saturating_sub(quota.used_bytes).max(0) as u64))
+}
+
+/// Why adding an image of `new_size` bytes would break `quota`, if it would.
+fn quota_violation(quota: &ImageQuota, new_size: i64) -> Option<String> {
+    if let Some(max) = quota.max_count {
+        if quota.used_count + 1 > max {
+            return Some(format!(
+                "project '{}' already has {} of {} allowed images",
+                quota.project, quota.used_count, max
+            ));
+        }
+    }
+    if let Some(max) = quota.max_bytes {
+        if quota.used_bytes + new_size > max {
+            return Some(format!(
+                "project '{}' would use {} of {} allowed bytes",
+                quota.project,
+                quota.used_bytes + new_size,
+                max
+            ));
+        }
+    }
+    None
+}
+
 fn path_within_root(root: &Path, candidate: &Path) -> bool {
     if !candidate.is_absolute() {
         return false;
@@ -198,4 +328,33 @@ mod tests {
             Path::new("/srv/images/vmlinux"),
         ));
     }
+
+    #[test]
+    fn quota_rejects_count_or_bytes_over_limit() {
+        let quota = ImageQuota {
+            project: "ci".into(),
+            max_bytes: Some(1_000),
+            max_count: Some(3),
+            used_bytes: 900,
+            used_count: 2,
+        };
+        assert_eq!(quota_violation(&quota, 100), None);
+        assert!(quota_violation(&quota, 101).unwrap().contains("bytes"));
+        assert_eq!(upload_room(&quota), Ok(Some(100)));
+
+        let full = ImageQuota {
+            used_count: 3,
+            ..quota.clone()
+        };
+        assert!(quota_violation(&full, 0).unwrap().contains("images"));
+        assert!(upload_room(&full).is_err());
+
+        let unlimited = ImageQuota {
+            max_bytes: None,
+            max_count: None,
+            ..full
+        };
+        assert_eq!(quota_violation(&unlimited, i64::MAX / 2), None);
+        assert_eq!(upload_room(&unlimited), Ok(None));
+    }
 }
@@ -1,16 +1,25 @@
 use std::path::Path as StdPath;
 
+use crate::core::pagination::{page_limit, page_offset};
+use crate::features::users::audit::Actor;
+use crate::features::users::repo::AuthenticatedUser;
 use crate::{AppState, DownloadProgress};
 use axum::{
-    extract::{Multipart, Path, Query},
+    extract::{
+        ws::{Message, WebSocket},
+        Multipart, Path, Query, WebSocketUpgrade,
+    },
     http::StatusCode,
+    response::IntoResponse,
     Extension, Json,
 };
 use nexus_types::{
-    CreateImageReq, CreateImageResp, DockerHubSearchReq, DockerHubSearchResp, DockerImageTagsResp,
-    DownloadDockerImageReq, DownloadDockerImageResp, GetImageResp, ImageFilter, ImagePathParams,
-    ListImagesResp, OkResponse,
+    AuditAction, CreateImageReq, CreateImageResp, DockerHubSearchReq, DockerHubSearchResp,
+    DockerImageTagsResp, DownloadDockerImageReq, DownloadDockerImageResp, GetImageResp,
+    ImageFilter, ImagePathParams, ImageQuota, ImageQuotaParams, ListImagesResp, OkResponse,
+    PreloadManifestReq, PreloadManifestResp, SetImageQuotaReq,
 };
+use tokio::sync::broadcast;
 
 #[utoipa::path(
     post,
@@ -19,19 +28,30 @@ use nexus_types::{
     responses(
         (status = 200, description = "Image registered", body = CreateImageResp),
         (status = 400, description = "Invalid image path"),
+        (status = 409, description = "Project image quota exceeded"),
         (status = 500, description = "Failed to store image metadata"),
     ),
     tag = "Images"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateImageReq>,
 ) -> Result<Json<CreateImageResp>, StatusCode> {
-    if !st.images.is_path_allowed(StdPath::new(&req.host_path)) {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-
-    let image = st.images.insert(&req).await.map_err(map_repo_error)?;
+    let result = if st.images.is_path_allowed(StdPath::new(&req.host_path)) {
+        st.images.insert(&req).await.map_err(map_repo_error)
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    };
+    actor
+        .record_with(
+            AuditAction::CreateImage,
+            ("image", result.as_ref().ok().map(|image| image.id)),
+            Some(serde_json::json!({"host_path": req.host_path})),
+            &result,
+        )
+        .await;
+    let image = result?;
 
     Ok(Json(CreateImageResp { id: image.id }))
 }
@@ -48,10 +68,13 @@ pub async fn create(
 )]
 pub async fn list(
     Extension(st): Extension<AppState>,
-    Query(filter): Query<ImageFilter>,
+    Query(mut filter): Query<ImageFilter>,
 ) -> Result<Json<ListImagesResp>, StatusCode> {
+    filter.limit = page_limit(filter.limit);
+    filter.offset = Some(page_offset(filter.offset));
     let items = st.images.list(&filter).await.map_err(map_repo_error)?;
-    Ok(Json(ListImagesResp { items }))
+    let total = st.images.count(&filter).await.map_err(map_repo_error)?;
+    Ok(Json(ListImagesResp { items, total }))
 }
 
 #[utoipa::path(
@@ -86,15 +109,66 @@ pub async fn get(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(ImagePathParams { id }): Path<ImagePathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    st.images.delete(id).await.map_err(map_repo_error)?;
+    let result = st.images.delete(id).await.map_err(map_repo_error);
+    actor
+        .record(AuditAction::DeleteImage, ("image", id), &result)
+        .await;
+    result?;
     Ok(Json(OkResponse::default()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/images/quota",
+    params(ImageQuotaParams),
+    responses(
+        (status = 200, description = "Project image usage against its quota", body = ImageQuota),
+        (status = 401, description = "Not signed in"),
+        (status = 500, description = "Failed to read quota"),
+    ),
+    tag = "Images"
+)]
+pub async fn get_quota(
+    Extension(st): Extension<AppState>,
+    Query(ImageQuotaParams { project }): Query<ImageQuotaParams>,
+) -> Result<Json<ImageQuota>, StatusCode> {
+    let quota = st.images.quota(&project).await.map_err(map_repo_error)?;
+    Ok(Json(quota))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/images/quota",
+    request_body = SetImageQuotaReq,
+    responses(
+        (status = 200, description = "Quota set", body = ImageQuota),
+        (status = 400, description = "Negative limit"),
+        (status = 403, description = "Admin role required"),
+        (status = 500, description = "Failed to store quota"),
+    ),
+    tag = "Images"
+)]
+pub async fn set_quota(
+    Extension(st): Extension<AppState>,
+    Json(req): Json<SetImageQuotaReq>,
+) -> Result<Json<ImageQuota>, StatusCode> {
+    if req.max_bytes.is_some_and(|v| v < 0) || req.max_count.is_some_and(|v| v < 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let quota = st.images.set_quota(&req).await.map_err(map_repo_error)?;
+    Ok(Json(quota))
+}
+
 fn map_repo_error(err: super::repo::ImageRepoError) -> StatusCode {
     match err {
         super::repo::ImageRepoError::InvalidPath(_) => StatusCode::BAD_REQUEST,
+        super::repo::ImageRepoError::QuotaExceeded(reason) => {
+            tracing::warn!("Image rejected: {}", reason);
+            StatusCode::CONFLICT
+        }
         super::repo::ImageRepoError::Sql(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
         super::repo::ImageRepoError::Sql(_) => StatusCode::INTERNAL_SERVER_ERROR,
     }
@@ -156,31 +230,36 @@ pub async fn dockerhub_tags(
     request_body = DownloadDockerImageReq,
     responses(
         (status = 200, description = "Docker image downloaded and cached", body = DownloadDockerImageResp),
+        (status = 400, description = "Unknown registry credential, or REGISTRY_ENCRYPTION_KEY is not set"),
         (status = 500, description = "Failed to download image"),
     ),
     tag = "Images"
 )]
 pub async fn dockerhub_download(
     Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
     Json(req): Json<DownloadDockerImageReq>,
 ) -> Result<Json<DownloadDockerImageResp>, StatusCode> {
     tracing::info!("Starting Docker image download: {}", req.image);
 
+    let registry_auth = crate::features::registries::service::resolve_auth(
+        &st,
+        user.map(|Extension(u)| u.id),
+        req.registry.as_deref(),
+        req.registry_auth.clone(),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to resolve registry credentials: {}", e);
+        if e.downcast_ref::<crate::core::error::Rejection>().is_some() {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
     // Initialize progress tracking
-    {
-        let mut progress_map = st.download_progress.lock().await;
-        progress_map.insert(
-            req.image.clone(),
-            DownloadProgress {
-                image: req.image.clone(),
-                status: "Initializing...".to_string(),
-                current_bytes: 0,
-                total_bytes: 0,
-                completed: false,
-                error: None,
-            },
-        );
-    }
+    st.download_progress.start(&req.image).await;
 
     let dockerhub = super::dockerhub::DockerHubClient::new(st.images.root().to_path_buf());
 
@@ -188,7 +267,7 @@ pub async fn dockerhub_download(
     let download_result = dockerhub
         .download_image(
             &req.image,
-            req.registry_auth.as_ref(),
+            registry_auth.as_ref(),
             st.download_progress.clone(),
         )
         .await;
@@ -198,12 +277,13 @@ pub async fn dockerhub_download(
         Err(e) => {
             tracing::error!("Failed to download Docker image '{}': {}", req.image, e);
             // Update progress with error
-            let mut progress_map = st.download_progress.lock().await;
-            if let Some(progress) = progress_map.get_mut(&req.image) {
-                progress.error = Some(e.to_string());
-                progress.completed = true;
-                progress.status = "Failed".to_string();
-            }
+            st.download_progress
+                .update(&req.image, |progress| {
+                    progress.error = Some(e.to_string());
+                    progress.completed = true;
+                    progress.status = "Failed".to_string();
+                })
+                .await;
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
@@ -221,15 +301,14 @@ pub async fn dockerhub_download(
     let image = st.images.insert(&image_req).await.map_err(map_repo_error)?;
 
     // Mark download as completed
-    {
-        let mut progress_map = st.download_progress.lock().await;
-        if let Some(progress) = progress_map.get_mut(&req.image) {
+    st.download_progress
+        .update(&req.image, |progress| {
             progress.completed = true;
             progress.status = "Completed".to_string();
             progress.current_bytes = size;
             progress.total_bytes = size;
-        }
-    }
+        })
+        .await;
 
     Ok(Json(DownloadDockerImageResp {
         id: image.id,
@@ -258,17 +337,79 @@ pub async fn dockerhub_download_progress(
         .map_err(|_| StatusCode::BAD_REQUEST)?
         .to_string();
 
-    let progress_map = st.download_progress.lock().await;
-
     // Try both encoded and decoded names for compatibility
-    if let Some(progress) = progress_map
-        .get(&decoded_name)
-        .or_else(|| progress_map.get(&image_name))
+    let progress = match st.download_progress.get(&decoded_name).await {
+        Some(progress) => Some(progress),
+        None => st.download_progress.get(&image_name).await,
+    };
+    progress.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/images/dockerhub/download/progress/{image_name}/ws",
+    params(
+        ("image_name" = String, Path, description = "Docker image name (e.g., nginx:latest)")
+    ),
+    responses(
+        (status = 101, description = "Streams DownloadProgress JSON messages until the download finishes"),
+        (status = 400, description = "Invalid image name"),
+    ),
+    tag = "Images"
+)]
+pub async fn dockerhub_download_progress_ws(
+    Extension(st): Extension<AppState>,
+    Path(image_name): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, StatusCode> {
+    let decoded_name = urlencoding::decode(&image_name)
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .to_string();
+    // Same compatibility rule as the poll endpoint: prefer the decoded name
+    // unless only the raw one is being tracked.
+    let image = if st.download_progress.get(&decoded_name).await.is_none()
+        && st.download_progress.get(&image_name).await.is_some()
     {
-        Ok(Json(progress.clone()))
+        image_name
     } else {
-        Err(StatusCode::NOT_FOUND)
+        decoded_name
+    };
+
+    Ok(ws.on_upgrade(move |socket| stream_download_progress(socket, st.download_progress, image)))
+}
+
+async fn stream_download_progress(
+    mut socket: WebSocket,
+    tracker: crate::DownloadProgressTracker,
+    image: String,
+) {
+    // Subscribe before reading the current entry so no update falls between
+    let mut updates = tracker.subscribe();
+    let mut latest = tracker.get(&image).await;
+
+    loop {
+        if let Some(progress) = latest.take() {
+            let Ok(text) = serde_json::to_string(&progress) else {
+                break;
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+            if progress.is_finished() {
+                break;
+            }
+        }
+
+        latest = match updates.recv().await {
+            Ok(progress) if progress.image == image => Some(progress),
+            Ok(_) => None,
+            // Missed some updates; the map still has the newest state
+            Err(broadcast::error::RecvError::Lagged(_)) => tracker.get(&image).await,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
     }
+
+    let _ = socket.send(Message::Close(None)).await;
 }
 
 #[utoipa::path(
@@ -299,6 +440,29 @@ pub async fn dockerhub_preload(
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/images/preload/manifest",
+    request_body = PreloadManifestReq,
+    responses(
+        (status = 200, description = "Per-entry import results, in manifest order", body = PreloadManifestResp),
+        (status = 400, description = "Empty manifest"),
+        (status = 401, description = "Not signed in"),
+        (status = 403, description = "Not an admin"),
+    ),
+    tag = "Images"
+)]
+pub async fn preload_manifest(
+    Extension(st): Extension<AppState>,
+    Json(req): Json<PreloadManifestReq>,
+) -> Result<Json<PreloadManifestResp>, StatusCode> {
+    if req.images.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let items = super::preload::preload_manifest(&st, req.images).await;
+    Ok(Json(PreloadManifestResp { items }))
+}
+
 /// Import a VMware VMDK (or any qemu-img-readable disk) as a registered
 /// image, optionally running virt-v2v to adapt the guest drivers from
 /// VMware's vmxnet3/pvscsi to virtio. Pure server-side operation: the
@@ -337,7 +501,25 @@ fn default_true() -> bool {
 )]
 pub async fn import_vmdk(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<ImportVmdkRequest>,
+) -> Result<Json<CreateImageResp>, StatusCode> {
+    let source_path = req.source_path.clone();
+    let result = import_vmdk_image(st, req).await;
+    actor
+        .record_with(
+            AuditAction::ImportImage,
+            ("image", result.as_ref().ok().map(|Json(resp)| resp.id)),
+            Some(serde_json::json!({"format": "vmdk", "source_path": source_path})),
+            &result,
+        )
+        .await;
+    result
+}
+
+async fn import_vmdk_image(
+    st: AppState,
+    req: ImportVmdkRequest,
 ) -> Result<Json<CreateImageResp>, StatusCode> {
     let source = std::path::Path::new(&req.source_path);
     if tokio::fs::metadata(source).await.is_err() {
@@ -502,7 +684,25 @@ fn sh_quote(s: &str) -> String {
 )]
 pub async fn import_p2v(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<ImportP2vRequest>,
+) -> Result<Json<CreateImageResp>, StatusCode> {
+    let source_host = req.ssh_host.clone();
+    let result = import_p2v_image(st, req).await;
+    actor
+        .record_with(
+            AuditAction::ImportImage,
+            ("image", result.as_ref().ok().map(|Json(resp)| resp.id)),
+            Some(serde_json::json!({"format": "p2v", "source_host": source_host})),
+            &result,
+        )
+        .await;
+    result
+}
+
+async fn import_p2v_image(
+    st: AppState,
+    req: ImportP2vRequest,
 ) -> Result<Json<CreateImageResp>, StatusCode> {
     if req.ssh_password.is_none() && req.ssh_key_path.is_none() {
         tracing::error!("p2v: ssh_password or ssh_key_path is required");
@@ -686,19 +886,43 @@ async fn sha256_file(path: &std::path::Path) -> Option<String> {
     Some(hex::encode(hasher.finalize()))
 }
 
+/// Removes a staged upload that won't be registered, passing `status` through.
+async fn discard_upload(staged: &super::upload::StagedUpload, status: StatusCode) -> StatusCode {
+    let _ = tokio::fs::remove_file(&staged.path).await;
+    status
+}
+
 #[utoipa::path(
     post,
     path = "/v1/images/upload",
-    request_body(content = inline(String), description = "Multipart form data with 'file' and 'kind' fields", content_type = "multipart/form-data"),
+    request_body(content = inline(String), description = "Multipart form data with 'file' and 'kind' fields, and optionally the 'sha256' the file must have", content_type = "multipart/form-data"),
     responses(
         (status = 200, description = "Image uploaded successfully", body = CreateImageResp),
-        (status = 400, description = "Invalid file or missing fields"),
+        (status = 400, description = "Invalid file, missing fields or sha256 mismatch"),
+        (status = 409, description = "Project image quota exceeded, or a file with that name already exists"),
+        (status = 413, description = "File larger than MANAGER_IMAGE_UPLOAD_MAX_BYTES"),
         (status = 500, description = "Upload failed"),
     ),
     tag = "Images"
 )]
 pub async fn upload_image(
     Extension(st): Extension<AppState>,
+    actor: Actor,
+    multipart: Multipart,
+) -> Result<Json<CreateImageResp>, StatusCode> {
+    let result = store_upload(st, multipart).await;
+    actor
+        .record(
+            AuditAction::UploadImage,
+            ("image", result.as_ref().ok().map(|Json(resp)| resp.id)),
+            &result,
+        )
+        .await;
+    result
+}
+
+async fn store_upload(
+    st: AppState,
     mut multipart: Multipart,
 ) -> Result<Json<CreateImageResp>, StatusCode> {
     let mut kind: Option<String> = None;
@@ -712,76 +936,129 @@ pub async fn upload_image(
     // order-independent: the `file` part is streamed to a staging directory in
     // this same pass (we must NOT break and re-iterate, or the file part gets
     // skipped), and the final destination is resolved from `kind` after the
-    // loop. `file_path` below holds the staged path until then.
-    let mut file_path: Option<std::path::PathBuf> = None;
-    let mut sha256: Option<String> = None;
-    let mut size: Option<i64> = None;
+    // loop. `staged` below holds the temporary file until then.
+    let mut staged: Option<super::upload::StagedUpload> = None;
+    // Optional digest the client expects the file to have, checked against
+    // the one computed while streaming.
+    let mut expected_sha256: Option<String> = None;
+    let max_bytes = super::upload::max_upload_bytes();
 
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?
-    {
-        let field_name = field.name().unwrap_or("").to_string();
+    let parsed: Result<(), StatusCode> = async {
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|_| StatusCode::BAD_REQUEST)?
+        {
+            let field_name = field.name().unwrap_or("").to_string();
 
-        match field_name.as_str() {
-            "kind" => {
-                kind = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            "name" => {
-                name = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            "project" => {
-                project = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            "image_kind" => {
-                image_kind = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            "nvram_template_path" => {
-                nvram_template_path =
-                    Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
-            }
-            "file" => {
-                // Stream the file to a staging dir without requiring `kind` to
-                // have arrived yet — browsers send the `file` part before the
-                // `kind` text field, so resolving the destination here would
-                // wrongly 400. The final directory is resolved after the loop.
-                let staging = st.images.root().join(".staging");
-                let (p, s, sz) = super::upload::write_field_to_disk(field, staging, "upload")
-                    .await
-                    .map_err(|e| {
-                        tracing::error!("File upload failed: {}", e);
-                        StatusCode::INTERNAL_SERVER_ERROR
+            match field_name.as_str() {
+                "kind" => {
+                    kind = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                }
+                "name" => {
+                    name = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                }
+                "project" => {
+                    project = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                }
+                "image_kind" => {
+                    image_kind = Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                }
+                "nvram_template_path" => {
+                    nvram_template_path =
+                        Some(field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?);
+                }
+                "sha256" => {
+                    let text = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                    expected_sha256 = Some(text.trim().to_ascii_lowercase());
+                }
+                "file" => {
+                    // Stream the file to a staging dir without requiring `kind` to
+                    // have arrived yet — browsers send the `file` part before the
+                    // `kind` text field, so resolving the destination here would
+                    // wrongly 400. The final directory is resolved after the loop.
+                    // Check the project's image quota before streaming, and
+                    // stop the stream once the file would no longer fit. A
+                    // `project` sent after the file is checked at registration
+                    let quota_project = project.as_deref().unwrap_or("uploaded");
+                    let quota = st
+                        .images
+                        .quota(quota_project)
+                        .await
+                        .map_err(map_repo_error)?;
+                    let room = super::repo::upload_room(&quota).map_err(|reason| {
+                        tracing::warn!("File upload refused: {}", reason);
+                        StatusCode::CONFLICT
                     })?;
-                file_path = Some(p);
-                sha256 = Some(s);
-                size = Some(sz);
+                    let limit = room.map_or(max_bytes, |room| room.min(max_bytes));
+                    let staging = st.images.root().join(".staging");
+                    let upload =
+                        super::upload::write_field_to_disk(field, staging, "upload", limit)
+                            .await
+                            .map_err(|e| {
+                                if e.is::<super::upload::UploadTooLarge>() {
+                                    tracing::warn!("File upload refused: {}", e);
+                                    if limit < max_bytes {
+                                        StatusCode::CONFLICT
+                                    } else {
+                                        StatusCode::PAYLOAD_TOO_LARGE
+                                    }
+                                } else {
+                                    tracing::error!("File upload failed: {}", e);
+                                    StatusCode::INTERNAL_SERVER_ERROR
+                                }
+                            })?;
+                    if let Some(previous) = staged.replace(upload) {
+                        let _ = tokio::fs::remove_file(&previous.path).await;
+                    }
+                }
+                _ => {}
             }
-            _ => {}
         }
+        Ok(())
     }
+    .await;
 
-    let kind = kind.ok_or(StatusCode::BAD_REQUEST)?;
-    let staged_path = file_path.ok_or(StatusCode::BAD_REQUEST)?;
+    let staged = match (parsed, staged) {
+        (Ok(()), Some(staged)) => staged,
+        (result, staged) => {
+            if let Some(staged) = staged {
+                let _ = tokio::fs::remove_file(&staged.path).await;
+            }
+            return Err(result.err().unwrap_or(StatusCode::BAD_REQUEST));
+        }
+    };
+    if expected_sha256
+        .as_deref()
+        .is_some_and(|expected| expected != staged.sha256)
+    {
+        tracing::warn!(sha256 = %staged.sha256, expected = ?expected_sha256, "uploaded file does not match the expected sha256");
+        return Err(discard_upload(&staged, StatusCode::BAD_REQUEST).await);
+    }
+    let Some(kind) = kind else {
+        return Err(discard_upload(&staged, StatusCode::BAD_REQUEST).await);
+    };
     // Resolve the destination now that every text field has been parsed, then
-    // move the staged file into place. This makes the handler independent of
+    // rename the staged file into place. This makes the handler independent of
     // multipart field ordering.
     let upload_dir = match kind.as_str() {
         "docker" => st.images.root().join("docker"),
         "kernel" | "rootfs" => st.images.root().to_path_buf(),
-        _ => {
-            let _ = tokio::fs::remove_file(&staged_path).await;
-            return Err(StatusCode::BAD_REQUEST);
-        }
+        _ => return Err(discard_upload(&staged, StatusCode::BAD_REQUEST).await),
     };
-    let file_path = super::upload::move_into_dir(&staged_path, upload_dir)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to finalize uploaded file: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    let sha256 = sha256.unwrap_or_default();
-    let size = size.unwrap_or(0);
+    let file_path = upload_dir.join(&staged.file_name);
+    // Never replace a file an existing image may point at, even when two
+    // uploads of the same name finish at once
+    if let Err(e) = super::upload::move_new(&staged.path, &file_path).await {
+        if e.is::<super::upload::DestinationExists>() {
+            tracing::warn!("Upload refused: {}", e);
+            return Err(discard_upload(&staged, StatusCode::CONFLICT).await);
+        }
+        tracing::error!("Failed to finalize uploaded file: {}", e);
+        return Err(discard_upload(&staged, StatusCode::INTERNAL_SERVER_ERROR).await);
+    }
+    let sha256 = staged.sha256;
+    let size = staged.size;
 
     // If Docker image, load it to get the actual image name
     let default_name = || {
@@ -812,7 +1089,14 @@ pub async fn upload_image(
         project: project.or(Some("uploaded".to_string())),
     };
 
-    let image = st.images.insert(&image_req).await.map_err(map_repo_error)?;
+    let image = match st.images.insert(&image_req).await {
+        Ok(image) => image,
+        Err(e) => {
+            // Nothing references the file yet; don't leave it eating the quota's disk
+            let _ = tokio::fs::remove_file(&file_path).await;
+            return Err(map_repo_error(e));
+        }
+    };
 
     // Persist the VMM-aware fields if the client supplied them. Defaults
     // ('linux_kernel' for the strict enum) are already set by the migration.
@@ -849,53 +1133,13 @@ pub async fn upload_image(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::features::hosts::repo::HostRepository;
-    use crate::features::images::repo::ImageRepository;
     use axum::{extract::Path, Extension};
     use nexus_types::CreateImageReq;
 
-    async fn test_registry(pool: &sqlx::PgPool) -> crate::features::storage::registry::Registry {
-        crate::features::storage::registry::Registry::load(pool, None)
-            .await
-            .expect("registry")
-    }
-
     #[ignore]
     #[sqlx::test(migrations = "./migrations")]
     async fn create_and_list_images(pool: sqlx::PgPool) {
-        let hosts = HostRepository::new(pool.clone());
-        let images = ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts,
-            images: images.clone(),
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
 
         let req = CreateImageReq {
             kind: "kernel".into(),
@@ -906,9 +1150,13 @@ mod tests {
             project: Some("default".into()),
         };
 
-        let Json(resp) = super::create(Extension(state.clone()), Json(req.clone()))
-            .await
-            .unwrap();
+        let Json(resp) = super::create(
+            Extension(state.clone()),
+            Actor::system(state.db.clone()),
+            Json(req.clone()),
+        )
+        .await
+        .unwrap();
 
         let Json(list) = super::list(Extension(state.clone()), Query(ImageFilter::default()))
             .await
@@ -926,6 +1174,7 @@ mod tests {
 
         let Json(ok) = super::delete(
             Extension(state.clone()),
+            Actor::system(state.db.clone()),
             Path(ImagePathParams { id: resp.id }),
         )
         .await
@@ -936,39 +1185,7 @@ mod tests {
     #[ignore]
     #[sqlx::test(migrations = "./migrations")]
     async fn reject_out_of_root_path(pool: sqlx::PgPool) {
-        let hosts = HostRepository::new(pool.clone());
-        let images = ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts,
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
 
         let req = CreateImageReq {
             kind: "kernel".into(),
@@ -979,7 +1196,42 @@ mod tests {
             project: None,
         };
 
-        let result = super::create(Extension(state), Json(req)).await;
+        let result = super::create(
+            Extension(state.clone()),
+            Actor::system(state.db.clone()),
+            Json(req),
+        )
+        .await;
         assert_eq!(result.unwrap_err(), StatusCode::BAD_REQUEST);
     }
+
+    #[tokio::test]
+    async fn progress_tracker_broadcasts_every_change() {
+        let tracker = crate::DownloadProgressTracker::default();
+        let mut updates = tracker.subscribe();
+
+        tracker.start("nginx:latest").await;
+        tracker
+            .update("nginx:latest", |p| {
+                p.current_bytes = 10;
+                p.total_bytes = 40;
+            })
+            .await;
+        // Updates to untracked images are dropped, not broadcast
+        tracker.update("redis:latest", |p| p.completed = true).await;
+        tracker
+            .update("nginx:latest", |p| p.error = Some("boom".into()))
+            .await;
+
+        let first = updates.recv().await.unwrap();
+        assert_eq!(first.status, "Initializing...");
+        let second = updates.recv().await.unwrap();
+        assert_eq!((second.current_bytes, second.total_bytes), (10, 40));
+        assert!(!second.is_finished());
+        let third = updates.recv().await.unwrap();
+        assert_eq!(third.image, "nginx:latest");
+        assert!(third.is_finished());
+        assert!(updates.try_recv().is_err());
+        assert_eq!(tracker.get("nginx:latest").await.unwrap().current_bytes, 10);
+    }
 }
@@ -88,6 +88,7 @@ pub async fn scan_and_register_base_images(image_repo: &ImageRepository) -> anyh
             kind: Some(kind.to_string()),
             name: Some((*display_name).to_string()),
             project: None,
+            ..Default::default()
         };
 
         let existing = image_repo.list(&filter).await?;
@@ -1,74 +1,194 @@
 use anyhow::{Context, Result};
 use axum::extract::multipart::Field;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
+pub const MAX_UPLOAD_BYTES_ENV: &str = "MANAGER_IMAGE_UPLOAD_MAX_BYTES";
+/// Room for the largest installer ISOs and cloud images.
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 64 << 30;
+
+/// Largest file `POST /v1/images/upload` accepts, from
+/// `MANAGER_IMAGE_UPLOAD_MAX_BYTES` (default 64 GiB).
+pub fn max_upload_bytes() -> u64 {
+    parse_max_upload_bytes(std::env::var(MAX_UPLOAD_BYTES_ENV).ok().as_deref())
+}
+
+fn parse_max_upload_bytes(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|bytes| *bytes > 0)
+        .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES)
+}
+
+/// The upload ran past [`max_upload_bytes`]; answered with 413.
+#[derive(Debug, thiserror::Error)]
+#[error("upload exceeds the {limit}-byte limit")]
+pub struct UploadTooLarge {
+    pub limit: u64,
+}
+
+/// A fully written upload, still under its temporary name.
+#[derive(Debug)]
+pub struct StagedUpload {
+    pub path: PathBuf,
+    /// The sanitized client filename the upload is moved into place under.
+    pub file_name: String,
+    pub sha256: String,
+    pub size: i64,
+}
+
 /// Stream a single multipart `file` field straight to disk while hashing it in
 /// one pass. Unlike buffering the whole part in memory (`field.bytes()`), this
 /// handles multi-GB ISOs (Windows installers, virtio-win, cloud images) without
-/// blowing up RAM. Returns (path, sha256-hex, size-bytes).
+/// blowing up RAM.
+///
+/// The bytes go to a uniquely named `.part` file in `upload_dir`, so
+/// concurrent uploads of the same filename don't interleave; it is removed if
+/// the stream fails or passes `max_bytes` ([`UploadTooLarge`]).
 pub async fn write_field_to_disk(
     mut field: Field<'_>,
     upload_dir: PathBuf,
     kind: &str,
-) -> Result<(PathBuf, String, i64)> {
+    max_bytes: u64,
+) -> Result<StagedUpload> {
     use sha2::{Digest, Sha256};
 
     tokio::fs::create_dir_all(&upload_dir)
         .await
         .context("Failed to create upload directory")?;
 
-    let original_filename = field.file_name().map(|s| s.to_string());
-    let filename = match &original_filename {
+    let file_name = match field.file_name() {
         Some(fname) => sanitize_filename(fname),
         None => format!("{}-{}.img", kind, uuid::Uuid::new_v4()),
     };
-    let path = upload_dir.join(&filename);
+    let path = upload_dir.join(format!("{}.part", uuid::Uuid::new_v4()));
 
     let mut file = File::create(&path).await?;
     let mut hasher = Sha256::new();
-    let mut size: i64 = 0;
-    while let Some(chunk) = field.chunk().await? {
-        file.write_all(&chunk).await?;
-        hasher.update(&chunk);
-        size += chunk.len() as i64;
+    let mut size: u64 = 0;
+    let written: Result<()> = async {
+        while let Some(chunk) = field.chunk().await? {
+            size += chunk.len() as u64;
+            if size > max_bytes {
+                return Err(UploadTooLarge { limit: max_bytes }.into());
+            }
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = written {
+        drop(file);
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(err);
     }
-    file.flush().await?;
 
     let sha256 = format!("{:x}", hasher.finalize());
-    tracing::info!("Uploaded file: {:?} ({} bytes)", path, size);
-    Ok((path, sha256, size))
+    tracing::info!("Uploaded file: {:?} ({} bytes)", file_name, size);
+    Ok(StagedUpload {
+        path,
+        file_name,
+        sha256,
+        size: size as i64,
+    })
 }
 
 /// Move a staged upload into its final directory once the destination is known.
 ///
 /// Uploads are streamed to a staging directory first so that multipart field
 /// ordering does not matter (browsers send the `file` part before the `kind`
-/// text field). A rename is used when source and destination share a
-/// filesystem; otherwise we fall back to copy + remove.
-pub async fn move_into_dir(staged: &std::path::Path, dest_dir: PathBuf) -> Result<PathBuf> {
-    tokio::fs::create_dir_all(&dest_dir)
-        .await
-        .context("Failed to create destination directory")?;
+/// text field).
+pub async fn move_into_dir(staged: &Path, dest_dir: PathBuf) -> Result<PathBuf> {
     let filename = staged
         .file_name()
         .map(|n| n.to_owned())
         .context("staged upload has no filename")?;
     let dest = dest_dir.join(filename);
+    move_to(staged, &dest).await?;
+    Ok(dest)
+}
 
-    if tokio::fs::rename(staged, &dest).await.is_err() {
+/// Moves `staged` to `dest`. A rename is used when source and destination
+/// share a filesystem (staging lives under the image root, so it normally
+/// does) and the file appears there whole; otherwise we fall back to copy +
+/// remove.
+pub async fn move_to(staged: &Path, dest: &Path) -> Result<()> {
+    if let Some(dest_dir) = dest.parent() {
+        tokio::fs::create_dir_all(dest_dir)
+            .await
+            .context("Failed to create destination directory")?;
+    }
+    if tokio::fs::rename(staged, dest).await.is_err() {
         // Cross-filesystem move: copy then remove the staged file.
-        tokio::fs::copy(staged, &dest)
+        tokio::fs::copy(staged, dest)
             .await
             .context("Failed to copy staged upload to destination")?;
         let _ = tokio::fs::remove_file(staged).await;
     }
-    Ok(dest)
+    Ok(())
+}
+
+/// Something already has the destination's name; answered with 409.
+#[derive(Debug, thiserror::Error)]
+#[error("{} already exists", .0.display())]
+pub struct DestinationExists(pub PathBuf);
+
+/// Moves `staged` to `dest` without ever replacing a file there. A hard link
+/// claims the name atomically (failing with [`DestinationExists`] if it's
+/// taken) before the staged name is dropped; across filesystems the copy
+/// goes into a file this call created.
+pub async fn move_new(staged: &Path, dest: &Path) -> Result<()> {
+    use std::io::ErrorKind;
+
+    if let Some(dest_dir) = dest.parent() {
+        tokio::fs::create_dir_all(dest_dir)
+            .await
+            .context("Failed to create destination directory")?;
+    }
+    match tokio::fs::hard_link(staged, dest).await {
+        Ok(()) => {
+            let _ = tokio::fs::remove_file(staged).await;
+            return Ok(());
+        }
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            return Err(DestinationExists(dest.to_path_buf()).into());
+        }
+        Err(_) => {}
+    }
+
+    // Cross-filesystem move: copy into a file only this call created
+    let mut out = match tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)
+        .await
+    {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            return Err(DestinationExists(dest.to_path_buf()).into());
+        }
+        Err(err) => return Err(err).context("Failed to create destination file"),
+    };
+    let copied: Result<()> = async {
+        let mut input = File::open(staged).await?;
+        tokio::io::copy(&mut input, &mut out).await?;
+        out.flush().await?;
+        Ok(())
+    }
+    .await;
+    if let Err(err) = copied {
+        drop(out);
+        let _ = tokio::fs::remove_file(dest).await;
+        return Err(err.context("Failed to copy staged upload to destination"));
+    }
+    let _ = tokio::fs::remove_file(staged).await;
+    Ok(())
 }
 
 /// Sanitize filename to prevent path traversal
-fn sanitize_filename(filename: &str) -> String {
+pub(crate) fn sanitize_filename(filename: &str) -> String {
     filename
         .chars()
         .map(|c| {
@@ -129,4 +249,111 @@ mod tests {
         assert_eq!(sanitize_filename("my file.tar"), "my_file.tar");
         assert_eq!(sanitize_filename("test@#$.tar"), "test___.tar");
     }
+
+    #[test]
+    fn max_upload_bytes_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_max_upload_bytes(None), DEFAULT_MAX_UPLOAD_BYTES);
+        assert_eq!(parse_max_upload_bytes(Some("0")), DEFAULT_MAX_UPLOAD_BYTES);
+        assert_eq!(
+            parse_max_upload_bytes(Some("lots")),
+            DEFAULT_MAX_UPLOAD_BYTES
+        );
+        assert_eq!(parse_max_upload_bytes(Some(" 1048576 ")), 1 << 20);
+    }
+
+    const CHUNK: usize = 64 << 10;
+    const CHUNKS: usize = 512;
+
+    /// Posts a 32 MiB `file` part, generated chunk by chunk as it is read like
+    /// a socket, through a route with the upload route's body limit, and
+    /// stages it with `max_bytes`.
+    async fn stage_large_upload(dir: &Path, max_bytes: u64) -> Result<StagedUpload> {
+        use axum::body::{Body, Bytes};
+        use axum::extract::{DefaultBodyLimit, Multipart};
+        use std::sync::{Arc, Mutex};
+        use tower::ServiceExt;
+
+        let boundary = "nqrust-upload-test";
+        let head = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"big disk.img\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n"
+        );
+        let tail = format!("\r\n--{boundary}--\r\n");
+        let parts = std::iter::once(Bytes::from(head))
+            .chain((0..CHUNKS).map(|i| Bytes::from(vec![(i % 251) as u8; CHUNK])))
+            .chain(std::iter::once(Bytes::from(tail)))
+            .map(Ok::<_, std::io::Error>);
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from_stream(futures::stream::iter(parts)))
+            .unwrap();
+
+        let outcome = Arc::new(Mutex::new(None));
+        let slot = outcome.clone();
+        let dir = dir.to_path_buf();
+        let app = axum::Router::new().route(
+            "/upload",
+            axum::routing::post(move |mut multipart: Multipart| async move {
+                let field = multipart.next_field().await.unwrap().unwrap();
+                let staged = write_field_to_disk(field, dir, "upload", max_bytes).await;
+                *slot.lock().unwrap() = Some(staged);
+            })
+            .layer(DefaultBodyLimit::disable()),
+        );
+        app.oneshot(request).await.unwrap();
+        let staged = outcome.lock().unwrap().take().unwrap();
+        staged
+    }
+
+    #[tokio::test]
+    async fn streams_large_upload_to_a_part_file_and_enforces_the_limit() {
+        use sha2::{Digest, Sha256};
+
+        let dir = tempfile::tempdir().unwrap();
+        let total = (CHUNK * CHUNKS) as u64;
+        let mut expected = Sha256::new();
+        for i in 0..CHUNKS {
+            expected.update(vec![(i % 251) as u8; CHUNK]);
+        }
+
+        let staged = stage_large_upload(dir.path(), total).await.unwrap();
+        assert_eq!(staged.file_name, "big_disk.img");
+        assert!(staged.path.to_string_lossy().ends_with(".part"));
+        assert_eq!(staged.size as u64, total);
+        assert_eq!(staged.sha256, format!("{:x}", expected.finalize()));
+        assert_eq!(
+            tokio::fs::metadata(&staged.path).await.unwrap().len(),
+            total
+        );
+
+        let dest = dir.path().join("images").join(&staged.file_name);
+        move_to(&staged.path, &dest).await.unwrap();
+        assert!(!staged.path.exists());
+        assert_eq!(tokio::fs::metadata(&dest).await.unwrap().len(), total);
+
+        // The name is taken now: the next upload under it is refused and
+        // the file already there is left alone
+        let again = stage_large_upload(dir.path(), total).await.unwrap();
+        let err = move_new(&again.path, &dest).await.unwrap_err();
+        assert!(err.is::<DestinationExists>());
+        assert!(again.path.exists());
+        tokio::fs::remove_file(&again.path).await.unwrap();
+        let other = dir.path().join("images").join("other.img");
+        move_new(&dest, &other).await.unwrap();
+        assert!(!dest.exists());
+        assert_eq!(tokio::fs::metadata(&other).await.unwrap().len(), total);
+
+        // One byte over the limit: refused, and the partial file is removed
+        let err = stage_large_upload(dir.path(), total - 1).await.unwrap_err();
+        assert!(err.is::<UploadTooLarge>());
+        let mut leftovers = tokio::fs::read_dir(dir.path()).await.unwrap();
+        while let Some(entry) = leftovers.next_entry().await.unwrap() {
+            assert_eq!(entry.file_name(), "images");
+        }
+    }
 }
@@ -0,0 +1,16 @@
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+pub mod registry;
+pub mod routes;
+
+pub use registry::{is_cancelled, JobHandle, JobRegistry};
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(routes::list))
+        .route("/:id", get(routes::get))
+        .route("/:id/cancel", post(routes::cancel))
+}
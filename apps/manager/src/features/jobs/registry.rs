@@ -0,0 +1,264 @@
+//! In-flight VM creates and live migrations, with the cancellation tokens
+//! `POST /v1/jobs/{id}/cancel` triggers.
+
+use anyhow::Result;
+use nexus_types::{Job, JobKind, JobStatus};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How long a finished job stays visible to `GET /v1/jobs/{id}`.
+const FINISHED_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Error a job's service function returns once it noticed the cancel and
+/// rolled back. Survives `.context(..)`, so check it with [`is_cancelled`].
+#[derive(Debug)]
+pub struct JobCancelled;
+
+impl std::fmt::Display for JobCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("job was cancelled")
+    }
+}
+
+impl std::error::Error for JobCancelled {}
+
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<JobCancelled>().is_some()
+}
+
+/// The running side of a job: service functions poll it between steps.
+#[derive(Clone)]
+pub struct JobHandle {
+    pub id: Uuid,
+    token: CancellationToken,
+}
+
+impl JobHandle {
+    /// Fails with [`JobCancelled`] once a cancel has been requested.
+    pub fn check(&self) -> Result<()> {
+        if self.token.is_cancelled() {
+            return Err(JobCancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Await `fut`, dropping it and failing with [`JobCancelled`] if the job
+    /// is cancelled first. Only wrap steps that are safe to abandon midway.
+    pub async fn run<T, E>(&self, fut: impl Future<Output = Result<T, E>>) -> Result<T>
+    where
+        E: Into<anyhow::Error>,
+    {
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => Err(JobCancelled.into()),
+            res = fut => res.map_err(Into::into),
+        }
+    }
+
+    pub async fn sleep(&self, duration: Duration) -> Result<()> {
+        self.run(async {
+            tokio::time::sleep(duration).await;
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CancelError {
+    NotFound,
+    AlreadyFinished(JobStatus),
+}
+
+struct Entry {
+    job: Job,
+    token: CancellationToken,
+}
+
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<Uuid, Entry>>>,
+}
+
+impl JobRegistry {
+    pub fn start(&self, kind: JobKind, vm_id: Uuid) -> JobHandle {
+        let handle = JobHandle {
+            id: Uuid::new_v4(),
+            token: CancellationToken::new(),
+        };
+        let job = Job {
+            id: handle.id,
+            kind,
+            vm_id,
+            status: JobStatus::Running,
+            error: None,
+            started_at: chrono::Utc::now(),
+            finished_at: None,
+        };
+        self.jobs.lock().unwrap().insert(
+            handle.id,
+            Entry {
+                job,
+                token: handle.token.clone(),
+            },
+        );
+        handle
+    }
+
+    /// Record the job's outcome. A job that was cancelled but got past its
+    /// last cancellation point still finishes as succeeded.
+    pub fn finish(&self, handle: &JobHandle, result: &Result<()>) {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(entry) = jobs.get_mut(&handle.id) else {
+                return;
+            };
+            let (status, error) = match result {
+                Ok(()) => (JobStatus::Succeeded, None),
+                Err(err) if is_cancelled(err) => (JobStatus::Cancelled, None),
+                Err(err) => (JobStatus::Failed, Some(format!("{err:#}"))),
+            };
+            entry.job.status = status;
+            entry.job.error = error;
+            entry.job.finished_at = Some(chrono::Utc::now());
+        }
+        self.expire_later(handle.id);
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Job> {
+        self.jobs.lock().unwrap().get(&id).map(|e| e.job.clone())
+    }
+
+    /// Known jobs, newest first, optionally only those for one VM.
+    pub fn list(&self, vm_id: Option<Uuid>) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| vm_id.is_none_or(|vm_id| e.job.vm_id == vm_id))
+            .map(|e| e.job.clone())
+            .collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        jobs
+    }
+
+    /// Trip the job's token. The job keeps `cancelling` until its service
+    /// function has rolled back and called [`JobRegistry::finish`].
+    pub fn cancel(&self, id: Uuid) -> Result<Job, CancelError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let entry = jobs.get_mut(&id).ok_or(CancelError::NotFound)?;
+        if entry.job.status.is_finished() {
+            return Err(CancelError::AlreadyFinished(entry.job.status));
+        }
+        entry.job.status = JobStatus::Cancelling;
+        entry.token.cancel();
+        Ok(entry.job.clone())
+    }
+
+    /// Cancel every job still running, for shutdown. Returns how many were.
+    pub fn cancel_running(&self) -> usize {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut cancelled = 0;
+        for entry in jobs.values_mut() {
+            if entry.job.status == JobStatus::Running {
+                entry.job.status = JobStatus::Cancelling;
+                entry.token.cancel();
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    fn expire_later(&self, id: Uuid) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let jobs = self.jobs.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(FINISHED_RETENTION).await;
+            jobs.lock().unwrap().remove(&id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[tokio::test]
+    async fn cancel_interrupts_a_running_step() {
+        let registry = JobRegistry::default();
+        let vm_id = Uuid::new_v4();
+        let job = registry.start(JobKind::CreateVm, vm_id);
+        assert!(job.check().is_ok());
+
+        let cancelled = registry.cancel(job.id).unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelling);
+
+        let err = job
+            .run(std::future::pending::<Result<()>>())
+            .await
+            .context("spawn firecracker")
+            .unwrap_err();
+        assert!(is_cancelled(&err));
+        assert!(is_cancelled(&job.check().unwrap_err()));
+
+        registry.finish(&job, &Err(err));
+        let job = registry.get(job.id).unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert!(job.finished_at.is_some());
+        assert_eq!(
+            registry.cancel(job.id),
+            Err(CancelError::AlreadyFinished(JobStatus::Cancelled))
+        );
+    }
+
+    #[tokio::test]
+    async fn uncancelled_jobs_pass_results_through() {
+        let registry = JobRegistry::default();
+        let job = registry.start(JobKind::MigrateVm, Uuid::new_v4());
+        assert_eq!(
+            job.run(async { Ok::<_, anyhow::Error>(7) }).await.unwrap(),
+            7
+        );
+
+        let failure = Err(anyhow!("target agent returned 500"));
+        registry.finish(&job, &failure);
+        let job = registry.get(job.id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("target agent returned 500"));
+    }
+
+    #[test]
+    fn list_filters_by_vm_and_cancel_rejects_unknown_ids() {
+        let registry = JobRegistry::default();
+        let vm_id = Uuid::new_v4();
+        registry.start(JobKind::CreateVm, vm_id);
+        registry.start(JobKind::CreateVm, Uuid::new_v4());
+
+        assert_eq!(registry.list(None).len(), 2);
+        let for_vm = registry.list(Some(vm_id));
+        assert_eq!(for_vm.len(), 1);
+        assert_eq!(for_vm[0].vm_id, vm_id);
+        assert_eq!(registry.cancel(Uuid::new_v4()), Err(CancelError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn shutdown_cancels_only_running_jobs() {
+        let registry = JobRegistry::default();
+        let done = registry.start(JobKind::CreateVm, Uuid::new_v4());
+        registry.finish(&done, &Ok(()));
+        let running = registry.start(JobKind::MigrateVm, Uuid::new_v4());
+
+        assert_eq!(registry.cancel_running(), 1);
+        assert!(is_cancelled(&running.check().unwrap_err()));
+        assert_eq!(registry.get(done.id).unwrap().status, JobStatus::Succeeded);
+        assert_eq!(registry.cancel_running(), 0);
+    }
+}
@@ -0,0 +1,101 @@
+use super::registry::CancelError;
+use crate::core::error::{ApiError, ErrorCode};
+use crate::features::users::audit::Actor;
+use crate::AppState;
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use nexus_types::{AuditAction, Job, ListJobsResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListJobsParams {
+    /// Only jobs acting on this VM.
+    pub vm_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct JobPathParams {
+    pub id: Uuid,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/jobs",
+    params(ListJobsParams),
+    responses(
+        (status = 200, description = "In-flight and recently finished jobs", body = ListJobsResponse),
+        (status = 401, description = "Not authenticated"),
+    ),
+    tag = "Jobs"
+)]
+pub async fn list(
+    Extension(st): Extension<AppState>,
+    Query(params): Query<ListJobsParams>,
+) -> Json<ListJobsResponse> {
+    Json(ListJobsResponse {
+        items: st.jobs.list(params.vm_id),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/jobs/{id}",
+    params(JobPathParams),
+    responses(
+        (status = 200, description = "Job status", body = Job),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "Unknown or expired job"),
+    ),
+    tag = "Jobs"
+)]
+pub async fn get(
+    Extension(st): Extension<AppState>,
+    Path(JobPathParams { id }): Path<JobPathParams>,
+) -> Result<Json<Job>, ApiError> {
+    st.jobs
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("job {id} not found")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/jobs/{id}/cancel",
+    params(JobPathParams),
+    responses(
+        (status = 200, description = "Cancel requested; poll the job until it is cancelled", body = Job),
+        (status = 401, description = "Not authenticated"),
+        (status = 404, description = "Unknown or expired job"),
+        (status = 409, description = "Job already finished"),
+    ),
+    tag = "Jobs"
+)]
+pub async fn cancel(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(JobPathParams { id }): Path<JobPathParams>,
+) -> Result<Json<Job>, ApiError> {
+    let result = match st.jobs.cancel(id) {
+        Ok(job) => {
+            tracing::info!(job_id = %id, vm_id = %job.vm_id, kind = ?job.kind, "job cancel requested");
+            Ok(job)
+        }
+        Err(CancelError::NotFound) => Err(ApiError::not_found(format!("job {id} not found"))),
+        Err(CancelError::AlreadyFinished(status)) => Err(ApiError::new(
+            ErrorCode::Conflict,
+            format!("job {id} already finished ({status:?})"),
+        )),
+    };
+    let outcome = result.as_ref().map_err(|err| err.message.clone());
+    let details = result
+        .as_ref()
+        .ok()
+        .map(|job| serde_json::json!({"vm_id": job.vm_id, "kind": job.kind}));
+    actor
+        .record_with(AuditAction::CancelJob, ("job", id), details, &outcome)
+        .await;
+    result.map(Json)
+}
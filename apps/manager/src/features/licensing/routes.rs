@@ -1,10 +1,10 @@
 use crate::features::licensing::license_service;
 use crate::features::licensing::service;
 use crate::features::users::audit;
-use crate::features::users::middleware::get_client_ip;
+use crate::features::users::client_ip::ClientIp;
 use crate::AppState;
 use axum::http::StatusCode;
-use axum::{http::HeaderMap, Extension, Json};
+use axum::{Extension, Json};
 use nexus_types::{
     AuditAction, EulaAcceptRequest, EulaAcceptResponse, EulaInfo, EulaStatus,
     LicenseActivateRequest, LicenseState, LicenseUploadRequest,
@@ -49,7 +49,7 @@ pub async fn get_license_status(
 
 pub async fn activate_license(
     Extension(state): Extension<AppState>,
-    headers: HeaderMap,
+    ClientIp(client_ip): ClientIp,
     Json(req): Json<LicenseActivateRequest>,
 ) -> Result<Json<LicenseState>, (StatusCode, String)> {
     let result = license_service::activate_license(
@@ -61,7 +61,7 @@ pub async fn activate_license(
     .await;
 
     // Audit log (pre-login setup, no authenticated user)
-    let client_ip = get_client_ip(&headers);
+    let client_ip = client_ip.map(|ip| ip.to_string());
     let details = serde_json::json!({
         "status": result.status,
         "is_licensed": result.is_licensed,
@@ -83,7 +83,7 @@ pub async fn activate_license(
 
 pub async fn activate_license_file(
     Extension(state): Extension<AppState>,
-    headers: HeaderMap,
+    ClientIp(client_ip): ClientIp,
     Json(req): Json<LicenseUploadRequest>,
 ) -> Result<Json<LicenseState>, (StatusCode, String)> {
     let result = license_service::activate_offline_license(
@@ -94,7 +94,7 @@ pub async fn activate_license_file(
     )
     .await;
 
-    let client_ip = get_client_ip(&headers);
+    let client_ip = client_ip.map(|ip| ip.to_string());
     let details = serde_json::json!({
         "status": result.status,
         "is_licensed": result.is_licensed,
@@ -107,18 +107,21 @@ pub async fn get_system_stats(
 ) -> Result<Json<SystemStatsResponse>, axum::http::StatusCode> {
     let db = &st.db;
 
-    let total_hosts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM host")
-        .fetch_one(db)
-        .await
-        .unwrap_or(0);
+    let total_hosts: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM host WHERE deregistered_at IS NULL")
+            .fetch_one(db)
+            .await
+            .unwrap_or(0);
     let total_vms: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vm")
         .fetch_one(db)
         .await
         .unwrap_or(0);
-    let running_vms: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vm WHERE state = 'running'")
-        .fetch_one(db)
-        .await
-        .unwrap_or(0);
+    let running_vms: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM vm WHERE state IN ('running', 'running-degraded')",
+    )
+    .fetch_one(db)
+    .await
+    .unwrap_or(0);
     let total_functions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM function")
         .fetch_one(db)
         .await
@@ -1,4 +1,5 @@
 use crate::features::metrics::repo;
+use crate::features::vms::repo::VmRow;
 use crate::AppState;
 use serde::Deserialize;
 use std::time::Duration;
@@ -10,16 +11,39 @@ use uuid::Uuid;
 const COLLECT_INTERVAL_SECS: u64 = 10;
 const HTTP_TIMEOUT_SECS: u64 = 2;
 const MAX_CONCURRENT: usize = 10;
+/// Retention and downsampling run every this many collection ticks (5 min).
+const MAINTENANCE_EVERY_TICKS: u64 = 30;
+/// Raw samples older than this are rolled up into per-minute averages.
+const DOWNSAMPLE_AFTER_HOURS: i32 = 24;
+const RETENTION_ENV: &str = "MANAGER_METRICS_RETENTION_DAYS";
+const DEFAULT_RETENTION_DAYS: i32 = 7;
+
+/// How many days of metrics to keep, from `MANAGER_METRICS_RETENTION_DAYS`.
+fn retention_days() -> i32 {
+    parse_retention_days(std::env::var(RETENTION_ENV).ok().as_deref())
+}
+
+fn parse_retention_days(raw: Option<&str>) -> i32 {
+    raw.and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
 
 pub fn spawn(state: AppState) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         let mut ticker = interval(Duration::from_secs(COLLECT_INTERVAL_SECS));
         ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let retention_days = retention_days();
+        let mut ticks: u64 = 0;
         loop {
             ticker.tick().await;
             if let Err(err) = collect_once(&state).await {
                 warn!(error = ?err, "metrics collector iteration failed");
             }
+            if ticks.is_multiple_of(MAINTENANCE_EVERY_TICKS) {
+                maintain(&state, retention_days).await;
+            }
+            ticks += 1;
         }
     })
 }
@@ -44,12 +68,17 @@ async fn collect_once(state: &AppState) -> anyhow::Result<()> {
         warn!(error = ?e, "container metrics collection failed");
     }
 
-    // Purge old data (cheap indexed delete)
-    if let Err(e) = repo::purge_old_metrics(&state.db).await {
+    Ok(())
+}
+
+/// Enforce retention, then downsample what is left.
+async fn maintain(state: &AppState, retention_days: i32) {
+    if let Err(e) = repo::purge_old_metrics(&state.db, retention_days).await {
         warn!(error = ?e, "metrics purge failed");
     }
-
-    Ok(())
+    if let Err(e) = repo::downsample_metrics(&state.db, DOWNSAMPLE_AFTER_HOURS).await {
+        warn!(error = ?e, "metrics downsampling failed");
+    }
 }
 
 // ── Host metrics ────────────────────────────────────────────────────
@@ -119,7 +148,10 @@ async fn collect_vm_metrics(
     sem: std::sync::Arc<Semaphore>,
 ) -> anyhow::Result<()> {
     let vms = crate::features::vms::repo::list(&state.db).await?;
-    let running: Vec<_> = vms.into_iter().filter(|vm| vm.state == "running").collect();
+    let running: Vec<_> = vms
+        .into_iter()
+        .filter(|vm| crate::features::vms::service::is_running_state(&vm.state))
+        .collect();
 
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
@@ -177,38 +209,28 @@ async fn collect_vm_metrics(
             continue;
         }
 
-        // Firecracker VMs: poll the in-guest agent at :9000.
-        let guest_ip = match vm.guest_ip.clone() {
-            Some(ip) => ip,
-            None => continue, // guest agent hasn't reported an IP yet
-        };
+        // Firecracker VMs: poll the in-guest agent, over vsock or the guest IP.
+        let state = state.clone();
         handles.push(tokio::spawn(async move {
             let _permit = sem.acquire().await;
-            let url = format!("http://{}:9000/metrics", guest_ip);
-
-            match client.get(&url).send().await {
-                Ok(resp) if resp.status().is_success() => match resp.json::<GuestMetrics>().await {
-                    Ok(m) => {
-                        if let Err(e) = repo::insert_vm_metric(
-                            &pool,
-                            vm.id,
-                            Some(m.cpu_usage_percent),
-                            Some(m.memory_usage_percent),
-                            Some(m.memory_used_kb as i64),
-                            Some(m.memory_total_kb as i64),
-                            m.load_average,
-                        )
-                        .await
-                        {
-                            warn!(vm_id = %vm.id, error = ?e, "failed to insert vm metric");
-                        }
-                    }
-                    Err(e) => {
-                        debug!(vm_id = %vm.id, error = ?e, "failed to parse guest metrics");
+            refresh_guest_ip(&state, &client, &vm).await;
+            match crate::features::vms::guest_vsock::guest_metrics::<GuestMetrics>(&client, &vm)
+                .await
+            {
+                Ok(m) => {
+                    if let Err(e) = repo::insert_vm_metric(
+                        &pool,
+                        vm.id,
+                        Some(m.cpu_usage_percent),
+                        Some(m.memory_usage_percent),
+                        Some(m.memory_used_kb as i64),
+                        Some(m.memory_total_kb as i64),
+                        m.load_average,
+                    )
+                    .await
+                    {
+                        warn!(vm_id = %vm.id, error = ?e, "failed to insert vm metric");
                     }
-                },
-                Ok(resp) => {
-                    debug!(vm_id = %vm.id, status = %resp.status(), "guest agent returned error");
                 }
                 Err(e) => {
                     debug!(vm_id = %vm.id, error = ?e, "failed to reach guest agent");
@@ -225,6 +247,35 @@ async fn collect_vm_metrics(
     Ok(())
 }
 
+/// Record the guest IP from the agent's vsock report when the stored one is
+/// missing or no longer on the guest. The agent also pushes it over the
+/// network, but a guest that can't reach the manager only shows up here.
+async fn refresh_guest_ip(state: &AppState, client: &reqwest::Client, vm: &VmRow) {
+    let report = match crate::features::vms::guest_vsock::guest_addresses(client, vm).await {
+        Ok(report) => report,
+        Err(e) => {
+            debug!(vm_id = %vm.id, error = ?e, "guest addresses unavailable over vsock");
+            return;
+        }
+    };
+    let addrs: Vec<&str> = report.guest_ips.iter().map(|a| a.ip.as_str()).collect();
+    let current = vm.guest_ip.as_deref().is_some_and(|ip| addrs.contains(&ip));
+    if addrs.is_empty() || current {
+        return;
+    }
+    match crate::features::vms::service::record_guest_ip(
+        state,
+        vm.id,
+        report.guest_ip.as_deref(),
+        &addrs,
+    )
+    .await
+    {
+        Ok(ip) => debug!(vm_id = %vm.id, guest_ip = ?ip, "guest IP updated over vsock"),
+        Err(e) => warn!(vm_id = %vm.id, error = ?e, "failed to record guest IP from vsock"),
+    }
+}
+
 // ── Container metrics ───────────────────────────────────────────────
 
 #[derive(sqlx::FromRow)]
@@ -447,3 +498,20 @@ fn extract_block_io(stats: &DockerStatsRaw) -> (i64, i64) {
     }
     (read, write)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retention_days_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_retention_days(None), DEFAULT_RETENTION_DAYS);
+        assert_eq!(parse_retention_days(Some("30")), 30);
+        assert_eq!(parse_retention_days(Some("0")), DEFAULT_RETENTION_DAYS);
+        assert_eq!(parse_retention_days(Some("-3")), DEFAULT_RETENTION_DAYS);
+        assert_eq!(
+            parse_retention_days(Some("forever")),
+            DEFAULT_RETENTION_DAYS
+        );
+    }
+}
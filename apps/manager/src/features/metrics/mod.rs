@@ -4,6 +4,10 @@ mod routes;
 
 use crate::AppState;
 use axum::Router;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+
+static RECORDER: OnceLock<PrometheusHandle> = OnceLock::new();
 
 pub fn router() -> Router {
     Router::new()
@@ -13,8 +17,56 @@ pub fn router() -> Router {
             "/containers/:id",
             axum::routing::get(routes::get_container_metrics),
         )
+        .route("/prometheus", axum::routing::get(routes::prometheus))
 }
 
 pub fn spawn_collector(state: AppState) -> tokio::task::JoinHandle<()> {
     collector::spawn(state)
 }
+
+fn recorder_builder() -> anyhow::Result<PrometheusBuilder> {
+    Ok(PrometheusBuilder::new().set_buckets_for_metric(
+        Matcher::Full(crate::features::reconciler::PASS_DURATION_METRIC.to_string()),
+        crate::features::reconciler::PASS_DURATION_BUCKETS,
+    )?)
+}
+
+/// Installs the global recorder behind the `metrics::` macros, so what they
+/// record is rendered on `/v1/metrics/prometheus`. Call once, at startup.
+pub fn install_recorder() -> anyhow::Result<()> {
+    let handle = recorder_builder()?.install_recorder()?;
+    let _ = RECORDER.set(handle);
+    Ok(())
+}
+
+/// Everything recorded through the `metrics::` macros, in the Prometheus
+/// text format; empty until [`install_recorder`] has run.
+pub(crate) fn render_recorded() -> String {
+    RECORDER
+        .get()
+        .map(PrometheusHandle::render)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrics::{Key, Recorder};
+
+    #[test]
+    fn reconcile_duration_renders_as_a_histogram() {
+        let recorder = recorder_builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        recorder
+            .register_histogram(&Key::from_static_name(
+                crate::features::reconciler::PASS_DURATION_METRIC,
+            ))
+            .record(0.3);
+        let rendered = handle.render();
+        assert!(
+            rendered.contains("manager_reconciler_pass_duration_seconds_bucket{le=\"0.5\"} 1"),
+            "{rendered}"
+        );
+        assert!(!rendered.contains("quantile"), "{rendered}");
+    }
+}
@@ -179,8 +179,19 @@ pub async fn query_container_metrics(
     .map(|rows| rows.into_iter().map(Into::into).collect())
 }
 
-pub async fn purge_old_metrics(pool: &PgPool) -> sqlx::Result<()> {
-    sqlx::query("SELECT metrics.purge_old_metrics()")
+/// Delete samples older than `retention_days`.
+pub async fn purge_old_metrics(pool: &PgPool, retention_days: i32) -> sqlx::Result<()> {
+    sqlx::query("SELECT metrics.purge_old_metrics(make_interval(days => $1))")
+        .bind(retention_days)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Roll raw samples older than `older_than_hours` up into per-minute rows.
+pub async fn downsample_metrics(pool: &PgPool, older_than_hours: i32) -> sqlx::Result<()> {
+    sqlx::query("SELECT metrics.downsample_metrics(make_interval(hours => $1))")
+        .bind(older_than_hours)
         .execute(pool)
         .await?;
     Ok(())
@@ -1,10 +1,14 @@
 use axum::extract::{Extension, Path, Query};
-use axum::http::StatusCode;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use nexus_types::{ContainerMetric, HostMetric, MetricsQueryParams, VmMetric};
 use uuid::Uuid;
 
+use crate::core::db::PoolStats;
 use crate::features::metrics::repo;
+use crate::features::users::repo::AuthenticatedUser;
+use crate::features::vms::ws_session::SessionSnapshot;
 use crate::AppState;
 
 const DEFAULT_LIMIT: i64 = 360; // 1 hour at 10s intervals
@@ -44,3 +48,178 @@ pub async fn get_container_metrics(
         .map(Json)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
+
+/// Bearer token a Prometheus scraper may send instead of a user session.
+pub const SCRAPE_TOKEN_ENV: &str = "MANAGER_METRICS_TOKEN";
+
+/// Manager-internal gauges, plus whatever the `metrics::` macros recorded,
+/// in Prometheus text exposition format. The gauges carry VM and user ids,
+/// so the caller must be signed in or send the scrape token.
+pub async fn prometheus(
+    Extension(state): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
+    headers: HeaderMap,
+) -> Response {
+    let scrape_token = std::env::var(SCRAPE_TOKEN_ENV).ok();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if user.is_none() && !is_scrape_token(scrape_token.as_deref(), bearer) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        render_ws_sessions(&state.ws_sessions.snapshot())
+            + &render_db_pool(&PoolStats::of(&state.db))
+            + &super::render_recorded(),
+    )
+        .into_response()
+}
+
+/// Whether `bearer` is the configured scrape token. An unset or blank token
+/// lets nobody in; the comparison doesn't stop at the first differing byte.
+fn is_scrape_token(configured: Option<&str>, bearer: Option<&str>) -> bool {
+    let (Some(configured), Some(bearer)) = (configured.map(str::trim), bearer.map(str::trim))
+    else {
+        return false;
+    };
+    if configured.is_empty() || configured.len() != bearer.len() {
+        return false;
+    }
+    configured
+        .bytes()
+        .zip(bearer.bytes())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+fn render_db_pool(stats: &PoolStats) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    for (name, help, value) in [
+        (
+            "nqrust_db_pool_connections",
+            "Open database connections.",
+            stats.size,
+        ),
+        (
+            "nqrust_db_pool_idle",
+            "Open database connections not in use.",
+            stats.idle,
+        ),
+        (
+            "nqrust_db_pool_in_use",
+            "Database connections held by queries; requests wait once this reaches the max.",
+            stats.in_use(),
+        ),
+        (
+            "nqrust_db_pool_max",
+            "Configured maximum database connections.",
+            stats.max,
+        ),
+        (
+            "nqrust_db_pool_min",
+            "Configured minimum database connections.",
+            stats.min,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "{name} {value}");
+    }
+    out
+}
+
+fn render_ws_sessions(snapshot: &SessionSnapshot) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = String::new();
+    out.push_str("# HELP nqrust_ws_sessions Open shell/metrics websocket sessions.\n");
+    out.push_str("# TYPE nqrust_ws_sessions gauge\n");
+    for (kind, count) in &snapshot.by_kind {
+        let _ = writeln!(
+            out,
+            "nqrust_ws_sessions{{kind=\"{}\"}} {count}",
+            kind.as_str()
+        );
+    }
+    out.push_str("# HELP nqrust_ws_sessions_by_vm Open websocket sessions per VM.\n");
+    out.push_str("# TYPE nqrust_ws_sessions_by_vm gauge\n");
+    for (vm_id, count) in &snapshot.by_vm {
+        let _ = writeln!(out, "nqrust_ws_sessions_by_vm{{vm_id=\"{vm_id}\"}} {count}");
+    }
+    out.push_str("# HELP nqrust_ws_sessions_by_user Open websocket sessions per user.\n");
+    out.push_str("# TYPE nqrust_ws_sessions_by_user gauge\n");
+    for (user_id, count) in &snapshot.by_user {
+        let _ = writeln!(
+            out,
+            "nqrust_ws_sessions_by_user{{user_id=\"{user_id}\"}} {count}"
+        );
+    }
+    out.push_str("# HELP nqrust_ws_session_limit Configured websocket session caps.\n");
+    out.push_str("# TYPE nqrust_ws_session_limit gauge\n");
+    let _ = writeln!(
+        out,
+        "nqrust_ws_session_limit{{scope=\"vm\"}} {}",
+        snapshot.caps.per_vm
+    );
+    let _ = writeln!(
+        out,
+        "nqrust_ws_session_limit{{scope=\"user\"}} {}",
+        snapshot.caps.per_user
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::vms::ws_session::{SessionCaps, SessionKind, WsSessionTracker};
+
+    #[test]
+    fn renders_ws_session_gauges() {
+        let tracker = WsSessionTracker::new(SessionCaps {
+            per_vm: 4,
+            per_user: 8,
+        });
+        let vm_id = Uuid::new_v4();
+        let _slot = tracker
+            .try_acquire(SessionKind::Shell, vm_id, None)
+            .unwrap();
+
+        let text = render_ws_sessions(&tracker.snapshot());
+        assert!(text.contains("nqrust_ws_sessions{kind=\"shell\"} 1\n"));
+        assert!(text.contains("nqrust_ws_sessions{kind=\"metrics\"} 0\n"));
+        assert!(text.contains(&format!(
+            "nqrust_ws_sessions_by_vm{{vm_id=\"{vm_id}\"}} 1\n"
+        )));
+        assert!(text.contains("nqrust_ws_session_limit{scope=\"user\"} 8\n"));
+    }
+
+    #[test]
+    fn only_the_configured_scrape_token_is_accepted() {
+        assert!(is_scrape_token(Some("s3cret"), Some("s3cret")));
+        assert!(!is_scrape_token(Some("s3cret"), Some("s3cres")));
+        assert!(!is_scrape_token(Some("s3cret"), Some("s3cret-longer")));
+        assert!(!is_scrape_token(Some("s3cret"), None));
+        assert!(!is_scrape_token(None, Some("s3cret")));
+        assert!(!is_scrape_token(Some(" "), Some("")));
+    }
+
+    #[test]
+    fn renders_db_pool_gauges() {
+        let text = render_db_pool(&PoolStats {
+            size: 7,
+            idle: 2,
+            max: 20,
+            min: 1,
+        });
+        assert!(text.contains("# TYPE nqrust_db_pool_in_use gauge\nnqrust_db_pool_in_use 5\n"));
+        assert!(text.contains("nqrust_db_pool_max 20\n"));
+    }
+}
@@ -1,18 +1,21 @@
 use crate::AppState;
-use axum::{Extension, Json, Router};
-use serde::Serialize;
+use axum::{Extension, Router};
 
 pub mod backup_targets;
 pub mod backups;
 pub mod containers;
 pub mod functions;
+pub mod health;
 pub mod hosts;
 pub mod images;
+pub mod jobs;
 pub mod licensing;
 pub mod logs; // A3 starter
 pub mod metrics;
 pub mod networks;
+pub mod overview;
 pub mod reconciler;
+pub mod registries;
 pub mod snapshots;
 pub mod sso;
 pub mod storage;
@@ -22,22 +25,9 @@ pub mod users;
 pub mod vms; // A2 core
 pub mod volumes;
 
-#[derive(Serialize)]
-pub struct HealthResponse {
-    pub status: String,
-    pub version: String,
-}
-
-async fn health_check() -> Json<HealthResponse> {
-    Json(HealthResponse {
-        status: "ok".to_string(),
-        version: env!("CARGO_PKG_VERSION").to_string(),
-    })
-}
-
 pub fn router(state: AppState) -> Router {
     Router::new()
-        .route("/health", axum::routing::get(health_check))
+        .merge(health::router())
         .nest(
             "/v1/auth",
             users::auth_router().route_layer(axum::middleware::from_fn_with_state(
@@ -57,7 +47,43 @@ pub fn router(state: AppState) -> Router {
         )
         .nest("/v1/hosts", hosts::router())
         .nest("/v1/images", images::router())
+        .route(
+            "/v1/images/quota",
+            axum::routing::get(images::routes::get_quota)
+                .merge(
+                    axum::routing::put(images::routes::set_quota)
+                        .layer(axum::middleware::from_fn(users::middleware::require_admin)),
+                )
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    users::middleware::auth_middleware,
+                )),
+        )
+        // Downloads whatever the manifest points at onto the manager
+        .route(
+            "/v1/images/preload/manifest",
+            axum::routing::post(images::routes::preload_manifest)
+                .layer(axum::middleware::from_fn(users::middleware::require_admin))
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    users::middleware::auth_middleware,
+                )),
+        )
         .nest("/v1/networks", networks::router())
+        .nest(
+            "/v1/overview",
+            overview::router().layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                users::middleware::auth_middleware,
+            )),
+        )
+        .nest(
+            "/v1/registries",
+            registries::router().layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                users::middleware::auth_middleware,
+            )),
+        )
         .nest("/v1/templates", templates::router())
         .nest(
             "/v1/vms",
@@ -66,6 +92,13 @@ pub fn router(state: AppState) -> Router {
                 users::middleware::optional_auth_middleware,
             )),
         )
+        .nest(
+            "/v1/jobs",
+            jobs::router().layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                users::middleware::auth_middleware,
+            )),
+        )
         .nest("/v1/snapshots", snapshots::router())
         .route(
             "/v1/vms/:id/snapshots",
@@ -104,5 +137,13 @@ pub fn router(state: AppState) -> Router {
                     users::middleware::auth_middleware,
                 )),
         )
+        .layer(axum::middleware::from_fn(
+            users::middleware::viewer_read_only,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            users::middleware::optional_auth_middleware,
+        ))
+        .layer(axum::middleware::from_fn(users::client_ip::middleware))
         .layer(Extension(state))
 }
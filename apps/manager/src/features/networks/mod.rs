@@ -20,4 +20,12 @@ pub fn router() -> Router {
         )
         .route("/:id/vms", get(routes::get_vms))
         .route("/:id/retry", post(routes::retry))
+        .route(
+            "/:id/rules",
+            get(routes::list_rules).post(routes::create_rule),
+        )
+        .route(
+            "/:id/rules/:rule_id",
+            axum::routing::delete(routes::delete_rule),
+        )
 }
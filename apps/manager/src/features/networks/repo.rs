@@ -157,6 +157,22 @@ impl NetworkRepository {
         .await
     }
 
+    /// List active, managed VXLAN networks (for peer convergence).
+    pub async fn list_active_vxlan_networks(&self) -> sqlx::Result<Vec<NetworkRow>> {
+        sqlx::query_as::<_, NetworkRow>(
+            r#"
+            SELECT * FROM network
+            WHERE type = 'vxlan'
+              AND managed = true
+              AND status = 'active'
+              AND vni IS NOT NULL
+            ORDER BY created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
     /// List active, managed networks bound to a specific host (for reconciliation).
     /// Excludes VXLAN networks which use the network_host junction table.
     pub async fn list_active_managed_for_host(
@@ -329,6 +345,8 @@ impl NetworkRepository {
 
     // --- network_host junction table methods ---
 
+    /// Record a host as a member of a network, in 'provisioning'. A host that
+    /// already has a (e.g. failed) membership is reset to 'provisioning'.
     pub async fn add_network_host(
         &self,
         network_id: Uuid,
@@ -340,6 +358,8 @@ impl NetworkRepository {
             r#"
             INSERT INTO network_host (network_id, host_id, vtep_ip, is_gateway, status)
             VALUES ($1, $2, $3, $4, 'provisioning')
+            ON CONFLICT (network_id, host_id) DO UPDATE
+              SET vtep_ip = EXCLUDED.vtep_ip, status = 'provisioning', error_message = NULL
             RETURNING *
             "#,
         )
@@ -389,6 +409,27 @@ impl NetworkRepository {
         Ok(())
     }
 
+    /// Refresh the VXLAN local IP recorded for every membership of a host,
+    /// e.g. after the host re-registers with a new address. Returns the
+    /// `(network_id, old vtep_ip)` of each membership that changed.
+    pub async fn update_vtep_ip_for_host(
+        &self,
+        host_id: Uuid,
+        vtep_ip: &str,
+    ) -> sqlx::Result<Vec<(Uuid, String)>> {
+        // The FROM side of the self-join still sees the pre-update row
+        sqlx::query_as(
+            r#"UPDATE network_host nh SET vtep_ip = $2
+            FROM network_host old
+            WHERE old.id = nh.id AND nh.host_id = $1 AND nh.vtep_ip <> $2
+            RETURNING nh.network_id, old.vtep_ip"#,
+        )
+        .bind(host_id)
+        .bind(vtep_ip)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn delete_network_hosts(&self, network_id: Uuid) -> sqlx::Result<()> {
         sqlx::query(r#"DELETE FROM network_host WHERE network_id = $1"#)
             .bind(network_id)
@@ -405,6 +446,85 @@ impl NetworkRepository {
                 .await?;
         Ok(result.0)
     }
+
+    // --- network_rule (security group) methods ---
+
+    /// List firewall rules for a network in evaluation order (oldest first).
+    pub async fn list_rules(&self, network_id: Uuid) -> sqlx::Result<Vec<NetworkRuleRow>> {
+        sqlx::query_as::<_, NetworkRuleRow>(
+            r#"SELECT * FROM network_rule WHERE network_id = $1 ORDER BY created_at, id"#,
+        )
+        .bind(network_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_rule(
+        &self,
+        network_id: Uuid,
+        direction: &str,
+        protocol: &str,
+        port_start: Option<i32>,
+        port_end: Option<i32>,
+        cidr: Option<&str>,
+        action: &str,
+    ) -> sqlx::Result<NetworkRuleRow> {
+        sqlx::query_as::<_, NetworkRuleRow>(
+            r#"
+            INSERT INTO network_rule (network_id, direction, protocol, port_start, port_end, cidr, action)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(network_id)
+        .bind(direction)
+        .bind(protocol)
+        .bind(port_start)
+        .bind(port_end)
+        .bind(cidr)
+        .bind(action)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Delete a rule, scoped to its network so a mismatched path is a 404.
+    /// Returns the deleted row so it can be put back.
+    pub async fn delete_rule(
+        &self,
+        network_id: Uuid,
+        rule_id: Uuid,
+    ) -> sqlx::Result<NetworkRuleRow> {
+        sqlx::query_as::<_, NetworkRuleRow>(
+            r#"DELETE FROM network_rule WHERE id = $1 AND network_id = $2 RETURNING *"#,
+        )
+        .bind(rule_id)
+        .bind(network_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Re-insert a deleted rule with its original id and position.
+    pub async fn restore_rule(&self, rule: &NetworkRuleRow) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO network_rule (id, network_id, direction, protocol, port_start, port_end, cidr, action, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(rule.id)
+        .bind(rule.network_id)
+        .bind(&rule.direction)
+        .bind(&rule.protocol)
+        .bind(rule.port_start)
+        .bind(rule.port_end)
+        .bind(rule.cidr.as_deref())
+        .bind(&rule.action)
+        .bind(rule.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, sqlx::FromRow)]
@@ -444,3 +564,16 @@ pub struct NetworkHostRow {
     pub error_message: Option<String>,
     pub created_at: DateTime<chrono::Utc>,
 }
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct NetworkRuleRow {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub direction: String,
+    pub protocol: String,
+    pub port_start: Option<i32>,
+    pub port_end: Option<i32>,
+    pub cidr: Option<String>,
+    pub action: String,
+    pub created_at: DateTime<chrono::Utc>,
+}
@@ -1,8 +1,11 @@
+use crate::core::error::ApiError;
 use crate::features::networks::repo::NetworkRepository;
 use crate::features::networks::service;
+use crate::features::users::audit::Actor;
 use crate::AppState;
 use axum::extract::Query;
 use axum::{extract::Path, http::StatusCode, Extension, Json};
+use nexus_types::AuditAction;
 use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
@@ -75,6 +78,13 @@ pub struct OkResponse {
     pub message: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteNetworkQuery {
+    /// Delete the record even if the host agent can't tear the network down
+    #[serde(default)]
+    pub force: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SuggestQuery {
     pub host_id: Uuid,
@@ -118,12 +128,14 @@ fn network_to_list_item(
     responses(
         (status = 201, description = "Network created", body = NetworkDetailResponse),
         (status = 400, description = "Invalid request"),
+        (status = 502, description = "Host agent failed to provision the network; the record is left in 'error'"),
         (status = 500, description = "Failed to create network"),
     ),
     tag = "Networks"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateNetworkRequest>,
 ) -> Result<(StatusCode, Json<NetworkDetailResponse>), (StatusCode, Json<OkResponse>)> {
     let params = service::CreateNetworkParams {
@@ -140,7 +152,18 @@ pub async fn create(
         gateway_host_id: req.gateway_host_id,
     };
 
-    match service::create_network(&st, params).await {
+    let details = serde_json::json!({"name": &params.name, "type": &params.network_type});
+    let result = service::create_network(&st, params).await;
+    let id = result.as_ref().ok().map(|network| network.id);
+    actor
+        .record_with(
+            AuditAction::CreateNetwork,
+            ("network", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    match result {
         Ok(network) => {
             let host_name = if let Some(hid) = network.host_id {
                 st.hosts.get(hid).await.ok().map(|h| h.name)
@@ -163,6 +186,8 @@ pub async fn create(
             let msg = e.to_string();
             let status = if msg.contains("must be") || msg.contains("required") {
                 StatusCode::BAD_REQUEST
+            } else if msg.contains("provisioning failed") {
+                StatusCode::BAD_GATEWAY
             } else {
                 StatusCode::INTERNAL_SERVER_ERROR
             };
@@ -271,11 +296,12 @@ pub async fn get(
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateNetworkRequest>,
 ) -> Result<Json<NetworkDetailResponse>, StatusCode> {
     let network_repo = NetworkRepository::new(st.db.clone());
-    let network = network_repo
+    let result = network_repo
         .update(
             id,
             req.name.as_deref(),
@@ -283,14 +309,17 @@ pub async fn update(
             req.cidr.as_deref(),
             req.gateway.as_deref(),
         )
-        .await
-        .map_err(|err| match err {
-            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-            other => {
-                error!(error = ?other, "failed to update network");
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+        .await;
+    actor
+        .record(AuditAction::UpdateNetwork, ("network", id), &result)
+        .await;
+    let network = result.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        other => {
+            error!(error = ?other, "failed to update network");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
 
     let vm_count = network_repo.get_vm_count(id).await.unwrap_or(0);
     let host_name = if let Some(host_id) = network.host_id {
@@ -316,15 +345,27 @@ pub async fn update(
         (status = 200, description = "Network deleted", body = OkResponse),
         (status = 404, description = "Network not found"),
         (status = 409, description = "Network has attached VMs"),
+        (status = 502, description = "Host agent failed to tear down the network; the record is left in 'error' (retry with force=true to delete it anyway)"),
         (status = 500, description = "Failed to delete network"),
     ),
     tag = "Networks"
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
+    Query(DeleteNetworkQuery { force }): Query<DeleteNetworkQuery>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<OkResponse>)> {
-    match service::delete_network(&st, id).await {
+    let result = service::delete_network(&st, id, force).await;
+    actor
+        .record_with(
+            AuditAction::DeleteNetwork,
+            ("network", id),
+            Some(serde_json::json!({"force": force})),
+            &result,
+        )
+        .await;
+    match result {
         Ok(()) => Ok(Json(OkResponse {
             message: "Network deleted successfully".to_string(),
         })),
@@ -334,6 +375,8 @@ pub async fn delete(
                 StatusCode::CONFLICT
             } else if msg.contains("not found") {
                 StatusCode::NOT_FOUND
+            } else if msg.contains("teardown failed") {
+                StatusCode::BAD_GATEWAY
             } else {
                 StatusCode::INTERNAL_SERVER_ERROR
             };
@@ -437,6 +480,115 @@ pub async fn get_vms(
     Ok(Json(serde_json::json!({ "vm_ids": vm_ids })))
 }
 
+#[derive(Debug, Serialize)]
+pub struct NetworkRuleListResponse {
+    pub items: Vec<crate::features::networks::repo::NetworkRuleRow>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/networks/{id}/rules",
+    responses(
+        (status = 200, description = "Firewall rules for the network", body = NetworkRuleListResponse),
+        (status = 404, description = "Network not found"),
+        (status = 500, description = "Failed to list rules"),
+    ),
+    tag = "Networks"
+)]
+pub async fn list_rules(
+    Extension(st): Extension<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<NetworkRuleListResponse>, StatusCode> {
+    let network_repo = NetworkRepository::new(st.db.clone());
+
+    let _ = network_repo.get(id).await.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        other => {
+            error!(error = ?other, "failed to get network");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
+    let items = network_repo.list_rules(id).await.map_err(|err| {
+        error!(error = ?err, "failed to list network rules");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(NetworkRuleListResponse { items }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/networks/{id}/rules",
+    request_body = service::CreateNetworkRuleParams,
+    responses(
+        (status = 201, description = "Rule created and pushed to hosts"),
+        (status = 400, description = "Invalid rule"),
+        (status = 404, description = "Network not found"),
+        (status = 502, description = "A host refused the new ruleset; the rule was not added"),
+        (status = 500, description = "Failed to apply rule"),
+    ),
+    tag = "Networks"
+)]
+pub async fn create_rule(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(id): Path<Uuid>,
+    Json(req): Json<service::CreateNetworkRuleParams>,
+) -> Result<
+    (
+        StatusCode,
+        Json<crate::features::networks::repo::NetworkRuleRow>,
+    ),
+    ApiError,
+> {
+    let result = service::create_rule(&st, id, req).await;
+    let details = result
+        .as_ref()
+        .ok()
+        .map(|rule| serde_json::json!({"rule_id": rule.id}));
+    actor
+        .record_with(
+            AuditAction::CreateNetworkRule,
+            ("network", id),
+            details,
+            &result,
+        )
+        .await;
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/networks/{id}/rules/{rule_id}",
+    responses(
+        (status = 200, description = "Rule deleted", body = OkResponse),
+        (status = 404, description = "Rule not found"),
+        (status = 502, description = "A host refused the remaining ruleset; the rule was kept"),
+        (status = 500, description = "Failed to apply remaining rules"),
+    ),
+    tag = "Networks"
+)]
+pub async fn delete_rule(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path((id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = service::delete_rule(&st, id, rule_id).await;
+    actor
+        .record_with(
+            AuditAction::DeleteNetworkRule,
+            ("network", id),
+            Some(serde_json::json!({"rule_id": rule_id})),
+            &result,
+        )
+        .await;
+    result?;
+    Ok(Json(OkResponse {
+        message: "Rule deleted successfully".to_string(),
+    }))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct InterfacesQuery {
     pub host_id: Uuid,
@@ -1,7 +1,13 @@
-use crate::features::networks::repo::{NetworkRepository, NetworkRow};
+use crate::core::error::{bad_gateway, bad_request, not_found};
+use crate::features::networks::repo::{
+    NetworkHostRow, NetworkRepository, NetworkRow, NetworkRuleRow,
+};
 use crate::AppState;
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
@@ -145,7 +151,7 @@ pub async fn create_network(st: &AppState, params: CreateNetworkParams) -> Resul
         provision_body["uplink_interface"] = serde_json::json!(uplink);
     }
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let provision_result = client.post(&agent_url).json(&provision_body).send().await;
 
     match provision_result {
@@ -179,7 +185,10 @@ pub async fn create_network(st: &AppState, params: CreateNetworkParams) -> Resul
 }
 
 /// Delete a network, tearing down infrastructure if managed.
-pub async fn delete_network(st: &AppState, id: Uuid) -> Result<()> {
+/// Delete a network, tearing it down on its host first. With `force`, a
+/// failed teardown (e.g. the host is unreachable) is logged and the record
+/// deleted anyway; the bridge may be left behind on the host.
+pub async fn delete_network(st: &AppState, id: Uuid, force: bool) -> Result<()> {
     let network_repo = NetworkRepository::new(st.db.clone());
 
     let network = network_repo.get(id).await.context("network not found")?;
@@ -198,39 +207,32 @@ pub async fn delete_network(st: &AppState, id: Uuid) -> Result<()> {
         return delete_vxlan_network(st, &network).await;
     }
 
-    // If managed, call agent to teardown
+    // If managed, call agent to teardown. A failed teardown leaves the record
+    // in 'error' so the bridge isn't forgotten while it still exists.
     if network.managed {
         if let Some(host_id) = network.host_id {
             let _ = network_repo.update_status(id, "deleting", None).await;
 
-            if let std::result::Result::Ok(host) = st.hosts.get(host_id).await {
-                let agent_url = format!(
-                    "{}/agent/v1/networks/teardown",
-                    host.addr.trim_end_matches('/')
-                );
-
-                let client = reqwest::Client::new();
-                let result = client
-                    .post(&agent_url)
-                    .json(&serde_json::json!({
-                        "network_type": network.type_,
-                        "bridge_name": network.bridge_name,
-                        "cidr": network.cidr.unwrap_or_default(),
-                    }))
-                    .send()
-                    .await;
-
-                match result {
-                    std::result::Result::Ok(resp) if resp.status().is_success() => {
-                        info!(network_id = %id, "network teardown successful");
-                    }
-                    std::result::Result::Ok(resp) => {
-                        let body = resp.text().await.unwrap_or_default();
-                        warn!(network_id = %id, error = %body, "agent teardown returned error, deleting record anyway");
-                    }
-                    Err(e) => {
-                        warn!(network_id = %id, error = %e, "failed to reach agent for teardown, deleting record anyway");
+            match st.hosts.get(host_id).await {
+                std::result::Result::Ok(host) => {
+                    if let Err(err_msg) = teardown_on_host(&host.addr, &network).await {
+                        if force {
+                            warn!(network_id = %id, host_id = %host_id, error = %err_msg, "network teardown failed, deleting record anyway (force)");
+                            return network_repo
+                                .delete(id)
+                                .await
+                                .context("failed to delete network record");
+                        }
+                        error!(network_id = %id, error = %err_msg, "network teardown failed");
+                        let _ = network_repo
+                            .update_status(id, "error", Some(&err_msg))
+                            .await;
+                        return Err(anyhow!("teardown failed: {}", err_msg));
                     }
+                    info!(network_id = %id, "network teardown successful");
+                }
+                Err(e) => {
+                    warn!(network_id = %id, host_id = %host_id, error = %e, "network host is gone, deleting record without teardown");
                 }
             }
         }
@@ -243,6 +245,32 @@ pub async fn delete_network(st: &AppState, id: Uuid) -> Result<()> {
     Ok(())
 }
 
+/// Ask the agent at `addr` to tear down a network's bridge.
+async fn teardown_on_host(addr: &str, network: &NetworkRow) -> std::result::Result<(), String> {
+    let agent_url = format!("{}/agent/v1/networks/teardown", addr.trim_end_matches('/'));
+
+    let client = crate::core::agent_http::client();
+    let result = client
+        .post(&agent_url)
+        .json(&serde_json::json!({
+            "network_type": network.type_,
+            "bridge_name": network.bridge_name,
+            "cidr": network.cidr.clone().unwrap_or_default(),
+        }))
+        .send()
+        .await;
+
+    match result {
+        std::result::Result::Ok(resp) if resp.status().is_success() => Ok(()),
+        std::result::Result::Ok(resp) => {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            Err(format!("agent returned {}: {}", status, body))
+        }
+        Err(e) => Err(format!("failed to reach agent: {}", e)),
+    }
+}
+
 /// Retry provisioning for a network in error state.
 pub async fn retry_network(st: &AppState, id: Uuid) -> Result<NetworkRow> {
     let network_repo = NetworkRepository::new(st.db.clone());
@@ -259,6 +287,7 @@ pub async fn retry_network(st: &AppState, id: Uuid) -> Result<NetworkRow> {
         .host_id
         .ok_or_else(|| anyhow!("network has no host"))?;
     let host = st.hosts.get(host_id).await.context("host not found")?;
+    let rules = provision_rules(&network_repo, id).await?;
 
     let _ = network_repo.update_status(id, "provisioning", None).await;
 
@@ -279,8 +308,9 @@ pub async fn retry_network(st: &AppState, id: Uuid) -> Result<NetworkRow> {
     if let Some(ref uplink) = network.uplink_interface {
         provision_body["uplink_interface"] = serde_json::json!(uplink);
     }
+    provision_body["rules"] = rules;
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let result = client.post(&agent_url).json(&provision_body).send().await;
 
     match result {
@@ -369,7 +399,7 @@ pub async fn list_host_interfaces(st: &AppState, host_id: Uuid) -> Result<Vec<se
         host.addr.trim_end_matches('/')
     );
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let resp = client
         .get(&agent_url)
         .send()
@@ -387,6 +417,240 @@ pub async fn list_host_interfaces(st: &AppState, host_id: Uuid) -> Result<Vec<se
     Ok(interfaces)
 }
 
+// ========== Firewall (security group) rules ==========
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNetworkRuleParams {
+    /// "ingress" (towards VMs) or "egress" (from VMs)
+    pub direction: String,
+    /// "tcp", "udp", "icmp", or "all"
+    pub protocol: String,
+    pub port_start: Option<u16>,
+    pub port_end: Option<u16>,
+    /// Remote CIDR the rule matches; omitted means any address
+    pub cidr: Option<String>,
+    /// "accept" or "drop"
+    pub action: String,
+}
+
+fn validate_rule(params: &CreateNetworkRuleParams) -> Result<()> {
+    if !matches!(params.direction.as_str(), "ingress" | "egress") {
+        bail!(bad_request("direction must be 'ingress' or 'egress'"));
+    }
+    if !matches!(params.protocol.as_str(), "tcp" | "udp" | "icmp" | "all") {
+        bail!(bad_request(
+            "protocol must be 'tcp', 'udp', 'icmp', or 'all'"
+        ));
+    }
+    if !matches!(params.action.as_str(), "accept" | "drop") {
+        bail!(bad_request("action must be 'accept' or 'drop'"));
+    }
+
+    match (params.port_start, params.port_end) {
+        (None, Some(_)) => bail!(bad_request("port_end requires port_start")),
+        (Some(_), _) if !matches!(params.protocol.as_str(), "tcp" | "udp") => {
+            bail!(bad_request("ports must only be set for tcp or udp rules"));
+        }
+        (Some(0), _) | (_, Some(0)) => bail!(bad_request("ports must be between 1 and 65535")),
+        (Some(start), Some(end)) if end < start => {
+            bail!(bad_request("port_end must be >= port_start"));
+        }
+        _ => {}
+    }
+
+    if let Some(cidr) = &params.cidr {
+        let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr.as_str(), "32"));
+        let valid = addr.parse::<std::net::Ipv4Addr>().is_ok()
+            && prefix.parse::<u8>().is_ok_and(|p| p <= 32);
+        if !valid {
+            bail!(bad_request(
+                "cidr must be an IPv4 address or CIDR, e.g. 10.0.0.0/8"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Serialize stored rules into the shape the agent's firewall API expects.
+fn rules_payload(rules: &[NetworkRuleRow]) -> serde_json::Value {
+    serde_json::Value::Array(
+        rules
+            .iter()
+            .map(|r| {
+                serde_json::json!({
+                    "direction": r.direction,
+                    "protocol": r.protocol,
+                    "port_start": r.port_start,
+                    "port_end": r.port_end,
+                    "cidr": r.cidr,
+                    "action": r.action,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Current ruleset for a network, for inclusion in a provision request.
+/// The agent replaces its chain with whatever it is sent, so a failed lookup
+/// is an error rather than an empty ruleset.
+pub async fn provision_rules(
+    network_repo: &NetworkRepository,
+    network_id: Uuid,
+) -> Result<serde_json::Value> {
+    let rules = network_repo
+        .list_rules(network_id)
+        .await
+        .context("failed to load network rules")?;
+    Ok(rules_payload(&rules))
+}
+
+/// Add a firewall rule to a network and push the new ruleset to its hosts.
+/// If any host refuses it the rule is removed again and the old ruleset
+/// re-pushed, so the stored rules never claim more than the hosts enforce.
+pub async fn create_rule(
+    st: &AppState,
+    network_id: Uuid,
+    params: CreateNetworkRuleParams,
+) -> Result<NetworkRuleRow> {
+    validate_rule(&params)?;
+
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let network = get_network_for_rules(&network_repo, network_id).await?;
+
+    let rule = network_repo
+        .create_rule(
+            network_id,
+            &params.direction,
+            &params.protocol,
+            params.port_start.map(i32::from),
+            params.port_end.map(i32::from),
+            params.cidr.as_deref(),
+            &params.action,
+        )
+        .await
+        .context("failed to insert network rule")?;
+
+    if let Err(e) = push_rules(st, &network).await {
+        network_repo
+            .delete_rule(network_id, rule.id)
+            .await
+            .context("failed to roll back network rule")?;
+        restore_rules(st, &network).await;
+        return Err(e.context("rule not added"));
+    }
+    Ok(rule)
+}
+
+/// Remove a firewall rule and push the remaining ruleset to the network's
+/// hosts. If any host refuses the change the rule is put back.
+pub async fn delete_rule(st: &AppState, network_id: Uuid, rule_id: Uuid) -> Result<()> {
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let network = get_network_for_rules(&network_repo, network_id).await?;
+
+    let rule = match network_repo.delete_rule(network_id, rule_id).await {
+        Ok(rule) => rule,
+        Err(sqlx::Error::RowNotFound) => bail!(not_found("Rule not found")),
+        Err(e) => return Err(anyhow::Error::from(e).context("failed to delete network rule")),
+    };
+
+    if let Err(e) = push_rules(st, &network).await {
+        network_repo
+            .restore_rule(&rule)
+            .await
+            .context("failed to roll back network rule")?;
+        restore_rules(st, &network).await;
+        return Err(e.context("rule not deleted"));
+    }
+    Ok(())
+}
+
+async fn get_network_for_rules(
+    network_repo: &NetworkRepository,
+    network_id: Uuid,
+) -> Result<NetworkRow> {
+    match network_repo.get(network_id).await {
+        Ok(network) => Ok(network),
+        Err(sqlx::Error::RowNotFound) => bail!(not_found("Network not found")),
+        Err(e) => Err(anyhow::Error::from(e).context("failed to load network")),
+    }
+}
+
+/// Re-push the stored ruleset after a rollback, so hosts that took the
+/// refused change go back to what the database says.
+async fn restore_rules(st: &AppState, network: &NetworkRow) {
+    if let Err(e) = push_rules(st, network).await {
+        warn!(network_id = %network.id, error = ?e, "failed to restore firewall rules after rollback");
+    }
+}
+
+/// Send the network's full ruleset to every host the network lives on. The
+/// agent replaces its chain wholesale, so this is safe to call repeatedly.
+pub async fn push_rules(st: &AppState, network: &NetworkRow) -> Result<()> {
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let rules = provision_rules(&network_repo, network.id).await?;
+
+    let host_ids: Vec<Uuid> = if network.type_ == "vxlan" {
+        network_repo
+            .list_network_hosts(network.id)
+            .await
+            .context("failed to list network hosts")?
+            .into_iter()
+            .map(|nh| nh.host_id)
+            .collect()
+    } else {
+        network.host_id.into_iter().collect()
+    };
+
+    let client = crate::core::agent_http::client();
+    let mut failures = Vec::new();
+    for host_id in host_ids {
+        let host = match st.hosts.get(host_id).await {
+            std::result::Result::Ok(h) => h,
+            Err(e) => {
+                failures.push(format!("{host_id}: {e}"));
+                continue;
+            }
+        };
+        let agent_url = format!(
+            "{}/agent/v1/networks/rules",
+            host.addr.trim_end_matches('/')
+        );
+        let result = client
+            .post(&agent_url)
+            .json(&serde_json::json!({
+                "network_type": network.type_,
+                "bridge_name": network.bridge_name,
+                "rules": rules,
+            }))
+            .send()
+            .await;
+
+        match result {
+            std::result::Result::Ok(resp) if resp.status().is_success() => {
+                info!(network_id = %network.id, host_id = %host_id, "firewall rules applied");
+            }
+            std::result::Result::Ok(resp) => {
+                let body = resp.text().await.unwrap_or_default();
+                warn!(network_id = %network.id, host_id = %host_id, error = %body, "agent rejected firewall rules");
+                failures.push(format!("{}: {}", host.name, body));
+            }
+            Err(e) => {
+                warn!(network_id = %network.id, host_id = %host_id, error = %e, "failed to reach agent for firewall rules");
+                failures.push(format!("{}: {}", host.name, e));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        bail!(bad_gateway(format!(
+            "failed to apply firewall rules: {}",
+            failures.join("; ")
+        )));
+    }
+    Ok(())
+}
+
 // ========== VXLAN overlay network functions ==========
 
 /// Create a VXLAN overlay network: provision on the gateway host, set up DHCP + NAT.
@@ -483,7 +747,7 @@ async fn create_vxlan_network(st: &AppState, params: CreateNetworkParams) -> Res
         "dhcp_range_end": if dhcp_on { Some(&dhcp_end) } else { None },
     });
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let result = client.post(&agent_url).json(&provision_body).send().await;
 
     match result {
@@ -537,7 +801,7 @@ async fn delete_vxlan_network(st: &AppState, network: &NetworkRow) -> Result<()>
         .await
         .unwrap_or_default();
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let vni = network.vni.unwrap_or(0);
 
     for nh in &network_hosts {
@@ -586,9 +850,129 @@ async fn delete_vxlan_network(st: &AppState, network: &NetworkRow) -> Result<()>
     Ok(())
 }
 
-/// Expand a VXLAN network to a new host. Called during VM creation when the VM's
-/// host doesn't yet participate in the overlay.
-pub async fn expand_vxlan_to_host(
+/// What it takes for a VM on a given host to use a network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostMembership {
+    /// The network is already provisioned on the host.
+    Ready,
+    /// Provision the network on the host (again, if an earlier attempt failed)
+    /// and record the membership.
+    Provision,
+    /// The network can't be used from the host.
+    Unavailable(String),
+}
+
+/// Decide whether `host_id` can use `network`, given the host's membership
+/// row if it has one. The owning host always can; VXLAN and bridged networks
+/// span to other hosts on demand; NAT and isolated networks are local to
+/// their host (their own subnet and DHCP server). Unmanaged networks are
+/// whatever bridge the host already has, so they're left alone.
+pub fn plan_host_membership(
+    network: &NetworkRow,
+    member: Option<&NetworkHostRow>,
+    host_id: Uuid,
+) -> HostMembership {
+    if network.status != "active" {
+        return HostMembership::Unavailable(format!(
+            "invalid network: {} is {}, not active",
+            network.name, network.status
+        ));
+    }
+    if !network.managed || network.host_id.is_none() || network.host_id == Some(host_id) {
+        return HostMembership::Ready;
+    }
+    match network.type_.as_str() {
+        "vxlan" | "bridged" => match member {
+            Some(m) if m.status == "active" => HostMembership::Ready,
+            _ => HostMembership::Provision,
+        },
+        other => HostMembership::Unavailable(format!(
+            "invalid network: {} is a {} network, which only exists on its own host",
+            network.name, other
+        )),
+    }
+}
+
+/// Make sure `network` is provisioned on `host_id` before a VM there is
+/// attached to it, provisioning it on demand. Fails when the network can't
+/// span to the host or provisioning there fails.
+pub async fn ensure_network_on_host(
+    st: &AppState,
+    network: &NetworkRow,
+    host_id: Uuid,
+) -> Result<()> {
+    match host_membership(st, network, host_id).await? {
+        HostMembership::Ready => Ok(()),
+        HostMembership::Unavailable(reason) => Err(anyhow!(reason)),
+        HostMembership::Provision => {
+            info!(network_id = %network.id, host_id = %host_id, network_type = %network.type_, "provisioning network on host");
+            let result = if network.type_ == "vxlan" {
+                expand_vxlan_to_host(st, network, host_id).await
+            } else {
+                span_bridged_to_host(st, network, host_id).await
+            };
+            result.with_context(|| {
+                format!(
+                    "failed to provision network {} on host {}",
+                    network.id, host_id
+                )
+            })
+        }
+    }
+}
+
+/// Attach a bridged network to another host's copy of its uplink NIC.
+async fn span_bridged_to_host(st: &AppState, network: &NetworkRow, host_id: Uuid) -> Result<()> {
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let host = st.hosts.get(host_id).await.context("host not found")?;
+    let host_ip = parse_host_ip(&host.addr)?;
+    let rules = provision_rules(&network_repo, network.id).await?;
+
+    let nh = network_repo
+        .add_network_host(network.id, host_id, &host_ip, false)
+        .await
+        .context("failed to insert network_host record")?;
+
+    let agent_url = format!(
+        "{}/agent/v1/networks/provision",
+        host.addr.trim_end_matches('/')
+    );
+    let client = crate::core::agent_http::client();
+    let result = client
+        .post(&agent_url)
+        .json(&serde_json::json!({
+            "network_type": network.type_,
+            "bridge_name": network.bridge_name,
+            "uplink_interface": network.uplink_interface,
+            "rules": rules,
+        }))
+        .send()
+        .await;
+
+    let err_msg = match result {
+        std::result::Result::Ok(resp) if resp.status().is_success() => {
+            let _ = network_repo
+                .update_network_host_status(nh.id, "active", None)
+                .await;
+            info!(network_id = %network.id, host = %host.name, "bridged network spanned to host");
+            return Ok(());
+        }
+        std::result::Result::Ok(resp) => {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            format!("agent on {} returned {}: {}", host.name, status, body)
+        }
+        Err(e) => format!("failed to reach agent on {}: {}", host.name, e),
+    };
+    let _ = network_repo
+        .update_network_host_status(nh.id, "error", Some(&err_msg))
+        .await;
+    Err(anyhow!(err_msg))
+}
+
+/// Expand a VXLAN network to a new host that doesn't yet participate in the
+/// overlay (or whose earlier attempt failed).
+async fn expand_vxlan_to_host(
     st: &AppState,
     network: &NetworkRow,
     new_host_id: Uuid,
@@ -603,6 +987,7 @@ pub async fn expand_vxlan_to_host(
     let vni = network
         .vni
         .ok_or_else(|| anyhow!("VXLAN network has no VNI"))?;
+    let rules = provision_rules(&network_repo, network.id).await?;
 
     info!(
         network_id = %network.id,
@@ -612,11 +997,20 @@ pub async fn expand_vxlan_to_host(
         "expanding VXLAN to new host"
     );
 
-    // Get all existing peers
-    let existing_peers = network_repo
+    // Get all existing peers (other than an earlier failed attempt on this host)
+    let existing_peers: Vec<NetworkHostRow> = network_repo
         .list_network_hosts(network.id)
         .await
-        .context("failed to list network hosts")?;
+        .context("failed to list network hosts")?
+        .into_iter()
+        .filter(|peer| peer.host_id != new_host_id)
+        .collect();
+
+    // Record the membership up front so a failure below is visible on it
+    let nh = network_repo
+        .add_network_host(network.id, new_host_id, &new_vtep_ip, false)
+        .await
+        .context("failed to insert network_host record")?;
 
     // 1. Provision VXLAN on new host (non-gateway)
     let agent_url = format!(
@@ -624,7 +1018,7 @@ pub async fn expand_vxlan_to_host(
         new_host.addr.trim_end_matches('/')
     );
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let result = client
         .post(&agent_url)
         .json(&serde_json::json!({
@@ -636,23 +1030,27 @@ pub async fn expand_vxlan_to_host(
             "gateway": network.gateway,
             "is_gateway": false,
             "dhcp_enabled": false,
+            "rules": rules,
         }))
         .send()
         .await;
 
-    match result {
-        std::result::Result::Ok(resp) if resp.status().is_success() => {}
+    let failure = match result {
+        std::result::Result::Ok(resp) if resp.status().is_success() => None,
         std::result::Result::Ok(resp) => {
             let body = resp.text().await.unwrap_or_default();
-            return Err(anyhow!(
+            Some(format!(
                 "failed to provision VXLAN on {}: {}",
-                new_host.name,
-                body
-            ));
-        }
-        Err(e) => {
-            return Err(anyhow!("failed to reach agent on {}: {}", new_host.name, e));
+                new_host.name, body
+            ))
         }
+        Err(e) => Some(format!("failed to reach agent on {}: {}", new_host.name, e)),
+    };
+    if let Some(err_msg) = failure {
+        let _ = network_repo
+            .update_network_host_status(nh.id, "error", Some(&err_msg))
+            .await;
+        return Err(anyhow!(err_msg));
     }
 
     // 2. Add FDB peers on new host → point to all existing hosts
@@ -687,11 +1085,7 @@ pub async fn expand_vxlan_to_host(
         }
     }
 
-    // 4. Record in DB
-    let nh = network_repo
-        .add_network_host(network.id, new_host_id, &new_vtep_ip, false)
-        .await
-        .context("failed to insert network_host record")?;
+    // 4. Mark the membership active
     let _ = network_repo
         .update_network_host_status(nh.id, "active", None)
         .await;
@@ -705,6 +1099,212 @@ pub async fn expand_vxlan_to_host(
     Ok(())
 }
 
+/// Hosts [`converge_vxlan_peers`] pushes FDB changes to at once.
+const VXLAN_PUSH_CONCURRENCY: usize = 8;
+/// How long one host gets for all of its FDB changes in a convergence pass.
+const VXLAN_HOST_PUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One FDB change to push to a VXLAN member host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VxlanPeerOp {
+    /// Host whose agent receives the change
+    pub host_id: Uuid,
+    pub peer_ip: String,
+    /// `true` for `peers/add`, `false` for `peers/remove`
+    pub add: bool,
+}
+
+/// Compute the FDB changes that converge a VNI to a full mesh of its healthy,
+/// active members. Every such member gets an entry for each other one, and
+/// drops the entry of any member whose host is no longer healthy.
+pub fn plan_vxlan_peers(members: &[NetworkHostRow], healthy: &HashSet<Uuid>) -> Vec<VxlanPeerOp> {
+    let (live, dead): (Vec<&NetworkHostRow>, Vec<&NetworkHostRow>) =
+        members.iter().partition(|m| healthy.contains(&m.host_id));
+
+    let mut ops = Vec::new();
+    for target in live.iter().filter(|m| m.status == "active") {
+        for peer in live.iter().filter(|m| m.status == "active") {
+            if peer.host_id != target.host_id && peer.vtep_ip != target.vtep_ip {
+                ops.push(VxlanPeerOp {
+                    host_id: target.host_id,
+                    peer_ip: peer.vtep_ip.clone(),
+                    add: true,
+                });
+            }
+        }
+        for peer in &dead {
+            if peer.vtep_ip != target.vtep_ip {
+                ops.push(VxlanPeerOp {
+                    host_id: target.host_id,
+                    peer_ip: peer.vtep_ip.clone(),
+                    add: false,
+                });
+            }
+        }
+    }
+    ops
+}
+
+/// Converge the FDB peer sets of every active VXLAN network with the current
+/// host registry. Both agent endpoints are idempotent, so this is safe to run
+/// on every reconciler tick.
+pub async fn converge_vxlan_peers(st: &AppState, skip: &HashSet<Uuid>) -> Result<()> {
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let networks = network_repo
+        .list_active_vxlan_networks()
+        .await
+        .context("failed to list VXLAN networks")?;
+    if networks.is_empty() {
+        return Ok(());
+    }
+
+    let healthy_hosts = st
+        .hosts
+        .list_healthy()
+        .await
+        .context("failed to list healthy hosts")?;
+    let healthy: HashSet<Uuid> = healthy_hosts.iter().map(|h| h.id).collect();
+
+    // One queue of FDB changes per host: hosts are pushed to side by side,
+    // each one's changes in order.
+    let mut per_host: HashMap<Uuid, Vec<(Uuid, i32, VxlanPeerOp)>> = HashMap::new();
+    for network in &networks {
+        let Some(vni) = network.vni else { continue };
+        let members = network_repo
+            .list_network_hosts(network.id)
+            .await
+            .unwrap_or_default();
+
+        for op in plan_vxlan_peers(&members, &healthy) {
+            if skip.contains(&op.host_id) {
+                continue;
+            }
+            per_host
+                .entry(op.host_id)
+                .or_default()
+                .push((network.id, vni, op));
+        }
+    }
+
+    let client = crate::core::agent_http::client();
+    let client = &client;
+    let healthy_hosts = &healthy_hosts;
+    futures::stream::iter(per_host)
+        .for_each_concurrent(VXLAN_PUSH_CONCURRENCY, |(host_id, ops)| async move {
+            let Some(host) = healthy_hosts.iter().find(|h| h.id == host_id) else {
+                return;
+            };
+            let push = async {
+                for (network_id, vni, op) in &ops {
+                    push_vxlan_peer_op(client, *network_id, *vni, &host.addr, op).await;
+                }
+            };
+            if tokio::time::timeout(VXLAN_HOST_PUSH_TIMEOUT, push).await.is_err() {
+                warn!(host_id = %host_id, timeout_secs = VXLAN_HOST_PUSH_TIMEOUT.as_secs(), "VXLAN peer sync timed out; retrying next pass");
+            }
+        })
+        .await;
+
+    Ok(())
+}
+
+/// Send one FDB change to `addr`'s agent. Failures are logged; the next
+/// convergence pass retries them.
+async fn push_vxlan_peer_op(
+    client: &reqwest::Client,
+    network_id: Uuid,
+    vni: i32,
+    addr: &str,
+    op: &VxlanPeerOp,
+) {
+    let url = format!(
+        "{}/agent/v1/networks/peers/{}",
+        addr.trim_end_matches('/'),
+        if op.add { "add" } else { "remove" }
+    );
+    let result = client
+        .post(&url)
+        .json(&serde_json::json!({
+            "vni": vni,
+            "peer_ip": op.peer_ip,
+        }))
+        .send()
+        .await;
+    match result {
+        std::result::Result::Ok(resp) if resp.status().is_success() => {}
+        std::result::Result::Ok(resp) => {
+            let body = resp.text().await.unwrap_or_default();
+            warn!(network_id = %network_id, host_id = %op.host_id, peer_ip = %op.peer_ip, add = op.add, error = %body, "VXLAN peer sync failed");
+        }
+        Err(e) => {
+            warn!(network_id = %network_id, host_id = %op.host_id, peer_ip = %op.peer_ip, add = op.add, error = %e, "failed to reach agent for VXLAN peer sync");
+        }
+    }
+}
+
+/// The FDB entries for `host_id`'s old VXLAN local IP to drop from the other
+/// members, once the host has moved to another address. Convergence only adds
+/// the new IP, so without this the old entries would stay forever.
+pub fn plan_stale_vtep_removals(
+    host_id: Uuid,
+    old_ip: &str,
+    members: &[NetworkHostRow],
+) -> Vec<VxlanPeerOp> {
+    members
+        .iter()
+        .filter(|m| m.host_id != host_id && m.vtep_ip != old_ip)
+        .map(|m| VxlanPeerOp {
+            host_id: m.host_id,
+            peer_ip: old_ip.to_string(),
+            add: false,
+        })
+        .collect()
+}
+
+/// Record a (re-)registered host's current address as its VXLAN local IP in
+/// every network it participates in, and remove the FDB entries for its old
+/// IP from the other members. Run before [`converge_vxlan_peers`] adds the
+/// new one.
+pub async fn refresh_host_vtep_ip(st: &AppState, host_id: Uuid, addr: &str) -> Result<()> {
+    let vtep_ip = parse_host_ip(addr)?;
+    let network_repo = NetworkRepository::new(st.db.clone());
+    let changed = network_repo
+        .update_vtep_ip_for_host(host_id, &vtep_ip)
+        .await
+        .context("failed to update VXLAN local IP")?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    info!(host_id = %host_id, vtep_ip = %vtep_ip, memberships = changed.len(), "updated VXLAN local IP");
+
+    let client = crate::core::agent_http::client();
+    for (network_id, old_ip) in changed {
+        let vni = match network_repo.get(network_id).await {
+            std::result::Result::Ok(network) => network.vni,
+            Err(e) => {
+                warn!(network_id = %network_id, error = %e, "failed to load network to drop stale VXLAN peer");
+                continue;
+            }
+        };
+        let Some(vni) = vni else { continue };
+        let members = network_repo
+            .list_network_hosts(network_id)
+            .await
+            .unwrap_or_default();
+        for op in plan_stale_vtep_removals(host_id, &old_ip, &members) {
+            match st.hosts.get(op.host_id).await {
+                std::result::Result::Ok(host) => {
+                    push_vxlan_peer_op(&client, network_id, vni, &host.addr, &op).await
+                }
+                Err(e) => {
+                    warn!(network_id = %network_id, host_id = %op.host_id, error = %e, "failed to load VXLAN member host")
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Get the count of hosts participating in a VXLAN network.
 #[allow(dead_code)]
 pub async fn get_network_host_count(st: &AppState, network_id: Uuid) -> i64 {
@@ -715,15 +1315,17 @@ pub async fn get_network_host_count(st: &AppState, network_id: Uuid) -> i64 {
         .unwrap_or(0)
 }
 
-/// Check if a host already participates in a VXLAN network.
-pub async fn network_host_exists(st: &AppState, network_id: Uuid, host_id: Uuid) -> bool {
-    let network_repo = NetworkRepository::new(st.db.clone());
-    network_repo
-        .get_network_host(network_id, host_id)
+/// How `host_id` stands with `network`; see [`plan_host_membership`].
+pub async fn host_membership(
+    st: &AppState,
+    network: &NetworkRow,
+    host_id: Uuid,
+) -> Result<HostMembership> {
+    let member = NetworkRepository::new(st.db.clone())
+        .get_network_host(network.id, host_id)
         .await
-        .ok()
-        .flatten()
-        .is_some()
+        .context("failed to look up network membership")?;
+    Ok(plan_host_membership(network, member.as_ref(), host_id))
 }
 
 /// Parse IP address from a host addr like "http://10.0.0.5:9090".
@@ -827,6 +1429,39 @@ mod tests {
         assert_eq!(parse_host_ip("10.0.0.5").unwrap(), "10.0.0.5");
     }
 
+    fn rule_params(protocol: &str, ports: (Option<u16>, Option<u16>)) -> CreateNetworkRuleParams {
+        CreateNetworkRuleParams {
+            direction: "ingress".to_string(),
+            protocol: protocol.to_string(),
+            port_start: ports.0,
+            port_end: ports.1,
+            cidr: Some("10.0.0.0/8".to_string()),
+            action: "accept".to_string(),
+        }
+    }
+
+    #[test]
+    fn validate_rule_accepts_port_range_and_icmp() {
+        assert!(validate_rule(&rule_params("tcp", (Some(80), Some(443)))).is_ok());
+        assert!(validate_rule(&rule_params("udp", (Some(53), None))).is_ok());
+        assert!(validate_rule(&rule_params("icmp", (None, None))).is_ok());
+    }
+
+    #[test]
+    fn validate_rule_rejects_bad_ports_and_cidr() {
+        // Ports only make sense for tcp/udp.
+        assert!(validate_rule(&rule_params("icmp", (Some(1), None))).is_err());
+        assert!(validate_rule(&rule_params("tcp", (Some(443), Some(80)))).is_err());
+        assert!(validate_rule(&rule_params("tcp", (None, Some(80)))).is_err());
+        assert!(validate_rule(&rule_params("sctp", (None, None))).is_err());
+
+        let mut p = rule_params("all", (None, None));
+        p.cidr = Some("10.0.0.0/33".to_string());
+        assert!(validate_rule(&p).is_err());
+        p.cidr = Some("not-an-ip".to_string());
+        assert!(validate_rule(&p).is_err());
+    }
+
     #[test]
     fn parse_host_ip_rejects_empty_input() {
         // Empty string yields empty IP.
@@ -834,4 +1469,211 @@ mod tests {
         // Scheme-only string also resolves to empty.
         assert!(parse_host_ip("http://").is_err());
     }
+
+    fn member(host_id: Uuid, vtep_ip: &str, status: &str) -> NetworkHostRow {
+        NetworkHostRow {
+            id: Uuid::new_v4(),
+            network_id: Uuid::nil(),
+            host_id,
+            vtep_ip: vtep_ip.to_string(),
+            is_gateway: false,
+            status: status.to_string(),
+            error_message: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn network(type_: &str, owner: Uuid) -> NetworkRow {
+        let now = chrono::Utc::now();
+        NetworkRow {
+            id: Uuid::new_v4(),
+            name: "net-a".to_string(),
+            description: None,
+            type_: type_.to_string(),
+            vlan_id: None,
+            bridge_name: "nqbr1".to_string(),
+            host_id: Some(owner),
+            cidr: Some("10.0.2.0/24".to_string()),
+            gateway: Some("10.0.2.1".to_string()),
+            status: "active".to_string(),
+            error_message: None,
+            managed: true,
+            dhcp_enabled: true,
+            dhcp_range_start: None,
+            dhcp_range_end: None,
+            created_by_user_id: None,
+            vni: None,
+            uplink_interface: Some("eth0".to_string()),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn plan_host_membership_spans_vxlan_and_bridged_on_demand() {
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        for type_ in ["vxlan", "bridged"] {
+            let net = network(type_, owner);
+            assert_eq!(
+                plan_host_membership(&net, None, owner),
+                HostMembership::Ready
+            );
+            assert_eq!(
+                plan_host_membership(&net, None, other),
+                HostMembership::Provision
+            );
+            let active = member(other, "10.0.0.2", "active");
+            assert_eq!(
+                plan_host_membership(&net, Some(&active), other),
+                HostMembership::Ready
+            );
+            // An earlier failed or interrupted attempt is provisioned again
+            for status in ["error", "provisioning"] {
+                let m = member(other, "10.0.0.2", status);
+                assert_eq!(
+                    plan_host_membership(&net, Some(&m), other),
+                    HostMembership::Provision
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn plan_host_membership_keeps_local_networks_on_their_host() {
+        let (owner, other) = (Uuid::new_v4(), Uuid::new_v4());
+        for type_ in ["nat", "isolated"] {
+            let net = network(type_, owner);
+            assert_eq!(
+                plan_host_membership(&net, None, owner),
+                HostMembership::Ready
+            );
+            assert!(matches!(
+                plan_host_membership(&net, None, other),
+                HostMembership::Unavailable(_)
+            ));
+        }
+
+        // Unmanaged (installer-registered) bridges are used as the host has them
+        let mut net = network("nat", owner);
+        net.managed = false;
+        assert_eq!(
+            plan_host_membership(&net, None, other),
+            HostMembership::Ready
+        );
+    }
+
+    #[test]
+    fn plan_host_membership_rejects_networks_that_are_not_active() {
+        let owner = Uuid::new_v4();
+        let mut net = network("vxlan", owner);
+        net.status = "error".to_string();
+        let HostMembership::Unavailable(reason) = plan_host_membership(&net, None, owner) else {
+            panic!("expected an errored network to be unavailable");
+        };
+        assert!(reason.contains("error"));
+    }
+
+    #[test]
+    fn plan_vxlan_peers_builds_full_mesh_of_healthy_members() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let members = vec![
+            member(a, "10.0.0.1", "active"),
+            member(b, "10.0.0.2", "active"),
+            member(c, "10.0.0.3", "active"),
+        ];
+        let healthy: HashSet<Uuid> = [a, b, c].into_iter().collect();
+
+        let ops = plan_vxlan_peers(&members, &healthy);
+        assert_eq!(ops.len(), 6);
+        assert!(ops.iter().all(|op| op.add));
+        assert!(ops.contains(&VxlanPeerOp {
+            host_id: a,
+            peer_ip: "10.0.0.3".to_string(),
+            add: true,
+        }));
+        assert!(!ops
+            .iter()
+            .any(|op| op.host_id == a && op.peer_ip == "10.0.0.1"));
+    }
+
+    #[test]
+    fn plan_stale_vtep_removals_targets_every_other_member() {
+        let (moved, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let members = vec![
+            member(moved, "10.0.0.9", "active"),
+            member(b, "10.0.0.2", "active"),
+            member(c, "10.0.0.3", "pending"),
+        ];
+        let ops = plan_stale_vtep_removals(moved, "10.0.0.1", &members);
+        assert_eq!(
+            ops,
+            vec![
+                VxlanPeerOp {
+                    host_id: b,
+                    peer_ip: "10.0.0.1".to_string(),
+                    add: false,
+                },
+                VxlanPeerOp {
+                    host_id: c,
+                    peer_ip: "10.0.0.1".to_string(),
+                    add: false,
+                },
+            ]
+        );
+        // A member that now holds the old IP keeps its own entry
+        let members = vec![member(b, "10.0.0.1", "active")];
+        assert!(plan_stale_vtep_removals(moved, "10.0.0.1", &members).is_empty());
+    }
+
+    #[test]
+    fn plan_vxlan_peers_removes_unhealthy_and_skips_inactive() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let members = vec![
+            member(a, "10.0.0.1", "active"),
+            member(b, "10.0.0.2", "active"),
+            member(c, "10.0.0.3", "provisioning"),
+        ];
+        // b stopped heartbeating; c is healthy but not yet provisioned
+        let healthy: HashSet<Uuid> = [a, c].into_iter().collect();
+
+        let ops = plan_vxlan_peers(&members, &healthy);
+        assert_eq!(
+            ops,
+            vec![VxlanPeerOp {
+                host_id: a,
+                peer_ip: "10.0.0.2".to_string(),
+                add: false,
+            }]
+        );
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn delete_network_keeps_the_record_unless_forced(pool: sqlx::PgPool) {
+        let st = crate::AppState::for_tests(pool.clone()).await;
+        // Nothing listens there, so the teardown fails
+        let host = st
+            .hosts
+            .register("net-host", "http://127.0.0.1:1", serde_json::json!({}))
+            .await
+            .unwrap();
+        let repo = NetworkRepository::new(pool.clone());
+        let network = repo
+            .create(
+                "lab", None, "nat", None, "nqbr9", host.id, None, None, "active", true, false,
+                None, None, None,
+            )
+            .await
+            .unwrap();
+
+        let err = delete_network(&st, network.id, false).await.unwrap_err();
+        assert!(err.to_string().contains("teardown failed"), "{err}");
+        assert_eq!(repo.get(network.id).await.unwrap().status, "error");
+
+        delete_network(&st, network.id, true).await.unwrap();
+        assert!(matches!(
+            repo.get(network.id).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
 }
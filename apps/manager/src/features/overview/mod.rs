@@ -0,0 +1,8 @@
+use axum::{routing::get, Router};
+
+pub mod repo;
+pub mod routes;
+
+pub fn router() -> Router {
+    Router::new().route("/", get(routes::get))
+}
@@ -0,0 +1,91 @@
+use nexus_types::StateCount;
+use sqlx::PgPool;
+
+#[derive(Clone)]
+pub struct OverviewRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct CapacityRow {
+    pub healthy_hosts: i64,
+    pub total_memory_mb: i64,
+    pub used_memory_mb: i64,
+    pub total_disk_gb: i64,
+    pub used_disk_gb: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct StateCountRow {
+    state: String,
+    count: i64,
+}
+
+impl OverviewRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn vm_states(&self) -> sqlx::Result<Vec<StateCount>> {
+        self.state_counts(
+            r#"SELECT state, COUNT(*) AS count FROM vm GROUP BY state ORDER BY state"#,
+        )
+        .await
+    }
+
+    pub async fn container_states(&self) -> sqlx::Result<Vec<StateCount>> {
+        self.state_counts(
+            r#"SELECT state, COUNT(*) AS count FROM containers GROUP BY state ORDER BY state"#,
+        )
+        .await
+    }
+
+    async fn state_counts(&self, sql: &str) -> sqlx::Result<Vec<StateCount>> {
+        let rows = sqlx::query_as::<_, StateCountRow>(sql)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| StateCount {
+                state: r.state,
+                count: r.count,
+            })
+            .collect())
+    }
+
+    pub async fn function_count(&self) -> sqlx::Result<i64> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM function"#)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Sum the capacity reported in the capabilities JSON of every host whose
+    /// last heartbeat is within `healthy_secs`. Used memory is only known for
+    /// agents that report `free_memory_mb`.
+    pub async fn healthy_capacity(&self, healthy_secs: i64) -> sqlx::Result<CapacityRow> {
+        sqlx::query_as::<_, CapacityRow>(
+            r#"
+            SELECT COUNT(*) AS healthy_hosts,
+                   COALESCE(SUM((capabilities_json->>'total_memory_mb')::bigint), 0)::bigint AS total_memory_mb,
+                   COALESCE(SUM((capabilities_json->>'total_memory_mb')::bigint
+                                - (capabilities_json->>'free_memory_mb')::bigint), 0)::bigint AS used_memory_mb,
+                   COALESCE(SUM((capabilities_json->>'total_disk_gb')::bigint), 0)::bigint AS total_disk_gb,
+                   COALESCE(SUM((capabilities_json->>'used_disk_gb')::bigint), 0)::bigint AS used_disk_gb
+            FROM host
+            WHERE last_seen_at > now() - make_interval(secs => $1)
+            "#,
+        )
+        .bind(healthy_secs as f64)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn audit_events_since(&self, hours: i32) -> sqlx::Result<i64> {
+        sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM audit.audit_logs WHERE created_at > now() - make_interval(hours => $1)"#,
+        )
+        .bind(hours)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
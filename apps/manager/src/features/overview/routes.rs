@@ -0,0 +1,76 @@
+use super::repo::OverviewRepository;
+use crate::core::error::ApiError;
+use crate::features::hosts::routes::HEALTHY_THRESHOLD_SECONDS;
+use crate::AppState;
+use axum::{Extension, Json};
+use nexus_types::ClusterOverview;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a computed overview is served before the next request hits the
+/// database again. Dashboards poll this endpoint on auto-refresh.
+const CACHE_TTL: Duration = Duration::from_secs(5);
+const RECENT_AUDIT_HOURS: i32 = 24;
+
+static CACHE: LazyLock<Mutex<Option<(Instant, ClusterOverview)>>> = LazyLock::new(Default::default);
+
+fn is_fresh(computed_at: Instant, now: Instant) -> bool {
+    now.saturating_duration_since(computed_at) < CACHE_TTL
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/overview",
+    responses(
+        (status = 200, description = "Cluster-wide resource totals", body = ClusterOverview),
+        (status = 401, description = "Not authenticated"),
+    ),
+    tag = "Overview"
+)]
+pub async fn get(Extension(st): Extension<AppState>) -> Result<Json<ClusterOverview>, ApiError> {
+    if let Some((computed_at, overview)) = CACHE.lock().unwrap().as_ref() {
+        if is_fresh(*computed_at, Instant::now()) {
+            return Ok(Json(overview.clone()));
+        }
+    }
+
+    let repo = OverviewRepository::new(st.db.clone());
+    let (vms, containers, functions, capacity, recent_audit_events) = tokio::try_join!(
+        repo.vm_states(),
+        repo.container_states(),
+        repo.function_count(),
+        repo.healthy_capacity(HEALTHY_THRESHOLD_SECONDS),
+        repo.audit_events_since(RECENT_AUDIT_HOURS),
+    )?;
+
+    let overview = ClusterOverview {
+        vms,
+        containers,
+        functions,
+        healthy_hosts: capacity.healthy_hosts,
+        total_memory_mb: capacity.total_memory_mb,
+        used_memory_mb: capacity.used_memory_mb,
+        total_disk_gb: capacity.total_disk_gb,
+        used_disk_gb: capacity.used_disk_gb,
+        recent_audit_events,
+        generated_at: chrono::Utc::now(),
+    };
+    *CACHE.lock().unwrap() = Some((Instant::now(), overview.clone()));
+    Ok(Json(overview))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_overview_expires_after_ttl() {
+        let start = Instant::now();
+        assert!(is_fresh(start, start));
+        assert!(is_fresh(
+            start,
+            start + CACHE_TTL - Duration::from_millis(1)
+        ));
+        assert!(!is_fresh(start, start + CACHE_TTL));
+    }
+}
@@ -0,0 +1,331 @@
+//! Restart-policy enforcement for containers. Each pass asks the runtime
+//! whether the containers the DB thinks are running still are, and restarts
+//! the dead ones whose `restart_policy` says so, backing off between attempts
+//! and giving up after `MANAGER_CONTAINER_RESTART_MAX_RETRIES`.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::features::containers::repo::{ContainerRepository, RestartCandidate};
+use crate::features::containers::service::{self, RuntimeState};
+use crate::AppState;
+use anyhow::Result;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+const MAX_RETRIES_ENV: &str = "MANAGER_CONTAINER_RESTART_MAX_RETRIES";
+const DEFAULT_MAX_RETRIES: i32 = 5;
+/// Delay before the second restart; doubles per attempt up to [`BACKOFF_CAP`].
+const BACKOFF_BASE: Duration = Duration::from_secs(10);
+const BACKOFF_CAP: Duration = Duration::from_secs(5 * 60);
+/// A container that has stayed up this long since its last policy restart
+/// gets its restart count back.
+const RESET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+fn max_retries() -> i32 {
+    parse_max_retries(std::env::var(MAX_RETRIES_ENV).ok().as_deref())
+}
+
+fn parse_max_retries(raw: Option<&str>) -> i32 {
+    raw.and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|n| *n >= 0)
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    /// The policy doesn't want this exit restarted.
+    Leave,
+    /// Still inside the backoff window of the previous attempt.
+    Wait,
+    Restart,
+    GiveUp,
+}
+
+/// Wait before the next restart once `restart_count` restarts have been made.
+fn backoff(restart_count: i32) -> Duration {
+    if restart_count <= 0 {
+        return Duration::ZERO;
+    }
+    let shift = (restart_count - 1).min(16) as u32;
+    BACKOFF_BASE.saturating_mul(1 << shift).min(BACKOFF_CAP)
+}
+
+fn decide(
+    policy: &str,
+    exit_code: Option<i64>,
+    restart_count: i32,
+    max_retries: i32,
+    since_last_restart: Option<Duration>,
+) -> Decision {
+    let warranted = match policy {
+        "always" => true,
+        // A vanished container or VM counts as a failure
+        "on-failure" => exit_code != Some(0),
+        _ => false,
+    };
+    if !warranted {
+        return Decision::Leave;
+    }
+    if restart_count >= max_retries {
+        return Decision::GiveUp;
+    }
+    if since_last_restart.is_some_and(|elapsed| elapsed < backoff(restart_count)) {
+        return Decision::Wait;
+    }
+    Decision::Restart
+}
+
+/// Containers whose policy restart is still running in the background, so
+/// later passes leave them alone until it finishes.
+#[derive(Clone, Default)]
+pub(super) struct Restarts(Arc<Mutex<HashSet<Uuid>>>);
+
+impl Restarts {
+    fn contains(&self, id: Uuid) -> bool {
+        self.0.lock().unwrap().contains(&id)
+    }
+
+    /// `None` if a restart of `id` is already running.
+    fn claim(&self, id: Uuid) -> Option<RestartClaim> {
+        self.0.lock().unwrap().insert(id).then(|| RestartClaim {
+            restarts: self.clone(),
+            id,
+        })
+    }
+}
+
+/// Releases the container's [`Restarts`] entry when the restart ends.
+struct RestartClaim {
+    restarts: Restarts,
+    id: Uuid,
+}
+
+impl Drop for RestartClaim {
+    fn drop(&mut self) {
+        self.restarts.0.lock().unwrap().remove(&self.id);
+    }
+}
+
+fn describe_exit(exit_code: Option<i64>, oom_killed: bool) -> String {
+    match exit_code {
+        _ if oom_killed => "container was killed for running out of memory".to_string(),
+        Some(code) => format!("container exited with code {code}"),
+        None => "container or its VM is gone".to_string(),
+    }
+}
+
+pub(super) async fn reconcile_containers(state: &AppState, restarts: &Restarts) -> Result<()> {
+    let repo = ContainerRepository::new(state.db.clone());
+    let max_retries = max_retries();
+    for candidate in repo.list_restart_candidates(max_retries).await? {
+        if restarts.contains(candidate.id) {
+            continue;
+        }
+        if let Err(err) = reconcile_container(state, &repo, restarts, &candidate, max_retries).await
+        {
+            warn!(container_id = %candidate.id, error = ?err, "container restart-policy check failed");
+        }
+    }
+    Ok(())
+}
+
+async fn reconcile_container(
+    state: &AppState,
+    repo: &ContainerRepository,
+    restarts: &Restarts,
+    candidate: &RestartCandidate,
+    max_retries: i32,
+) -> Result<()> {
+    let id = candidate.id;
+    let policy = candidate.restart_policy.as_deref().unwrap_or("no");
+    let container = repo.get(id).await?;
+    let runtime = match service::runtime_state(state, &container).await {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            debug!(container_id = %id, error = ?err, "container runtime unreachable, skipping");
+            return Ok(());
+        }
+    };
+    // The runtime check can take a while; a user stop or start in the
+    // meantime wins over what the candidate row said.
+    let current = repo.get(id).await?;
+    if current.state != candidate.state {
+        debug!(container_id = %id, from = %candidate.state, to = %current.state, "container state changed during the check, skipping");
+        return Ok(());
+    }
+    let since_last_restart = candidate
+        .last_restart_at
+        .and_then(|at| (chrono::Utc::now() - at).to_std().ok());
+
+    let (exit_code, oom_killed) = match runtime {
+        RuntimeState::Running => {
+            if candidate.state != "running" {
+                repo.update_state(id, "running", None).await?;
+            }
+            if candidate.restart_count > 0 && since_last_restart.is_some_and(|e| e >= RESET_AFTER) {
+                repo.reset_restart_count(id).await?;
+            }
+            return Ok(());
+        }
+        RuntimeState::Exited {
+            exit_code,
+            oom_killed,
+        } => (exit_code, oom_killed),
+    };
+
+    let cause = describe_exit(exit_code, oom_killed);
+    let decision = decide(
+        policy,
+        exit_code,
+        candidate.restart_count,
+        max_retries,
+        since_last_restart,
+    );
+    if candidate.state == "running" {
+        metrics::counter!("manager_reconciler_container_deaths", 1);
+        info!(container_id = %id, %policy, cause = %cause, "container died unexpectedly");
+        if decision == Decision::Leave {
+            // A clean exit `on-failure` doesn't restart: record a plain stop
+            repo.set_stopped(id).await?;
+            return Ok(());
+        }
+        repo.update_state(id, "error", Some(cause.clone())).await?;
+    }
+
+    match decision {
+        Decision::Leave | Decision::Wait => {}
+        Decision::GiveUp => give_up(repo, id, policy, candidate.restart_count, &cause).await?,
+        Decision::Restart => {
+            let Some(claim) = restarts.claim(id) else {
+                return Ok(());
+            };
+            let attempt = repo.record_restart_attempt(id).await?;
+            metrics::counter!("manager_reconciler_container_restart_attempts", 1);
+            info!(container_id = %id, %policy, attempt, "restarting container per restart policy");
+            // A restart boots a whole VM; it mustn't hold up the pass
+            let state = state.clone();
+            let policy = policy.to_string();
+            state.background.clone().spawn(async move {
+                let _claim = claim;
+                if let Err(err) = restart(&state, id, &policy, attempt, max_retries).await {
+                    warn!(container_id = %id, error = ?err, "recording container restart outcome failed");
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+async fn restart(
+    state: &AppState,
+    id: Uuid,
+    policy: &str,
+    attempt: i32,
+    max_retries: i32,
+) -> Result<()> {
+    match service::restart_for_policy(state, id).await {
+        Ok(()) => {
+            metrics::counter!("manager_reconciler_container_restart_success", 1);
+            info!(container_id = %id, attempt, "container restart succeeded");
+        }
+        Err(err) => {
+            metrics::counter!("manager_reconciler_container_restart_failure", 1);
+            error!(container_id = %id, attempt, error = ?err, "container restart failed");
+            let repo = ContainerRepository::new(state.db.clone());
+            let cause = format!("reconciler restart failed: {err:#}");
+            if attempt >= max_retries {
+                give_up(&repo, id, policy, attempt, &cause).await?;
+            } else {
+                repo.update_state(id, "error", Some(cause)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn give_up(
+    repo: &ContainerRepository,
+    id: Uuid,
+    policy: &str,
+    restart_count: i32,
+    cause: &str,
+) -> Result<()> {
+    metrics::counter!("manager_reconciler_container_restart_exhausted", 1);
+    warn!(container_id = %id, %policy, restart_count, "container restart retries exhausted");
+    let message = format!(
+        "restart policy '{policy}' gave up after {restart_count} restarts; last failure: {cause}"
+    );
+    repo.update_state(id, "error", Some(message)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_decides_which_exits_restart() {
+        assert_eq!(decide("no", Some(1), 0, 5, None), Decision::Leave);
+        assert_eq!(decide("on-failure", Some(0), 0, 5, None), Decision::Leave);
+        assert_eq!(
+            decide("on-failure", Some(137), 0, 5, None),
+            Decision::Restart
+        );
+        assert_eq!(decide("on-failure", None, 0, 5, None), Decision::Restart);
+        assert_eq!(decide("always", Some(0), 0, 5, None), Decision::Restart);
+    }
+
+    #[test]
+    fn restarts_back_off_then_give_up() {
+        let secs = Duration::from_secs;
+        assert_eq!(backoff(0), Duration::ZERO);
+        assert_eq!(backoff(1), secs(10));
+        assert_eq!(backoff(3), secs(40));
+        assert_eq!(backoff(30), BACKOFF_CAP);
+
+        assert_eq!(
+            decide("always", Some(1), 2, 5, Some(secs(5))),
+            Decision::Wait
+        );
+        assert_eq!(
+            decide("always", Some(1), 2, 5, Some(secs(25))),
+            Decision::Restart
+        );
+        assert_eq!(
+            decide("always", Some(1), 5, 5, Some(secs(600))),
+            Decision::GiveUp
+        );
+        assert_eq!(decide("always", Some(1), 0, 0, None), Decision::GiveUp);
+    }
+
+    #[test]
+    fn a_container_restarts_once_at_a_time() {
+        let restarts = Restarts::default();
+        let id = Uuid::new_v4();
+        let claim = restarts.claim(id).expect("first claim");
+        assert!(restarts.contains(id));
+        assert!(restarts.claim(id).is_none());
+        drop(claim);
+        assert!(!restarts.contains(id));
+        assert!(restarts.claim(id).is_some());
+    }
+
+    #[test]
+    fn max_retries_from_env() {
+        assert_eq!(parse_max_retries(None), DEFAULT_MAX_RETRIES);
+        assert_eq!(parse_max_retries(Some("-1")), DEFAULT_MAX_RETRIES);
+        assert_eq!(parse_max_retries(Some("lots")), DEFAULT_MAX_RETRIES);
+        assert_eq!(parse_max_retries(Some(" 3 ")), 3);
+    }
+
+    #[test]
+    fn exit_descriptions() {
+        assert_eq!(
+            describe_exit(Some(2), false),
+            "container exited with code 2"
+        );
+        assert!(describe_exit(Some(137), true).contains("out of memory"));
+        assert_eq!(describe_exit(None, false), "container or its VM is gone");
+    }
+}
@@ -0,0 +1,121 @@
+//! Host staleness. A host that misses `MANAGER_HOST_UNREACHABLE_HEARTBEATS`
+//! heartbeats in a row is flagged unreachable and each of its live VMs gets a
+//! `host_unreachable` event, once; the flag clears when it heartbeats again.
+
+use std::time::Duration;
+
+use crate::features::hosts::repo::HostRow;
+use crate::features::vms;
+use crate::AppState;
+use anyhow::Result;
+use tracing::{info, warn};
+
+const UNREACHABLE_HEARTBEATS_ENV: &str = "MANAGER_HOST_UNREACHABLE_HEARTBEATS";
+/// Six missed beats is the 90s the auto-reschedule pass has always used.
+const DEFAULT_UNREACHABLE_HEARTBEATS: u32 = 6;
+/// How often agents heartbeat (see the agent's `heartbeat_loop`).
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+fn stale_after() -> Duration {
+    parse_stale_after(std::env::var(UNREACHABLE_HEARTBEATS_ENV).ok().as_deref())
+}
+
+fn parse_stale_after(raw: Option<&str>) -> Duration {
+    let beats = raw
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_UNREACHABLE_HEARTBEATS);
+    HEARTBEAT_INTERVAL * beats
+}
+
+fn unreachable_message(host: &HostRow) -> String {
+    format!(
+        "host_unreachable: host {} ({}) has not heartbeated since {}; VM state is unknown",
+        host.name,
+        host.addr,
+        host.last_seen_at.to_rfc3339()
+    )
+}
+
+fn reachable_message(host: &HostRow) -> String {
+    format!(
+        "host_reachable: host {} ({}) is heartbeating again",
+        host.name, host.addr
+    )
+}
+
+pub(super) async fn reconcile_host_health(state: &AppState) -> Result<()> {
+    let stale_after = stale_after();
+    for host in state.hosts.clear_unreachable(stale_after).await? {
+        info!(host_id = %host.id, host_addr = %host.addr, "host is reachable again");
+        flag_vms(state, &host, "info", &reachable_message(&host)).await?;
+    }
+    for host in state.hosts.mark_unreachable(stale_after).await? {
+        metrics::counter!("manager_reconciler_hosts_unreachable", 1);
+        warn!(
+            host_id = %host.id,
+            host_addr = %host.addr,
+            last_seen_at = %host.last_seen_at,
+            "host missed too many heartbeats, marking unreachable"
+        );
+        flag_vms(state, &host, "warn", &unreachable_message(&host)).await?;
+    }
+    Ok(())
+}
+
+/// Event every VM on `host` that isn't stopped; their state is left alone.
+async fn flag_vms(state: &AppState, host: &HostRow, level: &str, message: &str) -> Result<()> {
+    let vms = vms::repo::list_by_host(&state.db, host.id).await?;
+    for vm in vms.iter().filter(|vm| vm.state != "stopped") {
+        let _ = vms::repo::insert_event(&state.db, vm.id, level, message).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staleness_counts_missed_heartbeats() {
+        assert_eq!(parse_stale_after(None), Duration::from_secs(90));
+        assert_eq!(parse_stale_after(Some("0")), Duration::from_secs(90));
+        assert_eq!(parse_stale_after(Some("often")), Duration::from_secs(90));
+        assert_eq!(parse_stale_after(Some(" 4 ")), Duration::from_secs(60));
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn a_flagged_host_clears_only_once_fresh_again(pool: sqlx::PgPool) {
+        use crate::features::hosts::repo::HostRepository;
+
+        let hosts = HostRepository::new(pool.clone());
+        let host = hosts
+            .register("h1", "http://10.0.0.1:9090", serde_json::json!({}))
+            .await
+            .unwrap();
+        let last_seen = |secs_ago: i64| {
+            sqlx::query(
+                "UPDATE host SET last_seen_at = now() - make_interval(secs => $2) WHERE id = $1",
+            )
+            .bind(host.id)
+            .bind(secs_ago as f64)
+            .execute(&pool)
+        };
+        let stale_after = Duration::from_secs(15);
+
+        last_seen(20).await.unwrap();
+        assert_eq!(hosts.mark_unreachable(stale_after).await.unwrap().len(), 1);
+        // Still past stale_after: no flapping back to reachable
+        assert!(hosts
+            .clear_unreachable(stale_after)
+            .await
+            .unwrap()
+            .is_empty());
+
+        last_seen(1).await.unwrap();
+        let cleared = hosts.clear_unreachable(stale_after).await.unwrap();
+        assert_eq!(cleared.len(), 1);
+        assert_eq!(cleared[0].id, host.id);
+    }
+}
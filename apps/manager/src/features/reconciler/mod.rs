@@ -1,5 +1,10 @@
-use std::collections::HashMap;
-use std::time::Duration;
+mod containers;
+mod hosts;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::features::hosts::repo::HostRow;
 use crate::features::networks;
@@ -7,43 +12,188 @@ use crate::features::vms;
 use crate::features::vms::repo::{VmDrive, VmNic};
 use crate::AppState;
 use anyhow::{anyhow, Result};
+use futures::StreamExt;
 use reqwest::StatusCode;
 use serde::Deserialize;
 use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-const INTERVAL_SECS: u64 = 15;
+const DISABLED_ENV: &str = "MANAGER_RECONCILER_DISABLED";
+const INTERVAL_ENV: &str = "MANAGER_RECONCILER_INTERVAL_SECS";
+const DEFAULT_INTERVAL_SECS: u64 = 15;
+const HOST_CONCURRENCY_ENV: &str = "MANAGER_RECONCILER_HOST_CONCURRENCY";
+const DEFAULT_HOST_CONCURRENCY: u64 = 8;
+const HOST_TIMEOUT_ENV: &str = "MANAGER_RECONCILER_HOST_TIMEOUT_SECS";
+const DEFAULT_HOST_TIMEOUT_SECS: u64 = 60;
+
+/// How long each reconcile pass takes, failed ones included.
+pub const PASS_DURATION_METRIC: &str = "manager_reconciler_pass_duration_seconds";
+/// A healthy pass is well under a second; one stuck behind host timeouts
+/// runs into tens of seconds.
+pub const PASS_DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// Per host: VMs the last pass found needing a restart plus orphaned
+/// artifacts, before the restart grace period filters them.
+const DRIFT_METRIC: &str = "manager_reconciler_drift";
+/// Per host: inventory fetches that failed or timed out.
+const INVENTORY_FAILURES_METRIC: &str = "manager_reconciler_inventory_failures";
+/// How long a tap no VM owns must stay around before it is deleted. A create
+/// brings its tap up and boots the VM before the VM row exists.
+const LEAKED_TAP_GRACE: Duration = Duration::from_secs(600);
+
+/// A positive integer from the environment, or `default` when unset/invalid.
+fn parse_positive(raw: Option<&str>, default: u64) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+fn env_positive(name: &str, default: u64) -> u64 {
+    parse_positive(std::env::var(name).ok().as_deref(), default)
+}
+
+/// Set while the reconcile loop is alive, for `/readyz`.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears [`RUNNING`] when the loop's task ends, including by panic.
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+/// `MANAGER_RECONCILER_DISABLED`, for tests and debugging.
+pub fn disabled_by_env() -> bool {
+    std::env::var(DISABLED_ENV).is_ok_and(|v| {
+        matches!(
+            v.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    })
+}
 
-pub fn spawn(state: AppState) -> tokio::task::JoinHandle<()> {
+/// Runs reconcile passes until `shutdown` is cancelled; a pass in progress
+/// is finished first, so the task ends between passes.
+pub fn spawn(state: AppState, shutdown: CancellationToken) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        let mut ticker = interval(Duration::from_secs(INTERVAL_SECS));
+        RUNNING.store(true, Ordering::Relaxed);
+        let _running = RunningGuard;
+        let period = Duration::from_secs(env_positive(INTERVAL_ENV, DEFAULT_INTERVAL_SECS));
+        let mut ticker = interval(period);
         ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
-        loop {
-            if let Err(err) = reconcile_once(&state).await {
+        let grace = Mutex::new(RestartGrace::default());
+        let leaked_taps = Mutex::new(LeakedTaps::default());
+        let drift_hosts = Mutex::new(HashSet::new());
+        let restarts = containers::Restarts::default();
+        while !shutdown.is_cancelled() {
+            let started = std::time::Instant::now();
+            if let Err(err) =
+                reconcile_once(&state, &grace, &leaked_taps, &drift_hosts, &restarts).await
+            {
                 error!(error = ?err, "reconciler iteration failed");
             }
-            ticker.tick().await;
+            metrics::histogram!(PASS_DURATION_METRIC, started.elapsed().as_secs_f64());
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.cancelled() => {}
+            }
         }
+        info!("reconciler stopped");
     })
 }
 
-async fn reconcile_once(state: &AppState) -> Result<()> {
+/// `drift_hosts` holds the hosts with a drift gauge, so one that leaves the
+/// healthy list gets its gauge zeroed instead of reporting stale drift.
+/// `restarts` holds the containers whose policy restart is still running.
+async fn reconcile_once(
+    state: &AppState,
+    grace: &Mutex<RestartGrace>,
+    leaked_taps: &Mutex<LeakedTaps>,
+    drift_hosts: &Mutex<HashSet<Uuid>>,
+    restarts: &containers::Restarts,
+) -> Result<()> {
     let hosts = state.hosts.list_healthy().await?;
-    for host in hosts {
-        match fetch_inventory(&host).await {
-            Ok(inventory) => {
-                reconcile_host(state, &host, inventory).await?;
-            }
-            Err(err) => {
-                warn!(host_id = %host.id, host_addr = %host.addr, error = ?err, "failed to fetch inventory");
-            }
+    {
+        let current: HashSet<Uuid> = hosts.iter().map(|host| host.id).collect();
+        let mut reported = drift_hosts.lock().unwrap();
+        for gone in reported.difference(&current) {
+            metrics::gauge!(DRIFT_METRIC, 0.0, "host_id" => gone.to_string());
         }
+        *reported = current;
+    }
+    // Hosts are reconciled side by side so a slow or hung agent only holds
+    // up its own slot.
+    let concurrency = env_positive(HOST_CONCURRENCY_ENV, DEFAULT_HOST_CONCURRENCY) as usize;
+    // Bounds the whole per-host pass, not just the inventory fetch: an agent
+    // that answers the fetch and then hangs on a restart or a network push
+    // would otherwise hold its slot for good.
+    let host_timeout =
+        Duration::from_secs(env_positive(HOST_TIMEOUT_ENV, DEFAULT_HOST_TIMEOUT_SECS));
+    // Hosts whose inventory could not be read; VXLAN convergence skips them.
+    let unreachable = Mutex::new(HashSet::new());
+    let unreachable_ref = &unreachable;
+    futures::stream::iter(hosts)
+        .for_each_concurrent(concurrency, |host| async move {
+            let mut fetched = false;
+            let pass = tokio::time::timeout(host_timeout, async {
+                let inventory = match fetch_inventory(&host).await {
+                    Ok(inventory) => inventory,
+                    Err(err) => {
+                        metrics::counter!(INVENTORY_FAILURES_METRIC, 1, "host_id" => host.id.to_string());
+                        // Nothing was measured, so don't keep reporting last pass's drift
+                        metrics::gauge!(DRIFT_METRIC, 0.0, "host_id" => host.id.to_string());
+                        warn!(host_id = %host.id, host_addr = %host.addr, error = ?err, "failed to fetch inventory");
+                        unreachable_ref.lock().unwrap().insert(host.id);
+                        return;
+                    }
+                };
+                fetched = true;
+                if let Err(err) = reconcile_host(state, &host, inventory, grace, leaked_taps).await
+                {
+                    error!(host_id = %host.id, host_addr = %host.addr, error = ?err, "host reconciliation failed");
+                }
+            })
+            .await;
+            if pass.is_err() {
+                metrics::counter!("manager_reconciler_host_timeouts", 1);
+                warn!(host_id = %host.id, host_addr = %host.addr, timeout_secs = host_timeout.as_secs(), fetched, "host reconciliation timed out");
+                if !fetched {
+                    metrics::counter!(INVENTORY_FAILURES_METRIC, 1, "host_id" => host.id.to_string());
+                    metrics::gauge!(DRIFT_METRIC, 0.0, "host_id" => host.id.to_string());
+                    unreachable_ref.lock().unwrap().insert(host.id);
+                }
+            }
+        })
+        .await;
+    grace.lock().unwrap().finish_pass();
+
+    // Converge VXLAN FDB peer sets with the current host registry: healthy
+    // members form a full mesh, unhealthy ones are dropped from it. Hosts
+    // that didn't answer this pass are left for the next one.
+    let unreachable = unreachable.into_inner().unwrap();
+    if let Err(err) = networks::service::converge_vxlan_peers(state, &unreachable).await {
+        warn!(error = ?err, "VXLAN peer convergence failed");
+    }
+
+    // Containers whose restart_policy wants them back up after dying.
+    if let Err(err) = containers::reconcile_containers(state, restarts).await {
+        warn!(error = ?err, "container restart-policy pass failed");
     }
 
-    // Auto-HA: when MANAGER_HA_AUTO_RESCHEDULE=1, look for hosts that have
-    // missed heartbeats long enough to be considered dead and reschedule
-    // their QEMU VMs onto healthy peers with shared-storage volumes.
+    // Hosts that stopped heartbeating: flag them and their VMs.
+    if let Err(err) = hosts::reconcile_host_health(state).await {
+        warn!(error = ?err, "host health pass failed");
+    }
+
+    // Auto-HA: when MANAGER_HA_AUTO_RESCHEDULE=1, reschedule the QEMU VMs of
+    // hosts flagged unreachable onto healthy peers with shared-storage volumes.
     // Disabled by default — operators opt in.
     if std::env::var("MANAGER_HA_AUTO_RESCHEDULE").is_ok() {
         if let Err(err) = auto_reschedule_dead_hosts(state).await {
@@ -53,26 +203,16 @@ async fn reconcile_once(state: &AppState) -> Result<()> {
     Ok(())
 }
 
-/// Auto-HA: detect dead hosts (last_seen_at older than threshold) and try
-/// to reschedule each of their QEMU VMs onto a healthy peer. Best-effort;
-/// failures are logged. Local-overlay VMs are skipped (qemu_service::
-/// reschedule refuses them).
+/// Auto-HA: try to reschedule each QEMU VM of an unreachable host onto a
+/// healthy peer. Best-effort; failures are logged. Local-overlay VMs are
+/// skipped (qemu_service::reschedule refuses them).
 async fn auto_reschedule_dead_hosts(state: &AppState) -> Result<()> {
-    // Pool every host so we can compare last_seen_at; list_healthy filters
-    // dead ones out by design. The 90s threshold matches the FC reconciler's
-    // posture of "two missed 30s heartbeats = dead enough to rebuild".
-    let all_hosts = state.hosts.list_all().await?;
-    let now = chrono::Utc::now();
-    let dead: Vec<HostRow> = all_hosts
-        .into_iter()
-        .filter(|h| {
-            (now - h.last_seen_at).num_seconds() > 90 // dead for >90s
-        })
-        .collect();
+    // Flagged by hosts::reconcile_host_health after enough missed heartbeats.
+    let dead: Vec<HostRow> = state.hosts.list_unreachable().await?;
     if dead.is_empty() {
         return Ok(());
     }
-    let healthy = state.hosts.list_healthy().await?;
+    let healthy = state.hosts.list_schedulable().await?;
     if healthy.is_empty() {
         warn!(
             dead_host_count = dead.len(),
@@ -120,9 +260,23 @@ async fn auto_reschedule_dead_hosts(state: &AppState) -> Result<()> {
     Ok(())
 }
 
-async fn reconcile_host(state: &AppState, host: &HostRow, inventory: AgentInventory) -> Result<()> {
+async fn reconcile_host(
+    state: &AppState,
+    host: &HostRow,
+    inventory: AgentInventory,
+    grace: &Mutex<RestartGrace>,
+    leaked_taps: &Mutex<LeakedTaps>,
+) -> Result<()> {
     let vms = vms::repo::list_by_host(&state.db, host.id).await?;
-    let plan = diff_host(&vms, &inventory);
+    let known_taps = vms::repo::tap_names_on_host(&state.db, host.id).await?;
+    let mut plan = diff_host(&vms, &known_taps, &inventory);
+    let drift = plan.restart.len() + plan.orphans.len() + plan.leaked_taps.len();
+    metrics::gauge!(DRIFT_METRIC, drift as f64, "host_id" => host.id.to_string());
+    plan.restart = grace.lock().unwrap().confirm(&plan.restart);
+    let leaked = leaked_taps
+        .lock()
+        .unwrap()
+        .confirm(host.id, &plan.leaked_taps, Instant::now());
     let vm_map: HashMap<Uuid, vms::repo::VmRow> =
         vms.into_iter().map(|row| (row.id, row)).collect();
 
@@ -160,6 +314,20 @@ async fn reconcile_host(state: &AppState, host: &HostRow, inventory: AgentInvent
         }
     }
 
+    // The orphan stop above only removes the tap it can infer from the VM id;
+    // these are the ones the inventory reports and no VM on the host owns.
+    for tap in leaked {
+        match delete_orphan_tap(&host.addr, &tap).await {
+            Ok(()) => {
+                metrics::counter!("manager_reconciler_leaked_taps_removed", 1);
+                info!(host_id = %host.id, %tap, "removed leaked tap");
+            }
+            Err(err) => {
+                warn!(host_id = %host.id, %tap, error = ?err, "failed to remove leaked tap");
+            }
+        }
+    }
+
     reconcile_devices(state, host, &vm_map, &inventory).await?;
     reconcile_networks(state, host).await?;
     reconcile_port_forwards(state, &vm_map).await;
@@ -171,7 +339,9 @@ async fn reconcile_host(state: &AppState, host: &HostRow, inventory: AgentInvent
 /// This ensures iptables rules are restored after an agent restart.
 async fn reconcile_port_forwards(state: &AppState, vm_map: &HashMap<Uuid, vms::repo::VmRow>) {
     for (vm_id, vm) in vm_map {
-        if vm.state != "running" || vm.guest_ip.as_ref().is_none_or(|ip| ip.is_empty()) {
+        if !vms::service::is_running_state(&vm.state)
+            || vm.guest_ip.as_ref().is_none_or(|ip| ip.is_empty())
+        {
             continue;
         }
         if let Err(e) = vms::port_forwards::service::apply_forwards(state, *vm_id).await {
@@ -182,7 +352,7 @@ async fn reconcile_port_forwards(state: &AppState, vm_map: &HashMap<Uuid, vms::r
 
 async fn reconcile_networks(state: &AppState, host: &HostRow) -> Result<()> {
     let network_repo = networks::repo::NetworkRepository::new(state.db.clone());
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
 
     // --- Single-host networks (NAT, isolated, bridged) ---
     let managed_networks = network_repo
@@ -244,6 +414,14 @@ async fn reconcile_networks(state: &AppState, host: &HostRow) -> Result<()> {
         );
         metrics::counter!("manager_reconciler_network_reprovision_attempts", 1);
 
+        let rules = match networks::service::provision_rules(&network_repo, network.id).await {
+            Ok(rules) => rules,
+            Err(err) => {
+                metrics::counter!("manager_reconciler_network_reprovision_failure", 1);
+                warn!(network_id = %network.id, error = ?err, "skipping VXLAN re-provision");
+                continue;
+            }
+        };
         let provision_url = format!(
             "{}/agent/v1/networks/provision",
             host.addr.trim_end_matches('/')
@@ -260,6 +438,7 @@ async fn reconcile_networks(state: &AppState, host: &HostRow) -> Result<()> {
             "vni": network.vni,
             "local_ip": nh.vtep_ip,
             "is_gateway": nh.is_gateway,
+            "rules": rules,
         });
 
         match client.post(&provision_url).json(&body).send().await {
@@ -384,6 +563,14 @@ async fn reconcile_single_network(
     if let Some(ref uplink) = network.uplink_interface {
         body["uplink_interface"] = serde_json::json!(uplink);
     }
+    body["rules"] = match networks::service::provision_rules(network_repo, network.id).await {
+        Ok(rules) => rules,
+        Err(err) => {
+            metrics::counter!("manager_reconciler_network_reprovision_failure", 1);
+            warn!(network_id = %network.id, error = ?err, "skipping network re-provision");
+            return;
+        }
+    };
 
     match client.post(&provision_url).json(&body).send().await {
         Ok(resp) if resp.status().is_success() => {
@@ -449,7 +636,7 @@ async fn reconcile_vm_drives(
 ) -> Result<()> {
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
 
     for drive in desired {
         let body = serde_json::json!({
@@ -484,7 +671,7 @@ async fn reconcile_vm_nics(
 ) -> Result<()> {
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
 
     for nic in desired {
         let put_body = serde_json::json!({
@@ -525,7 +712,7 @@ async fn reconcile_vm_nics(
 }
 
 async fn fetch_inventory(host: &HostRow) -> Result<AgentInventory> {
-    let response = reqwest::Client::new()
+    let response = crate::core::agent_http::client()
         .get(format!("{}/agent/v1/inventory", host.addr))
         .send()
         .await?;
@@ -542,10 +729,7 @@ async fn fetch_inventory(host: &HostRow) -> Result<AgentInventory> {
 }
 
 async fn cleanup_orphan(host_addr: &str, orphan: &OrphanArtifacts) -> Result<()> {
-    let tap = orphan
-        .tap
-        .clone()
-        .unwrap_or_else(|| format!("tap-{}", &orphan.vm_id.to_string()[..8]));
+    let tap = vms::service::tap_name_candidate(orphan.vm_id, 0);
     let fc_unit = orphan
         .scope
         .clone()
@@ -553,17 +737,19 @@ async fn cleanup_orphan(host_addr: &str, orphan: &OrphanArtifacts) -> Result<()>
     let sock = orphan
         .sockets
         .iter()
-        .find(|path| path.ends_with(".sock"))
+        .find(|path| path.ends_with(".sock") && !vms::guest_vsock::is_uds_path(path))
         .cloned()
         .unwrap_or_else(|| format!("/srv/fc/vms/{}/sock/fc.sock", orphan.vm_id));
+    let vsock_uds = vms::guest_vsock::uds_path(&sock, orphan.vm_id);
 
     let body = serde_json::json!({
         "tap": tap,
         "sock": sock,
         "fc_unit": fc_unit,
+        "vsock_uds": vsock_uds,
     });
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .post(format!("{host_addr}/agent/v1/vms/{}/stop", orphan.vm_id))
         .json(&body)
         .send()
@@ -572,6 +758,15 @@ async fn cleanup_orphan(host_addr: &str, orphan: &OrphanArtifacts) -> Result<()>
     Ok(())
 }
 
+async fn delete_orphan_tap(host_addr: &str, tap: &str) -> Result<()> {
+    crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/taps/{tap}/delete"))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct AgentInventory {
     pub scopes: Vec<String>,
@@ -590,22 +785,90 @@ pub struct SocketInventory {
 pub struct HostPlan {
     pub restart: Vec<Uuid>,
     pub orphans: Vec<OrphanArtifacts>,
+    /// Reported taps that no VM on the host owns.
+    pub leaked_taps: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct OrphanArtifacts {
     pub vm_id: Uuid,
     pub scope: Option<String>,
-    pub tap: Option<String>,
     pub sockets: Vec<String>,
 }
 
+/// Hysteresis for `HostPlan::restart`: a VM is only restarted once it has been
+/// missing its scope/socket on two consecutive passes, so a VM that is still
+/// booting (scope up, socket not yet created) isn't restarted on every tick.
+///
+/// Only what a pass actually observed carries over to the next one: a VM that
+/// was deleted, moved, or whose host couldn't be read this pass starts over.
+#[derive(Debug, Default)]
+pub struct RestartGrace {
+    /// Missing on the previous pass.
+    suspects: HashSet<Uuid>,
+    /// Missing on this pass, not yet confirmed.
+    pending: HashSet<Uuid>,
+}
+
+impl RestartGrace {
+    /// Record this pass's restart candidates for one host and return the ones
+    /// that were already missing on the previous pass.
+    pub fn confirm(&mut self, candidates: &[Uuid]) -> Vec<Uuid> {
+        let mut confirmed = Vec::new();
+        for vm_id in candidates {
+            if self.suspects.remove(vm_id) {
+                confirmed.push(*vm_id);
+            } else {
+                self.pending.insert(*vm_id);
+            }
+        }
+        confirmed
+    }
+
+    /// Close the pass: this pass's unconfirmed candidates become the next
+    /// pass's suspects, and everything not seen this pass is dropped.
+    pub fn finish_pass(&mut self) {
+        self.suspects = std::mem::take(&mut self.pending);
+    }
+}
+
+/// Grace period for `HostPlan::leaked_taps`: a tap is only deleted once no VM
+/// has owned it for [`LEAKED_TAP_GRACE`].
+#[derive(Debug, Default)]
+pub struct LeakedTaps {
+    first_seen: HashMap<(Uuid, String), Instant>,
+}
+
+impl LeakedTaps {
+    /// Record this pass's unowned taps on `host_id` and return the ones that
+    /// have been unowned for the whole grace period.
+    pub fn confirm(&mut self, host_id: Uuid, unowned: &[String], now: Instant) -> Vec<String> {
+        self.first_seen
+            .retain(|(host, tap), _| *host != host_id || unowned.contains(tap));
+        let mut confirmed = Vec::new();
+        for tap in unowned {
+            let since = *self.first_seen.entry((host_id, tap.clone())).or_insert(now);
+            if now.saturating_duration_since(since) >= LEAKED_TAP_GRACE {
+                self.first_seen.remove(&(host_id, tap.clone()));
+                confirmed.push(tap.clone());
+            }
+        }
+        confirmed
+    }
+}
+
 struct VmPresence {
     has_scope: bool,
     has_socket: bool,
 }
 
-pub fn diff_host(vms: &[vms::repo::VmRow], inventory: &AgentInventory) -> HostPlan {
+/// `known_taps` are the taps the host's VMs own (see
+/// `vms::repo::tap_names_on_host`).
+pub fn diff_host(
+    vms: &[vms::repo::VmRow],
+    known_taps: &HashSet<String>,
+    inventory: &AgentInventory,
+) -> HostPlan {
     let mut status: HashMap<Uuid, VmPresence> = vms
         .iter()
         .map(|vm| {
@@ -638,21 +901,15 @@ pub fn diff_host(vms: &[vms::repo::VmRow], inventory: &AgentInventory) -> HostPl
         }
     }
 
-    for tap in &inventory.taps {
-        match parse_tap(tap) {
-            Some(vm_id) if status.contains_key(&vm_id) => {
-                // taps are best-effort; restarts focus on scope/socket
-            }
-            Some(vm_id) => {
-                let entry = orphans.entry(vm_id).or_insert_with(|| OrphanArtifacts {
-                    vm_id,
-                    ..Default::default()
-                });
-                entry.tap = Some(tap.clone());
-            }
-            None => debug!(%tap, "ignoring tap without vm id"),
-        }
-    }
+    // Tap names are truncated ids, so ownership comes from the DB rather than
+    // the name; restarts focus on scope/socket. Only names in the manager's
+    // `tap-…` scheme are candidates, never another tool's tap.
+    let leaked_taps = inventory
+        .taps
+        .iter()
+        .filter(|tap| tap.starts_with("tap-") && !known_taps.contains(*tap))
+        .cloned()
+        .collect();
 
     for sock_inv in &inventory.sockets {
         match Uuid::parse_str(&sock_inv.vm_id) {
@@ -690,7 +947,7 @@ pub fn diff_host(vms: &[vms::repo::VmRow], inventory: &AgentInventory) -> HostPl
         if vm.vmm_kind.as_deref() == Some("qemu") {
             continue;
         }
-        if vm.state == "running" {
+        if vms::service::is_running_state(&vm.state) {
             if let Some(presence) = status.get(&vm.id) {
                 if !presence.has_scope || !presence.has_socket {
                     restart.push(vm.id);
@@ -704,6 +961,7 @@ pub fn diff_host(vms: &[vms::repo::VmRow], inventory: &AgentInventory) -> HostPl
     HostPlan {
         restart,
         orphans: orphans.into_values().collect(),
+        leaked_taps,
     }
 }
 
@@ -714,11 +972,6 @@ fn parse_scope(scope: &str) -> Option<Uuid> {
         .and_then(|id| Uuid::parse_str(id).ok())
 }
 
-fn parse_tap(tap: &str) -> Option<Uuid> {
-    tap.strip_prefix("tap-")
-        .and_then(|id| Uuid::parse_str(id).ok())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -749,11 +1002,25 @@ mod tests {
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            state_changed_at: chrono::Utc::now(),
+            started_at: None,
         }
     }
 
+    fn taps_of(vms: &[&vms::repo::VmRow]) -> HashSet<String> {
+        vms.iter().map(|vm| vm.tap.clone()).collect()
+    }
+
     #[test]
     fn diff_marks_restart_when_scope_missing() {
         let vm_id = Uuid::new_v4();
@@ -768,7 +1035,8 @@ mod tests {
             }],
         };
 
-        let plan = diff_host(&[vm], &inv);
+        let known = taps_of(&[&vm]);
+        let plan = diff_host(&[vm], &known, &inv);
         assert_eq!(plan.restart, vec![vm_id]);
         assert!(plan.orphans.is_empty());
     }
@@ -783,7 +1051,8 @@ mod tests {
             sockets: vec![],
         };
 
-        let plan = diff_host(&[vm], &inv);
+        let known = taps_of(&[&vm]);
+        let plan = diff_host(&[vm], &known, &inv);
         assert_eq!(plan.restart, vec![vm_id]);
     }
 
@@ -796,7 +1065,7 @@ mod tests {
             sockets: vec![],
         };
 
-        let plan = diff_host(&[], &inv);
+        let plan = diff_host(&[], &HashSet::new(), &inv);
         assert_eq!(plan.restart.len(), 0);
         assert_eq!(plan.orphans.len(), 1);
         assert_eq!(plan.orphans[0].vm_id, vm_id);
@@ -809,7 +1078,7 @@ mod tests {
         let vm = make_vm(vm_id);
         let inv = AgentInventory {
             scopes: vec!["weird.scope".into()],
-            taps: vec!["tap-not-a-uuid".into()],
+            taps: vec!["tap0".into()],
             sockets: vec![SocketInventory {
                 vm_id: "not-a-uuid".into(),
                 sockets: vec!["/tmp/foo.sock".into()],
@@ -817,8 +1086,114 @@ mod tests {
             }],
         };
 
-        let plan = diff_host(&[vm], &inv);
+        let known = taps_of(&[&vm]);
+        let plan = diff_host(&[vm], &known, &inv);
         assert!(plan.orphans.is_empty());
+        assert!(plan.leaked_taps.is_empty());
         assert_eq!(plan.restart, vec![vm_id]);
     }
+
+    #[test]
+    fn diff_reports_taps_no_vm_on_the_host_owns() {
+        let vm_id = Uuid::new_v4();
+        let gone_id = Uuid::new_v4();
+        let mut vm = make_vm(vm_id);
+        vm.tap = vms::service::tap_name_candidate(vm_id, 0);
+        let nic_tap = vms::service::nic_tap_name(vm_id, 1);
+        let leaked = vms::service::tap_name_candidate(gone_id, 0);
+        let mut known = taps_of(&[&vm]);
+        known.insert(nic_tap.clone());
+        let inv = AgentInventory {
+            scopes: vec![format!("fc-{vm_id}.scope")],
+            taps: vec![vm.tap.clone(), nic_tap, leaked.clone(), "tap0".into()],
+            sockets: vec![SocketInventory {
+                vm_id: vm_id.to_string(),
+                sockets: vec![vm.api_sock.clone()],
+                logs: vec![],
+            }],
+        };
+
+        let plan = diff_host(&[vm], &known, &inv);
+        assert_eq!(plan.leaked_taps, vec![leaked]);
+        assert!(plan.restart.is_empty());
+        assert!(plan.orphans.is_empty());
+    }
+
+    #[test]
+    fn leaked_tap_is_only_confirmed_after_the_grace_period() {
+        let host_id = Uuid::new_v4();
+        let tap = "tap-0123456789a".to_string();
+        let start = Instant::now();
+        let mut leaked = LeakedTaps::default();
+
+        assert!(leaked
+            .confirm(host_id, std::slice::from_ref(&tap), start)
+            .is_empty());
+        // Owned again (the create recorded its VM row): the clock resets
+        assert!(leaked
+            .confirm(host_id, &[], start + LEAKED_TAP_GRACE)
+            .is_empty());
+        let later = start + LEAKED_TAP_GRACE * 2;
+        assert!(leaked
+            .confirm(host_id, std::slice::from_ref(&tap), later)
+            .is_empty());
+        // Another host's pass leaves this host's entries alone
+        assert!(leaked.confirm(Uuid::new_v4(), &[], later).is_empty());
+        assert_eq!(
+            leaked.confirm(
+                host_id,
+                std::slice::from_ref(&tap),
+                later + LEAKED_TAP_GRACE
+            ),
+            vec![tap]
+        );
+    }
+
+    #[test]
+    fn single_missing_observation_does_not_restart() {
+        let vm_id = Uuid::new_v4();
+        let mut grace = RestartGrace::default();
+
+        let mut pass = |candidates: &[Uuid]| {
+            let confirmed = grace.confirm(candidates);
+            grace.finish_pass();
+            confirmed
+        };
+
+        // First pass: missing socket (still booting) is only noted
+        assert!(pass(&[vm_id]).is_empty());
+        // Second pass: socket showed up, suspicion cleared
+        assert!(pass(&[]).is_empty());
+        // Missing again once: still no restart
+        assert!(pass(&[vm_id]).is_empty());
+        // Missing on a second consecutive pass: restart
+        assert_eq!(pass(&[vm_id]), vec![vm_id]);
+        // Restart resets the window
+        assert!(pass(&[vm_id]).is_empty());
+    }
+
+    #[test]
+    fn a_pass_without_the_vm_resets_its_grace() {
+        let vm_id = Uuid::new_v4();
+        let mut grace = RestartGrace::default();
+
+        assert!(grace.confirm(&[vm_id]).is_empty());
+        grace.finish_pass();
+        // Its host's inventory couldn't be read (or the VM is gone): no
+        // confirm call for it this pass, so the suspicion is dropped.
+        grace.finish_pass();
+        assert!(grace.suspects.is_empty());
+        // Missing again afterwards starts a fresh window
+        assert!(grace.confirm(&[vm_id]).is_empty());
+        grace.finish_pass();
+        assert_eq!(grace.confirm(&[vm_id]), vec![vm_id]);
+    }
+
+    #[test]
+    fn tuning_env_falls_back_to_defaults() {
+        assert_eq!(parse_positive(None, DEFAULT_INTERVAL_SECS), 15);
+        assert_eq!(parse_positive(Some("0"), DEFAULT_HOST_TIMEOUT_SECS), 60);
+        assert_eq!(parse_positive(Some("fast"), DEFAULT_HOST_CONCURRENCY), 8);
+        assert_eq!(parse_positive(Some(" 30 "), DEFAULT_INTERVAL_SECS), 30);
+    }
 }
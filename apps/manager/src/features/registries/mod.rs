@@ -0,0 +1,26 @@
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
+
+use crate::features::users::middleware::require_admin;
+
+pub mod repo;
+pub mod routes;
+pub mod service;
+
+/// Needs a logged-in user (layered in `features::mod`); storing and deleting
+/// credentials is for admins.
+pub fn router() -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(routes::list)
+                .merge(post(routes::create).layer(axum::middleware::from_fn(require_admin))),
+        )
+        .route(
+            "/:id",
+            get(routes::get)
+                .merge(delete(routes::delete).layer(axum::middleware::from_fn(require_admin))),
+        )
+}
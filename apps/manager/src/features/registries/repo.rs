@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct RegistryRepository {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RegistryCredentialRow {
+    pub id: Uuid,
+    pub name: String,
+    pub server_address: Option<String>,
+    pub username: String,
+    pub encrypted_password: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct CreateParams<'a> {
+    pub owner: Uuid,
+    pub name: &'a str,
+    pub server_address: Option<&'a str>,
+    pub username: &'a str,
+    pub encrypted_password: &'a str,
+}
+
+impl RegistryRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self, owner: Uuid) -> sqlx::Result<Vec<RegistryCredentialRow>> {
+        sqlx::query_as::<_, RegistryCredentialRow>(
+            r#"SELECT * FROM registry_credential WHERE created_by_user_id = $1 ORDER BY name"#,
+        )
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get(&self, owner: Uuid, id: Uuid) -> sqlx::Result<Option<RegistryCredentialRow>> {
+        sqlx::query_as::<_, RegistryCredentialRow>(
+            r#"SELECT * FROM registry_credential WHERE id = $1 AND created_by_user_id = $2"#,
+        )
+        .bind(id)
+        .bind(owner)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn get_by_name(
+        &self,
+        owner: Uuid,
+        name: &str,
+    ) -> sqlx::Result<Option<RegistryCredentialRow>> {
+        sqlx::query_as::<_, RegistryCredentialRow>(
+            r#"SELECT * FROM registry_credential WHERE name = $1 AND created_by_user_id = $2"#,
+        )
+        .bind(name)
+        .bind(owner)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn create(&self, p: CreateParams<'_>) -> sqlx::Result<RegistryCredentialRow> {
+        sqlx::query_as::<_, RegistryCredentialRow>(
+            r#"
+            INSERT INTO registry_credential
+                (name, server_address, username, encrypted_password, created_by_user_id)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(p.name)
+        .bind(p.server_address)
+        .bind(p.username)
+        .bind(p.encrypted_password)
+        .bind(p.owner)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn delete(&self, owner: Uuid, id: Uuid) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"DELETE FROM registry_credential WHERE id = $1 AND created_by_user_id = $2"#,
+        )
+        .bind(id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
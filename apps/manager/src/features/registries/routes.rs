@@ -0,0 +1,98 @@
+use crate::core::error::ApiError;
+use crate::features::users::audit::Actor;
+use crate::features::users::repo::AuthenticatedUser;
+use crate::AppState;
+use axum::{extract::Path, http::StatusCode, Extension, Json};
+use nexus_types::{
+    AuditAction, CreateRegistryReq, ListRegistriesResp, OkResponse, RegistryCredential,
+};
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/v1/registries",
+    request_body = CreateRegistryReq,
+    responses(
+        (status = 201, description = "Registry credential stored", body = RegistryCredential),
+        (status = 400, description = "Invalid request, or REGISTRY_ENCRYPTION_KEY is not set"),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Admin role required"),
+        (status = 409, description = "You already have a registry with this name"),
+    ),
+    tag = "Registries"
+)]
+pub async fn create(
+    Extension(st): Extension<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    actor: Actor,
+    Json(req): Json<CreateRegistryReq>,
+) -> Result<(StatusCode, Json<RegistryCredential>), ApiError> {
+    let result = super::service::create(&st, user.id, req).await;
+    let id = result.as_ref().ok().map(|created| created.id);
+    actor
+        .record(AuditAction::CreateRegistry, ("registry", id), &result)
+        .await;
+    Ok((StatusCode::CREATED, Json(result?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/registries",
+    responses(
+        (status = 200, description = "The caller's stored registry credentials (without passwords)", body = ListRegistriesResp),
+        (status = 401, description = "Not logged in"),
+    ),
+    tag = "Registries"
+)]
+pub async fn list(
+    Extension(st): Extension<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+) -> Result<Json<ListRegistriesResp>, ApiError> {
+    let items = super::service::list(&st, user.id).await?;
+    Ok(Json(ListRegistriesResp { items }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/registries/{id}",
+    params(("id" = Uuid, Path, description = "Registry credential id")),
+    responses(
+        (status = 200, description = "Registry credential (without password)", body = RegistryCredential),
+        (status = 401, description = "Not logged in"),
+        (status = 404, description = "Registry not found, or stored by another user"),
+    ),
+    tag = "Registries"
+)]
+pub async fn get(
+    Extension(st): Extension<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<RegistryCredential>, ApiError> {
+    Ok(Json(super::service::get(&st, user.id, id).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/registries/{id}",
+    params(("id" = Uuid, Path, description = "Registry credential id")),
+    responses(
+        (status = 200, description = "Registry credential deleted", body = OkResponse),
+        (status = 401, description = "Not logged in"),
+        (status = 403, description = "Admin role required"),
+        (status = 404, description = "Registry not found, or stored by another user"),
+    ),
+    tag = "Registries"
+)]
+pub async fn delete(
+    Extension(st): Extension<AppState>,
+    Extension(user): Extension<AuthenticatedUser>,
+    actor: Actor,
+    Path(id): Path<Uuid>,
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::delete(&st, user.id, id).await;
+    actor
+        .record(AuditAction::DeleteRegistry, ("registry", id), &result)
+        .await;
+    result?;
+    Ok(Json(OkResponse::default()))
+}
@@ -0,0 +1,196 @@
+use super::repo::{CreateParams, RegistryCredentialRow, RegistryRepository};
+use crate::core::error::{bad_request, conflict, not_found};
+use crate::features::sso::crypto;
+use crate::AppState;
+use anyhow::{bail, Result};
+use nexus_types::{CreateRegistryReq, RegistryAuth, RegistryCredential};
+use uuid::Uuid;
+
+/// Wire form of a stored credential. The password is deliberately dropped.
+fn row_to_wire(row: RegistryCredentialRow) -> RegistryCredential {
+    RegistryCredential {
+        id: row.id,
+        name: row.name,
+        server_address: row.server_address,
+        username: row.username,
+        created_at: row.created_at,
+    }
+}
+
+/// The key registry passwords are sealed with. It is kept apart from the SSO
+/// key, and stored credentials stay off until it is set.
+fn encryption_key(st: &AppState) -> Result<&[u8; 32]> {
+    st.registry_encryption_key.as_ref().ok_or_else(|| {
+        bad_request("Stored registry credentials are disabled: REGISTRY_ENCRYPTION_KEY must be set")
+            .into()
+    })
+}
+
+fn decrypt_auth(row: &RegistryCredentialRow, key: &[u8; 32]) -> Result<RegistryAuth> {
+    Ok(RegistryAuth {
+        username: row.username.clone(),
+        password: crypto::decrypt(&row.encrypted_password, key)?,
+        server_address: row.server_address.clone(),
+    })
+}
+
+pub async fn create(
+    st: &AppState,
+    owner: Uuid,
+    req: CreateRegistryReq,
+) -> Result<RegistryCredential> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        bail!(bad_request("Registry name is required"));
+    }
+    if req.username.is_empty() || req.password.is_empty() {
+        bail!(bad_request("Registry username and password are required"));
+    }
+
+    let encrypted_password = crypto::encrypt(&req.password, encryption_key(st)?)?;
+    let repo = RegistryRepository::new(st.db.clone());
+    match repo
+        .create(CreateParams {
+            owner,
+            name,
+            server_address: req.server_address.as_deref(),
+            username: &req.username,
+            encrypted_password: &encrypted_password,
+        })
+        .await
+    {
+        Ok(row) => Ok(row_to_wire(row)),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+            bail!(conflict(format!("Registry '{}' already exists", name)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn list(st: &AppState, owner: Uuid) -> Result<Vec<RegistryCredential>> {
+    let rows = RegistryRepository::new(st.db.clone()).list(owner).await?;
+    Ok(rows.into_iter().map(row_to_wire).collect())
+}
+
+/// Another user's credential is reported as not found.
+pub async fn get(st: &AppState, owner: Uuid, id: Uuid) -> Result<RegistryCredential> {
+    match RegistryRepository::new(st.db.clone())
+        .get(owner, id)
+        .await?
+    {
+        Some(row) => Ok(row_to_wire(row)),
+        None => bail!(not_found("Registry not found")),
+    }
+}
+
+pub async fn delete(st: &AppState, owner: Uuid, id: Uuid) -> Result<()> {
+    if !RegistryRepository::new(st.db.clone())
+        .delete(owner, id)
+        .await?
+    {
+        bail!(not_found("Registry not found"));
+    }
+    Ok(())
+}
+
+/// Pick the credentials for a pull: a named stored credential wins over
+/// inline `registry_auth`, and an unknown name is an error rather than an
+/// anonymous pull. Only `user_id`'s own credentials resolve; an anonymous
+/// caller has none.
+pub async fn resolve_auth(
+    st: &AppState,
+    user_id: Option<Uuid>,
+    registry: Option<&str>,
+    inline: Option<RegistryAuth>,
+) -> Result<Option<RegistryAuth>> {
+    let Some(name) = registry else {
+        return Ok(inline);
+    };
+    let row = match user_id {
+        Some(owner) => {
+            RegistryRepository::new(st.db.clone())
+                .get_by_name(owner, name)
+                .await?
+        }
+        None => None,
+    };
+    match row {
+        Some(row) => Ok(Some(decrypt_auth(&row, encryption_key(st)?)?)),
+        None => bail!(not_found(format!(
+            "Registry credential '{}' not found",
+            name
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_password_decrypts_but_is_never_serialized() {
+        let key = crypto::derive_key("test-key");
+        let row = RegistryCredentialRow {
+            id: Uuid::new_v4(),
+            name: "ghcr".into(),
+            server_address: Some("ghcr.io".into()),
+            username: "bot".into(),
+            encrypted_password: crypto::encrypt("s3cret", &key).unwrap(),
+            created_at: chrono::Utc::now(),
+        };
+
+        let auth = decrypt_auth(&row, &key).unwrap();
+        assert_eq!(auth.username, "bot");
+        assert_eq!(auth.password, "s3cret");
+        assert_eq!(auth.server_address.as_deref(), Some("ghcr.io"));
+
+        let json = serde_json::to_string(&row_to_wire(row)).unwrap();
+        assert!(!json.contains("password"));
+        assert!(!json.contains("s3cret"));
+    }
+
+    async fn user(pool: &sqlx::PgPool, name: &str) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, role) VALUES ($1, 'x', 'admin') RETURNING id",
+        )
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn credentials_are_only_visible_to_their_owner(pool: sqlx::PgPool) {
+        let owner = user(&pool, "owner").await;
+        let other = user(&pool, "other").await;
+        let repo = RegistryRepository::new(pool.clone());
+        let row = repo
+            .create(CreateParams {
+                owner,
+                name: "ghcr",
+                server_address: None,
+                username: "bot",
+                encrypted_password: "sealed",
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(repo.list(owner).await.unwrap().len(), 1);
+        assert!(repo.list(other).await.unwrap().is_empty());
+        assert!(repo.get(other, row.id).await.unwrap().is_none());
+        assert!(repo.get_by_name(other, "ghcr").await.unwrap().is_none());
+        assert!(!repo.delete(other, row.id).await.unwrap());
+        // Names are per user
+        repo.create(CreateParams {
+            owner: other,
+            name: "ghcr",
+            server_address: None,
+            username: "bot",
+            encrypted_password: "sealed",
+        })
+        .await
+        .unwrap();
+        assert!(repo.delete(owner, row.id).await.unwrap());
+    }
+}
@@ -0,0 +1,87 @@
+//! Firecracker only promises to load snapshots taken by its own release, so
+//! each snapshot records the version it was taken with and restores compare
+//! it against what the target host's agent reports.
+
+use anyhow::{Context, Result};
+use std::time::Duration;
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A restore refused because the snapshot and the host disagree on the
+/// Firecracker release. Survives `.context(..)`, so check it with
+/// [`is_incompatible`].
+#[derive(Debug)]
+pub struct IncompatibleSnapshot {
+    pub snapshot_version: String,
+    pub host_version: String,
+}
+
+impl std::fmt::Display for IncompatibleSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "snapshot conflict: taken with Firecracker {} but the host runs {}; \
+             Firecracker only loads snapshots from its own release (force=true restores anyway)",
+            self.snapshot_version, self.host_version
+        )
+    }
+}
+
+impl std::error::Error for IncompatibleSnapshot {}
+
+pub fn is_incompatible(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<IncompatibleSnapshot>().is_some()
+}
+
+/// Compare the recorded and the host's version. Either side being unknown
+/// (snapshots that pre-date the column, agents without Firecracker info)
+/// lets the restore through.
+pub fn check(
+    snapshot_version: Option<&str>,
+    host_version: Option<&str>,
+) -> Result<(), IncompatibleSnapshot> {
+    match (snapshot_version, host_version) {
+        (Some(snapshot), Some(host)) if snapshot != host => Err(IncompatibleSnapshot {
+            snapshot_version: snapshot.to_string(),
+            host_version: host.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The Firecracker version from the agent's `/agent/v1/health`, `None` when
+/// the agent has no Firecracker or predates the field.
+pub async fn host_firecracker_version(host_addr: &str) -> Result<Option<String>> {
+    let health: serde_json::Value = crate::core::agent_http::client()
+        .get(format!("{host_addr}/agent/v1/health"))
+        .timeout(HEALTH_TIMEOUT)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("querying agent health")?
+        .json()
+        .await
+        .context("decoding agent health")?;
+    Ok(health
+        .pointer("/firecracker/version")
+        .and_then(|v| v.as_str())
+        .map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_known_mismatches_are_refused() {
+        assert!(check(Some("1.7.0"), Some("1.7.0")).is_ok());
+        assert!(check(None, Some("1.7.0")).is_ok());
+        assert!(check(Some("1.7.0"), None).is_ok());
+
+        let err = check(Some("1.6.0"), Some("1.7.0")).unwrap_err();
+        assert_eq!(err.snapshot_version, "1.6.0");
+        let err = anyhow::Error::from(err).context("restoring snapshot");
+        assert!(is_incompatible(&err));
+        assert!(format!("{err:#}").contains("host runs 1.7.0"));
+    }
+}
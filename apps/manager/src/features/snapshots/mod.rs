@@ -3,11 +3,13 @@ use axum::{
     Router,
 };
 
+pub mod compat;
 pub mod repo;
 pub mod routes;
 
 pub fn router() -> Router {
     Router::new()
+        .route("/", get(routes::list))
         .route("/:id", get(routes::get).delete(routes::delete))
         .route("/:id/instantiate", post(routes::instantiate))
 }
@@ -1,3 +1,4 @@
+use nexus_types::ListSnapshotsParams;
 use serde::Serialize;
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -14,10 +15,91 @@ pub struct SnapshotRow {
     pub parent_id: Option<Uuid>,
     pub track_dirty_pages: bool,
     pub name: Option<String>,
+    /// Firecracker release the snapshot was taken with, as the agent's
+    /// health endpoint reported it.
+    pub firecracker_version: Option<String>,
+    pub vcpu: Option<i32>,
+    pub mem_mib: Option<i32>,
+    pub cpu_template: Option<String>,
+    /// The memory file is `mem.fc.zst` and must be decompressed to restore.
+    pub compressed: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SnapshotSort {
+    #[default]
+    CreatedAt,
+    SizeBytes,
+}
+
+/// What [`SnapshotRepository::list`] returns and in which order. The default
+/// is every snapshot, newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFilter {
+    pub vm_id: Option<Uuid>,
+    pub snapshot_type: Option<String>,
+    /// Case-insensitive substring of the name.
+    pub name: Option<String>,
+    pub sort: SnapshotSort,
+    pub descending: bool,
+}
+
+impl Default for SnapshotFilter {
+    fn default() -> Self {
+        Self {
+            vm_id: None,
+            snapshot_type: None,
+            name: None,
+            sort: SnapshotSort::CreatedAt,
+            descending: true,
+        }
+    }
+}
+
+impl SnapshotFilter {
+    /// Validates the list endpoint's query; the error names the bad value.
+    pub fn from_params(params: ListSnapshotsParams) -> anyhow::Result<Self> {
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let sort = match params.sort.as_deref().map(str::trim) {
+            None | Some("") | Some("created_at") => SnapshotSort::CreatedAt,
+            Some("size_bytes") => SnapshotSort::SizeBytes,
+            Some(other) => {
+                anyhow::bail!("invalid sort '{other}': must be 'created_at' or 'size_bytes'")
+            }
+        };
+        let descending = match params.order.as_deref().map(str::trim) {
+            None | Some("") | Some("desc") => true,
+            Some("asc") => false,
+            Some(other) => anyhow::bail!("invalid order '{other}': must be 'asc' or 'desc'"),
+        };
+        Ok(Self {
+            vm_id: params.vm_id,
+            snapshot_type: non_empty(params.snapshot_type),
+            name: non_empty(params.name),
+            sort,
+            descending,
+        })
+    }
+}
+
+/// Makes `%`, `_` and `\` match themselves in an `ILIKE ... ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 #[derive(Clone)]
 pub struct SnapshotRepository {
     pool: PgPool,
@@ -31,9 +113,9 @@ impl SnapshotRepository {
     pub async fn insert(&self, new_row: &NewSnapshotRow) -> sqlx::Result<SnapshotRow> {
         sqlx::query_as::<_, SnapshotRow>(
             r#"
-            INSERT INTO snapshot (id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, created_at, updated_at
+            INSERT INTO snapshot (id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, firecracker_version, vcpu, mem_mib, cpu_template, compressed)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+            RETURNING id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, firecracker_version, vcpu, mem_mib, cpu_template, compressed, created_at, updated_at
             "#,
         )
         .bind(new_row.id)
@@ -46,28 +128,46 @@ impl SnapshotRepository {
         .bind(new_row.parent_id)
         .bind(new_row.track_dirty_pages)
         .bind(&new_row.name)
+        .bind(&new_row.firecracker_version)
+        .bind(new_row.vcpu)
+        .bind(new_row.mem_mib)
+        .bind(&new_row.cpu_template)
+        .bind(new_row.compressed)
         .fetch_one(&self.pool)
         .await
     }
 
-    pub async fn list_for_vm(&self, vm_id: Uuid) -> sqlx::Result<Vec<SnapshotRow>> {
-        sqlx::query_as::<_, SnapshotRow>(
+    /// Snapshots matching `filter`, ordered by its sort. A `vm_id` no VM has
+    /// just matches nothing.
+    pub async fn list(&self, filter: &SnapshotFilter) -> sqlx::Result<Vec<SnapshotRow>> {
+        // The sort column and direction come from enums, never from input
+        let column = match filter.sort {
+            SnapshotSort::CreatedAt => "created_at",
+            SnapshotSort::SizeBytes => "size_bytes",
+        };
+        let direction = if filter.descending { "DESC" } else { "ASC" };
+        let sql = format!(
             r#"
-            SELECT id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, created_at, updated_at
+            SELECT id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, firecracker_version, vcpu, mem_mib, cpu_template, compressed, created_at, updated_at
             FROM snapshot
-            WHERE vm_id = $1
-            ORDER BY created_at DESC
-            "#,
-        )
-        .bind(vm_id)
-        .fetch_all(&self.pool)
-        .await
+            WHERE ($1::uuid IS NULL OR vm_id = $1)
+              AND ($2::text IS NULL OR lower(snapshot_type) = lower($2))
+              AND ($3::text IS NULL OR name ILIKE '%' || $3 || '%' ESCAPE '\')
+            ORDER BY {column} {direction}, id {direction}
+            "#
+        );
+        sqlx::query_as::<_, SnapshotRow>(&sql)
+            .bind(filter.vm_id)
+            .bind(&filter.snapshot_type)
+            .bind(filter.name.as_deref().map(escape_like))
+            .fetch_all(&self.pool)
+            .await
     }
 
     pub async fn get(&self, id: Uuid) -> sqlx::Result<SnapshotRow> {
         sqlx::query_as::<_, SnapshotRow>(
             r#"
-            SELECT id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, created_at, updated_at
+            SELECT id, vm_id, snapshot_path, mem_path, size_bytes, state, snapshot_type, parent_id, track_dirty_pages, name, firecracker_version, vcpu, mem_mib, cpu_template, compressed, created_at, updated_at
             FROM snapshot
             WHERE id = $1
             "#,
@@ -77,7 +177,30 @@ impl SnapshotRepository {
         .await
     }
 
+    /// VMs created from the snapshot that still point at it.
+    pub async fn vms_using(&self, id: Uuid) -> sqlx::Result<Vec<Uuid>> {
+        sqlx::query_scalar("SELECT id FROM vm WHERE source_snapshot_id = $1 ORDER BY id")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Snapshots taken on top of this one (diff chains).
+    pub async fn children(&self, id: Uuid) -> sqlx::Result<Vec<Uuid>> {
+        sqlx::query_scalar("SELECT id FROM snapshot WHERE parent_id = $1 ORDER BY id")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Deletes the row, first clearing `source_snapshot_id` on any VM still
+    /// pointing at it (the foreign key has no `ON DELETE`).
     pub async fn delete(&self, id: Uuid) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE vm SET source_snapshot_id = NULL WHERE source_snapshot_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
         sqlx::query(
             r#"
             DELETE FROM snapshot
@@ -85,9 +208,9 @@ impl SnapshotRepository {
             "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
-        Ok(())
+        tx.commit().await
     }
 }
 
@@ -125,6 +248,11 @@ pub struct NewSnapshotRow {
     pub parent_id: Option<Uuid>,
     pub track_dirty_pages: bool,
     pub name: Option<String>,
+    pub firecracker_version: Option<String>,
+    pub vcpu: Option<i32>,
+    pub mem_mib: Option<i32>,
+    pub cpu_template: Option<String>,
+    pub compressed: bool,
 }
 
 #[cfg(test)]
@@ -143,6 +271,11 @@ mod tests {
             parent_id: None,
             track_dirty_pages: false,
             name: Some("nightly".into()),
+            firecracker_version: Some("1.7.0".into()),
+            vcpu: Some(2),
+            mem_mib: Some(1024),
+            cpu_template: None,
+            compressed: false,
         }
     }
 
@@ -180,6 +313,11 @@ mod tests {
             parent_id: Some(parent),
             track_dirty_pages: true,
             name: None,
+            firecracker_version: None,
+            vcpu: None,
+            mem_mib: None,
+            cpu_template: None,
+            compressed: false,
         };
 
         assert_eq!(row.snapshot_type, "Diff");
@@ -203,6 +341,11 @@ mod tests {
             parent_id: None,
             track_dirty_pages: false,
             name: Some("snap-a".into()),
+            firecracker_version: Some("1.7.0".into()),
+            vcpu: Some(2),
+            mem_mib: Some(1024),
+            cpu_template: None,
+            compressed: false,
             created_at: now,
             updated_at: now,
         };
@@ -214,4 +357,37 @@ mod tests {
         assert_eq!(copy.created_at, now);
         assert_eq!(copy.updated_at, now);
     }
+
+    #[test]
+    fn list_filter_defaults_to_newest_first_and_rejects_unknown_sorts() {
+        assert_eq!(
+            SnapshotFilter::from_params(ListSnapshotsParams::default()).unwrap(),
+            SnapshotFilter::default()
+        );
+        assert!(SnapshotFilter::default().descending);
+
+        let filter = SnapshotFilter::from_params(ListSnapshotsParams {
+            snapshot_type: Some(" ".into()),
+            name: Some(" nightly ".into()),
+            sort: Some("size_bytes".into()),
+            order: Some("asc".into()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(filter.snapshot_type, None);
+        assert_eq!(filter.name.as_deref(), Some("nightly"));
+        assert_eq!(filter.sort, SnapshotSort::SizeBytes);
+        assert!(!filter.descending);
+
+        for (sort, order) in [("name", "desc"), ("created_at", "up")] {
+            assert!(SnapshotFilter::from_params(ListSnapshotsParams {
+                sort: Some(sort.into()),
+                order: Some(order.into()),
+                ..Default::default()
+            })
+            .is_err());
+        }
+
+        assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+    }
 }
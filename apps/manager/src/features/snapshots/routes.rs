@@ -1,15 +1,44 @@
+use crate::core::agent_http::FaultExt;
+use crate::features::users::audit::{status_outcome, Actor};
 use crate::AppState;
-use axum::{extract::Path, http::StatusCode, Extension, Json};
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension, Json,
+};
+use futures::StreamExt;
 use nexus_types::{
-    CreateSnapshotRequest, CreateSnapshotResponse, GetSnapshotResponse, InstantiateSnapshotReq,
-    InstantiateSnapshotResp, ListSnapshotsResponse, OkResponse, Snapshot, SnapshotPathParams,
-    VmPathParams,
+    AuditAction, CreateSnapshotRequest, CreateSnapshotResponse, DeleteSnapshotResponse,
+    GetSnapshotResponse, HostSnapshotAllResp, HostSnapshotResult, InstantiateSnapshotReq,
+    InstantiateSnapshotResp, ListSnapshotsParams, ListSnapshotsResponse, Snapshot,
+    SnapshotPathParams, VmPathParams,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use utoipa::IntoParams;
 use uuid::Uuid;
 
-use super::repo::{NewSnapshotRow, SnapshotRepository};
+use super::compat;
+use super::repo::{NewSnapshotRow, SnapshotFilter, SnapshotRepository};
+use crate::features::vms::service::{is_running_state, PauseGuard};
+
+/// VMs `POST /v1/hosts/{id}/snapshot-all` snapshots at once. Each one is
+/// paused while its memory is written, so this also bounds how many guests
+/// are frozen together and how much the host's disk is hit.
+const SNAPSHOT_ALL_CONCURRENCY: usize = 4;
+
+/// Ceiling on writing a snapshot, which dumps the VM's whole memory.
+const SNAPSHOT_CREATE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(900);
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InstantiateSnapshotParams {
+    /// Restore even when the host runs a different Firecracker release than
+    /// the one the snapshot was taken with.
+    #[serde(default)]
+    pub force: bool,
+}
 
 /// Build the on-disk path where QEMU snapshot state is written. Lives under
 /// the VM's storage dir so cleanup follows the same path FC snapshots use.
@@ -39,9 +68,10 @@ async fn create_qemu_snapshot(
         let _ = tokio::fs::create_dir_all(parent).await;
     }
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let resp = client
         .post(format!("{}/agent/v1/vmm/{}/snapshot", vm.host_addr, vm.id))
+        .timeout(SNAPSHOT_CREATE_TIMEOUT)
         .json(&json!({
             "vmm_kind": "qemu",
             "state_path": state_path,
@@ -75,6 +105,11 @@ async fn create_qemu_snapshot(
         parent_id: None,
         track_dirty_pages: false,
         name: Some(snapshot_name.clone()),
+        firecracker_version: None,
+        vcpu: Some(vm.vcpu),
+        mem_mib: Some(vm.mem_mib),
+        cpu_template: None,
+        compressed: false,
     };
     let row = st.snapshots.insert(&new_row).await.map_err(|err| {
         tracing::error!(vm_id=%vm.id, error=?err, "insert qemu snapshot row");
@@ -119,9 +154,9 @@ async fn snapshot_vmm_kind(db: &sqlx::PgPool, snapshot_id: Uuid) -> String {
 /// covered by unit tests without spinning up a VM or agent.
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct AgentSnapshotUrls {
-    vm_url: String,
     snapshot_url: String,
     prepare_url: String,
+    compress_url: String,
     machine_config_url: String,
 }
 
@@ -133,9 +168,9 @@ fn build_agent_snapshot_urls(host_addr: &str, vm_id: Uuid, api_sock: &str) -> Ag
     let base = format!("{host_addr}/agent/v1/vms/{vm_id}");
     let qs = format!("?sock={}", urlencoding::encode(api_sock));
     AgentSnapshotUrls {
-        vm_url: format!("{base}/proxy/vm{qs}"),
         snapshot_url: format!("{base}/proxy/snapshot/create{qs}"),
         prepare_url: format!("{base}/snapshots/prepare"),
+        compress_url: format!("{base}/snapshots/compress"),
         machine_config_url: format!("{base}/proxy/machine-config{qs}"),
     }
 }
@@ -234,7 +269,9 @@ fn resolve_instantiate_name(
     ),
     responses(
         (status = 200, description = "Snapshot created", body = CreateSnapshotResponse),
+        (status = 400, description = "compress requested for a Diff or QEMU snapshot"),
         (status = 404, description = "VM not found"),
+        (status = 409, description = "VM is neither running nor paused, or is running with pause off"),
         (status = 500, description = "Failed to record snapshot"),
         (status = 502, description = "Agent interaction failed"),
     ),
@@ -242,10 +279,89 @@ fn resolve_instantiate_name(
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id: vm_id }): Path<VmPathParams>,
-    _body: Option<Json<CreateSnapshotRequest>>,
+    body: Option<Json<CreateSnapshotRequest>>,
+) -> Result<Json<CreateSnapshotResponse>, StatusCode> {
+    create_audited(&st, &actor, vm_id, body.map(|Json(req)| req)).await
+}
+
+async fn create_audited(
+    st: &AppState,
+    actor: &Actor,
+    vm_id: Uuid,
+    payload: Option<CreateSnapshotRequest>,
+) -> Result<Json<CreateSnapshotResponse>, StatusCode> {
+    let result = create_snapshot(st, vm_id, payload).await;
+    let details = result
+        .as_ref()
+        .ok()
+        .map(|Json(resp)| json!({"snapshot_id": resp.id, "name": &resp.name}));
+    actor
+        .record_with(
+            AuditAction::CreateVmSnapshot,
+            ("vm", vm_id),
+            details,
+            &result,
+        )
+        .await;
+    result
+}
+
+/// Snapshots each VM with the default options (pausing it for the snapshot),
+/// [`SNAPSHOT_ALL_CONCURRENCY`] at a time, auditing each like
+/// `POST /v1/vms/{id}/snapshots`.
+pub(crate) async fn snapshot_vms(
+    st: &AppState,
+    actor: &Actor,
+    vm_ids: Vec<Uuid>,
+) -> HostSnapshotAllResp {
+    let results: Vec<HostSnapshotResult> = futures::stream::iter(vm_ids)
+        .map(|vm_id| async move {
+            match create_audited(st, actor, vm_id, None).await {
+                Ok(Json(resp)) => HostSnapshotResult {
+                    vm_id,
+                    ok: true,
+                    snapshot_id: Some(resp.id),
+                    error: None,
+                },
+                Err(status) => HostSnapshotResult {
+                    vm_id,
+                    ok: false,
+                    snapshot_id: None,
+                    error: Some(status.to_string()),
+                },
+            }
+        })
+        .buffered(SNAPSHOT_ALL_CONCURRENCY)
+        .collect()
+        .await;
+    HostSnapshotAllResp {
+        snapshot_ids: results.iter().filter_map(|r| r.snapshot_id).collect(),
+        results,
+    }
+}
+
+/// Whether the snapshot should pause the VM itself (and resume it after).
+/// An already paused VM is left paused; Firecracker can't snapshot a running
+/// VM, so one with `pause` off is refused.
+fn snapshot_pauses_vm(state: &str, pause: bool) -> Result<bool, &'static str> {
+    if state == "paused" {
+        Ok(false)
+    } else if !is_running_state(state) {
+        Err("vm must be running or paused to snapshot")
+    } else if pause {
+        Ok(true)
+    } else {
+        Err("vm is running and pause is off; pause it first")
+    }
+}
+
+async fn create_snapshot(
+    st: &AppState,
+    vm_id: Uuid,
+    payload: Option<CreateSnapshotRequest>,
 ) -> Result<Json<CreateSnapshotResponse>, StatusCode> {
-    let payload = _body.map(|Json(req)| req);
     let vm = crate::features::vms::repo::get(&st.db, vm_id)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
@@ -253,8 +369,15 @@ pub async fn create(
     // Snapshots are per-backend. QEMU goes through the pluggable VMM route
     // which speaks QMP; Firecracker keeps using the legacy REST path below.
     let vmm_kind = vm_vmm_kind(&st.db, vm.id).await;
+    let compress = payload.as_ref().and_then(|p| p.compress).unwrap_or(false);
+    if compress
+        && (vmm_kind == "qemu"
+            || payload.as_ref().and_then(|p| p.snapshot_type.as_deref()) == Some("Diff"))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
     if vmm_kind == "qemu" {
-        return create_qemu_snapshot(&st, &vm, payload).await;
+        return create_qemu_snapshot(st, &vm, payload).await;
     }
 
     let snapshot_id = Uuid::new_v4();
@@ -262,7 +385,7 @@ pub async fn create(
         payload.as_ref().and_then(|p| p.name.as_deref()),
         snapshot_id,
     );
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let urls = build_agent_snapshot_urls(&vm.host_addr, vm.id, &vm.api_sock);
 
     let snapshot_type =
@@ -273,14 +396,34 @@ pub async fn create(
         .and_then(|p| p.track_dirty_pages)
         .unwrap_or(false);
 
-    client
-        .patch(&urls.vm_url)
-        .json(&json!({"state": "Paused"}))
-        .send()
-        .await
-        .map_err(|_| StatusCode::BAD_GATEWAY)?
-        .error_for_status()
-        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+    // Recorded so a later restore can refuse another Firecracker release.
+    let firecracker_version = match compat::host_firecracker_version(&vm.host_addr).await {
+        Ok(version) => version,
+        Err(err) => {
+            tracing::warn!(vm_id = %vm.id, error = ?err, "could not read host Firecracker version");
+            None
+        }
+    };
+    let cpu_template: Option<String> =
+        sqlx::query_scalar(r#"SELECT cpu_template FROM vm WHERE id = $1"#)
+            .bind(vm.id)
+            .fetch_one(&st.db)
+            .await
+            .ok()
+            .flatten();
+
+    let pause = payload.as_ref().and_then(|p| p.pause).unwrap_or(true);
+    let paused = match snapshot_pauses_vm(&vm.state, pause) {
+        Ok(true) => Some(PauseGuard::pause(st, vm.id).await.map_err(|err| {
+            tracing::error!(vm_id = %vm.id, error = ?err, "failed to pause vm for snapshot");
+            StatusCode::BAD_GATEWAY
+        })?),
+        Ok(false) => None,
+        Err(reason) => {
+            tracing::warn!(vm_id = %vm.id, state = %vm.state, reason, "refusing snapshot");
+            return Err(StatusCode::CONFLICT);
+        }
+    };
 
     if track_dirty_pages {
         // ensure Firecracker tracking enabled before diff snapshot
@@ -320,20 +463,17 @@ pub async fn create(
 
     let snapshot_result = client
         .put(&urls.snapshot_url)
+        .timeout(SNAPSHOT_CREATE_TIMEOUT)
         .json(&create_payload)
         .send()
         .await
         .map_err(|_| StatusCode::BAD_GATEWAY)?
         .error_for_status();
 
-    let resume_result = client
-        .patch(&urls.vm_url)
-        .json(&json!({"state": "Resumed"}))
-        .send()
-        .await;
-
-    if let Err(err) = resume_result.and_then(|resp| resp.error_for_status()) {
-        tracing::warn!(vm_id = %vm.id, error = %err, "failed to resume vm after snapshot");
+    if let Some(paused) = paused {
+        if let Err(err) = paused.resume().await {
+            tracing::warn!(vm_id = %vm.id, error = ?err, "failed to resume vm after snapshot");
+        }
     }
 
     snapshot_result.map_err(|_| StatusCode::BAD_GATEWAY)?;
@@ -350,8 +490,51 @@ pub async fn create(
         .await
         .map_err(|_| StatusCode::BAD_GATEWAY)?;
 
-    let total_size =
-        combined_snapshot_size_i64(sizes_resp.snapshot_size_bytes, sizes_resp.mem_size_bytes);
+    // A diff snapshot's storage cost is its delta file alone; the memory it
+    // restores from belongs to the parent.
+    let mut mem_path = resolve_storage_mem_path(&snapshot_type, sizes_resp.mem_path.as_deref());
+    let mut mem_size_bytes = if snapshot_type == "Diff" {
+        None
+    } else {
+        sizes_resp.mem_size_bytes
+    };
+    if compress {
+        let packed: reqwest::Result<AgentMemFileResponse> = async {
+            client
+                .post(&urls.compress_url)
+                .json(&json!({ "snapshot_id": snapshot_id }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+        }
+        .await;
+        let packed = match packed {
+            Ok(packed) => packed,
+            Err(err) => {
+                tracing::error!(vm_id = %vm.id, %snapshot_id, error = %err, "memory file compression failed");
+                // No row will point at the files, so nothing else would remove them.
+                if let Err(err) = delete_snapshot_dir(&vm.host_addr, vm.id, snapshot_id).await {
+                    tracing::warn!(vm_id = %vm.id, %snapshot_id, error = ?err, "failed to remove snapshot files");
+                }
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+        };
+        tracing::info!(
+            vm_id = %vm.id,
+            %snapshot_id,
+            raw_bytes = ?mem_size_bytes,
+            compressed_bytes = ?packed.mem_size_bytes,
+            "compressed snapshot memory file"
+        );
+        mem_path = packed.mem_path;
+        mem_size_bytes = packed.mem_size_bytes;
+    }
+    if sizes_resp.snapshot_size_bytes.is_none() {
+        tracing::warn!(vm_id = %vm.id, %snapshot_id, "snapshot file missing after create; size unknown");
+    }
+    let total_size = combined_snapshot_size_i64(sizes_resp.snapshot_size_bytes, mem_size_bytes);
 
     let repo: SnapshotRepository = st.snapshots.clone();
     let row = repo
@@ -359,13 +542,18 @@ pub async fn create(
             id: snapshot_id,
             vm_id,
             snapshot_path: sizes_resp.snapshot_path,
-            mem_path: resolve_storage_mem_path(&snapshot_type, sizes_resp.mem_path.as_deref()),
+            mem_path,
             size_bytes: total_size,
             state: "available".into(),
             snapshot_type,
             parent_id,
             track_dirty_pages,
             name: Some(snapshot_name.clone()),
+            firecracker_version,
+            vcpu: Some(vm.vcpu),
+            mem_mib: Some(vm.mem_mib),
+            cpu_template,
+            compressed: compress,
         })
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -376,12 +564,33 @@ pub async fn create(
     }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/snapshots",
+    params(ListSnapshotsParams),
+    responses(
+        (status = 200, description = "Snapshots listed, newest first unless sorted otherwise", body = ListSnapshotsResponse),
+        (status = 400, description = "Invalid sort or order"),
+        (status = 500, description = "Failed to list snapshots"),
+    ),
+    tag = "Snapshots"
+)]
+pub async fn list(
+    Extension(st): Extension<AppState>,
+    Query(params): Query<ListSnapshotsParams>,
+) -> Result<Json<ListSnapshotsResponse>, (StatusCode, String)> {
+    let filter = SnapshotFilter::from_params(params)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    list_filtered(&st, &filter).await
+}
+
 #[utoipa::path(
     get,
     path = "/v1/vms/{id}/snapshots",
-    params(VmPathParams),
+    params(VmPathParams, ListSnapshotsParams),
     responses(
-        (status = 200, description = "Snapshots listed", body = ListSnapshotsResponse),
+        (status = 200, description = "Snapshots listed, newest first unless sorted otherwise", body = ListSnapshotsResponse),
+        (status = 400, description = "Invalid sort or order"),
         (status = 500, description = "Failed to list snapshots"),
     ),
     tag = "Snapshots"
@@ -389,12 +598,25 @@ pub async fn create(
 pub async fn list_for_vm(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id: vm_id }): Path<VmPathParams>,
-) -> Result<Json<ListSnapshotsResponse>, StatusCode> {
-    let repo = st.snapshots.clone();
-    let items = repo
-        .list_for_vm(vm_id)
+    Query(params): Query<ListSnapshotsParams>,
+) -> Result<Json<ListSnapshotsResponse>, (StatusCode, String)> {
+    let filter = SnapshotFilter {
+        vm_id: Some(vm_id),
+        ..SnapshotFilter::from_params(params)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    };
+    list_filtered(&st, &filter).await
+}
+
+async fn list_filtered(
+    st: &AppState,
+    filter: &SnapshotFilter,
+) -> Result<Json<ListSnapshotsResponse>, (StatusCode, String)> {
+    let items = st
+        .snapshots
+        .list(filter)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .into_iter()
         .map(Snapshot::from)
         .collect();
@@ -427,29 +649,132 @@ pub async fn get(
 #[utoipa::path(
     delete,
     path = "/v1/snapshots/{id}",
-    params(SnapshotPathParams),
+    params(SnapshotPathParams, DeleteSnapshotParams),
     responses(
-        (status = 200, description = "Snapshot deleted", body = OkResponse),
+        (status = 200, description = "Snapshot and its files deleted", body = DeleteSnapshotResponse),
         (status = 404, description = "Snapshot not found"),
+        (status = 409, description = "Diff snapshots depend on it, or VMs were created from it and force is not set"),
         (status = 500, description = "Failed to delete snapshot"),
+        (status = 502, description = "The host's agent could not remove the snapshot files"),
     ),
     tag = "Snapshots"
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(SnapshotPathParams { id }): Path<SnapshotPathParams>,
-) -> Result<Json<OkResponse>, StatusCode> {
+    Query(params): Query<DeleteSnapshotParams>,
+) -> Result<Json<DeleteSnapshotResponse>, (StatusCode, String)> {
+    let force = params.force;
+    let result = delete_snapshot(&st, id, params).await;
+    actor
+        .record_with(
+            AuditAction::DeleteVmSnapshot,
+            ("snapshot", id),
+            Some(json!({"force": force})),
+            &status_outcome(&result),
+        )
+        .await;
+    result
+}
+
+async fn delete_snapshot(
+    st: &AppState,
+    id: Uuid,
+    params: DeleteSnapshotParams,
+) -> Result<Json<DeleteSnapshotResponse>, (StatusCode, String)> {
+    let internal = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
     let repo = st.snapshots.clone();
-    repo.delete(id)
+    let snapshot = repo.get(id).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, String::new()),
+        e => internal(e),
+    })?;
+
+    let children = repo.children(id).await.map_err(internal)?;
+    if !children.is_empty() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "{} snapshot(s) are based on this one and must be deleted first: {}",
+                children.len(),
+                join_ids(&children)
+            ),
+        ));
+    }
+    let vms = repo.vms_using(id).await.map_err(internal)?;
+    if !vms.is_empty() && !params.force {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "{} VM(s) were created from this snapshot: {}; retry with force=true",
+                vms.len(),
+                join_ids(&vms)
+            ),
+        ));
+    }
+
+    // Files first: if the agent can't remove them the row stays, so the
+    // delete can be retried instead of leaving files nothing points at
+    let freed_bytes = remove_snapshot_files(st, &snapshot)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(OkResponse::default()))
+        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("{e:#}")))?;
+    repo.delete(id).await.map_err(internal)?;
+    tracing::info!(snapshot_id = %id, freed_bytes, detached_vms = vms.len(), "snapshot deleted");
+    Ok(Json(DeleteSnapshotResponse { freed_bytes }))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteSnapshotParams {
+    /// Delete even though VMs were created from the snapshot; their
+    /// `source_snapshot_id` is cleared.
+    #[serde(default)]
+    pub force: bool,
+}
+
+fn join_ids(ids: &[Uuid]) -> String {
+    ids.iter()
+        .map(Uuid::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Has the agent on the snapshot's host remove its directory; returns the
+/// bytes freed.
+async fn remove_snapshot_files(
+    st: &AppState,
+    snapshot: &super::repo::SnapshotRow,
+) -> anyhow::Result<u64> {
+    let vm = crate::features::vms::repo::get(&st.db, snapshot.vm_id).await?;
+    delete_snapshot_dir(&vm.host_addr, snapshot.vm_id, snapshot.id).await
+}
+
+async fn delete_snapshot_dir(
+    host_addr: &str,
+    vm_id: Uuid,
+    snapshot_id: Uuid,
+) -> anyhow::Result<u64> {
+    #[derive(Deserialize)]
+    struct DeleteResp {
+        freed_bytes: u64,
+    }
+    let resp: DeleteResp = crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/vms/{vm_id}/snapshots/delete"))
+        .json(&json!({ "snapshot_id": snapshot_id }))
+        .send()
+        .await?
+        .or_fault()
+        .await
+        .context("removing snapshot files on host")?
+        .json()
+        .await?;
+    Ok(resp.freed_bytes)
 }
 
 #[utoipa::path(
     post,
     path = "/v1/snapshots/{id}/instantiate",
-    params(SnapshotPathParams),
+    params(SnapshotPathParams, InstantiateSnapshotParams),
     request_body(
         content = InstantiateSnapshotReq,
         content_type = "application/json",
@@ -457,22 +782,49 @@ pub async fn delete(
     ),
     responses(
         (status = 200, description = "Snapshot instantiated", body = InstantiateSnapshotResp),
+        (status = 400, description = "Requested vcpu/mem_mib cannot be applied to this snapshot, or host_id is unhealthy or cannot reach the snapshot files"),
         (status = 404, description = "Snapshot not found"),
+        (status = 409, description = "Host runs a different Firecracker release than the snapshot (retry with force=true), or the VM would exceed the caller's quota"),
         (status = 502, description = "Failed to instantiate snapshot"),
     ),
     tag = "Snapshots"
 )]
 pub async fn instantiate(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(SnapshotPathParams { id }): Path<SnapshotPathParams>,
+    Query(params): Query<InstantiateSnapshotParams>,
     body: Option<Json<InstantiateSnapshotReq>>,
-) -> Result<Json<InstantiateSnapshotResp>, StatusCode> {
+) -> Result<Json<InstantiateSnapshotResp>, (StatusCode, String)> {
     let payload = body.map(|Json(req)| req).unwrap_or_default();
+    let result = instantiate_snapshot(&st, id, params, payload, actor.user_id).await;
+    let vm_id = result.as_ref().ok().map(|Json(resp)| resp.id);
+    actor
+        .record_with(
+            AuditAction::RestoreVmSnapshot,
+            ("vm", vm_id),
+            Some(json!({"snapshot_id": id})),
+            &status_outcome(&result),
+        )
+        .await;
+    result
+}
+
+async fn instantiate_snapshot(
+    st: &AppState,
+    id: Uuid,
+    params: InstantiateSnapshotParams,
+    payload: InstantiateSnapshotReq,
+    user_id: Option<Uuid>,
+) -> Result<Json<InstantiateSnapshotResp>, (StatusCode, String)> {
     let repo = st.snapshots.clone();
-    let snapshot = repo.get(id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    let snapshot = repo
+        .get(id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, String::new()))?;
     let source_vm = crate::features::vms::repo::get(&st.db, snapshot.vm_id)
         .await
-        .map_err(|_| StatusCode::BAD_GATEWAY)?;
+        .map_err(|_| (StatusCode::BAD_GATEWAY, String::new()))?;
 
     let src_kind = vm_vmm_kind(&st.db, snapshot.vm_id).await;
     let snap_kind = snapshot_vmm_kind(&st.db, snapshot.id).await;
@@ -481,6 +833,12 @@ pub async fn instantiate(
     // VM and cold-boot it from that disk state (a consistent revert-to-snapshot;
     // the migrate-to-file RAM state is not replayed). Reuses create_and_start_qemu.
     if snap_kind == "qemu" && src_kind == "qemu" {
+        if payload.host_id.is_some() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "host_id is only supported for Firecracker snapshots".to_string(),
+            ));
+        }
         let new_id = Uuid::new_v4();
         let name = resolve_instantiate_name(payload.name, snapshot.name.as_deref(), snapshot.id);
 
@@ -488,12 +846,38 @@ pub async fn instantiate(
         let snap_disk = qemu_snapshot_state_path(snapshot.vm_id, snapshot.id)
             .parent()
             .map(|p| p.join("disk.qcow2"))
-            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            .ok_or((StatusCode::INTERNAL_SERVER_ERROR, String::new()))?;
         if tokio::fs::metadata(&snap_disk).await.is_err() {
             tracing::error!(snapshot_id=%snapshot.id, path=%snap_disk.display(),
                 "qemu snapshot has no captured disk (taken before the disk-capture fix?)");
-            return Err(StatusCode::BAD_REQUEST);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Snapshot has no captured disk to restore from".to_string(),
+            ));
         }
+        // A QEMU restore cold-boots from the captured disk, so the shape
+        // can change freely.
+        let vcpu = payload.vcpu.unwrap_or(source_vm.vcpu.max(1) as u8);
+        let mem_mib = payload.mem_mib.unwrap_or(source_vm.mem_mib.max(1) as u32);
+        let reservation = crate::features::users::quota::reserve(
+            &st.db,
+            user_id,
+            nexus_types::UserQuotaUsage {
+                vms: 1,
+                vcpu: vcpu as i64,
+                mem_mib: mem_mib as i64,
+                containers: 0,
+            },
+        )
+        .await
+        .map_err(|err| {
+            if crate::features::users::quota::is_exceeded(&err) {
+                return (StatusCode::CONFLICT, err.to_string());
+            }
+            tracing::error!(snapshot_id = %id, error = ?err, "quota check failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+        })?;
+
         // Copy it into the new VM's storage as its root disk (the master stays
         // intact so the snapshot can be instantiated again).
         let dst_dir = std::path::PathBuf::from(
@@ -503,12 +887,12 @@ pub async fn instantiate(
         .join("storage");
         if let Err(e) = tokio::fs::create_dir_all(&dst_dir).await {
             tracing::error!(error=?e, "create storage dir for snapshot restore");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, String::new()));
         }
         let new_disk = dst_dir.join("disk.qcow2");
         if let Err(e) = tokio::fs::copy(&snap_disk, &new_disk).await {
             tracing::error!(error=?e, "copy snapshot disk for restore");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, String::new()));
         }
 
         // Source VM's persisted boot mode (OVMF/UEFI etc.).
@@ -525,8 +909,8 @@ pub async fn instantiate(
 
         let req = nexus_types::CreateVmReq {
             name: name.clone(),
-            vcpu: source_vm.vcpu.max(1) as u8,
-            mem_mib: source_vm.mem_mib.max(1) as u32,
+            vcpu,
+            mem_mib,
             vmm_kind: Some(nexus_vmm::VmmKind::Qemu),
             boot_mode,
             guest_os: source_vm
@@ -538,19 +922,15 @@ pub async fn instantiate(
             ..Default::default()
         };
 
-        crate::features::vms::qemu_service::create_and_start_qemu(
-            &st,
-            new_id,
-            req,
-            None,
-            None,
-            "snapshot-restore",
-        )
-        .await
-        .map_err(|err| {
-            tracing::error!(snapshot_id=%id, error=?err, "failed to instantiate qemu snapshot");
-            StatusCode::BAD_GATEWAY
-        })?;
+        crate::features::vms::qemu_service::create_and_start_qemu(st, new_id, req, None, user_id)
+            .await
+            .map_err(|err| {
+                tracing::error!(snapshot_id=%id, error=?err, "failed to instantiate qemu snapshot");
+                (StatusCode::BAD_GATEWAY, String::new())
+            })?;
+        if let Err(err) = reservation.release().await {
+            tracing::warn!(snapshot_id = %id, error = ?err, "failed to release quota reservation");
+        }
 
         return Ok(Json(InstantiateSnapshotResp { id: new_id, name }));
     }
@@ -564,24 +944,45 @@ pub async fn instantiate(
             source_vmm_kind=%src_kind,
             "snapshot instantiate (this route) is FC-only; QEMU instantiate lands in a follow-up",
         );
-        return Err(StatusCode::BAD_REQUEST);
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Cross-backend snapshot restore is not supported".to_string(),
+        ));
     }
 
+    crate::features::vms::service::validate_restore_shape(
+        source_vm.vcpu,
+        source_vm.mem_mib,
+        payload.vcpu,
+        payload.mem_mib,
+    )
+    .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+
     let vm_id = Uuid::new_v4();
     let name = resolve_instantiate_name(payload.name, snapshot.name.as_deref(), snapshot.id);
 
     crate::features::vms::service::create_from_snapshot(
-        &st,
+        st,
         vm_id,
         name.clone(),
         None,
         snapshot.clone(),
         Some(source_vm),
+        payload.mem_mib,
+        payload.host_id,
+        params.force,
+        user_id,
     )
     .await
     .map_err(|err| {
+        if compat::is_incompatible(&err) || crate::features::users::quota::is_exceeded(&err) {
+            return (StatusCode::CONFLICT, err.to_string());
+        }
+        if err.to_string().starts_with("invalid host_id") {
+            return (StatusCode::BAD_REQUEST, err.to_string());
+        }
         tracing::error!(snapshot_id = %id, error = ?err, "failed to instantiate snapshot");
-        StatusCode::BAD_GATEWAY
+        (StatusCode::BAD_GATEWAY, String::new())
     })?;
 
     Ok(Json(InstantiateSnapshotResp { id: vm_id, name }))
@@ -608,6 +1009,14 @@ struct AgentPrepareSnapshotResponse {
     diff_dir: Option<String>,
 }
 
+/// The agent's answer to `snapshots/compress`.
+#[derive(Deserialize)]
+struct AgentMemFileResponse {
+    mem_path: String,
+    #[serde(default)]
+    mem_size_bytes: Option<u64>,
+}
+
 impl From<super::repo::SnapshotRow> for Snapshot {
     fn from(row: super::repo::SnapshotRow) -> Self {
         Snapshot {
@@ -623,6 +1032,11 @@ impl From<super::repo::SnapshotRow> for Snapshot {
             snapshot_type: Some(row.snapshot_type.clone()),
             parent_id: row.parent_id,
             track_dirty_pages: row.track_dirty_pages,
+            firecracker_version: row.firecracker_version,
+            vcpu: row.vcpu,
+            mem_mib: row.mem_mib,
+            cpu_template: row.cpu_template,
+            compressed: row.compressed,
         }
     }
 }
@@ -646,10 +1060,6 @@ mod tests {
         let expected_base = format!("http://10.0.0.5:9090/agent/v1/vms/{vm_id}");
         let expected_qs = "?sock=%2Fsrv%2Ffc%2Fvms%2Fx%2Fsock%2Ffc.sock";
 
-        assert_eq!(
-            urls.vm_url,
-            format!("{expected_base}/proxy/vm{expected_qs}")
-        );
         assert_eq!(
             urls.snapshot_url,
             format!("{expected_base}/proxy/snapshot/create{expected_qs}")
@@ -658,6 +1068,10 @@ mod tests {
             urls.prepare_url,
             format!("{expected_base}/snapshots/prepare")
         );
+        assert_eq!(
+            urls.compress_url,
+            format!("{expected_base}/snapshots/compress")
+        );
         assert_eq!(
             urls.machine_config_url,
             format!("{expected_base}/proxy/machine-config{expected_qs}")
@@ -671,18 +1085,28 @@ mod tests {
         let urls = build_agent_snapshot_urls("http://h", Uuid::nil(), "/tmp/with space&amp.sock");
 
         assert!(
-            urls.vm_url
+            urls.snapshot_url
                 .contains("sock=%2Ftmp%2Fwith%20space%26amp.sock"),
             "encoded sock missing in {}",
-            urls.vm_url
+            urls.snapshot_url
         );
         assert!(
-            !urls.vm_url.contains(" "),
+            !urls.snapshot_url.contains(" "),
             "raw space leaked into URL: {}",
-            urls.vm_url
+            urls.snapshot_url
         );
     }
 
+    #[test]
+    fn snapshot_pauses_running_vms_unless_opted_out() {
+        assert_eq!(snapshot_pauses_vm("running", true), Ok(true));
+        // Already paused: left paused, not resumed afterwards
+        assert_eq!(snapshot_pauses_vm("paused", true), Ok(false));
+        assert_eq!(snapshot_pauses_vm("paused", false), Ok(false));
+        assert!(snapshot_pauses_vm("running", false).is_err());
+        assert!(snapshot_pauses_vm("stopped", true).is_err());
+    }
+
     #[test]
     fn resolve_snapshot_name_uses_override_when_present() {
         assert_eq!(
@@ -823,6 +1247,11 @@ mod tests {
             parent_id: Some(parent_id),
             track_dirty_pages: true,
             name: Some("nightly".into()),
+            firecracker_version: Some("1.7.0".into()),
+            vcpu: Some(2),
+            mem_mib: Some(512),
+            cpu_template: Some("T2".into()),
+            compressed: true,
             created_at: now,
             updated_at: now,
         };
@@ -841,6 +1270,10 @@ mod tests {
         assert_eq!(snap.snapshot_type.as_deref(), Some("Diff"));
         assert_eq!(snap.parent_id, Some(parent_id));
         assert!(snap.track_dirty_pages);
+        assert_eq!(snap.firecracker_version.as_deref(), Some("1.7.0"));
+        assert_eq!((snap.vcpu, snap.mem_mib), (Some(2), Some(512)));
+        assert_eq!(snap.cpu_template.as_deref(), Some("T2"));
+        assert!(snap.compressed);
         assert_eq!(snap.created_at, now);
         assert_eq!(snap.updated_at, now);
     }
@@ -45,6 +45,15 @@ pub fn decrypt(encrypted: &str, key: &[u8; 32]) -> Result<String> {
     String::from_utf8(plaintext).context("decrypted data is not valid UTF-8")
 }
 
+/// Key material used when `SSO_ENCRYPTION_KEY` isn't set. Anyone with the
+/// source can derive it, so nothing long-lived should be sealed with it.
+pub const INSECURE_DEFAULT_KEY: &str = "insecure-default-key-change-me";
+
+/// Whether `key` is the one derived from [`INSECURE_DEFAULT_KEY`].
+pub fn is_insecure_default(key: &[u8; 32]) -> bool {
+    *key == derive_key(INSECURE_DEFAULT_KEY)
+}
+
 /// Derive a 32-byte key from an environment variable value using SHA-256.
 pub fn derive_key(key_material: &str) -> [u8; 32] {
     use sha2::{Digest, Sha256};
@@ -1,4 +1,5 @@
 use crate::features::sso::{crypto, oidc, provisioning, saml};
+use crate::features::users::audit::Actor;
 use crate::AppState;
 use axum::{
     extract::{Form, Path, Query},
@@ -7,9 +8,9 @@ use axum::{
     Extension, Json,
 };
 use nexus_types::{
-    CreateSsoProviderRequest, ListSsoProviderConfigsResponse, ListSsoProvidersResponse,
-    SsoProvider, SsoProviderConfig, SsoProviderPathParams, SsoSlugPathParams, SsoTestResult,
-    UpdateSsoProviderRequest,
+    AuditAction, CreateSsoProviderRequest, ListSsoProviderConfigsResponse,
+    ListSsoProvidersResponse, SsoProvider, SsoProviderConfig, SsoProviderPathParams,
+    SsoSlugPathParams, SsoTestResult, UpdateSsoProviderRequest,
 };
 use serde::Deserialize;
 use tracing::{error, info, warn};
@@ -393,6 +394,7 @@ pub async fn admin_list_providers(
 
 pub async fn admin_create_provider(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateSsoProviderRequest>,
 ) -> Result<Json<SsoProviderConfig>, StatusCode> {
     let encrypted_secret = if let Some(secret) = &req.oidc_client_secret {
@@ -406,14 +408,21 @@ pub async fn admin_create_provider(
         None
     };
 
-    let row = st
+    let result = st
         .sso_providers
         .create(&req, encrypted_secret.as_deref())
-        .await
-        .map_err(|e| {
-            error!(?e, "failed to create SSO provider");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .await;
+    actor
+        .record(
+            AuditAction::SsoProviderCreated,
+            ("sso_provider", result.as_ref().ok().map(|row| row.id)),
+            &result,
+        )
+        .await;
+    let row = result.map_err(|e| {
+        error!(?e, "failed to create SSO provider");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
     info!(provider_id = %row.id, slug = %row.slug, "SSO provider created");
     Ok(Json(row_to_config(&row)))
@@ -433,6 +442,7 @@ pub async fn admin_get_provider(
 
 pub async fn admin_update_provider(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(SsoProviderPathParams { id }): Path<SsoProviderPathParams>,
     Json(req): Json<UpdateSsoProviderRequest>,
 ) -> Result<Json<SsoProviderConfig>, StatusCode> {
@@ -447,17 +457,24 @@ pub async fn admin_update_provider(
         None
     };
 
-    let row = st
+    let result = st
         .sso_providers
         .update(id, &req, encrypted_secret.as_deref())
-        .await
-        .map_err(|e| {
-            error!(?e, "failed to update SSO provider");
-            match e {
-                crate::features::sso::repo::SsoRepoError::ProviderNotFound => StatusCode::NOT_FOUND,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            }
-        })?;
+        .await;
+    actor
+        .record(
+            AuditAction::SsoProviderUpdated,
+            ("sso_provider", id),
+            &result,
+        )
+        .await;
+    let row = result.map_err(|e| {
+        error!(?e, "failed to update SSO provider");
+        match e {
+            crate::features::sso::repo::SsoRepoError::ProviderNotFound => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    })?;
 
     info!(provider_id = %id, "SSO provider updated");
     Ok(Json(row_to_config(&row)))
@@ -465,9 +482,18 @@ pub async fn admin_update_provider(
 
 pub async fn admin_delete_provider(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(SsoProviderPathParams { id }): Path<SsoProviderPathParams>,
 ) -> Result<Json<nexus_types::OkResponse>, StatusCode> {
-    st.sso_providers.delete(id).await.map_err(|e| {
+    let result = st.sso_providers.delete(id).await;
+    actor
+        .record(
+            AuditAction::SsoProviderDeleted,
+            ("sso_provider", id),
+            &result,
+        )
+        .await;
+    result.map_err(|e| {
         error!(?e, "failed to delete SSO provider");
         match e {
             crate::features::sso::repo::SsoRepoError::ProviderNotFound => StatusCode::NOT_FOUND,
@@ -1,9 +1,12 @@
 use crate::features::backups::types::{BackupReq, BackupResp, RestoreReq, RestoreResp};
 use anyhow::{anyhow, Context, Result};
 use nexus_storage::{AttachedPath, BackendKind, VolumeHandle};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
+
+/// Ceiling on populate, backup and restore, which copy a whole volume.
+const COPY_TIMEOUT: Duration = Duration::from_secs(1800);
 
 fn agent_url(host_addr: &str, path: &str) -> String {
     let base = if host_addr.starts_with("http") {
@@ -25,7 +28,7 @@ struct AttachResp {
 }
 
 pub async fn agent_attach(host_addr: &str, volume: &VolumeHandle) -> Result<AttachedPath> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/attach"))
         .json(&AttachReq { volume })
         .send()
@@ -52,7 +55,7 @@ pub async fn agent_detach(
     volume: &VolumeHandle,
     attached: &AttachedPath,
 ) -> Result<()> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/detach"))
         .json(&DetachReq { volume, attached })
         .send()
@@ -79,8 +82,9 @@ pub async fn agent_populate(
     source_path: &PathBuf,
     target_size_bytes: u64,
 ) -> Result<()> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/populate"))
+        .timeout(COPY_TIMEOUT)
         .json(&PopulateReq {
             backend_kind,
             attached,
@@ -109,7 +113,7 @@ pub async fn agent_resize2fs(
     backend_kind: BackendKind,
     attached: &AttachedPath,
 ) -> Result<()> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/resize2fs"))
         .json(&Resize2fsReq {
             backend_kind,
@@ -125,8 +129,9 @@ pub async fn agent_resize2fs(
 }
 
 pub async fn agent_backup(host_addr: &str, req: BackupReq) -> Result<BackupResp> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/backup"))
+        .timeout(COPY_TIMEOUT)
         .json(&req)
         .send()
         .await
@@ -140,8 +145,9 @@ pub async fn agent_backup(host_addr: &str, req: BackupReq) -> Result<BackupResp>
 }
 
 pub async fn agent_restore(host_addr: &str, req: RestoreReq) -> Result<RestoreResp> {
-    let resp = Client::new()
+    let resp = crate::core::agent_http::client()
         .post(agent_url(host_addr, "/v1/storage/restore"))
+        .timeout(COPY_TIMEOUT)
         .json(&req)
         .send()
         .await
@@ -162,7 +162,7 @@ impl IscsiLvmControlPlaneBackend {
                 "iscsi_lvm backend requires config.agent_url",
             ))
         })?;
-        let resp = reqwest::Client::new()
+        let resp = crate::core::agent_http::client()
             .post(&url)
             .json(req)
             .send()
@@ -199,7 +199,7 @@ impl IscsiLvmControlPlaneBackend {
                 "iscsi_lvm backend requires config.agent_url",
             ))
         })?;
-        let resp = reqwest::Client::new()
+        let resp = crate::core::agent_http::client()
             .post(&url)
             .json(req)
             .send()
@@ -16,6 +16,8 @@ use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 const DEFAULT_MOUNT_BASE: &str = "/var/lib/nqrust/nfs";
+/// Ceiling on agent ops that copy a whole image over NFS.
+const COPY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1800);
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct NfsConfig {
@@ -181,16 +183,15 @@ impl NfsControlPlaneBackend {
                 "nfs backend requires config.agent_url, or assume_mounted=true for local testing",
             ))
         })?;
-        let resp = reqwest::Client::new()
-            .post(&url)
-            .json(req)
-            .send()
-            .await
-            .map_err(|e| {
-                StorageError::backend(std::io::Error::other(format!(
-                    "agent nfs {op} request failed: {e}"
-                )))
-            })?;
+        let mut request = crate::core::agent_http::client().post(&url);
+        if matches!(op, "clone_from_path" | "snapshot") {
+            request = request.timeout(COPY_TIMEOUT);
+        }
+        let resp = request.json(req).send().await.map_err(|e| {
+            StorageError::backend(std::io::Error::other(format!(
+                "agent nfs {op} request failed: {e}"
+            )))
+        })?;
         let status = resp.status();
         let body = resp.text().await.map_err(|e| {
             StorageError::backend(std::io::Error::other(format!(
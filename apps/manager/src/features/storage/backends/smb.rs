@@ -136,19 +136,17 @@ impl SmbControlPlaneBackend {
                 "smb backend requires config.agent_url, or assume_mounted=true for local testing",
             ))
         })?;
-        let client = reqwest::Client::builder()
+        let resp = crate::core::agent_http::client()
+            .post(&url)
             .timeout(std::time::Duration::from_secs(30))
-            .build()
+            .json(req)
+            .send()
+            .await
             .map_err(|e| {
                 StorageError::backend(std::io::Error::other(format!(
-                    "agent smb client init failed: {e}"
+                    "agent smb {op} request failed: {e}"
                 )))
             })?;
-        let resp = client.post(&url).json(req).send().await.map_err(|e| {
-            StorageError::backend(std::io::Error::other(format!(
-                "agent smb {op} request failed: {e}"
-            )))
-        })?;
         let status = resp.status();
         let body = resp.text().await.map_err(|e| {
             StorageError::backend(std::io::Error::other(format!(
@@ -179,19 +177,17 @@ impl SmbControlPlaneBackend {
                 "smb backend requires config.agent_url, or assume_mounted=true for local testing",
             ))
         })?;
-        let client = reqwest::Client::builder()
+        let resp = crate::core::agent_http::client()
+            .post(&url)
             .timeout(std::time::Duration::from_secs(30))
-            .build()
+            .json(req)
+            .send()
+            .await
             .map_err(|e| {
                 StorageError::backend(std::io::Error::other(format!(
-                    "agent smb client init failed: {e}"
+                    "agent smb {op} request failed: {e}"
                 )))
             })?;
-        let resp = client.post(&url).json(req).send().await.map_err(|e| {
-            StorageError::backend(std::io::Error::other(format!(
-                "agent smb {op} request failed: {e}"
-            )))
-        })?;
         let status = resp.status();
         if !status.is_success() {
             let body = resp.text().await.unwrap_or_default();
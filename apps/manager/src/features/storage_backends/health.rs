@@ -235,28 +235,18 @@ async fn probe_iscsi_lvm(
             };
         with_scheme.trim_end_matches('/').to_string()
     };
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            return BackendHealth {
-                reachable: false,
-                status: format!("reqwest builder: {e}"),
-                used_bytes: None,
-                total_bytes: None,
-            };
-        }
-    };
+    let client = crate::core::agent_http::client();
+    let probe_timeout = Duration::from_secs(10);
     // Best-effort: a session may already be open; ignore login errors.
     let _ = client
         .post(format!("{base}/v1/storage/iscsi_lvm/login"))
+        .timeout(probe_timeout)
         .json(&serde_json::json!({"iqn": cfg.iqn, "portal": cfg.portal}))
         .send()
         .await;
     match client
         .post(format!("{base}/v1/storage/iscsi_lvm/vg_status"))
+        .timeout(probe_timeout)
         .json(&serde_json::json!({"vg": cfg.vg_name}))
         .send()
         .await
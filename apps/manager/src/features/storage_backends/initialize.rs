@@ -129,28 +129,16 @@ pub async fn initialize(
     };
 
     // Call agent's /v1/storage/iscsi_lvm/init_vg.
-    let client = match reqwest::Client::builder()
-        // pvcreate + vgcreate can take a few seconds on a real LUN.
-        .timeout(std::time::Duration::from_secs(60))
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": format!("reqwest builder: {e}")})),
-            )
-                .into_response();
-        }
-    };
     let body = serde_json::json!({
         "iqn": cfg.iqn,
         "portal": cfg.portal,
         "lun": cfg.lun,
         "vg_name": cfg.vg_name,
     });
-    match client
+    match crate::core::agent_http::client()
         .post(format!("{base}/v1/storage/iscsi_lvm/init_vg"))
+        // pvcreate + vgcreate can take a few seconds on a real LUN.
+        .timeout(std::time::Duration::from_secs(60))
         .json(&body)
         .send()
         .await
@@ -1,8 +1,14 @@
 use crate::features::storage::config::{validate, RawBackendEntry};
 use crate::features::storage_backends::repo::{StorageBackendRepository, StorageBackendRow};
+use crate::features::users::audit::{response_outcome, Actor};
 use crate::AppState;
-use axum::{extract::Path, http::StatusCode, response::IntoResponse, Extension, Json};
-use nexus_types::{Capabilities, StorageBackend};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use nexus_types::{AuditAction, Capabilities, StorageBackend};
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
@@ -98,8 +104,23 @@ pub struct CreateStorageBackendReq {
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateStorageBackendReq>,
 ) -> impl IntoResponse {
+    let name = req.name.clone();
+    let response = create_backend(st, req).await;
+    actor
+        .record_with(
+            AuditAction::CreateStorageBackend,
+            ("storage_backend", None),
+            Some(serde_json::json!({"name": name})),
+            &response_outcome(&response),
+        )
+        .await;
+    response
+}
+
+async fn create_backend(st: AppState, req: CreateStorageBackendReq) -> Response {
     let validated = match validate(RawBackendEntry {
         name: req.name.clone(),
         kind: req.kind,
@@ -293,11 +314,9 @@ async fn set_smb_credentials_on_agent(
         "{}/v1/storage/smb/set_credentials",
         agent_url.trim_end_matches('/')
     );
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()?;
-    let resp = client
+    let resp = crate::core::agent_http::client()
         .post(&url)
+        .timeout(std::time::Duration::from_secs(10))
         .json(&serde_json::json!({
             "backend_id": backend_id,
             "username": username,
@@ -385,11 +404,9 @@ async fn clear_smb_credentials_on_agent(
         "{}/v1/storage/smb/clear_credentials",
         agent_url.trim_end_matches('/')
     );
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()?;
-    let _ = client
+    let _ = crate::core::agent_http::client()
         .post(&full_url)
+        .timeout(std::time::Duration::from_secs(5))
         .json(&serde_json::json!({ "backend_id": row.id }))
         .send()
         .await;
@@ -407,7 +424,23 @@ async fn clear_smb_credentials_on_agent(
     ),
     tag = "StorageBackends",
 )]
-pub async fn delete(Extension(st): Extension<AppState>, Path(id): Path<Uuid>) -> impl IntoResponse {
+pub async fn delete(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let response = delete_backend(st, id).await;
+    actor
+        .record(
+            AuditAction::DeleteStorageBackend,
+            ("storage_backend", id),
+            &response_outcome(&response),
+        )
+        .await;
+    response
+}
+
+async fn delete_backend(st: AppState, id: Uuid) -> Response {
     let repo = StorageBackendRepository::new(st.db.clone());
     let row = match repo.get(id).await {
         Ok(Some(r)) => r,
@@ -744,9 +777,22 @@ pub async fn get_config(
 /// field is ignored.
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
     Json(req): Json<CreateStorageBackendReq>,
 ) -> impl IntoResponse {
+    let response = update_backend(st, id, req).await;
+    actor
+        .record(
+            AuditAction::UpdateStorageBackend,
+            ("storage_backend", id),
+            &response_outcome(&response),
+        )
+        .await;
+    response
+}
+
+async fn update_backend(st: AppState, id: Uuid, req: CreateStorageBackendReq) -> Response {
     let password = req.password.clone();
     let validated = match validate(RawBackendEntry {
         name: req.name.clone(),
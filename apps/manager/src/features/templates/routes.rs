@@ -1,9 +1,10 @@
+use crate::features::users::audit::Actor;
 use crate::AppState;
 use axum::{extract::Path, http::StatusCode, Extension, Json};
 use nexus_types::{
-    CreateTemplateReq, CreateTemplateResp, GetTemplateResp, InstantiateTemplateReq,
-    InstantiateTemplateResp, ListTemplatesResp, OkResponse, TemplatePathParams, UpdateTemplateReq,
-    UpdateTemplateResp,
+    AuditAction, CreateTemplateReq, CreateTemplateResp, GetTemplateResp, InstantiateTemplateReq,
+    InstantiateTemplateResp, ListTemplatesResp, OkResponse, TemplatePathParams, TemplateSpec,
+    UpdateTemplateReq, UpdateTemplateResp,
 };
 use uuid::Uuid;
 
@@ -13,17 +14,29 @@ use uuid::Uuid;
     request_body = CreateTemplateReq,
     responses(
         (status = 200, description = "Template created", body = CreateTemplateResp),
+        (status = 400, description = "Invalid template spec"),
         (status = 500, description = "Failed to create template"),
     ),
     tag = "Templates"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateTemplateReq>,
 ) -> Result<Json<CreateTemplateResp>, StatusCode> {
-    let template = super::repo::insert(&st.db, &req)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    validate_spec(&req.spec).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let result = super::repo::insert(&st.db, &req).await;
+    let template_id = result.as_ref().ok().map(|t| t.id);
+    let details = serde_json::json!({"name": req.name});
+    actor
+        .record_with(
+            AuditAction::CreateTemplate,
+            ("template", template_id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let template = result.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok(Json(CreateTemplateResp { id: template.id }))
 }
 
@@ -76,6 +89,7 @@ pub async fn get(
     request_body = UpdateTemplateReq,
     responses(
         (status = 200, description = "Template updated", body = UpdateTemplateResp),
+        (status = 400, description = "Invalid template spec"),
         (status = 404, description = "Template not found"),
         (status = 500, description = "Failed to update template"),
     ),
@@ -83,15 +97,19 @@ pub async fn get(
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(TemplatePathParams { id }): Path<TemplatePathParams>,
     Json(req): Json<UpdateTemplateReq>,
 ) -> Result<Json<UpdateTemplateResp>, StatusCode> {
-    let template = super::repo::update(&st.db, id, &req)
-        .await
-        .map_err(|err| match err {
-            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    validate_spec(&req.spec).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let result = super::repo::update(&st.db, id, &req).await;
+    actor
+        .record(AuditAction::UpdateTemplate, ("template", id), &result)
+        .await;
+    let template = result.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    })?;
     Ok(Json(UpdateTemplateResp { item: template }))
 }
 
@@ -108,14 +126,17 @@ pub async fn update(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(TemplatePathParams { id }): Path<TemplatePathParams>,
 ) -> Result<Json<OkResponse>, StatusCode> {
-    super::repo::delete(&st.db, id)
-        .await
-        .map_err(|err| match err {
-            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    let result = super::repo::delete(&st.db, id).await;
+    actor
+        .record(AuditAction::DeleteTemplate, ("template", id), &result)
+        .await;
+    result.map_err(|err| match err {
+        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -126,13 +147,16 @@ pub async fn delete(
     request_body = InstantiateTemplateReq,
     responses(
         (status = 200, description = "Template instantiated", body = InstantiateTemplateResp),
+        (status = 400, description = "Override out of bounds"),
         (status = 404, description = "Template not found"),
+        (status = 409, description = "The VM would exceed the caller's quota"),
         (status = 500, description = "Failed to instantiate template"),
     ),
     tag = "Templates"
 )]
 pub async fn instantiate(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(TemplatePathParams { id }): Path<TemplatePathParams>,
     Json(req): Json<InstantiateTemplateReq>,
 ) -> Result<Json<InstantiateTemplateResp>, StatusCode> {
@@ -143,23 +167,100 @@ pub async fn instantiate(
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         })?;
 
+    validate_overrides(&req).map_err(|err| {
+        tracing::warn!(template_id = %id, error = %err, "rejected template overrides");
+        StatusCode::BAD_REQUEST
+    })?;
+
     let vm_id = Uuid::new_v4();
-    let vm_req = template.spec.into_vm_req(req.name);
+    let overridden = req.overridden_fields();
+    let vm_req = req.into_vm_req(template.spec);
+    let overrides_note = overrides_event(&template.name, &vm_req, &overridden);
 
-    super::super::vms::service::create_and_start(
+    let result = super::super::vms::service::create_and_start(
         &st,
         vm_id,
         vm_req,
         Some(template.id),
-        None,
-        "system",
+        actor.user_id,
     )
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await;
+    let details = serde_json::json!({"template_id": template.id});
+    actor
+        .record_with(AuditAction::CreateVm, ("vm", vm_id), Some(details), &result)
+        .await;
+    result.map_err(|err| {
+        if crate::features::users::quota::is_exceeded(&err) {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
+    if let Some(note) = overrides_note {
+        let _ = super::super::vms::repo::insert_event(&st.db, vm_id, "info", &note).await;
+    }
 
     Ok(Json(InstantiateTemplateResp { id: vm_id }))
 }
 
+const MAX_OVERRIDE_VCPU: u8 = 32;
+const MIN_OVERRIDE_MEM_MIB: u32 = 128;
+const MAX_OVERRIDE_MEM_MIB: u32 = 256 * 1024;
+
+fn validate_spec(spec: &TemplateSpec) -> anyhow::Result<()> {
+    if let Some(limiter) = spec.rootfs_rate_limiter.as_ref() {
+        crate::features::vms::service::validate_rate_limiter("rootfs_rate_limiter", limiter)?;
+    }
+    if let Some(limiter) = spec.entropy_rate_limiter.as_ref() {
+        crate::features::vms::service::validate_rate_limiter("entropy_rate_limiter", limiter)?;
+    }
+    Ok(())
+}
+
+fn validate_overrides(req: &InstantiateTemplateReq) -> anyhow::Result<()> {
+    if let Some(vcpu) = req.vcpu {
+        if !(1..=MAX_OVERRIDE_VCPU).contains(&vcpu) {
+            anyhow::bail!("Invalid vcpu override {vcpu}: must be 1-{MAX_OVERRIDE_VCPU}");
+        }
+    }
+    if let Some(mem_mib) = req.mem_mib {
+        if !(MIN_OVERRIDE_MEM_MIB..=MAX_OVERRIDE_MEM_MIB).contains(&mem_mib) {
+            anyhow::bail!(
+                "Invalid mem_mib override {mem_mib}: must be {MIN_OVERRIDE_MEM_MIB}-{MAX_OVERRIDE_MEM_MIB}"
+            );
+        }
+    }
+    if let Some(tags) = req.tags.as_deref() {
+        crate::features::vms::service::validate_vm_tags(tags)?;
+    }
+    Ok(())
+}
+
+/// VM event text recording which template fields this instance overrode.
+fn overrides_event(
+    template_name: &str,
+    vm_req: &nexus_types::CreateVmReq,
+    overridden: &[&str],
+) -> Option<String> {
+    if overridden.is_empty() {
+        return None;
+    }
+    let values: Vec<String> = overridden
+        .iter()
+        .map(|field| match *field {
+            "vcpu" => format!("vcpu={}", vm_req.vcpu),
+            "mem_mib" => format!("mem_mib={}", vm_req.mem_mib),
+            "tags" => format!("tags=[{}]", vm_req.tags.join(", ")),
+            other => other.to_string(),
+        })
+        .collect();
+    Some(format!(
+        "instantiated from template '{template_name}' with overrides: {}",
+        values.join(", ")
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,12 +270,6 @@ mod tests {
     use serde_json::json;
     use std::convert::TryFrom;
 
-    async fn test_registry(pool: &sqlx::PgPool) -> crate::features::storage::registry::Registry {
-        crate::features::storage::registry::Registry::load(pool, None)
-            .await
-            .expect("registry")
-    }
-
     fn full_spec() -> TemplateSpec {
         TemplateSpec {
             vcpu: 4,
@@ -184,6 +279,10 @@ mod tests {
             kernel_path: Some("/srv/kernel".into()),
             rootfs_path: Some("/srv/rootfs".into()),
             rootfs_size_mb: Some(2048),
+            boot_args: None,
+            init: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
         }
     }
 
@@ -208,6 +307,93 @@ mod tests {
         assert_eq!(req.rootfs_size_mb, rootfs_size_mb);
     }
 
+    #[test]
+    fn template_rate_limiter_carries_into_the_vm_and_is_validated() {
+        let mut spec = full_spec();
+        assert!(validate_spec(&spec).is_ok());
+        spec.rootfs_rate_limiter = Some(json!({"size": -1}));
+        assert!(validate_spec(&spec).is_err());
+
+        let limiter = json!({"bandwidth": {"size": 10_485_760, "refill_time": 1000}});
+        spec.rootfs_rate_limiter = Some(limiter.clone());
+        assert!(validate_spec(&spec).is_ok());
+        spec.entropy_rate_limiter = Some(json!({"ops": "many"}));
+        assert!(validate_spec(&spec).is_err());
+        spec.entropy_rate_limiter = Some(json!({"ops": {"size": 100, "refill_time": 1000}}));
+        let req = spec.into_vm_req("vm".into());
+        assert_eq!(req.rootfs_rate_limiter, Some(limiter));
+        assert!(req.entropy_rate_limiter.is_some());
+    }
+
+    #[test]
+    fn template_spec_into_vm_req_carries_boot_args_and_init() {
+        let mut spec = full_spec();
+        spec.boot_args = Some("ro init=/sbin/init quiet".into());
+        assert_eq!(
+            spec.clone().into_vm_req("vm".into()).boot_args.as_deref(),
+            Some("ro init=/sbin/init quiet")
+        );
+
+        spec.init = Some("/usr/bin/custom-init".into());
+        assert_eq!(
+            spec.clone().into_vm_req("vm".into()).boot_args.as_deref(),
+            Some("ro quiet init=/usr/bin/custom-init")
+        );
+
+        spec.boot_args = None;
+        assert_eq!(
+            spec.into_vm_req("vm".into()).boot_args.as_deref(),
+            Some("init=/usr/bin/custom-init")
+        );
+    }
+
+    #[test]
+    fn instantiate_overrides_merge_over_the_template_spec() {
+        let spec = full_spec();
+        let req = InstantiateTemplateReq {
+            name: "big".into(),
+            mem_mib: Some(8192),
+            tags: Some(vec!["env:dev".into()]),
+            ..Default::default()
+        };
+        assert_eq!(req.overridden_fields(), vec!["mem_mib", "tags"]);
+
+        let vm_req = req.clone().into_vm_req(spec.clone());
+        assert_eq!(vm_req.name, "big");
+        assert_eq!(vm_req.vcpu, spec.vcpu);
+        assert_eq!(vm_req.mem_mib, 8192);
+        assert_eq!(vm_req.tags, vec!["env:dev".to_string()]);
+        assert_eq!(spec.mem_mib, 4096, "template spec is left as-is");
+
+        assert_eq!(
+            overrides_event("ubuntu", &vm_req, &req.overridden_fields()).as_deref(),
+            Some(
+                "instantiated from template 'ubuntu' with overrides: mem_mib=8192, tags=[env:dev]"
+            )
+        );
+        assert_eq!(overrides_event("ubuntu", &vm_req, &[]), None);
+    }
+
+    #[test]
+    fn instantiate_overrides_are_bounds_checked() {
+        let with = |vcpu, mem_mib| InstantiateTemplateReq {
+            name: "vm".into(),
+            vcpu,
+            mem_mib,
+            tags: None,
+        };
+        assert!(validate_overrides(&with(None, None)).is_ok());
+        assert!(validate_overrides(&with(Some(32), Some(128))).is_ok());
+        assert!(validate_overrides(&with(Some(0), None)).is_err());
+        assert!(validate_overrides(&with(Some(33), None)).is_err());
+        assert!(validate_overrides(&with(None, Some(64))).is_err());
+        assert!(validate_overrides(&with(None, Some(MAX_OVERRIDE_MEM_MIB + 1))).is_err());
+
+        let mut blank_tag = with(None, None);
+        blank_tag.tags = Some(vec![" ".into()]);
+        assert!(validate_overrides(&blank_tag).is_err());
+    }
+
     #[test]
     fn template_spec_into_vm_req_blanks_non_template_fields() {
         let req = full_spec().into_vm_req("any".into());
@@ -234,6 +420,10 @@ mod tests {
             kernel_path: None,
             rootfs_path: None,
             rootfs_size_mb: None,
+            boot_args: None,
+            init: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
         };
 
         let req = spec.into_vm_req("tiny-vm".into());
@@ -261,6 +451,10 @@ mod tests {
             kernel_path: None,
             rootfs_path: None,
             rootfs_size_mb: None,
+            boot_args: None,
+            init: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
         };
         let weird_name = "  Mixed-Case Name  ".to_string();
         let req = spec.clone().into_vm_req(weird_name.clone());
@@ -282,36 +476,9 @@ mod tests {
             .unwrap();
         let images = crate::features::images::repo::ImageRepository::new(pool.clone(), "/tmp");
 
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let registry = test_registry(&pool).await;
         let state = crate::AppState {
-            db: pool.clone(),
-            hosts: hosts.clone(),
-            images: images.clone(),
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
+            images,
+            ..crate::AppState::for_tests(pool.clone()).await
         };
 
         let create_req = CreateTemplateReq {
@@ -324,20 +491,30 @@ mod tests {
                 kernel_path: Some("/tmp/kernel".into()),
                 rootfs_path: Some("/tmp/rootfs".into()),
                 rootfs_size_mb: None,
+                boot_args: None,
+                init: None,
+                rootfs_rate_limiter: None,
+                entropy_rate_limiter: None,
             },
         };
         let spec = create_req.spec.clone();
 
-        let Json(create_resp) = super::create(Extension(state.clone()), Json(create_req.clone()))
-            .await
-            .unwrap();
+        let Json(create_resp) = super::create(
+            Extension(state.clone()),
+            Actor::system(state.db.clone()),
+            Json(create_req.clone()),
+        )
+        .await
+        .unwrap();
         let template_id = create_resp.id;
 
         let Json(inst_resp) = super::instantiate(
             Extension(state.clone()),
+            Actor::system(state.db.clone()),
             Path(TemplatePathParams { id: template_id }),
             Json(InstantiateTemplateReq {
                 name: "vm-from-template".into(),
+                ..Default::default()
             }),
         )
         .await
@@ -3,10 +3,18 @@
 /// This module provides functions to log user actions to the audit_logs table
 /// for compliance, security auditing, and debugging purposes.
 use anyhow::Result;
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
 use nexus_types::{AuditAction, AuditLog, AuditLogQueryParams, ListAuditLogsResponse};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::client_ip::ClientIp;
+use super::repo::AuthenticatedUser;
+use crate::AppState;
+
 /// Log a user action to the audit trail
 ///
 /// # Arguments
@@ -143,6 +151,135 @@ pub async fn log_login(
     .await
 }
 
+/// The caller of a request, as the audit trail records them. Extract it in a
+/// mutating handler and hand the operation's result to [`Actor::record`];
+/// requests without a signed-in user (auth disabled) are logged as "system".
+pub struct Actor {
+    db: PgPool,
+    pub user_id: Option<Uuid>,
+    pub username: String,
+    pub ip: Option<String>,
+}
+
+/// What an audited action was applied to: a resource type, plus its id once
+/// known (a failed create has none).
+pub struct Resource {
+    pub kind: &'static str,
+    pub id: Option<Uuid>,
+}
+
+impl From<(&'static str, Uuid)> for Resource {
+    fn from((kind, id): (&'static str, Uuid)) -> Self {
+        Self { kind, id: Some(id) }
+    }
+}
+
+impl From<(&'static str, Option<Uuid>)> for Resource {
+    fn from((kind, id): (&'static str, Option<Uuid>)) -> Self {
+        Self { kind, id }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let st = parts
+            .extensions
+            .get::<AppState>()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        let (user_id, username) = match parts.extensions.get::<AuthenticatedUser>() {
+            Some(user) => (Some(user.id), user.username.clone()),
+            None => (None, "system".to_string()),
+        };
+        Ok(Self {
+            db: st.db.clone(),
+            user_id,
+            username,
+            ip: parts
+                .extensions
+                .get::<ClientIp>()
+                .and_then(|ClientIp(ip)| *ip)
+                .map(|ip| ip.to_string()),
+        })
+    }
+}
+
+impl Actor {
+    /// An anonymous caller, for handlers called directly from tests.
+    #[cfg(test)]
+    pub fn system(db: PgPool) -> Self {
+        Self {
+            db,
+            user_id: None,
+            username: "system".to_string(),
+            ip: None,
+        }
+    }
+
+    /// Records `action` on `resource` as succeeded or failed per `result`,
+    /// keeping the error's full chain. A failed insert is only logged: the
+    /// audit trail never fails the request it describes.
+    pub async fn record<T, E: std::fmt::Display>(
+        &self,
+        action: AuditAction,
+        resource: impl Into<Resource>,
+        result: &Result<T, E>,
+    ) {
+        self.record_with(action, resource, None, result).await
+    }
+
+    /// [`Actor::record`] with extra context stored in `details`.
+    pub async fn record_with<T, E: std::fmt::Display>(
+        &self,
+        action: AuditAction,
+        resource: impl Into<Resource>,
+        details: Option<serde_json::Value>,
+        result: &Result<T, E>,
+    ) {
+        let resource = resource.into();
+        let error = result.as_ref().err().map(|err| format!("{err:#}"));
+        let name = action.as_str();
+        if let Err(err) = log_action(
+            &self.db,
+            self.user_id,
+            &self.username,
+            action,
+            Some(resource.kind),
+            resource.id,
+            details,
+            self.ip.as_deref(),
+            error.is_none(),
+            error.as_deref(),
+        )
+        .await
+        {
+            tracing::warn!(action = name, error = ?err, "failed to write audit log");
+        }
+    }
+}
+
+/// A `(status, message)` handler result in the form [`Actor::record`] takes.
+pub fn status_outcome<T>(result: &Result<T, (StatusCode, String)>) -> Result<(), String> {
+    match result {
+        Ok(_) => Ok(()),
+        Err((status, msg)) if msg.is_empty() => Err(status.to_string()),
+        Err((status, msg)) => Err(format!("{status}: {msg}")),
+    }
+}
+
+/// A handler's finished response in the form [`Actor::record`] takes: any
+/// non-2xx status is a failure.
+pub fn response_outcome(response: &axum::response::Response) -> Result<(), StatusCode> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(status)
+    }
+}
+
 /// Query audit logs with filters and pagination
 ///
 /// Uses a fixed query with optional WHERE conditions.
@@ -238,5 +375,95 @@ mod tests {
         assert_eq!(AuditAction::Login.as_str(), "login");
         assert_eq!(AuditAction::CreateVm.as_str(), "create_vm");
         assert_eq!(AuditAction::DeleteFunction.as_str(), "delete_function");
+        assert_eq!(AuditAction::ExecContainer.as_str(), "exec_container");
+        assert_eq!(AuditAction::CancelJob.as_str(), "cancel_job");
+    }
+
+    #[test]
+    fn handler_errors_are_recorded_as_failures() {
+        assert_eq!(status_outcome(&Ok::<_, (StatusCode, String)>(1)), Ok(()));
+        assert_eq!(
+            status_outcome::<()>(&Err((StatusCode::CONFLICT, "in use".into()))),
+            Err("409 Conflict: in use".to_string())
+        );
+        assert_eq!(
+            status_outcome::<()>(&Err((StatusCode::NOT_FOUND, String::new()))),
+            Err("404 Not Found".to_string())
+        );
+
+        use axum::response::IntoResponse;
+        assert_eq!(
+            response_outcome(&StatusCode::NO_CONTENT.into_response()),
+            Ok(())
+        );
+        assert_eq!(
+            response_outcome(&StatusCode::UNPROCESSABLE_ENTITY.into_response()),
+            Err(StatusCode::UNPROCESSABLE_ENTITY)
+        );
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn record_logs_failures_with_their_error(pool: PgPool) {
+        let actor = Actor {
+            username: "alice".into(),
+            ip: Some("10.0.0.7".into()),
+            ..Actor::system(pool.clone())
+        };
+        let vm_id = Uuid::new_v4();
+        let failed: Result<(), anyhow::Error> =
+            Err(anyhow::anyhow!("agent unreachable").context("failed to stop vm"));
+        actor
+            .record(AuditAction::StopVm, ("vm", vm_id), &failed)
+            .await;
+        actor
+            .record(
+                AuditAction::StartVm,
+                ("vm", vm_id),
+                &Ok::<_, anyhow::Error>(()),
+            )
+            .await;
+
+        let logs = list_audit_logs(
+            &pool,
+            AuditLogQueryParams {
+                user_id: None,
+                action: Some("stop_vm".into()),
+                resource_type: Some("vm".into()),
+                limit: None,
+                offset: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(logs.total, 1);
+        let log = &logs.items[0];
+        assert!(!log.success);
+        assert_eq!(
+            log.error_message.as_deref(),
+            Some("failed to stop vm: agent unreachable")
+        );
+        assert_eq!(
+            (
+                log.username.as_str(),
+                log.resource_id,
+                log.ip_address.as_deref()
+            ),
+            ("alice", Some(vm_id), Some("10.0.0.7"))
+        );
+
+        let started = list_audit_logs(
+            &pool,
+            AuditLogQueryParams {
+                user_id: None,
+                action: Some("start_vm".into()),
+                resource_type: None,
+                limit: None,
+                offset: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert!(started.items[0].success && started.items[0].error_message.is_none());
     }
 }
@@ -0,0 +1,212 @@
+//! The address a request came from, as the audit trail records it.
+//!
+//! `X-Forwarded-For` / `X-Real-IP` are only believed when the TCP peer is a
+//! trusted proxy (`MANAGER_TRUSTED_PROXIES`, loopback by default); anyone
+//! else could put any address in them. Without proxy headers the peer's own
+//! address is used.
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+pub const TRUSTED_PROXIES_ENV: &str = "MANAGER_TRUSTED_PROXIES";
+
+/// The resolved client address, stashed in request extensions by
+/// [`middleware`]. Extracting it never fails: it is `None` when the address
+/// is unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ClientIp>()
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+/// Peers whose proxy headers are believed: IPs or CIDR ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<(IpAddr, u8)>);
+
+impl TrustedProxies {
+    /// Reads [`TRUSTED_PROXIES_ENV`]: a comma-separated list such as
+    /// `10.0.0.0/8,192.168.1.5`, or empty to trust no proxy. Unset trusts
+    /// loopback, where the UI's server-side proxy runs.
+    pub fn from_env() -> Self {
+        match std::env::var(TRUSTED_PROXIES_ENV) {
+            Ok(raw) => Self::parse(&raw),
+            Err(_) => Self::parse("127.0.0.0/8,::1"),
+        }
+    }
+
+    /// Entries that don't parse are logged and skipped.
+    pub fn parse(raw: &str) -> Self {
+        let mut ranges = Vec::new();
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match parse_range(entry) {
+                Some(range) => ranges.push(range),
+                None => tracing::warn!(entry, "ignoring invalid {TRUSTED_PROXIES_ENV} entry"),
+            }
+        }
+        Self(ranges)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0
+            .iter()
+            .any(|&(network, prefix)| in_range(ip, network, prefix))
+    }
+}
+
+fn parse_range(entry: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
+
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        // An IPv4 client seen through a dual-stack socket
+        (IpAddr::V6(ip), IpAddr::V4(_)) => ip
+            .to_ipv4_mapped()
+            .is_some_and(|ip| in_range(IpAddr::V4(ip), network, prefix)),
+        (IpAddr::V4(_), IpAddr::V6(_)) => false,
+    }
+}
+
+/// One address from a proxy header; proxies sometimes append the port.
+fn parse_addr(raw: &str) -> Option<IpAddr> {
+    let raw = raw.trim();
+    raw.parse::<IpAddr>()
+        .ok()
+        .or_else(|| raw.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Picks the client address for a request from `peer` (unknown when the
+/// server runs without connect info).
+///
+/// A trusted (or unknown) peer's `X-Forwarded-For` is read right to left,
+/// skipping further trusted proxies, so a client can't spoof the entries our
+/// own proxies appended; `X-Real-IP` is the fallback, then the peer.
+pub fn resolve(
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+    trusted: &TrustedProxies,
+) -> Option<IpAddr> {
+    if let Some(peer) = peer.filter(|peer| !trusted.contains(*peer)) {
+        return Some(peer);
+    }
+
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_addr)
+        .collect();
+    if let Some(client) = forwarded.iter().rev().find(|ip| !trusted.contains(**ip)) {
+        return Some(*client);
+    }
+    if let Some(first) = forwarded.first() {
+        return Some(*first);
+    }
+
+    headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_addr)
+        .or(peer)
+}
+
+/// Resolves the [`ClientIp`] of every request. Layered router-wide, outside
+/// the auth middlewares.
+pub async fn middleware(mut req: Request, next: Next) -> Response {
+    static TRUSTED: OnceLock<TrustedProxies> = OnceLock::new();
+    let trusted = TRUSTED.get_or_init(TrustedProxies::from_env);
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let ip = resolve(req.headers(), peer, trusted);
+    req.extensions_mut().insert(ClientIp(ip));
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn proxy_headers_are_only_believed_from_trusted_peers() {
+        let trusted = TrustedProxies::parse("127.0.0.0/8, ::1, 10.1.0.0/16, bogus");
+        assert!(trusted.contains(ip("10.1.200.3")));
+        assert!(trusted.contains(ip("::ffff:127.0.0.1")));
+        assert!(!trusted.contains(ip("10.2.0.1")));
+
+        let spoofed = headers(&[("x-forwarded-for", "6.6.6.6")]);
+        assert_eq!(
+            resolve(&spoofed, Some(ip("203.0.113.9")), &trusted),
+            Some(ip("203.0.113.9"))
+        );
+
+        // The client prepended a fake entry; our proxies appended the real one
+        let proxied = headers(&[("x-forwarded-for", "6.6.6.6, 198.51.100.4:5123, 10.1.0.2")]);
+        assert_eq!(
+            resolve(&proxied, Some(ip("127.0.0.1")), &trusted),
+            Some(ip("198.51.100.4"))
+        );
+
+        let real_ip = headers(&[("x-real-ip", "198.51.100.7")]);
+        assert_eq!(
+            resolve(&real_ip, Some(ip("::1")), &trusted),
+            Some(ip("198.51.100.7"))
+        );
+        assert_eq!(
+            resolve(&HeaderMap::new(), Some(ip("127.0.0.1")), &trusted),
+            Some(ip("127.0.0.1"))
+        );
+        assert_eq!(resolve(&HeaderMap::new(), None, &trusted), None);
+
+        let nobody = TrustedProxies::parse("");
+        assert_eq!(
+            resolve(&proxied, Some(ip("127.0.0.1")), &nobody),
+            Some(ip("127.0.0.1"))
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Slows password guessing on `/v1/auth/login`. Failed logins are counted
+//! per username and per client IP over a sliding window; once either count
+//! reaches its limit further attempts get 429 without the password being
+//! checked, until the oldest failure ages out of the window. Each attempt is
+//! counted as failed before its password is checked and taken back if it
+//! wasn't, so concurrent guesses can't overshoot the limit.
+//!
+//! That is in memory and forgets on restart; [`AccountLockout`] is the
+//! persistent per-account lock kept on the user row.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const MAX_PER_USERNAME_ENV: &str = "MANAGER_LOGIN_MAX_FAILURES";
+const MAX_PER_IP_ENV: &str = "MANAGER_LOGIN_MAX_FAILURES_PER_IP";
+const WINDOW_ENV: &str = "MANAGER_LOGIN_FAILURE_WINDOW_SECS";
+const DEFAULT_MAX_PER_USERNAME: usize = 5;
+const DEFAULT_MAX_PER_IP: usize = 20;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(15 * 60);
+const LOCKOUT_THRESHOLD_ENV: &str = "MANAGER_ACCOUNT_LOCKOUT_THRESHOLD";
+const LOCKOUT_SECS_ENV: &str = "MANAGER_ACCOUNT_LOCKOUT_SECS";
+const DEFAULT_LOCKOUT_THRESHOLD: usize = 10;
+const DEFAULT_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+/// Above this many tracked keys every stale one is swept on the next failure.
+const SWEEP_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottleLimits {
+    pub per_username: usize,
+    pub per_ip: usize,
+    pub window: Duration,
+}
+
+impl ThrottleLimits {
+    /// Limits from `MANAGER_LOGIN_MAX_FAILURES` /
+    /// `MANAGER_LOGIN_MAX_FAILURES_PER_IP` / `MANAGER_LOGIN_FAILURE_WINDOW_SECS`
+    /// (defaults: 5 per username, 20 per IP, 15 minutes).
+    pub fn from_env() -> Self {
+        let var = |key| std::env::var(key).ok();
+        Self {
+            per_username: parse_positive(var(MAX_PER_USERNAME_ENV), DEFAULT_MAX_PER_USERNAME),
+            per_ip: parse_positive(var(MAX_PER_IP_ENV), DEFAULT_MAX_PER_IP),
+            window: Duration::from_secs(parse_positive(
+                var(WINDOW_ENV),
+                DEFAULT_WINDOW.as_secs() as usize,
+            ) as u64),
+        }
+    }
+}
+
+/// Consecutive wrong passwords that lock an account, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountLockout {
+    pub threshold: u32,
+    pub cooldown: Duration,
+}
+
+impl AccountLockout {
+    /// From `MANAGER_ACCOUNT_LOCKOUT_THRESHOLD` / `MANAGER_ACCOUNT_LOCKOUT_SECS`
+    /// (defaults: 10 failures, 15 minutes).
+    pub fn from_env() -> Self {
+        let var = |key| std::env::var(key).ok();
+        Self {
+            threshold: parse_positive(var(LOCKOUT_THRESHOLD_ENV), DEFAULT_LOCKOUT_THRESHOLD)
+                .try_into()
+                .unwrap_or(u32::MAX),
+            cooldown: Duration::from_secs(parse_positive(
+                var(LOCKOUT_SECS_ENV),
+                DEFAULT_LOCKOUT.as_secs() as usize,
+            ) as u64),
+        }
+    }
+}
+
+fn parse_positive(raw: Option<String>, default: usize) -> usize {
+    raw.and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+/// A login attempt [`LoginThrottle::claim`] has counted as a failure.
+#[derive(Debug)]
+#[must_use = "an attempt that didn't fail must be released"]
+pub struct Attempt {
+    username: String,
+    ip: Option<IpAddr>,
+    at: Instant,
+}
+
+#[derive(Default)]
+struct Failures {
+    by_username: HashMap<String, VecDeque<Instant>>,
+    by_ip: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+/// Recent login failures, shared through `AppState`.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    limits: ThrottleLimits,
+    lockout: AccountLockout,
+    failures: Arc<Mutex<Failures>>,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self::new(ThrottleLimits::from_env(), AccountLockout::from_env())
+    }
+}
+
+fn prune(times: &mut VecDeque<Instant>, cutoff: Option<Instant>) {
+    while times.front().is_some_and(|t| Some(*t) <= cutoff) {
+        times.pop_front();
+    }
+}
+
+/// Usernames are matched case-insensitively so a guesser can't reset the
+/// count by changing case.
+fn username_key(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+impl LoginThrottle {
+    pub fn new(limits: ThrottleLimits, lockout: AccountLockout) -> Self {
+        Self {
+            limits,
+            lockout,
+            failures: Arc::new(Mutex::new(Failures::default())),
+        }
+    }
+
+    pub fn lockout(&self) -> AccountLockout {
+        self.lockout
+    }
+
+    /// Counts a login attempt as failed before its password is checked, so
+    /// parallel guesses can't all slip in under the limit. `Err` with how
+    /// long until another attempt is allowed when `username` or `ip` is
+    /// locked out. An attempt that didn't fail is handed back to
+    /// [`Self::release`].
+    pub fn claim(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<Attempt, Duration> {
+        let cutoff = now.checked_sub(self.limits.window);
+        let mut failures = self.failures.lock().unwrap();
+        let mut wait = None;
+        let mut over = |times: Option<&mut VecDeque<Instant>>, limit: usize| {
+            let Some(times) = times else { return };
+            prune(times, cutoff);
+            if times.len() >= limit {
+                // Allowed again once enough failures age out to drop below the limit
+                let unlock = times[times.len() - limit] + self.limits.window;
+                let remaining = unlock.saturating_duration_since(now);
+                wait = Some(wait.map_or(remaining, |w: Duration| w.max(remaining)));
+            }
+        };
+        over(
+            failures.by_username.get_mut(&username_key(username)),
+            self.limits.per_username,
+        );
+        if let Some(ip) = ip {
+            over(failures.by_ip.get_mut(&ip), self.limits.per_ip);
+        }
+        if let Some(wait) = wait {
+            return Err(wait);
+        }
+
+        let Failures { by_username, by_ip } = &mut *failures;
+        if by_username.len() + by_ip.len() > SWEEP_THRESHOLD {
+            by_username.retain(|_, times| {
+                prune(times, cutoff);
+                !times.is_empty()
+            });
+            by_ip.retain(|_, times| {
+                prune(times, cutoff);
+                !times.is_empty()
+            });
+        }
+        let attempt = Attempt {
+            username: username_key(username),
+            ip,
+            at: now,
+        };
+        by_username
+            .entry(attempt.username.clone())
+            .or_default()
+            .push_back(now);
+        if let Some(ip) = ip {
+            by_ip.entry(ip).or_default().push_back(now);
+        }
+        Ok(attempt)
+    }
+
+    /// Take back an attempt [`Self::claim`] counted, for one that succeeded
+    /// or was refused for another reason than the password.
+    pub fn release(&self, attempt: Attempt) {
+        let mut failures = self.failures.lock().unwrap();
+        let drop_one = |times: Option<&mut VecDeque<Instant>>| {
+            if let Some(times) = times {
+                if let Some(pos) = times.iter().position(|t| *t == attempt.at) {
+                    times.remove(pos);
+                }
+            }
+        };
+        drop_one(failures.by_username.get_mut(&attempt.username));
+        if let Some(ip) = attempt.ip {
+            drop_one(failures.by_ip.get_mut(&ip));
+        }
+    }
+
+    /// A successful login clears the username's failures; the IP's stay so a
+    /// guesser can't reset them by logging into an account of their own.
+    pub fn record_success(&self, username: &str) {
+        self.failures
+            .lock()
+            .unwrap()
+            .by_username
+            .remove(&username_key(username));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_out_per_username_and_per_ip_until_failures_age_out() {
+        let throttle = LoginThrottle::new(
+            ThrottleLimits {
+                per_username: 3,
+                per_ip: 5,
+                window: Duration::from_secs(60),
+            },
+            AccountLockout {
+                threshold: 10,
+                cooldown: Duration::from_secs(900),
+            },
+        );
+        let t0 = Instant::now();
+        let secs = |s| t0 + Duration::from_secs(s);
+        let ip: IpAddr = "198.51.100.4".parse().unwrap();
+        let other_ip: IpAddr = "198.51.100.5".parse().unwrap();
+
+        for s in 0..3 {
+            let _failed = throttle.claim("alice", Some(ip), secs(s)).unwrap();
+        }
+        // Locked from any address, in any case, until the first failure ages out
+        assert_eq!(
+            throttle
+                .claim("Alice", Some(other_ip), secs(10))
+                .unwrap_err(),
+            Duration::from_secs(50)
+        );
+
+        // The IP is at 3 of 5; two failures on other accounts lock it out
+        let _failed = throttle.claim("bob", Some(ip), secs(11)).unwrap();
+        let _failed = throttle.claim("carol", Some(ip), secs(12)).unwrap();
+        assert!(throttle.claim("dave", Some(ip), secs(13)).is_err());
+        throttle.release(throttle.claim("dave", Some(other_ip), secs(13)).unwrap());
+        throttle.release(throttle.claim("alice", None, secs(60)).unwrap());
+
+        throttle.record_success("alice");
+        assert!(throttle.claim("alice", None, secs(30)).is_ok());
+    }
+
+    #[test]
+    fn claimed_attempts_count_until_released() {
+        let throttle = LoginThrottle::new(
+            ThrottleLimits {
+                per_username: 2,
+                per_ip: 10,
+                window: Duration::from_secs(60),
+            },
+            AccountLockout {
+                threshold: 10,
+                cooldown: Duration::from_secs(900),
+            },
+        );
+        let now = Instant::now();
+        let ip: IpAddr = "198.51.100.4".parse().unwrap();
+
+        // Two guesses in flight use up the limit before either is verified
+        let first = throttle.claim("alice", Some(ip), now).unwrap();
+        let _second = throttle.claim("alice", Some(ip), now).unwrap();
+        assert!(throttle.claim("alice", Some(ip), now).is_err());
+
+        // One turning out right frees its slot, on the IP too
+        throttle.release(first);
+        let third = throttle.claim("alice", Some(ip), now).unwrap();
+        throttle.release(third);
+        let failures = throttle.failures.lock().unwrap();
+        assert_eq!(failures.by_ip[&ip].len(), 1);
+    }
+}
@@ -2,7 +2,7 @@ use crate::features::users::repo::{AuthenticatedUser, UserRepoError};
 use crate::AppState;
 use axum::{
     extract::Request,
-    http::{header::AUTHORIZATION, StatusCode},
+    http::{header::AUTHORIZATION, Method, StatusCode},
     middleware::Next,
     response::Response,
     Extension,
@@ -18,6 +18,10 @@ pub async fn auth_middleware(
     if req.uri().path().ends_with("/login") {
         return Ok(next.run(req).await);
     }
+    // Already identified by the router-wide `optional_auth_middleware`
+    if req.extensions().get::<AuthenticatedUser>().is_some() {
+        return Ok(next.run(req).await);
+    }
 
     let auth_header = req
         .headers()
@@ -51,6 +55,9 @@ pub async fn optional_auth_middleware(
     mut req: Request,
     next: Next,
 ) -> Response {
+    if req.extensions().get::<AuthenticatedUser>().is_some() {
+        return next.run(req).await;
+    }
     if let Some(auth_header) = req
         .headers()
         .get(AUTHORIZATION)
@@ -80,26 +87,117 @@ pub async fn require_admin(
     Ok(next.run(req).await)
 }
 
-/// Helper to extract client IP address from request
-pub fn get_client_ip(headers: &axum::http::HeaderMap) -> Option<String> {
-    // Try X-Forwarded-For header first (for proxied requests)
-    if let Some(forwarded) = headers.get("x-forwarded-for") {
-        if let Ok(forwarded_str) = forwarded.to_str() {
-            // Take the first IP if there are multiple
-            return forwarded_str
-                .split(',')
-                .next()
-                .map(|s| s.trim().to_string());
+/// Rejects every request a Viewer could change something with, before it
+/// reaches a handler. Layered router-wide inside `optional_auth_middleware`,
+/// so it sees the caller on every route, authenticated or not.
+pub async fn viewer_read_only(req: Request, next: Next) -> Result<Response, StatusCode> {
+    if let Some(user) = req.extensions().get::<AuthenticatedUser>() {
+        if user.role == Role::Viewer && !viewer_may(req.method(), req.uri().path()) {
+            return Err(StatusCode::FORBIDDEN);
         }
     }
+    Ok(next.run(req).await)
+}
 
-    // Try X-Real-IP header
-    if let Some(real_ip) = headers.get("x-real-ip") {
-        if let Ok(ip_str) = real_ip.to_str() {
-            return Some(ip_str.to_string());
-        }
+/// Viewers may read anything and manage their own session and profile under
+/// `/v1/auth`, but not open the interactive shell or VNC websockets: those
+/// are GETs that hand over a keyboard.
+pub fn viewer_may(method: &Method, path: &str) -> bool {
+    if path.starts_with("/v1/auth/") {
+        return true;
+    }
+    if !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    !(path.ends_with("/shell/ws") || path.ends_with("/console/vnc/ws"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        routing::{get, post},
+        Router,
+    };
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    fn app(role: Role) -> Router {
+        let user = AuthenticatedUser {
+            id: Uuid::new_v4(),
+            username: "someone".into(),
+            role,
+        };
+        // Nested like `features::router`, so the layer sees full paths
+        let vms = Router::new()
+            .route("/", get(|| async { "list" }).post(|| async { "create" }))
+            .route(
+                "/:id",
+                get(|| async { "get" }).delete(|| async { "delete" }),
+            )
+            .route("/:id/start", post(|| async { "start" }))
+            .route("/:id/stop", post(|| async { "stop" }))
+            .route("/:id/shell/ws", get(|| async { "shell" }))
+            .route("/:id/metrics/ws", get(|| async { "metrics" }));
+        Router::new()
+            .nest("/v1/vms", vms)
+            .route("/v1/auth/me/password", post(|| async { "password" }))
+            .layer(axum::middleware::from_fn(viewer_read_only))
+            .layer(axum::Extension(user))
     }
 
-    // TODO: Could extract from connection info if available
-    None
+    async fn status(app: Router, method: Method, uri: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn viewer_can_read_vms_but_not_change_them() {
+        let vm = "/v1/vms/00000000-0000-0000-0000-000000000001";
+        let viewer = app(Role::Viewer);
+
+        assert_eq!(
+            status(viewer.clone(), Method::GET, "/v1/vms").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(viewer.clone(), Method::GET, vm).await,
+            StatusCode::OK
+        );
+        let metrics = format!("{vm}/metrics/ws");
+        assert_eq!(
+            status(viewer.clone(), Method::GET, &metrics).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(viewer.clone(), Method::POST, "/v1/auth/me/password").await,
+            StatusCode::OK
+        );
+
+        for (method, uri) in [
+            (Method::POST, "/v1/vms".to_string()),
+            (Method::POST, format!("{vm}/start")),
+            (Method::POST, format!("{vm}/stop")),
+            (Method::DELETE, vm.to_string()),
+            (Method::GET, format!("{vm}/shell/ws")),
+        ] {
+            assert_eq!(
+                status(viewer.clone(), method.clone(), &uri).await,
+                StatusCode::FORBIDDEN,
+                "{method} {uri}"
+            );
+        }
+
+        let user = app(Role::User);
+        assert_eq!(
+            status(user.clone(), Method::POST, "/v1/vms").await,
+            StatusCode::OK
+        );
+        assert_eq!(status(user, Method::DELETE, vm).await, StatusCode::OK);
+    }
 }
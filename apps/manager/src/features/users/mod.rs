@@ -5,7 +5,11 @@ use axum::{
 
 pub mod audit;
 pub mod authz;
+pub mod client_ip;
+pub mod login_throttle;
 pub mod middleware;
+pub mod password;
+pub mod quota;
 pub mod repo;
 pub mod routes;
 
@@ -41,5 +45,7 @@ pub fn users_router() -> Router {
                 .delete(routes::delete),
         )
         .route("/:id/avatar", get(routes::get_user_avatar))
+        .route("/:id/quota", get(routes::get_quota).put(routes::put_quota))
+        .route("/:id/unlock", post(routes::unlock))
         .layer(from_fn(middleware::require_admin)) // Protect all user management routes - admin only
 }
@@ -0,0 +1,83 @@
+//! Strength rules for passwords set through the API. Hashing (Argon2) lives
+//! in the repo; this only decides what is acceptable to hash.
+
+/// Matches the UI's own client-side check.
+pub const MIN_LENGTH: usize = 8;
+
+/// Passwords that top every leaked-password list, compared case-insensitively.
+const COMMON: &[&str] = &[
+    "password",
+    "password1",
+    "password123",
+    "passw0rd",
+    "p@ssw0rd",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "87654321",
+    "11111111",
+    "00000000",
+    "qwertyui",
+    "qwerty123",
+    "qwertyuiop",
+    "1q2w3e4r",
+    "1qaz2wsx",
+    "asdfghjk",
+    "abc12345",
+    "abcd1234",
+    "iloveyou",
+    "sunshine",
+    "princess",
+    "football",
+    "baseball",
+    "superman",
+    "trustno1",
+    "welcome1",
+    "letmein1",
+    "admin123",
+    "administrator",
+    "changeme",
+    "root1234",
+    "toor1234",
+];
+
+/// Why a password was refused. Returned to the caller as a 400.
+#[derive(Debug, thiserror::Error)]
+#[error("password too weak: {0}")]
+pub struct WeakPassword(&'static str);
+
+/// Checks `password` against the rules; `username` is empty when the caller
+/// doesn't know it.
+pub fn check_strength(username: &str, password: &str) -> Result<(), WeakPassword> {
+    if password.chars().count() < MIN_LENGTH {
+        return Err(WeakPassword("must be at least 8 characters"));
+    }
+    let lower = password.to_lowercase();
+    if COMMON.contains(&lower.as_str()) {
+        return Err(WeakPassword("is a commonly used password"));
+    }
+    if !username.is_empty() && lower == username.to_lowercase() {
+        return Err(WeakPassword("must differ from the username"));
+    }
+    if lower.chars().all(|c| lower.starts_with(c)) {
+        return Err(WeakPassword("repeats a single character"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weak_passwords_are_refused() {
+        assert!(check_strength("alice", "correct horse").is_ok());
+        assert!(check_strength("", "s3cure-enough").is_ok());
+
+        for weak in ["short", "Password1", "12345678", "aaaaaaaaaa"] {
+            assert!(check_strength("alice", weak).is_err(), "{weak}");
+        }
+        assert!(check_strength("operator", "OPERATOR").is_err());
+        assert!(check_strength("", "operator").is_ok());
+    }
+}
@@ -0,0 +1,409 @@
+//! Per-user resource quotas. Usage is summed from the `created_by_user_id`
+//! of the user's VMs, functions and containers whenever they create another;
+//! going over a limit fails the create with [`QuotaExceeded`] (409).
+
+use anyhow::Result;
+use nexus_types::{UserQuota, UserQuotaUsage};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A create would take the user over one of their limits. Survives
+/// `.context(..)`, so check it with [`is_exceeded`].
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub limit: &'static str,
+    pub max: i64,
+    pub requested: i64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "quota exceeded: {} is {} but this would bring usage to {}",
+            self.limit, self.max, self.requested
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+pub fn is_exceeded(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<QuotaExceeded>().is_some()
+}
+
+/// Checks `usage + demand` against every set limit.
+pub fn check(
+    quota: &UserQuota,
+    usage: &UserQuotaUsage,
+    demand: &UserQuotaUsage,
+) -> Result<(), QuotaExceeded> {
+    let limits = [
+        (
+            "max_vms",
+            quota.max_vms.map(i64::from),
+            usage.vms,
+            demand.vms,
+        ),
+        (
+            "max_vcpu",
+            quota.max_vcpu.map(i64::from),
+            usage.vcpu,
+            demand.vcpu,
+        ),
+        (
+            "max_mem_mib",
+            quota.max_mem_mib,
+            usage.mem_mib,
+            demand.mem_mib,
+        ),
+        (
+            "max_containers",
+            quota.max_containers.map(i64::from),
+            usage.containers,
+            demand.containers,
+        ),
+    ];
+    for (limit, max, used, wanted) in limits {
+        let Some(max) = max else { continue };
+        if wanted > 0 && used + wanted > max {
+            return Err(QuotaExceeded {
+                limit,
+                max,
+                requested: used + wanted,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A `quota_reservation` row standing in for a create until it has inserted
+/// its own row, so another create for the same user counts it. Nothing is
+/// held open meanwhile: no lock, no connection. Call
+/// [`QuotaReservation::release`] once the row is in; dropping it (a failed
+/// create) deletes the reservation in the background.
+pub struct QuotaReservation {
+    held: Option<(PgPool, Uuid)>,
+}
+
+impl QuotaReservation {
+    /// Between the create's insert and this, the resource counts twice,
+    /// which can only refuse a concurrent create, never let one through.
+    pub async fn release(mut self) -> Result<()> {
+        if let Some((db, id)) = self.held.take() {
+            delete_reservation(&db, id).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        let Some((db, id)) = self.held.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        runtime.spawn(async move {
+            if let Err(err) = delete_reservation(&db, id).await {
+                tracing::warn!(reservation_id = %id, error = ?err, "failed to drop quota reservation");
+            }
+        });
+    }
+}
+
+async fn delete_reservation(db: &PgPool, id: Uuid) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM quota_reservation WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Longest a reservation counts. Creates finish well inside it; past it the
+/// row is one a dead manager left behind.
+const RESERVATION_TTL_SECS: i64 = 3600;
+
+/// Fails with [`QuotaExceeded`] if creating `demand` would take `user_id`
+/// over their quota. Resources created without a user (system-owned, or
+/// with auth disabled) and users without a quota are never limited.
+///
+/// Serialised per user, but only for the check itself: a transaction-scoped
+/// advisory lock on `user_id` covers summing usage plus live reservations
+/// and inserting this one, and is gone when `reserve` returns.
+pub async fn reserve(
+    db: &PgPool,
+    user_id: Option<Uuid>,
+    demand: UserQuotaUsage,
+) -> Result<QuotaReservation> {
+    let Some(user_id) = user_id else {
+        return Ok(QuotaReservation { held: None });
+    };
+    let mut tx = db.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('user_quota:' || $1::text, 0))")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    let Some(quota) = get(&mut *tx, user_id).await? else {
+        tx.commit().await?;
+        return Ok(QuotaReservation { held: None });
+    };
+    sqlx::query(
+        "DELETE FROM quota_reservation WHERE user_id = $1 \
+         AND created_at < now() - make_interval(secs => $2)",
+    )
+    .bind(user_id)
+    .bind(RESERVATION_TTL_SECS as f64)
+    .execute(&mut *tx)
+    .await?;
+    let owned = usage(&mut *tx, user_id).await?;
+    let pending = reserved(&mut *tx, user_id).await?;
+    let used = UserQuotaUsage {
+        vms: owned.vms + pending.vms,
+        vcpu: owned.vcpu + pending.vcpu,
+        mem_mib: owned.mem_mib + pending.mem_mib,
+        containers: owned.containers + pending.containers,
+    };
+    check(&quota, &used, &demand)?;
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO quota_reservation (user_id, vms, vcpu, mem_mib, containers) \
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(demand.vms)
+    .bind(demand.vcpu)
+    .bind(demand.mem_mib)
+    .bind(demand.containers)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(QuotaReservation {
+        held: Some((db.clone(), id)),
+    })
+}
+
+/// What the user's in-flight creates have reserved.
+async fn reserved<'e>(
+    db: impl sqlx::PgExecutor<'e>,
+    user_id: Uuid,
+) -> sqlx::Result<UserQuotaUsage> {
+    let (vms, vcpu, mem_mib, containers): (i64, i64, i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(vms), 0)::BIGINT,
+               COALESCE(SUM(vcpu), 0)::BIGINT,
+               COALESCE(SUM(mem_mib), 0)::BIGINT,
+               COALESCE(SUM(containers), 0)::BIGINT
+        FROM quota_reservation WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+    Ok(UserQuotaUsage {
+        vms,
+        vcpu,
+        mem_mib,
+        containers,
+    })
+}
+
+#[derive(sqlx::FromRow)]
+struct QuotaRow {
+    max_vms: Option<i32>,
+    max_vcpu: Option<i32>,
+    max_mem_mib: Option<i64>,
+    max_containers: Option<i32>,
+}
+
+pub async fn get<'e>(
+    db: impl sqlx::PgExecutor<'e>,
+    user_id: Uuid,
+) -> sqlx::Result<Option<UserQuota>> {
+    let row = sqlx::query_as::<_, QuotaRow>(
+        "SELECT max_vms, max_vcpu, max_mem_mib, max_containers FROM user_quota WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|row| UserQuota {
+        max_vms: row.max_vms,
+        max_vcpu: row.max_vcpu,
+        max_mem_mib: row.max_mem_mib,
+        max_containers: row.max_containers,
+    }))
+}
+
+pub async fn put(db: &PgPool, user_id: Uuid, quota: &UserQuota) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_quota (user_id, max_vms, max_vcpu, max_mem_mib, max_containers)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (user_id) DO UPDATE
+        SET max_vms = EXCLUDED.max_vms,
+            max_vcpu = EXCLUDED.max_vcpu,
+            max_mem_mib = EXCLUDED.max_mem_mib,
+            max_containers = EXCLUDED.max_containers,
+            updated_at = now()
+        "#,
+    )
+    .bind(user_id)
+    .bind(quota.max_vms)
+    .bind(quota.max_vcpu)
+    .bind(quota.max_mem_mib)
+    .bind(quota.max_containers)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Sums what the user owns. Containers are sized the way
+/// `containers::service::create_container` sizes their VM: at least one
+/// vCPU and 512 MiB.
+pub async fn usage<'e>(
+    db: impl sqlx::PgExecutor<'e>,
+    user_id: Uuid,
+) -> sqlx::Result<UserQuotaUsage> {
+    let (vms, vcpu, mem_mib, containers): (i64, i64, i64, i64) = sqlx::query_as(
+        r#"
+        WITH owned AS (
+            SELECT 1::BIGINT AS vms, 0::BIGINT AS containers,
+                   vcpu::BIGINT AS vcpu, mem_mib::BIGINT AS mem_mib
+            FROM vm WHERE created_by_user_id = $1
+            UNION ALL
+            SELECT 0, 0, vcpu::BIGINT, memory_mb::BIGINT
+            FROM function WHERE created_by_user_id = $1
+            UNION ALL
+            SELECT 0, 1,
+                   GREATEST(CEIL(COALESCE(cpu_limit, 1)), 1)::BIGINT,
+                   GREATEST(COALESCE(memory_limit_mb, 512), 512)::BIGINT
+            FROM containers WHERE created_by_user_id = $1
+        )
+        SELECT COALESCE(SUM(vms), 0)::BIGINT,
+               COALESCE(SUM(vcpu), 0)::BIGINT,
+               COALESCE(SUM(mem_mib), 0)::BIGINT,
+               COALESCE(SUM(containers), 0)::BIGINT
+        FROM owned
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await?;
+    Ok(UserQuotaUsage {
+        vms,
+        vcpu,
+        mem_mib,
+        containers,
+    })
+}
+
+pub fn validate(quota: &UserQuota) -> Result<()> {
+    let negative = quota.max_vms.is_some_and(|v| v < 0)
+        || quota.max_vcpu.is_some_and(|v| v < 0)
+        || quota.max_mem_mib.is_some_and(|v| v < 0)
+        || quota.max_containers.is_some_and(|v| v < 0);
+    if negative {
+        anyhow::bail!("invalid quota: limits must be zero or more");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_reports_the_first_limit_exceeded() {
+        let quota = UserQuota {
+            max_vms: Some(2),
+            max_vcpu: Some(4),
+            max_mem_mib: None,
+            max_containers: Some(0),
+        };
+        let usage = UserQuotaUsage {
+            vms: 1,
+            vcpu: 2,
+            mem_mib: 1 << 20,
+            containers: 0,
+        };
+        let vm = |vcpu| UserQuotaUsage {
+            vms: 1,
+            vcpu,
+            mem_mib: 1024,
+            containers: 0,
+        };
+
+        assert!(check(&quota, &usage, &vm(2)).is_ok());
+        let err = check(&quota, &usage, &vm(3)).unwrap_err();
+        assert_eq!((err.limit, err.max, err.requested), ("max_vcpu", 4, 5));
+
+        let container = UserQuotaUsage {
+            containers: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            check(&quota, &usage, &container).unwrap_err().limit,
+            "max_containers"
+        );
+
+        // Already over a limit (it was lowered) doesn't block other kinds
+        let over = UserQuotaUsage { vms: 5, ..usage };
+        assert!(check(&quota, &over, &UserQuotaUsage::default()).is_ok());
+        assert!(is_exceeded(&anyhow::Error::new(
+            check(&quota, &over, &vm(1)).unwrap_err()
+        )));
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn reservations_count_until_the_row_is_in(pool: PgPool) {
+        let user = crate::features::users::repo::UserRepository::new(pool.clone())
+            .create_user("quota-user", "password123", nexus_types::Role::User)
+            .await
+            .unwrap();
+        let quota = UserQuota {
+            max_vms: Some(1),
+            ..Default::default()
+        };
+        put(&pool, user.id, &quota).await.unwrap();
+        let one_vm = UserQuotaUsage {
+            vms: 1,
+            ..Default::default()
+        };
+
+        // A reservation holds no lock: the second create is refused at once
+        let first = reserve(&pool, Some(user.id), one_vm).await.unwrap();
+        let err = reserve(&pool, Some(user.id), one_vm).await.err().unwrap();
+        assert!(is_exceeded(&err));
+
+        // A failed create gives its reservation back
+        drop(first);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let first = reserve(&pool, Some(user.id), one_vm).await.unwrap();
+
+        // The create's row lands before its reservation is released
+        let host = crate::features::hosts::repo::HostRepository::new(pool.clone())
+            .register("quota-host", "http://127.0.0.1:1", serde_json::json!({}))
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO vm (id, name, host_id, api_sock, tap, log_path, fc_unit, created_by_user_id) \
+             VALUES ($1, 'q', $2, '/s', 't', '/l', 'u', $3)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(host.id)
+        .bind(user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        first.release().await.unwrap();
+
+        let err = reserve(&pool, Some(user.id), one_vm).await.err().unwrap();
+        assert!(is_exceeded(&err));
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM quota_reservation")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(pending, 0);
+    }
+}
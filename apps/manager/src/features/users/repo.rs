@@ -26,6 +26,7 @@ pub struct UserRow {
     pub timezone: Option<String>,
     pub theme: Option<String>,
     pub preferences: Option<sqlx::types::JsonValue>,
+    pub locked_until: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -48,6 +49,7 @@ impl UserRow {
             avatar_path: self.avatar_path.clone(),
             timezone: self.timezone.clone(),
             theme: self.theme.clone(),
+            locked_until: self.locked_until.filter(|until| *until > Utc::now()),
             created_at: self.created_at,
         }
     }
@@ -122,7 +124,7 @@ impl UserRepository {
             r#"
             INSERT INTO users (id, username, password_hash, role, auth_source)
             VALUES ($1, $2, $3, $4, 'local')
-            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             "#,
         )
         .bind(Uuid::new_v4())
@@ -147,7 +149,7 @@ impl UserRepository {
             r#"
             INSERT INTO users (id, username, password_hash, role, auth_source, email)
             VALUES ($1, $2, NULL, $3, 'sso', $4)
-            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             "#,
         )
         .bind(Uuid::new_v4())
@@ -163,7 +165,7 @@ impl UserRepository {
     pub async fn get_by_email(&self, email: &str) -> Result<UserRow, UserRepoError> {
         let row = sqlx::query_as::<_, UserRow>(
             r#"
-            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             FROM users
             WHERE email = $1
             "#,
@@ -192,7 +194,7 @@ impl UserRepository {
     pub async fn get_by_username(&self, username: &str) -> Result<UserRow, UserRepoError> {
         let row = sqlx::query_as::<_, UserRow>(
             r#"
-            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             FROM users
             WHERE username = $1
             "#,
@@ -210,7 +212,7 @@ impl UserRepository {
 
         let row = sqlx::query_as::<_, UserRow>(
             r#"
-            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             FROM users
             WHERE id = $1
             "#,
@@ -234,7 +236,7 @@ impl UserRepository {
     pub async fn list(&self) -> Result<Vec<UserRow>, UserRepoError> {
         let rows = sqlx::query_as::<_, UserRow>(
             r#"
-            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            SELECT id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             FROM users
             ORDER BY created_at DESC
             "#,
@@ -274,7 +276,7 @@ impl UserRepository {
             UPDATE users
             SET username = $2, password_hash = $3, role = $4, updated_at = now()
             WHERE id = $1
-            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, created_at, updated_at
+            RETURNING id, username, password_hash, role, auth_source, email, last_login_at, avatar_path, timezone, theme, preferences, locked_until, created_at, updated_at
             "#,
         )
         .bind(id)
@@ -317,6 +319,66 @@ impl UserRepository {
         Ok(user)
     }
 
+    /// When `username`'s account is locked out, the time the lock lifts.
+    /// Checked before the password so a locked account answers the same
+    /// way whether or not the guess was right.
+    pub async fn locked_until(
+        &self,
+        username: &str,
+    ) -> Result<Option<DateTime<Utc>>, UserRepoError> {
+        let until = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "SELECT locked_until FROM users WHERE username = $1 AND locked_until > now()",
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(until.flatten())
+    }
+
+    /// Counts a wrong password for `username`. The `threshold`th failure in a
+    /// row locks the account for `cooldown` and restarts the count; returns
+    /// the lock's end when the account is now locked.
+    pub async fn record_login_failure(
+        &self,
+        username: &str,
+        threshold: u32,
+        cooldown: std::time::Duration,
+    ) -> Result<Option<DateTime<Utc>>, UserRepoError> {
+        let until = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            r#"
+            UPDATE users
+            SET failed_login_count = CASE WHEN failed_login_count + 1 >= $2 THEN 0
+                                          ELSE failed_login_count + 1 END,
+                locked_until = CASE WHEN failed_login_count + 1 >= $2
+                                    THEN now() + make_interval(secs => $3)
+                                    ELSE locked_until END
+            WHERE username = $1
+            RETURNING CASE WHEN locked_until > now() THEN locked_until END
+            "#,
+        )
+        .bind(username)
+        .bind(threshold as i32)
+        .bind(cooldown.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(until.flatten())
+    }
+
+    /// Clears the failure count and any lock, after a successful login or
+    /// when an admin unlocks the account.
+    pub async fn reset_login_failures(&self, id: Uuid) -> Result<(), UserRepoError> {
+        let result = sqlx::query(
+            "UPDATE users SET failed_login_count = 0, locked_until = NULL WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(UserRepoError::UserNotFound);
+        }
+        Ok(())
+    }
+
     pub async fn create_token(
         &self,
         user_id: Uuid,
@@ -581,8 +643,64 @@ pub enum UserRepoError {
     InvalidRole(String),
     #[error("password hashing error: {0}")]
     HashingError(String),
+    #[error(transparent)]
+    WeakPassword(#[from] super::password::WeakPassword),
     #[error("this account uses SSO authentication — please sign in with your identity provider")]
     SsoOnlyUser,
     #[error(transparent)]
     Sql(#[from] sqlx::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn consecutive_failures_lock_the_account_until_reset(pool: PgPool) {
+        let users = UserRepository::new(pool);
+        let user = users
+            .create_user("mallory-target", "long enough pw", nexus_types::Role::User)
+            .await
+            .unwrap();
+        let cooldown = Duration::from_secs(600);
+
+        for _ in 0..2 {
+            let locked = users
+                .record_login_failure("mallory-target", 3, cooldown)
+                .await
+                .unwrap();
+            assert_eq!(locked, None);
+        }
+        let locked = users
+            .record_login_failure("mallory-target", 3, cooldown)
+            .await
+            .unwrap();
+        assert!(locked.is_some_and(|until| until > Utc::now()));
+        assert_eq!(users.locked_until("mallory-target").await.unwrap(), locked);
+        assert!(users
+            .get_by_id(user.id)
+            .await
+            .unwrap()
+            .to_user()
+            .locked_until
+            .is_some());
+
+        // Unknown usernames have nothing to lock
+        assert_eq!(
+            users
+                .record_login_failure("nobody", 1, cooldown)
+                .await
+                .unwrap(),
+            None
+        );
+
+        users.reset_login_failures(user.id).await.unwrap();
+        assert_eq!(users.locked_until("mallory-target").await.unwrap(), None);
+        assert!(matches!(
+            users.reset_login_failures(Uuid::new_v4()).await,
+            Err(UserRepoError::UserNotFound)
+        ));
+    }
+}
@@ -1,21 +1,26 @@
-use crate::features::users::repo::AuthenticatedUser;
+use crate::features::users::audit::{self, Actor};
+use crate::features::users::client_ip::ClientIp;
+use crate::features::users::password;
+use crate::features::users::repo::{AuthenticatedUser, UserRepoError};
 use crate::AppState;
 use axum::{
     body::Body,
     extract::{Multipart, Path},
     http::{header, StatusCode},
-    response::Response,
+    response::{IntoResponse, Response},
     Extension, Json,
 };
 use nexus_types::{
-    ChangePasswordRequest, CreateUserRequest, GetPreferencesResponse, GetUserResponse,
-    ListUsersResponse, LoginRequest, LoginResponse, UpdatePreferencesRequest, UpdateProfileRequest,
-    UpdateUserRequest, User, UserPathParams,
+    AuditAction, ChangePasswordRequest, CreateUserRequest, GetPreferencesResponse,
+    GetUserQuotaResponse, GetUserResponse, ListUsersResponse, LoginRequest, LoginResponse,
+    UpdatePreferencesRequest, UpdateProfileRequest, UpdateUserRequest, User, UserPathParams,
+    UserQuota,
 };
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[utoipa::path(
     post,
@@ -24,29 +29,100 @@ use tracing::{error, info};
     responses(
         (status = 200, description = "Login successful", body = LoginResponse),
         (status = 401, description = "Invalid credentials"),
+        (status = 429, description = "Too many failed attempts for this username or client IP, or the account is locked; see Retry-After"),
         (status = 500, description = "Failed to authenticate"),
     ),
     tag = "Auth"
 )]
 pub async fn login(
     Extension(st): Extension<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, StatusCode> {
-    let user = st
-        .users
-        .verify_password(&req.username, &req.password)
-        .await
-        .map_err(|e| {
-            error!(?e, "failed to verify password");
-            match e {
-                crate::features::users::repo::UserRepoError::SsoOnlyUser => StatusCode::FORBIDDEN,
-                _ => StatusCode::UNAUTHORIZED,
+) -> Result<Json<LoginResponse>, Response> {
+    let ip_address = client_ip.map(|ip| ip.to_string());
+    let log_refused = |reason: &'static str| {
+        audit::log_login(
+            &st.db,
+            &req.username,
+            false,
+            None,
+            ip_address.as_deref(),
+            Some(reason),
+        )
+    };
+
+    // Counted as a failure until the password checks out
+    let attempt = match st
+        .login_throttle
+        .claim(&req.username, client_ip, Instant::now())
+    {
+        Ok(attempt) => attempt,
+        Err(retry_after) => {
+            warn!(username = %req.username, ?client_ip, ?retry_after, "login throttled");
+            let _ = log_refused("too many failed login attempts").await;
+            return Err(too_many_attempts(retry_after));
+        }
+    };
+    // Before the password is looked at, so a locked account answers the
+    // same (and as fast) for right and wrong guesses
+    let locked_until = match st.users.locked_until(&req.username).await {
+        Ok(locked_until) => locked_until,
+        Err(e) => {
+            error!(?e, "failed to check account lockout");
+            st.login_throttle.release(attempt);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+    if let Some(until) = locked_until {
+        let _ = log_refused("account locked").await;
+        return Err(account_locked(until, chrono::Utc::now()));
+    }
+
+    let result = st.users.verify_password(&req.username, &req.password).await;
+    let _ = audit::log_login(
+        &st.db,
+        &req.username,
+        result.is_ok(),
+        result.as_ref().ok().map(|user| user.id),
+        ip_address.as_deref(),
+        result.as_ref().err().map(|e| e.to_string()).as_deref(),
+    )
+    .await;
+    match &result {
+        Ok(user) => {
+            st.login_throttle.release(attempt);
+            st.login_throttle.record_success(&req.username);
+            if let Err(e) = st.users.reset_login_failures(user.id).await {
+                warn!(?e, user_id = %user.id, "failed to reset failed login count");
             }
-        })?;
+        }
+        Err(UserRepoError::InvalidCredentials | UserRepoError::UserNotFound) => {
+            // The claimed attempt stays counted as a failure
+            let lockout = st.login_throttle.lockout();
+            match st
+                .users
+                .record_login_failure(&req.username, lockout.threshold, lockout.cooldown)
+                .await
+            {
+                Ok(Some(until)) => warn!(username = %req.username, %until, "account locked"),
+                Ok(None) => {}
+                Err(e) => warn!(?e, "failed to count failed login"),
+            }
+        }
+        Err(_) => st.login_throttle.release(attempt),
+    }
+    let user = result.map_err(|e| {
+        error!(?e, "failed to verify password");
+        match e {
+            UserRepoError::SsoOnlyUser => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+        .into_response()
+    })?;
 
     let token = st.users.create_token(user.id, None).await.map_err(|e| {
         error!(?e, "failed to create token");
-        StatusCode::INTERNAL_SERVER_ERROR
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
     })?;
 
     Ok(Json(LoginResponse {
@@ -55,6 +131,23 @@ pub async fn login(
     }))
 }
 
+/// 429 for a locked account, retrying once `locked_until` has passed.
+fn account_locked(
+    until: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Response {
+    too_many_attempts((until - now).to_std().unwrap_or_default())
+}
+
+fn too_many_attempts(retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, secs.max(1).to_string())],
+    )
+        .into_response()
+}
+
 #[utoipa::path(
     get,
     path = "/v1/auth/me",
@@ -109,7 +202,7 @@ pub async fn list(
     request_body = CreateUserRequest,
     responses(
         (status = 200, description = "User created", body = User),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Invalid request, or the password is too weak"),
         (status = 403, description = "Forbidden - admin only"),
         (status = 500, description = "Failed to create user"),
     ),
@@ -117,25 +210,41 @@ pub async fn list(
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateUserRequest>,
 ) -> Result<Json<User>, StatusCode> {
-    let user = st
-        .users
-        .create_user(&req.username, &req.password, req.role)
-        .await
-        .map_err(|e| {
-            error!(?e, "failed to create user");
-            match e {
-                crate::features::users::repo::UserRepoError::Sql(sqlx::Error::Database(db_err)) => {
-                    if db_err.constraint().is_some() {
-                        StatusCode::CONFLICT
-                    } else {
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    }
+    let result = match password::check_strength(&req.username, &req.password) {
+        Ok(()) => {
+            st.users
+                .create_user(&req.username, &req.password, req.role)
+                .await
+        }
+        Err(weak) => Err(weak.into()),
+    };
+    let id = result.as_ref().ok().map(|user| user.id);
+    let details = serde_json::json!({"username": &req.username, "role": req.role});
+    actor
+        .record_with(
+            AuditAction::CreateUser,
+            ("user", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    let user = result.map_err(|e| {
+        error!(?e, "failed to create user");
+        match e {
+            UserRepoError::WeakPassword(_) => StatusCode::BAD_REQUEST,
+            UserRepoError::Sql(sqlx::Error::Database(db_err)) => {
+                if db_err.constraint().is_some() {
+                    StatusCode::CONFLICT
+                } else {
+                    StatusCode::INTERNAL_SERVER_ERROR
                 }
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
             }
-        })?;
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    })?;
 
     Ok(Json(user.to_user()))
 }
@@ -157,7 +266,7 @@ pub async fn get(
     Path(UserPathParams { id }): Path<UserPathParams>,
 ) -> Result<Json<GetUserResponse>, StatusCode> {
     let user = st.users.get_by_id(id).await.map_err(|e| match e {
-        crate::features::users::repo::UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
+        UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
         _ => {
             error!(?e, "failed to fetch user");
             StatusCode::INTERNAL_SERVER_ERROR
@@ -177,7 +286,7 @@ pub async fn get(
     responses(
         (status = 200, description = "User updated", body = User),
         (status = 404, description = "User not found"),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Invalid role, or the password is too weak"),
         (status = 403, description = "Forbidden - admin only"),
         (status = 500, description = "Failed to update user"),
     ),
@@ -185,27 +294,181 @@ pub async fn get(
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(UserPathParams { id }): Path<UserPathParams>,
     Json(req): Json<UpdateUserRequest>,
 ) -> Result<Json<User>, StatusCode> {
-    let user = st
-        .users
-        .update(
-            id,
-            req.username.as_deref(),
-            req.password.as_deref(),
-            req.role,
+    let strength = req.password.as_deref().map_or(Ok(()), |password| {
+        password::check_strength(req.username.as_deref().unwrap_or_default(), password)
+    });
+    let result = match strength {
+        Ok(()) => {
+            st.users
+                .update(
+                    id,
+                    req.username.as_deref(),
+                    req.password.as_deref(),
+                    req.role,
+                )
+                .await
+        }
+        Err(weak) => Err(weak.into()),
+    };
+    let details = serde_json::json!({
+        "username": req.username,
+        "role": req.role,
+        "password_changed": req.password.is_some(),
+    });
+    actor
+        .record_with(
+            AuditAction::UpdateUser,
+            ("user", id),
+            Some(details),
+            &result,
         )
-        .await
-        .map_err(|e| match e {
-            crate::features::users::repo::UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
-            crate::features::users::repo::UserRepoError::InvalidRole(_) => StatusCode::BAD_REQUEST,
-            _ => {
-                error!(?e, "failed to update user");
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+        .await;
+    let user = result.map_err(|e| match e {
+        UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
+        UserRepoError::InvalidRole(_) => StatusCode::BAD_REQUEST,
+        UserRepoError::WeakPassword(_) => StatusCode::BAD_REQUEST,
+        _ => {
+            error!(?e, "failed to update user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+
+    Ok(Json(user.to_user()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/users/{id}/quota",
+    params(UserPathParams),
+    responses(
+        (status = 200, description = "The user's quota (empty if unlimited) and current usage", body = GetUserQuotaResponse),
+        (status = 404, description = "User not found"),
+        (status = 403, description = "Forbidden - admin only"),
+        (status = 500, description = "Failed to fetch quota"),
+    ),
+    tag = "Users"
+)]
+pub async fn get_quota(
+    Extension(st): Extension<AppState>,
+    Path(UserPathParams { id }): Path<UserPathParams>,
+) -> Result<Json<GetUserQuotaResponse>, StatusCode> {
+    quota_response(&st, id).await.map(Json)
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/users/{id}/quota",
+    params(UserPathParams),
+    request_body = UserQuota,
+    responses(
+        (status = 200, description = "Quota replaced; unset limits are unlimited", body = GetUserQuotaResponse),
+        (status = 404, description = "User not found"),
+        (status = 400, description = "Negative limit"),
+        (status = 403, description = "Forbidden - admin only"),
+        (status = 500, description = "Failed to update quota"),
+    ),
+    tag = "Users"
+)]
+pub async fn put_quota(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(UserPathParams { id }): Path<UserPathParams>,
+    Json(req): Json<UserQuota>,
+) -> Result<Json<GetUserQuotaResponse>, StatusCode> {
+    let result = replace_quota(&st, id, &req).await;
+    let details = serde_json::json!({"quota": &req});
+    actor
+        .record_with(
+            AuditAction::UpdateUser,
+            ("user", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    result?;
+    quota_response(&st, id).await.map(Json)
+}
+
+async fn replace_quota(st: &AppState, id: uuid::Uuid, quota: &UserQuota) -> Result<(), StatusCode> {
+    super::quota::validate(quota).map_err(|_| StatusCode::BAD_REQUEST)?;
+    ensure_user(st, id).await?;
+    super::quota::put(&st.db, id, quota).await.map_err(|e| {
+        error!(?e, "failed to update quota");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
 
+async fn ensure_user(st: &AppState, id: uuid::Uuid) -> Result<(), StatusCode> {
+    st.users.get_by_id(id).await.map_err(|e| match e {
+        UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
+        _ => {
+            error!(?e, "failed to fetch user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    Ok(())
+}
+
+async fn quota_response(st: &AppState, id: uuid::Uuid) -> Result<GetUserQuotaResponse, StatusCode> {
+    ensure_user(st, id).await?;
+    let quota = super::quota::get(&st.db, id).await;
+    let usage = super::quota::usage(&st.db, id).await;
+    match (quota, usage) {
+        (Ok(quota), Ok(usage)) => Ok(GetUserQuotaResponse {
+            quota: quota.unwrap_or_default(),
+            usage,
+        }),
+        (Err(e), _) | (_, Err(e)) => {
+            error!(?e, "failed to fetch quota");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/users/{id}/unlock",
+    params(UserPathParams),
+    responses(
+        (status = 200, description = "Lockout lifted, failed login count and login throttle cleared", body = User),
+        (status = 404, description = "User not found"),
+        (status = 403, description = "Forbidden - admin only"),
+        (status = 500, description = "Failed to unlock user"),
+    ),
+    tag = "Users"
+)]
+pub async fn unlock(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(UserPathParams { id }): Path<UserPathParams>,
+) -> Result<Json<User>, StatusCode> {
+    let result = st.users.reset_login_failures(id).await;
+    actor
+        .record_with(
+            AuditAction::UpdateUser,
+            ("user", id),
+            Some(serde_json::json!({"unlocked": true})),
+            &result,
+        )
+        .await;
+    result.map_err(|e| match e {
+        UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
+        _ => {
+            error!(?e, "failed to unlock user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
+    let user = st.users.get_by_id(id).await.map_err(|e| {
+        error!(?e, "failed to fetch user");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // Also lift the in-memory per-username throttle, or the user would
+    // still get 429 until their failures age out
+    st.login_throttle.record_success(&user.username);
     Ok(Json(user.to_user()))
 }
 
@@ -223,10 +486,15 @@ pub async fn update(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(UserPathParams { id }): Path<UserPathParams>,
 ) -> Result<Json<nexus_types::OkResponse>, StatusCode> {
-    st.users.delete(id).await.map_err(|e| match e {
-        crate::features::users::repo::UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
+    let result = st.users.delete(id).await;
+    actor
+        .record(AuditAction::DeleteUser, ("user", id), &result)
+        .await;
+    result.map_err(|e| match e {
+        UserRepoError::UserNotFound => StatusCode::NOT_FOUND,
         _ => {
             error!(?e, "failed to delete user");
             StatusCode::INTERNAL_SERVER_ERROR
@@ -331,18 +599,27 @@ pub async fn get_profile(
 pub async fn update_profile(
     Extension(user): Extension<AuthenticatedUser>,
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<UpdateProfileRequest>,
 ) -> Result<Json<User>, StatusCode> {
     info!(user_id = ?user.id, "updating profile for user");
 
-    let user_row = st
+    let result = st
         .users
         .update_profile(user.id, req.username.as_deref())
-        .await
-        .map_err(|e| {
-            error!(?e, user_id = ?user.id, "failed to update profile");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .await;
+    actor
+        .record_with(
+            AuditAction::UpdateUser,
+            ("user", user.id),
+            Some(serde_json::json!({"username": req.username})),
+            &result,
+        )
+        .await;
+    let user_row = result.map_err(|e| {
+        error!(?e, user_id = ?user.id, "failed to update profile");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
     info!(user_id = ?user.id, "profile updated successfully");
     Ok(Json(user_row.to_user()))
@@ -354,6 +631,7 @@ pub async fn update_profile(
     request_body = ChangePasswordRequest,
     responses(
         (status = 200, description = "Password changed", body = nexus_types::OkResponse),
+        (status = 400, description = "New password too weak"),
         (status = 401, description = "Invalid current password"),
         (status = 500, description = "Failed to change password"),
     ),
@@ -362,20 +640,31 @@ pub async fn update_profile(
 pub async fn change_password(
     Extension(user): Extension<AuthenticatedUser>,
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<ChangePasswordRequest>,
 ) -> Result<Json<nexus_types::OkResponse>, StatusCode> {
-    st.users
+    if let Err(e) = password::check_strength(&user.username, &req.new_password) {
+        info!(user_id = ?user.id, %e, "refused password change");
+        let refused: Result<(), _> = Err(e);
+        actor
+            .record(AuditAction::ChangePassword, ("user", user.id), &refused)
+            .await;
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let result = st
+        .users
         .change_password(user.id, &req.current_password, &req.new_password)
-        .await
-        .map_err(|e| match e {
-            crate::features::users::repo::UserRepoError::InvalidCredentials => {
-                StatusCode::UNAUTHORIZED
-            }
-            _ => {
-                error!(?e, "failed to change password");
-                StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+        .await;
+    actor
+        .record(AuditAction::ChangePassword, ("user", user.id), &result)
+        .await;
+    result.map_err(|e| match e {
+        UserRepoError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+        _ => {
+            error!(?e, "failed to change password");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    })?;
 
     Ok(Json(nexus_types::OkResponse::default()))
 }
@@ -575,3 +864,20 @@ pub async fn delete_avatar(
 
     Ok(Json(nexus_types::OkResponse::default()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_account_gets_429_until_the_lock_lifts() {
+        let now = chrono::Utc::now();
+        let resp = account_locked(now + chrono::Duration::milliseconds(90_500), now);
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "91");
+
+        // A lock that lapsed mid-request still asks for a retry
+        let resp = account_locked(now - chrono::Duration::seconds(5), now);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "1");
+    }
+}
@@ -0,0 +1,324 @@
+//! Automatic balloon sizing. VMs with a [`BalloonPolicy`] have their balloon
+//! inflated while the guest agent keeps reporting spare memory, handing the
+//! RAM back to the host, and deflated as soon as the guest runs short. VMs
+//! without a policy (the default) are never touched.
+
+use crate::core::error::bad_request;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use nexus_types::{BalloonConfig, BalloonPolicy};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::repo::VmRow;
+use crate::AppState;
+
+const INTERVAL_ENV: &str = "MANAGER_BALLOON_POLICY_INTERVAL_SECS";
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+fn interval_secs() -> u64 {
+    parse_interval_secs(std::env::var(INTERVAL_ENV).ok().as_deref())
+}
+
+fn parse_interval_secs(raw: Option<&str>) -> u64 {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_INTERVAL_SECS)
+}
+
+pub fn validate(policy: &BalloonPolicy, mem_mib: u64) -> Result<()> {
+    if policy.step_mib == 0 {
+        bail!(bad_request(
+            "invalid balloon policy: step_mib must be greater than 0"
+        ));
+    }
+    if policy.low_watermark_mib >= policy.high_watermark_mib {
+        bail!(bad_request(
+            "invalid balloon policy: low_watermark_mib must be below high_watermark_mib"
+        ));
+    }
+    if policy.high_watermark_mib >= mem_mib {
+        bail!(bad_request(format!(
+            "invalid balloon policy: high_watermark_mib must be below the VM's {mem_mib} MiB"
+        )));
+    }
+    if policy.max_balloon_mib.is_some_and(|max| max >= mem_mib) {
+        bail!(bad_request(format!(
+            "invalid balloon policy: max_balloon_mib must be below the VM's {mem_mib} MiB"
+        )));
+    }
+    Ok(())
+}
+
+pub async fn get(db: &PgPool, vm_id: Uuid) -> Result<Option<BalloonPolicy>> {
+    let policy: Option<serde_json::Value> =
+        sqlx::query_scalar("SELECT policy FROM vm_balloon_policy WHERE vm_id = $1")
+            .bind(vm_id)
+            .fetch_optional(db)
+            .await?;
+    policy
+        .map(|p| serde_json::from_value(p).context("decoding balloon policy"))
+        .transpose()
+}
+
+pub async fn upsert(db: &PgPool, vm_id: Uuid, policy: &BalloonPolicy) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO vm_balloon_policy (vm_id, policy)
+        VALUES ($1, $2)
+        ON CONFLICT (vm_id) DO UPDATE
+        SET policy = EXCLUDED.policy, updated_at = now()
+        "#,
+    )
+    .bind(vm_id)
+    .bind(serde_json::to_value(policy)?)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete(db: &PgPool, vm_id: Uuid) -> Result<bool> {
+    let done = sqlx::query("DELETE FROM vm_balloon_policy WHERE vm_id = $1")
+        .bind(vm_id)
+        .execute(db)
+        .await?;
+    Ok(done.rows_affected() > 0)
+}
+
+async fn list_running(db: &PgPool) -> Result<Vec<(Uuid, serde_json::Value)>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT p.vm_id, p.policy
+        FROM vm_balloon_policy p
+        JOIN vm v ON v.id = p.vm_id
+        WHERE v.state IN ('running', 'running-degraded')
+        "#,
+    )
+    .fetch_all(db)
+    .await?)
+}
+
+/// Where the balloon should go next, if anywhere. `available_mib` is the
+/// guest's view, `above_high_for` how long it has stayed over the high
+/// watermark. Inflating never takes the guest below the high watermark, so
+/// one step can't push it straight under the low one.
+fn next_balloon_mib(
+    policy: &BalloonPolicy,
+    mem_mib: u64,
+    current_mib: u64,
+    available_mib: u64,
+    above_high_for: Option<Duration>,
+) -> Option<u64> {
+    if available_mib < policy.low_watermark_mib {
+        let target = current_mib.saturating_sub(policy.step_mib);
+        return (target < current_mib).then_some(target);
+    }
+    let sustained = above_high_for.is_some_and(|d| d.as_secs() >= policy.sustain_secs);
+    if available_mib > policy.high_watermark_mib && sustained {
+        let cap = policy.max_balloon_mib.unwrap_or(mem_mib / 2);
+        let step = policy
+            .step_mib
+            .min(available_mib - policy.high_watermark_mib);
+        let target = (current_mib + step).min(cap);
+        return (target > current_mib).then_some(target);
+    }
+    None
+}
+
+#[derive(Deserialize)]
+struct GuestMemory {
+    memory_available_kb: u64,
+}
+
+pub fn spawn(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(interval_secs()));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // When each VM first went over its high watermark this streak.
+        let mut above_high_since: HashMap<Uuid, Instant> = HashMap::new();
+        loop {
+            ticker.tick().await;
+            if let Err(err) = apply_policies(&state, &mut above_high_since).await {
+                warn!(error = ?err, "balloon policy pass failed");
+            }
+        }
+    })
+}
+
+async fn apply_policies(
+    state: &AppState,
+    above_high_since: &mut HashMap<Uuid, Instant>,
+) -> Result<()> {
+    let policies = list_running(&state.db).await?;
+    above_high_since.retain(|id, _| policies.iter().any(|(vm_id, _)| vm_id == id));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()?;
+
+    for (vm_id, policy) in policies {
+        let policy: BalloonPolicy = match serde_json::from_value(policy) {
+            Ok(policy) => policy,
+            Err(err) => {
+                warn!(%vm_id, error = %err, "skipping unreadable balloon policy");
+                continue;
+            }
+        };
+        let Some(vm) = load_vm(&state.db, vm_id).await else {
+            continue;
+        };
+        if let Err(err) = apply_policy(&client, &vm, &policy, above_high_since).await {
+            debug!(%vm_id, error = ?err, "balloon policy not applied");
+        }
+    }
+    Ok(())
+}
+
+/// The VM a listed policy belongs to, or `None` when it can't be loaded,
+/// most often because it was deleted since the list was read. Either way
+/// only that VM is skipped, not the rest of the pass.
+async fn load_vm(db: &PgPool, vm_id: Uuid) -> Option<VmRow> {
+    match super::repo::get(db, vm_id).await {
+        Ok(vm) => Some(vm),
+        Err(sqlx::Error::RowNotFound) => {
+            debug!(%vm_id, "VM deleted mid-pass, skipping its balloon policy");
+            None
+        }
+        Err(err) => {
+            warn!(%vm_id, error = ?err, "skipping balloon policy: failed to load VM");
+            None
+        }
+    }
+}
+
+async fn apply_policy(
+    client: &reqwest::Client,
+    vm: &VmRow,
+    policy: &BalloonPolicy,
+    above_high_since: &mut HashMap<Uuid, Instant>,
+) -> Result<()> {
+    let guest: GuestMemory = super::guest_vsock::guest_metrics(client, vm).await?;
+    let available_mib = guest.memory_available_kb / 1024;
+    let now = Instant::now();
+    if available_mib > policy.high_watermark_mib {
+        above_high_since.entry(vm.id).or_insert(now);
+    } else {
+        above_high_since.remove(&vm.id);
+    }
+
+    let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
+    let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
+    let resp = client.get(format!("{base}/balloon{qs}")).send().await?;
+    if !resp.status().is_success() {
+        bail!("VM has no balloon device");
+    }
+    let current: BalloonConfig = resp.json().await?;
+
+    let above_high_for = above_high_since.get(&vm.id).map(|since| now - *since);
+    let Some(amount_mib) = next_balloon_mib(
+        policy,
+        vm.mem_mib as u64,
+        current.amount_mib,
+        available_mib,
+        above_high_for,
+    ) else {
+        return Ok(());
+    };
+
+    client
+        .patch(format!("{base}/balloon{qs}"))
+        .json(&json!({ "amount_mib": amount_mib }))
+        .send()
+        .await?
+        .error_for_status()?;
+    // Each further step needs another sustained stretch of spare memory.
+    above_high_since.remove(&vm.id);
+    metrics::counter!("manager_balloon_policy_adjustments", 1);
+    info!(
+        vm_id = %vm.id,
+        from_mib = current.amount_mib,
+        to_mib = amount_mib,
+        available_mib,
+        "balloon resized by policy"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> BalloonPolicy {
+        BalloonPolicy {
+            high_watermark_mib: 1024,
+            low_watermark_mib: 256,
+            step_mib: 128,
+            sustain_secs: 60,
+            max_balloon_mib: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_vm_deleted_mid_pass_is_skipped() {
+        let db = PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+        assert!(load_vm(&db, Uuid::new_v4()).await.is_none());
+    }
+
+    #[test]
+    fn interval_defaults_when_unset_or_invalid() {
+        assert_eq!(parse_interval_secs(None), 30);
+        assert_eq!(parse_interval_secs(Some("0")), 30);
+        assert_eq!(parse_interval_secs(Some(" 10 ")), 10);
+    }
+
+    #[test]
+    fn validate_rejects_inverted_or_oversized_policies() {
+        assert!(validate(&policy(), 4096).is_ok());
+        let inverted = BalloonPolicy {
+            low_watermark_mib: 2048,
+            ..policy()
+        };
+        assert!(validate(&inverted, 4096).is_err());
+        assert!(validate(&policy(), 1024).is_err());
+        let huge = BalloonPolicy {
+            max_balloon_mib: Some(4096),
+            ..policy()
+        };
+        assert!(validate(&huge, 4096).is_err());
+        let stuck = BalloonPolicy {
+            step_mib: 0,
+            ..policy()
+        };
+        assert!(validate(&stuck, 4096)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid balloon policy"));
+    }
+
+    #[test]
+    fn inflates_only_after_sustained_spare_memory() {
+        let p = policy();
+        let short = Some(Duration::from_secs(30));
+        let long = Some(Duration::from_secs(60));
+        assert_eq!(next_balloon_mib(&p, 4096, 0, 2048, short), None);
+        assert_eq!(next_balloon_mib(&p, 4096, 0, 2048, long), Some(128));
+        // Never past the high watermark, nor past half the VM by default.
+        assert_eq!(next_balloon_mib(&p, 4096, 0, 1064, long), Some(40));
+        assert_eq!(next_balloon_mib(&p, 4096, 2000, 2048, long), Some(2048));
+        assert_eq!(next_balloon_mib(&p, 4096, 2048, 2048, long), None);
+    }
+
+    #[test]
+    fn deflates_under_pressure() {
+        let p = policy();
+        assert_eq!(next_balloon_mib(&p, 4096, 512, 100, None), Some(384));
+        assert_eq!(next_balloon_mib(&p, 4096, 64, 100, None), Some(0));
+        assert_eq!(next_balloon_mib(&p, 4096, 0, 100, None), None);
+        assert_eq!(next_balloon_mib(&p, 4096, 512, 512, None), None);
+    }
+}
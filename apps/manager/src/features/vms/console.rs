@@ -0,0 +1,195 @@
+//! The serial console log Firecracker writes to `logs/console.log` in the
+//! VM's directory: its last lines for `GET /v1/vms/{id}/console`, and what
+//! gets appended for `/console/ws`. Both are read through the same path
+//! check as drives, and a VM that hasn't written any output yet (early boot,
+//! or never started) reads as empty rather than failing.
+
+use crate::core::error::not_found;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use nexus_types::{VmConsoleParams, VmConsoleResponse};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use uuid::Uuid;
+
+use crate::AppState;
+
+pub const DEFAULT_TAIL_LINES: usize = 200;
+pub const MAX_TAIL_LINES: usize = 10_000;
+/// Most of the log a tail reads, however short its lines.
+const MAX_TAIL_BYTES: u64 = 4 * 1024 * 1024;
+/// Most a follower sends per read, so a chatty guest can't build one huge frame.
+const MAX_FOLLOW_CHUNK: u64 = 64 * 1024;
+const CHUNK: u64 = 8 * 1024;
+
+pub fn log_path(st: &AppState, vm_id: Uuid) -> Result<PathBuf> {
+    let path = st.storage.vm_dir(vm_id).join("logs/console.log");
+    super::service::ensure_allowed_path(st, &path.to_string_lossy())?;
+    Ok(path)
+}
+
+/// Lines the request asked for, within bounds.
+pub fn tail_lines(params: &VmConsoleParams) -> usize {
+    params
+        .tail
+        .unwrap_or(DEFAULT_TAIL_LINES)
+        .clamp(1, MAX_TAIL_LINES)
+}
+
+pub async fn get(
+    st: &AppState,
+    vm_id: Uuid,
+    params: &VmConsoleParams,
+) -> Result<VmConsoleResponse> {
+    if let Err(sqlx::Error::RowNotFound) = super::repo::get(&st.db, vm_id).await {
+        bail!(not_found("VM not found"));
+    }
+    let path = log_path(st, vm_id)?;
+    Ok(match tail(&path, tail_lines(params)).await? {
+        Some((text, _)) => VmConsoleResponse {
+            text,
+            available: true,
+        },
+        None => VmConsoleResponse {
+            text: String::new(),
+            available: false,
+        },
+    })
+}
+
+/// The last `lines` lines of the log and its length, or `None` while it
+/// doesn't exist.
+pub async fn tail(path: &Path, lines: usize) -> Result<Option<(String, u64)>> {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let len = file.metadata().await?.len();
+    let floor = len.saturating_sub(MAX_TAIL_BYTES);
+    let mut start = len;
+    let mut buf: Vec<u8> = Vec::new();
+    let mut seen = 0;
+    // Read backwards until the newline before the first wanted line (plus
+    // the one ending the file, if any) is in the buffer, or the file starts.
+    while start > floor && seen <= lines {
+        let step = CHUNK.min(start - floor);
+        start -= step;
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut chunk = vec![0; step as usize];
+        file.read_exact(&mut chunk).await?;
+        seen += chunk.iter().filter(|b| **b == b'\n').count();
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+    Ok(Some((
+        last_lines(&String::from_utf8_lossy(&buf), lines),
+        len,
+    )))
+}
+
+fn last_lines(text: &str, lines: usize) -> String {
+    if lines == 0 {
+        return String::new();
+    }
+    let body = text.strip_suffix('\n').unwrap_or(text);
+    let cut = body
+        .rmatch_indices('\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1);
+    text[cut..].to_string()
+}
+
+/// Reads what is appended to the log after `offset`. Starts over from the
+/// top when the file shrinks, which is a restart truncating it.
+pub struct Follower {
+    path: PathBuf,
+    offset: u64,
+}
+
+impl Follower {
+    pub fn new(path: PathBuf, offset: u64) -> Self {
+        Self { path, offset }
+    }
+
+    /// New output since the last call; empty while there is none or the log
+    /// doesn't exist yet.
+    pub async fn poll(&mut self) -> Result<String> {
+        let mut file = match File::open(&self.path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let len = file.metadata().await?.len();
+        if len < self.offset {
+            self.offset = 0;
+        }
+        if len == self.offset {
+            return Ok(String::new());
+        }
+        let step = (len - self.offset).min(MAX_FOLLOW_CHUNK);
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        let mut chunk = vec![0; step as usize];
+        file.read_exact(&mut chunk).await?;
+        self.offset += step;
+        Ok(String::from_utf8_lossy(&chunk).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tail_lines_defaults_and_clamps() {
+        let params = |tail| VmConsoleParams { tail };
+        assert_eq!(tail_lines(&params(None)), 200);
+        assert_eq!(tail_lines(&params(Some(0))), 1);
+        assert_eq!(tail_lines(&params(Some(50))), 50);
+        assert_eq!(tail_lines(&params(Some(1_000_000))), 10_000);
+    }
+
+    #[test]
+    fn last_lines_keeps_the_trailing_newline() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), "b\nc\n");
+        assert_eq!(last_lines("a\nb\nc", 2), "b\nc");
+        assert_eq!(last_lines("a\nb\n", 5), "a\nb\n");
+        assert_eq!(last_lines("a\nb\n", 0), "");
+    }
+
+    #[tokio::test]
+    async fn tail_reads_across_chunks_and_tolerates_a_missing_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("console.log");
+        assert!(tail(&path, 10).await.unwrap().is_none());
+
+        let text: String = (0..5000).map(|i| format!("line {i}\n")).collect();
+        tokio::fs::write(&path, &text).await.unwrap();
+        let (out, len) = tail(&path, 3).await.unwrap().unwrap();
+        assert_eq!(out, "line 4997\nline 4998\nline 4999\n");
+        assert_eq!(len, text.len() as u64);
+        let (out, _) = tail(&path, 2000).await.unwrap().unwrap();
+        assert_eq!(out.lines().count(), 2000);
+        assert!(out.starts_with("line 3000\n"));
+    }
+
+    #[tokio::test]
+    async fn follower_picks_up_appends_and_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("console.log");
+        let mut follower = Follower::new(path.clone(), 0);
+        assert_eq!(follower.poll().await.unwrap(), "");
+
+        tokio::fs::write(&path, "boot\n").await.unwrap();
+        assert_eq!(follower.poll().await.unwrap(), "boot\n");
+        assert_eq!(follower.poll().await.unwrap(), "");
+
+        tokio::fs::write(&path, "boot\nlogin:").await.unwrap();
+        assert_eq!(follower.poll().await.unwrap(), "login:");
+
+        tokio::fs::write(&path, "again\n").await.unwrap();
+        assert_eq!(follower.poll().await.unwrap(), "again\n");
+    }
+}
@@ -0,0 +1,208 @@
+//! Checks for `PATCH /v1/vms/{id}/machine-config`'s `cpu_template` and the
+//! custom template body of `PUT /v1/vms/{id}/cpu-config`, so a typo comes
+//! back as a 400 instead of a template Firecracker silently ignores or
+//! refuses at boot.
+
+use crate::core::error::bad_request;
+use anyhow::{bail, Result};
+use nexus_types::CpuConfigReq;
+use serde_json::Value;
+
+/// Firecracker's static CPU templates. `None` clears a template.
+pub const KNOWN_CPU_TEMPLATES: &[&str] = &["C3", "T2", "T2S", "T2CL", "T2A", "V1N1", "None"];
+
+const CPUID_REGISTERS: &[&str] = &["eax", "ebx", "ecx", "edx"];
+
+pub fn validate_cpu_template(template: &str) -> Result<()> {
+    if !KNOWN_CPU_TEMPLATES.contains(&template) {
+        bail!(
+            "invalid cpu_template '{template}': must be one of {}",
+            KNOWN_CPU_TEMPLATES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Shape-check a custom CPU template against Firecracker's schema. Bit
+/// values are left for Firecracker to judge.
+pub fn validate_cpu_config(req: &CpuConfigReq) -> Result<()> {
+    if let Some(modifiers) = &req.cpuid_modifiers {
+        for (i, entry) in array("cpuid_modifiers", modifiers)?.iter().enumerate() {
+            let field = format!("cpuid_modifiers[{i}]");
+            let obj = object(&field, entry)?;
+            for key in ["leaf", "subleaf"] {
+                number_or_hex(&format!("{field}.{key}"), obj.get(key))?;
+            }
+            if let Some(flags) = obj.get("flags") {
+                if !flags.is_u64() {
+                    bail!(bad_request(format!(
+                        "invalid {field}.flags: must be a non-negative integer"
+                    )));
+                }
+            }
+            let registers = obj.get("modifiers").unwrap_or(&Value::Null);
+            for (j, register) in array(&format!("{field}.modifiers"), registers)?
+                .iter()
+                .enumerate()
+            {
+                let field = format!("{field}.modifiers[{j}]");
+                let reg = object(&field, register)?;
+                match reg.get("register").and_then(Value::as_str) {
+                    Some(name) if CPUID_REGISTERS.contains(&name) => {}
+                    _ => bail!(
+                        "invalid {field}.register: must be one of {}",
+                        CPUID_REGISTERS.join(", ")
+                    ),
+                }
+                bitmap(&field, reg)?;
+            }
+        }
+    }
+    for (name, modifiers) in [
+        ("msr_modifiers", &req.msr_modifiers),
+        ("reg_modifiers", &req.reg_modifiers),
+    ] {
+        let Some(modifiers) = modifiers else { continue };
+        for (i, entry) in array(name, modifiers)?.iter().enumerate() {
+            let field = format!("{name}[{i}]");
+            let obj = object(&field, entry)?;
+            number_or_hex(&format!("{field}.addr"), obj.get("addr"))?;
+            bitmap(&field, obj)?;
+        }
+    }
+    if let Some(features) = &req.vcpu_features {
+        for (i, entry) in array("vcpu_features", features)?.iter().enumerate() {
+            let field = format!("vcpu_features[{i}]");
+            let obj = object(&field, entry)?;
+            if !obj.get("index").is_some_and(Value::is_u64) {
+                bail!(bad_request(format!(
+                    "invalid {field}.index: must be a non-negative integer"
+                )));
+            }
+            bitmap(&field, obj)?;
+        }
+    }
+    if let Some(caps) = &req.kvm_capabilities {
+        if !array("kvm_capabilities", caps)?
+            .iter()
+            .all(Value::is_string)
+        {
+            bail!(bad_request(
+                "invalid kvm_capabilities: entries must be strings like \"56\" or \"!56\""
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn array<'a>(field: &str, value: &'a Value) -> Result<&'a Vec<Value>> {
+    match value {
+        Value::Array(items) => Ok(items),
+        _ => bail!(bad_request(format!(
+            "invalid {field}: must be a JSON array"
+        ))),
+    }
+}
+
+fn object<'a>(field: &str, value: &'a Value) -> Result<&'a serde_json::Map<String, Value>> {
+    match value {
+        Value::Object(obj) => Ok(obj),
+        _ => bail!(bad_request(format!(
+            "invalid {field}: must be a JSON object"
+        ))),
+    }
+}
+
+/// Firecracker takes ids and addresses as integers or `"0x.."` strings.
+fn number_or_hex(field: &str, value: Option<&Value>) -> Result<()> {
+    match value {
+        Some(v) if v.is_u64() => Ok(()),
+        Some(Value::String(s))
+            if s.strip_prefix("0x")
+                .is_some_and(|hex| u64::from_str_radix(hex, 16).is_ok()) =>
+        {
+            Ok(())
+        }
+        _ => bail!(bad_request(format!(
+            "invalid {field}: must be an integer or a 0x-prefixed hex string"
+        ))),
+    }
+}
+
+/// `"0b"` followed by `0`, `1` or `x` (leave the bit alone).
+fn bitmap(field: &str, obj: &serde_json::Map<String, Value>) -> Result<()> {
+    let valid = obj
+        .get("bitmap")
+        .and_then(Value::as_str)
+        .and_then(|s| s.strip_prefix("0b"))
+        .is_some_and(|bits| !bits.is_empty() && bits.chars().all(|c| matches!(c, '0' | '1' | 'x')));
+    if !valid {
+        bail!(bad_request(format!(
+            "invalid {field}.bitmap: must be \"0b\" followed by 0, 1 or x characters"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cpu_config(body: Value) -> CpuConfigReq {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn only_known_templates_pass() {
+        assert!(validate_cpu_template("T2S").is_ok());
+        assert!(validate_cpu_template("None").is_ok());
+        let err = validate_cpu_template("t2").unwrap_err().to_string();
+        assert!(err.starts_with("invalid cpu_template 't2'"));
+        assert!(err.contains("C3, T2, T2S"));
+    }
+
+    #[test]
+    fn accepts_firecracker_shaped_custom_templates() {
+        let req = cpu_config(json!({
+            "kvm_capabilities": ["!56"],
+            "cpuid_modifiers": [{
+                "leaf": "0x1",
+                "subleaf": 0,
+                "flags": 0,
+                "modifiers": [{"register": "ecx", "bitmap": "0bxxxx0xxx"}]
+            }],
+            "msr_modifiers": [{"addr": "0x10a", "bitmap": "0b0000000000000000000000000000000000000000000000000000000000000000"}]
+        }));
+        assert!(validate_cpu_config(&req).is_ok());
+        assert!(validate_cpu_config(&cpu_config(json!({}))).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_modifiers_by_path() {
+        let err = |body: Value| {
+            validate_cpu_config(&cpu_config(body))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            err(json!({"cpuid_modifiers": {"leaf": "0x1"}})),
+            "invalid cpuid_modifiers: must be a JSON array"
+        );
+        assert!(err(json!({"cpuid_modifiers": [{
+            "leaf": "0x1", "subleaf": "0x0",
+            "modifiers": [{"register": "rax", "bitmap": "0b1"}]
+        }]}))
+        .starts_with("invalid cpuid_modifiers[0].modifiers[0].register"));
+        assert!(
+            err(json!({"msr_modifiers": [{"addr": "0x10a", "bitmap": "1010"}]}))
+                .starts_with("invalid msr_modifiers[0].bitmap")
+        );
+        assert!(
+            err(json!({"reg_modifiers": [{"addr": "10a", "bitmap": "0b1"}]}))
+                .starts_with("invalid reg_modifiers[0].addr")
+        );
+        assert!(err(json!({"kvm_capabilities": [56]})).starts_with("invalid kvm_capabilities"));
+    }
+}
@@ -220,6 +220,9 @@ async fn install_files(
 # Auto-generated during VM creation
 VM_ID={}
 MANAGER_URL={}
+# Interface whose address is reported as the guest IP
+# (default: the one carrying the default route, else eth0)
+#REPORT_INTERFACE=eth0
 "#,
         vm_id, manager_url
     );
@@ -0,0 +1,154 @@
+//! The vsock channel to the guest agent. Fresh Firecracker boots get a vsock
+//! device whose host side is a Unix socket next to the API socket; the agent
+//! relays `GET /metrics` and `GET /addresses` over it, so metrics and the
+//! guest IP work before (or without) the guest network. TCP on the guest IP
+//! stays as the fallback for metrics.
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use super::repo::VmRow;
+use super::routes::UpdateGuestIpReq;
+
+const GUEST_VSOCK_ENV: &str = "MANAGER_GUEST_VSOCK";
+/// Port the guest agent listens on, over TCP and vsock alike.
+pub const GUEST_AGENT_PORT: u32 = 9000;
+#[cfg_attr(test, allow(dead_code))]
+pub const GUEST_VSOCK_ID: &str = "agent";
+const GUEST_METRICS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether `configure_vm` attaches the guest agent vsock device. On unless
+/// `MANAGER_GUEST_VSOCK` is `0`, `false` or `off`.
+#[cfg_attr(test, allow(dead_code))]
+pub fn enabled() -> bool {
+    parse_enabled(std::env::var(GUEST_VSOCK_ENV).ok().as_deref())
+}
+
+fn parse_enabled(raw: Option<&str>) -> bool {
+    !matches!(
+        raw.map(|v| v.trim().to_ascii_lowercase()).as_deref(),
+        Some("0" | "false" | "off")
+    )
+}
+
+/// A stable CID per VM. 0-2 are reserved (hypervisor, local, host) and
+/// `u32::MAX` is `VMADDR_CID_ANY`.
+pub fn guest_cid(vm_id: Uuid) -> u32 {
+    let bytes = vm_id.as_bytes();
+    let raw = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    3 + raw % (u32::MAX - 3)
+}
+
+/// Host side of the vsock device, beside the Firecracker API socket so the
+/// agent's per-VM socket checks accept it. Named after the VM because
+/// Firecracker saves the path in snapshots: a VM restored from another VM's
+/// snapshot overrides it with its own (see `load_snapshot`).
+pub fn uds_path(api_sock: &str, vm_id: Uuid) -> String {
+    Path::new(api_sock)
+        .with_file_name(format!("vsock-{}.sock", vm_id.simple()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether `path` names a vsock socket rather than a Firecracker API socket.
+pub fn is_uds_path(path: &str) -> bool {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("vsock"))
+}
+
+/// The guest agent's `/metrics`, over vsock through the agent first, then
+/// over TCP if the guest has reported an IP.
+pub async fn guest_metrics<T: DeserializeOwned>(client: &reqwest::Client, vm: &VmRow) -> Result<T> {
+    let vsock_err = match via_vsock(client, vm, "metrics").await {
+        Ok(metrics) => return Ok(metrics),
+        Err(err) => err,
+    };
+    let Some(guest_ip) = vm.guest_ip.as_deref() else {
+        return Err(vsock_err.context("guest has no IP to fall back to"));
+    };
+    tracing::debug!(vm_id = %vm.id, error = %vsock_err, "guest vsock unavailable, trying TCP");
+    client
+        .get(format!("http://{guest_ip}:{GUEST_AGENT_PORT}/metrics"))
+        .timeout(GUEST_METRICS_TIMEOUT)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("querying guest agent over TCP")?
+        .json()
+        .await
+        .context("decoding guest metrics")
+}
+
+/// The guest agent's IP report, read over vsock. Guests that can't reach
+/// the manager over the network never push it to `/v1/vms/{id}/guest-ip`.
+pub async fn guest_addresses(client: &reqwest::Client, vm: &VmRow) -> Result<UpdateGuestIpReq> {
+    via_vsock(client, vm, "addresses").await
+}
+
+async fn via_vsock<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    vm: &VmRow,
+    endpoint: &str,
+) -> Result<T> {
+    if vm.vmm_kind.as_deref() == Some("qemu") {
+        bail!("QEMU VMs have no guest vsock");
+    }
+    let url = format!(
+        "{}/agent/v1/vms/{}/vsock/{endpoint}?uds={}&port={GUEST_AGENT_PORT}",
+        vm.host_addr.trim_end_matches('/'),
+        vm.id,
+        urlencoding::encode(&uds_path(&vm.api_sock, vm.id))
+    );
+    client
+        .get(url)
+        .timeout(GUEST_METRICS_TIMEOUT)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("querying guest agent over vsock")?
+        .json()
+        .await
+        .with_context(|| format!("decoding guest {endpoint}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guest_vsock_defaults_on() {
+        assert!(parse_enabled(None));
+        assert!(parse_enabled(Some("yes")));
+        assert!(!parse_enabled(Some(" Off ")));
+        assert!(!parse_enabled(Some("0")));
+    }
+
+    #[test]
+    fn cids_are_stable_and_unreserved() {
+        let id = Uuid::parse_str("00000000-0000-4000-8000-000000000000").unwrap();
+        assert_eq!(guest_cid(id), 3);
+        let id = Uuid::parse_str("ffffffff-0000-4000-8000-000000000000").unwrap();
+        assert_eq!(guest_cid(id), 6);
+        let id = Uuid::new_v4();
+        assert_eq!(guest_cid(id), guest_cid(id));
+        assert!((3..u32::MAX).contains(&guest_cid(id)));
+    }
+
+    #[test]
+    fn uds_sits_beside_the_api_socket() {
+        let id = Uuid::parse_str("0123456789abcdef0123456789abcdef").unwrap();
+        let uds = uds_path("/srv/fc/vms/abc/sock/fc.sock", id);
+        assert_eq!(
+            uds,
+            "/srv/fc/vms/abc/sock/vsock-0123456789abcdef0123456789abcdef.sock"
+        );
+        assert!(is_uds_path(&uds));
+        assert!(!is_uds_path("/srv/fc/vms/abc/sock/fc.sock"));
+    }
+}
@@ -0,0 +1,277 @@
+//! `Idempotency-Key` support for `POST /v1/vms`. The first request with a key
+//! claims it for the VM id it is about to create; a retry with the same key
+//! (same user, within the TTL) gets that id back instead of a second VM.
+//! Reusing a key for a different request body is refused.
+
+use crate::core::error::{bad_request, conflict};
+use anyhow::{bail, Context, Result};
+use axum::http::HeaderMap;
+use nexus_types::{CreateVmReq, Job, JobKind};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const HEADER: &str = "idempotency-key";
+const TTL_ENV: &str = "MANAGER_VM_IDEMPOTENCY_TTL_SECS";
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_KEY_LEN: usize = 255;
+
+/// How long a key is remembered, from `MANAGER_VM_IDEMPOTENCY_TTL_SECS`
+/// (default 24 hours).
+pub fn ttl() -> Duration {
+    parse_ttl(std::env::var(TTL_ENV).ok().as_deref())
+}
+
+fn parse_ttl(raw: Option<&str>) -> Duration {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TTL)
+}
+
+/// The request's idempotency key, or `None` when the header is absent.
+pub fn key_from_headers(headers: &HeaderMap) -> Result<Option<String>> {
+    let Some(value) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN);
+    match key {
+        Some(key) => Ok(Some(key.to_string())),
+        None => bail!(bad_request(format!(
+            "invalid Idempotency-Key: must be 1-{MAX_KEY_LEN} visible ASCII characters"
+        ))),
+    }
+}
+
+/// Keys are per user; unauthenticated callers share one scope.
+pub fn scope(user_id: Option<Uuid>) -> String {
+    user_id.map_or_else(|| "anonymous".to_string(), |id| id.to_string())
+}
+
+/// SHA-256 of the request body, to tell a retry from a different request
+/// sent with the same key.
+pub fn fingerprint(req: &CreateVmReq) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let body = serde_json::to_vec(req).context("encoding create request")?;
+    Ok(hex::encode(Sha256::digest(body)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// The key is new and now belongs to the VM being created.
+    New,
+    /// An earlier request with the same body already used the key for this VM.
+    Existing(Uuid),
+    /// The key was first used with a different request body.
+    Mismatch,
+}
+
+/// Claim `key` for `vm_id`, or return the VM an earlier request claimed it
+/// for. Expired keys are pruned first so they can be reused.
+pub async fn claim(
+    db: &PgPool,
+    scope: &str,
+    key: &str,
+    vm_id: Uuid,
+    request_hash: &str,
+) -> Result<Claim> {
+    sqlx::query(
+        r#"DELETE FROM vm_create_idempotency
+           WHERE created_at < now() - make_interval(secs => $1)"#,
+    )
+    .bind(ttl().as_secs_f64())
+    .execute(db)
+    .await
+    .context("pruning expired idempotency keys")?;
+
+    let inserted: Option<Uuid> = sqlx::query_scalar(
+        r#"INSERT INTO vm_create_idempotency (scope, key, vm_id, request_hash)
+           VALUES ($1, $2, $3, $4)
+           ON CONFLICT (scope, key) DO NOTHING
+           RETURNING vm_id"#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(vm_id)
+    .bind(request_hash)
+    .fetch_optional(db)
+    .await
+    .context("recording idempotency key")?;
+    if inserted.is_some() {
+        return Ok(Claim::New);
+    }
+
+    let existing: Option<(Uuid, Option<String>)> = sqlx::query_as(
+        r#"SELECT vm_id, request_hash FROM vm_create_idempotency
+           WHERE scope = $1 AND key = $2"#,
+    )
+    .bind(scope)
+    .bind(key)
+    .fetch_optional(db)
+    .await
+    .context("looking up idempotency key")?;
+    match existing {
+        Some((_, Some(hash))) if hash != request_hash => Ok(Claim::Mismatch),
+        Some((vm_id, _)) => Ok(Claim::Existing(vm_id)),
+        // Released by a failed create between our insert and select
+        None => bail!(conflict(
+            "Idempotency-Key conflict with a create that just failed; retry"
+        )),
+    }
+}
+
+/// The newest create job of the replayed VM, if this manager still has one.
+pub fn replayed_job(jobs: &[Job]) -> Option<&Job> {
+    jobs.iter()
+        .filter(|job| job.kind == JobKind::CreateVm)
+        .max_by_key(|job| job.started_at)
+}
+
+/// Forget a key whose create failed, so the client's retry creates afresh.
+pub async fn release(db: &PgPool, scope: &str, key: &str, vm_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"DELETE FROM vm_create_idempotency WHERE scope = $1 AND key = $2 AND vm_id = $3"#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(vm_id)
+    .execute(db)
+    .await
+    .context("releasing idempotency key")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn key_is_optional_but_must_be_sane_when_present() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(key_from_headers(&headers).unwrap(), None);
+
+        headers.insert(HEADER, HeaderValue::from_static(" tf-run-42 "));
+        assert_eq!(
+            key_from_headers(&headers).unwrap().as_deref(),
+            Some("tf-run-42")
+        );
+
+        headers.insert(HEADER, HeaderValue::from_static("  "));
+        assert!(key_from_headers(&headers).is_err());
+
+        headers.insert(HEADER, HeaderValue::from_str(&"k".repeat(256)).unwrap());
+        let err = key_from_headers(&headers).unwrap_err();
+        assert!(err.to_string().starts_with("invalid Idempotency-Key"));
+    }
+
+    #[test]
+    fn scope_separates_users() {
+        let user = Uuid::new_v4();
+        assert_eq!(scope(Some(user)), user.to_string());
+        assert_eq!(scope(None), "anonymous");
+    }
+
+    #[test]
+    fn fingerprint_tells_bodies_apart() {
+        let req = CreateVmReq {
+            name: "web".into(),
+            vcpu: 2,
+            mem_mib: 512,
+            ..Default::default()
+        };
+        let same = req.clone();
+        let other = CreateVmReq {
+            vcpu: 4,
+            ..req.clone()
+        };
+        assert_eq!(fingerprint(&req).unwrap(), fingerprint(&same).unwrap());
+        assert_ne!(fingerprint(&req).unwrap(), fingerprint(&other).unwrap());
+        assert_eq!(fingerprint(&req).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn replay_picks_the_newest_create_job() {
+        let vm_id = Uuid::new_v4();
+        let job = |kind, minutes_ago| Job {
+            id: Uuid::new_v4(),
+            kind,
+            vm_id,
+            status: nexus_types::JobStatus::Succeeded,
+            error: None,
+            started_at: chrono::Utc::now() - chrono::Duration::minutes(minutes_ago),
+            finished_at: None,
+        };
+        let older = job(JobKind::CreateVm, 10);
+        let newer = job(JobKind::CreateVm, 5);
+        let migrate = job(JobKind::MigrateVm, 1);
+        let jobs = vec![older, migrate, newer.clone()];
+        assert_eq!(replayed_job(&jobs).map(|j| j.id), Some(newer.id));
+        assert!(replayed_job(&jobs[1..2]).is_none());
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn claim_replays_the_first_vm_and_refuses_other_bodies(pool: PgPool) {
+        let first = Uuid::new_v4();
+        assert_eq!(
+            claim(&pool, "alice", "k1", first, "h1").await.unwrap(),
+            Claim::New
+        );
+        assert_eq!(
+            claim(&pool, "alice", "k1", Uuid::new_v4(), "h1")
+                .await
+                .unwrap(),
+            Claim::Existing(first)
+        );
+        assert_eq!(
+            claim(&pool, "alice", "k1", Uuid::new_v4(), "h2")
+                .await
+                .unwrap(),
+            Claim::Mismatch
+        );
+        // Keys are per user
+        assert_eq!(
+            claim(&pool, "bob", "k1", Uuid::new_v4(), "h2")
+                .await
+                .unwrap(),
+            Claim::New
+        );
+    }
+
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn release_frees_only_the_claiming_vm(pool: PgPool) {
+        let first = Uuid::new_v4();
+        claim(&pool, "alice", "k1", first, "h1").await.unwrap();
+
+        // Another VM's failure doesn't free the key
+        release(&pool, "alice", "k1", Uuid::new_v4()).await.unwrap();
+        assert_eq!(
+            claim(&pool, "alice", "k1", Uuid::new_v4(), "h1")
+                .await
+                .unwrap(),
+            Claim::Existing(first)
+        );
+
+        release(&pool, "alice", "k1", first).await.unwrap();
+        let retry = Uuid::new_v4();
+        assert_eq!(
+            claim(&pool, "alice", "k1", retry, "h2").await.unwrap(),
+            Claim::New
+        );
+    }
+
+    #[test]
+    fn ttl_falls_back_to_a_day() {
+        assert_eq!(parse_ttl(None), DEFAULT_TTL);
+        assert_eq!(parse_ttl(Some("0")), DEFAULT_TTL);
+        assert_eq!(parse_ttl(Some("soon")), DEFAULT_TTL);
+        assert_eq!(parse_ttl(Some("3600")), Duration::from_secs(3600));
+    }
+}
@@ -3,23 +3,39 @@ use axum::{
     Router,
 };
 
+pub mod balloon_policy; // automatic balloon sizing
+pub mod console; // serial console log
+pub mod cpu_templates; // cpu_template / cpu-config validation
 pub mod guest_agent;
+pub mod guest_vsock; // vsock channel to the guest agent
+pub mod idempotency; // Idempotency-Key for POST /v1/vms
 pub mod port_forwards;
+pub mod progress; // create progress events
 pub mod qemu_service; // QEMU-backed create/start path (0.5.0)
 pub mod repo; // db
 pub mod routes; // handlers
 pub mod service; // orchestration
 pub mod shell; // shell session helpers // automatic guest agent installation
+pub mod ws_session; // websocket idle / max-duration limits
 
 pub fn router() -> Router {
     Router::new()
         .route("/", post(routes::create).get(routes::list))
+        .route("/validate", post(routes::validate))
         .route(
             "/:id",
             get(routes::get)
                 .patch(routes::update)
                 .delete(routes::delete),
         )
+        // Progress frames name the image, host and config of a create, so
+        // only signed-in callers may watch them
+        .route(
+            "/:id/create/ws",
+            get(routes::create_progress_websocket).route_layer(axum::middleware::from_fn(
+                crate::features::users::middleware::auth_middleware,
+            )),
+        )
         .route("/:id/start", post(routes::start))
         .route("/:id/stop", post(routes::stop))
         .route("/:id/pause", post(routes::pause))
@@ -41,6 +57,8 @@ pub fn router() -> Router {
                 .delete(routes::delete_drive),
         )
         .route("/:id/drives/:drive_id/resize", post(routes::resize_drive))
+        .route("/:id/events", get(routes::list_events))
+        .route("/:id/processes", get(routes::list_processes))
         .route("/:id/nics", get(routes::list_nics).post(routes::create_nic))
         .route(
             "/:id/nics/:nic_id",
@@ -49,8 +67,11 @@ pub fn router() -> Router {
                 .delete(routes::delete_nic),
         )
         .route("/:id/shell", get(routes::get_shell_credentials))
+        .route("/:id/shell/rotate", post(routes::rotate_shell_credentials))
         .route("/:id/shell/ws", get(routes::shell_websocket))
         .route("/:id/metrics/ws", get(routes::metrics_websocket))
+        .route("/:id/console", get(routes::get_console))
+        .route("/:id/console/ws", get(routes::console_websocket))
         .route("/:id/console/vnc/ws", get(routes::vnc_websocket))
         .route("/:id/guest-ip", post(routes::update_guest_ip))
         .route(
@@ -62,7 +83,10 @@ pub fn router() -> Router {
             axum::routing::put(routes::put_cpu_config),
         )
         .route("/:id/vsock", axum::routing::put(routes::put_vsock))
-        .route("/:id/mmds", axum::routing::put(routes::put_mmds))
+        .route(
+            "/:id/mmds",
+            axum::routing::get(routes::get_mmds).put(routes::put_mmds),
+        )
         .route(
             "/:id/mmds/config",
             axum::routing::put(routes::put_mmds_config),
@@ -86,4 +110,10 @@ pub fn router() -> Router {
             "/:id/balloon/statistics",
             axum::routing::patch(routes::patch_balloon_statistics),
         )
+        .route(
+            "/:id/balloon/policy",
+            get(routes::get_balloon_policy)
+                .put(routes::put_balloon_policy)
+                .delete(routes::delete_balloon_policy),
+        )
 }
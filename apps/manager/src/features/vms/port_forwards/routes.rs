@@ -74,9 +74,11 @@ pub async fn create(
 
     // If VM is running and has a guest IP, apply the forward immediately
     if let Ok(vm) = super::super::repo::get(&st.db, id).await {
-        if vm.state == "running" && vm.guest_ip.as_ref().is_some_and(|ip| !ip.is_empty()) {
+        if super::super::service::is_running_state(&vm.state)
+            && vm.guest_ip.as_ref().is_some_and(|ip| !ip.is_empty())
+        {
             let guest_ip = vm.guest_ip.as_deref().unwrap();
-            let _ = reqwest::Client::new()
+            let _ = crate::core::agent_http::client()
                 .post(format!(
                     "{}/agent/v1/vms/{}/port-forward",
                     vm.host_addr, vm.id
@@ -116,9 +118,11 @@ pub async fn delete(
 
     // If VM is running, remove the iptables rule
     if let Ok(vm) = super::super::repo::get(&st.db, id).await {
-        if vm.state == "running" && vm.guest_ip.as_ref().is_some_and(|ip| !ip.is_empty()) {
+        if super::super::service::is_running_state(&vm.state)
+            && vm.guest_ip.as_ref().is_some_and(|ip| !ip.is_empty())
+        {
             let guest_ip = vm.guest_ip.as_deref().unwrap();
-            let _ = reqwest::Client::new()
+            let _ = crate::core::agent_http::client()
                 .delete(format!(
                     "{}/agent/v1/vms/{}/port-forward",
                     vm.host_addr, vm.id
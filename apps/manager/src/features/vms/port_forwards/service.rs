@@ -19,7 +19,7 @@ pub async fn apply_forwards(st: &AppState, vm_id: Uuid) -> Result<()> {
     info!(vm_id=%vm_id, count=%forwards.len(), "applying port forwards");
 
     for fwd in &forwards {
-        let resp = reqwest::Client::new()
+        let resp = crate::core::agent_http::client()
             .post(format!(
                 "{}/agent/v1/vms/{}/port-forward",
                 vm.host_addr, vm.id
@@ -65,7 +65,7 @@ pub async fn cleanup_forwards(st: &AppState, vm_id: Uuid) -> Result<()> {
     info!(vm_id=%vm_id, count=%forwards.len(), "cleaning up port forwards");
 
     for fwd in &forwards {
-        let resp = reqwest::Client::new()
+        let resp = crate::core::agent_http::client()
             .delete(format!(
                 "{}/agent/v1/vms/{}/port-forward",
                 vm.host_addr, vm.id
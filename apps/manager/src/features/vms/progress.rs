@@ -0,0 +1,183 @@
+//! Step-by-step progress for `POST /v1/vms`, so the UI can show a live
+//! creation log instead of waiting blind for the final response.
+
+use nexus_types::{VmCreateProgressEvent, VmCreateStep, VmCreateStepStatus};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// How long a finished create's log stays around for late subscribers.
+const FINISHED_RETENTION: Duration = Duration::from_secs(5 * 60);
+
+/// Creation logs keyed by VM id. Every event is also broadcast so websocket
+/// clients get pushed updates; the stored log lets a client that connects
+/// mid-create replay what it missed.
+#[derive(Clone)]
+pub struct CreateProgressTracker {
+    logs: Arc<Mutex<HashMap<Uuid, Vec<VmCreateProgressEvent>>>>,
+    updates: broadcast::Sender<VmCreateProgressEvent>,
+}
+
+impl Default for CreateProgressTracker {
+    fn default() -> Self {
+        let (updates, _) = broadcast::channel(256);
+        Self {
+            logs: Arc::default(),
+            updates,
+        }
+    }
+}
+
+impl CreateProgressTracker {
+    /// Start a fresh log for `vm_id`, dropping any left from an earlier create.
+    pub fn begin(&self, vm_id: Uuid) {
+        self.logs.lock().unwrap().insert(vm_id, Vec::new());
+    }
+
+    pub fn emit(
+        &self,
+        vm_id: Uuid,
+        step: VmCreateStep,
+        status: VmCreateStepStatus,
+        message: impl Into<String>,
+    ) {
+        let event = {
+            let mut logs = self.logs.lock().unwrap();
+            let log = logs.entry(vm_id).or_default();
+            let event = VmCreateProgressEvent {
+                vm_id,
+                seq: log.len() as u32 + 1,
+                step,
+                status,
+                message: message.into(),
+                at: chrono::Utc::now(),
+            };
+            log.push(event.clone());
+            event
+        };
+        if event.is_finished() {
+            self.expire_later(vm_id, event.seq);
+        }
+        // No receivers is fine: nobody is watching this create
+        let _ = self.updates.send(event);
+    }
+
+    /// Record the create's outcome; this is the last event of the log.
+    pub fn finish(&self, vm_id: Uuid, result: &anyhow::Result<()>) {
+        match result {
+            Ok(()) => self.emit(
+                vm_id,
+                VmCreateStep::Done,
+                VmCreateStepStatus::Ok,
+                "VM created",
+            ),
+            Err(err) => self.emit(
+                vm_id,
+                VmCreateStep::Done,
+                VmCreateStepStatus::Failed,
+                format!("{err:#}"),
+            ),
+        }
+    }
+
+    /// Events for `vm_id` with a sequence number after `after_seq`.
+    pub fn log_since(&self, vm_id: Uuid, after_seq: u32) -> Vec<VmCreateProgressEvent> {
+        self.logs
+            .lock()
+            .unwrap()
+            .get(&vm_id)
+            .map(|log| {
+                log.iter()
+                    .filter(|event| event.seq > after_seq)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<VmCreateProgressEvent> {
+        self.updates.subscribe()
+    }
+
+    /// Drop the finished log after [`FINISHED_RETENTION`], unless a new create
+    /// for the same id has started since.
+    fn expire_later(&self, vm_id: Uuid, final_seq: u32) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let logs = self.logs.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(FINISHED_RETENTION).await;
+            let mut logs = logs.lock().unwrap();
+            let unchanged = logs
+                .get(&vm_id)
+                .and_then(|log| log.last())
+                .is_some_and(|last| last.seq == final_seq && last.is_finished());
+            if unchanged {
+                logs.remove(&vm_id);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[tokio::test]
+    async fn emit_numbers_events_and_broadcasts_them() {
+        let tracker = CreateProgressTracker::default();
+        let mut updates = tracker.subscribe();
+        let vm_id = Uuid::new_v4();
+
+        tracker.begin(vm_id);
+        tracker.emit(
+            vm_id,
+            VmCreateStep::Tap,
+            VmCreateStepStatus::Ok,
+            "tap-1 on br0",
+        );
+        tracker.emit(
+            vm_id,
+            VmCreateStep::Spawn,
+            VmCreateStepStatus::Started,
+            "waiting for socket",
+        );
+        tracker.finish(vm_id, &Err(anyhow!("socket not ready")));
+
+        let first = updates.recv().await.unwrap();
+        assert_eq!((first.seq, first.step), (1, VmCreateStep::Tap));
+        assert_eq!(updates.recv().await.unwrap().seq, 2);
+        let last = updates.recv().await.unwrap();
+        assert!(last.is_finished());
+        assert_eq!(last.status, VmCreateStepStatus::Failed);
+        assert_eq!(last.message, "socket not ready");
+
+        let replay = tracker.log_since(vm_id, 1);
+        assert_eq!(replay.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn begin_resets_an_earlier_log() {
+        let tracker = CreateProgressTracker::default();
+        let vm_id = Uuid::new_v4();
+        tracker.emit(
+            vm_id,
+            VmCreateStep::Resolve,
+            VmCreateStepStatus::Ok,
+            "host h1",
+        );
+        tracker.begin(vm_id);
+        assert!(tracker.log_since(vm_id, 0).is_empty());
+        tracker.emit(
+            vm_id,
+            VmCreateStep::Resolve,
+            VmCreateStepStatus::Ok,
+            "host h1",
+        );
+        assert_eq!(tracker.log_since(vm_id, 0)[0].seq, 1);
+    }
+}
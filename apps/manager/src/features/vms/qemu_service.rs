@@ -8,17 +8,18 @@
 //! Anything that's truly common (TAP creation, host selection, audit logs)
 //! is shared via the existing helpers.
 
+use crate::core::error::{bad_request, not_found};
 use std::time::Duration;
 
 use anyhow::{anyhow, bail, Context, Result};
 use nexus_types::CreateVmReq;
 use nexus_vmm::{BootMode, DiskSpec, GuestOs, NicSpec, VmmKind};
-use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 use tracing::info;
 use uuid::Uuid;
 
+use crate::features::jobs::JobHandle;
 use crate::AppState;
 
 /// Default OVMF firmware paths on Arch / Fedora / Debian. The agent's
@@ -26,6 +27,23 @@ use crate::AppState;
 const DEFAULT_OVMF_CODE: &str = "/usr/share/edk2/x64/OVMF_CODE.4m.fd";
 const DEFAULT_OVMF_VARS: &str = "/usr/share/edk2/x64/OVMF_VARS.4m.fd";
 
+/// The target of a reschedule or migration is draining (its agent is
+/// shutting down); routes answer 409.
+#[derive(Debug)]
+pub struct TargetDraining(pub String);
+
+impl std::fmt::Display for TargetDraining {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "target host {} is draining", self.0)
+    }
+}
+
+impl std::error::Error for TargetDraining {}
+
+pub fn is_target_draining(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TargetDraining>().is_some()
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct BootResp {
@@ -90,7 +108,9 @@ pub fn validate_and_resolve(req: &CreateVmReq) -> Result<(VmmKind, GuestOs, Boot
         bail!("vmm_kind={vmm_kind} does not support guest_os={guest_os}",);
     }
     if matches!(boot_mode, BootMode::Uefi { .. }) && !feats.uefi_boot {
-        bail!("vmm_kind={vmm_kind} cannot UEFI-boot guest_os={guest_os}");
+        bail!(bad_request(format!(
+            "vmm_kind={vmm_kind} cannot UEFI-boot guest_os={guest_os}"
+        )));
     }
     if req.enable_vnc && !feats.vnc_console {
         bail!("vmm_kind={vmm_kind} does not support vnc_console");
@@ -104,8 +124,7 @@ pub async fn create_and_start_qemu(
     id: Uuid,
     req: CreateVmReq,
     template_id: Option<Uuid>,
-    _user_id: Option<Uuid>,
-    _audit_username: &str,
+    user_id: Option<Uuid>,
 ) -> Result<()> {
     let (vmm_kind, _guest_os, boot_mode, enable_vnc) = validate_and_resolve(&req)?;
     // Resolved guest_os used by several later branches (TPM auto-enable,
@@ -134,8 +153,8 @@ pub async fn create_and_start_qemu(
     let tap_name = if test_mode {
         "user".to_string()
     } else {
-        let tn = format!("tap-{}", &id.to_string()[..8]);
-        create_tap(&host.addr, id, &bridge)
+        let tn = super::service::allocate_tap_name(&st.db, id).await?;
+        create_tap(&host.addr, id, &tn, &bridge)
             .await
             .context("create_tap on agent")?;
         tn
@@ -305,9 +324,9 @@ pub async fn create_and_start_qemu(
             }
         };
         // Use a per-extra-NIC TAP. Keep names short — ifname max len is 15.
-        let extra_tap = format!("xtp-{}-{i}", &id.to_string()[..6]);
+        let extra_tap = format!("tap-{}-{}", &id.simple().to_string()[..8], i + 1);
         if !test_mode {
-            if let Err(e) = create_tap(&host.addr, *extra_net_id, &extra_bridge).await {
+            if let Err(e) = create_tap(&host.addr, id, &extra_tap, &extra_bridge).await {
                 tracing::warn!(network_id=%extra_net_id, error=?e, "skipping extra NIC — TAP creation failed");
                 continue;
             }
@@ -379,10 +398,7 @@ pub async fn create_and_start_qemu(
     // (the agent keeps going after our client gives up). Use a generous
     // timeout, and on ANY boot failure fire a best-effort destroy so we
     // never leave an unmanaged QEMU process behind.
-    let http = Client::builder()
-        .timeout(Duration::from_secs(300))
-        .build()
-        .context("build http client")?;
+    let http = crate::core::agent_http::client();
 
     // Best-effort orphan cleanup helper: tell the agent to destroy whatever
     // it may have spawned for this id, then release the host reservation.
@@ -394,15 +410,13 @@ pub async fn create_and_start_qemu(
         vcpu: i32,
         mem_mib: i64,
     ) {
-        let c = Client::builder().timeout(Duration::from_secs(30)).build();
-        if let Ok(c) = c {
-            let _ = c
-                .post(format!(
-                    "{host_addr}/agent/v1/vmm/{id}/destroy?vmm_kind=qemu"
-                ))
-                .send()
-                .await;
-        }
+        let _ = crate::core::agent_http::client()
+            .post(format!(
+                "{host_addr}/agent/v1/vmm/{id}/destroy?vmm_kind=qemu"
+            ))
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await;
         let _ = host_repo.release_reservation(host_id, vcpu, mem_mib).await;
     }
 
@@ -410,6 +424,7 @@ pub async fn create_and_start_qemu(
     let resp = match http
         .post(format!("{}/agent/v1/vmm/{}/boot", host.addr, id))
         .json(&body)
+        .timeout(Duration::from_secs(300))
         .send()
         .await
     {
@@ -472,14 +487,24 @@ pub async fn create_and_start_qemu(
         source_snapshot_id: None,
         guest_ip: None,
         tags: req.tags.clone(),
-        created_by_user_id: None,
+        created_by_user_id: user_id,
         vmm_kind: Some("qemu".to_string()),
         guest_os: Some(guest_os_resolved.as_str().to_string()),
         console_kind: Some(if enable_vnc { "vnc" } else { "unix_serial" }.to_string()),
         vnc_listen: handle.vnc.clone(),
         cpu_type: None,
+        boot_args: None,
+        smt: None,
+        huge_pages: None,
+        rootfs_rate_limiter: None,
+        entropy_rate_limiter: None,
+        rootfs_mode: None,
+        cpu_quota: None,
+        io_weight: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        state_changed_at: chrono::Utc::now(),
+        started_at: Some(chrono::Utc::now()),
     };
     super::repo::insert(&st.db, &row)
         .await
@@ -573,8 +598,14 @@ pub async fn restart_qemu(st: &AppState, vm: &super::repo::VmRow) -> Result<()>
     let tap_name = if test_mode {
         "user".to_string()
     } else {
-        let tn = format!("tap-{}", &id.to_string()[..8]);
-        create_tap(&host.addr, id, &bridge)
+        // Reuse the name chosen at create time; rows created in test mode
+        // carry the slirp sentinel and need a real name allocated.
+        let tn = if vm.tap == "user" {
+            super::service::allocate_tap_name(&st.db, id).await?
+        } else {
+            vm.tap.clone()
+        };
+        create_tap(&host.addr, id, &tn, &bridge)
             .await
             .context("create_tap on qemu restart")?;
         tn
@@ -642,13 +673,10 @@ pub async fn restart_qemu(st: &AppState, vm: &super::repo::VmRow) -> Result<()>
         "cpu_type": vm.cpu_type,
     });
 
-    let http = Client::builder()
-        .timeout(Duration::from_secs(300))
-        .build()
-        .context("build http client (restart_qemu)")?;
-    let resp = http
+    let resp = crate::core::agent_http::client()
         .post(format!("{}/agent/v1/vmm/{}/boot", host.addr, id))
         .json(&body)
+        .timeout(Duration::from_secs(300))
         .send()
         .await
         .context("agent boot (restart) request failed")?;
@@ -661,7 +689,9 @@ pub async fn restart_qemu(st: &AppState, vm: &super::repo::VmRow) -> Result<()>
     // Update the existing row in place (no insert).
     sqlx::query(
         r#"UPDATE vm SET state = 'running', api_sock = $2, tap = $3, fc_unit = $4,
-                         vnc_listen = $5, updated_at = now() WHERE id = $1"#,
+                         vnc_listen = $5, updated_at = now(), started_at = now(),
+                         state_changed_at = CASE WHEN state <> 'running' THEN now() ELSE state_changed_at END
+           WHERE id = $1"#,
     )
     .bind(id)
     .bind(&handle.api_sock)
@@ -735,6 +765,9 @@ pub async fn reschedule(st: &AppState, vm_id: Uuid, target_host_id: Uuid) -> Res
         .get(target_host_id)
         .await
         .context("load target host")?;
+    if target_host.draining_since.is_some() {
+        return Err(TargetDraining(target_host.name.clone()).into());
+    }
     let kinds = host_repo
         .vmm_kinds_installed(target_host_id)
         .await
@@ -791,7 +824,7 @@ pub async fn reschedule(st: &AppState, vm_id: Uuid, target_host_id: Uuid) -> Res
     let backend = st
         .registry
         .get(backend_id)
-        .ok_or_else(|| anyhow!("backend {backend_id} not found"))?;
+        .ok_or_else(|| not_found(format!("backend {backend_id} not found")))?;
     let handle = nexus_storage::VolumeHandle {
         volume_id,
         backend_id: nexus_storage::BackendInstanceId(backend_id),
@@ -833,13 +866,10 @@ pub async fn reschedule(st: &AppState, vm_id: Uuid, target_host_id: Uuid) -> Res
         "enable_balloon": true,
         "enable_rng": true,
     });
-    let http = Client::builder()
-        .timeout(Duration::from_secs(60))
-        .build()
-        .context("build http client")?;
-    let resp = http
+    let resp = crate::core::agent_http::client()
         .post(format!("{}/agent/v1/vmm/{}/boot", target_host.addr, vm.id))
         .json(&body)
+        .timeout(Duration::from_secs(60))
         .send()
         .await
         .context("agent boot request")?;
@@ -856,7 +886,8 @@ pub async fn reschedule(st: &AppState, vm_id: Uuid, target_host_id: Uuid) -> Res
         .release_reservation(vm.host_id, vm.vcpu, vm.mem_mib as i64)
         .await;
     sqlx::query(
-        r#"UPDATE vm SET host_id = $2, state = 'running', updated_at = now() WHERE id = $1"#,
+        r#"UPDATE vm SET host_id = $2, state = 'running', updated_at = now(),
+                         state_changed_at = now(), started_at = now() WHERE id = $1"#,
     )
     .bind(vm_id)
     .bind(target_host_id)
@@ -878,8 +909,13 @@ pub async fn reschedule(st: &AppState, vm_id: Uuid, target_host_id: Uuid) -> Res
 /// manager POSTs `/migrate/outgoing` on the source to drive the QMP
 /// `migrate`. When the stream finishes, the source QEMU exits and the
 /// target's paused QEMU transitions to running automatically.
+///
+/// Until the source reports the stream finished, cancelling `job` kills
+/// the target's paused QEMU (which fails the source's migrate and leaves
+/// the VM running where it was) and releases the target reservation.
 pub async fn live_migrate(
     st: &AppState,
+    job: &JobHandle,
     vm_id: Uuid,
     target_host_id: Uuid,
     target_port: u16,
@@ -896,6 +932,9 @@ pub async fn live_migrate(
         .get(target_host_id)
         .await
         .context("load target host")?;
+    if target_host.draining_since.is_some() {
+        return Err(TargetDraining(target_host.name.clone()).into());
+    }
     let kinds = host_repo
         .vmm_kinds_installed(target_host_id)
         .await
@@ -910,142 +949,167 @@ pub async fn live_migrate(
     if !fit {
         bail!("target host {target_host_id} is at capacity");
     }
-    // Step 1: tell the target to spawn a paused QEMU listening for the
-    // incoming migration stream. We re-derive the spec from the source
-    // VM's persisted boot_mode + the volume_attachment on shared storage.
-    let boot_mode_json: Option<serde_json::Value> =
-        sqlx::query_scalar(r#"SELECT boot_mode FROM vm WHERE id = $1"#)
-            .bind(vm_id)
-            .fetch_optional(&st.db)
-            .await
-            .context("load saved boot_mode")?
-            .flatten();
-    let Some(boot_mode_json) = boot_mode_json else {
-        let _ = host_repo
-            .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
-            .await;
-        bail!("vm has no persisted boot_mode");
-    };
-    let boot_mode_target: BootMode =
-        serde_json::from_value(boot_mode_json).context("decode persisted boot_mode")?;
-
-    // The target needs to mount the same shared volume on its host.
-    let vol_row = sqlx::query_as::<_, (Uuid, Uuid, String)>(
-        r#"SELECT v.id, v.backend_id, v.path
-           FROM volume v
-           JOIN volume_attachment va ON va.volume_id = v.id
-           WHERE va.vm_id = $1 AND va.drive_id = 'rootfs'
-           ORDER BY va.attached_at DESC LIMIT 1"#,
-    )
-    .bind(vm_id)
-    .fetch_optional(&st.db)
-    .await
-    .ok()
-    .flatten();
-    let Some((volume_id, backend_id, locator)) = vol_row else {
-        let _ = host_repo
-            .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
-            .await;
-        bail!(
-            "live migration requires shared-storage volume; this VM uses local overlay. \
-             Use snapshot+restore to a target host instead."
-        );
-    };
-    let backend = st
-        .registry
-        .get(backend_id)
-        .ok_or_else(|| anyhow!("backend {backend_id} not in registry"))?;
-    let handle_for_target = nexus_storage::VolumeHandle {
-        volume_id,
-        backend_id: nexus_storage::BackendInstanceId(backend_id),
-        backend_kind: backend.kind(),
-        locator,
-        size_bytes: 0,
-    };
-    backend
-        .activate_volume(&handle_for_target)
+    let mut incoming_requested = false;
+    let migrated = async {
+        // Step 1: tell the target to spawn a paused QEMU listening for the
+        // incoming migration stream. We re-derive the spec from the source
+        // VM's persisted boot_mode + the volume_attachment on shared storage.
+        let boot_mode_json: Option<serde_json::Value> =
+            sqlx::query_scalar(r#"SELECT boot_mode FROM vm WHERE id = $1"#)
+                .bind(vm_id)
+                .fetch_optional(&st.db)
+                .await
+                .context("load saved boot_mode")?
+                .flatten();
+        let Some(boot_mode_json) = boot_mode_json else {
+            let _ = host_repo
+                .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
+                .await;
+            bail!("vm has no persisted boot_mode");
+        };
+        let boot_mode_target: BootMode =
+            serde_json::from_value(boot_mode_json).context("decode persisted boot_mode")?;
+
+        // The target needs to mount the same shared volume on its host.
+        let vol_row = sqlx::query_as::<_, (Uuid, Uuid, String)>(
+            r#"SELECT v.id, v.backend_id, v.path
+               FROM volume v
+               JOIN volume_attachment va ON va.volume_id = v.id
+               WHERE va.vm_id = $1 AND va.drive_id = 'rootfs'
+               ORDER BY va.attached_at DESC LIMIT 1"#,
+        )
+        .bind(vm_id)
+        .fetch_optional(&st.db)
         .await
-        .context("activate shared volume on target")?;
-    let target_attached =
-        crate::features::storage::agent_rpc::agent_attach(&target_host.addr, &handle_for_target)
+        .ok()
+        .flatten();
+        let Some((volume_id, backend_id, locator)) = vol_row else {
+            let _ = host_repo
+                .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
+                .await;
+            bail!(
+                "live migration requires shared-storage volume; this VM uses local overlay. \
+                 Use snapshot+restore to a target host instead."
+            );
+        };
+        let backend = st
+            .registry
+            .get(backend_id)
+            .ok_or_else(|| anyhow!("backend {backend_id} not in registry"))?;
+        let handle_for_target = nexus_storage::VolumeHandle {
+            volume_id,
+            backend_id: nexus_storage::BackendInstanceId(backend_id),
+            backend_kind: backend.kind(),
+            locator,
+            size_bytes: 0,
+        };
+        job.run(backend.activate_volume(&handle_for_target))
+            .await
+            .context("activate shared volume on target")?;
+        let target_attached = job
+            .run(crate::features::storage::agent_rpc::agent_attach(
+                &target_host.addr,
+                &handle_for_target,
+            ))
             .await
             .context("attach shared volume on target")?;
-    let target_disk_path = target_attached.path().to_string_lossy().into_owned();
-
-    let http = Client::builder()
-        .timeout(Duration::from_secs(900)) // up to 15 min for big VMs
-        .build()
-        .context("build http client")?;
-    let incoming_body = json!({
-        "vmm_kind": "qemu",
-        "listen_port": target_port,
-        "vcpu": vm.vcpu,
-        "mem_mib": vm.mem_mib,
-        "boot": boot_mode_target,
-        "disks": [{
-            "drive_id": "rootfs",
-            "source": target_disk_path,
-            "read_only": false,
-            "root_device": true,
-            "format": "raw",
-            "cdrom": false,
-        }],
-        // Target NIC uses user-mode by default; bridge-aware NIC setup is
-        // a follow-up. For TAP-based VMs, operator pre-creates the TAP.
-        "nics": [{
-            "iface_id": "net0",
-            "host_dev": "user",
-            "mac": generate_mac(vm_id),
-        }],
-        "enable_balloon": true,
-        "enable_rng": true,
-    });
-    let target_resp = http
-        .post(format!(
-            "{}/agent/v1/vmm/{}/migrate/incoming",
-            target_host.addr, vm_id
-        ))
-        .json(&incoming_body)
-        .send()
-        .await
-        .context("agent migrate/incoming request")?;
-    if !target_resp.status().is_success() {
-        let _ = host_repo
-            .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
-            .await;
-        let status = target_resp.status();
-        let body = target_resp.text().await.unwrap_or_default();
-        bail!("target agent returned {status} on migrate/incoming: {body}");
+        let target_disk_path = target_attached.path().to_string_lossy().into_owned();
+
+        let http = crate::core::agent_http::client();
+        // up to 15 min for big VMs
+        let migrate_timeout = Duration::from_secs(900);
+        let incoming_body = json!({
+            "vmm_kind": "qemu",
+            "listen_port": target_port,
+            "vcpu": vm.vcpu,
+            "mem_mib": vm.mem_mib,
+            "boot": boot_mode_target,
+            "disks": [{
+                "drive_id": "rootfs",
+                "source": target_disk_path,
+                "read_only": false,
+                "root_device": true,
+                "format": "raw",
+                "cdrom": false,
+            }],
+            // Target NIC uses user-mode by default; bridge-aware NIC setup is
+            // a follow-up. For TAP-based VMs, operator pre-creates the TAP.
+            "nics": [{
+                "iface_id": "net0",
+                "host_dev": "user",
+                "mac": generate_mac(vm_id),
+            }],
+            "enable_balloon": true,
+            "enable_rng": true,
+        });
+        incoming_requested = true;
+        let target_resp = job
+            .run(
+                http.post(format!(
+                    "{}/agent/v1/vmm/{}/migrate/incoming",
+                    target_host.addr, vm_id
+                ))
+                .json(&incoming_body)
+                .timeout(migrate_timeout)
+                .send(),
+            )
+            .await
+            .context("agent migrate/incoming request")?;
+        if !target_resp.status().is_success() {
+            let _ = host_repo
+                .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
+                .await;
+            let status = target_resp.status();
+            let body = target_resp.text().await.unwrap_or_default();
+            bail!("target agent returned {status} on migrate/incoming: {body}");
+        }
+        // Brief pause to let -incoming socket bind before the source connects.
+        job.sleep(Duration::from_secs(2)).await?;
+
+        // Step 2: tell the source to drive the QMP migrate.
+        let target_host_ip = target_host
+            .addr
+            .trim_start_matches("http://")
+            .trim_start_matches("https://")
+            .split(':')
+            .next()
+            .unwrap_or("127.0.0.1");
+        let target_uri = format!("tcp:{target_host_ip}:{target_port}");
+        let resp = job
+            .run(
+                http.post(format!(
+                    "{}/agent/v1/vmm/{}/migrate/outgoing",
+                    vm.host_addr, vm.id
+                ))
+                .json(&json!({ "target_uri": target_uri }))
+                .timeout(migrate_timeout)
+                .send(),
+            )
+            .await
+            .context("agent migrate/outgoing request")?;
+        if !resp.status().is_success() {
+            // Release the reservation on failure.
+            let _ = host_repo
+                .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
+                .await;
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            bail!("source agent returned {status} on migrate: {body}");
+        }
+        Ok::<_, anyhow::Error>(())
     }
-    // Brief pause to let -incoming socket bind before the source connects.
-    tokio::time::sleep(Duration::from_secs(2)).await;
-
-    // Step 2: tell the source to drive the QMP migrate.
-    let target_host_ip = target_host
-        .addr
-        .trim_start_matches("http://")
-        .trim_start_matches("https://")
-        .split(':')
-        .next()
-        .unwrap_or("127.0.0.1");
-    let target_uri = format!("tcp:{target_host_ip}:{target_port}");
-    let resp = http
-        .post(format!(
-            "{}/agent/v1/vmm/{}/migrate/outgoing",
-            vm.host_addr, vm.id
-        ))
-        .json(&json!({ "target_uri": target_uri }))
-        .send()
-        .await
-        .context("agent migrate/outgoing request")?;
-    if !resp.status().is_success() {
-        // Release the reservation on failure.
-        let _ = host_repo
-            .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
-            .await;
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        bail!("source agent returned {status} on migrate: {body}");
+    .await;
+    if let Err(err) = migrated {
+        if crate::features::jobs::is_cancelled(&err) {
+            info!(vm_id = %vm_id, job_id = %job.id, "migration cancelled, rolling back");
+            if incoming_requested {
+                destroy_incoming(&target_host.addr, vm_id).await;
+            }
+            let _ = host_repo
+                .release_reservation(target_host_id, vm.vcpu, vm.mem_mib as i64)
+                .await;
+        }
+        return Err(err);
     }
     // Release the source's reservation; the source VM is gone.
     let _ = host_repo
@@ -1064,6 +1128,22 @@ pub async fn live_migrate(
     Ok(())
 }
 
+/// Kill the paused QEMU a cancelled migration left on the target. Its
+/// listener going away fails the source's QMP migrate, so the guest keeps
+/// running on the source.
+async fn destroy_incoming(target_addr: &str, vm_id: Uuid) {
+    let res = crate::core::agent_http::client()
+        .post(format!(
+            "{target_addr}/agent/v1/vmm/{vm_id}/destroy?vmm_kind=qemu"
+        ))
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status());
+    if let Err(e) = res {
+        tracing::warn!(vm_id = %vm_id, error = %e, "rollback: failed to destroy incoming QEMU on target");
+    }
+}
+
 /// Pick a healthy host that has the requested VMM kind installed. Returns
 /// the first match — same posture as the FC `first_healthy` selector.
 async fn pick_host(
@@ -1090,13 +1170,15 @@ async fn pick_host(
 }
 
 #[cfg(not(test))]
-async fn create_tap(host_addr: &str, id: Uuid, bridge: &str) -> Result<()> {
-    let http = Client::builder()
+async fn create_tap(host_addr: &str, id: Uuid, tap: &str, bridge: &str) -> Result<()> {
+    crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
+        .json(&json!({
+            "bridge": bridge,
+            "owner_user": serde_json::Value::Null,
+            "tap_name": tap,
+        }))
         .timeout(Duration::from_secs(10))
-        .build()
-        .context("build http client (create_tap)")?;
-    http.post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
-        .json(&json!({"bridge": bridge, "owner_user": serde_json::Value::Null}))
         .send()
         .await
         .context("create_tap request failed")?
@@ -1106,7 +1188,7 @@ async fn create_tap(host_addr: &str, id: Uuid, bridge: &str) -> Result<()> {
 }
 
 #[cfg(test)]
-async fn create_tap(_host_addr: &str, _id: Uuid, _bridge: &str) -> Result<()> {
+async fn create_tap(_host_addr: &str, _id: Uuid, _tap: &str, _bridge: &str) -> Result<()> {
     Ok(())
 }
 
@@ -1189,7 +1271,7 @@ async fn resolve_qemu_disk(
             let backend = st
                 .registry
                 .get(bid)
-                .ok_or_else(|| anyhow!("storage backend {bid} not found"))?;
+                .ok_or_else(|| not_found(format!("storage backend {bid} not found")))?;
             let opts = nexus_storage::CreateOpts {
                 name: format!("vm-{vm_id}-rootfs"),
                 size_bytes,
@@ -1738,6 +1820,7 @@ mod tests {
             tags: vec![],
             rootfs_size_mb: None,
             network_id: None,
+            bridge: None,
             extra_network_ids: vec![],
             port_forwards: vec![],
             backend_id: None,
@@ -1754,6 +1837,16 @@ mod tests {
             data_disks: vec![],
             vfio_devices: vec![],
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            overlay_size_mb: None,
+            cpu_quota: None,
+            io_weight: None,
+            id: None,
         }
     }
 }
@@ -35,15 +35,43 @@ pub struct VmRow {
     pub vnc_listen: Option<String>,
     #[sqlx(default)]
     pub cpu_type: Option<String>,
+    /// Extra kernel command-line parameters requested at create time.
+    #[sqlx(default)]
+    pub boot_args: Option<String>,
+    /// Firecracker machine-config chosen at create time.
+    #[sqlx(default)]
+    pub smt: Option<bool>,
+    #[sqlx(default)]
+    pub huge_pages: Option<String>,
+    /// Rootfs drive rate limiter chosen at create time (or by the template).
+    #[sqlx(default)]
+    pub rootfs_rate_limiter: Option<serde_json::Value>,
+    /// Entropy device rate limiter chosen at create time (or by the template).
+    #[sqlx(default)]
+    pub entropy_rate_limiter: Option<serde_json::Value>,
+    /// `shared` when the rootfs is a read-only golden image; `None` is a copy.
+    #[sqlx(default)]
+    pub rootfs_mode: Option<String>,
+    /// Scope `CPUQuota=` (percent of one CPU) and `IOWeight=`.
+    #[sqlx(default)]
+    pub cpu_quota: Option<i32>,
+    #[sqlx(default)]
+    pub io_weight: Option<i32>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// When `state` last changed; `updated_at` also moves on renames and
+    /// other edits.
+    pub state_changed_at: chrono::DateTime<chrono::Utc>,
+    /// When the VMM was last booted; uptime counts from here.
+    #[sqlx(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[cfg(not(test))]
 pub async fn insert(db: &PgPool, row: &VmRow) -> sqlx::Result<()> {
     sqlx::query(
-        r#"INSERT INTO vm (id,name,state,host_id,template_id,api_sock,tap,log_path,http_port,fc_unit,vcpu,mem_mib,kernel_path,rootfs_path,source_snapshot_id,tags,created_by_user_id)
-           VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17)"#,
+        r#"INSERT INTO vm (id,name,state,host_id,template_id,api_sock,tap,log_path,http_port,fc_unit,vcpu,mem_mib,kernel_path,rootfs_path,source_snapshot_id,tags,created_by_user_id,boot_args,smt,huge_pages,rootfs_rate_limiter,entropy_rate_limiter,rootfs_mode,cpu_quota,io_weight,started_at)
+           VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18,$19,$20,$21,$22,$23,$24,$25,$26)"#,
     )
     .bind(row.id)
     .bind(&row.name)
@@ -62,6 +90,15 @@ pub async fn insert(db: &PgPool, row: &VmRow) -> sqlx::Result<()> {
     .bind(row.source_snapshot_id)
     .bind(&row.tags)
     .bind(row.created_by_user_id)
+    .bind(&row.boot_args)
+    .bind(row.smt)
+    .bind(&row.huge_pages)
+    .bind(&row.rootfs_rate_limiter)
+    .bind(&row.entropy_rate_limiter)
+    .bind(&row.rootfs_mode)
+    .bind(row.cpu_quota)
+    .bind(row.io_weight)
+    .bind(row.started_at)
     .execute(db)
     .await?;
     Ok(())
@@ -73,8 +110,13 @@ pub async fn insert(_: &PgPool, row: &VmRow) -> sqlx::Result<()> {
     Ok(())
 }
 
-#[cfg(not(test))]
 pub async fn list(db: &PgPool) -> sqlx::Result<Vec<VmRow>> {
+    list_page(db, None, 0).await
+}
+
+/// One page of VMs, newest first. `limit: None` returns every row.
+#[cfg(not(test))]
+pub async fn list_page(db: &PgPool, limit: Option<i64>, offset: i64) -> sqlx::Result<Vec<VmRow>> {
     sqlx::query_as::<_, VmRow>(
         r#"
         SELECT vm.id,
@@ -101,22 +143,52 @@ pub async fn list(db: &PgPool) -> sqlx::Result<Vec<VmRow>> {
                vm.console_kind,
                vm.vnc_listen,
                vm.cpu_type,
+               vm.boot_args,
+               vm.smt,
+               vm.huge_pages,
+               vm.rootfs_rate_limiter,
+               vm.entropy_rate_limiter,
+               vm.rootfs_mode,
+               vm.cpu_quota,
+               vm.io_weight,
                vm.created_at,
-               vm.updated_at
+               vm.updated_at,
+               vm.state_changed_at,
+               vm.started_at
         FROM vm
         JOIN host ON host.id = vm.host_id
         ORDER BY vm.created_at DESC
+        LIMIT $1
+        OFFSET $2
         "#,
     )
+    .bind(limit)
+    .bind(offset)
     .fetch_all(db)
     .await
 }
 
 #[cfg(test)]
-pub async fn list(_: &PgPool) -> sqlx::Result<Vec<VmRow>> {
+pub async fn list_page(_: &PgPool, limit: Option<i64>, offset: i64) -> sqlx::Result<Vec<VmRow>> {
     let mut rows: Vec<VmRow> = store().lock().unwrap().values().cloned().collect();
     rows.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-    Ok(rows)
+    Ok(rows
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit.map_or(usize::MAX, |l| l as usize))
+        .collect())
+}
+
+#[cfg(not(test))]
+pub async fn count(db: &PgPool) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM vm JOIN host ON host.id = vm.host_id")
+        .fetch_one(db)
+        .await
+}
+
+#[cfg(test)]
+pub async fn count(_: &PgPool) -> sqlx::Result<i64> {
+    Ok(store().lock().unwrap().len() as i64)
 }
 
 #[cfg(not(test))]
@@ -147,8 +219,18 @@ pub async fn list_by_host(db: &PgPool, host_id: Uuid) -> sqlx::Result<Vec<VmRow>
                vm.console_kind,
                vm.vnc_listen,
                vm.cpu_type,
+               vm.boot_args,
+               vm.smt,
+               vm.huge_pages,
+               vm.rootfs_rate_limiter,
+               vm.entropy_rate_limiter,
+               vm.rootfs_mode,
+               vm.cpu_quota,
+               vm.io_weight,
                vm.created_at,
-               vm.updated_at
+               vm.updated_at,
+               vm.state_changed_at,
+               vm.started_at
         FROM vm
         JOIN host ON host.id = vm.host_id
         WHERE vm.host_id = $1
@@ -201,8 +283,18 @@ pub async fn get(db: &PgPool, id: Uuid) -> sqlx::Result<VmRow> {
                vm.console_kind,
                vm.vnc_listen,
                vm.cpu_type,
+               vm.boot_args,
+               vm.smt,
+               vm.huge_pages,
+               vm.rootfs_rate_limiter,
+               vm.entropy_rate_limiter,
+               vm.rootfs_mode,
+               vm.cpu_quota,
+               vm.io_weight,
                vm.created_at,
-               vm.updated_at
+               vm.updated_at,
+               vm.state_changed_at,
+               vm.started_at
         FROM vm
         JOIN host ON host.id = vm.host_id
         WHERE vm.id=$1
@@ -225,7 +317,13 @@ pub async fn get(_: &PgPool, id: Uuid) -> sqlx::Result<VmRow> {
 
 #[cfg(not(test))]
 pub async fn update_state(db: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()> {
-    sqlx::query(r#"UPDATE vm SET state=$2, updated_at=now() WHERE id=$1"#)
+    sqlx::query(
+        r#"UPDATE vm
+           SET state=$2,
+               updated_at=now(),
+               state_changed_at = CASE WHEN state IS DISTINCT FROM $2 THEN now() ELSE state_changed_at END
+           WHERE id=$1"#,
+    )
         .bind(id)
         .bind(state)
         .execute(db)
@@ -233,12 +331,68 @@ pub async fn update_state(db: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()
     Ok(())
 }
 
+/// Record that the VMM was just booted, restarting the uptime count.
+#[cfg(not(test))]
+pub async fn mark_started(db: &PgPool, id: Uuid) -> sqlx::Result<()> {
+    sqlx::query("UPDATE vm SET started_at = now() WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub async fn mark_started(_: &PgPool, id: Uuid) -> sqlx::Result<()> {
+    let mut guard = store().lock().unwrap();
+    let row = guard.get_mut(&id).ok_or(sqlx::Error::RowNotFound)?;
+    row.started_at = Some(chrono::Utc::now());
+    Ok(())
+}
+
+/// Remember the state a VM is being paused from, for [`paused_from`].
+#[cfg(not(test))]
+pub async fn set_paused_from(db: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()> {
+    sqlx::query("UPDATE vm SET paused_from = $2 WHERE id = $1")
+        .bind(id)
+        .bind(state)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub async fn set_paused_from(_: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()> {
+    paused_from_store()
+        .lock()
+        .unwrap()
+        .insert(id, state.to_string());
+    Ok(())
+}
+
+/// The state the VM was in before its last pause, if recorded.
+#[cfg(not(test))]
+pub async fn paused_from(db: &PgPool, id: Uuid) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar("SELECT paused_from FROM vm WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+#[cfg(test)]
+pub async fn paused_from(_: &PgPool, id: Uuid) -> sqlx::Result<Option<String>> {
+    Ok(paused_from_store().lock().unwrap().get(&id).cloned())
+}
+
 #[cfg(test)]
 pub async fn update_state(_: &PgPool, id: Uuid, state: &str) -> sqlx::Result<()> {
     let mut guard = store().lock().unwrap();
     let row = guard.get_mut(&id).ok_or(sqlx::Error::RowNotFound)?;
+    let now = chrono::Utc::now();
+    if row.state != state {
+        row.state_changed_at = now;
+    }
     row.state = state.to_string();
-    row.updated_at = chrono::Utc::now();
+    row.updated_at = now;
     Ok(())
 }
 
@@ -279,10 +433,70 @@ pub async fn insert_event(_: &PgPool, vm_id: Uuid, level: &str, message: &str) -
         vm_id,
         level: level.to_string(),
         message: message.to_string(),
+        at: chrono::Utc::now(),
     });
     Ok(())
 }
 
+/// List a VM's events newest-first.
+#[cfg(not(test))]
+pub async fn list_events(
+    db: &PgPool,
+    vm_id: Uuid,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    limit: i64,
+    offset: i64,
+) -> sqlx::Result<Vec<nexus_types::VmEvent>> {
+    let rows: Vec<(i64, String, String, chrono::DateTime<chrono::Utc>)> = sqlx::query_as(
+        r#"
+        SELECT id, level, message, at FROM vm_event
+        WHERE vm_id = $1 AND ($2::timestamptz IS NULL OR at >= $2)
+        ORDER BY at DESC, id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(vm_id)
+    .bind(since)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, kind, message, created_at)| nexus_types::VmEvent {
+            id,
+            kind,
+            message,
+            created_at,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub async fn list_events(
+    _: &PgPool,
+    vm_id: Uuid,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    limit: i64,
+    offset: i64,
+) -> sqlx::Result<Vec<nexus_types::VmEvent>> {
+    let events = events_store().lock().unwrap();
+    Ok(events
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, e)| e.vm_id == vm_id && since.is_none_or(|since| e.at >= since))
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|(i, e)| nexus_types::VmEvent {
+            id: i as i64 + 1,
+            kind: e.level.clone(),
+            message: e.message.clone(),
+            created_at: e.at,
+        })
+        .collect())
+}
+
 pub async fn update_guest_ip(db: &PgPool, vm_id: Uuid, guest_ip: Option<&str>) -> sqlx::Result<()> {
     sqlx::query("UPDATE vm SET guest_ip = $1, updated_at = NOW() WHERE id = $2")
         .bind(guest_ip)
@@ -299,20 +513,14 @@ pub async fn update_metadata(
     name: Option<&str>,
     tags: Option<&[String]>,
 ) -> sqlx::Result<()> {
-    if let Some(name) = name {
-        sqlx::query("UPDATE vm SET name = $2, updated_at = NOW() WHERE id = $1")
-            .bind(id)
-            .bind(name)
-            .execute(db)
-            .await?;
-    }
-    if let Some(tags) = tags {
-        sqlx::query("UPDATE vm SET tags = $2, updated_at = NOW() WHERE id = $1")
-            .bind(id)
-            .bind(tags)
-            .execute(db)
-            .await?;
-    }
+    sqlx::query(
+        "UPDATE vm SET name = COALESCE($2, name), tags = COALESCE($3, tags), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(name)
+    .bind(tags)
+    .execute(db)
+    .await?;
     Ok(())
 }
 
@@ -335,6 +543,84 @@ pub async fn update_metadata(
     Ok(())
 }
 
+/// Returns which of `names` are already used as a VM tap or NIC host device.
+#[cfg(not(test))]
+pub async fn tap_names_in_use(
+    db: &PgPool,
+    names: &[String],
+) -> sqlx::Result<std::collections::HashSet<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT tap FROM vm WHERE tap = ANY($1)
+        UNION
+        SELECT host_dev_name FROM vm_network_interface WHERE host_dev_name = ANY($1)
+        "#,
+    )
+    .bind(names)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(|(name,)| name).collect())
+}
+
+#[cfg(test)]
+pub async fn tap_names_in_use(
+    _: &PgPool,
+    names: &[String],
+) -> sqlx::Result<std::collections::HashSet<String>> {
+    let vms = store().lock().unwrap();
+    let nics = nic_store().lock().unwrap();
+    Ok(names
+        .iter()
+        .filter(|name| {
+            vms.values().any(|vm| &vm.tap == *name)
+                || nics.values().any(|nic| &nic.host_dev_name == *name)
+        })
+        .cloned()
+        .collect())
+}
+
+/// Every tap the VMs placed on `host_id` own: their primary taps and their
+/// NICs' host devices.
+#[cfg(not(test))]
+pub async fn tap_names_on_host(
+    db: &PgPool,
+    host_id: Uuid,
+) -> sqlx::Result<std::collections::HashSet<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT tap FROM vm WHERE host_id = $1
+        UNION
+        SELECT n.host_dev_name FROM vm_network_interface n
+        JOIN vm ON vm.id = n.vm_id
+        WHERE vm.host_id = $1
+        "#,
+    )
+    .bind(host_id)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(|(name,)| name).collect())
+}
+
+#[cfg(test)]
+pub async fn tap_names_on_host(
+    _: &PgPool,
+    host_id: Uuid,
+) -> sqlx::Result<std::collections::HashSet<String>> {
+    let vms = store().lock().unwrap();
+    let nics = nic_store().lock().unwrap();
+    let on_host = |vm_id: &Uuid| vms.get(vm_id).is_some_and(|vm| vm.host_id == host_id);
+    Ok(vms
+        .values()
+        .filter(|vm| vm.host_id == host_id)
+        .map(|vm| vm.tap.clone())
+        .chain(
+            nics.values()
+                .filter(|nic| on_host(&nic.vm_id))
+                .map(|nic| nic.host_dev_name.clone()),
+        )
+        .collect())
+}
+
 #[derive(Clone, Serialize, sqlx::FromRow)]
 pub struct VmDrive {
     pub id: Uuid,
@@ -414,6 +700,12 @@ fn store() -> &'static Mutex<HashMap<Uuid, VmRow>> {
     STORE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+#[cfg(test)]
+fn paused_from_store() -> &'static Mutex<HashMap<Uuid, String>> {
+    static STORE: OnceLock<Mutex<HashMap<Uuid, String>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
 #[cfg(test)]
 fn drive_store() -> &'static Mutex<HashMap<Uuid, VmDrive>> {
     static STORE: OnceLock<Mutex<HashMap<Uuid, VmDrive>>> = OnceLock::new();
@@ -756,6 +1048,39 @@ pub mod nics {
         }
     }
 
+    /// Returns true if another NIC attached to `network_id` already uses `mac`.
+    /// Comparison is case-insensitive since callers may store either case.
+    #[allow(unused_variables)]
+    pub async fn mac_in_use(db: &PgPool, network_id: Uuid, mac: &str) -> sqlx::Result<bool> {
+        #[cfg(not(test))]
+        {
+            sqlx::query_scalar::<_, bool>(
+                r#"
+                SELECT EXISTS (
+                    SELECT 1
+                    FROM vm_network_interface
+                    WHERE network_id = $1
+                      AND lower(guest_mac) = lower($2)
+                )
+                "#,
+            )
+            .bind(network_id)
+            .bind(mac)
+            .fetch_one(db)
+            .await
+        }
+        #[cfg(test)]
+        {
+            let store = nic_store().lock().unwrap();
+            Ok(store.values().any(|n| {
+                n.network_id == Some(network_id)
+                    && n.guest_mac
+                        .as_deref()
+                        .is_some_and(|m| m.eq_ignore_ascii_case(mac))
+            }))
+        }
+    }
+
     #[allow(unused_variables)]
     pub async fn delete(db: &PgPool, id: Uuid) -> sqlx::Result<()> {
         #[cfg(not(test))]
@@ -780,6 +1105,121 @@ pub mod nics {
     }
 }
 
+#[derive(Clone, Serialize, sqlx::FromRow)]
+pub struct VmMmdsRow {
+    pub vm_id: Uuid,
+    pub config: Option<serde_json::Value>,
+    pub data: Option<serde_json::Value>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(test)]
+fn mmds_store() -> &'static Mutex<HashMap<Uuid, VmMmdsRow>> {
+    static STORE: OnceLock<Mutex<HashMap<Uuid, VmMmdsRow>>> = OnceLock::new();
+    STORE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub mod mmds {
+    #[cfg(test)]
+    use super::mmds_store;
+    use super::{PgPool, Uuid, VmMmdsRow};
+
+    #[allow(unused_variables)]
+    pub async fn get(db: &PgPool, vm_id: Uuid) -> sqlx::Result<Option<VmMmdsRow>> {
+        #[cfg(not(test))]
+        {
+            sqlx::query_as::<_, VmMmdsRow>(
+                r#"
+                SELECT vm_id, config, data, updated_at
+                FROM vm_mmds
+                WHERE vm_id = $1
+                "#,
+            )
+            .bind(vm_id)
+            .fetch_optional(db)
+            .await
+        }
+        #[cfg(test)]
+        {
+            Ok(mmds_store().lock().unwrap().get(&vm_id).cloned())
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub async fn upsert_config(
+        db: &PgPool,
+        vm_id: Uuid,
+        config: &serde_json::Value,
+    ) -> sqlx::Result<()> {
+        #[cfg(not(test))]
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO vm_mmds (vm_id, config)
+                VALUES ($1, $2)
+                ON CONFLICT (vm_id) DO UPDATE
+                SET config = EXCLUDED.config, updated_at = now()
+                "#,
+            )
+            .bind(vm_id)
+            .bind(config)
+            .execute(db)
+            .await?;
+            Ok(())
+        }
+        #[cfg(test)]
+        {
+            let mut store = mmds_store().lock().unwrap();
+            let row = store.entry(vm_id).or_insert_with(|| empty_row(vm_id));
+            row.config = Some(config.clone());
+            row.updated_at = chrono::Utc::now();
+            Ok(())
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub async fn upsert_data(
+        db: &PgPool,
+        vm_id: Uuid,
+        data: &serde_json::Value,
+    ) -> sqlx::Result<()> {
+        #[cfg(not(test))]
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO vm_mmds (vm_id, data)
+                VALUES ($1, $2)
+                ON CONFLICT (vm_id) DO UPDATE
+                SET data = EXCLUDED.data, updated_at = now()
+                "#,
+            )
+            .bind(vm_id)
+            .bind(data)
+            .execute(db)
+            .await?;
+            Ok(())
+        }
+        #[cfg(test)]
+        {
+            let mut store = mmds_store().lock().unwrap();
+            let row = store.entry(vm_id).or_insert_with(|| empty_row(vm_id));
+            row.data = Some(data.clone());
+            row.updated_at = chrono::Utc::now();
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    fn empty_row(vm_id: Uuid) -> VmMmdsRow {
+        VmMmdsRow {
+            vm_id,
+            config: None,
+            data: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+}
+
 #[cfg(test)]
 #[allow(dead_code)]
 pub fn reset_store() {
@@ -794,6 +1234,7 @@ pub struct TestVmEvent {
     pub vm_id: Uuid,
     pub level: String,
     pub message: String,
+    pub at: chrono::DateTime<chrono::Utc>,
 }
 
 #[cfg(test)]
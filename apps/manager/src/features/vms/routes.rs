@@ -1,23 +1,33 @@
+use super::idempotency;
+use super::ws_session::{SessionClock, SessionKind, SessionLimits};
+use crate::core::error::{ApiError, ErrorCode};
+use crate::core::pagination::{page_limit, page_offset};
+use crate::features::users::audit::Actor;
+use crate::features::users::authz::can_modify_resource;
 use crate::features::users::repo::AuthenticatedUser;
 use crate::AppState;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        Path, WebSocketUpgrade,
+        Path, Query, WebSocketUpgrade,
     },
+    http::HeaderMap,
     response::IntoResponse,
     Extension, Json,
 };
 use futures::{SinkExt, StreamExt};
 use nexus_types::{
-    BalloonConfig, BalloonStatsConfig, CpuConfigReq, CreateDriveReq, CreateNicReq, CreateVmReq,
-    CreateVmResponse, EntropyConfigReq, GetVmResponse, ListDrivesResponse, ListNicsResponse,
-    ListVmsResponse, LoggerUpdateReq, MachineConfigPatchReq, MmdsConfigReq, MmdsDataReq,
-    OkResponse, SerialConfigReq, UpdateDriveReq, UpdateNicReq, UpdateVmReq, Vm, VmDrive, VmNic,
-    VmPathParams, VsockConfigReq,
+    AuditAction, BalloonConfig, BalloonPolicy, BalloonStatsConfig, CpuConfigReq, CreateDriveReq,
+    CreateNicReq, CreateVmReq, CreateVmResponse, EntropyConfigReq, GetVmResponse,
+    GuestProcessesParams, JobKind, JobStatus, ListDrivesResponse, ListGuestProcessesResponse,
+    ListNicsResponse, ListVmEventsParams, ListVmEventsResponse, ListVmsParams, ListVmsResponse,
+    LoggerUpdateReq, MachineConfigPatchReq, MmdsConfigReq, MmdsDataReq, OkResponse,
+    SerialConfigReq, StopVmParams, UpdateDriveReq, UpdateNicReq, UpdateVmReq, ValidateVmResponse,
+    Vm, VmConsoleParams, VmConsoleResponse, VmDrive, VmMmds, VmNic, VmPathParams, VsockConfigReq,
 };
 use reqwest::StatusCode;
 use serde::Serialize;
+use tokio::sync::broadcast;
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
 use uuid::Uuid;
 
@@ -42,18 +52,13 @@ fn extract_user_info(user: Option<Extension<AuthenticatedUser>>) -> (Option<Uuid
 pub async fn get_shell_credentials(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
-) -> Result<Json<VmShellCredentialResponse>, StatusCode> {
-    match st
-        .shell_repo
-        .get_credentials(id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    {
+) -> Result<Json<VmShellCredentialResponse>, ApiError> {
+    match st.shell_repo.get_credentials(id).await? {
         Some(cred) => Ok(Json(VmShellCredentialResponse {
             username: cred.username,
             password: cred.password,
         })),
-        None => Err(StatusCode::NOT_FOUND),
+        None => Err(ApiError::not_found("shell credentials not found")),
     }
 }
 
@@ -63,6 +68,44 @@ pub struct VmShellCredentialResponse {
     pub password: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/shell/rotate",
+    params(VmPathParams),
+    responses(
+        (status = 200, description = "New shell credentials, returned only once", body = VmShellCredentialResponse),
+        (status = 400, description = "VM is not running or stopped"),
+        (status = 403, description = "Caller is neither an admin nor the VM owner"),
+        (status = 404, description = "VM not found"),
+        (status = 500, description = "Failed to apply the new password in the guest"),
+    ),
+    tag = "VMs"
+)]
+pub async fn rotate_shell_credentials(
+    Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+) -> Result<Json<VmShellCredentialResponse>, ApiError> {
+    let Some(Extension(user)) = user else {
+        return Err(ApiError::forbidden("authentication required"));
+    };
+    let vm = super::repo::get(&st.db, id).await?;
+    if !can_modify_resource(user.role, vm.created_by_user_id, user.id) {
+        return Err(ApiError::forbidden(
+            "only an admin or the VM owner can rotate shell credentials",
+        ));
+    }
+
+    let result = super::service::rotate_shell_credentials(&st, id).await;
+    let details = serde_json::json!({"event": "shell_credentials_rotated"});
+    actor
+        .record_with(AuditAction::UpdateVm, ("vm", id), Some(details), &result)
+        .await;
+    let (username, password) = result?;
+    Ok(Json(VmShellCredentialResponse { username, password }))
+}
+
 #[utoipa::path(
     get,
     path = "/v1/vms/{id}/shell/ws",
@@ -70,6 +113,7 @@ pub struct VmShellCredentialResponse {
     responses(
         (status = 101, description = "WebSocket connection established"),
         (status = 404, description = "VM not found"),
+        (status = 429, description = "Too many websocket sessions for this VM or user"),
         (status = 502, description = "Failed to connect to agent"),
     ),
     tag = "VMs"
@@ -77,6 +121,7 @@ pub struct VmShellCredentialResponse {
 pub async fn shell_websocket(
     ws: WebSocketUpgrade,
     Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> axum::response::Response {
     // Fetch VM to get host address
@@ -87,14 +132,97 @@ pub async fn shell_websocket(
         }
     };
 
-    // Upgrade the WebSocket connection
+    let (user_id, _) = extract_user_info(user);
+    let slot = match st.ws_sessions.try_acquire(SessionKind::Shell, id, user_id) {
+        Ok(slot) => slot,
+        Err(e) => return (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+    };
+
+    // Upgrade the WebSocket connection; the slot is released when the proxy
+    // returns, error or not
     ws.on_upgrade(move |socket| async move {
+        let _slot = slot;
         if let Err(e) = proxy_to_agent_shell(vm.host_addr, id, socket).await {
             tracing::error!("WebSocket proxy error: {:?}", e);
         }
     })
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/create/ws",
+    params(VmPathParams),
+    responses(
+        (status = 101, description = "WebSocket streaming VmCreateProgressEvent JSON frames"),
+        (status = 401, description = "Not signed in"),
+    ),
+    tag = "VMs"
+)]
+pub async fn create_progress_websocket(
+    ws: WebSocketUpgrade,
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+) -> axum::response::Response {
+    // No VM lookup: the row only exists once the create is nearly done, and
+    // clients are expected to connect before POSTing with the same id
+    ws.on_upgrade(move |socket| stream_create_progress(st, id, socket))
+}
+
+/// Replay the create log so far, then push new events until the terminal
+/// `done` event, the client leaves, or the session goes idle.
+async fn stream_create_progress(st: AppState, vm_id: Uuid, mut socket: WebSocket) {
+    let tracker = &st.create_progress;
+    // Subscribe before reading the log so nothing lands between the two
+    let mut updates = tracker.subscribe();
+    let mut pending = tracker.log_since(vm_id, 0);
+    let mut last_seq = 0;
+    let mut clock = SessionClock::start(SessionLimits::from_env());
+
+    loop {
+        for event in pending.drain(..) {
+            if event.seq <= last_seq {
+                continue;
+            }
+            last_seq = event.seq;
+            let Ok(text) = serde_json::to_string(&event) else {
+                continue;
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+            clock.touch();
+            if event.is_finished() {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+        }
+
+        tokio::select! {
+            update = updates.recv() => {
+                pending = match update {
+                    Ok(event) if event.vm_id == vm_id => vec![event],
+                    Ok(_) => Vec::new(),
+                    // Missed some updates; the stored log still has them
+                    Err(broadcast::error::RecvError::Lagged(_)) => tracker.log_since(vm_id, last_seq),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+            }
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => clock.touch(),
+            },
+            _ = tokio::time::sleep_until(clock.deadline()) => {
+                if let Some(expiry) = clock.expired_at(tokio::time::Instant::now()) {
+                    let _ = socket.send(Message::Text(clock.close_message(expiry))).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    let _ = socket.send(Message::Close(None)).await;
+}
+
 /// Browser ↔ noVNC bridge. Mirrors `shell_websocket` but targets the
 /// agent's VNC WS endpoint instead of the shell. Used by the UI's
 /// in-browser noVNC client for graphical install / Windows access.
@@ -213,71 +341,188 @@ async fn proxy_to_agent_shell(
     let (mut agent_write, mut agent_read) = agent_stream.split();
     let (mut client_write, mut client_read) = client_ws.split();
 
-    // Proxy messages bidirectionally
-    let client_to_agent = async {
-        while let Some(msg) = client_read.next().await {
-            match msg {
-                Ok(Message::Text(text)) => {
-                    if agent_write.send(WsMessage::Text(text)).await.is_err() {
-                        break;
-                    }
-                }
-                Ok(Message::Binary(data)) => {
-                    if agent_write.send(WsMessage::Binary(data)).await.is_err() {
-                        break;
-                    }
+    // Proxy messages bidirectionally until either side closes or the session
+    // outlives its idle / absolute limits
+    let mut clock = SessionClock::start(SessionLimits::from_env());
+    let expired = loop {
+        tokio::select! {
+            msg = client_read.next() => {
+                clock.touch();
+                let forward = match msg {
+                    Some(Ok(Message::Text(text))) => WsMessage::Text(text),
+                    Some(Ok(Message::Binary(data))) => WsMessage::Binary(data),
+                    Some(Ok(Message::Ping(data))) => WsMessage::Ping(data),
+                    Some(Ok(Message::Pong(data))) => WsMessage::Pong(data),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break None,
+                };
+                if agent_write.send(forward).await.is_err() {
+                    break None;
                 }
-                Ok(Message::Ping(data)) => {
-                    if agent_write.send(WsMessage::Ping(data)).await.is_err() {
-                        break;
-                    }
+            }
+            msg = agent_read.next() => {
+                clock.touch();
+                let forward = match msg {
+                    Some(Ok(WsMessage::Text(text))) => Message::Text(text),
+                    Some(Ok(WsMessage::Binary(data))) => Message::Binary(data),
+                    Some(Ok(WsMessage::Ping(data))) => Message::Ping(data),
+                    Some(Ok(WsMessage::Pong(data))) => Message::Pong(data),
+                    Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break None,
+                    Some(Ok(_)) => continue,
+                };
+                if client_write.send(forward).await.is_err() {
+                    break None;
                 }
-                Ok(Message::Pong(data)) => {
-                    if agent_write.send(WsMessage::Pong(data)).await.is_err() {
-                        break;
-                    }
+            }
+            _ = tokio::time::sleep_until(clock.deadline()) => {
+                if let Some(expiry) = clock.expired_at(tokio::time::Instant::now()) {
+                    break Some(expiry);
                 }
-                Ok(Message::Close(_)) => break,
-                Err(_) => break,
             }
         }
     };
 
-    let agent_to_client = async {
-        while let Some(msg) = agent_read.next().await {
-            match msg {
-                Ok(WsMessage::Text(text)) => {
-                    if client_write.send(Message::Text(text)).await.is_err() {
+    if let Some(expiry) = expired {
+        let reason = clock.close_message(expiry);
+        tracing::info!(vm_id = %vm_id, reason = %reason, "closing shell WebSocket");
+        let _ = client_write
+            .send(Message::Text(format!("\r\n{reason}\r\n")))
+            .await;
+        let _ = client_write.send(Message::Close(None)).await;
+        let _ = agent_write.send(WsMessage::Close(None)).await;
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/console",
+    params(VmPathParams, VmConsoleParams),
+    responses(
+        (status = 200, description = "Tail of the VM's serial console log", body = VmConsoleResponse),
+        (status = 404, description = "VM not found"),
+    ),
+    tag = "VMs"
+)]
+pub async fn get_console(
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+    Query(params): Query<VmConsoleParams>,
+) -> Result<Json<VmConsoleResponse>, ApiError> {
+    Ok(Json(super::console::get(&st, id, &params).await?))
+}
+
+/// Follows the serial console log: the last `tail` lines first, then
+/// whatever the guest writes, as text frames. Waits quietly while the log
+/// doesn't exist yet.
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/console/ws",
+    params(VmPathParams, VmConsoleParams),
+    responses(
+        (status = 101, description = "WebSocket connection established"),
+        (status = 404, description = "VM not found"),
+        (status = 429, description = "Too many websocket sessions for this VM or user"),
+    ),
+    tag = "VMs"
+)]
+pub async fn console_websocket(
+    ws: WebSocketUpgrade,
+    Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+    Query(params): Query<VmConsoleParams>,
+) -> axum::response::Response {
+    if super::repo::get(&st.db, id).await.is_err() {
+        return (StatusCode::NOT_FOUND, "VM not found").into_response();
+    }
+    let path = match super::console::log_path(&st, id) {
+        Ok(path) => path,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    let (user_id, _) = extract_user_info(user);
+    let slot = match st
+        .ws_sessions
+        .try_acquire(SessionKind::Console, id, user_id)
+    {
+        Ok(slot) => slot,
+        Err(e) => return (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+    };
+    let lines = super::console::tail_lines(&params);
+
+    ws.on_upgrade(move |socket| async move {
+        let _slot = slot;
+        if let Err(e) = stream_console(id, path, lines, socket).await {
+            tracing::error!(vm_id = %id, "Console WebSocket error: {:?}", e);
+        }
+    })
+}
+
+const CONSOLE_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+async fn stream_console(
+    vm_id: Uuid,
+    path: std::path::PathBuf,
+    lines: usize,
+    ws: WebSocket,
+) -> anyhow::Result<()> {
+    use tokio::time::{interval, Instant};
+
+    let (mut sender, mut receiver) = ws.split();
+    let offset = match super::console::tail(&path, lines).await? {
+        Some((text, len)) => {
+            if !text.is_empty() {
+                sender.send(Message::Text(text)).await?;
+            }
+            len
+        }
+        None => 0,
+    };
+    let mut follower = super::console::Follower::new(path, offset);
+    let mut ticker = interval(CONSOLE_POLL);
+    let mut clock = SessionClock::start(SessionLimits::from_env());
+
+    loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                clock.touch();
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => {
+                        tracing::info!(vm_id = %vm_id, "Console WebSocket client disconnected");
                         break;
                     }
-                }
-                Ok(WsMessage::Binary(data)) => {
-                    if client_write.send(Message::Binary(data)).await.is_err() {
-                        break;
+                    Some(Ok(Message::Ping(data))) => {
+                        if sender.send(Message::Pong(data)).await.is_err() {
+                            break;
+                        }
                     }
+                    _ => {}
                 }
-                Ok(WsMessage::Ping(data)) => {
-                    if client_write.send(Message::Ping(data)).await.is_err() {
-                        break;
-                    }
+            }
+
+            _ = tokio::time::sleep_until(clock.deadline()) => {
+                if let Some(expiry) = clock.expired_at(Instant::now()) {
+                    let reason = clock.close_message(expiry);
+                    tracing::info!(vm_id = %vm_id, reason = %reason, "closing console WebSocket");
+                    let _ = sender.send(Message::Text(reason)).await;
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
                 }
-                Ok(WsMessage::Pong(data)) => {
-                    if client_write.send(Message::Pong(data)).await.is_err() {
-                        break;
-                    }
+            }
+
+            _ = ticker.tick() => {
+                // Console output doesn't count as activity: a chatty guest
+                // would otherwise hold an abandoned session open
+                let text = follower.poll().await?;
+                if text.is_empty() {
+                    continue;
+                }
+                if sender.send(Message::Text(text)).await.is_err() {
+                    break;
                 }
-                Ok(WsMessage::Close(_)) => break,
-                Err(_) => break,
-                _ => {}
             }
         }
-    };
-
-    tokio::select! {
-        _ = client_to_agent => {},
-        _ = agent_to_client => {},
     }
-
     Ok(())
 }
 
@@ -289,12 +534,14 @@ async fn proxy_to_agent_shell(
         (status = 101, description = "WebSocket connection established"),
         (status = 404, description = "VM not found"),
         (status = 400, description = "VM not running"),
+        (status = 429, description = "Too many websocket sessions for this VM or user"),
     ),
     tag = "VMs"
 )]
 pub async fn metrics_websocket(
     ws: WebSocketUpgrade,
     Extension(st): Extension<AppState>,
+    user: Option<Extension<AuthenticatedUser>>,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> axum::response::Response {
     // Fetch VM to check if it's running
@@ -305,7 +552,7 @@ pub async fn metrics_websocket(
         }
     };
 
-    if vm.state != "running" {
+    if !super::service::is_running_state(&vm.state) {
         return (
             StatusCode::BAD_REQUEST,
             "VM must be running to stream metrics",
@@ -313,14 +560,64 @@ pub async fn metrics_websocket(
             .into_response();
     }
 
+    // Firecracker has a single metrics FIFO per VM; a second reader would
+    // steal lines from the first
+    let Some(session) = MetricsSession::acquire(id) else {
+        return (
+            StatusCode::CONFLICT,
+            "Metrics are already being streamed for this VM",
+        )
+            .into_response();
+    };
+
+    let (user_id, _) = extract_user_info(user);
+    let slot = match st
+        .ws_sessions
+        .try_acquire(SessionKind::Metrics, id, user_id)
+    {
+        Ok(slot) => slot,
+        Err(e) => return (StatusCode::TOO_MANY_REQUESTS, e.to_string()).into_response(),
+    };
+
     // Upgrade the WebSocket connection
     ws.on_upgrade(move |socket| async move {
+        let _session = session;
+        let _slot = slot;
         if let Err(e) = stream_metrics(st, id, socket).await {
             tracing::error!(vm_id = %id, "Metrics WebSocket error: {:?}", e);
         }
     })
 }
 
+/// VMs with a metrics websocket open, so only one session reads each FIFO.
+static METRICS_SESSIONS: std::sync::LazyLock<std::sync::Mutex<std::collections::HashSet<Uuid>>> =
+    std::sync::LazyLock::new(Default::default);
+
+/// Claim on a VM's metrics FIFO, released when dropped.
+struct MetricsSession(Uuid);
+
+impl MetricsSession {
+    fn acquire(vm_id: Uuid) -> Option<Self> {
+        // Build the guard lazily: dropping a rejected one would release the
+        // session that is already streaming (and deadlock on this lock)
+        let inserted = METRICS_SESSIONS.lock().unwrap().insert(vm_id);
+        inserted.then(|| Self(vm_id))
+    }
+}
+
+impl Drop for MetricsSession {
+    fn drop(&mut self) {
+        METRICS_SESSIONS.lock().unwrap().remove(&self.0);
+    }
+}
+
+const FIFO_REOPEN_MIN: std::time::Duration = std::time::Duration::from_secs(1);
+const FIFO_REOPEN_MAX: std::time::Duration = std::time::Duration::from_secs(30);
+
+fn next_fifo_backoff(current: std::time::Duration) -> std::time::Duration {
+    (current * 2).min(FIFO_REOPEN_MAX)
+}
+
 async fn stream_metrics(
     st: AppState,
     vm_id: Uuid,
@@ -328,7 +625,7 @@ async fn stream_metrics(
 ) -> Result<(), Box<dyn std::error::Error>> {
     use tokio::io::{AsyncBufReadExt, BufReader};
     use tokio::net::unix::pipe;
-    use tokio::time::{interval, timeout, Duration};
+    use tokio::time::{interval, timeout, Duration, Instant};
 
     let (mut sender, mut receiver) = ws.split();
     let metrics_path = format!("/srv/fc/vms/{}/logs/metrics.json", vm_id);
@@ -347,11 +644,17 @@ async fn stream_metrics(
             return Ok(());
         }
     };
-    let mut reader = BufReader::new(fifo_rx);
+    // `None` while Firecracker has the write end closed; reopened with backoff
+    // instead of spinning on EOF every tick
+    let mut reader = Some(BufReader::new(fifo_rx));
+    let mut backoff = FIFO_REOPEN_MIN;
+    let mut reopen_at = Instant::now();
+    let mut clock = SessionClock::start(SessionLimits::from_env());
 
     loop {
         tokio::select! {
             msg = receiver.next() => {
+                clock.touch();
                 match msg {
                     Some(Ok(Message::Close(_))) | None => {
                         tracing::info!(vm_id = %vm_id, "Metrics WebSocket client disconnected");
@@ -366,7 +669,33 @@ async fn stream_metrics(
                 }
             }
 
+            _ = tokio::time::sleep_until(clock.deadline()) => {
+                if let Some(expiry) = clock.expired_at(Instant::now()) {
+                    let reason = clock.close_message(expiry);
+                    tracing::info!(vm_id = %vm_id, reason = %reason, "closing metrics WebSocket");
+                    let _ = sender.send(Message::Text(reason)).await;
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+
             _ = ticker.tick() => {
+                if reader.is_none() {
+                    if Instant::now() < reopen_at {
+                        continue;
+                    }
+                    match pipe::OpenOptions::new().open_receiver(&metrics_path) {
+                        Ok(rx) => reader = Some(BufReader::new(rx)),
+                        Err(e) => {
+                            tracing::debug!(vm_id = %vm_id, error = %e, "Failed to reopen metrics FIFO");
+                            reopen_at = Instant::now() + backoff;
+                            backoff = next_fifo_backoff(backoff);
+                            continue;
+                        }
+                    }
+                }
+                let Some(fifo) = reader.as_mut() else { continue };
+
                 // Flush metrics from Firecracker into the FIFO
                 if let Err(e) = super::service::flush_vm_metrics(&st, vm_id).await {
                     tracing::debug!(vm_id = %vm_id, "Failed to flush metrics: {}", e);
@@ -375,8 +704,16 @@ async fn stream_metrics(
 
                 // Read one JSON line with a timeout (Firecracker writes within ms of flush)
                 let mut line = String::new();
-                match timeout(Duration::from_millis(800), reader.read_line(&mut line)).await {
-                    Ok(Ok(n)) if n > 0 => {
+                match timeout(Duration::from_millis(800), fifo.read_line(&mut line)).await {
+                    Ok(Ok(0)) => {
+                        // Writer gone (Firecracker restarted or exited)
+                        tracing::debug!(vm_id = %vm_id, "Metrics FIFO closed by writer, reopening in {:?}", backoff);
+                        reader = None;
+                        reopen_at = Instant::now() + backoff;
+                        backoff = next_fifo_backoff(backoff);
+                    }
+                    Ok(Ok(_)) => {
+                        backoff = FIFO_REOPEN_MIN;
                         if let Ok(fc_metrics) = serde_json::from_str::<serde_json::Value>(&line) {
                             let (cpu_percent, memory_percent) = match super::service::get_process_stats(&st, vm_id).await {
                                 Ok(stats) => (stats.cpu_percent, stats.memory_percent),
@@ -393,6 +730,8 @@ async fn stream_metrics(
                                 memory_percent,
                             );
 
+                            // Pushed samples don't count as activity: only the
+                            // client keeps a metrics session alive
                             if let Ok(json) = serde_json::to_string(&simplified) {
                                 if sender.send(Message::Text(json)).await.is_err() {
                                     break;
@@ -407,8 +746,8 @@ async fn stream_metrics(
                     Ok(Err(e)) => {
                         tracing::debug!(vm_id = %vm_id, "Failed to read metrics FIFO: {}", e);
                     }
-                    _ => {
-                        // Timeout or empty read — skip this tick
+                    Err(_) => {
+                        // Timeout — skip this tick
                     }
                 }
             }
@@ -503,38 +842,177 @@ fn simplify_firecracker_metrics(
     post,
     path = "/v1/vms",
     request_body = CreateVmReq,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key return the VM the first request created"),
+    ),
     responses(
         (status = 200, description = "VM created", body = CreateVmResponse),
+        (status = 400, description = "Invalid VM configuration"),
+        (status = 409, description = "The requested id is already in use, or create was cancelled through /v1/jobs, or source_snapshot_id was taken with another Firecracker release, or the VM would exceed the caller's quota, or the first create with this Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key was already used with a different request body"),
         (status = 500, description = "Failed to create VM"),
     ),
     tag = "VMs"
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
+    headers: HeaderMap,
     Json(req): Json<CreateVmReq>,
 ) -> Result<Json<CreateVmResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    let id = Uuid::new_v4();
-    super::service::create_and_start(&st, id, req, None, user_id, &username)
+    let user_id = actor.user_id;
+    let id = req.id.unwrap_or_else(Uuid::new_v4);
+
+    let claim_err = |status: StatusCode, err: anyhow::Error| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: "Failed to create VM".to_string(),
+                fault_message: Some(err.to_string()),
+            }),
+        )
+    };
+    let idempotency_key = idempotency::key_from_headers(&headers)
+        .map_err(|err| claim_err(StatusCode::BAD_REQUEST, err))?;
+    let scope = idempotency::scope(user_id);
+    if let Some(key) = &idempotency_key {
+        let request_hash = idempotency::fingerprint(&req)
+            .map_err(|err| claim_err(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+        let claim = idempotency::claim(&st.db, &scope, key, id, &request_hash)
+            .await
+            .map_err(|err| claim_err(crate::core::error::status_of(&err), err))?;
+        match claim {
+            idempotency::Claim::New => {}
+            idempotency::Claim::Mismatch => {
+                return Err(claim_err(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    anyhow::anyhow!(
+                        "Idempotency-Key was already used with a different request body"
+                    ),
+                ));
+            }
+            idempotency::Claim::Existing(vm_id) => {
+                let jobs = st.jobs.list(Some(vm_id));
+                let job = idempotency::replayed_job(&jobs);
+                if let Some(job) = job.filter(|job| job.status != JobStatus::Succeeded) {
+                    let what = if job.status.is_finished() {
+                        "failed and is being released; retry"
+                    } else {
+                        "is still in progress; retry once it finishes"
+                    };
+                    return Err(claim_err(
+                        StatusCode::CONFLICT,
+                        anyhow::anyhow!(
+                            "the create with this Idempotency-Key (VM {vm_id}, job {}) {what}",
+                            job.id
+                        ),
+                    ));
+                }
+                tracing::info!(vm_id = %vm_id, key = %key, "replaying idempotent VM create");
+                return Ok(Json(CreateVmResponse {
+                    id: vm_id,
+                    job_id: job.map(|job| job.id),
+                }));
+            }
+        }
+    }
+
+    // A caller-chosen id must not land on a VM that exists or is still
+    // being created (its row only appears near the end of the create)
+    if req.id.is_some() {
+        let taken = id_in_use(&st, id).await;
+        if !matches!(taken, Ok(false)) {
+            if let Some(key) = &idempotency_key {
+                if let Err(err) = idempotency::release(&st.db, &scope, key, id).await {
+                    tracing::warn!(vm_id = %id, error = ?err, "failed to release idempotency key");
+                }
+            }
+            return Err(match taken {
+                Ok(_) => claim_err(
+                    StatusCode::CONFLICT,
+                    anyhow::anyhow!("VM id {id} is already in use"),
+                ),
+                Err(err) => claim_err(StatusCode::INTERNAL_SERVER_ERROR, err),
+            });
+        }
+    }
+
+    let job = st.jobs.start(JobKind::CreateVm, id);
+    let result = super::service::create_and_start_with_job(&st, &job, id, req, None, user_id).await;
+    actor
+        .record(AuditAction::CreateVm, ("vm", id), &result)
+        .await;
+    if let (Err(_), Some(key)) = (&result, &idempotency_key) {
+        if let Err(err) = idempotency::release(&st.db, &scope, key, id).await {
+            tracing::warn!(vm_id = %id, error = ?err, "failed to release idempotency key");
+        }
+    }
+    result.map_err(|err| {
+        tracing::warn!(vm_id = %id, error = ?err, "create VM failed (full chain)");
+        let err = ApiError::from(err);
+        (
+            err.code.status(),
+            Json(ErrorResponse {
+                error: "Failed to create VM".to_string(),
+                fault_message: Some(err.message),
+            }),
+        )
+    })?;
+    Ok(Json(CreateVmResponse {
+        id,
+        job_id: Some(job.id),
+    }))
+}
+
+/// Whether `id` belongs to a VM row or to a create job still in flight.
+async fn id_in_use(st: &AppState, id: Uuid) -> anyhow::Result<bool> {
+    let creating = st
+        .jobs
+        .list(Some(id))
+        .iter()
+        .any(|job| job.kind == JobKind::CreateVm && !job.status.is_finished());
+    if creating {
+        return Ok(true);
+    }
+    match super::repo::get(&st.db, id).await {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::RowNotFound) => Ok(false),
+        Err(err) => Err(anyhow::Error::new(err).context("failed to look up VM id")),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/validate",
+    request_body = CreateVmReq,
+    responses(
+        (status = 200, description = "A create with this request would go ahead", body = ValidateVmResponse),
+        (status = 400, description = "The create would fail; the message says why"),
+        (status = 500, description = "The check itself failed, e.g. the database is unreachable"),
+    ),
+    tag = "VMs"
+)]
+pub async fn validate(
+    Extension(st): Extension<AppState>,
+    Json(req): Json<CreateVmReq>,
+) -> Result<Json<ValidateVmResponse>, ApiError> {
+    super::service::validate_create(&st, &req)
         .await
+        .map(Json)
         .map_err(|err| {
-            tracing::error!(vm_id = %id, error = ?err, "create VM failed (full chain)");
-            let chain: Vec<String> = err.chain().map(|e| e.to_string()).collect();
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Failed to create VM".to_string(),
-                    fault_message: Some(chain.join(" -> ")),
-                }),
-            )
-        })?;
-    Ok(Json(CreateVmResponse { id }))
+            if super::service::is_lookup_failure(&err) {
+                tracing::error!(error = ?err, "VM create dry run failed");
+                ApiError::new(ErrorCode::Internal, format!("{err:#}"))
+            } else {
+                ApiError::new(ErrorCode::BadRequest, format!("{err:#}"))
+            }
+        })
 }
 
 #[utoipa::path(
     get,
     path = "/v1/vms",
+    params(ListVmsParams),
     responses(
         (status = 200, description = "VMs listed", body = ListVmsResponse),
         (status = 500, description = "Failed to list VMs"),
@@ -543,8 +1021,11 @@ pub async fn create(
 )]
 pub async fn list(
     Extension(st): Extension<AppState>,
+    Query(params): Query<ListVmsParams>,
 ) -> Result<Json<ListVmsResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let items = super::repo::list(&st.db).await.map_err(|err| {
+    let limit = page_limit(params.limit);
+    let offset = page_offset(params.offset);
+    let err = |err: sqlx::Error| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -552,9 +1033,13 @@ pub async fn list(
                 fault_message: Some(err.to_string()),
             }),
         )
-    })?;
+    };
+    let items = super::repo::list_page(&st.db, limit, offset)
+        .await
+        .map_err(err)?;
+    let total = super::repo::count(&st.db).await.map_err(err)?;
     let items = items.into_iter().map(Vm::from).collect();
-    Ok(Json(ListVmsResponse { items }))
+    Ok(Json(ListVmsResponse { items, total }))
 }
 
 #[utoipa::path(
@@ -591,6 +1076,30 @@ pub struct ErrorResponse {
     pub fault_message: Option<String>,
 }
 
+/// An [`ErrorResponse`] handler result in the form [`Actor::record`] takes.
+fn error_outcome<T>(result: &Result<T, (StatusCode, Json<ErrorResponse>)>) -> Result<(), String> {
+    match result {
+        Ok(_) => Ok(()),
+        Err((status, Json(err))) => Err(match &err.fault_message {
+            Some(fault) => format!("{status}: {}: {fault}", err.error),
+            None => format!("{status}: {}", err.error),
+        }),
+    }
+}
+
+/// Audits a change to a VM's device or machine configuration as `update_vm`.
+async fn record_config_change<T, E: std::fmt::Display>(
+    actor: &Actor,
+    id: Uuid,
+    change: &str,
+    result: &Result<T, E>,
+) {
+    let details = serde_json::json!({ "change": change });
+    actor
+        .record_with(AuditAction::UpdateVm, ("vm", id), Some(details), result)
+        .await;
+}
+
 #[utoipa::path(
     patch,
     path = "/v1/vms/{id}",
@@ -606,34 +1115,23 @@ pub struct ErrorResponse {
 )]
 pub async fn update(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<UpdateVmReq>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::update_vm_metadata(
-        &st,
-        id,
-        req.name.as_deref(),
-        req.tags.as_deref(),
-        user_id,
-        &username,
-    )
-    .await
-    .map_err(|err| {
-        let err_str = err.to_string();
-        let status = if err_str.contains("not found") {
-            StatusCode::NOT_FOUND
-        } else if err_str.contains("cannot be empty") {
-            StatusCode::BAD_REQUEST
-        } else {
-            StatusCode::INTERNAL_SERVER_ERROR
-        };
+    let result =
+        super::service::update_vm_metadata(&st, id, req.name.as_deref(), req.tags.as_deref()).await;
+    let details = serde_json::json!({"name": req.name, "tags": req.tags});
+    actor
+        .record_with(AuditAction::UpdateVm, ("vm", id), Some(details), &result)
+        .await;
+    result.map_err(|err| {
+        let err = ApiError::from(err);
         (
-            status,
+            err.code.status(),
             Json(ErrorResponse {
                 error: "Failed to update VM".to_string(),
-                fault_message: Some(err_str),
+                fault_message: Some(err.message),
             }),
         )
     })?;
@@ -653,34 +1151,30 @@ pub async fn update(
 )]
 pub async fn start(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::start_vm_by_id_with_user(&st, id, user_id, &username)
-        .await
-        .map_err(|err| {
-            let err_str = err.to_string();
-            let status = if err_str.contains("not found") {
-                StatusCode::NOT_FOUND
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            };
-            (
-                status,
-                Json(ErrorResponse {
-                    error: "Failed to start VM".to_string(),
-                    fault_message: Some(err_str),
-                }),
-            )
-        })?;
+    let result = super::service::start_vm_by_id(&st, id).await;
+    actor
+        .record(AuditAction::StartVm, ("vm", id), &result)
+        .await;
+    result.map_err(|err| {
+        let err = ApiError::from(err);
+        (
+            err.code.status(),
+            Json(ErrorResponse {
+                error: "Failed to start VM".to_string(),
+                fault_message: Some(err.message),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
 #[utoipa::path(
     post,
     path = "/v1/vms/{id}/stop",
-    params(VmPathParams),
+    params(VmPathParams, StopVmParams),
     responses(
         (status = 200, description = "VM stopped", body = OkResponse),
         (status = 500, description = "Failed to stop VM"),
@@ -689,21 +1183,25 @@ pub async fn start(
 )]
 pub async fn stop(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
+    Query(params): Query<StopVmParams>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::stop_only(&st, id, user_id, &username)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Failed to stop VM".to_string(),
-                    fault_message: Some(err.to_string()),
-                }),
-            )
-        })?;
+    let result = if params.graceful.unwrap_or(false) {
+        super::service::stop_graceful(&st, id, params.timeout).await
+    } else {
+        super::service::stop_only(&st, id).await
+    };
+    actor.record(AuditAction::StopVm, ("vm", id), &result).await;
+    result.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Failed to stop VM".to_string(),
+                fault_message: Some(err.to_string()),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -721,27 +1219,28 @@ pub async fn stop(
 )]
 pub async fn pause(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::pause_vm(&st, id, user_id, &username)
-        .await
-        .map_err(|err| {
-            let err_str = err.to_string();
-            let status = if err_str.contains("must be running") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            };
-            (
-                status,
-                Json(ErrorResponse {
-                    error: "Failed to pause VM".to_string(),
-                    fault_message: Some(err_str),
-                }),
-            )
-        })?;
+    let result = super::service::pause_vm(&st, id).await;
+    actor
+        .record(AuditAction::PauseVm, ("vm", id), &result)
+        .await;
+    result.map_err(|err| {
+        let err_str = err.to_string();
+        let status = if err_str.contains("must be running") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: "Failed to pause VM".to_string(),
+                fault_message: Some(err_str),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -759,27 +1258,28 @@ pub async fn pause(
 )]
 pub async fn resume(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::resume_vm(&st, id, user_id, &username)
-        .await
-        .map_err(|err| {
-            let err_str = err.to_string();
-            let status = if err_str.contains("must be paused") {
-                StatusCode::BAD_REQUEST
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
-            };
-            (
-                status,
-                Json(ErrorResponse {
-                    error: "Failed to resume VM".to_string(),
-                    fault_message: Some(err_str),
-                }),
-            )
-        })?;
+    let result = super::service::resume_vm(&st, id).await;
+    actor
+        .record(AuditAction::ResumeVm, ("vm", id), &result)
+        .await;
+    result.map_err(|err| {
+        let err_str = err.to_string();
+        let status = if err_str.contains("must be paused") {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: "Failed to resume VM".to_string(),
+                fault_message: Some(err_str),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -822,8 +1322,30 @@ pub struct BackupVmRequest {
 )]
 pub async fn backup_vm(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<BackupVmRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let details = serde_json::json!({
+        "target_id": req.target_id,
+        "destination_path": req.destination_path,
+    });
+    let result = run_backup(&st, id, req).await;
+    actor
+        .record_with(
+            AuditAction::BackupVm,
+            ("vm", id),
+            Some(details),
+            &error_outcome(&result),
+        )
+        .await;
+    result
+}
+
+async fn run_backup(
+    st: &AppState,
+    id: Uuid,
+    req: BackupVmRequest,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
     let vm = super::repo::get(&st.db, id).await.map_err(|_| {
         (
@@ -848,7 +1370,7 @@ pub async fn backup_vm(
 
     // Path A: volume-backed → use existing chunked-encrypted backup pipeline.
     if let (Some(volume_id), Some(target_id)) = (vol_id, req.target_id) {
-        let backup_id = crate::features::backups::service::create_backup(&st, volume_id, target_id)
+        let backup_id = crate::features::backups::service::create_backup(st, volume_id, target_id)
             .await
             .map_err(|err| {
                 (
@@ -882,19 +1404,7 @@ pub async fn backup_vm(
             }),
         )
     })?;
-    let http = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(1800)) // up to 30 min for large disks
-        .build()
-        .map_err(|err| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "http client".into(),
-                    fault_message: Some(err.to_string()),
-                }),
-            )
-        })?;
-    let resp = http
+    let resp = crate::core::agent_http::client()
         .post(format!(
             "{}/agent/v1/vmm/{}/backup/disk",
             vm.host_addr, vm.id
@@ -906,6 +1416,7 @@ pub async fn backup_vm(
             "format": req.format.clone().unwrap_or_else(|| "qcow2".into()),
             "compress": req.compress,
         }))
+        .timeout(std::time::Duration::from_secs(1800)) // up to 30 min for large disks
         .send()
         .await
         .map_err(|err| {
@@ -951,25 +1462,39 @@ pub struct RescheduleRequest {
     responses(
         (status = 200, description = "VM rescheduled", body = OkResponse),
         (status = 400, description = "Reschedule preconditions not met"),
+        (status = 409, description = "Target host is draining"),
     ),
     tag = "VMs"
 )]
 pub async fn reschedule(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<RescheduleRequest>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    super::qemu_service::reschedule(&st, id, req.target_host_id)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse {
-                    error: "Reschedule failed".to_string(),
-                    fault_message: Some(err.to_string()),
-                }),
-            )
-        })?;
+    let result = super::qemu_service::reschedule(&st, id, req.target_host_id).await;
+    actor
+        .record_with(
+            AuditAction::RescheduleVm,
+            ("vm", id),
+            Some(serde_json::json!({"target_host_id": req.target_host_id})),
+            &result,
+        )
+        .await;
+    result.map_err(|err| {
+        let status = if super::qemu_service::is_target_draining(&err) {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: "Reschedule failed".to_string(),
+                fault_message: Some(err.to_string()),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -984,35 +1509,63 @@ pub struct MigrateRequest {
     pub target_port: u16,
 }
 
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MigrateResponse {
+    /// The migration's entry in `/v1/jobs`.
+    pub job_id: Uuid,
+}
+
 #[utoipa::path(
     post,
     path = "/v1/vms/{id}/migrate",
     params(VmPathParams),
     request_body = MigrateRequest,
     responses(
-        (status = 200, description = "Migration succeeded", body = OkResponse),
+        (status = 200, description = "Migration succeeded", body = MigrateResponse),
         (status = 400, description = "Invalid migration target"),
+        (status = 409, description = "Migration was cancelled through /v1/jobs, or the target host is draining"),
         (status = 502, description = "Agent reported migration failure"),
     ),
     tag = "VMs"
 )]
 pub async fn migrate(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<MigrateRequest>,
-) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    super::qemu_service::live_migrate(&st, id, req.target_host_id, req.target_port)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse {
-                    error: "Failed to migrate VM".to_string(),
-                    fault_message: Some(err.to_string()),
-                }),
-            )
-        })?;
-    Ok(Json(OkResponse::default()))
+) -> Result<Json<MigrateResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let job = st.jobs.start(JobKind::MigrateVm, id);
+    let result =
+        super::qemu_service::live_migrate(&st, &job, id, req.target_host_id, req.target_port).await;
+    st.jobs.finish(&job, &result);
+    actor
+        .record_with(
+            AuditAction::MigrateVm,
+            ("vm", id),
+            Some(serde_json::json!({
+                "target_host_id": req.target_host_id,
+                "job_id": job.id,
+            })),
+            &result,
+        )
+        .await;
+    result.map_err(|err| {
+        let status = if crate::features::jobs::is_cancelled(&err)
+            || super::qemu_service::is_target_draining(&err)
+        {
+            StatusCode::CONFLICT
+        } else {
+            StatusCode::BAD_GATEWAY
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: "Failed to migrate VM".to_string(),
+                fault_message: Some(err.to_string()),
+            }),
+        )
+    })?;
+    Ok(Json(MigrateResponse { job_id: job.id }))
 }
 
 /// Mark an `installing` VM as install-complete:
@@ -1062,7 +1615,7 @@ pub async fn install_complete(
             }),
         ));
     }
-    let http = reqwest::Client::new();
+    let http = crate::core::agent_http::client();
     let url = format!("{}/agent/v1/vmm/{}/cdrom/eject", vm.host_addr, vm.id);
     let resp = http
         .post(&url)
@@ -1089,10 +1642,16 @@ pub async fn install_complete(
             }),
         ));
     }
-    let _ = sqlx::query(r#"UPDATE vm SET state = 'running', updated_at = now() WHERE id = $1"#)
-        .bind(id)
-        .execute(&st.db)
-        .await;
+    let _ = sqlx::query(
+        r#"UPDATE vm
+           SET state = 'running',
+               updated_at = now(),
+               state_changed_at = CASE WHEN state <> 'running' THEN now() ELSE state_changed_at END
+           WHERE id = $1"#,
+    )
+    .bind(id)
+    .execute(&st.db)
+    .await;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1108,21 +1667,22 @@ pub async fn install_complete(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
-    user: Option<Extension<AuthenticatedUser>>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let (user_id, username) = extract_user_info(user);
-    super::service::stop_and_delete_with_user(&st, id, user_id, &username)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Failed to delete VM".to_string(),
-                    fault_message: Some(err.to_string()),
-                }),
-            )
-        })?;
+    let result = super::service::stop_and_delete(&st, id).await;
+    actor
+        .record(AuditAction::DeleteVm, ("vm", id), &result)
+        .await;
+    result.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: "Failed to delete VM".to_string(),
+                fault_message: Some(err.to_string()),
+            }),
+        )
+    })?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1133,30 +1693,20 @@ pub async fn delete(
     request_body = MachineConfigPatchReq,
     responses(
         (status = 200, description = "Machine config patched", body = OkResponse),
-        (status = 400, description = "Invalid request"),
+        (status = 400, description = "Invalid request or unknown cpu_template"),
         (status = 404, description = "VM not found"),
     ),
     tag = "VM configuration"
 )]
 pub async fn patch_machine_config(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<MachineConfigPatchReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::patch_machine_config(&st, id, req)
-        .await
-        .map_err(|err| {
-            if err
-                .to_string()
-                .contains("not within the configured image root")
-            {
-                axum::http::StatusCode::BAD_REQUEST
-            } else if err.to_string().contains("not found") {
-                axum::http::StatusCode::NOT_FOUND
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::patch_machine_config(&st, id, req).await;
+    record_config_change(&actor, id, "machine_config", &result).await;
+    result?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1167,6 +1717,7 @@ pub async fn patch_machine_config(
     request_body = CpuConfigReq,
     responses(
         (status = 200, description = "CPU config applied", body = OkResponse),
+        (status = 400, description = "Malformed custom CPU template"),
         (status = 404, description = "VM not found"),
     ),
     tag = "VM configuration"
@@ -1175,10 +1726,8 @@ pub async fn put_cpu_config(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<CpuConfigReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_cpu_config(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_cpu_config(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1197,10 +1746,8 @@ pub async fn put_vsock(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<VsockConfigReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_vsock(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_vsock(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1219,10 +1766,8 @@ pub async fn put_mmds(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<MmdsDataReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_mmds(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_mmds(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1241,13 +1786,29 @@ pub async fn put_mmds_config(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<MmdsConfigReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_mmds_config(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_mmds_config(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/mmds",
+    params(VmPathParams),
+    responses(
+        (status = 200, description = "Stored MMDS config and data", body = VmMmds),
+        (status = 404, description = "VM not found"),
+        (status = 500, description = "Failed to read MMDS state"),
+    ),
+    tag = "VM configuration"
+)]
+pub async fn get_mmds(
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+) -> Result<Json<VmMmds>, ApiError> {
+    Ok(Json(super::service::get_mmds(&st.db, id).await?))
+}
+
 #[utoipa::path(
     put,
     path = "/v1/vms/{id}/entropy",
@@ -1263,10 +1824,8 @@ pub async fn put_entropy(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<EntropyConfigReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_entropy(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_entropy(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1285,10 +1844,8 @@ pub async fn put_serial(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<SerialConfigReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_serial(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::put_serial(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1307,10 +1864,8 @@ pub async fn put_logger(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<LoggerUpdateReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::patch_logger(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::patch_logger(&st, id, req).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1327,12 +1882,13 @@ pub async fn put_logger(
 )]
 pub async fn put_balloon(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<BalloonConfig>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::put_balloon(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::put_balloon(&st, id, req).await;
+    record_config_change(&actor, id, "balloon", &result).await;
+    result?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1349,12 +1905,13 @@ pub async fn put_balloon(
 )]
 pub async fn patch_balloon(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<BalloonConfig>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::patch_balloon(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::patch_balloon(&st, id, req).await;
+    record_config_change(&actor, id, "balloon", &result).await;
+    result?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1371,12 +1928,91 @@ pub async fn patch_balloon(
 )]
 pub async fn patch_balloon_statistics(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<BalloonStatsConfig>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::patch_balloon_stats(&st, id, req)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::patch_balloon_stats(&st, id, req).await;
+    record_config_change(&actor, id, "balloon_statistics", &result).await;
+    result?;
+    Ok(Json(OkResponse::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/balloon/policy",
+    params(VmPathParams),
+    responses(
+        (status = 200, description = "Balloon policy", body = BalloonPolicy),
+        (status = 404, description = "VM not found or no policy set"),
+    ),
+    tag = "VM configuration"
+)]
+pub async fn get_balloon_policy(
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+) -> Result<Json<BalloonPolicy>, ApiError> {
+    super::repo::get(&st.db, id).await?;
+    super::balloon_policy::get(&st.db, id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("no balloon policy set"))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/vms/{id}/balloon/policy",
+    params(VmPathParams),
+    request_body = BalloonPolicy,
+    responses(
+        (status = 200, description = "Balloon policy set", body = OkResponse),
+        (status = 400, description = "Invalid policy"),
+        (status = 404, description = "VM not found"),
+    ),
+    tag = "VM configuration"
+)]
+pub async fn put_balloon_policy(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+    Json(req): Json<BalloonPolicy>,
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = async {
+        let vm = super::repo::get(&st.db, id).await?;
+        super::balloon_policy::validate(&req, vm.mem_mib as u64)?;
+        super::balloon_policy::upsert(&st.db, id, &req).await
+    }
+    .await;
+    record_config_change(&actor, id, "balloon_policy", &result).await;
+    result?;
+    Ok(Json(OkResponse::default()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/vms/{id}/balloon/policy",
+    params(VmPathParams),
+    responses(
+        (status = 200, description = "Balloon policy removed; the balloon stays where it is", body = OkResponse),
+        (status = 404, description = "VM not found or no policy set"),
+    ),
+    tag = "VM configuration"
+)]
+pub async fn delete_balloon_policy(
+    Extension(st): Extension<AppState>,
+    actor: Actor,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::balloon_policy::delete(&st.db, id).await;
+    let outcome = match &result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("no balloon policy set".to_string()),
+        Err(err) => Err(format!("{err:#}")),
+    };
+    record_config_change(&actor, id, "balloon_policy_removed", &outcome).await;
+    if !result? {
+        return Err(ApiError::not_found("no balloon policy set"));
+    }
     Ok(Json(OkResponse::default()))
 }
 
@@ -1392,10 +2028,8 @@ pub async fn patch_balloon_statistics(
 pub async fn list_drives(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
-) -> Result<Json<ListDrivesResponse>, axum::http::StatusCode> {
-    let items = super::service::list_drives(&st, id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<ListDrivesResponse>, ApiError> {
+    let items = super::service::list_drives(&st, id).await?;
     Ok(Json(ListDrivesResponse { items }))
 }
 
@@ -1406,7 +2040,9 @@ pub async fn list_drives(
     request_body = CreateDriveReq,
     responses(
         (status = 200, description = "Drive created", body = VmDrive),
+        (status = 400, description = "Invalid request"),
         (status = 404, description = "VM not found"),
+        (status = 409, description = "Drive already exists"),
     ),
     tag = "VM devices"
 )]
@@ -1414,22 +2050,8 @@ pub async fn create_drive(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<CreateDriveReq>,
-) -> Result<Json<VmDrive>, axum::http::StatusCode> {
-    super::service::create_drive(&st, id, req)
-        .await
-        .map(Json)
-        .map_err(|err| {
-            let err_str = err.to_string();
-            if err_str.contains("already exists")
-                || err_str.contains("not within the configured image root")
-            {
-                axum::http::StatusCode::BAD_REQUEST
-            } else if err_str.contains("not found") {
-                axum::http::StatusCode::NOT_FOUND
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })
+) -> Result<Json<VmDrive>, ApiError> {
+    Ok(Json(super::service::create_drive(&st, id, req).await?))
 }
 
 #[derive(serde::Deserialize, utoipa::ToSchema)]
@@ -1451,8 +2073,32 @@ pub struct ResizeDriveReq {
 )]
 pub async fn resize_drive(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path((id, drive_id)): Path<(Uuid, Uuid)>,
     Json(req): Json<ResizeDriveReq>,
+) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let details = serde_json::json!({
+        "change": "resize_drive",
+        "drive_id": drive_id,
+        "size_bytes": req.size_bytes,
+    });
+    let result = resize(&st, id, drive_id, req).await;
+    actor
+        .record_with(
+            AuditAction::UpdateVm,
+            ("vm", id),
+            Some(details),
+            &error_outcome(&result),
+        )
+        .await;
+    result
+}
+
+async fn resize(
+    st: &AppState,
+    id: Uuid,
+    drive_id: Uuid,
+    req: ResizeDriveReq,
 ) -> Result<Json<OkResponse>, (StatusCode, Json<ErrorResponse>)> {
     let err = |code: StatusCode, msg: &str| {
         (
@@ -1466,7 +2112,7 @@ pub async fn resize_drive(
     let vm = super::repo::get(&st.db, id)
         .await
         .map_err(|_| err(StatusCode::NOT_FOUND, "VM not found"))?;
-    let drive = super::service::list_drives(&st, id)
+    let drive = super::service::list_drives(st, id)
         .await
         .map_err(|_| err(StatusCode::INTERNAL_SERVER_ERROR, "list drives failed"))?
         .into_iter()
@@ -1479,7 +2125,7 @@ pub async fn resize_drive(
         "source": drive.path_on_host,
         "size_bytes": req.size_bytes,
     });
-    let resp = reqwest::Client::new()
+    let resp = crate::core::agent_http::client()
         .post(format!("{}/agent/v1/vmm/{}/disk/resize", vm.host_addr, id))
         .json(&body)
         .send()
@@ -1527,13 +2173,12 @@ pub async fn resize_drive(
 pub async fn get_drive(
     Extension(st): Extension<AppState>,
     Path((id, drive_id)): Path<(Uuid, Uuid)>,
-) -> Result<Json<VmDrive>, axum::http::StatusCode> {
+) -> Result<Json<VmDrive>, ApiError> {
     let drive = super::service::list_drives(&st, id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .await?
         .into_iter()
         .find(|d| d.id == drive_id)
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+        .ok_or_else(|| ApiError::not_found("drive not found"))?;
     Ok(Json(drive))
 }
 
@@ -1554,20 +2199,10 @@ pub async fn update_drive(
     Extension(st): Extension<AppState>,
     Path((id, drive_id)): Path<(Uuid, Uuid)>,
     Json(req): Json<UpdateDriveReq>,
-) -> Result<Json<VmDrive>, axum::http::StatusCode> {
-    super::service::update_drive(&st, id, drive_id, req)
-        .await
-        .map(Json)
-        .map_err(|err| {
-            let err_str = err.to_string();
-            if err_str.contains("does not belong")
-                || err_str.contains("not within the configured image root")
-            {
-                axum::http::StatusCode::BAD_REQUEST
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })
+) -> Result<Json<VmDrive>, ApiError> {
+    Ok(Json(
+        super::service::update_drive(&st, id, drive_id, req).await?,
+    ))
 }
 
 #[utoipa::path(
@@ -1585,19 +2220,52 @@ pub async fn update_drive(
 pub async fn delete_drive(
     Extension(st): Extension<AppState>,
     Path((id, drive_id)): Path<(Uuid, Uuid)>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::delete_drive(&st, id, drive_id)
-        .await
-        .map_err(|err| {
-            if err.to_string().contains("does not belong") {
-                axum::http::StatusCode::BAD_REQUEST
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::delete_drive(&st, id, drive_id).await?;
     Ok(Json(OkResponse::default()))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/events",
+    params(VmPathParams, ListVmEventsParams),
+    responses(
+        (status = 200, description = "VM events listed, newest first", body = ListVmEventsResponse),
+        (status = 404, description = "VM not found"),
+        (status = 500, description = "Failed to list VM events"),
+    ),
+    tag = "VMs"
+)]
+pub async fn list_events(
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+    Query(params): Query<ListVmEventsParams>,
+) -> Result<Json<ListVmEventsResponse>, ApiError> {
+    Ok(Json(super::service::list_events(&st.db, id, params).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/processes",
+    params(VmPathParams, GuestProcessesParams),
+    responses(
+        (status = 200, description = "Guest processes, biggest first", body = ListGuestProcessesResponse),
+        (status = 400, description = "Invalid sort"),
+        (status = 404, description = "VM not found"),
+        (status = 500, description = "Guest agent unreachable"),
+    ),
+    tag = "VMs"
+)]
+pub async fn list_processes(
+    Extension(st): Extension<AppState>,
+    Path(VmPathParams { id }): Path<VmPathParams>,
+    Query(params): Query<GuestProcessesParams>,
+) -> Result<Json<ListGuestProcessesResponse>, ApiError> {
+    Ok(Json(
+        super::service::guest_processes(&st, id, params).await?,
+    ))
+}
+
 #[utoipa::path(
     get,
     path = "/v1/vms/{id}/nics",
@@ -1610,10 +2278,8 @@ pub async fn delete_drive(
 pub async fn list_nics(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
-) -> Result<Json<ListNicsResponse>, axum::http::StatusCode> {
-    let items = super::service::list_nics(&st, id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<ListNicsResponse>, ApiError> {
+    let items = super::service::list_nics(&st, id).await?;
     Ok(Json(ListNicsResponse { items }))
 }
 
@@ -1630,13 +2296,15 @@ pub async fn list_nics(
 )]
 pub async fn create_nic(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<CreateNicReq>,
-) -> Result<Json<VmNic>, axum::http::StatusCode> {
-    super::service::create_nic(&st, id, req)
-        .await
-        .map(Json)
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+) -> Result<Json<VmNic>, ApiError> {
+    let result = super::service::create_nic(&st, id, req).await;
+    actor
+        .record(AuditAction::CreateNic, ("vm", id), &result)
+        .await;
+    Ok(Json(result?))
 }
 
 #[utoipa::path(
@@ -1653,13 +2321,12 @@ pub async fn create_nic(
 pub async fn get_nic(
     Extension(st): Extension<AppState>,
     Path((id, nic_id)): Path<(Uuid, Uuid)>,
-) -> Result<Json<VmNic>, axum::http::StatusCode> {
+) -> Result<Json<VmNic>, ApiError> {
     let nic = super::service::list_nics(&st, id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?
+        .await?
         .into_iter()
         .find(|n| n.id == nic_id)
-        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+        .ok_or_else(|| ApiError::not_found("NIC not found"))?;
     Ok(Json(nic))
 }
 
@@ -1678,19 +2345,20 @@ pub async fn get_nic(
 )]
 pub async fn update_nic(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path((id, nic_id)): Path<(Uuid, Uuid)>,
     Json(req): Json<UpdateNicReq>,
-) -> Result<Json<VmNic>, axum::http::StatusCode> {
-    super::service::update_nic(&st, id, nic_id, req)
-        .await
-        .map(Json)
-        .map_err(|err| {
-            if err.to_string().contains("does not belong") {
-                axum::http::StatusCode::BAD_REQUEST
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })
+) -> Result<Json<VmNic>, ApiError> {
+    let result = super::service::update_nic(&st, id, nic_id, req).await;
+    actor
+        .record_with(
+            AuditAction::UpdateNic,
+            ("vm", id),
+            Some(serde_json::json!({"nic_id": nic_id})),
+            &result,
+        )
+        .await;
+    Ok(Json(result?))
 }
 
 #[utoipa::path(
@@ -1707,17 +2375,15 @@ pub async fn update_nic(
 )]
 pub async fn delete_nic(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path((id, nic_id)): Path<(Uuid, Uuid)>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::delete_nic(&st, id, nic_id)
-        .await
-        .map_err(|err| {
-            if err.to_string().contains("does not belong") {
-                axum::http::StatusCode::BAD_REQUEST
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+) -> Result<Json<OkResponse>, ApiError> {
+    let result = super::service::delete_nic(&st, id, nic_id).await;
+    let details = serde_json::json!({"nic_id": nic_id});
+    actor
+        .record_with(AuditAction::DeleteNic, ("vm", id), Some(details), &result)
+        .await;
+    result?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1735,10 +2401,8 @@ pub async fn delete_nic(
 pub async fn flush_metrics(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::flush_vm_metrics(&st, id)
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::flush_vm_metrics(&st, id).await?;
     Ok(Json(OkResponse::default()))
 }
 
@@ -1757,21 +2421,14 @@ pub async fn flush_metrics(
 pub async fn ctrl_alt_del(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::service::send_ctrl_alt_del(&st, id)
-        .await
-        .map_err(|err| {
-            if err.to_string().contains("must be running") {
-                axum::http::StatusCode::BAD_REQUEST
-            } else {
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR
-            }
-        })?;
+) -> Result<Json<OkResponse>, ApiError> {
+    super::service::send_ctrl_alt_del(&st, id).await?;
     Ok(Json(OkResponse::default()))
 }
 
 impl From<super::repo::VmRow> for Vm {
     fn from(row: super::repo::VmRow) -> Self {
+        let uptime_seconds = uptime_seconds(&row.state, row.started_at, chrono::Utc::now());
         Self {
             id: row.id,
             name: row.name,
@@ -1801,13 +2458,37 @@ impl From<super::repo::VmRow> for Vm {
             cpu_type: row.cpu_type,
             created_at: row.created_at,
             updated_at: row.updated_at,
+            state_changed_at: row.state_changed_at,
+            started_at: row.started_at,
+            uptime_seconds,
         }
     }
 }
 
+/// Time since the VMM was booted, while it runs, like a container's uptime.
+fn uptime_seconds(
+    state: &str,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<i64> {
+    let started_at = started_at.filter(|_| super::service::is_running_state(state))?;
+    Some((now - started_at).num_seconds().max(0))
+}
+
 #[derive(serde::Deserialize)]
 pub struct UpdateGuestIpReq {
-    pub guest_ip: String,
+    /// The address on the agent's report interface; `null` when it has none
+    /// or the guest agent is shutting down
+    pub guest_ip: Option<String>,
+    /// Every non-loopback address on the guest (newer guest agents)
+    #[serde(default)]
+    pub guest_ips: Vec<GuestAddr>,
+}
+
+/// One guest address; the agent also names its `interface`, unused here
+#[derive(serde::Deserialize)]
+pub struct GuestAddr {
+    pub ip: String,
 }
 
 #[utoipa::path(
@@ -1816,7 +2497,7 @@ pub struct UpdateGuestIpReq {
     params(VmPathParams),
     request_body = UpdateGuestIpReq,
     responses(
-        (status = 200, description = "Guest IP updated", body = OkResponse),
+        (status = 200, description = "Guest IP updated, or cleared for a null guest_ip", body = OkResponse),
         (status = 404, description = "VM not found"),
         (status = 500, description = "Failed to update guest IP"),
     ),
@@ -1826,12 +2507,15 @@ pub async fn update_guest_ip(
     Extension(st): Extension<AppState>,
     Path(VmPathParams { id }): Path<VmPathParams>,
     Json(req): Json<UpdateGuestIpReq>,
-) -> Result<Json<OkResponse>, axum::http::StatusCode> {
-    super::repo::update_guest_ip(&st.db, id, Some(&req.guest_ip))
-        .await
-        .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    tracing::info!(vm_id = %id, guest_ip = %req.guest_ip, "Updated VM guest IP");
+) -> Result<Json<OkResponse>, ApiError> {
+    let addrs: Vec<&str> = req.guest_ips.iter().map(|a| a.ip.as_str()).collect();
+    let guest_ip =
+        super::service::record_guest_ip(&st, id, req.guest_ip.as_deref(), &addrs).await?;
+
+    match guest_ip {
+        Some(ip) => tracing::info!(vm_id = %id, guest_ip = %ip, "Updated VM guest IP"),
+        None => tracing::info!(vm_id = %id, "Cleared VM guest IP"),
+    }
     Ok(Json(OkResponse::default()))
 }
 
@@ -1842,12 +2526,6 @@ mod tests {
     use axum::{extract::Path, Extension};
     use serde_json::json;
 
-    async fn test_registry(pool: &sqlx::PgPool) -> crate::features::storage::registry::Registry {
-        crate::features::storage::registry::Registry::load(pool, None)
-            .await
-            .expect("registry")
-    }
-
     // Uses SQLx runtime DB with the same migrations as prod code.
     #[ignore]
     #[sqlx::test(migrations = "./migrations")]
@@ -1884,48 +2562,30 @@ mod tests {
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
             created_at: now,
             updated_at: now,
+            state_changed_at: now,
+            started_at: None,
         };
         super::super::repo::insert(&pool, &row).await.unwrap();
 
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts: hosts.clone(),
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
 
-        let Json(body) = super::delete(Extension(state), None, Path(VmPathParams { id }))
-            .await
-            .unwrap();
+        let Json(body) = super::delete(
+            Extension(state),
+            Actor::system(pool.clone()),
+            Path(VmPathParams { id }),
+        )
+        .await
+        .unwrap();
         assert_eq!(body, OkResponse::default());
 
         let fetched = super::super::repo::get(&pool, id).await;
@@ -1935,47 +2595,50 @@ mod tests {
     #[ignore]
     #[sqlx::test(migrations = "./migrations")]
     async fn delete_route_unknown_id_returns_ok(pool: sqlx::PgPool) {
-        let hosts = HostRepository::new(pool.clone());
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let registry = test_registry(&pool).await;
-        let state = crate::AppState {
-            db: pool.clone(),
-            hosts,
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
-            allow_direct_image_paths: true,
-            storage,
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
-        };
+        let state = crate::AppState::for_tests(pool.clone()).await;
         let Json(body) = super::delete(
             Extension(state),
-            None,
+            Actor::system(pool.clone()),
             Path(VmPathParams { id: Uuid::new_v4() }),
         )
         .await
         .unwrap();
         assert_eq!(body, OkResponse::default());
     }
+
+    #[test]
+    fn only_one_metrics_session_per_vm() {
+        let vm = Uuid::new_v4();
+        let first = MetricsSession::acquire(vm).expect("first session");
+        assert!(MetricsSession::acquire(vm).is_none());
+        assert!(MetricsSession::acquire(Uuid::new_v4()).is_some());
+        drop(first);
+        assert!(MetricsSession::acquire(vm).is_some());
+    }
+
+    #[test]
+    fn fifo_reopen_backoff_doubles_up_to_cap() {
+        assert_eq!(
+            next_fifo_backoff(FIFO_REOPEN_MIN),
+            std::time::Duration::from_secs(2)
+        );
+        assert_eq!(
+            next_fifo_backoff(std::time::Duration::from_secs(20)),
+            FIFO_REOPEN_MAX
+        );
+    }
+
+    #[test]
+    fn uptime_only_counts_while_running() {
+        let booted = chrono::Utc::now();
+        let later = booted + chrono::Duration::seconds(90);
+        assert_eq!(uptime_seconds("running", Some(booted), later), Some(90));
+        assert_eq!(
+            uptime_seconds(super::super::service::RUNNING_DEGRADED, Some(booted), later),
+            Some(90)
+        );
+        assert_eq!(uptime_seconds("stopped", Some(booted), later), None);
+        assert_eq!(uptime_seconds("paused", Some(booted), later), None);
+        assert_eq!(uptime_seconds("running", None, later), None);
+    }
 }
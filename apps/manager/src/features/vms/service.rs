@@ -1,33 +1,89 @@
-use crate::{features::snapshots::repo::SnapshotRow, AppState};
+use crate::core::agent_http::FaultExt;
+use crate::core::error::{bad_request, conflict, not_found};
+use crate::{
+    features::snapshots::{compat, repo::SnapshotRow},
+    AppState,
+};
 use anyhow::{anyhow, bail, Context, Result};
 use nexus_types::{
     AuditAction, BalloonConfig, BalloonStatsConfig, CpuConfigReq, CreateDriveReq, CreateNicReq,
-    CreateVmReq, EntropyConfigReq, LoggerUpdateReq, MachineConfigPatchReq, MmdsConfigReq,
-    MmdsDataReq, SerialConfigReq, UpdateDriveReq, UpdateNicReq, VsockConfigReq,
+    CreateVmReq, EntropyConfigReq, JobKind, LoggerUpdateReq, MachineConfigPatchReq, MmdsConfigReq,
+    MmdsDataReq, RootfsMode, SerialConfigReq, StopVmReport, UpdateDriveReq, UpdateNicReq,
+    ValidateVmResponse, VmCreateStep, VmCreateStepStatus, VsockConfigReq,
 };
-use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 use serde_json::Value;
 use sqlx::PgPool;
+use std::net::Ipv4Addr;
 use std::path::Path;
 use std::time::{Duration, Instant};
 use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::features::jobs::{self, JobHandle};
 use crate::features::users::audit;
 
 struct NetworkSelection {
     bridge: String,
+    /// The advertised name of a non-default bridge; `None` for the host's
+    /// default bridge, which auto-registers as the NAT'd default network.
+    name: Option<String>,
 }
 
-fn select_network(capabilities: &Value) -> Result<NetworkSelection> {
-    if let Some(bridge) = capabilities.get("bridge").and_then(|v| v.as_str()) {
+/// Picks the host bridge for eth0. Hosts advertise their default as
+/// `bridge` and every named bridge (the agent's `FC_BRIDGES`) under
+/// `bridges`; `requested` matches either a name or a device there.
+fn select_network(capabilities: &Value, requested: Option<&str>) -> Result<NetworkSelection> {
+    let default = capabilities
+        .get("bridge")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("host capabilities missing bridge name"))?;
+    let Some(requested) = requested else {
         return Ok(NetworkSelection {
-            bridge: bridge.to_string(),
+            bridge: default.to_string(),
+            name: None,
         });
+    };
+
+    let named: Vec<(&str, &str)> = capabilities
+        .get("bridges")
+        .and_then(|v| v.as_object())
+        .map(|bridges| {
+            bridges
+                .iter()
+                .filter_map(|(name, bridge)| Some((name.as_str(), bridge.as_str()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+    let found = named
+        .iter()
+        .find(|(name, bridge)| *name == requested || *bridge == requested);
+    match found {
+        Some((_, bridge)) if *bridge == default => {}
+        Some((name, bridge)) => {
+            return Ok(NetworkSelection {
+                bridge: bridge.to_string(),
+                name: Some(name.to_string()),
+            })
+        }
+        None if requested == default => {}
+        None => {
+            let names: Vec<&str> = named.iter().map(|(name, _)| *name).collect();
+            bail!(bad_request(format!(
+                "invalid bridge: host has no bridge '{requested}' (has: {})",
+                if names.is_empty() {
+                    default.to_string()
+                } else {
+                    names.join(", ")
+                }
+            )));
+        }
     }
-    Err(anyhow!("host capabilities missing bridge name"))
+    Ok(NetworkSelection {
+        bridge: default.to_string(),
+        name: None,
+    })
 }
 
 fn normalize_rate_limiter(raw: &Value) -> Value {
@@ -68,14 +124,298 @@ fn normalize_rate_limiter(raw: &Value) -> Value {
     }
 }
 
+/// Token bucket fields Firecracker parses as `u64`.
+const RATE_LIMITER_BUCKET_FIELDS: [&str; 3] = ["size", "one_time_burst", "refill_time"];
+
+/// Reject rate limiter JSON Firecracker would refuse, naming the offending
+/// field. Accepts both the legacy flat shape (`{"size": ..}`) and the nested
+/// `bandwidth`/`ops` buckets that [`normalize_rate_limiter`] produces.
+pub(crate) fn validate_rate_limiter(field: &str, raw: &Value) -> Result<()> {
+    let Value::Object(obj) = raw else {
+        bail!(bad_request(format!(
+            "invalid {field}: must be a JSON object"
+        )));
+    };
+    let check_bucket = |prefix: &str, bucket: &serde_json::Map<String, Value>| -> Result<()> {
+        for key in RATE_LIMITER_BUCKET_FIELDS {
+            match bucket.get(key) {
+                None => {}
+                Some(value) if value.is_u64() => {}
+                Some(value) => {
+                    bail!(bad_request(format!("invalid {field}: {prefix}{key} must be a non-negative integer, got {value}")))
+                }
+            }
+        }
+        Ok(())
+    };
+    check_bucket("", obj)?;
+    for bucket in ["bandwidth", "ops"] {
+        match obj.get(bucket) {
+            None => {}
+            Some(Value::Object(inner)) => check_bucket(&format!("{bucket}."), inner)?,
+            Some(value) => bail!(bad_request(format!(
+                "invalid {field}: {bucket} must be a JSON object, got {value}"
+            ))),
+        }
+    }
+    Ok(())
+}
+
+const ENTROPY_DEVICE_ENV: &str = "MANAGER_ENTROPY_DEVICE";
+
+/// Whether `configure_vm` attaches a virtio-rng device on fresh boots.
+/// On unless `MANAGER_ENTROPY_DEVICE` is `0`, `false` or `off`.
+#[cfg_attr(test, allow(dead_code))]
+fn entropy_device_enabled() -> bool {
+    parse_entropy_device_enabled(std::env::var(ENTROPY_DEVICE_ENV).ok().as_deref())
+}
+
+fn parse_entropy_device_enabled(raw: Option<&str>) -> bool {
+    !matches!(
+        raw.map(|v| v.trim().to_ascii_lowercase()).as_deref(),
+        Some("0" | "false" | "off")
+    )
+}
+
+const DEFAULT_ROOTFS_RATE_LIMITER_ENV: &str = "MANAGER_DEFAULT_ROOTFS_RATE_LIMITER";
+
+/// Manager-wide rate limiter for rootfs drives, from the JSON in
+/// `MANAGER_DEFAULT_ROOTFS_RATE_LIMITER`. Unset or invalid means unlimited.
+#[cfg_attr(test, allow(dead_code))]
+fn default_rootfs_rate_limiter() -> Option<Value> {
+    parse_default_rootfs_rate_limiter(
+        std::env::var(DEFAULT_ROOTFS_RATE_LIMITER_ENV)
+            .ok()
+            .as_deref(),
+    )
+}
+
+fn parse_default_rootfs_rate_limiter(raw: Option<&str>) -> Option<Value> {
+    let raw = raw.map(str::trim).filter(|raw| !raw.is_empty())?;
+    let parsed = serde_json::from_str::<Value>(raw)
+        .map_err(anyhow::Error::from)
+        .and_then(|value| {
+            validate_rate_limiter(DEFAULT_ROOTFS_RATE_LIMITER_ENV, &value)?;
+            Ok(value)
+        });
+    match parsed {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!(error = %err, "ignoring {DEFAULT_ROOTFS_RATE_LIMITER_ENV}");
+            None
+        }
+    }
+}
+
+/// Rate limiter for the rootfs drive: an explicit `rootfs` drive setting
+/// wins, then the VM's (or its template's), then the manager default.
+fn effective_rootfs_rate_limiter(
+    drive: Option<&Value>,
+    vm: Option<&Value>,
+    default: Option<&Value>,
+) -> Option<Value> {
+    drive.or(vm).or(default).map(normalize_rate_limiter)
+}
+
+/// Derive a locally-administered unicast MAC (`02:xx:xx:xx:xx:xx`) from the
+/// VM id and interface index. Deterministic so the guest sees the same MAC
+/// across restarts, which keeps DHCP leases and udev naming stable.
+fn generate_guest_mac(vm_id: Uuid, iface_index: u32) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    hasher.update(vm_id.as_bytes());
+    hasher.update(iface_index.to_be_bytes());
+    let digest = hasher.finalize();
+
+    format!(
+        "02:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        digest[0], digest[1], digest[2], digest[3], digest[4]
+    )
+}
+
+/// Longest interface name the kernel accepts (IFNAMSIZ - 1).
+const MAX_TAP_NAME_LEN: usize = 15;
+/// How many candidate names `allocate_tap_name` tries before giving up.
+const MAX_TAP_ATTEMPTS: u32 = 16;
+
+/// Candidate TAP device name for `id`. Attempt 0 uses the leading hex of the
+/// UUID so names stay recognisable in `ip link`; later attempts hash the id
+/// with the attempt number so two VMs sharing a prefix still diverge.
+pub(crate) fn tap_name_candidate(id: Uuid, attempt: u32) -> String {
+    use sha2::{Digest, Sha256};
+
+    let suffix_len = MAX_TAP_NAME_LEN - "tap-".len();
+    let hex = if attempt == 0 {
+        id.simple().to_string()
+    } else {
+        let mut hasher = Sha256::new();
+        hasher.update(id.as_bytes());
+        hasher.update(attempt.to_be_bytes());
+        hex::encode(hasher.finalize())
+    };
+    format!("tap-{}", &hex[..suffix_len])
+}
+
+/// First candidate name for `id` that `taken` does not report as in use.
+fn pick_tap_name(id: Uuid, taken: &std::collections::HashSet<String>) -> Result<String> {
+    (0..MAX_TAP_ATTEMPTS)
+        .map(|attempt| tap_name_candidate(id, attempt))
+        .find(|name| !taken.contains(name))
+        .ok_or_else(|| anyhow!("no free tap name for vm {id} after {MAX_TAP_ATTEMPTS} attempts"))
+}
+
+/// Choose a TAP name for a new VM that no existing VM or NIC already uses.
+/// The manager owns the name; the agent creates exactly what it is given.
+pub(crate) async fn allocate_tap_name(db: &PgPool, id: Uuid) -> Result<String> {
+    let candidates: Vec<String> = (0..MAX_TAP_ATTEMPTS)
+        .map(|attempt| tap_name_candidate(id, attempt))
+        .collect();
+    let taken = super::repo::tap_names_in_use(db, &candidates)
+        .await
+        .context("failed to check tap names in use")?;
+    pick_tap_name(id, &taken)
+}
+
+/// Validate a user-supplied MAC and return it in canonical lowercase form.
+/// Multicast and all-zero addresses are rejected since Firecracker would
+/// accept them but the guest NIC would never receive unicast traffic.
+fn normalize_guest_mac(raw: &str) -> Result<String> {
+    let mac = raw.trim().to_ascii_lowercase();
+    let octets: Vec<&str> = mac.split(':').collect();
+    if octets.len() != 6
+        || !octets
+            .iter()
+            .all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        bail!(bad_request(
+            "guest_mac must be in the form xx:xx:xx:xx:xx:xx"
+        ));
+    }
+
+    let first = u8::from_str_radix(octets[0], 16)?;
+    if first & 0x01 != 0 {
+        bail!(bad_request("guest_mac must be a unicast address"));
+    }
+    if octets.iter().all(|o| *o == "00") {
+        bail!(bad_request("guest_mac must not be all zeros"));
+    }
+
+    Ok(mac)
+}
+
+const READINESS_TIMEOUT_ENV: &str = "MANAGER_VM_READINESS_TIMEOUT_SECS";
+const READINESS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const READINESS_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// State of a VM whose VMM is up but whose guest agent never answered the
+/// readiness probe.
+pub const RUNNING_DEGRADED: &str = "running-degraded";
+
+/// Whether the VMM is up, regardless of how the readiness probe went.
+pub fn is_running_state(state: &str) -> bool {
+    state == "running" || state == RUNNING_DEGRADED
+}
+
+/// How long to wait for the guest agent after boot, from
+/// `MANAGER_VM_READINESS_TIMEOUT_SECS`. `None` (the default) skips the wait so
+/// images without a guest agent are marked running immediately.
+fn readiness_timeout() -> Option<Duration> {
+    parse_readiness_timeout(std::env::var(READINESS_TIMEOUT_ENV).ok().as_deref())
+}
+
+fn parse_readiness_timeout(raw: Option<&str>) -> Option<Duration> {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// Poll the guest agent's `/health` until it answers or `timeout` elapses.
+/// The guest IP is only known once the guest agent reports it, so each round
+/// re-reads the VM row first.
+async fn wait_for_guest_ready(st: &AppState, id: Uuid, timeout: Duration) -> bool {
+    let client = match reqwest::Client::builder()
+        .timeout(READINESS_PROBE_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(vm_id = %id, error = ?e, "failed to build readiness probe client");
+            return false;
+        }
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        let guest_ip = super::repo::get(&st.db, id)
+            .await
+            .ok()
+            .and_then(|vm| vm.guest_ip)
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = guest_ip {
+            let healthy = client
+                .get(format!("http://{}:9000/health", ip))
+                .send()
+                .await
+                .is_ok_and(|resp| resp.status().is_success());
+            if healthy {
+                return true;
+            }
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+    }
+}
+
 pub async fn create_and_start(
     st: &AppState,
     id: Uuid,
+    req: CreateVmReq,
+    template_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+) -> Result<()> {
+    let job = st.jobs.start(JobKind::CreateVm, id);
+    create_and_start_with_job(st, &job, id, req, template_id, user_id).await
+}
+
+/// Create and boot a VM, logging each step to `st.create_progress` for
+/// `/v1/vms/{id}/create/ws`. Cancelling `job` before the Firecracker
+/// instance has started tears down what was set up on the host.
+pub async fn create_and_start_with_job(
+    st: &AppState,
+    job: &JobHandle,
+    id: Uuid,
+    req: CreateVmReq,
+    template_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+) -> Result<()> {
+    st.create_progress.begin(id);
+    let result = run_create_and_start(st, job, id, req, template_id, user_id).await;
+    st.create_progress.finish(id, &result);
+    st.jobs.finish(job, &result);
+    result
+}
+
+async fn run_create_and_start(
+    st: &AppState,
+    job: &JobHandle,
+    id: Uuid,
     mut req: CreateVmReq,
     template_id: Option<Uuid>,
     user_id: Option<Uuid>,
-    audit_username: &str,
 ) -> Result<()> {
+    validate_huge_pages(req.huge_pages.as_deref())?;
+    validate_smt(req.smt, req.vcpu)?;
+    validate_rootfs_mode(req.rootfs_mode, req.rootfs_size_mb, req.overlay_size_mb)?;
+    validate_scope_limits(req.cpu_quota, req.io_weight, req.vcpu)?;
+    if let Some(limiter) = req.rootfs_rate_limiter.as_ref() {
+        validate_rate_limiter("rootfs_rate_limiter", limiter)?;
+    }
+    if let Some(limiter) = req.entropy_rate_limiter.as_ref() {
+        validate_rate_limiter("entropy_rate_limiter", limiter)?;
+    }
+    // A restore is sized by its snapshot and checks the quota itself
     if let Some(snapshot_id) = req.source_snapshot_id.take() {
         let name = req.name.clone();
         let snapshot = st
@@ -83,9 +423,36 @@ pub async fn create_and_start(
             .get(snapshot_id)
             .await
             .with_context(|| format!("failed to load snapshot {snapshot_id}"))?;
-        return create_from_snapshot(st, id, name, template_id, snapshot, None).await;
+        return create_from_snapshot(
+            st,
+            id,
+            name,
+            template_id,
+            snapshot,
+            None,
+            None,
+            None,
+            false,
+            user_id,
+        )
+        .await;
     }
 
+    // Held until the VM row is in, so two creates for one user can't both
+    // fit under the quota
+    let reservation = crate::features::users::quota::reserve(
+        &st.db,
+        user_id,
+        nexus_types::UserQuotaUsage {
+            vms: 1,
+            vcpu: req.vcpu as i64,
+            mem_mib: req.mem_mib as i64,
+            containers: 0,
+        },
+    )
+    .await?;
+    let progress = &st.create_progress;
+
     // ---- Pluggable VMM dispatcher (0.5.0) ----
     // If the caller asked for QEMU explicitly, or the boot mode auto-selects to
     // QEMU (UEFI/PVH), branch to the QEMU service. Anything else (default,
@@ -94,15 +461,33 @@ pub async fn create_and_start(
     let kind_auto = req.boot_mode.as_ref().map(::nexus_vmm::auto_select);
     let chosen_kind = kind_explicit.or(kind_auto);
     if matches!(chosen_kind, Some(::nexus_vmm::VmmKind::Qemu)) {
-        return crate::features::vms::qemu_service::create_and_start_qemu(
+        if req.rootfs_mode == Some(RootfsMode::Shared) {
+            bail!(bad_request(
+                "invalid rootfs_mode: shared is only supported for Firecracker VMs"
+            ));
+        }
+        if req.cpu_quota.is_some() || req.io_weight.is_some() {
+            bail!(bad_request(
+                "invalid cpu_quota/io_weight: scope limits are only supported for Firecracker VMs"
+            ));
+        }
+        if req.bridge.is_some() {
+            bail!(bad_request(
+                "invalid bridge: named bridges are only supported for Firecracker VMs"
+            ));
+        }
+        crate::features::vms::qemu_service::create_and_start_qemu(
             st,
             id,
             req,
             template_id,
             user_id,
-            audit_username,
         )
-        .await;
+        .await?;
+        if let Err(err) = reservation.release().await {
+            warn!(vm_id = %id, error = ?err, "failed to release quota reservation");
+        }
+        return Ok(());
     }
 
     let host = st
@@ -112,67 +497,50 @@ pub async fn create_and_start(
         .context("no healthy hosts available")?;
 
     // --- Task 12a: Scheduler filter — reject host if it doesn't support the requested backend ---
-    {
-        let backend_id = req.backend_id.or_else(|| st.registry.default_id());
-        if let Some(bid) = backend_id {
-            let backend_kind_str = st
-                .registry
-                .get(bid)
-                .map(|b| b.kind().as_db_str().to_string())
-                .unwrap_or_else(|| "local_file".to_string());
-            let host_repo = crate::features::hosts::repo::HostRepository::new(st.db.clone());
-            let kinds = host_repo
-                .supported_backend_kinds(host.id)
-                .await
-                .context("failed to query host supported_backend_kinds")?;
-            // If the host has declared supported kinds AND the requested kind is not among them,
-            // refuse.  An empty list means "unconfigured — allow any" for backward compat.
-            if !kinds.is_empty() && !kinds.iter().any(|k| k == &backend_kind_str) {
-                return Err(anyhow::anyhow!(
-                    "host {} does not support backend kind '{}'; supported: {:?}",
-                    host.id,
-                    backend_kind_str,
-                    kinds
-                ));
-            }
-        }
-    }
+    ensure_host_supports_backend(st, host.id, req.backend_id).await?;
 
     // Resolve network: use explicit network_id if provided, else fall back to host capabilities
     let req_network_id = req.network_id;
     let req_port_forwards = std::mem::take(&mut req.port_forwards);
+    if req_network_id.is_some() && req.bridge.is_some() {
+        bail!(bad_request(
+            "invalid bridge: give either network_id or bridge, not both"
+        ));
+    }
     let network = if let Some(nid) = req_network_id {
         use crate::features::networks::repo::NetworkRepository;
         let network_repo = NetworkRepository::new(st.db.clone());
         let net = network_repo
             .get(nid)
             .await
-            .map_err(|_| anyhow::anyhow!("specified network not found: {}", nid))?;
-
-        // Auto-expand VXLAN overlay to this host if not already participating
-        if net.type_ == "vxlan" {
-            use crate::features::networks::service as net_svc;
-            if !net_svc::network_host_exists(st, net.id, host.id).await {
-                info!(vm_id = %id, network_id = %net.id, host_id = %host.id, "auto-expanding VXLAN overlay to host");
-                net_svc::expand_vxlan_to_host(st, &net, host.id)
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "failed to expand VXLAN network {} to host {}",
-                            net.id, host.id
-                        )
-                    })?;
-            }
-        }
+            .map_err(|_| not_found(format!("specified network not found: {}", nid)))?;
+
+        // Provision VXLAN/bridged networks on this host if they aren't yet
+        crate::features::networks::service::ensure_network_on_host(st, &net, host.id).await?;
 
         NetworkSelection {
             bridge: net.bridge_name,
+            name: None,
         }
     } else {
-        select_network(&host.capabilities_json)?
+        select_network(&host.capabilities_json, req.bridge.as_deref())?
+    };
+    // A named bridge is registered before anything is provisioned: without
+    // its network on eth0, a restart could only fall back to the default
+    // bridge and move the VM off the segment it asked for.
+    let bridge_network_id = match network.name {
+        Some(_) => Some(
+            ensure_network_registered(st, &network, host.id)
+                .await
+                .with_context(|| {
+                    format!("failed to register network for bridge {}", network.bridge)
+                })?,
+        ),
+        None => None,
     };
 
-    let paths = VmPaths::new(id, &st.storage).await?;
+    let tap = allocate_tap_name(&st.db, id).await?;
+    let paths = VmPaths::new(id, tap, &st.storage).await?;
 
     // Extract credentials and tags before moving req into resolve_vm_spec
     let username = req.username.clone().unwrap_or_else(|| "root".to_string());
@@ -182,126 +550,248 @@ pub async fn create_and_start(
         .unwrap_or_else(|| format!("vm-{}", &id.to_string()[..8]));
     let tags = req.tags.clone();
 
+    job.check()?;
     let spec = resolve_vm_spec(st, req, id, host.id, &host.addr).await?;
+    progress.emit(
+        id,
+        VmCreateStep::Resolve,
+        VmCreateStepStatus::Ok,
+        format!(
+            "host {}, bridge {}, rootfs {}",
+            host.addr, network.bridge, spec.rootfs_path
+        ),
+    );
 
-    // Inject credentials into rootfs BEFORE VM starts (while rootfs is not in use)
-    // This is the fallback for images without cloud-init
-    if let Err(e) = inject_credentials_to_rootfs(id, &spec.rootfs_path, &username, &password).await
-    {
-        warn!(vm_id = %id, error = ?e, "rootfs credential injection failed (will try cloud-init)");
-    }
+    // Everything up to InstanceStart can be cancelled through /v1/jobs;
+    // after that the VM row is written and the create runs to completion.
+    let booted = async {
+        // Inject credentials into rootfs BEFORE VM starts (while rootfs is not in use)
+        // This is the fallback for images without cloud-init
+        if spec.rootfs_read_only {
+            progress.emit(
+                id,
+                VmCreateStep::Credentials,
+                VmCreateStepStatus::Warning,
+                "shared rootfs is read-only, relying on cloud-init",
+            );
+        } else if let Err(e) =
+            inject_credentials_to_rootfs(id, &spec.rootfs_path, &username, &password).await
+        {
+            warn!(vm_id = %id, error = ?e, "rootfs credential injection failed (will try cloud-init)");
+            progress.emit(
+                id,
+                VmCreateStep::Credentials,
+                VmCreateStepStatus::Warning,
+                format!("rootfs injection failed, relying on cloud-init: {e}"),
+            );
+        } else {
+            progress.emit(
+                id,
+                VmCreateStep::Credentials,
+                VmCreateStepStatus::Ok,
+                format!("injected credentials for {username}"),
+            );
+        }
 
-    // Install guest agent into rootfs BEFORE VM starts (while rootfs is not in use)
-    // Get manager URL from MANAGER_BIND (use bridge IP from network.bridge)
-    let manager_bind =
-        std::env::var("MANAGER_BIND").unwrap_or_else(|_| "127.0.0.1:18080".to_string());
+        job.check()?;
 
-    // Get bridge IP for manager URL (VMs connect via bridge network)
-    let bridge_ip = std::process::Command::new("ip")
-        .args(["addr", "show", &network.bridge])
-        .output()
-        .ok()
-        .and_then(|output| {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if line.trim().starts_with("inet ") {
-                    if let Some(ip_part) = line.split_whitespace().nth(1) {
-                        if let Some(ip) = ip_part.split('/').next() {
-                            return Some(ip.to_string());
+        // Install guest agent into rootfs BEFORE VM starts (while rootfs is not in use)
+        // Get manager URL from MANAGER_BIND (use bridge IP from network.bridge)
+        let manager_bind =
+            std::env::var("MANAGER_BIND").unwrap_or_else(|_| "127.0.0.1:18080".to_string());
+
+        // Get bridge IP for manager URL (VMs connect via bridge network)
+        let bridge_ip = std::process::Command::new("ip")
+            .args(["addr", "show", &network.bridge])
+            .output()
+            .ok()
+            .and_then(|output| {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                for line in stdout.lines() {
+                    if line.trim().starts_with("inet ") {
+                        if let Some(ip_part) = line.split_whitespace().nth(1) {
+                            if let Some(ip) = ip_part.split('/').next() {
+                                return Some(ip.to_string());
+                            }
                         }
                     }
                 }
-            }
-            None
-        })
-        .unwrap_or_else(|| {
-            manager_bind
-                .split(':')
-                .next()
-                .unwrap_or("127.0.0.1")
-                .to_string()
-        });
+                None
+            })
+            .unwrap_or_else(|| {
+                manager_bind
+                    .split(':')
+                    .next()
+                    .unwrap_or("127.0.0.1")
+                    .to_string()
+            });
 
-    let manager_port = manager_bind.split(':').nth(1).unwrap_or("18080");
-    let manager_url = format!("http://{}:{}", bridge_ip, manager_port);
+        let manager_port = manager_bind.split(':').nth(1).unwrap_or("18080");
+        let manager_url = format!("http://{}:{}", bridge_ip, manager_port);
+
+        eprintln!("=== GUEST AGENT INSTALLATION STARTED for VM {} ===", id);
+        eprintln!("Rootfs path: {}", &spec.rootfs_path);
+        eprintln!("Manager bind: {}", manager_bind);
+        eprintln!("Bridge: {}", network.bridge);
+        eprintln!("Bridge IP: {}", bridge_ip);
+        eprintln!("Manager port: {}", manager_port);
+        eprintln!("Manager URL: {}", &manager_url);
+        if spec.rootfs_read_only {
+            progress.emit(
+                id,
+                VmCreateStep::GuestAgent,
+                VmCreateStepStatus::Warning,
+                "shared rootfs is read-only, not installed",
+            );
+        } else if let Err(e) =
+            super::guest_agent::install_to_rootfs(&spec.rootfs_path, id, &manager_url).await
+        {
+            eprintln!("=== GUEST AGENT INSTALLATION FAILED for VM {} ===", id);
+            eprintln!("Error: {:?}", e);
+            warn!(vm_id = %id, error = ?e, "failed to install guest agent (continuing without it)");
+            progress.emit(
+                id,
+                VmCreateStep::GuestAgent,
+                VmCreateStepStatus::Warning,
+                format!("install failed, continuing without it: {e}"),
+            );
+            let _ = audit::log_action(
+                &st.db,
+                None,
+                "system",
+                AuditAction::SystemEvent,
+                Some("vm"),
+                Some(id),
+                Some(json!({"event": "guest_agent_install_failed", "error": e.to_string()})),
+                None,
+                false,
+                Some("guest agent installation failed"),
+            )
+            .await;
+        } else {
+            eprintln!("=== GUEST AGENT INSTALLATION SUCCESS for VM {} ===", id);
+            progress.emit(
+                id,
+                VmCreateStep::GuestAgent,
+                VmCreateStepStatus::Ok,
+                "installed into rootfs",
+            );
+            let _ = audit::log_action(
+                &st.db,
+                None,
+                "system",
+                AuditAction::SystemEvent,
+                Some("vm"),
+                Some(id),
+                Some(json!({"event": "guest_agent_installed"})),
+                None,
+                true,
+                None,
+            )
+            .await;
+        }
 
-    eprintln!("=== GUEST AGENT INSTALLATION STARTED for VM {} ===", id);
-    eprintln!("Rootfs path: {}", &spec.rootfs_path);
-    eprintln!("Manager bind: {}", manager_bind);
-    eprintln!("Bridge: {}", network.bridge);
-    eprintln!("Bridge IP: {}", bridge_ip);
-    eprintln!("Manager port: {}", manager_port);
-    eprintln!("Manager URL: {}", &manager_url);
-    if let Err(e) = super::guest_agent::install_to_rootfs(&spec.rootfs_path, id, &manager_url).await
-    {
-        eprintln!("=== GUEST AGENT INSTALLATION FAILED for VM {} ===", id);
-        eprintln!("Error: {:?}", e);
-        warn!(vm_id = %id, error = ?e, "failed to install guest agent (continuing without it)");
-        let _ = audit::log_action(
-            &st.db,
-            None,
-            "system",
-            AuditAction::SystemEvent,
-            Some("vm"),
-            Some(id),
-            Some(json!({"event": "guest_agent_install_failed", "error": e.to_string()})),
-            None,
-            false,
-            Some("guest agent installation failed"),
-        )
-        .await;
-    } else {
-        eprintln!("=== GUEST AGENT INSTALLATION SUCCESS for VM {} ===", id);
-        let _ = audit::log_action(
-            &st.db,
-            None,
-            "system",
-            AuditAction::SystemEvent,
-            Some("vm"),
-            Some(id),
-            Some(json!({"event": "guest_agent_installed"})),
-            None,
-            true,
-            None,
-        )
-        .await;
-    }
+        job.check()?;
+        create_tap(&host.addr, id, &paths.tap, &network.bridge).await?;
+        progress.emit(
+            id,
+            VmCreateStep::Tap,
+            VmCreateStepStatus::Ok,
+            format!("{} on {}", paths.tap, network.bridge),
+        );
 
-    create_tap(&host.addr, id, &network.bridge).await?;
+        // Activate the rootfs volume on this host. For backends with shared
+        // block storage (iscsi_lvm), this issues `lvchange -aey` so this host
+        // gets exclusive access. No-op for local_file / NFS.
+        if let Some(handle) = spec.rootfs_volume_handle.as_ref() {
+            if let Some(backend) = st.registry.get(handle.backend_id.0) {
+                job.run(backend.activate_volume(handle))
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "activating rootfs volume on backend {}",
+                            handle.backend_id.0
+                        )
+                    })?;
+                progress.emit(
+                    id,
+                    VmCreateStep::Storage,
+                    VmCreateStepStatus::Ok,
+                    format!("activated rootfs volume on backend {}", handle.backend_id.0),
+                );
+            }
+        }
 
-    // Activate the rootfs volume on this host. For backends with shared
-    // block storage (iscsi_lvm), this issues `lvchange -aey` so this host
-    // gets exclusive access. No-op for local_file / NFS.
-    if let Some(handle) = spec.rootfs_volume_handle.as_ref() {
-        if let Some(backend) = st.registry.get(handle.backend_id.0) {
-            backend.activate_volume(handle).await.with_context(|| {
-                format!(
-                    "activating rootfs volume on backend {}",
-                    handle.backend_id.0
-                )
-            })?;
+        progress.emit(
+            id,
+            VmCreateStep::Spawn,
+            VmCreateStepStatus::Started,
+            "waiting for firecracker socket",
+        );
+        job.run(spawn_firecracker(st, &host.addr, id, &paths, spec.scope_limits))
+            .await?;
+        progress.emit(
+            id,
+            VmCreateStep::Spawn,
+            VmCreateStepStatus::Ok,
+            "socket ready",
+        );
+        if std::env::var("MANAGER_TEST_MODE").is_ok() {
+            eprintln!("MANAGER_TEST_MODE: Skipping VM configuration");
+        } else {
+            progress.emit(
+                id,
+                VmCreateStep::Configure,
+                VmCreateStepStatus::Started,
+                "machine config, boot source, drives, network",
+            );
+            job.run(configure_vm(st, &host.addr, id, &spec, &paths))
+                .await?;
+            progress.emit(
+                id,
+                VmCreateStep::Configure,
+                VmCreateStepStatus::Ok,
+                format!("{} vCPU, {} MiB", spec.vcpu, spec.mem_mib),
+            );
         }
-    }
 
-    spawn_firecracker(st, &host.addr, id, &paths).await?;
-    if std::env::var("MANAGER_TEST_MODE").is_ok() {
-        eprintln!("MANAGER_TEST_MODE: Skipping VM configuration");
-    } else {
-        configure_vm(st, &host.addr, id, &spec, &paths).await?;
+        if std::env::var("MANAGER_TEST_MODE").is_ok() {
+            eprintln!("MANAGER_TEST_MODE: Skipping VM start");
+        } else {
+            job.run(start_vm(&host.addr, id, &paths)).await?;
+            progress.emit(
+                id,
+                VmCreateStep::Start,
+                VmCreateStepStatus::Ok,
+                "instance started",
+            );
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    if let Err(err) = booted {
+        if jobs::is_cancelled(&err) {
+            info!(vm_id = %id, job_id = %job.id, "create cancelled, rolling back");
+            roll_back_cancelled_create(st, &host.addr, id, &paths, &spec).await;
+        }
+        return Err(err);
     }
 
-    if std::env::var("MANAGER_TEST_MODE").is_ok() {
-        eprintln!("MANAGER_TEST_MODE: Skipping VM start");
+    // With a readiness wait the row starts as "starting" and is promoted once
+    // the guest agent answers (see the end of this function).
+    let readiness = readiness_timeout().filter(|_| std::env::var("MANAGER_TEST_MODE").is_err());
+    let initial_state = if readiness.is_some() {
+        "starting"
     } else {
-        start_vm(&host.addr, id, &paths).await?;
-    }
+        "running"
+    };
 
     super::repo::insert(
         &st.db,
         &super::repo::VmRow {
             id,
             name: spec.name.clone(),
-            state: "running".into(),
+            state: initial_state.into(),
             host_id: host.id,
             template_id,
             host_addr: host.addr.clone(),
@@ -317,24 +807,39 @@ pub async fn create_and_start(
             source_snapshot_id: None,
             guest_ip: None, // Will be set when guest agent reports
             tags,
-            created_by_user_id: None, // TODO: Set from authenticated user context
+            created_by_user_id: user_id,
             vmm_kind: None,
             guest_os: None,
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: spec.boot_args.clone(),
+            smt: Some(spec.smt),
+            huge_pages: spec.huge_pages.clone(),
+            rootfs_rate_limiter: spec.rootfs_rate_limiter.clone(),
+            entropy_rate_limiter: spec.entropy_rate_limiter.clone(),
+            rootfs_mode: spec
+                .rootfs_read_only
+                .then(|| RootfsMode::Shared.as_str().to_string()),
+            cpu_quota: spec.scope_limits.cpu_quota.map(|q| q as i32),
+            io_weight: spec.scope_limits.io_weight.map(i32::from),
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            state_changed_at: chrono::Utc::now(),
+            started_at: Some(chrono::Utc::now()),
         },
     )
     .await?;
+    if let Err(err) = reservation.release().await {
+        warn!(vm_id = %id, error = ?err, "failed to release quota reservation");
+    }
 
     // Resolve network ID: use explicit selection or auto-register from bridge
-    let network_id_opt = if let Some(nid) = req_network_id {
+    let network_id_opt = if let Some(nid) = req_network_id.or(bridge_network_id) {
         Some(nid)
     } else {
         info!(vm_id = %id, bridge = %network.bridge, host_id = %host.id, "attempting to auto-register network");
-        match ensure_network_registered(st, &network.bridge, host.id).await {
+        match ensure_network_registered(st, &network, host.id).await {
             Ok(network_id) => {
                 info!(vm_id = %id, bridge = %network.bridge, network_id = %network_id, "network auto-registration successful or already exists");
                 Some(network_id)
@@ -394,6 +899,12 @@ pub async fn create_and_start(
             info!(vm_id = %id, volume_id = %handle.volume_id,
                 "rootfs volume attached via handle");
         }
+    } else if let Some(overlay) = spec.rootfs_overlay.as_ref() {
+        // The shared image is not this VM's volume; only its overlay is.
+        if let Err(e) = record_overlay_drive(st, id, overlay).await {
+            warn!(vm_id = %id, volume_id = %overlay.volume_id, error = ?e,
+                "failed to record rootfs overlay drive (it will be missing on restart)");
+        }
     } else {
         info!(vm_id = %id, rootfs = %spec.rootfs_path, host_id = %host.id, "attempting to auto-register rootfs volume");
         match ensure_volume_registered(st, id, &spec.rootfs_path, host.id).await {
@@ -444,22 +955,259 @@ pub async fn create_and_start(
         }
     }
 
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        audit_username,
-        AuditAction::CreateVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
+    progress.emit(
+        id,
+        VmCreateStep::Register,
+        VmCreateStepStatus::Ok,
+        "VM, NIC, volume and credential records saved",
+    );
+
+    if let Some(timeout) = readiness {
+        progress.emit(
+            id,
+            VmCreateStep::Readiness,
+            VmCreateStepStatus::Started,
+            format!("waiting up to {}s for guest agent", timeout.as_secs()),
+        );
+        let state = if wait_for_guest_ready(st, id, timeout).await {
+            info!(vm_id = %id, "guest agent answered readiness probe");
+            "running"
+        } else {
+            warn!(vm_id = %id, timeout_secs = timeout.as_secs(), "guest agent not ready before timeout");
+            let _ = super::repo::insert_event(
+                &st.db,
+                id,
+                "warn",
+                "guest agent did not become ready before the readiness timeout",
+            )
+            .await;
+            RUNNING_DEGRADED
+        };
+        progress.emit(
+            id,
+            VmCreateStep::Readiness,
+            if state == RUNNING_DEGRADED {
+                VmCreateStepStatus::Warning
+            } else {
+                VmCreateStepStatus::Ok
+            },
+            state,
+        );
+        super::repo::update_state(&st.db, id, state).await?;
+    }
+    Ok(())
+}
+
+async fn ensure_host_supports_backend(
+    st: &AppState,
+    host_id: Uuid,
+    req_backend_id: Option<Uuid>,
+) -> Result<()> {
+    let Some(bid) = req_backend_id.or_else(|| st.registry.default_id()) else {
+        return Ok(());
+    };
+    let backend_kind_str = st
+        .registry
+        .get(bid)
+        .map(|b| b.kind().as_db_str().to_string())
+        .unwrap_or_else(|| "local_file".to_string());
+    let host_repo = crate::features::hosts::repo::HostRepository::new(st.db.clone());
+    let kinds = host_repo
+        .supported_backend_kinds(host_id)
+        .await
+        .context("failed to query host supported_backend_kinds")?;
+    // If the host has declared supported kinds AND the requested kind is not among them,
+    // refuse.  An empty list means "unconfigured — allow any" for backward compat.
+    if !kinds.is_empty() && !kinds.iter().any(|k| k == &backend_kind_str) {
+        bail!(bad_request(format!(
+            "host {} does not support backend kind '{}'; supported: {:?}",
+            host_id, backend_kind_str, kinds
+        )));
+    }
+    Ok(())
+}
+
+/// The checks [`validate_create`] makes on `req` alone, before any lookup.
+fn validate_request_fields(req: &CreateVmReq) -> Result<()> {
+    validate_huge_pages(req.huge_pages.as_deref())?;
+    validate_smt(req.smt, req.vcpu)?;
+    validate_rootfs_mode(req.rootfs_mode, req.rootfs_size_mb, req.overlay_size_mb)?;
+    validate_scope_limits(req.cpu_quota, req.io_weight, req.vcpu)?;
+    validate_vm_tags(&req.tags)?;
+    if let Some(limiter) = req.rootfs_rate_limiter.as_ref() {
+        validate_rate_limiter("rootfs_rate_limiter", limiter)?;
+    }
+    if let Some(limiter) = req.entropy_rate_limiter.as_ref() {
+        validate_rate_limiter("entropy_rate_limiter", limiter)?;
+    }
     Ok(())
 }
 
+/// Whether an error from [`validate_create`] is the dry run itself failing
+/// (the database or an HTTP lookup erroring) rather than the request being
+/// refused. A missing row is a refusal: the request named something that
+/// doesn't exist.
+pub fn is_lookup_failure(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| match cause.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => false,
+            Some(_) => true,
+            None => cause.is::<reqwest::Error>(),
+        })
+}
+
+/// Dry run of [`create_and_start`]: check `req` against the same host,
+/// network, image and storage resolution a create does, stopping short of
+/// provisioning the rootfs or touching the host.
+pub async fn validate_create(st: &AppState, req: &CreateVmReq) -> Result<ValidateVmResponse> {
+    validate_request_fields(req)?;
+    let mut warnings = Vec::new();
+
+    let host = st
+        .hosts
+        .first_healthy()
+        .await
+        .context("no healthy hosts available")?;
+    let host_repo = crate::features::hosts::repo::HostRepository::new(st.db.clone());
+    if !host_repo
+        .has_capacity(host.id, i32::from(req.vcpu), i64::from(req.mem_mib))
+        .await
+        .context("failed to query host capacity")?
+    {
+        bail!(bad_request(format!(
+            "host {} has no capacity for {} vCPU / {} MiB",
+            host.id, req.vcpu, req.mem_mib
+        )));
+    }
+
+    if let Some(snapshot_id) = req.source_snapshot_id {
+        st.snapshots
+            .get(snapshot_id)
+            .await
+            .with_context(|| format!("failed to load snapshot {snapshot_id}"))?;
+        warnings.push("restoring from a snapshot: its machine config and disks are used".into());
+        return Ok(ValidateVmResponse {
+            ok: true,
+            would_place_on_host: host.id,
+            warnings,
+        });
+    }
+
+    let kind = req
+        .vmm_kind
+        .or_else(|| req.boot_mode.as_ref().map(::nexus_vmm::auto_select));
+    if matches!(kind, Some(::nexus_vmm::VmmKind::Qemu)) {
+        if req.bridge.is_some() {
+            bail!(bad_request(
+                "invalid bridge: named bridges are only supported for Firecracker VMs"
+            ));
+        }
+        let kinds = host_repo
+            .vmm_kinds_installed(host.id)
+            .await
+            .context("query vmm_kinds_installed")?;
+        if !kinds.iter().any(|k| k == "qemu") {
+            bail!(bad_request(format!(
+                "host {} does not have vmm_kind 'qemu' installed",
+                host.id
+            )));
+        }
+        warnings.push("QEMU VM: boot media are checked by the agent at create time".into());
+        return Ok(ValidateVmResponse {
+            ok: true,
+            would_place_on_host: host.id,
+            warnings,
+        });
+    }
+
+    ensure_host_supports_backend(st, host.id, req.backend_id).await?;
+    if req.network_id.is_some() && req.bridge.is_some() {
+        bail!(bad_request(
+            "invalid bridge: give either network_id or bridge, not both"
+        ));
+    }
+    if let Some(nid) = req.network_id {
+        use crate::features::networks::repo::NetworkRepository;
+        let net = NetworkRepository::new(st.db.clone())
+            .get(nid)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => {
+                    not_found(format!("specified network not found: {}", nid)).into()
+                }
+                err => anyhow::Error::new(err).context("failed to load network"),
+            })?;
+        use crate::features::networks::service::{host_membership, HostMembership};
+        match host_membership(st, &net, host.id).await? {
+            HostMembership::Ready => {}
+            HostMembership::Provision => warnings.push(format!(
+                "{} network {} will be provisioned on host {}",
+                net.type_, net.id, host.id
+            )),
+            HostMembership::Unavailable(reason) => bail!(bad_request(reason)),
+        }
+    } else {
+        select_network(&host.capabilities_json, req.bridge.as_deref())?;
+    }
+
+    resolve_image_path(st, req.kernel_image_id, req.kernel_path.clone(), "kernel").await?;
+    let rootfs =
+        resolve_image_path(st, req.rootfs_image_id, req.rootfs_path.clone(), "rootfs").await?;
+    let pre_copied = rootfs.contains("/containers/") || rootfs.contains("/functions/");
+    if !pre_copied {
+        let backend_id = req
+            .backend_id
+            .or_else(|| st.registry.default_id())
+            .ok_or_else(|| anyhow!("no storage backend selected and no default configured"))?;
+        if st.registry.get(backend_id).is_none() {
+            bail!(not_found(format!("storage backend {backend_id} not found")));
+        }
+    }
+    if req.username.is_none() && req.password.is_none() {
+        warnings
+            .push("no credentials given: the guest login defaults to root / vm-<id prefix>".into());
+    }
+
+    Ok(ValidateVmResponse {
+        ok: true,
+        would_place_on_host: host.id,
+        warnings,
+    })
+}
+
+/// Whether the agent at `host_addr` can open the snapshot's files and
+/// `disk_paths`, the kernel and drives the restored VM needs.
+async fn snapshot_reachable_from(
+    host_addr: &str,
+    snapshot: &SnapshotRow,
+    disk_paths: &[String],
+) -> Result<bool> {
+    #[derive(Deserialize)]
+    struct StatResp {
+        reachable: bool,
+    }
+    let resp: StatResp = crate::core::agent_http::client()
+        .post(format!(
+            "{host_addr}/agent/v1/vms/{}/snapshots/stat",
+            snapshot.vm_id
+        ))
+        .json(&json!({
+            "snapshot_id": snapshot.id,
+            "snapshot_path": snapshot.snapshot_path,
+            "mem_path": snapshot.mem_path,
+            "disk_paths": disk_paths,
+        }))
+        .send()
+        .await?
+        .or_fault()
+        .await
+        .context("checking snapshot files on target host")?
+        .json()
+        .await?;
+    Ok(resp.reachable)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_from_snapshot(
     st: &AppState,
     id: Uuid,
@@ -467,6 +1215,10 @@ pub async fn create_from_snapshot(
     template_id: Option<Uuid>,
     snapshot: SnapshotRow,
     source_vm: Option<super::repo::VmRow>,
+    mem_mib: Option<u32>,
+    target_host_id: Option<Uuid>,
+    force: bool,
+    user_id: Option<Uuid>,
 ) -> Result<()> {
     let SnapshotRow {
         id: source_snapshot_id,
@@ -484,12 +1236,49 @@ pub async fn create_from_snapshot(
     };
     ensure_allowed_path(st, &source_vm.kernel_path)?;
     ensure_allowed_path(st, &source_vm.rootfs_path)?;
+    validate_restore_shape(source_vm.vcpu, source_vm.mem_mib, None, mem_mib)?;
 
-    let host = st
-        .hosts
-        .get(source_vm.host_id)
-        .await
-        .with_context(|| format!("failed to load host {}", source_vm.host_id))?;
+    let host = match target_host_id.filter(|id| *id != source_vm.host_id) {
+        Some(host_id) => {
+            let host = st.hosts.get(host_id).await.map_err(|_| {
+                bad_request(format!("invalid host_id: host {host_id} does not exist"))
+            })?;
+            if !st.hosts.is_alive(host_id).await? {
+                bail!(bad_request(format!(
+                    "invalid host_id: host {} is not healthy",
+                    host.name
+                )));
+            }
+            if host.draining_since.is_some() {
+                bail!(bad_request(format!(
+                    "invalid host_id: host {} is shutting down",
+                    host.name
+                )));
+            }
+            // Nothing copies snapshot files or disks between hosts, so the
+            // target has to see them already (shared storage).
+            let mut disk_paths = vec![source_vm.kernel_path.clone(), source_vm.rootfs_path.clone()];
+            for drive in super::repo::drives::list(&st.db, source_vm.id).await? {
+                if !disk_paths.contains(&drive.path_on_host) {
+                    disk_paths.push(drive.path_on_host);
+                }
+            }
+            disk_paths.retain(|p| !p.is_empty());
+            if !snapshot_reachable_from(&host.addr, &snapshot, &disk_paths).await? {
+                bail!(bad_request(format!(
+                    "invalid host_id: snapshot files or the source VM's kernel/disks are not reachable from host {} and cannot be transferred there",
+                    host.name
+                )));
+            }
+            host
+        }
+        None => st
+            .hosts
+            .get(source_vm.host_id)
+            .await
+            .with_context(|| format!("failed to load host {}", source_vm.host_id))?,
+    };
+    ensure_snapshot_compatible(&snapshot, &host.addr, force).await?;
     let spec = ResolvedVmSpec {
         name: name.clone(),
         vcpu: source_vm
@@ -505,13 +1294,36 @@ pub async fn create_from_snapshot(
         rootfs_is_vhost_user: false,
         rootfs_size_bytes: None,
         rootfs_volume_handle: None,
+        boot_args: source_vm.boot_args.clone(),
+        smt: source_vm.smt.unwrap_or(false),
+        huge_pages: source_vm.huge_pages.clone(),
+        rootfs_rate_limiter: source_vm.rootfs_rate_limiter.clone(),
+        entropy_rate_limiter: source_vm.entropy_rate_limiter.clone(),
+        rootfs_read_only: source_vm.rootfs_mode.as_deref() == Some("shared"),
+        rootfs_overlay: None,
+        scope_limits: ScopeLimits::of(&source_vm),
     };
 
-    let paths = VmPaths::new(id, &st.storage)
+    // The restore belongs to whoever asked for it, so it counts against
+    // their quota; held until the row below is in
+    let reservation = crate::features::users::quota::reserve(
+        &st.db,
+        user_id,
+        nexus_types::UserQuotaUsage {
+            vms: 1,
+            vcpu: spec.vcpu as i64,
+            mem_mib: spec.mem_mib as i64,
+            containers: 0,
+        },
+    )
+    .await?;
+
+    let tap = allocate_tap_name(&st.db, id).await?;
+    let paths = VmPaths::new(id, tap, &st.storage)
         .await?
         .with_snapshot(snapshot_path.clone(), mem_path.clone());
 
-    let network = select_network(&host.capabilities_json)?;
+    let network = select_network(&host.capabilities_json, None)?;
 
     // Install guest agent into rootfs BEFORE VM starts (while rootfs is not in use)
     // Get manager URL from MANAGER_BIND (use bridge IP from network.bridge)
@@ -557,7 +1369,10 @@ pub async fn create_from_snapshot(
     eprintln!("Bridge IP: {}", bridge_ip);
     eprintln!("Manager port: {}", manager_port);
     eprintln!("Manager URL: {}", &manager_url);
-    if let Err(e) = super::guest_agent::install_to_rootfs(&spec.rootfs_path, id, &manager_url).await
+    if spec.rootfs_read_only {
+        info!(vm_id = %id, "shared rootfs is read-only, guest agent not installed");
+    } else if let Err(e) =
+        super::guest_agent::install_to_rootfs(&spec.rootfs_path, id, &manager_url).await
     {
         eprintln!(
             "=== GUEST AGENT INSTALLATION FAILED for VM {} (from snapshot) ===",
@@ -586,8 +1401,8 @@ pub async fn create_from_snapshot(
         .await;
     }
 
-    create_tap(&host.addr, id, &network.bridge).await?;
-    spawn_firecracker(st, &host.addr, id, &paths).await?;
+    create_tap(&host.addr, id, &paths.tap, &network.bridge).await?;
+    spawn_firecracker(st, &host.addr, id, &paths, spec.scope_limits).await?;
     if std::env::var("MANAGER_TEST_MODE").is_ok() {
         eprintln!("MANAGER_TEST_MODE: Skipping VM configuration");
     } else {
@@ -598,6 +1413,13 @@ pub async fn create_from_snapshot(
         eprintln!("MANAGER_TEST_MODE: Skipping VM start");
     } else {
         start_vm(&host.addr, id, &paths).await?;
+        if let Some(mem_mib) = mem_mib {
+            if let Err(e) =
+                expose_restored_memory(&host.addr, id, &paths, spec.mem_mib, mem_mib).await
+            {
+                warn!(vm_id = %id, error = ?e, "failed to deflate balloon after snapshot restore");
+            }
+        }
     }
 
     super::repo::insert(
@@ -621,21 +1443,51 @@ pub async fn create_from_snapshot(
             source_snapshot_id: Some(source_snapshot_id),
             guest_ip: None,               // Will be set when guest agent reports
             tags: source_vm.tags.clone(), // Preserve tags from source VM
-            created_by_user_id: source_vm.created_by_user_id, // Preserve ownership from source VM
+            created_by_user_id: user_id,
             vmm_kind: None,
             guest_os: None,
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: source_vm.boot_args.clone(),
+            smt: source_vm.smt,
+            huge_pages: source_vm.huge_pages.clone(),
+            rootfs_rate_limiter: source_vm.rootfs_rate_limiter.clone(),
+            entropy_rate_limiter: source_vm.entropy_rate_limiter.clone(),
+            rootfs_mode: source_vm.rootfs_mode.clone(),
+            cpu_quota: source_vm.cpu_quota,
+            io_weight: source_vm.io_weight,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            state_changed_at: chrono::Utc::now(),
+            started_at: Some(chrono::Utc::now()),
         },
     )
     .await?;
+    if let Err(err) = reservation.release().await {
+        warn!(vm_id = %id, error = ?err, "failed to release quota reservation");
+    }
+
+    // The restored guest agent resumes with the source VM's identity in
+    // memory and keeps reporting under it. It also keeps the source's
+    // address, so it can only be told apart while the source is down.
+    if std::env::var("MANAGER_TEST_MODE").is_err() {
+        match source_vm.guest_ip.as_deref().filter(|ip| !ip.is_empty()) {
+            Some(ip) if !is_running_state(&source_vm.state) => {
+                if let Err(e) = update_guest_agent_config(ip, id, &manager_url).await {
+                    warn!(vm_id = %id, guest_ip = %ip, error = ?e, "failed to hand the restored guest agent its new identity");
+                }
+            }
+            Some(_) => {
+                info!(vm_id = %id, source_vm_id = %source_vm.id, "source vm is running at the restored guest's address; guest agent keeps the old identity until reboot");
+            }
+            None => {}
+        }
+    }
 
     // Auto-register network if it doesn't exist
     info!(vm_id = %id, bridge = %network.bridge, host_id = %host.id, "attempting to auto-register network");
-    let network_id_opt = match ensure_network_registered(st, &network.bridge, host.id).await {
+    let network_id_opt = match ensure_network_registered(st, &network, host.id).await {
         Ok(network_id) => {
             info!(vm_id = %id, bridge = %network.bridge, network_id = %network_id, "network auto-registration successful or already exists");
             Some(network_id)
@@ -683,6 +1535,71 @@ pub async fn create_from_snapshot(
     Ok(())
 }
 
+/// Check a requested restore shape against the snapshot's source VM.
+/// Firecracker restores with the vCPU count the snapshot was taken with, and
+/// guest memory can only grow into what the VM was booted with.
+pub(crate) fn validate_restore_shape(
+    source_vcpu: i32,
+    source_mem_mib: i32,
+    vcpu: Option<u8>,
+    mem_mib: Option<u32>,
+) -> Result<()> {
+    if let Some(vcpu) = vcpu {
+        if i32::from(vcpu) != source_vcpu {
+            bail!(bad_request(format!(
+                "Invalid vcpu {vcpu}: a Firecracker snapshot restores with the {source_vcpu} vCPUs it was taken with; the count cannot change on restore"
+            )));
+        }
+    }
+    if let Some(mem_mib) = mem_mib {
+        if i64::from(mem_mib) > i64::from(source_mem_mib) {
+            bail!(bad_request(format!(
+                "Invalid mem_mib {mem_mib}: must be at most {source_mem_mib}, the memory the snapshotted VM was booted with; Firecracker cannot hot-plug memory beyond that"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Balloon size that leaves `mem_mib` visible to a guest booted with
+/// `total_mib`, when the current balloon holds back more than that. Restore
+/// only ever deflates; a smaller request leaves the balloon alone.
+fn balloon_deflate_target(total_mib: u32, mem_mib: u32, current_balloon_mib: u64) -> Option<u64> {
+    let target = u64::from(total_mib.saturating_sub(mem_mib));
+    (target < current_balloon_mib).then_some(target)
+}
+
+/// Deflate a freshly restored VM's balloon so the guest sees `mem_mib`.
+async fn expose_restored_memory(
+    host_addr: &str,
+    id: Uuid,
+    paths: &VmPaths,
+    total_mib: u32,
+    mem_mib: u32,
+) -> Result<()> {
+    let base = format!("{host_addr}/agent/v1/vms/{id}/proxy");
+    let qs = format!("?sock={}", urlencoding::encode(&paths.sock));
+    let client = crate::core::agent_http::client();
+
+    let resp = client.get(format!("{base}/balloon{qs}")).send().await?;
+    if !resp.status().is_success() {
+        // No balloon device: the guest already sees all of its memory.
+        return Ok(());
+    }
+    let current: BalloonConfig = resp.json().await?;
+    if let Some(amount_mib) = balloon_deflate_target(total_mib, mem_mib, current.amount_mib) {
+        client
+            .patch(format!("{base}/balloon{qs}"))
+            .json(&json!({ "amount_mib": amount_mib }))
+            .send()
+            .await?
+            .or_fault()
+            .await?;
+        info!(vm_id = %id, amount_mib, "deflated balloon after snapshot restore");
+    }
+    Ok(())
+}
+
 /// Look up the rootfs `VolumeHandle` for a VM, if one exists in the
 /// `volume_attachment` table. Used by activate/deactivate hooks in the
 /// VM lifecycle to call `backend.activate_volume`/`deactivate_volume`.
@@ -692,19 +1609,29 @@ pub async fn create_from_snapshot(
 async fn lookup_rootfs_volume_handle(
     st: &AppState,
     vm_id: Uuid,
+) -> Result<Option<nexus_storage::VolumeHandle>> {
+    lookup_volume_handle(st, vm_id, "rootfs").await
+}
+
+/// [`lookup_rootfs_volume_handle`] for any of the VM's drives.
+async fn lookup_volume_handle(
+    st: &AppState,
+    vm_id: Uuid,
+    drive_id: &str,
 ) -> Result<Option<nexus_storage::VolumeHandle>> {
     let row: Option<(uuid::Uuid, String, Option<uuid::Uuid>, i64)> = sqlx::query_as(
         r#"SELECT v.id, v.path, v.backend_id, v.size_bytes
            FROM volume v
            JOIN volume_attachment va ON va.volume_id = v.id
-           WHERE va.vm_id = $1 AND va.drive_id = 'rootfs'
+           WHERE va.vm_id = $1 AND va.drive_id = $2
            ORDER BY va.attached_at DESC
            LIMIT 1"#,
     )
     .bind(vm_id)
+    .bind(drive_id)
     .fetch_optional(&st.db)
     .await
-    .context("looking up rootfs volume_attachment for handle")?;
+    .with_context(|| format!("looking up {drive_id} volume_attachment for handle"))?;
 
     let Some((volume_id, locator, backend_id, size_bytes)) = row else {
         return Ok(None);
@@ -828,9 +1755,17 @@ pub async fn restart_vm(st: &AppState, vm: &super::repo::VmRow) -> Result<()> {
         rootfs_is_vhost_user,
         rootfs_size_bytes: None,
         rootfs_volume_handle: None,
+        boot_args: vm.boot_args.clone(),
+        smt: vm.smt.unwrap_or(false),
+        huge_pages: vm.huge_pages.clone(),
+        rootfs_rate_limiter: vm.rootfs_rate_limiter.clone(),
+        entropy_rate_limiter: vm.entropy_rate_limiter.clone(),
+        rootfs_read_only: vm.rootfs_mode.as_deref() == Some("shared"),
+        rootfs_overlay: None,
+        scope_limits: ScopeLimits::of(vm),
     };
 
-    let network = select_network(&host.capabilities_json)?;
+    let network = select_network(&host.capabilities_json, None)?;
 
     // Create TAP devices for all NICs (including eth0 and additional NICs)
     create_all_tap_devices(st, &host.addr, vm.id, &network.bridge).await?;
@@ -849,16 +1784,17 @@ pub async fn restart_vm(st: &AppState, vm: &super::repo::VmRow) -> Result<()> {
         }
     }
 
-    spawn_firecracker(st, &host.addr, vm.id, &paths).await?;
+    spawn_firecracker(st, &host.addr, vm.id, &paths, spec.scope_limits).await?;
     configure_vm(st, &host.addr, vm.id, &spec, &paths).await?;
     start_vm(&host.addr, vm.id, &paths).await?;
+    super::repo::mark_started(&st.db, vm.id).await?;
     super::repo::update_state(&st.db, vm.id, "running").await?;
 
     // Spawn background task to configure secondary network interfaces via guest agent
     // This runs asynchronously so restart completes immediately
     let st_clone = st.clone();
     let vm_id = vm.id;
-    tokio::spawn(async move {
+    tokio::spawn(crate::core::request_id::propagate(async move {
         // Wait for guest agent to report IP (retry for up to 60 seconds)
         for attempt in 1..=12 {
             tokio::time::sleep(Duration::from_secs(5)).await;
@@ -909,17 +1845,82 @@ pub async fn restart_vm(st: &AppState, vm: &super::repo::VmRow) -> Result<()> {
         }
 
         warn!(vm_id=%vm_id, "timeout waiting for guest IP, skipping secondary NIC configuration");
-    });
+    }));
+
+    Ok(())
+}
+
+const DEFAULT_GRACEFUL_STOP_TIMEOUT_SECS: u64 = 30;
+const MAX_GRACEFUL_STOP_TIMEOUT_SECS: u64 = 300;
+/// Longest a single inventory poll may take, so a hung agent can't hold the
+/// graceful stop past its deadline.
+const SCOPE_POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Stop a VM, first asking the guest to shut down cleanly.
+///
+/// Sends Ctrl-Alt-Del and polls the host inventory until the VM's scope is
+/// gone or `timeout_secs` elapses, then runs the regular [`stop_only`]
+/// teardown. QEMU VMs skip straight to `stop_only`, whose destroy path
+/// already issues a QMP shutdown.
+pub async fn stop_graceful(st: &AppState, id: Uuid, timeout_secs: Option<u64>) -> Result<()> {
+    let vm = super::repo::get(&st.db, id).await?;
+    let timeout_secs = timeout_secs
+        .unwrap_or(DEFAULT_GRACEFUL_STOP_TIMEOUT_SECS)
+        .clamp(1, MAX_GRACEFUL_STOP_TIMEOUT_SECS);
+
+    if vm.vmm_kind.as_deref() != Some("qemu") && is_running_state(&vm.state) {
+        match send_ctrl_alt_del(st, id).await {
+            Ok(()) => {
+                tracing::info!(vm_id = %id, timeout_secs, "sent Ctrl-Alt-Del, waiting for guest shutdown");
+                let deadline =
+                    tokio::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
+                loop {
+                    let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+                    if !vm_scope_running(&vm, remaining.min(SCOPE_POLL_TIMEOUT)).await {
+                        tracing::info!(vm_id = %id, "guest shut down cleanly");
+                        break;
+                    }
+                    if tokio::time::Instant::now() >= deadline {
+                        tracing::warn!(vm_id = %id, timeout_secs, "guest did not shut down in time, forcing stop");
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+            Err(e) => {
+                tracing::warn!(vm_id = %id, error = ?e, "failed to send Ctrl-Alt-Del, forcing stop");
+            }
+        }
+    }
+
+    stop_only(st, id).await
+}
+
+/// Whether the host inventory still lists the VM's scope. Errors reaching the
+/// agent count as "still running" so the caller keeps waiting until timeout.
+/// The whole poll, body included, is cut off after `timeout`.
+async fn vm_scope_running(vm: &super::repo::VmRow, timeout: std::time::Duration) -> bool {
+    let poll = async {
+        let resp = crate::core::agent_http::client()
+            .get(format!("{}/agent/v1/inventory", vm.host_addr))
+            .send()
+            .await
+            .ok()?
+            .error_for_status()
+            .ok()?;
+        resp.json::<serde_json::Value>().await.ok()
+    };
+    let inventory = tokio::time::timeout(timeout, poll).await.ok().flatten();
+    inventory.is_none_or(|inv| inventory_lists_scope(&inv, &vm.fc_unit))
+}
 
-    Ok(())
+fn inventory_lists_scope(inventory: &serde_json::Value, fc_unit: &str) -> bool {
+    inventory["scopes"]
+        .as_array()
+        .is_some_and(|scopes| scopes.iter().any(|s| s.as_str() == Some(fc_unit)))
 }
 
-pub async fn stop_only(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
+pub async fn stop_only(st: &AppState, id: Uuid) -> Result<()> {
     let vm = super::repo::get(&st.db, id).await?;
     super::repo::update_state(&st.db, id, "stopping").await?;
 
@@ -939,36 +1940,48 @@ pub async fn stop_only(
         .unwrap_or_else(|_| "firecracker".to_string());
 
     if vmm_kind == "qemu" {
-        let resp = reqwest::Client::new()
+        let resp = crate::core::agent_http::client()
             .post(format!(
                 "{}/agent/v1/vmm/{}/destroy?vmm_kind=qemu",
                 vm.host_addr, vm.id
             ))
             .send()
             .await?;
-        resp.error_for_status()?;
+        resp.or_fault().await?;
         // Mark stopped (the QEMU destroy succeeded); otherwise the row is left
         // in the transient "stopping" state forever.
         super::repo::update_state(&st.db, id, "stopped").await?;
-        // Drop into the same volume_attachment detach / log housekeeping
-        // below so iSCSI sessions get cleaned up correctly. The audit log
-        // entry at the bottom of this function still fires.
-        let _ = (user_id, username);
         return Ok(());
     }
 
-    let response = reqwest::Client::new()
+    let response = crate::core::agent_http::client()
         .post(format!("{}/agent/v1/vms/{}/stop", vm.host_addr, vm.id))
         .json(&serde_json::json!({
             "tap": vm.tap,
             "sock": vm.api_sock,
             "fc_unit": vm.fc_unit,
+            "vsock_uds": super::guest_vsock::uds_path(&vm.api_sock, vm.id),
             // Do NOT send storage_path - drives are persisted for restart
         }))
         .send()
         .await?;
 
-    response.error_for_status()?;
+    let response = response.or_fault().await?;
+    match response.json::<StopVmReport>().await {
+        Ok(report) => match stop_report_leaks(&report) {
+            Ok(leaks) => {
+                for leak in leaks {
+                    warn!(vm_id=%id, %leak, "agent stop left an artifact behind");
+                    let _ = super::repo::insert_event(&st.db, id, "warn", &leak).await;
+                }
+            }
+            Err(err) => {
+                super::repo::update_state(&st.db, id, &vm.state).await?;
+                return Err(err);
+            }
+        },
+        Err(err) => warn!(vm_id=%id, error=%err, "agent stop returned no teardown report"),
+    }
 
     // Detach non-LocalFile volumes (e.g. log out iSCSI sessions). LocalFile is
     // a no-op at the agent level but we skip it to avoid an unnecessary RPC.
@@ -1075,32 +2088,32 @@ pub async fn stop_only(
     }
 
     super::repo::update_state(&st.db, id, "stopped").await?;
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::StopVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
     Ok(())
 }
 
-pub async fn stop_and_delete(st: &AppState, id: Uuid) -> Result<()> {
-    stop_and_delete_with_user(st, id, None, "system").await
+/// Failures the stop can live with, as event messages. A scope that could not
+/// be stopped is an error instead: the VM may still be running, so it must not
+/// be marked stopped.
+fn stop_report_leaks(report: &StopVmReport) -> Result<Vec<String>> {
+    if report.scope.failed() {
+        bail!(
+            "agent failed to stop vm scope: {}",
+            report.scope.error.as_deref().unwrap_or("unknown error")
+        );
+    }
+    Ok(report
+        .failures()
+        .into_iter()
+        .map(|(artifact, result)| {
+            format!(
+                "stop left {artifact} behind: {}",
+                result.error.as_deref().unwrap_or("unknown error")
+            )
+        })
+        .collect())
 }
 
-pub async fn stop_and_delete_with_user(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
+pub async fn stop_and_delete(st: &AppState, id: Uuid) -> Result<()> {
     // Capture host + reservation before we delete the row, so we can release
     // capacity afterwards even on the failure path.
     let pre_delete: Option<(Uuid, i32, i32)> =
@@ -1111,7 +2124,7 @@ pub async fn stop_and_delete_with_user(
             .ok()
             .flatten();
 
-    if let Err(err) = stop_only(st, id, None, "system").await {
+    if let Err(err) = stop_only(st, id).await {
         tracing::warn!(vm_id = %id, error = ?err, "failed to stop vm before deletion");
     }
 
@@ -1124,6 +2137,16 @@ pub async fn stop_and_delete_with_user(
         info!(vm_id = %id, path = ?storage_path, "cleaned up VM storage directory");
     }
 
+    // The overlay belongs to this VM alone, so it goes with it; find it
+    // while its attachment row still exists.
+    let overlay = match lookup_volume_handle(st, id, OVERLAY_DRIVE_ID).await {
+        Ok(overlay) => overlay,
+        Err(e) => {
+            tracing::warn!(vm_id = %id, error = ?e, "failed to look up rootfs overlay; leaving it");
+            None
+        }
+    };
+
     // Reset volume statuses and mark active attachments detached before cascading delete removes the rows
     let volume_repo = crate::features::volumes::repo::VolumeRepository::new(st.db.clone());
     let attached_vols: Vec<(Uuid,)> =
@@ -1149,6 +2172,10 @@ pub async fn stop_and_delete_with_user(
     // Delete from database (this cascades to vm_drive and vm_network_interface)
     super::repo::delete_row(&st.db, id).await?;
 
+    if let Some(overlay) = overlay {
+        delete_overlay(st, id, overlay).await;
+    }
+
     // Release the host's vcpu/mem reservation so subsequent VMs can land on
     // this host. Best-effort — the row is gone either way, so a release
     // failure shouldn't surface to the caller.
@@ -1162,35 +2189,32 @@ pub async fn stop_and_delete_with_user(
         }
     }
 
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::DeleteVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
     Ok(())
 }
 
-pub async fn start_vm_by_id(st: &AppState, id: Uuid) -> Result<()> {
-    start_vm_by_id_with_user(st, id, None, "system").await
+/// Destroy a deleted VM's rootfs overlay on its backend and drop its
+/// `volume` row. Best-effort: the VM is already gone.
+async fn delete_overlay(st: &AppState, vm_id: Uuid, overlay: nexus_storage::VolumeHandle) {
+    let volume_id = overlay.volume_id;
+    if let Some(backend) = st.registry.get(overlay.backend_id.0) {
+        if let Err(e) = backend.destroy(overlay).await {
+            tracing::warn!(vm_id = %vm_id, %volume_id, error = ?e, "failed to destroy rootfs overlay");
+            return;
+        }
+    }
+    let volume_repo = crate::features::volumes::repo::VolumeRepository::new(st.db.clone());
+    match volume_repo.delete(volume_id).await {
+        Ok(_) => info!(vm_id = %vm_id, %volume_id, "deleted rootfs overlay"),
+        Err(e) => {
+            tracing::warn!(vm_id = %vm_id, %volume_id, error = ?e, "failed to delete rootfs overlay volume row")
+        }
+    }
 }
 
-pub async fn start_vm_by_id_with_user(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
+pub async fn start_vm_by_id(st: &AppState, id: Uuid) -> Result<()> {
     let vm = super::repo::get(&st.db, id).await?;
 
-    if vm.state == "running" {
+    if is_running_state(&vm.state) {
         return Ok(()); // Already running
     }
 
@@ -1202,116 +2226,109 @@ pub async fn start_vm_by_id_with_user(
     } else {
         restart_vm(st, &vm).await?;
     }
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::StartVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
     Ok(())
 }
 
-pub async fn pause_vm(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
-    let vm = super::repo::get(&st.db, id).await?;
+pub async fn pause_vm(st: &AppState, id: Uuid) -> Result<()> {
+    pause(&st.db, id).await
+}
+
+async fn pause(db: &PgPool, id: Uuid) -> Result<()> {
+    let vm = super::repo::get(db, id).await?;
 
-    if vm.state != "running" {
-        bail!("VM must be running to pause");
+    if !is_running_state(&vm.state) {
+        bail!(bad_request("VM must be running to pause"));
     }
 
-    super::repo::update_state(&st.db, id, "pausing").await?;
+    super::repo::update_state(db, id, "pausing").await?;
+    if let Err(err) = set_vmm_state(&vm, "Paused").await {
+        // The VM is still running as it was
+        let _ = super::repo::update_state(db, id, &vm.state).await;
+        return Err(err.context("pause failed"));
+    }
+    super::repo::set_paused_from(db, id, &vm.state).await?;
+    super::repo::update_state(db, id, "paused").await?;
+    Ok(())
+}
 
+/// PATCH the VMM's `/vm` state (`Paused` / `Resumed`) through the agent.
+async fn set_vmm_state(vm: &super::repo::VmRow, state: &str) -> Result<()> {
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build reqwest client (pause_vm)")?;
-
-    let response = client
+    let response = crate::core::agent_http::client()
         .patch(format!("{base}/vm{qs}"))
-        .json(&serde_json::json!({
-            "state": "Paused"
-        }))
+        .json(&serde_json::json!({ "state": state }))
+        .timeout(Duration::from_secs(10))
         .send()
         .await?;
 
-    response.error_for_status()?;
-    super::repo::update_state(&st.db, id, "paused").await?;
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::PauseVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
+    response.or_fault().await?;
     Ok(())
 }
 
-pub async fn resume_vm(
-    st: &AppState,
-    id: Uuid,
-    user_id: Option<Uuid>,
-    username: &str,
-) -> Result<()> {
-    let vm = super::repo::get(&st.db, id).await?;
+pub async fn resume_vm(st: &AppState, id: Uuid) -> Result<()> {
+    resume(&st.db, id).await
+}
+
+async fn resume(db: &PgPool, id: Uuid) -> Result<()> {
+    let vm = super::repo::get(db, id).await?;
 
     if vm.state != "paused" {
-        bail!("VM must be paused to resume");
+        bail!(bad_request("VM must be paused to resume"));
     }
+    // Back to what it was paused from, so a degraded VM stays degraded
+    let resume_to = super::repo::paused_from(db, id)
+        .await?
+        .filter(|state| is_running_state(state))
+        .unwrap_or_else(|| "running".to_string());
 
-    super::repo::update_state(&st.db, id, "resuming").await?;
+    super::repo::update_state(db, id, "resuming").await?;
+    if let Err(err) = set_vmm_state(&vm, "Resumed").await {
+        let _ = super::repo::update_state(db, id, "paused").await;
+        return Err(err.context("resume failed"));
+    }
+    super::repo::update_state(db, id, &resume_to).await?;
+    Ok(())
+}
 
-    let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
-    let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
+/// Holds a VM paused by [`pause_vm`]. [`Self::resume`] resumes it; dropped
+/// without that, e.g. on an early error return or a cancelled request, it
+/// resumes the VM in the background so the VM isn't left stuck paused.
+pub struct PauseGuard {
+    st: AppState,
+    vm_id: Uuid,
+    armed: bool,
+}
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build reqwest client (resume_vm)")?;
+impl PauseGuard {
+    pub async fn pause(st: &AppState, vm_id: Uuid) -> Result<Self> {
+        pause_vm(st, vm_id).await?;
+        Ok(Self {
+            st: st.clone(),
+            vm_id,
+            armed: true,
+        })
+    }
 
-    let response = client
-        .patch(format!("{base}/vm{qs}"))
-        .json(&serde_json::json!({
-            "state": "Resumed"
-        }))
-        .send()
-        .await?;
+    pub async fn resume(mut self) -> Result<()> {
+        self.armed = false;
+        resume_vm(&self.st, self.vm_id).await
+    }
+}
 
-    response.error_for_status()?;
-    super::repo::update_state(&st.db, id, "running").await?;
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        username,
-        AuditAction::ResumeVm,
-        Some("vm"),
-        Some(id),
-        None,
-        None,
-        true,
-        None,
-    )
-    .await;
-    Ok(())
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let (st, vm_id) = (self.st.clone(), self.vm_id);
+        tokio::spawn(async move {
+            if let Err(err) = resume_vm(&st, vm_id).await {
+                warn!(%vm_id, error = ?err, "failed to resume vm after an aborted operation");
+            }
+        });
+    }
 }
 
 pub async fn flush_vm_metrics(st: &AppState, id: Uuid) -> Result<()> {
@@ -1319,7 +2336,7 @@ pub async fn flush_vm_metrics(st: &AppState, id: Uuid) -> Result<()> {
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    let response = reqwest::Client::new()
+    let response = crate::core::agent_http::client()
         .put(format!("{base}/actions{qs}"))
         .json(&serde_json::json!({
             "action_type": "FlushMetrics"
@@ -1327,7 +2344,7 @@ pub async fn flush_vm_metrics(st: &AppState, id: Uuid) -> Result<()> {
         .send()
         .await?;
 
-    response.error_for_status()?;
+    response.or_fault().await?;
 
     Ok(())
 }
@@ -1354,10 +2371,11 @@ struct GuestMetrics {
 pub async fn get_process_stats(st: &AppState, id: Uuid) -> Result<ProcessStats> {
     let vm = super::repo::get(&st.db, id).await?;
 
-    // Try to get metrics from guest agent first (if guest_ip is set)
-    if let Some(guest_ip) = &vm.guest_ip {
-        if let Ok(guest_metrics) = get_guest_metrics(guest_ip).await {
-            // Convert guest metrics to ProcessStats format
+    // Try the guest agent first, over vsock or the guest IP
+    match super::guest_vsock::guest_metrics::<GuestMetrics>(&crate::core::agent_http::client(), &vm)
+        .await
+    {
+        Ok(guest_metrics) => {
             return Ok(ProcessStats {
                 pid: 0, // Not applicable for guest metrics
                 cpu_percent: guest_metrics.cpu_usage_percent,
@@ -1366,7 +2384,9 @@ pub async fn get_process_stats(st: &AppState, id: Uuid) -> Result<ProcessStats>
             });
         }
         // If guest agent fails, fall through to host-side metrics
-        tracing::debug!(vm_id = %id, guest_ip = %guest_ip, "Guest agent unavailable, falling back to host-side metrics");
+        Err(err) => {
+            tracing::debug!(vm_id = %id, error = %err, "Guest agent unavailable, falling back to host-side metrics")
+        }
     }
 
     // Fallback: Get host-side process stats via agent
@@ -1375,7 +2395,7 @@ pub async fn get_process_stats(st: &AppState, id: Uuid) -> Result<ProcessStats>
         vm.host_addr, vm.id
     );
 
-    let response = reqwest::Client::new()
+    let response = crate::core::agent_http::client()
         .post(&url)
         .json(&serde_json::json!({
             "sock_path": vm.api_sock
@@ -1383,34 +2403,22 @@ pub async fn get_process_stats(st: &AppState, id: Uuid) -> Result<ProcessStats>
         .send()
         .await?;
 
-    let stats = response.error_for_status()?.json::<ProcessStats>().await?;
+    let stats = response.or_fault().await?.json::<ProcessStats>().await?;
 
     Ok(stats)
 }
 
-async fn get_guest_metrics(guest_ip: &str) -> Result<GuestMetrics> {
-    let url = format!("http://{}:9000/metrics", guest_ip);
-    let response = reqwest::Client::new()
-        .get(&url)
-        .timeout(std::time::Duration::from_secs(2))
-        .send()
-        .await?;
-
-    let metrics = response.error_for_status()?.json::<GuestMetrics>().await?;
-    Ok(metrics)
-}
-
 pub async fn send_ctrl_alt_del(st: &AppState, id: Uuid) -> Result<()> {
     let vm = super::repo::get(&st.db, id).await?;
 
-    if vm.state != "running" {
-        bail!("VM must be running to send Ctrl-Alt-Del");
+    if !is_running_state(&vm.state) {
+        bail!(bad_request("VM must be running to send Ctrl-Alt-Del"));
     }
 
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    let response = reqwest::Client::new()
+    let response = crate::core::agent_http::client()
         .put(format!("{base}/actions{qs}"))
         .json(&serde_json::json!({
             "action_type": "SendCtrlAltDel"
@@ -1418,7 +2426,7 @@ pub async fn send_ctrl_alt_del(st: &AppState, id: Uuid) -> Result<()> {
         .send()
         .await?;
 
-    response.error_for_status()?;
+    response.or_fault().await?;
 
     Ok(())
 }
@@ -1435,13 +2443,17 @@ struct VmPaths {
 }
 
 impl VmPaths {
-    async fn new(id: Uuid, storage: &crate::features::storage::LocalStorage) -> Result<Self> {
+    async fn new(
+        id: Uuid,
+        tap: String,
+        storage: &crate::features::storage::LocalStorage,
+    ) -> Result<Self> {
         storage.ensure_vm_dirs(id).await?;
         Ok(Self {
             sock: storage.sock_path(id),
             log_path: storage.log_path(id),
             metrics_path: storage.metrics_path(id),
-            tap: format!("tap-{}", &id.to_string()[..8]),
+            tap,
             fc_unit: format!("fc-{id}.scope"),
             snapshot_path: None,
             mem_path: None,
@@ -1484,6 +2496,38 @@ struct ResolvedVmSpec {
     /// or when the VM was created from a snapshot.
     #[allow(dead_code)]
     rootfs_volume_handle: Option<nexus_storage::VolumeHandle>,
+    /// User-supplied kernel parameters, merged by `build_boot_args`.
+    boot_args: Option<String>,
+    smt: bool,
+    huge_pages: Option<String>,
+    /// The VM's own rootfs rate limiter; the manager default applies when `None`.
+    rootfs_rate_limiter: Option<Value>,
+    entropy_rate_limiter: Option<Value>,
+    /// `rootfs_mode: shared`: the image itself is attached read-only.
+    rootfs_read_only: bool,
+    /// The shared rootfs's writable overlay on first boot. Later boots find
+    /// it among the VM's drives.
+    rootfs_overlay: Option<nexus_storage::VolumeHandle>,
+    scope_limits: ScopeLimits,
+}
+
+/// cgroup limits the agent puts on the VM's `fc-{id}.scope` when it spawns
+/// Firecracker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ScopeLimits {
+    /// `CPUQuota=`, in percent of one host CPU.
+    cpu_quota: Option<u32>,
+    /// `IOWeight=`.
+    io_weight: Option<u16>,
+}
+
+impl ScopeLimits {
+    fn of(vm: &super::repo::VmRow) -> Self {
+        Self {
+            cpu_quota: vm.cpu_quota.and_then(|q| u32::try_from(q).ok()),
+            io_weight: vm.io_weight.and_then(|w| u16::try_from(w).ok()),
+        }
+    }
 }
 
 async fn resolve_vm_spec(
@@ -1495,17 +2539,33 @@ async fn resolve_vm_spec(
 ) -> Result<ResolvedVmSpec> {
     let kernel_path =
         resolve_image_path(st, req.kernel_image_id, req.kernel_path, "kernel").await?;
-    let (rootfs_path, rootfs_size_bytes, rootfs_volume_handle) = provision_rootfs(
-        st,
-        req.rootfs_image_id,
-        req.rootfs_path,
-        vm_id,
-        req.rootfs_size_mb,
-        req.backend_id,
-        vm_host_id,
-        host_addr,
-    )
-    .await?;
+    let shared = req.rootfs_mode == Some(RootfsMode::Shared);
+    let (rootfs_path, rootfs_size_bytes, rootfs_volume_handle, rootfs_overlay) = if shared {
+        let image_path =
+            resolve_image_path(st, req.rootfs_image_id, req.rootfs_path, "rootfs").await?;
+        let overlay = provision_overlay(
+            st,
+            vm_id,
+            req.overlay_size_mb.unwrap_or(DEFAULT_OVERLAY_SIZE_MB),
+            req.backend_id,
+            vm_host_id,
+        )
+        .await?;
+        (image_path, None, None, Some(overlay))
+    } else {
+        let (path, size_bytes, handle) = provision_rootfs(
+            st,
+            req.rootfs_image_id,
+            req.rootfs_path,
+            vm_id,
+            req.rootfs_size_mb,
+            req.backend_id,
+            vm_host_id,
+            host_addr,
+        )
+        .await?;
+        (path, size_bytes, handle, None)
+    };
 
     Ok(ResolvedVmSpec {
         name: req.name,
@@ -1516,9 +2576,143 @@ async fn resolve_vm_spec(
         rootfs_is_vhost_user: false,
         rootfs_size_bytes,
         rootfs_volume_handle,
+        boot_args: req
+            .boot_args
+            .map(|args| args.trim().to_string())
+            .filter(|args| !args.is_empty()),
+        smt: req.smt.unwrap_or(false),
+        huge_pages: req.huge_pages,
+        rootfs_rate_limiter: req.rootfs_rate_limiter,
+        entropy_rate_limiter: req.entropy_rate_limiter,
+        rootfs_read_only: shared,
+        rootfs_overlay,
+        scope_limits: ScopeLimits {
+            cpu_quota: req.cpu_quota,
+            io_weight: req.io_weight,
+        },
     })
 }
 
+/// The overlay's `vm_drive` and `volume_attachment` rows, so restarts attach
+/// it again and deleting the VM releases it.
+async fn record_overlay_drive(
+    st: &AppState,
+    vm_id: Uuid,
+    overlay: &nexus_storage::VolumeHandle,
+) -> Result<()> {
+    super::repo::drives::insert(
+        &st.db,
+        vm_id,
+        OVERLAY_DRIVE_ID,
+        &overlay.locator,
+        Some(overlay.size_bytes as i64),
+        false,
+        false,
+        None,
+        None,
+        None,
+    )
+    .await?;
+    sqlx::query(
+        r#"INSERT INTO volume_attachment (volume_id, vm_id, drive_id) VALUES ($1, $2, $3)
+           ON CONFLICT DO NOTHING"#,
+    )
+    .bind(overlay.volume_id)
+    .bind(vm_id)
+    .bind(OVERLAY_DRIVE_ID)
+    .execute(&st.db)
+    .await
+    .context("inserting overlay volume_attachment row")?;
+    Ok(())
+}
+
+/// Overlay disk size when `overlay_size_mb` is not given.
+const DEFAULT_OVERLAY_SIZE_MB: u32 = 1024;
+/// Drive id of a shared rootfs's writable overlay.
+const OVERLAY_DRIVE_ID: &str = "overlay";
+
+fn validate_rootfs_mode(
+    mode: Option<RootfsMode>,
+    rootfs_size_mb: Option<u32>,
+    overlay_size_mb: Option<u32>,
+) -> Result<()> {
+    if mode != Some(RootfsMode::Shared) {
+        if overlay_size_mb.is_some() {
+            bail!(bad_request(
+                "invalid overlay_size_mb: only a shared rootfs has an overlay"
+            ));
+        }
+        return Ok(());
+    }
+    if rootfs_size_mb.is_some() {
+        bail!(bad_request(
+            "invalid rootfs_size_mb: a shared rootfs is never resized"
+        ));
+    }
+    if overlay_size_mb == Some(0) {
+        bail!(bad_request(
+            "invalid overlay_size_mb: must be greater than 0"
+        ));
+    }
+    Ok(())
+}
+
+/// systemd's bounds, plus a CPU quota no larger than the vCPUs can use.
+fn validate_scope_limits(cpu_quota: Option<u32>, io_weight: Option<u16>, vcpu: u8) -> Result<()> {
+    let max_quota = u32::from(vcpu.max(1)) * 100;
+    if cpu_quota.is_some_and(|q| q == 0 || q > max_quota) {
+        bail!(bad_request(format!(
+            "invalid cpu_quota: must be between 1 and {max_quota} (100 per vCPU)"
+        )));
+    }
+    if io_weight.is_some_and(|w| !(1..=10000).contains(&w)) {
+        bail!(bad_request(
+            "invalid io_weight: must be between 1 and 10000"
+        ));
+    }
+    Ok(())
+}
+
+/// Blank writable disk for a shared rootfs, recorded as a volume like the
+/// data disks `create_drive` provisions. The `vm_drive` row follows once the
+/// VM row exists.
+async fn provision_overlay(
+    st: &AppState,
+    vm_id: Uuid,
+    size_mb: u32,
+    req_backend_id: Option<Uuid>,
+    vm_host_id: Uuid,
+) -> Result<nexus_storage::VolumeHandle> {
+    let backend_id = req_backend_id
+        .or_else(|| st.registry.default_id())
+        .ok_or_else(|| anyhow::anyhow!("no storage backend selected and no default configured"))?;
+    let name = format!("overlay-{vm_id}");
+    let handle = crate::features::storage::rootfs_allocator::allocate_data_disk(
+        &st.registry,
+        backend_id,
+        size_mb as u64 * 1024 * 1024,
+        &name,
+    )
+    .await
+    .context("failed to provision rootfs overlay via storage registry")?;
+
+    sqlx::query(
+        r#"INSERT INTO volume (id, name, path, size_bytes, type, status, host_id, backend_id)
+           VALUES ($1, $2, $3, $4, 'raw', 'available', $5, $6)
+           ON CONFLICT (path) DO NOTHING"#,
+    )
+    .bind(handle.volume_id)
+    .bind(&name)
+    .bind(&handle.locator)
+    .bind(handle.size_bytes as i64)
+    .bind(st.host_id_for_local_file(vm_host_id))
+    .bind(backend_id)
+    .execute(&st.db)
+    .await
+    .context("failed to record rootfs overlay volume")?;
+    Ok(handle)
+}
+
 async fn resolve_image_path(
     st: &AppState,
     image_id: Option<Uuid>,
@@ -1537,13 +2731,15 @@ async fn resolve_image_path(
 
     if let Some(path) = direct_path {
         if !st.allow_direct_image_paths {
-            bail!("{field} path not permitted in production mode");
+            bail!(bad_request(format!(
+                "{field} path not permitted in production mode"
+            )));
         }
         ensure_allowed_path(st, &path)?;
         return Ok(path);
     }
 
-    Err(anyhow!("{field} requires an image id or host path"))
+    Err(bad_request(format!("{field} requires an image id or host path")).into())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1568,12 +2764,12 @@ async fn provision_rootfs(
         image.host_path
     } else if let Some(path) = direct_path {
         if !st.allow_direct_image_paths {
-            bail!("rootfs path not permitted in production mode");
+            bail!(bad_request("rootfs path not permitted in production mode"));
         }
         ensure_allowed_path(st, &path)?;
         path
     } else {
-        bail!("rootfs requires an image id or host path")
+        bail!(bad_request("rootfs requires an image id or host path"))
     };
 
     // Check if this is already a per-VM copy (from containers/functions feature)
@@ -1670,7 +2866,7 @@ async fn provision_rootfs(
     ))
 }
 
-fn ensure_allowed_path(st: &AppState, path: &str) -> Result<()> {
+pub(crate) fn ensure_allowed_path(st: &AppState, path: &str) -> Result<()> {
     let candidate = Path::new(path);
 
     // Allow paths within the image root
@@ -1687,7 +2883,9 @@ fn ensure_allowed_path(st: &AppState, path: &str) -> Result<()> {
         return Ok(());
     }
 
-    bail!("path {path} is not within the configured image root or storage root");
+    bail!(bad_request(format!(
+        "path {path} is not within the configured image root or storage root"
+    )));
 }
 
 pub async fn list_drives(st: &AppState, vm_id: Uuid) -> Result<Vec<nexus_types::VmDrive>> {
@@ -1700,6 +2898,10 @@ pub async fn create_drive(
     vm_id: Uuid,
     req: CreateDriveReq,
 ) -> Result<nexus_types::VmDrive> {
+    if let Some(rate_limiter) = req.rate_limiter.as_ref() {
+        validate_rate_limiter("rate_limiter", rate_limiter)?;
+    }
+
     // Verify VM exists and get its host assignment
     let vm = super::repo::get(&st.db, vm_id).await?;
 
@@ -1759,10 +2961,10 @@ pub async fn create_drive(
         .iter()
         .any(|d| d.drive_id == req.drive_id)
     {
-        bail!("drive_id already exists for this VM");
+        bail!(conflict("drive_id already exists for this VM"));
     }
 
-    // Insert into database ONLY - drive will be applied on next VM start
+    // Persist first so the drive is applied on every later start.
     let drive = super::repo::drives::insert(
         &st.db,
         vm_id,
@@ -1777,6 +2979,8 @@ pub async fn create_drive(
     )
     .await?;
 
+    // Firecracker only takes `PUT /drives` before InstanceStart, so a running
+    // Firecracker VM gets the drive from `configure_vm` on its next start.
     info!(vm_id = %vm_id, drive_id = %req.drive_id, path = %host_path,
           "Drive created in database, will be attached on next VM start");
 
@@ -1791,7 +2995,7 @@ pub async fn create_drive(
     // without a restart. Best-effort: the drive is already persisted, so on any
     // failure it still attaches on the next boot (restart_qemu reads the DB).
     // Firecracker VMs use the legacy proxy path and are unaffected.
-    if vm.state == "running" && vm.vmm_kind.as_deref() == Some("qemu") {
+    if is_running_state(&vm.state) && vm.vmm_kind.as_deref() == Some("qemu") {
         let fmt = crate::features::vms::qemu_service::probe_disk_format(&host_path).await;
         let body = serde_json::json!({
             "vmm_kind": "qemu",
@@ -1801,7 +3005,7 @@ pub async fn create_drive(
             "read_only": req.is_read_only,
             "cdrom": false,
         });
-        match reqwest::Client::new()
+        match crate::core::agent_http::client()
             .post(format!("{}/agent/v1/vmm/{}/disk/add", vm.host_addr, vm.id))
             .json(&body)
             .send()
@@ -1821,15 +3025,39 @@ pub async fn create_drive(
     Ok(drive.into())
 }
 
+/// Firecracker `PUT /drives/{id}` body for a stored drive; optional fields
+/// are only sent when set.
+fn drive_config(drive: &super::repo::VmDrive) -> serde_json::Value {
+    let mut config = serde_json::json!({
+        "drive_id": drive.drive_id,
+        "path_on_host": drive.path_on_host,
+        "is_root_device": drive.is_root_device,
+        "is_read_only": drive.is_read_only,
+    });
+    if let Some(ref cache) = drive.cache_type {
+        config["cache_type"] = serde_json::json!(cache);
+    }
+    if let Some(ref io) = drive.io_engine {
+        config["io_engine"] = serde_json::json!(io);
+    }
+    if let Some(ref rl) = drive.rate_limiter {
+        config["rate_limiter"] = rl.clone();
+    }
+    config
+}
+
 pub async fn update_drive(
     st: &AppState,
     vm_id: Uuid,
     drive_id: Uuid,
     req: UpdateDriveReq,
 ) -> Result<nexus_types::VmDrive> {
+    if let Some(rate_limiter) = req.rate_limiter.as_ref() {
+        validate_rate_limiter("rate_limiter", rate_limiter)?;
+    }
     let drive = super::repo::drives::get(&st.db, drive_id).await?;
     if drive.vm_id != vm_id {
-        bail!("drive does not belong to VM");
+        bail!(bad_request("drive does not belong to VM"));
     }
 
     let new_path = req
@@ -1844,7 +3072,7 @@ pub async fn update_drive(
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .patch(format!("{base}/drives/{}{}", drive.drive_id, qs))
         .json(&serde_json::json!({
             "drive_id": drive.drive_id,
@@ -1853,7 +3081,8 @@ pub async fn update_drive(
         }))
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
 
     Ok(updated.into())
 }
@@ -1861,7 +3090,7 @@ pub async fn update_drive(
 pub async fn delete_drive(st: &AppState, vm_id: Uuid, drive_id: Uuid) -> Result<()> {
     let drive = super::repo::drives::get(&st.db, drive_id).await?;
     if drive.vm_id != vm_id {
-        bail!("drive does not belong to VM");
+        bail!(bad_request("drive does not belong to VM"));
     }
 
     // Detach volume from volume registry if it exists
@@ -1872,9 +3101,9 @@ pub async fn delete_drive(st: &AppState, vm_id: Uuid, drive_id: Uuid) -> Result<
     // For a RUNNING QEMU VM, hot-remove the device live (QMP) before we drop the
     // DB row / unlink the file, so the guest releases it cleanly. Best-effort:
     // if it fails the removal still takes effect on the next start.
-    if vm.state == "running" && vm.vmm_kind.as_deref() == Some("qemu") {
+    if is_running_state(&vm.state) && vm.vmm_kind.as_deref() == Some("qemu") {
         let body = serde_json::json!({"vmm_kind": "qemu", "drive_id": drive.drive_id});
-        match reqwest::Client::new()
+        match crate::core::agent_http::client()
             .post(format!(
                 "{}/agent/v1/vmm/{}/disk/remove",
                 vm.host_addr, vm.id
@@ -1919,10 +3148,83 @@ pub async fn delete_drive(st: &AppState, vm_id: Uuid, drive_id: Uuid) -> Result<
         }
     }
 
-    info!(vm_id = %vm_id, drive_id = %drive.drive_id,
-          "Drive deleted from database, will be removed from Firecracker on next VM start");
-
-    Ok(())
+    info!(vm_id = %vm_id, drive_id = %drive.drive_id,
+          "Drive deleted from database, will be removed from Firecracker on next VM start");
+
+    Ok(())
+}
+
+const DEFAULT_EVENTS_LIMIT: i64 = 50;
+const MAX_EVENTS_LIMIT: i64 = 500;
+
+/// List a VM's events newest-first. Errors with "VM not found" for unknown VMs
+/// so callers can tell them apart from a VM that simply has no events.
+pub async fn list_events(
+    db: &sqlx::PgPool,
+    vm_id: Uuid,
+    params: nexus_types::ListVmEventsParams,
+) -> Result<nexus_types::ListVmEventsResponse> {
+    if let Err(sqlx::Error::RowNotFound) = super::repo::get(db, vm_id).await {
+        bail!(not_found("VM not found"));
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .clamp(1, MAX_EVENTS_LIMIT);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    // Fetch one extra row to learn whether another page exists
+    let mut items = super::repo::list_events(db, vm_id, params.since, limit + 1, offset).await?;
+    let next_offset = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        Some(offset + limit)
+    } else {
+        None
+    };
+
+    Ok(nexus_types::ListVmEventsResponse { items, next_offset })
+}
+
+/// The guest's biggest processes, read from the guest agent's `/processes`
+/// over the guest network.
+pub async fn guest_processes(
+    st: &AppState,
+    vm_id: Uuid,
+    params: nexus_types::GuestProcessesParams,
+) -> Result<nexus_types::ListGuestProcessesResponse> {
+    let vm = match super::repo::get(&st.db, vm_id).await {
+        Err(sqlx::Error::RowNotFound) => bail!(not_found("VM not found")),
+        other => other?,
+    };
+    let sort = params.sort.as_deref().unwrap_or("memory");
+    if sort != "memory" && sort != "cpu" {
+        bail!(bad_request("invalid sort: must be 'memory' or 'cpu'"));
+    }
+    let Some(guest_ip) = vm.guest_ip.as_deref().filter(|ip| !ip.is_empty()) else {
+        bail!("guest agent unreachable: VM has not reported a guest IP");
+    };
+
+    let mut url = format!(
+        "http://{guest_ip}:{}/processes?sort={sort}",
+        super::guest_vsock::GUEST_AGENT_PORT
+    );
+    if let Some(limit) = params.limit {
+        url.push_str(&format!("&limit={limit}"));
+    }
+    let items = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .context("failed to build reqwest client")?
+        .get(url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .context("querying guest agent processes")?
+        .json()
+        .await
+        .context("decoding guest processes")?;
+    Ok(nexus_types::ListGuestProcessesResponse { items })
 }
 
 pub async fn list_nics(st: &AppState, vm_id: Uuid) -> Result<Vec<nexus_types::VmNic>> {
@@ -1930,13 +3232,25 @@ pub async fn list_nics(st: &AppState, vm_id: Uuid) -> Result<Vec<nexus_types::Vm
     Ok(rows.into_iter().map(Into::into).collect())
 }
 
+fn validate_nic_rate_limiters(rx: Option<&Value>, tx: Option<&Value>) -> Result<()> {
+    if let Some(rx) = rx {
+        validate_rate_limiter("rx_rate_limiter", rx)?;
+    }
+    if let Some(tx) = tx {
+        validate_rate_limiter("tx_rate_limiter", tx)?;
+    }
+    Ok(())
+}
+
 pub async fn create_nic(
     st: &AppState,
     vm_id: Uuid,
     req: CreateNicReq,
 ) -> Result<nexus_types::VmNic> {
+    validate_nic_rate_limiters(req.rx_rate_limiter.as_ref(), req.tx_rate_limiter.as_ref())?;
+
     // Validate VM exists
-    let _vm = super::repo::get(&st.db, vm_id).await?;
+    let vm = super::repo::get(&st.db, vm_id).await?;
 
     // Get existing NICs to determine next interface ID
     let existing = super::repo::nics::list(&st.db, vm_id).await?;
@@ -1946,16 +3260,16 @@ pub async fn create_nic(
         // Validate provided interface ID
         let iface_id = provided_id.trim().to_ascii_lowercase();
         if !iface_id.starts_with("eth") {
-            bail!("interface id must start with eth");
+            bail!(bad_request("interface id must start with eth"));
         }
         if iface_id == "eth0" {
-            bail!("eth0 is reserved for the primary interface");
+            bail!(bad_request("eth0 is reserved for the primary interface"));
         }
         if iface_id.len() <= 3 {
-            bail!("interface id must include an index, e.g. eth1");
+            bail!(bad_request("interface id must include an index, e.g. eth1"));
         }
         if !iface_id[3..].chars().all(|c| c.is_ascii_digit()) {
-            bail!("interface id must be in the form eth<index>");
+            bail!(bad_request("interface id must be in the form eth<index>"));
         }
 
         // Check for duplicate
@@ -1963,7 +3277,7 @@ pub async fn create_nic(
             .iter()
             .any(|nic| nic.iface_id.eq_ignore_ascii_case(&iface_id))
         {
-            bail!("interface id already exists for this VM");
+            bail!(conflict("interface id already exists for this VM"));
         }
 
         iface_id
@@ -1994,31 +3308,37 @@ pub async fn create_nic(
     let network = network_repo
         .get(req.network_id)
         .await
-        .map_err(|_| anyhow::anyhow!("Network not found"))?;
-
-    // Auto-generate TAP device name: tap-{vm-4chars}-{num}
-    // Linux interface names must be ≤15 chars, so we use shortened format
-    // Examples: tap-ce77-1, tap-ce77-10, tap-ce77-222
-    let vm_short_id = &vm_id.to_string()[..4]; // Use first 4 chars instead of 8
-
-    // Extract numeric part from iface_id (e.g., "eth1" -> "1", "eth222" -> "222")
-    let iface_num = iface_id.trim_start_matches("eth");
-    let host_dev_name = format!("tap-{}-{}", vm_short_id, iface_num);
-
-    // Check for duplicate TAP device names (shouldn't happen with our naming scheme, but be safe)
-    if existing
-        .iter()
-        .any(|nic| nic.host_dev_name.eq_ignore_ascii_case(&host_dev_name))
+        .map_err(|_| not_found("Network not found"))?;
+
+    // The NIC is attached on the VM's host, so the network has to exist there
+    crate::features::networks::service::ensure_network_on_host(st, &network, vm.host_id).await?;
+
+    // Callers never name host taps: derive it from the VM and interface index
+    let iface_index = iface_id
+        .trim_start_matches("eth")
+        .parse::<u32>()
+        .context("interface id must be in the form eth<index>")?;
+    // Two VMs can share the 4-char prefix, so check every VM and NIC
+    let host_dev_name = allocate_nic_tap_name(&st.db, vm_id, iface_index).await?;
+
+    // Use the caller's MAC if given, otherwise derive a stable one from the
+    // VM id + interface index. Either way it must be unique on the network.
+    let guest_mac = match req
+        .guest_mac
+        .as_deref()
+        .map(str::trim)
+        .filter(|mac| !mac.is_empty())
     {
-        bail!("host device already in use by another interface");
+        Some(mac) => normalize_guest_mac(mac)?,
+        None => generate_guest_mac(vm_id, iface_index),
+    };
+    if super::repo::nics::mac_in_use(&st.db, req.network_id, &guest_mac).await? {
+        bail!(conflict(format!(
+            "guest_mac {} is already in use on this network",
+            guest_mac
+        )));
     }
 
-    let guest_mac = req
-        .guest_mac
-        .as_ref()
-        .map(|mac| mac.trim())
-        .filter(|mac| !mac.is_empty());
-
     let rx_rate_limiter = req.rx_rate_limiter.as_ref().map(normalize_rate_limiter);
     let tx_rate_limiter = req.tx_rate_limiter.as_ref().map(normalize_rate_limiter);
 
@@ -2029,21 +3349,33 @@ pub async fn create_nic(
         None
     };
 
-    // Insert network interface into database with network_id and assigned_ip
-    // Interface will be attached to Firecracker on next VM start/restart
-    let nic = super::repo::nics::insert(
+    // Persist first so the interface is applied on every later start. The
+    // unique index catches a concurrent attach that passed the check above.
+    let nic = match super::repo::nics::insert(
         &st.db,
         vm_id,
         &iface_id,
         &host_dev_name,
-        guest_mac,
+        Some(&guest_mac),
         rx_rate_limiter.as_ref(),
         tx_rate_limiter.as_ref(),
         Some(req.network_id),
         assigned_ip.as_deref(),
     )
-    .await?;
+    .await
+    {
+        Ok(nic) => nic,
+        Err(sqlx::Error::Database(e)) if e.constraint() == Some("idx_vm_nic_network_mac") => {
+            bail!(conflict(format!(
+                "guest_mac {} is already in use on this network",
+                guest_mac
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
 
+    // Firecracker has no NIC hot-plug, so a running VM gets the interface
+    // from `configure_vm` on its next start.
     info!(vm_id = %vm_id, iface_id = %iface_id, host_dev = %host_dev_name,
           network_id = %req.network_id, bridge = %network.bridge_name,
           "Network interface created in database, will be attached on next VM start");
@@ -2051,15 +3383,81 @@ pub async fn create_nic(
     Ok(nic.into())
 }
 
+/// Host tap for an additional NIC: `tap-{vm-4chars}-{index}`, e.g.
+/// `tap-ce77-1`. Linux interface names must be ≤15 chars, so the VM id is
+/// shortened to 4 chars.
+pub(crate) fn nic_tap_name(vm_id: Uuid, iface_index: u32) -> String {
+    format!("tap-{}-{}", &vm_id.simple().to_string()[..4], iface_index)
+}
+
+/// Candidate host tap for NIC `iface_index` of `vm_id`. Attempt 0 is
+/// [`nic_tap_name`]; later attempts hash the VM, index and attempt, as
+/// [`tap_name_candidate`] does for primary taps.
+fn nic_tap_name_candidate(vm_id: Uuid, iface_index: u32, attempt: u32) -> String {
+    use sha2::{Digest, Sha256};
+
+    if attempt == 0 {
+        return nic_tap_name(vm_id, iface_index);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(vm_id.as_bytes());
+    hasher.update(iface_index.to_be_bytes());
+    hasher.update(attempt.to_be_bytes());
+    let hex = hex::encode(hasher.finalize());
+    format!("tap-{}", &hex[..MAX_TAP_NAME_LEN - "tap-".len()])
+}
+
+/// Choose a host tap for an additional NIC that no VM or NIC on any host
+/// already uses, like [`allocate_tap_name`].
+pub(crate) async fn allocate_nic_tap_name(
+    db: &PgPool,
+    vm_id: Uuid,
+    iface_index: u32,
+) -> Result<String> {
+    let candidates: Vec<String> = (0..MAX_TAP_ATTEMPTS)
+        .map(|attempt| nic_tap_name_candidate(vm_id, iface_index, attempt))
+        .filter(|name| name.len() <= MAX_TAP_NAME_LEN)
+        .collect();
+    let taken = super::repo::tap_names_in_use(db, &candidates)
+        .await
+        .context("failed to check tap names in use")?;
+    candidates
+        .into_iter()
+        .find(|name| !taken.contains(name))
+        .ok_or_else(|| {
+            anyhow!("no free tap name for vm {vm_id} eth{iface_index} after {MAX_TAP_ATTEMPTS} attempts")
+        })
+}
+
+/// Firecracker `PUT /network-interfaces/{id}` body for a stored NIC; optional
+/// fields are only sent when set.
+fn nic_config(nic: &super::repo::VmNic) -> Value {
+    let mut config = json!({
+        "iface_id": nic.iface_id,
+        "host_dev_name": nic.host_dev_name,
+    });
+    if let Some(ref mac) = nic.guest_mac {
+        config["guest_mac"] = json!(mac);
+    }
+    if let Some(ref rx) = nic.rx_rate_limiter {
+        config["rx_rate_limiter"] = normalize_rate_limiter(rx);
+    }
+    if let Some(ref tx) = nic.tx_rate_limiter {
+        config["tx_rate_limiter"] = normalize_rate_limiter(tx);
+    }
+    config
+}
+
 pub async fn update_nic(
     st: &AppState,
     vm_id: Uuid,
     nic_id: Uuid,
     req: UpdateNicReq,
 ) -> Result<nexus_types::VmNic> {
+    validate_nic_rate_limiters(req.rx_rate_limiter.as_ref(), req.tx_rate_limiter.as_ref())?;
     let nic = super::repo::nics::get(&st.db, nic_id).await?;
     if nic.vm_id != vm_id {
-        bail!("network interface does not belong to VM");
+        bail!(bad_request("network interface does not belong to VM"));
     }
 
     // Update database only - changes will apply on next VM start/restart
@@ -2083,7 +3481,7 @@ pub async fn update_nic(
 pub async fn delete_nic(st: &AppState, vm_id: Uuid, nic_id: Uuid) -> Result<()> {
     let nic = super::repo::nics::get(&st.db, nic_id).await?;
     if nic.vm_id != vm_id {
-        bail!("network interface does not belong to VM");
+        bail!(bad_request("network interface does not belong to VM"));
     }
 
     // Delete from database only - interface removal will apply on next VM start/restart
@@ -2100,32 +3498,46 @@ pub async fn patch_machine_config(
     vm_id: Uuid,
     req: MachineConfigPatchReq,
 ) -> Result<()> {
+    if let Some(template) = req.cpu_template.as_deref() {
+        super::cpu_templates::validate_cpu_template(template)?;
+    }
     let vm = super::repo::get(&st.db, vm_id).await?;
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .patch(format!("{base}/machine-config{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
 
+    if let Some(template) = req.cpu_template.as_deref() {
+        // Kept so snapshots can record the template they were taken under.
+        sqlx::query(r#"UPDATE vm SET cpu_template = $2 WHERE id = $1"#)
+            .bind(vm.id)
+            .bind(template)
+            .execute(&st.db)
+            .await?;
+    }
     super::repo::update_state(&st.db, vm.id, &vm.state).await?;
     Ok(())
 }
 
 pub async fn put_cpu_config(st: &AppState, vm_id: Uuid, req: CpuConfigReq) -> Result<()> {
+    super::cpu_templates::validate_cpu_config(&req)?;
     let vm = super::repo::get(&st.db, vm_id).await?;
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/cpu-config{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2134,40 +3546,103 @@ pub async fn put_vsock(st: &AppState, vm_id: Uuid, req: VsockConfigReq) -> Resul
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/vsock{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
 pub async fn put_mmds(st: &AppState, vm_id: Uuid, req: MmdsDataReq) -> Result<()> {
     let vm = super::repo::get(&st.db, vm_id).await?;
+    // Store before pushing so a restart reapplies it even if the push fails
+    super::repo::mmds::upsert_data(&st.db, vm.id, &req.data).await?;
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/mmds{qs}"))
         .json(&req.data)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
 pub async fn put_mmds_config(st: &AppState, vm_id: Uuid, req: MmdsConfigReq) -> Result<()> {
     let vm = super::repo::get(&st.db, vm_id).await?;
+    super::repo::mmds::upsert_config(&st.db, vm.id, &serde_json::to_value(&req)?).await?;
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/mmds/config{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
+    Ok(())
+}
+
+pub async fn get_mmds(db: &sqlx::PgPool, vm_id: Uuid) -> Result<nexus_types::VmMmds> {
+    if let Err(sqlx::Error::RowNotFound) = super::repo::get(db, vm_id).await {
+        bail!(not_found("VM not found"));
+    }
+
+    let Some(row) = super::repo::mmds::get(db, vm_id).await? else {
+        return Ok(nexus_types::VmMmds::default());
+    };
+    let config = row
+        .config
+        .map(serde_json::from_value)
+        .transpose()
+        .context("stored MMDS config is invalid")?;
+    Ok(nexus_types::VmMmds {
+        config,
+        data: row.data,
+        updated_at: Some(row.updated_at),
+    })
+}
+
+/// Push the stored MMDS config and data into a freshly configured Firecracker.
+/// Config goes first: Firecracker needs it (and the NICs it names) before data.
+#[cfg(not(test))]
+async fn reapply_mmds(
+    st: &AppState,
+    http: &reqwest::Client,
+    base: &str,
+    qs: &str,
+    id: Uuid,
+) -> Result<()> {
+    let Some(stored) = super::repo::mmds::get(&st.db, id).await? else {
+        return Ok(());
+    };
+    if let Some(config) = &stored.config {
+        http.put(format!("{base}/mmds/config{qs}"))
+            .json(config)
+            .send()
+            .await
+            .context("mmds config request failed to send")?
+            .or_fault()
+            .await
+            .context("mmds config returned error status")?;
+    }
+    if let Some(data) = &stored.data {
+        http.put(format!("{base}/mmds{qs}"))
+            .json(data)
+            .send()
+            .await
+            .context("mmds request failed to send")?
+            .or_fault()
+            .await
+            .context("mmds returned error status")?;
+    }
+    info!(vm_id=%id, step="mmds", "reapplied stored MMDS state");
     Ok(())
 }
 
@@ -2176,12 +3651,13 @@ pub async fn put_entropy(st: &AppState, vm_id: Uuid, req: EntropyConfigReq) -> R
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/entropy{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2190,12 +3666,13 @@ pub async fn put_serial(st: &AppState, vm_id: Uuid, req: SerialConfigReq) -> Res
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/serial{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2204,12 +3681,13 @@ pub async fn patch_logger(st: &AppState, vm_id: Uuid, req: LoggerUpdateReq) -> R
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/logger{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2218,12 +3696,13 @@ pub async fn put_balloon(st: &AppState, vm_id: Uuid, req: BalloonConfig) -> Resu
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/balloon{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2232,12 +3711,13 @@ pub async fn patch_balloon(st: &AppState, vm_id: Uuid, req: BalloonConfig) -> Re
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .patch(format!("{base}/balloon{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -2250,15 +3730,54 @@ pub async fn patch_balloon_stats(
     let base = format!("{}/agent/v1/vms/{}/proxy", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
-    reqwest::Client::new()
+    crate::core::agent_http::client()
         .patch(format!("{base}/balloon/statistics{qs}"))
         .json(&req)
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
+/// Refuse to restore `snapshot` on a host running another Firecracker
+/// release unless `force` is set. An unreachable agent health endpoint is
+/// treated as an unknown version.
+async fn ensure_snapshot_compatible(
+    snapshot: &SnapshotRow,
+    host_addr: &str,
+    force: bool,
+) -> Result<()> {
+    let host_version = match compat::host_firecracker_version(host_addr).await {
+        Ok(version) => version,
+        Err(err) => {
+            warn!(snapshot_id = %snapshot.id, error = ?err, "could not read host Firecracker version");
+            None
+        }
+    };
+    match compat::check(
+        snapshot.firecracker_version.as_deref(),
+        host_version.as_deref(),
+    ) {
+        Ok(()) => Ok(()),
+        Err(err) if force => {
+            warn!(snapshot_id = %snapshot.id, error = %err, "restoring across Firecracker releases (forced)");
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Ceiling on decompressing and loading a snapshot's memory file, which for a
+/// large VM takes minutes rather than the usual agent timeout.
+const SNAPSHOT_LOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The agent's answer to `snapshots/decompress`.
+#[derive(Deserialize)]
+struct RestoreMemFile {
+    mem_path: String,
+}
+
 pub async fn load_snapshot(
     st: &AppState,
     vm_id: Uuid,
@@ -2266,29 +3785,74 @@ pub async fn load_snapshot(
 ) -> Result<()> {
     let vm = super::repo::get(&st.db, vm_id).await?;
 
-    let client = reqwest::Client::new();
+    let client = crate::core::agent_http::client();
     let base = format!("{}/agent/v1/vms/{}", vm.host_addr, vm.id);
     let qs = format!("?sock={}", urlencoding::encode(&vm.api_sock));
 
     let is_diff = snapshot.snapshot_type == "Diff";
-    let mem_value = if is_diff || snapshot.mem_path.is_empty() {
+    // The snapshot's files live under the source VM on the agent.
+    let snapshots_base = format!("{}/agent/v1/vms/{}/snapshots", vm.host_addr, snapshot.vm_id);
+    let restore_mem_path = if snapshot.compressed && !is_diff {
+        let restore: RestoreMemFile = client
+            .post(format!("{snapshots_base}/decompress"))
+            .json(&json!({ "snapshot_id": snapshot.id }))
+            .timeout(SNAPSHOT_LOAD_TIMEOUT)
+            .send()
+            .await?
+            .or_fault()
+            .await
+            .context("decompressing snapshot memory file")?
+            .json()
+            .await?;
+        Some(restore.mem_path)
+    } else {
+        None
+    };
+    let mem_path = restore_mem_path.as_deref().unwrap_or(&snapshot.mem_path);
+    let mem_value = if is_diff || mem_path.is_empty() {
         serde_json::Value::Null
     } else {
-        serde_json::Value::String(snapshot.mem_path.clone())
+        serde_json::Value::String(mem_path.to_string())
     };
 
-    let load_payload = serde_json::json!({
+    let mut load_payload = serde_json::json!({
         "snapshot_path": snapshot.snapshot_path.clone(),
         "mem_file_path": mem_value,
         "enable_diff_snapshots": snapshot.track_dirty_pages,
     });
+    // The snapshot carries the source VM's vsock socket path; binding that
+    // would clash with the source (or a sibling restore) on this host
+    if super::guest_vsock::enabled() {
+        load_payload["vsock_override"] =
+            json!({ "uds_path": super::guest_vsock::uds_path(&vm.api_sock, vm.id) });
+    }
 
-    let load_resp = client
+    let load_result = match client
         .put(format!("{base}/proxy/snapshot/load{qs}"))
         .json(&load_payload)
+        .timeout(SNAPSHOT_LOAD_TIMEOUT)
         .send()
-        .await?;
-    load_resp.error_for_status()?;
+        .await
+    {
+        Ok(resp) => resp.or_fault().await.map(drop),
+        Err(err) => Err(err.into()),
+    };
+
+    // Firecracker has the file mapped by now (or failed to load it), so the
+    // decompressed copy can go either way.
+    if let Some(path) = restore_mem_path {
+        let discarded = client
+            .post(format!("{snapshots_base}/discard"))
+            .json(&json!({ "snapshot_id": snapshot.id, "mem_path": path }))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        if let Err(err) = discarded {
+            warn!(vm_id = %vm.id, snapshot_id = %snapshot.id, error = %err,
+                "failed to discard decompressed snapshot memory");
+        }
+    }
+    load_result?;
 
     if let Some(parent_id) = snapshot.parent_id {
         tracing::info!(vm_id = %vm.id, parent_id = %parent_id, "diff snapshot load uses parent");
@@ -2448,6 +4012,47 @@ async fn detect_distro(mount_point: &str) -> Result<String> {
     Ok("unknown".to_string())
 }
 
+/// SHA-512 crypt hash of `password` for /etc/shadow, via `openssl passwd`.
+async fn hash_password(password: &str) -> Result<String> {
+    use tokio::process::Command;
+
+    // Use -stdin to avoid interactive prompts
+    let mut child = Command::new("openssl")
+        .args(["passwd", "-6", "-stdin"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("failed to spawn openssl")?;
+
+    // Write password to stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        use tokio::io::AsyncWriteExt;
+        stdin
+            .write_all(password.as_bytes())
+            .await
+            .context("failed to write password to openssl stdin")?;
+        stdin
+            .write_all(b"\n")
+            .await
+            .context("failed to write newline to openssl stdin")?;
+        drop(stdin); // Close stdin to signal EOF
+    }
+
+    let hash_output = child
+        .wait_with_output()
+        .await
+        .context("failed to wait for openssl")?;
+
+    if !hash_output.status.success() {
+        bail!("openssl passwd failed");
+    }
+
+    Ok(String::from_utf8_lossy(&hash_output.stdout)
+        .trim()
+        .to_string())
+}
+
 /// Fallback: Inject credentials directly into rootfs by mounting and modifying /etc/shadow
 /// This is used when cloud-init is not available in the guest OS
 #[cfg(not(test))]
@@ -2493,43 +4098,13 @@ async fn inject_credentials_to_rootfs(
         bail!("failed to mount rootfs at {}", rootfs_path);
     }
 
-    // Generate password hash using openssl (SHA-512)
-    // Use -stdin to avoid interactive prompts
-    let mut child = Command::new("openssl")
-        .args(["passwd", "-6", "-stdin"])
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .context("failed to spawn openssl")?;
-
-    // Write password to stdin
-    if let Some(mut stdin) = child.stdin.take() {
-        use tokio::io::AsyncWriteExt;
-        stdin
-            .write_all(password.as_bytes())
-            .await
-            .context("failed to write password to openssl stdin")?;
-        stdin
-            .write_all(b"\n")
-            .await
-            .context("failed to write newline to openssl stdin")?;
-        drop(stdin); // Close stdin to signal EOF
-    }
-
-    let hash_output = child
-        .wait_with_output()
-        .await
-        .context("failed to wait for openssl")?;
-
-    if !hash_output.status.success() {
-        cleanup(mount_dir.clone()).await;
-        bail!("openssl passwd failed");
-    }
-
-    let password_hash = String::from_utf8_lossy(&hash_output.stdout)
-        .trim()
-        .to_string();
+    let password_hash = match hash_password(password).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            cleanup(mount_dir.clone()).await;
+            return Err(e);
+        }
+    };
 
     // Read current /etc/shadow using sudo (requires elevated permissions)
     let shadow_path = mount_path.join("etc/shadow");
@@ -2962,31 +4537,210 @@ mod tests {
     use nexus_types::CreateImageReq;
     use serde_json::json;
 
-    async fn test_registry(pool: &sqlx::PgPool) -> crate::features::storage::registry::Registry {
-        crate::features::storage::registry::Registry::load(pool, None)
+    #[derive(Clone)]
+    pub struct TestSnapshotLoad {
+        vm_id: Uuid,
+        snapshot_path: String,
+        mem_path: String,
+    }
+
+    static SNAPSHOT_LOAD_CALLS: std::sync::OnceLock<std::sync::Mutex<Vec<TestSnapshotLoad>>> =
+        std::sync::OnceLock::new();
+
+    fn snapshot_load_store() -> &'static std::sync::Mutex<Vec<TestSnapshotLoad>> {
+        SNAPSHOT_LOAD_CALLS.get_or_init(|| std::sync::Mutex::new(Vec::new()))
+    }
+
+    pub fn reset_snapshot_load_calls() {
+        snapshot_load_store().lock().unwrap().clear();
+    }
+
+    pub fn snapshot_load_calls() -> Vec<TestSnapshotLoad> {
+        snapshot_load_store().lock().unwrap().clone()
+    }
+
+    #[test]
+    fn drive_config_omits_unset_optional_fields() {
+        let now = chrono::Utc::now();
+        let mut drive = repo::VmDrive {
+            id: Uuid::new_v4(),
+            vm_id: Uuid::new_v4(),
+            drive_id: "data1".into(),
+            path_on_host: "/srv/images/data1.img".into(),
+            size_bytes: None,
+            is_root_device: false,
+            is_read_only: true,
+            cache_type: None,
+            io_engine: None,
+            rate_limiter: None,
+            created_at: now,
+            updated_at: now,
+        };
+        assert_eq!(
+            drive_config(&drive),
+            json!({
+                "drive_id": "data1",
+                "path_on_host": "/srv/images/data1.img",
+                "is_root_device": false,
+                "is_read_only": true,
+            })
+        );
+
+        drive.cache_type = Some("Writeback".into());
+        drive.io_engine = Some("Async".into());
+        let config = drive_config(&drive);
+        assert_eq!(config["cache_type"], "Writeback");
+        assert_eq!(config["io_engine"], "Async");
+        assert!(config.get("rate_limiter").is_none());
+    }
+
+    #[test]
+    fn nic_tap_name_fits_ifnamsiz() {
+        let vm_id: Uuid = "ce77a1b2-0000-4000-8000-000000000000".parse().unwrap();
+        assert_eq!(nic_tap_name(vm_id, 1), "tap-ce77-1");
+        // IFNAMSIZ leaves 15 usable bytes: six index digits fit, seven don't
+        assert_eq!(nic_tap_name(vm_id, 999_999).len(), 15);
+        assert!(nic_tap_name(vm_id, 1_000_000).len() > 15);
+    }
+
+    #[tokio::test]
+    async fn snapshot_reachable_from_asks_the_target_agent() {
+        let mut server = mockito::Server::new_async().await;
+        let now = chrono::Utc::now();
+        let snapshot = SnapshotRow {
+            id: Uuid::new_v4(),
+            vm_id: Uuid::new_v4(),
+            snapshot_path: "/srv/fc/vms/source/snapshots/snap/snapshot.fc".into(),
+            mem_path: "/srv/fc/vms/source/snapshots/snap/mem/mem.fc".into(),
+            size_bytes: 0,
+            state: "available".into(),
+            snapshot_type: "Full".into(),
+            parent_id: None,
+            name: None,
+            track_dirty_pages: false,
+            firecracker_version: None,
+            vcpu: None,
+            mem_mib: None,
+            cpu_template: None,
+            compressed: false,
+            created_at: now,
+            updated_at: now,
+        };
+        let stat = server
+            .mock(
+                "POST",
+                format!("/agent/v1/vms/{}/snapshots/stat", snapshot.vm_id).as_str(),
+            )
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "snapshot_id": snapshot.id,
+                "snapshot_path": snapshot.snapshot_path,
+                "mem_path": snapshot.mem_path,
+                "disk_paths": ["/srv/images/vmlinux", "/srv/images/rootfs.ext4"],
+            })))
+            .with_status(200)
+            .with_body(r#"{"reachable":false}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let disks = [
+            "/srv/images/vmlinux".to_string(),
+            "/srv/images/rootfs.ext4".to_string(),
+        ];
+        assert!(!snapshot_reachable_from(&server.url(), &snapshot, &disks)
             .await
-            .expect("registry")
+            .unwrap());
+        stat.assert_async().await;
     }
-    #[derive(Clone)]
-    pub struct TestSnapshotLoad {
-        vm_id: Uuid,
-        snapshot_path: String,
-        mem_path: String,
+
+    #[test]
+    fn nic_config_omits_unset_optional_fields() {
+        let now = chrono::Utc::now();
+        let mut nic = repo::VmNic {
+            id: Uuid::new_v4(),
+            vm_id: Uuid::new_v4(),
+            iface_id: "eth1".into(),
+            host_dev_name: "tap-abcd-1".into(),
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            network_id: None,
+            assigned_ip: None,
+            created_at: now,
+            updated_at: now,
+        };
+        assert_eq!(
+            nic_config(&nic),
+            json!({"iface_id": "eth1", "host_dev_name": "tap-abcd-1"})
+        );
+
+        nic.guest_mac = Some("02:fc:00:00:00:01".into());
+        let config = nic_config(&nic);
+        assert_eq!(config["guest_mac"], "02:fc:00:00:00:01");
+        assert!(config.get("rx_rate_limiter").is_none());
     }
 
-    static SNAPSHOT_LOAD_CALLS: std::sync::OnceLock<std::sync::Mutex<Vec<TestSnapshotLoad>>> =
-        std::sync::OnceLock::new();
+    #[test]
+    fn pick_guest_ip_prefers_the_primary_network() {
+        let addrs = ["192.168.1.9", "10.0.0.5", "fd00::5"];
+        assert_eq!(
+            pick_guest_ip(Some("192.168.1.9"), &addrs, Some("10.0.0.0/24")).as_deref(),
+            Some("10.0.0.5")
+        );
+        // No address in the network: trust the agent's choice
+        assert_eq!(
+            pick_guest_ip(Some("192.168.1.9"), &addrs, Some("172.16.0.0/16")).as_deref(),
+            Some("192.168.1.9")
+        );
+        // Nothing on the report interface: first IPv4 address
+        assert_eq!(
+            pick_guest_ip(None, &["fd00::5", "10.0.0.5"], None).as_deref(),
+            Some("10.0.0.5")
+        );
+        // Older agents send only guest_ip; shutdown sends neither
+        assert_eq!(
+            pick_guest_ip(Some("10.0.0.5"), &[], None).as_deref(),
+            Some("10.0.0.5")
+        );
+        assert_eq!(pick_guest_ip(None, &[], Some("10.0.0.0/24")), None);
+    }
 
-    fn snapshot_load_store() -> &'static std::sync::Mutex<Vec<TestSnapshotLoad>> {
-        SNAPSHOT_LOAD_CALLS.get_or_init(|| std::sync::Mutex::new(Vec::new()))
+    #[test]
+    fn ipv4_in_cidr_masks_by_prefix() {
+        assert!(ipv4_in_cidr("10.0.0.5", "10.0.0.0/24"));
+        assert!(!ipv4_in_cidr("10.0.1.5", "10.0.0.0/24"));
+        assert!(ipv4_in_cidr("10.0.1.5", "10.0.0.0/16"));
+        assert!(ipv4_in_cidr("8.8.8.8", "0.0.0.0/0"));
+        assert!(!ipv4_in_cidr("fd00::5", "10.0.0.0/24"));
+        assert!(!ipv4_in_cidr("10.0.0.5", "10.0.0.0/33"));
+        assert!(!ipv4_in_cidr("10.0.0.5", "10.0.0.0"));
     }
 
-    pub fn reset_snapshot_load_calls() {
-        snapshot_load_store().lock().unwrap().clear();
+    #[test]
+    fn lookup_failures_are_told_apart_from_refusals() {
+        let db_down = anyhow::Error::new(sqlx::Error::PoolTimedOut).context("query host capacity");
+        assert!(is_lookup_failure(&db_down));
+        let missing = anyhow::Error::new(sqlx::Error::RowNotFound).context("load snapshot");
+        assert!(!is_lookup_failure(&missing));
+        assert!(!is_lookup_failure(&anyhow!(
+            "host 1 has no capacity for 2 vCPU / 512 MiB"
+        )));
     }
 
-    pub fn snapshot_load_calls() -> Vec<TestSnapshotLoad> {
-        snapshot_load_store().lock().unwrap().clone()
+    #[test]
+    fn request_field_errors_are_refusals() {
+        let err = validate_request_fields(&CreateVmReq {
+            huge_pages: Some("1G".into()),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(!is_lookup_failure(&err));
+        assert!(validate_request_fields(&CreateVmReq {
+            vcpu: 1,
+            mem_mib: 512,
+            ..Default::default()
+        })
+        .is_ok());
     }
 
     #[ignore]
@@ -3023,36 +4777,9 @@ mod tests {
             .await
             .unwrap();
 
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let registry = test_registry(&pool).await;
-        let state = AppState {
-            db: pool.clone(),
-            hosts: hosts.clone(),
-            images: images.clone(),
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
+        let state = crate::AppState {
             allow_direct_image_paths: false,
-            storage: storage.clone(),
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
+            ..crate::AppState::for_tests(pool.clone()).await
         };
 
         let vm_id = Uuid::new_v4();
@@ -3069,7 +4796,6 @@ mod tests {
             },
             None,
             None,
-            "test",
         )
         .await
         .unwrap();
@@ -3080,6 +4806,26 @@ mod tests {
         assert_eq!(stored.host_id, host.id);
     }
 
+    #[ignore]
+    #[sqlx::test(migrations = "./migrations")]
+    async fn validate_create_refuses_without_hosts_and_fails_without_a_db(pool: sqlx::PgPool) {
+        let state = crate::AppState::for_tests(pool.clone()).await;
+        let req = CreateVmReq {
+            name: "vm".into(),
+            vcpu: 1,
+            mem_mib: 512,
+            ..Default::default()
+        };
+
+        let err = validate_create(&state, &req).await.unwrap_err();
+        assert!(format!("{err:#}").contains("no healthy hosts"));
+        assert!(!is_lookup_failure(&err));
+
+        pool.close().await;
+        let err = validate_create(&state, &req).await.unwrap_err();
+        assert!(is_lookup_failure(&err));
+    }
+
     #[ignore]
     #[sqlx::test(migrations = "./migrations")]
     async fn reject_direct_paths_in_prod(pool: sqlx::PgPool) {
@@ -3089,38 +4835,9 @@ mod tests {
             .register("host", "http://127.0.0.1:1", json!({}))
             .await
             .unwrap();
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let registry = test_registry(&pool).await;
-        let state = AppState {
-            db: pool.clone(),
-            hosts,
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
+        let state = crate::AppState {
             allow_direct_image_paths: false,
-            storage: storage.clone(),
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
+            ..crate::AppState::for_tests(pool.clone()).await
         };
 
         let err = create_and_start(
@@ -3136,7 +4853,6 @@ mod tests {
             },
             None,
             None,
-            "test",
         )
         .await
         .unwrap_err();
@@ -3154,38 +4870,9 @@ mod tests {
             .register("host", "http://127.0.0.1:1", json!({}))
             .await
             .unwrap();
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let registry = test_registry(&pool).await;
-        let state = AppState {
-            db: pool.clone(),
-            hosts,
-            images,
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
+        let state = crate::AppState {
             allow_direct_image_paths: false,
-            storage: storage.clone(),
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
+            ..crate::AppState::for_tests(pool.clone()).await
         };
 
         let vm = repo::VmRow {
@@ -3213,8 +4900,18 @@ mod tests {
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            state_changed_at: chrono::Utc::now(),
+            started_at: None,
         };
 
         let err = restart_vm(&state, &vm).await.unwrap_err();
@@ -3234,38 +4931,9 @@ mod tests {
             .register("host", "http://127.0.0.1:1", json!({"healthy": true}))
             .await
             .unwrap();
-        let images =
-            crate::features::images::repo::ImageRepository::new(pool.clone(), "/srv/images");
-        let snapshots = crate::features::snapshots::repo::SnapshotRepository::new(pool.clone());
-        let users = crate::features::users::repo::UserRepository::new(pool.clone());
-        let shell_repo = crate::features::vms::shell::ShellRepository::new(pool.clone());
-        let download_progress =
-            std::sync::Arc::new(tokio::sync::Mutex::new(std::collections::HashMap::new()));
-        let storage = crate::features::storage::LocalStorage::new();
-        storage.init().await.unwrap();
-        let registry = test_registry(&pool).await;
-        let state = AppState {
-            db: pool.clone(),
-            hosts: hosts.clone(),
-            images: images.clone(),
-            snapshots,
-            users,
-            shell_repo,
-            licensing: crate::features::licensing::repo::LicensingRepository::new(pool.clone()),
+        let state = crate::AppState {
             allow_direct_image_paths: false,
-            storage: storage.clone(),
-            registry,
-            download_progress,
-            license_state: std::sync::Arc::new(tokio::sync::RwLock::new(
-                nexus_types::LicenseState::default(),
-            )),
-            license_config: crate::features::licensing::license_service::LicenseConfig::from_env(),
-            sso_providers: crate::features::sso::repo::SsoProviderRepository::new(pool.clone()),
-            user_identities: crate::features::sso::repo::UserIdentityRepository::new(pool.clone()),
-            auth_states: crate::features::sso::repo::AuthStateRepository::new(pool.clone()),
-            sso_base_url: "http://localhost:18080".to_string(),
-            sso_frontend_url: "http://localhost:3000".to_string(),
-            sso_encryption_key: crate::features::sso::crypto::derive_key("test-key"),
+            ..crate::AppState::for_tests(pool.clone()).await
         };
 
         let now = chrono::Utc::now();
@@ -3298,8 +4966,18 @@ mod tests {
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
             created_at: now,
             updated_at: now,
+            state_changed_at: now,
+            started_at: None,
         };
         repo::insert(&state.db, &source_row).await.unwrap();
 
@@ -3315,6 +4993,11 @@ mod tests {
             parent_id: None,
             name: None,
             track_dirty_pages: false,
+            firecracker_version: None,
+            vcpu: None,
+            mem_mib: None,
+            cpu_template: None,
+            compressed: false,
             created_at: now,
             updated_at: now,
         };
@@ -3329,6 +5012,10 @@ mod tests {
             None,
             snapshot_row.clone(),
             Some(source_row.clone()),
+            None,
+            None,
+            false,
+            None,
         )
         .await
         .unwrap();
@@ -3365,7 +5052,7 @@ mod tests {
             template_id: None,
             host_addr: "http://127.0.0.1:9090".into(),
             api_sock: format!("/srv/fc/vms/{id}/sock/fc.sock"),
-            tap: format!("tap-{}", &id.to_string()[..8]),
+            tap: tap_name_candidate(id, 0),
             log_path: format!("/srv/fc/vms/{id}/logs/firecracker.log"),
             http_port: 0,
             fc_unit: format!("fc-{id}.scope"),
@@ -3382,22 +5069,32 @@ mod tests {
             console_kind: None,
             vnc_listen: None,
             cpu_type: None,
+            boot_args: None,
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_mode: None,
+            cpu_quota: None,
+            io_weight: None,
             created_at: now,
             updated_at: now,
+            state_changed_at: now,
+            started_at: None,
         }
     }
 
     #[test]
     fn test_select_network_returns_bridge_name() {
         let caps = json!({"bridge": "fcbr0"});
-        let sel = select_network(&caps).expect("bridge present should succeed");
+        let sel = select_network(&caps, None).expect("bridge present should succeed");
         assert_eq!(sel.bridge, "fcbr0");
     }
 
     #[test]
     fn test_select_network_missing_bridge_errors() {
         let caps = json!({});
-        let err = match select_network(&caps) {
+        let err = match select_network(&caps, None) {
             Ok(_) => panic!("expected error when bridge is missing"),
             Err(e) => e,
         };
@@ -3411,7 +5108,7 @@ mod tests {
     fn test_select_network_non_string_bridge_errors() {
         // bridge present but wrong type must NOT be accepted
         let caps = json!({"bridge": 123});
-        let err = match select_network(&caps) {
+        let err = match select_network(&caps, None) {
             Ok(_) => panic!("expected error when bridge is not a string"),
             Err(e) => e,
         };
@@ -3421,6 +5118,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_select_network_picks_requested_named_bridge() {
+        let caps = json!({
+            "bridge": "fcbr0",
+            "bridges": {"public": "fcbr0", "private": "fcbr1"},
+        });
+        let private = select_network(&caps, Some("private")).unwrap();
+        assert_eq!(private.bridge, "fcbr1");
+        assert_eq!(private.name.as_deref(), Some("private"));
+        assert_eq!(
+            select_network(&caps, Some("fcbr1")).unwrap().bridge,
+            "fcbr1"
+        );
+
+        // The default bridge keeps registering as the default network
+        let public = select_network(&caps, Some("public")).unwrap();
+        assert_eq!((public.bridge.as_str(), public.name), ("fcbr0", None));
+
+        let err = select_network(&caps, Some("dmz")).err().unwrap();
+        assert!(err.to_string().contains("private, public"), "{err}");
+        // Agents without FC_BRIDGES only have their default
+        assert!(select_network(&json!({"bridge": "fcbr0"}), Some("fcbr0")).is_ok());
+        assert!(select_network(&json!({"bridge": "fcbr0"}), Some("fcbr1")).is_err());
+    }
+
     #[test]
     fn test_normalize_rate_limiter_passthrough_when_already_nested() {
         let raw = json!({
@@ -3484,6 +5206,98 @@ mod tests {
         assert_eq!(out, raw);
     }
 
+    #[test]
+    fn test_validate_rate_limiter_accepts_flat_and_nested_shapes() {
+        let flat = json!({"size": 1024, "one_time_burst": 2048, "refill_time": 1000});
+        assert!(validate_rate_limiter("rate_limiter", &flat).is_ok());
+
+        let nested = json!({
+            "bandwidth": {"size": 1000, "refill_time": 100},
+            "ops": {"size": 50, "one_time_burst": 0, "refill_time": 100}
+        });
+        assert!(validate_rate_limiter("rx_rate_limiter", &nested).is_ok());
+        assert!(validate_rate_limiter("rate_limiter", &json!({})).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rate_limiter_names_the_bad_field() {
+        let err = validate_rate_limiter("rate_limiter", &json!({"size": "fast"})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid rate_limiter: size must be a non-negative integer, got "fast""#
+        );
+
+        let err =
+            validate_rate_limiter("tx_rate_limiter", &json!({"refill_time": -5})).unwrap_err();
+        assert!(err.to_string().contains("tx_rate_limiter: refill_time"));
+
+        let nested = json!({"bandwidth": {"size": 1000}, "ops": {"one_time_burst": 1.5}});
+        let err = validate_rate_limiter("rx_rate_limiter", &nested).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("rx_rate_limiter: ops.one_time_burst must be a non-negative integer"));
+
+        let err = validate_rate_limiter("rx_rate_limiter", &json!({"bandwidth": 100})).unwrap_err();
+        assert!(err.to_string().contains("bandwidth must be a JSON object"));
+        assert!(validate_rate_limiter("rate_limiter", &json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_validate_rate_limiter_errors_map_to_bad_request() {
+        let err = validate_nic_rate_limiters(None, Some(&json!({"size": "fast"}))).unwrap_err();
+        assert_eq!(
+            crate::core::error::ApiError::from(err).code,
+            crate::core::error::ErrorCode::BadRequest
+        );
+    }
+
+    #[test]
+    fn test_rootfs_rate_limiter_precedence() {
+        let drive = json!({"bandwidth": {"size": 1, "refill_time": 1}});
+        let vm = json!({"size": 2, "refill_time": 1});
+        let default = json!({"ops": {"size": 3, "refill_time": 1}});
+
+        // An explicit rootfs drive setting always wins
+        assert_eq!(
+            effective_rootfs_rate_limiter(Some(&drive), Some(&vm), Some(&default)),
+            Some(drive.clone())
+        );
+        // Then the VM's (or template's), normalized like any other limiter
+        assert_eq!(
+            effective_rootfs_rate_limiter(None, Some(&vm), Some(&default)),
+            Some(json!({"bandwidth": {"size": 2, "refill_time": 1}}))
+        );
+        assert_eq!(
+            effective_rootfs_rate_limiter(None, None, Some(&default)),
+            Some(default)
+        );
+        assert_eq!(effective_rootfs_rate_limiter(None, None, None), None);
+    }
+
+    #[test]
+    fn test_entropy_device_defaults_on() {
+        assert!(parse_entropy_device_enabled(None));
+        assert!(parse_entropy_device_enabled(Some("1")));
+        assert!(!parse_entropy_device_enabled(Some(" OFF ")));
+        assert!(!parse_entropy_device_enabled(Some("false")));
+        assert!(!parse_entropy_device_enabled(Some("0")));
+    }
+
+    #[test]
+    fn test_default_rootfs_rate_limiter_ignores_bad_env() {
+        assert_eq!(parse_default_rootfs_rate_limiter(None), None);
+        assert_eq!(parse_default_rootfs_rate_limiter(Some("  ")), None);
+        assert_eq!(parse_default_rootfs_rate_limiter(Some("{not json")), None);
+        assert_eq!(
+            parse_default_rootfs_rate_limiter(Some(r#"{"size": "fast"}"#)),
+            None
+        );
+        assert_eq!(
+            parse_default_rootfs_rate_limiter(Some(r#"{"size": 1048576, "refill_time": 1000}"#)),
+            Some(json!({"size": 1048576, "refill_time": 1000}))
+        );
+    }
+
     #[test]
     fn test_vmpaths_from_row_mirrors_row_fields() {
         let id = Uuid::new_v4();
@@ -3526,21 +5340,153 @@ mod tests {
     }
 
     #[test]
-    fn test_tap_name_uses_first_eight_uuid_chars() {
-        // The TAP device name format is dictated by host-side networking
-        // expectations. Multiple call sites compute it as
-        // `tap-{first_8_chars_of_uuid}`. Lock that contract here so the
-        // refactor cannot accidentally widen or shorten it.
+    fn test_tap_name_candidate_fits_ifnamsiz() {
+        // The kernel rejects interface names longer than 15 bytes, and the
+        // agent only accepts `tap`-prefixed alphanumeric/dash names.
         let id = Uuid::new_v4();
-        let id_str = id.to_string();
-        let tap = format!("tap-{}", &id_str[..8]);
-        // Total length: 4 (prefix) + 8 (hex) = 12.
-        assert_eq!(tap.len(), 12);
-        assert!(tap.starts_with("tap-"));
-        // The 8-char prefix must be lowercase hex (UUIDs are lowercase).
-        assert!(tap[4..]
+        for attempt in 0..MAX_TAP_ATTEMPTS {
+            let tap = tap_name_candidate(id, attempt);
+            assert_eq!(tap.len(), MAX_TAP_NAME_LEN);
+            assert!(tap.starts_with("tap-"));
+            assert!(tap[4..]
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+        }
+        assert_eq!(
+            tap_name_candidate(id, 0),
+            format!("tap-{}", &id.simple().to_string()[..11])
+        );
+    }
+
+    #[test]
+    fn test_pick_tap_name_avoids_prefix_collision() {
+        // Two VMs whose UUIDs share the leading hex would have collided on
+        // the old `tap-{id[..8]}` scheme. The second one must get a
+        // different, still-deterministic name.
+        let first = Uuid::parse_str("0123456789ab4def8123456789abcdef").unwrap();
+        let second = Uuid::parse_str("0123456789ab4fff8fffffffffffffff").unwrap();
+        let first_tap = pick_tap_name(first, &Default::default()).unwrap();
+        assert_eq!(first_tap, tap_name_candidate(first, 0));
+
+        let taken = std::collections::HashSet::from([first_tap.clone()]);
+        let second_tap = pick_tap_name(second, &taken).unwrap();
+        assert_ne!(second_tap, first_tap);
+        assert_eq!(second_tap, tap_name_candidate(second, 1));
+        assert_eq!(pick_tap_name(second, &taken).unwrap(), second_tap);
+    }
+
+    #[test]
+    fn test_stop_report_leaks_fails_only_when_scope_survives() {
+        use nexus_types::ArtifactTeardown;
+
+        let clean = StopVmReport::new(
+            ArtifactTeardown::from_result(Ok(true)),
+            ArtifactTeardown::from_result(Ok(false)),
+            ArtifactTeardown::from_result(Ok(true)),
+            None,
+        );
+        assert!(stop_report_leaks(&clean).unwrap().is_empty());
+
+        let leaked_tap = StopVmReport::new(
+            ArtifactTeardown::from_result(Ok(true)),
+            ArtifactTeardown::from_result(Err("RTNETLINK answers: busy".into())),
+            ArtifactTeardown::from_result(Ok(false)),
+            None,
+        );
+        assert_eq!(
+            stop_report_leaks(&leaked_tap).unwrap(),
+            vec!["stop left tap behind: RTNETLINK answers: busy".to_string()]
+        );
+
+        let scope_alive = StopVmReport::new(
+            ArtifactTeardown::from_result(Err("access denied".into())),
+            ArtifactTeardown::from_result(Ok(true)),
+            ArtifactTeardown::from_result(Ok(true)),
+            None,
+        );
+        let err = stop_report_leaks(&scope_alive).unwrap_err();
+        assert!(err.to_string().contains("access denied"));
+    }
+
+    #[test]
+    fn test_parse_readiness_timeout_is_opt_in() {
+        assert_eq!(parse_readiness_timeout(None), None);
+        assert_eq!(parse_readiness_timeout(Some("0")), None);
+        assert_eq!(parse_readiness_timeout(Some("soon")), None);
+        assert_eq!(
+            parse_readiness_timeout(Some(" 30 ")),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_is_running_state_accepts_degraded() {
+        assert!(is_running_state("running"));
+        assert!(is_running_state(RUNNING_DEGRADED));
+        assert!(!is_running_state("starting"));
+        assert!(!is_running_state("stopped"));
+    }
+
+    #[test]
+    fn test_generate_shell_password_is_random_and_shell_safe() {
+        let first = generate_shell_password();
+        let second = generate_shell_password();
+        assert_eq!(first.len(), 32);
+        assert_ne!(first, second);
+        assert!(first
             .chars()
-            .all(|c| c.is_ascii_hexdigit() && (c.is_ascii_digit() || c.is_ascii_lowercase())));
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn test_pick_tap_name_gives_up_when_every_candidate_is_taken() {
+        let id = Uuid::new_v4();
+        let taken = (0..MAX_TAP_ATTEMPTS)
+            .map(|attempt| tap_name_candidate(id, attempt))
+            .collect();
+        assert!(pick_tap_name(id, &taken).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_allocate_tap_name_skips_names_held_by_existing_vms() {
+        let db = sqlx::PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+        let existing = Uuid::parse_str("fedcba987654411188888888888888aa").unwrap();
+        repo::insert(&db, &make_vm_row_for_paths(existing))
+            .await
+            .unwrap();
+
+        let colliding = Uuid::parse_str("fedcba987654422299999999999999bb").unwrap();
+        let tap = allocate_tap_name(&db, colliding).await.unwrap();
+        assert_eq!(tap, tap_name_candidate(colliding, 1));
+
+        repo::delete_row(&db, existing).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn allocate_nic_tap_name_skips_names_held_by_other_vms() {
+        let db = sqlx::PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+        let existing = Uuid::parse_str("ab12cd00000041118888888888888801").unwrap();
+        let nic = repo::nics::insert(
+            &db,
+            existing,
+            "eth1",
+            &nic_tap_name(existing, 1),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Same 4-char prefix, so the readable name is already taken
+        let colliding = Uuid::parse_str("ab12ef00000042229999999999999902").unwrap();
+        let tap = allocate_nic_tap_name(&db, colliding, 1).await.unwrap();
+        assert_eq!(tap, nic_tap_name_candidate(colliding, 1, 1));
+        assert_eq!(tap.len(), MAX_TAP_NAME_LEN);
+
+        repo::nics::delete(&db, nic.id).await.unwrap();
     }
 
     #[test]
@@ -3560,6 +5506,11 @@ mod tests {
             parent_id: None,
             name: None,
             track_dirty_pages: false,
+            firecracker_version: None,
+            vcpu: None,
+            mem_mib: None,
+            cpu_template: None,
+            compressed: false,
             created_at: now,
             updated_at: now,
         };
@@ -3601,6 +5552,11 @@ mod tests {
             parent_id: Some(Uuid::new_v4()),
             name: None,
             track_dirty_pages: true,
+            firecracker_version: None,
+            vcpu: None,
+            mem_mib: None,
+            cpu_template: None,
+            compressed: false,
             created_at: now,
             updated_at: now,
         };
@@ -3624,14 +5580,298 @@ mod tests {
     }
 
     #[test]
-    fn test_proxy_query_string_uses_url_encoded_socket_path() {
-        // Many callers build their FC-proxy URL as
-        //     {host}/agent/v1/vms/{id}/proxy?sock={encoded_sock}
-        // Lock the encoding here: forward slashes, dashes and dots come
-        // through verbatim because they are valid path characters.
-        let sock = "/srv/fc/vms/abc-def/sock/fc.sock";
-        let qs = format!("?sock={}", urlencoding::encode(sock));
-        assert_eq!(qs, "?sock=%2Fsrv%2Ffc%2Fvms%2Fabc-def%2Fsock%2Ffc.sock");
+    fn test_proxy_query_string_uses_url_encoded_socket_path() {
+        // Many callers build their FC-proxy URL as
+        //     {host}/agent/v1/vms/{id}/proxy?sock={encoded_sock}
+        // Lock the encoding here: forward slashes, dashes and dots come
+        // through verbatim because they are valid path characters.
+        let sock = "/srv/fc/vms/abc-def/sock/fc.sock";
+        let qs = format!("?sock={}", urlencoding::encode(sock));
+        assert_eq!(qs, "?sock=%2Fsrv%2Ffc%2Fvms%2Fabc-def%2Fsock%2Ffc.sock");
+    }
+
+    #[test]
+    fn test_generate_guest_mac_is_locally_administered_and_stable() {
+        let vm_id = Uuid::new_v4();
+        let mac = generate_guest_mac(vm_id, 1);
+        assert!(mac.starts_with("02:"), "unexpected prefix: {mac}");
+        assert_eq!(mac.len(), 17);
+        assert_eq!(normalize_guest_mac(&mac).unwrap(), mac);
+        // Same inputs must always produce the same MAC across restarts.
+        assert_eq!(generate_guest_mac(vm_id, 1), mac);
+    }
+
+    #[test]
+    fn test_generate_guest_mac_unique_across_vms_and_ifaces() {
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..500 {
+            let vm_id = Uuid::new_v4();
+            for idx in 0..8 {
+                assert!(seen.insert(generate_guest_mac(vm_id, idx)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_guest_mac_rejects_bad_input() {
+        assert_eq!(
+            normalize_guest_mac(" 02:AB:cd:00:11:22 ").unwrap(),
+            "02:ab:cd:00:11:22"
+        );
+        assert!(normalize_guest_mac("02:ab:cd:00:11").is_err());
+        assert!(normalize_guest_mac("02-ab-cd-00-11-22").is_err());
+        assert!(normalize_guest_mac("02:ab:cd:00:11:zz").is_err());
+        assert!(normalize_guest_mac("01:00:5e:00:00:01").is_err()); // multicast
+        assert!(normalize_guest_mac("00:00:00:00:00:00").is_err());
+    }
+
+    #[test]
+    fn test_inventory_lists_scope_matches_exact_unit() {
+        let inv = json!({"scopes": ["fc-abc.scope", "fc-def.scope"], "taps": [], "sockets": []});
+        assert!(inventory_lists_scope(&inv, "fc-abc.scope"));
+        assert!(!inventory_lists_scope(&inv, "fc-ab.scope"));
+        assert!(!inventory_lists_scope(&json!({}), "fc-abc.scope"));
+    }
+
+    #[tokio::test]
+    async fn test_list_events_paginates_newest_first_and_rejects_unknown_vm() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+
+        let missing = list_events(&pool, Uuid::new_v4(), Default::default()).await;
+        assert!(missing.unwrap_err().to_string().contains("not found"));
+
+        let id = Uuid::new_v4();
+        repo::insert(&pool, &make_vm_row_for_paths(id))
+            .await
+            .unwrap();
+        for i in 0..3 {
+            repo::insert_event(&pool, id, "error", &format!("restart {i}"))
+                .await
+                .unwrap();
+        }
+
+        let params = nexus_types::ListVmEventsParams {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = list_events(&pool, id, params).await.unwrap();
+        let messages: Vec<_> = first.items.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["restart 2", "restart 1"]);
+        assert_eq!(first.next_offset, Some(2));
+
+        let params = nexus_types::ListVmEventsParams {
+            limit: Some(2),
+            offset: first.next_offset,
+            ..Default::default()
+        };
+        let second = list_events(&pool, id, params).await.unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert_eq!(second.items[0].message, "restart 0");
+        assert_eq!(second.items[0].kind, "error");
+        assert_eq!(second.next_offset, None);
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume_keep_the_pre_pause_state() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let id = Uuid::new_v4();
+        let mut row = make_vm_row_for_paths(id);
+        row.state = RUNNING_DEGRADED.into();
+        row.host_addr = server.url();
+        repo::insert(&pool, &row).await.unwrap();
+        let vm_path = mockito::Matcher::Regex(format!("^/agent/v1/vms/{id}/proxy/vm"));
+
+        // The agent refusing the pause leaves the VM as it was
+        let refused = server
+            .mock("PATCH", vm_path.clone())
+            .with_status(500)
+            .with_body(r#"{"fault_message":"vmm busy"}"#)
+            .create_async()
+            .await;
+        assert!(pause(&pool, id).await.is_err());
+        assert_eq!(repo::get(&pool, id).await.unwrap().state, RUNNING_DEGRADED);
+        refused.remove_async().await;
+
+        let ok = server
+            .mock("PATCH", vm_path)
+            .with_status(204)
+            .expect(2)
+            .create_async()
+            .await;
+        pause(&pool, id).await.unwrap();
+        assert_eq!(repo::get(&pool, id).await.unwrap().state, "paused");
+        resume(&pool, id).await.unwrap();
+        assert_eq!(repo::get(&pool, id).await.unwrap().state, RUNNING_DEGRADED);
+        ok.assert_async().await;
+
+        repo::delete_row(&pool, id).await.unwrap();
+    }
+
+    #[test]
+    fn boot_args_merge_user_tokens_and_pin_vm_id() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            build_boot_args(id, None),
+            format!("console=ttyS0 reboot=k panic=1 pci=off init=/sbin/init vm_id={id}")
+        );
+
+        let args = build_boot_args(
+            id,
+            Some("root=/dev/vda ro init=/bin/sh vm_id=spoofed console=hvc0"),
+        );
+        assert_eq!(
+            args,
+            format!(
+                "reboot=k panic=1 pci=off root=/dev/vda ro init=/bin/sh console=hvc0 vm_id={id}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_machine_config_body_carries_smt_and_huge_pages() {
+        let mut spec = ResolvedVmSpec {
+            name: "vm".into(),
+            vcpu: 2,
+            mem_mib: 1024,
+            kernel_path: "/k".into(),
+            rootfs_path: "/r".into(),
+            rootfs_is_vhost_user: false,
+            rootfs_size_bytes: None,
+            rootfs_volume_handle: None,
+            boot_args: None,
+            smt: false,
+            huge_pages: None,
+            rootfs_rate_limiter: None,
+            entropy_rate_limiter: None,
+            rootfs_read_only: false,
+            rootfs_overlay: None,
+            scope_limits: ScopeLimits::default(),
+        };
+        assert_eq!(
+            machine_config_body(&spec),
+            json!({"vcpu_count": 2, "mem_size_mib": 1024, "smt": false})
+        );
+
+        spec.smt = true;
+        spec.huge_pages = Some("2M".into());
+        let body = machine_config_body(&spec);
+        assert_eq!(body["smt"], json!(true));
+        assert_eq!(body["huge_pages"], json!("2M"));
+    }
+
+    #[test]
+    fn restore_shape_rejects_vcpu_change_and_memory_growth_past_boot_size() {
+        assert!(validate_restore_shape(2, 1024, None, None).is_ok());
+        assert!(validate_restore_shape(2, 1024, Some(2), Some(1024)).is_ok());
+        let err = validate_restore_shape(2, 1024, Some(4), None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot change on restore"), "{err}");
+        let err = validate_restore_shape(2, 1024, None, Some(2048))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Invalid mem_mib"), "{err}");
+    }
+
+    #[test]
+    fn balloon_deflates_only_to_expose_more_memory() {
+        // Booted with 2 GiB, balloon holding 1 GiB: guest sees 1 GiB.
+        assert_eq!(balloon_deflate_target(2048, 1536, 1024), Some(512));
+        assert_eq!(balloon_deflate_target(2048, 2048, 1024), Some(0));
+        // Already visible, or would require inflating: leave it alone.
+        assert_eq!(balloon_deflate_target(2048, 1024, 1024), None);
+        assert_eq!(balloon_deflate_target(2048, 512, 1024), None);
+        assert_eq!(balloon_deflate_target(2048, 2048, 0), None);
+    }
+
+    #[test]
+    fn test_validate_vm_tags_enforces_count_and_length() {
+        assert!(validate_vm_tags(&[]).is_ok());
+        assert!(validate_vm_tags(&["env:prod".into(), "a".repeat(MAX_VM_TAG_LEN)]).is_ok());
+        assert!(validate_vm_tags(&["  ".into()]).is_err());
+        assert!(validate_vm_tags(&["a".repeat(MAX_VM_TAG_LEN + 1)]).is_err());
+        let too_many: Vec<String> = (0..=MAX_VM_TAGS).map(|i| format!("t{i}")).collect();
+        let err = validate_vm_tags(&too_many).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid tags"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_huge_pages_accepts_firecracker_values_only() {
+        assert!(validate_huge_pages(None).is_ok());
+        assert!(validate_huge_pages(Some("None")).is_ok());
+        assert!(validate_huge_pages(Some("2M")).is_ok());
+        let err = validate_huge_pages(Some("1G")).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid huge_pages"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_smt_needs_one_or_an_even_vcpu_count() {
+        assert!(validate_smt(None, 3).is_ok());
+        assert!(validate_smt(Some(false), 3).is_ok());
+        assert!(validate_smt(Some(true), 1).is_ok());
+        assert!(validate_smt(Some(true), 4).is_ok());
+        let err = validate_smt(Some(true), 3).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid smt"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_rootfs_mode_limits_overlay_options_to_shared() {
+        assert!(validate_rootfs_mode(None, Some(4096), None).is_ok());
+        assert!(validate_rootfs_mode(Some(RootfsMode::Shared), None, Some(256)).is_ok());
+        let err = validate_rootfs_mode(None, None, Some(256))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("invalid overlay_size_mb"), "{err}");
+        let err = validate_rootfs_mode(Some(RootfsMode::Shared), Some(4096), None)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("invalid rootfs_size_mb"), "{err}");
+        assert!(validate_rootfs_mode(Some(RootfsMode::Shared), None, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_validate_scope_limits_bounds_quota_by_vcpus() {
+        assert!(validate_scope_limits(None, None, 2).is_ok());
+        assert!(validate_scope_limits(Some(200), Some(10000), 2).is_ok());
+        let err = validate_scope_limits(Some(250), None, 2)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("invalid cpu_quota"), "{err}");
+        assert!(validate_scope_limits(Some(0), None, 2).is_err());
+        assert!(validate_scope_limits(None, Some(0), 2).is_err());
+        assert!(validate_scope_limits(None, Some(10001), 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_mmds_returns_stored_config_and_data() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://nobody@localhost/nobody").unwrap();
+
+        let missing = get_mmds(&pool, Uuid::new_v4()).await;
+        assert!(missing.unwrap_err().to_string().contains("not found"));
+
+        let id = Uuid::new_v4();
+        repo::insert(&pool, &make_vm_row_for_paths(id))
+            .await
+            .unwrap();
+        let empty = get_mmds(&pool, id).await.unwrap();
+        assert!(empty.config.is_none() && empty.data.is_none());
+
+        let config = json!({"version": "V2", "network_interfaces": ["eth0"]});
+        repo::mmds::upsert_config(&pool, id, &config).await.unwrap();
+        repo::mmds::upsert_data(&pool, id, &json!({"latest": {"user-data": "abc"}}))
+            .await
+            .unwrap();
+
+        let stored = get_mmds(&pool, id).await.unwrap();
+        let stored_config = stored.config.unwrap();
+        assert_eq!(stored_config.version.as_deref(), Some("V2"));
+        assert_eq!(
+            stored_config.network_interfaces,
+            Some(vec!["eth0".to_string()])
+        );
+        assert_eq!(stored.data.unwrap()["latest"]["user-data"], "abc");
+        assert!(stored.updated_at.is_some());
     }
 }
 
@@ -3641,7 +5881,7 @@ async fn allocate_ip_from_cidr(db: &PgPool, network_id: Uuid, cidr: &str) -> Res
     // Parse CIDR (e.g., "10.9.0.0/24")
     let parts: Vec<&str> = cidr.split('/').collect();
     if parts.len() != 2 {
-        bail!("Invalid CIDR format: {}", cidr);
+        bail!(bad_request(format!("Invalid CIDR format: {}", cidr)));
     }
 
     let network_addr = parts[0];
@@ -3650,7 +5890,10 @@ async fn allocate_ip_from_cidr(db: &PgPool, network_id: Uuid, cidr: &str) -> Res
     // Parse network address octets
     let octets: Vec<&str> = network_addr.split('.').collect();
     if octets.len() != 4 {
-        bail!("Invalid IP address in CIDR: {}", network_addr);
+        bail!(bad_request(format!(
+            "Invalid IP address in CIDR: {}",
+            network_addr
+        )));
     }
 
     let base_octets: Result<Vec<u8>, _> = octets.iter().map(|o| o.parse()).collect();
@@ -3688,6 +5931,75 @@ async fn allocate_ip_from_cidr(db: &PgPool, network_id: Uuid, cidr: &str) -> Res
     bail!("No available IPs in network {}", cidr);
 }
 
+/// Record the guest IP the guest agent reported. When it also reports all of
+/// its addresses, the one inside the eth0 NIC's network wins, so a guest
+/// reporting from the wrong interface still ends up with a reachable IP.
+pub async fn record_guest_ip(
+    st: &AppState,
+    vm_id: Uuid,
+    reported: Option<&str>,
+    addrs: &[&str],
+) -> Result<Option<String>> {
+    let primary_cidr = if addrs.is_empty() {
+        None
+    } else {
+        primary_network_cidr(st, vm_id).await
+    };
+    let ip = pick_guest_ip(reported, addrs, primary_cidr.as_deref());
+    super::repo::update_guest_ip(&st.db, vm_id, ip.as_deref()).await?;
+    Ok(ip)
+}
+
+/// CIDR of the network eth0 is attached to, if it has one
+async fn primary_network_cidr(st: &AppState, vm_id: Uuid) -> Option<String> {
+    use crate::features::networks::repo::NetworkRepository;
+    let nics = super::repo::nics::list(&st.db, vm_id).await.ok()?;
+    let network_id = nics.iter().find(|n| n.iface_id == "eth0")?.network_id?;
+    NetworkRepository::new(st.db.clone())
+        .get(network_id)
+        .await
+        .ok()?
+        .cidr
+}
+
+/// The address inside `primary_cidr`, else the one the agent reported, else
+/// the first IPv4 address among `addrs`.
+fn pick_guest_ip(
+    reported: Option<&str>,
+    addrs: &[&str],
+    primary_cidr: Option<&str>,
+) -> Option<String> {
+    primary_cidr
+        .and_then(|cidr| addrs.iter().find(|ip| ipv4_in_cidr(ip, cidr)))
+        .copied()
+        .or(reported)
+        .or_else(|| {
+            addrs
+                .iter()
+                .find(|ip| ip.parse::<Ipv4Addr>().is_ok())
+                .copied()
+        })
+        .map(str::to_string)
+}
+
+fn ipv4_in_cidr(ip: &str, cidr: &str) -> bool {
+    let Some((net, prefix)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(ip), Ok(net), Ok(prefix)) = (
+        ip.parse::<Ipv4Addr>(),
+        net.parse::<Ipv4Addr>(),
+        prefix.parse::<u32>(),
+    ) else {
+        return false;
+    };
+    if prefix > 32 {
+        return false;
+    }
+    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    u32::from(ip) & mask == u32::from(net) & mask
+}
+
 /// Helper function to detect connection errors that should trigger a retry
 fn is_connection_error(e: &reqwest::Error) -> bool {
     e.is_timeout() || e.is_connect() || e.to_string().contains("No route to host")
@@ -3748,7 +6060,7 @@ async fn configure_secondary_nics_via_guest_agent(st: &AppState, vm_id: Uuid) ->
                       "retrying interface configuration with updated guest IP");
             }
 
-            let client = Client::builder()
+            let client = reqwest::Client::builder()
                 .timeout(Duration::from_secs(5))
                 .build()
                 .context("failed to build reqwest client")?;
@@ -3837,11 +6149,6 @@ async fn create_tap_with_vlan(
     bridge: &str,
     vlan_id: Option<u16>,
 ) -> Result<()> {
-    let http = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build reqwest client (create_tap_with_vlan)")?;
-
     info!(vm_id=%id, tap=%tap_name, %bridge, ?vlan_id, "creating TAP device on agent");
 
     let mut payload = json!({
@@ -3854,12 +6161,15 @@ async fn create_tap_with_vlan(
         payload["vlan_id"] = json!(vlan);
     }
 
-    http.post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
+    crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
         .json(&payload)
+        .timeout(Duration::from_secs(10))
         .send()
         .await
         .context("create_tap_with_vlan request failed to send")?
-        .error_for_status()
+        .or_fault()
+        .await
         .context("create_tap_with_vlan returned error status")?;
 
     info!(vm_id=%id, tap=%tap_name, "TAP device created successfully");
@@ -3867,26 +6177,24 @@ async fn create_tap_with_vlan(
 }
 
 #[cfg(not(test))]
-async fn create_tap(host_addr: &str, id: Uuid, bridge: &str) -> Result<()> {
-    let http = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build reqwest client (create_tap)")?;
-    let tap = format!("tap-{}", &id.to_string()[..8]);
+async fn create_tap(host_addr: &str, id: Uuid, tap: &str, bridge: &str) -> Result<()> {
     info!(vm_id=%id, step="tap", %tap, "creating tap on agent");
-    http.post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
-        .json(&json!({"bridge": bridge, "owner_user": Value::Null}))
+    crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/vms/{id}/tap"))
+        .json(&json!({"bridge": bridge, "owner_user": Value::Null, "tap_name": tap}))
+        .timeout(Duration::from_secs(10))
         .send()
         .await
         .context("create_tap request failed to send")?
-        .error_for_status()
+        .or_fault()
+        .await
         .context("create_tap returned error status")?;
     info!(vm_id=%id, step="tap", "ok");
     Ok(())
 }
 
 #[cfg(test)]
-async fn create_tap(_: &str, _: Uuid, _: &str) -> Result<()> {
+async fn create_tap(_: &str, _: Uuid, _: &str, _: &str) -> Result<()> {
     Ok(())
 }
 
@@ -3896,20 +6204,19 @@ async fn spawn_firecracker(
     host_addr: &str,
     id: Uuid,
     paths: &VmPaths,
+    limits: ScopeLimits,
 ) -> Result<()> {
-    let http = Client::builder()
-        .timeout(Duration::from_secs(2))
-        .build()
-        .context("failed to build reqwest client (spawn)")?;
-
     info!(vm_id=%id, step="spawn", sock=%paths.sock, "requesting firecracker spawn on agent");
     // Fire-and-forget: do not block the creation flow on systemd-run latency
-    match http
+    match crate::core::agent_http::client()
         .post(format!("{host_addr}/agent/v1/vms/{id}/spawn"))
         .json(&json!({
             "sock": paths.sock,
-            "log_path": paths.log_path
+            "log_path": paths.log_path,
+            "cpu_quota": limits.cpu_quota,
+            "io_weight": limits.io_weight
         }))
+        .timeout(Duration::from_secs(2))
         .send()
         .await
     {
@@ -3949,15 +6256,13 @@ async fn poll_socket_ready(
     expected_sock: &str,
     timeout: Duration,
 ) -> Result<bool> {
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(3))
-        .build()
-        .context("failed to build reqwest client (inventory)")?;
+    let client = crate::core::agent_http::client();
     let id_str = id.to_string();
     let start = Instant::now();
     while start.elapsed() < timeout {
         let resp = client
             .get(format!("{host_addr}/agent/v1/inventory"))
+            .timeout(Duration::from_secs(3))
             .send()
             .await;
         if let Ok(ok) = resp {
@@ -3977,10 +6282,81 @@ async fn poll_socket_ready(
 }
 
 #[cfg(test)]
-async fn spawn_firecracker(_: &AppState, _: &str, _: Uuid, _: &VmPaths) -> Result<()> {
+async fn spawn_firecracker(
+    _: &AppState,
+    _: &str,
+    _: Uuid,
+    _: &VmPaths,
+    _: ScopeLimits,
+) -> Result<()> {
+    Ok(())
+}
+
+const BASE_BOOT_ARGS: &str = "console=ttyS0 reboot=k panic=1 pci=off init=/sbin/init";
+
+/// Kernel command line for a Firecracker VM. User tokens are appended to the
+/// base args; a user token with the same key (`root=...`, `ro`) replaces the
+/// base one. `vm_id=` is always the manager's value so the guest agent can
+/// tell which VM it is running in after a snapshot restore.
+#[cfg_attr(test, allow(dead_code))]
+fn build_boot_args(vm_id: Uuid, extra: Option<&str>) -> String {
+    fn key(token: &str) -> &str {
+        token.split_once('=').map_or(token, |(k, _)| k)
+    }
+
+    let user: Vec<&str> = extra
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|token| key(token) != "vm_id")
+        .collect();
+    let mut tokens: Vec<&str> = BASE_BOOT_ARGS
+        .split_whitespace()
+        .filter(|base| !user.iter().any(|u| key(u) == key(base)))
+        .collect();
+    tokens.extend(user);
+
+    let mut args = tokens.join(" ");
+    args.push_str(&format!(" vm_id={vm_id}"));
+    args
+}
+
+/// Page sizes Firecracker accepts for `machine-config.huge_pages`.
+const HUGE_PAGES_VALUES: &[&str] = &["None", "2M"];
+
+fn validate_huge_pages(huge_pages: Option<&str>) -> Result<()> {
+    match huge_pages {
+        Some(value) if !HUGE_PAGES_VALUES.contains(&value) => bail!(bad_request(format!(
+            "Invalid huge_pages '{value}': must be one of {}",
+            HUGE_PAGES_VALUES.join(", ")
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Firecracker only enables SMT for one vCPU or an even count; caught here
+/// so the create is refused before anything is provisioned.
+fn validate_smt(smt: Option<bool>, vcpu: u8) -> Result<()> {
+    if smt == Some(true) && vcpu != 1 && !vcpu.is_multiple_of(2) {
+        bail!(bad_request(format!(
+            "Invalid smt: needs 1 or an even number of vCPUs, got {vcpu}"
+        )));
+    }
     Ok(())
 }
 
+#[cfg_attr(test, allow(dead_code))]
+fn machine_config_body(spec: &ResolvedVmSpec) -> Value {
+    let mut body = json!({
+        "vcpu_count": spec.vcpu,
+        "mem_size_mib": spec.mem_mib,
+        "smt": spec.smt,
+    });
+    if let Some(huge_pages) = &spec.huge_pages {
+        body["huge_pages"] = json!(huge_pages);
+    }
+    body
+}
+
 #[cfg_attr(test, allow(dead_code))]
 fn firecracker_drive_config(
     drive_id: &str,
@@ -4015,22 +6391,16 @@ async fn configure_vm(
 ) -> Result<()> {
     let base = format!("{host_addr}/agent/v1/vms/{id}/proxy");
     let qs = format!("?sock={}", urlencoding::encode(&paths.sock));
-    let http = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build reqwest client")?;
+    let http = crate::core::agent_http::client();
 
     info!(vm_id=%id, step="machine-config", vcpu=%spec.vcpu, mem_mib=%spec.mem_mib, "configuring machine");
     http.put(format!("{base}/machine-config{qs}"))
-        .json(&json!({
-            "vcpu_count": spec.vcpu,
-            "mem_size_mib": spec.mem_mib,
-            "smt": false
-        }))
+        .json(&machine_config_body(spec))
         .send()
         .await
         .context("machine-config request failed to send")?
-        .error_for_status()
+        .or_fault()
+        .await
         .context("machine-config returned error status")?;
     info!(vm_id=%id, step="machine-config", "ok");
 
@@ -4039,69 +6409,144 @@ async fn configure_vm(
         http.put(format!("{base}/boot-source{qs}"))
             .json(&json!({
                 "kernel_image_path": spec.kernel_path,
-                "boot_args": "console=ttyS0 reboot=k panic=1 pci=off init=/sbin/init",
+                "boot_args": build_boot_args(id, spec.boot_args.as_deref()),
             }))
             .send()
             .await
             .context("boot-source request failed to send")?
-            .error_for_status()
+            .or_fault()
+            .await
             .context("boot-source returned error status")?;
         info!(vm_id=%id, step="boot-source", "ok");
 
+        let db_drives = super::repo::drives::list(&st.db, id).await?;
+        let mut rootfs_config = firecracker_drive_config(
+            "rootfs",
+            &spec.rootfs_path,
+            true,
+            spec.rootfs_read_only,
+            spec.rootfs_is_vhost_user,
+        );
+        let rootfs_drive_limiter = db_drives
+            .iter()
+            .find(|drive| drive.drive_id == "rootfs")
+            .and_then(|drive| drive.rate_limiter.as_ref());
+        if let Some(limiter) = effective_rootfs_rate_limiter(
+            rootfs_drive_limiter,
+            spec.rootfs_rate_limiter.as_ref(),
+            default_rootfs_rate_limiter().as_ref(),
+        ) {
+            rootfs_config["rate_limiter"] = limiter;
+        }
+
         info!(vm_id=%id, step="drives", rootfs_path=%spec.rootfs_path, "attaching rootfs drive");
         http.put(format!("{base}/drives/rootfs{qs}"))
-            .json(&firecracker_drive_config(
-                "rootfs",
-                &spec.rootfs_path,
-                true,
-                false,
-                spec.rootfs_is_vhost_user,
-            ))
+            .json(&rootfs_config)
             .send()
             .await
             .context("drives request failed to send")?
-            .error_for_status()
+            .or_fault()
+            .await
             .context("drives returned error status")?;
         info!(vm_id=%id, step="drives", "ok");
 
+        // A shared rootfs's overlay goes right after it, so the guest always
+        // sees it as /dev/vdb.
+        let overlay_path = if spec.rootfs_read_only {
+            spec.rootfs_overlay
+                .as_ref()
+                .map(|overlay| overlay.locator.clone())
+                .or_else(|| {
+                    db_drives
+                        .iter()
+                        .find(|drive| drive.drive_id == OVERLAY_DRIVE_ID)
+                        .map(|drive| drive.path_on_host.clone())
+                })
+        } else {
+            None
+        };
+        if let Some(path) = overlay_path.as_deref() {
+            info!(vm_id=%id, step="drives", overlay_path=%path, "attaching rootfs overlay");
+            http.put(format!("{base}/drives/{OVERLAY_DRIVE_ID}{qs}"))
+                .json(&firecracker_drive_config(
+                    OVERLAY_DRIVE_ID,
+                    path,
+                    false,
+                    false,
+                    false,
+                ))
+                .send()
+                .await
+                .context("overlay drive request failed to send")?
+                .or_fault()
+                .await
+                .context("overlay drive returned error status")?;
+        }
+
         // Attach all additional drives from database
-        let db_drives = super::repo::drives::list(&st.db, id).await?;
         for drive in &db_drives {
+            if overlay_path.is_some() && drive.drive_id == OVERLAY_DRIVE_ID {
+                continue;
+            }
             // Validate drive path is allowed
             ensure_allowed_path(st, &drive.path_on_host)?;
 
             info!(vm_id=%id, drive_id=%drive.drive_id, path=%drive.path_on_host, "attaching additional drive from DB");
 
-            // Build drive config - only include optional fields if they have values
-            let mut drive_config = json!({
-                "drive_id": drive.drive_id,
-                "path_on_host": drive.path_on_host,
-                "is_root_device": drive.is_root_device,
-                "is_read_only": drive.is_read_only,
-            });
-
-            // Only add optional fields if they are Some
-            if let Some(ref cache) = drive.cache_type {
-                drive_config["cache_type"] = json!(cache);
-            }
-            if let Some(ref io) = drive.io_engine {
-                drive_config["io_engine"] = json!(io);
-            }
-            if let Some(ref rl) = drive.rate_limiter {
-                drive_config["rate_limiter"] = rl.clone();
-            }
-
             http.put(format!("{base}/drives/{}{}", drive.drive_id, qs))
-                .json(&drive_config)
+                .json(&drive_config(drive))
                 .send()
                 .await
                 .context("additional drive request failed to send")?
-                .error_for_status()
+                .or_fault()
+                .await
                 .context("additional drive returned error status")?;
         }
         if !db_drives.is_empty() {
             info!(vm_id=%id, count=%db_drives.len(), "attached drives from database");
         }
+
+        // A restored snapshot already carries its entropy device.
+        if entropy_device_enabled() {
+            let mut entropy = json!({});
+            if let Some(limiter) = spec.entropy_rate_limiter.as_ref() {
+                entropy["rate_limiter"] = normalize_rate_limiter(limiter);
+            }
+            // Best-effort: Firecracker before 1.4 has no entropy device.
+            let result = http
+                .put(format!("{base}/entropy{qs}"))
+                .json(&entropy)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+            match result {
+                Ok(_) => info!(vm_id=%id, step="entropy", "ok"),
+                Err(err) => {
+                    warn!(vm_id=%id, step="entropy", error=?err, "entropy device not attached")
+                }
+            }
+        }
+
+        // A restored snapshot already carries its vsock device too.
+        if super::guest_vsock::enabled() {
+            let result = http
+                .put(format!("{base}/vsock{qs}"))
+                .json(&json!({
+                    "vsock_id": super::guest_vsock::GUEST_VSOCK_ID,
+                    "guest_cid": super::guest_vsock::guest_cid(id),
+                    "uds_path": super::guest_vsock::uds_path(&paths.sock, id),
+                }))
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+            // Best-effort: the guest agent is still reachable over TCP.
+            match result {
+                Ok(_) => info!(vm_id=%id, step="vsock", "ok"),
+                Err(err) => {
+                    warn!(vm_id=%id, step="vsock", error=?err, "guest vsock not attached")
+                }
+            }
+        }
     }
 
     info!(vm_id=%id, step="network-interfaces", tap=%paths.tap, "configuring network interface");
@@ -4114,7 +6559,8 @@ async fn configure_vm(
         .send()
         .await
         .context("network-interfaces request failed to send")?
-        .error_for_status()
+        .or_fault()
+        .await
         .context("network-interfaces returned error status")?;
     info!(vm_id=%id, step="network-interfaces", "ok");
 
@@ -4129,35 +6575,26 @@ async fn configure_vm(
 
         info!(vm_id=%id, iface_id=%nic.iface_id, host_dev=%nic.host_dev_name, "attaching additional NIC from DB");
 
-        // Build NIC config - only include optional fields if they have values
-        let mut nic_config = json!({
-            "iface_id": nic.iface_id,
-            "host_dev_name": nic.host_dev_name,
-        });
-
-        // Only add optional fields if they are Some
-        if let Some(ref mac) = nic.guest_mac {
-            nic_config["guest_mac"] = json!(mac);
-        }
-        if let Some(ref rx) = nic.rx_rate_limiter {
-            nic_config["rx_rate_limiter"] = normalize_rate_limiter(rx);
-        }
-        if let Some(ref tx) = nic.tx_rate_limiter {
-            nic_config["tx_rate_limiter"] = normalize_rate_limiter(tx);
-        }
-
         http.put(format!("{base}/network-interfaces/{}{}", nic.iface_id, qs))
-            .json(&nic_config)
+            .json(&nic_config(nic))
             .send()
             .await
             .context("additional NIC request failed to send")?
-            .error_for_status()
+            .or_fault()
+            .await
             .context("additional NIC returned error status")?;
     }
     if !db_nics.is_empty() {
         info!(vm_id=%id, count=%db_nics.len(), "attached network interfaces from database");
     }
 
+    // MMDS state is part of the snapshot; only a cold boot needs it pushed again
+    if paths.snapshot_path.is_none() {
+        if let Err(e) = reapply_mmds(st, &http, &base, &qs, id).await {
+            warn!(vm_id=%id, error=?e, "failed to reapply stored MMDS state");
+        }
+    }
+
     info!(vm_id=%id, step="logger", log_path=%paths.log_path, "configuring logger");
     http.put(format!("{base}/logger{qs}"))
         .json(&json!({
@@ -4169,7 +6606,8 @@ async fn configure_vm(
         .send()
         .await
         .context("logger request failed to send")?
-        .error_for_status()
+        .or_fault()
+        .await
         .context("logger returned error status")?;
     info!(vm_id=%id, step="logger", "ok");
 
@@ -4219,7 +6657,7 @@ async fn configure_vm(
     if enable_metrics {
         // Ensure FIFO exists on the agent before configuring Firecracker metrics
         info!(vm_id=%id, step="metrics", metrics_path=%paths.metrics_path, "preparing metrics fifo");
-        Client::new()
+        crate::core::agent_http::client()
             .post(format!("{host_addr}/agent/v1/vms/{id}/metrics/prepare"))
             .json(&json!({
                 "metrics_path": paths.metrics_path
@@ -4227,7 +6665,8 @@ async fn configure_vm(
             .send()
             .await
             .context("metrics prepare request failed to send")?
-            .error_for_status()
+            .or_fault()
+            .await
             .context("metrics prepare returned error status")?;
 
         info!(vm_id=%id, step="metrics", metrics_path=%paths.metrics_path, "configuring metrics");
@@ -4239,7 +6678,8 @@ async fn configure_vm(
             .send()
             .await
             .context("metrics request failed to send")?
-            .error_for_status()
+            .or_fault()
+            .await
             .context("metrics returned error status")?;
         info!(vm_id=%id, step="metrics", "ok");
     } else {
@@ -4264,12 +6704,13 @@ async fn configure_vm(
 async fn start_vm(host_addr: &str, id: Uuid, paths: &VmPaths) -> Result<()> {
     let base = format!("{host_addr}/agent/v1/vms/{id}/proxy");
     let qs = format!("?sock={}", urlencoding::encode(&paths.sock));
-    Client::new()
+    crate::core::agent_http::client()
         .put(format!("{base}/actions{qs}"))
         .json(&json!({"action_type": "InstanceStart"}))
         .send()
         .await?
-        .error_for_status()?;
+        .or_fault()
+        .await?;
     Ok(())
 }
 
@@ -4278,16 +6719,77 @@ async fn start_vm(_: &str, _: Uuid, _: &VmPaths) -> Result<()> {
     Ok(())
 }
 
+/// Undo the host side of a create cancelled before InstanceStart. Every step
+/// tolerates the artifact not existing yet, so this is safe wherever the
+/// cancel landed; failures are only logged.
+async fn roll_back_cancelled_create(
+    st: &AppState,
+    host_addr: &str,
+    id: Uuid,
+    paths: &VmPaths,
+    spec: &ResolvedVmSpec,
+) {
+    if let Err(e) = teardown_on_host(host_addr, id, paths).await {
+        warn!(vm_id = %id, error = ?e, "rollback: agent teardown failed");
+    }
+    if let Some(handle) = spec.rootfs_volume_handle.as_ref() {
+        if let Some(backend) = st.registry.get(handle.backend_id.0) {
+            if let Err(e) = backend.deactivate_volume(handle).await {
+                warn!(vm_id = %id, error = ?e, "rollback: rootfs volume deactivate failed");
+            }
+        }
+    }
+    let storage_path = st.storage.vm_dir(id);
+    match tokio::fs::remove_dir_all(&storage_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            warn!(vm_id = %id, path = ?storage_path, error = ?e, "rollback: failed to remove storage directory")
+        }
+    }
+    info!(vm_id = %id, "rolled back cancelled create");
+}
+
+/// Stop the firecracker scope and delete the tap; the agent's stop handler
+/// does both and reports artifacts that were already absent.
+#[cfg(not(test))]
+async fn teardown_on_host(host_addr: &str, id: Uuid, paths: &VmPaths) -> Result<()> {
+    let report = crate::core::agent_http::client()
+        .post(format!("{host_addr}/agent/v1/vms/{id}/stop"))
+        .timeout(Duration::from_secs(30))
+        .json(&json!({
+            "tap": paths.tap,
+            "sock": paths.sock,
+            "fc_unit": paths.fc_unit,
+            "vsock_uds": super::guest_vsock::uds_path(&paths.sock, id),
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<StopVmReport>()
+        .await?;
+    for (artifact, teardown) in report.failures() {
+        warn!(vm_id = %id, artifact, error = ?teardown.error, "rollback: agent left an artifact behind");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+async fn teardown_on_host(_: &str, _: Uuid, _: &VmPaths) -> Result<()> {
+    Ok(())
+}
+
 /// Auto-register network if it doesn't already exist
 async fn ensure_network_registered(
     st: &AppState,
-    bridge_name: &str,
+    selection: &NetworkSelection,
     host_id: Uuid,
 ) -> Result<Uuid> {
     use crate::features::networks::repo::NetworkRepository;
     use tracing::info;
 
     let network_repo = NetworkRepository::new(st.db.clone());
+    let bridge_name = selection.bridge.as_str();
 
     // Check if a network with this bridge already exists for this host
     let existing = network_repo.list_by_host(host_id).await?;
@@ -4300,30 +6802,57 @@ async fn ensure_network_registered(
         }
     }
 
-    // Create new network record — default bridge uses NAT (10.0.0.0/24)
-    let name = "Default Network".to_string();
-    let description = Some("Auto-registered default network");
-
-    info!(bridge = %bridge_name, host_id = %host_id, name = %name, "creating new network record");
-
-    let result = network_repo
-        .create(
-            &name,
-            description,
-            "nat",
-            None, // no VLAN ID for default bridge
-            bridge_name,
-            host_id,
-            Some("10.0.0.0/24"),
-            Some("10.0.0.1"),
-            "active", // installer-created networks are already active
-            false,    // not managed (installer-created, read-only)
-            true,     // DHCP enabled
-            Some("10.0.0.10"),
-            Some("10.0.0.250"),
-            None, // no uplink_interface (installer-created)
-        )
-        .await?;
+    let result = match &selection.name {
+        // A named bridge is an L2 segment set up outside the manager, so its
+        // addressing is unknown
+        Some(name) => {
+            info!(bridge = %bridge_name, host_id = %host_id, name = %name, "creating new network record");
+            network_repo
+                .create(
+                    name,
+                    Some("Auto-registered host bridge"),
+                    "bridged",
+                    None,
+                    bridge_name,
+                    host_id,
+                    None,
+                    None,
+                    "active",
+                    false, // not managed (set up on the host, read-only)
+                    false, // no DHCP from the manager
+                    None,
+                    None,
+                    None,
+                )
+                .await?
+        }
+        // Create new network record — default bridge uses NAT (10.0.0.0/24)
+        None => {
+            let name = "Default Network".to_string();
+            let description = Some("Auto-registered default network");
+
+            info!(bridge = %bridge_name, host_id = %host_id, name = %name, "creating new network record");
+
+            network_repo
+                .create(
+                    &name,
+                    description,
+                    "nat",
+                    None, // no VLAN ID for default bridge
+                    bridge_name,
+                    host_id,
+                    Some("10.0.0.0/24"),
+                    Some("10.0.0.1"),
+                    "active", // installer-created networks are already active
+                    false,    // not managed (installer-created, read-only)
+                    true,     // DHCP enabled
+                    Some("10.0.0.10"),
+                    Some("10.0.0.250"),
+                    None, // no uplink_interface (installer-created)
+                )
+                .await?
+        }
+    };
 
     info!(bridge = %bridge_name, network_id = %result.id, "network created successfully");
 
@@ -4523,43 +7052,153 @@ async fn ensure_volume_registered(
     Ok(())
 }
 
+const MAX_VM_TAGS: usize = 32;
+const MAX_VM_TAG_LEN: usize = 64;
+
+pub(crate) fn validate_vm_tags(tags: &[String]) -> Result<()> {
+    if tags.len() > MAX_VM_TAGS {
+        bail!(bad_request(format!(
+            "Invalid tags: at most {MAX_VM_TAGS} tags are allowed"
+        )));
+    }
+    for tag in tags {
+        if tag.trim().is_empty() || tag.chars().count() > MAX_VM_TAG_LEN {
+            bail!(bad_request(format!(
+                "Invalid tag '{tag}': must be 1-{MAX_VM_TAG_LEN} non-blank characters"
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// Update VM metadata (name, tags). Does not affect running VM.
 pub async fn update_vm_metadata(
     st: &AppState,
     id: Uuid,
     name: Option<&str>,
     tags: Option<&[String]>,
-    user_id: Option<Uuid>,
-    audit_username: &str,
 ) -> Result<()> {
     // Verify VM exists
     let _vm = super::repo::get(&st.db, id)
         .await
-        .map_err(|_| anyhow!("VM not found: {}", id))?;
+        .map_err(|_| not_found(format!("VM not found: {}", id)))?;
 
     if let Some(name) = name {
         if name.trim().is_empty() {
-            bail!("VM name cannot be empty");
+            bail!(bad_request("VM name cannot be empty"));
         }
     }
+    if let Some(tags) = tags {
+        validate_vm_tags(tags)?;
+    }
 
     super::repo::update_metadata(&st.db, id, name, tags)
         .await
         .context("failed to update VM metadata")?;
 
-    let _ = audit::log_action(
-        &st.db,
-        user_id,
-        audit_username,
-        AuditAction::UpdateVm,
-        Some("vm"),
-        Some(id),
-        Some(json!({"name": name, "tags": tags})),
-        None,
-        true,
-        None,
-    )
-    .await;
+    Ok(())
+}
+
+/// Length in random bytes of a rotated shell password (32 base64 characters).
+const ROTATED_PASSWORD_BYTES: usize = 24;
+
+fn generate_shell_password() -> String {
+    use base64::{engine::general_purpose, Engine as _};
+    use rand::RngCore;
+    let mut bytes = [0u8; ROTATED_PASSWORD_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Replace a VM's shell password and push it into the guest.
+///
+/// A running VM is updated through the guest agent's `/run-command`, so the
+/// guest must have `ALLOW_EXEC=1`; a stopped VM gets the new hash written into
+/// its rootfs. The stored credentials only change once the guest has the new
+/// password, so a failed rotation leaves the old one working.
+pub async fn rotate_shell_credentials(st: &AppState, id: Uuid) -> Result<(String, String)> {
+    let vm = super::repo::get(&st.db, id).await?;
+    let username = st
+        .shell_repo
+        .get_credentials(id)
+        .await?
+        .map(|cred| cred.username)
+        .unwrap_or_else(|| "root".to_string());
+    let password = generate_shell_password();
+
+    if is_running_state(&vm.state) {
+        let guest_ip = vm
+            .guest_ip
+            .as_deref()
+            .filter(|ip| !ip.is_empty())
+            .context("guest agent has not reported an IP yet")?;
+        set_guest_password(guest_ip, &username, &password).await?;
+    } else if vm.state == "stopped" {
+        inject_credentials_to_rootfs(id, &vm.rootfs_path, &username, &password).await?;
+    } else {
+        bail!(bad_request(format!(
+            "VM must be running or stopped to rotate shell credentials (state: {})",
+            vm.state
+        )));
+    }
+
+    st.shell_repo
+        .upsert_credentials(id, &username, &password)
+        .await?;
+
+    Ok((username, password))
+}
+
+/// Point a running guest agent at a new VM id and manager URL, so it reports
+/// its IP under them.
+async fn update_guest_agent_config(guest_ip: &str, vm_id: Uuid, manager_url: &str) -> Result<()> {
+    reqwest::Client::new()
+        .post(format!("http://{}:9000/update-config", guest_ip))
+        .timeout(Duration::from_secs(10))
+        .json(&json!({
+            "vm_id": vm_id.to_string(),
+            "manager_url": manager_url,
+        }))
+        .send()
+        .await
+        .context("guest agent unreachable")?
+        .error_for_status()
+        .context("guest agent refused config update")?;
+    Ok(())
+}
 
+/// Set `username`'s password in a running guest. Only the crypt hash crosses
+/// the wire, so the plaintext never shows up in the guest's process list.
+async fn set_guest_password(guest_ip: &str, username: &str, password: &str) -> Result<()> {
+    let hash = hash_password(password).await?;
+    let resp = reqwest::Client::new()
+        .post(format!("http://{}:9000/run-command", guest_ip))
+        .timeout(Duration::from_secs(30))
+        .json(&json!({
+            "command": ["usermod", "-p", hash, username],
+            "timeout_secs": 15,
+        }))
+        .send()
+        .await
+        .context("guest agent unreachable")?;
+    let status = resp.status();
+    let body: Value = resp.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        bail!(
+            "guest agent refused password change: {}",
+            body.get("error")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error")
+        );
+    }
+    if body.get("exit_code").and_then(Value::as_i64) != Some(0) {
+        bail!(
+            "usermod failed in guest: {}",
+            body.get("stderr")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .trim()
+        );
+    }
     Ok(())
 }
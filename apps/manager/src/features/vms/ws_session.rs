@@ -0,0 +1,412 @@
+//! Limits for the websocket sessions the manager serves (shell, metrics and
+//! console): how long one may live, so an abandoned browser tab can't hold an
+//! agent PTY or metrics FIFO open forever, and how many may be open at once
+//! per VM and per user.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+const IDLE_TIMEOUT_ENV: &str = "MANAGER_WS_IDLE_TIMEOUT_SECS";
+const MAX_DURATION_ENV: &str = "MANAGER_WS_MAX_DURATION_SECS";
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const DEFAULT_MAX_DURATION: Duration = Duration::from_secs(8 * 60 * 60);
+const MAX_PER_VM_ENV: &str = "MANAGER_WS_MAX_SESSIONS_PER_VM";
+const MAX_PER_USER_ENV: &str = "MANAGER_WS_MAX_SESSIONS_PER_USER";
+const DEFAULT_MAX_PER_VM: usize = 4;
+const DEFAULT_MAX_PER_USER: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimits {
+    /// Close after this long without a frame in either direction.
+    pub idle: Duration,
+    /// Close this long after the session opened, active or not.
+    pub max: Duration,
+}
+
+impl SessionLimits {
+    /// Limits from `MANAGER_WS_IDLE_TIMEOUT_SECS` / `MANAGER_WS_MAX_DURATION_SECS`
+    /// (defaults: 15 minutes idle, 8 hours total).
+    pub fn from_env() -> Self {
+        Self {
+            idle: parse_limit(
+                std::env::var(IDLE_TIMEOUT_ENV).ok().as_deref(),
+                DEFAULT_IDLE_TIMEOUT,
+            ),
+            max: parse_limit(
+                std::env::var(MAX_DURATION_ENV).ok().as_deref(),
+                DEFAULT_MAX_DURATION,
+            ),
+        }
+    }
+}
+
+fn parse_limit(raw: Option<&str>, default: Duration) -> Duration {
+    raw.and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(default)
+}
+
+/// Why the manager ended a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionExpiry {
+    Idle,
+    MaxDuration,
+}
+
+/// Tracks activity on one websocket session against its [`SessionLimits`].
+pub struct SessionClock {
+    limits: SessionLimits,
+    started: Instant,
+    last_activity: Instant,
+}
+
+impl SessionClock {
+    pub fn start(limits: SessionLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            limits,
+            started: now,
+            last_activity: now,
+        }
+    }
+
+    /// Record a frame passing through the session.
+    pub fn touch(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// The earliest moment the session could expire; sleep until then and
+    /// re-check with [`SessionClock::expired_at`].
+    pub fn deadline(&self) -> Instant {
+        (self.last_activity + self.limits.idle).min(self.started + self.limits.max)
+    }
+
+    pub fn expired_at(&self, now: Instant) -> Option<SessionExpiry> {
+        if now >= self.started + self.limits.max {
+            Some(SessionExpiry::MaxDuration)
+        } else if now >= self.last_activity + self.limits.idle {
+            Some(SessionExpiry::Idle)
+        } else {
+            None
+        }
+    }
+
+    /// Text sent to the client as the last frame before the close.
+    pub fn close_message(&self, expiry: SessionExpiry) -> String {
+        match expiry {
+            SessionExpiry::Idle => format!(
+                "session closed: no activity for {}",
+                format_limit(self.limits.idle)
+            ),
+            SessionExpiry::MaxDuration => format!(
+                "session closed: reached the {} session limit",
+                format_limit(self.limits.max)
+            ),
+        }
+    }
+}
+
+fn format_limit(limit: Duration) -> String {
+    let secs = limit.as_secs();
+    if secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// What a websocket session is streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SessionKind {
+    Shell,
+    Metrics,
+    Console,
+}
+
+impl SessionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SessionKind::Shell => "shell",
+            SessionKind::Metrics => "metrics",
+            SessionKind::Console => "console",
+        }
+    }
+}
+
+/// How many sessions may be open at once, counting every kind together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionCaps {
+    pub per_vm: usize,
+    pub per_user: usize,
+}
+
+impl SessionCaps {
+    /// Caps from `MANAGER_WS_MAX_SESSIONS_PER_VM` /
+    /// `MANAGER_WS_MAX_SESSIONS_PER_USER` (defaults: 4 per VM, 8 per user).
+    pub fn from_env() -> Self {
+        Self {
+            per_vm: parse_cap(
+                std::env::var(MAX_PER_VM_ENV).ok().as_deref(),
+                DEFAULT_MAX_PER_VM,
+            ),
+            per_user: parse_cap(
+                std::env::var(MAX_PER_USER_ENV).ok().as_deref(),
+                DEFAULT_MAX_PER_USER,
+            ),
+        }
+    }
+}
+
+fn parse_cap(raw: Option<&str>, default: usize) -> usize {
+    raw.and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|cap| *cap > 0)
+        .unwrap_or(default)
+}
+
+#[derive(Default)]
+struct SessionCounts {
+    by_kind: BTreeMap<SessionKind, usize>,
+    by_vm: HashMap<Uuid, usize>,
+    by_user: HashMap<Uuid, usize>,
+}
+
+fn decrement<K: std::hash::Hash + Eq>(map: &mut HashMap<K, usize>, key: &K) {
+    if let Some(count) = map.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            map.remove(key);
+        }
+    }
+}
+
+/// Open websocket sessions, shared through `AppState`.
+#[derive(Clone)]
+pub struct WsSessionTracker {
+    caps: SessionCaps,
+    counts: Arc<Mutex<SessionCounts>>,
+}
+
+impl Default for WsSessionTracker {
+    fn default() -> Self {
+        Self::new(SessionCaps::from_env())
+    }
+}
+
+/// Which cap refused a new session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionCapExceeded {
+    Vm(usize),
+    User(usize),
+}
+
+impl std::fmt::Display for SessionCapExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionCapExceeded::Vm(cap) => {
+                write!(f, "too many websocket sessions for this VM (limit {cap})")
+            }
+            SessionCapExceeded::User(cap) => {
+                write!(f, "too many websocket sessions for this user (limit {cap})")
+            }
+        }
+    }
+}
+
+/// Point-in-time session counts for the Prometheus endpoint.
+pub struct SessionSnapshot {
+    pub caps: SessionCaps,
+    pub by_kind: Vec<(SessionKind, usize)>,
+    pub by_vm: Vec<(Uuid, usize)>,
+    pub by_user: Vec<(Uuid, usize)>,
+}
+
+impl WsSessionTracker {
+    pub fn new(caps: SessionCaps) -> Self {
+        Self {
+            caps,
+            counts: Arc::default(),
+        }
+    }
+
+    /// Claim a session slot; the slot is released when the guard drops, so
+    /// hold it for the life of the socket.
+    pub fn try_acquire(
+        &self,
+        kind: SessionKind,
+        vm_id: Uuid,
+        user_id: Option<Uuid>,
+    ) -> Result<WsSessionGuard, SessionCapExceeded> {
+        let mut counts = self.counts.lock().unwrap();
+        if counts.by_vm.get(&vm_id).copied().unwrap_or(0) >= self.caps.per_vm {
+            return Err(SessionCapExceeded::Vm(self.caps.per_vm));
+        }
+        if let Some(user_id) = user_id {
+            if counts.by_user.get(&user_id).copied().unwrap_or(0) >= self.caps.per_user {
+                return Err(SessionCapExceeded::User(self.caps.per_user));
+            }
+            *counts.by_user.entry(user_id).or_default() += 1;
+        }
+        *counts.by_vm.entry(vm_id).or_default() += 1;
+        *counts.by_kind.entry(kind).or_default() += 1;
+        Ok(WsSessionGuard {
+            tracker: self.clone(),
+            kind,
+            vm_id,
+            user_id,
+        })
+    }
+
+    pub fn snapshot(&self) -> SessionSnapshot {
+        let counts = self.counts.lock().unwrap();
+        let mut by_vm: Vec<_> = counts.by_vm.iter().map(|(k, v)| (*k, *v)).collect();
+        by_vm.sort();
+        let mut by_user: Vec<_> = counts.by_user.iter().map(|(k, v)| (*k, *v)).collect();
+        by_user.sort();
+        SessionSnapshot {
+            caps: self.caps,
+            by_kind: [SessionKind::Shell, SessionKind::Metrics]
+                .into_iter()
+                .map(|kind| (kind, counts.by_kind.get(&kind).copied().unwrap_or(0)))
+                .collect(),
+            by_vm,
+            by_user,
+        }
+    }
+}
+
+/// One open session's slot in the [`WsSessionTracker`].
+pub struct WsSessionGuard {
+    tracker: WsSessionTracker,
+    kind: SessionKind,
+    vm_id: Uuid,
+    user_id: Option<Uuid>,
+}
+
+impl Drop for WsSessionGuard {
+    fn drop(&mut self) {
+        let mut counts = self.tracker.counts.lock().unwrap();
+        if let Some(count) = counts.by_kind.get_mut(&self.kind) {
+            *count = count.saturating_sub(1);
+        }
+        decrement(&mut counts.by_vm, &self.vm_id);
+        if let Some(user_id) = self.user_id {
+            decrement(&mut counts.by_user, &user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(idle: u64, max: u64) -> SessionLimits {
+        SessionLimits {
+            idle: Duration::from_secs(idle),
+            max: Duration::from_secs(max),
+        }
+    }
+
+    #[test]
+    fn parse_limit_falls_back_to_default() {
+        let default = Duration::from_secs(60);
+        assert_eq!(parse_limit(None, default), default);
+        assert_eq!(parse_limit(Some("0"), default), default);
+        assert_eq!(parse_limit(Some("forever"), default), default);
+        assert_eq!(parse_limit(Some(" 90 "), default), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn clock_expires_on_idle_before_max() {
+        let clock = SessionClock::start(limits(60, 3600));
+        assert_eq!(clock.deadline(), clock.started + Duration::from_secs(60));
+        assert_eq!(clock.expired_at(clock.started), None);
+        assert_eq!(
+            clock.expired_at(clock.started + Duration::from_secs(60)),
+            Some(SessionExpiry::Idle)
+        );
+    }
+
+    #[test]
+    fn max_duration_wins_over_activity() {
+        let mut clock = SessionClock::start(limits(60, 90));
+        clock.last_activity = clock.started + Duration::from_secs(80);
+        assert_eq!(clock.deadline(), clock.started + Duration::from_secs(90));
+        assert_eq!(
+            clock.expired_at(clock.started + Duration::from_secs(90)),
+            Some(SessionExpiry::MaxDuration)
+        );
+    }
+
+    #[test]
+    fn close_message_names_the_limit() {
+        let clock = SessionClock::start(limits(15 * 60, 8 * 3600));
+        assert_eq!(
+            clock.close_message(SessionExpiry::Idle),
+            "session closed: no activity for 15m"
+        );
+        assert_eq!(
+            clock.close_message(SessionExpiry::MaxDuration),
+            "session closed: reached the 8h session limit"
+        );
+    }
+
+    #[test]
+    fn tracker_enforces_vm_and_user_caps() {
+        let tracker = WsSessionTracker::new(SessionCaps {
+            per_vm: 2,
+            per_user: 2,
+        });
+        let (vm_a, vm_b, user) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let shell = tracker
+            .try_acquire(SessionKind::Shell, vm_a, Some(user))
+            .unwrap();
+        let metrics = tracker
+            .try_acquire(SessionKind::Metrics, vm_a, None)
+            .unwrap();
+        assert_eq!(
+            tracker.try_acquire(SessionKind::Shell, vm_a, None).err(),
+            Some(SessionCapExceeded::Vm(2))
+        );
+
+        let other = tracker
+            .try_acquire(SessionKind::Shell, vm_b, Some(user))
+            .unwrap();
+        assert_eq!(
+            tracker
+                .try_acquire(SessionKind::Shell, vm_b, Some(user))
+                .err(),
+            Some(SessionCapExceeded::User(2))
+        );
+
+        let snapshot = tracker.snapshot();
+        assert_eq!(
+            snapshot.by_kind,
+            vec![(SessionKind::Shell, 2), (SessionKind::Metrics, 1)]
+        );
+        assert_eq!(snapshot.by_user, vec![(user, 2)]);
+
+        drop((shell, metrics, other));
+        let snapshot = tracker.snapshot();
+        assert!(snapshot.by_vm.is_empty());
+        assert!(snapshot.by_user.is_empty());
+        assert_eq!(
+            snapshot.by_kind,
+            vec![(SessionKind::Shell, 0), (SessionKind::Metrics, 0)]
+        );
+    }
+
+    #[test]
+    fn parse_cap_ignores_zero_and_garbage() {
+        assert_eq!(parse_cap(None, 4), 4);
+        assert_eq!(parse_cap(Some("0"), 4), 4);
+        assert_eq!(parse_cap(Some("lots"), 4), 4);
+        assert_eq!(parse_cap(Some("16"), 4), 16);
+    }
+}
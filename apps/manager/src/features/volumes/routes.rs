@@ -1,3 +1,4 @@
+use crate::features::users::audit::Actor;
 use crate::features::volumes::repo::{VolumeRepository, VolumeRow};
 use crate::AppState;
 use axum::{
@@ -6,6 +7,7 @@ use axum::{
     response::{IntoResponse, Response},
     Extension, Json,
 };
+use nexus_types::AuditAction;
 use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
@@ -176,7 +178,26 @@ async fn volume_to_list_item(
 )]
 pub async fn create(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Json(req): Json<CreateVolumeRequest>,
+) -> Result<Json<CreateVolumeResponse>, StatusCode> {
+    let details = serde_json::json!({"name": &req.name, "size_gb": req.size_gb});
+    let result = create_volume(&st, req).await;
+    let id = result.as_ref().ok().map(|Json(resp)| resp.id);
+    actor
+        .record_with(
+            AuditAction::CreateVolume,
+            ("volume", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    result
+}
+
+async fn create_volume(
+    st: &AppState,
+    req: CreateVolumeRequest,
 ) -> Result<Json<CreateVolumeResponse>, StatusCode> {
     // Validate volume type
     if req.volume_type != "raw" && req.volume_type != "qcow2" && req.volume_type != "ext4" {
@@ -304,9 +325,28 @@ pub async fn get(
 )]
 pub async fn attach(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
     Json(req): Json<AttachVolumeRequest>,
 ) -> Response {
+    let details = serde_json::json!({"vm_id": req.vm_id, "drive_id": &req.drive_id});
+    let response = attach_volume(&st, id, req).await;
+    let result = match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(status),
+    };
+    actor
+        .record_with(
+            AuditAction::AttachVolume,
+            ("volume", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    response
+}
+
+async fn attach_volume(st: &AppState, id: Uuid, req: AttachVolumeRequest) -> Response {
     let volume_repo = VolumeRepository::new(st.db.clone());
 
     // Verify volume exists and is available
@@ -366,8 +406,27 @@ pub async fn attach(
 )]
 pub async fn detach(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
     Json(req): Json<DetachVolumeRequest>,
+) -> Result<Json<OkResponse>, StatusCode> {
+    let details = serde_json::json!({"vm_id": req.vm_id});
+    let result = detach_volume(&st, id, req).await;
+    actor
+        .record_with(
+            AuditAction::DetachVolume,
+            ("volume", id),
+            Some(details),
+            &result,
+        )
+        .await;
+    result
+}
+
+async fn detach_volume(
+    st: &AppState,
+    id: Uuid,
+    req: DetachVolumeRequest,
 ) -> Result<Json<OkResponse>, StatusCode> {
     let volume_repo = VolumeRepository::new(st.db.clone());
 
@@ -404,8 +463,17 @@ pub async fn detach(
 )]
 pub async fn delete(
     Extension(st): Extension<AppState>,
+    actor: Actor,
     Path(id): Path<Uuid>,
 ) -> Result<Json<OkResponse>, StatusCode> {
+    let result = delete_volume(&st, id).await;
+    actor
+        .record(AuditAction::DeleteVolume, ("volume", id), &result)
+        .await;
+    result
+}
+
+async fn delete_volume(st: &AppState, id: Uuid) -> Result<Json<OkResponse>, StatusCode> {
     let volume_repo = VolumeRepository::new(st.db.clone());
 
     // Get volume to check status and get path
@@ -8,9 +8,9 @@ mod features;
 
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::future::IntoFuture as _;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
-use tower_http::cors::{Any, CorsLayer};
+use tokio::sync::{broadcast, Mutex, RwLock};
 use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 use utoipa::OpenApi as _;
@@ -19,13 +19,18 @@ use crate::features::storage::LocalStorage;
 use anyhow::Context as _;
 use features::hosts::repo::HostRepository;
 use features::images::repo::ImageRepository;
+use features::jobs::JobRegistry;
 use features::licensing::license_service::{self, LicenseConfig, SharedLicenseState};
 use features::licensing::repo::LicensingRepository;
 use features::snapshots::repo::SnapshotRepository;
 use features::sso::crypto as sso_crypto;
 use features::sso::repo::{AuthStateRepository, SsoProviderRepository, UserIdentityRepository};
+use features::users::login_throttle::LoginThrottle;
 use features::users::repo::UserRepository;
+use features::vms::progress::CreateProgressTracker;
 use features::vms::shell::ShellRepository;
+use features::vms::ws_session::WsSessionTracker;
+use tokio_util::task::TaskTracker;
 
 #[derive(Clone, Debug, serde::Serialize)]
 pub struct DownloadProgress {
@@ -35,9 +40,73 @@ pub struct DownloadProgress {
     pub total_bytes: i64,
     pub completed: bool,
     pub error: Option<String>,
+    /// Pull attempt currently running, starting at 1. Byte counts carry over
+    /// between attempts because layers the daemon already finished are kept.
+    pub attempt: u32,
 }
 
-pub type DownloadProgressTracker = Arc<Mutex<HashMap<String, DownloadProgress>>>;
+impl DownloadProgress {
+    /// No further updates will follow this one.
+    pub fn is_finished(&self) -> bool {
+        self.completed || self.error.is_some()
+    }
+}
+
+/// In-flight Docker image downloads keyed by image name. Every change is
+/// also broadcast so websocket clients get pushed updates instead of polling.
+#[derive(Clone)]
+pub struct DownloadProgressTracker {
+    entries: Arc<Mutex<HashMap<String, DownloadProgress>>>,
+    updates: broadcast::Sender<DownloadProgress>,
+}
+
+impl Default for DownloadProgressTracker {
+    fn default() -> Self {
+        let (updates, _) = broadcast::channel(256);
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            updates,
+        }
+    }
+}
+
+impl DownloadProgressTracker {
+    pub async fn get(&self, image: &str) -> Option<DownloadProgress> {
+        self.entries.lock().await.get(image).cloned()
+    }
+
+    /// Reset the entry for `image` to a fresh "Initializing..." state.
+    pub async fn start(&self, image: &str) {
+        let progress = DownloadProgress {
+            image: image.to_string(),
+            status: "Initializing...".to_string(),
+            current_bytes: 0,
+            total_bytes: 0,
+            completed: false,
+            error: None,
+            attempt: 1,
+        };
+        self.entries
+            .lock()
+            .await
+            .insert(image.to_string(), progress.clone());
+        let _ = self.updates.send(progress);
+    }
+
+    /// Apply `f` to the entry for `image`, if one exists, and broadcast it.
+    pub async fn update(&self, image: &str, f: impl FnOnce(&mut DownloadProgress)) {
+        let mut entries = self.entries.lock().await;
+        if let Some(progress) = entries.get_mut(image) {
+            f(progress);
+            // No receivers is fine: nobody is watching this download
+            let _ = self.updates.send(progress.clone());
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DownloadProgress> {
+        self.updates.subscribe()
+    }
+}
 
 #[derive(Clone)]
 pub struct AppState {
@@ -61,6 +130,18 @@ pub struct AppState {
     pub sso_base_url: String,
     pub sso_frontend_url: String,
     pub sso_encryption_key: [u8; 32],
+    // Seals stored registry passwords; `None` turns stored credentials off
+    pub registry_encryption_key: Option<[u8; 32]>,
+    // Open shell/metrics websockets, for per-VM and per-user caps
+    pub ws_sessions: WsSessionTracker,
+    // Step-by-step logs of in-flight VM creates
+    pub create_progress: CreateProgressTracker,
+    // Cancellable creates and migrations, for /v1/jobs
+    pub jobs: JobRegistry,
+    // Recent failed logins, for /v1/auth/login lockouts
+    pub login_throttle: LoginThrottle,
+    // Detached provisioning and bookkeeping tasks, awaited at shutdown
+    pub background: TaskTracker,
 }
 
 #[tokio::main]
@@ -89,7 +170,17 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let db = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+    features::metrics::install_recorder()?;
+
+    let pool_config = core::db::PoolConfig::from_env()?;
+    info!(
+        max_connections = pool_config.max_connections,
+        min_connections = pool_config.min_connections,
+        acquire_timeout_secs = pool_config.acquire_timeout.as_secs(),
+        idle_timeout_secs = pool_config.idle_timeout.map(|t| t.as_secs()),
+        "database pool config"
+    );
+    let db = pool_config.connect(&std::env::var("DATABASE_URL")?).await?;
     sqlx::migrate!("./migrations").run(&db).await?;
 
     // Initialize default admin user if no users exist
@@ -122,7 +213,7 @@ async fn main() -> anyhow::Result<()> {
     let allow_direct_image_paths = std::env::var("MANAGER_ALLOW_IMAGE_PATHS")
         .map(|value| matches_ignore_case(value.trim()))
         .unwrap_or(false);
-    let download_progress = Arc::new(Mutex::new(HashMap::new()));
+    let download_progress = DownloadProgressTracker::default();
     let licensing = LicensingRepository::new(db.clone());
     let license_config = LicenseConfig::from_env();
     let license_state: SharedLicenseState =
@@ -138,11 +229,22 @@ async fn main() -> anyhow::Result<()> {
     });
     let sso_frontend_url =
         std::env::var("SSO_FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".into());
+    let cors = core::cors::CorsConfig::from_env(&sso_frontend_url)?;
+    if cors.origins == core::cors::Allow::Any {
+        warn!("CORS allows any origin (MANAGER_DEV_MODE or MANAGER_CORS_ORIGINS=*)");
+    }
     let sso_encryption_key =
         sso_crypto::derive_key(&std::env::var("SSO_ENCRYPTION_KEY").unwrap_or_else(|_| {
             warn!("SSO_ENCRYPTION_KEY not set — using insecure default (set this in production!)");
-            "insecure-default-key-change-me".to_string()
+            sso_crypto::INSECURE_DEFAULT_KEY.to_string()
         }));
+    let registry_encryption_key = match std::env::var("REGISTRY_ENCRYPTION_KEY") {
+        Ok(key) if !key.trim().is_empty() => Some(sso_crypto::derive_key(key.trim())),
+        _ => {
+            warn!("REGISTRY_ENCRYPTION_KEY not set — stored registry credentials are disabled");
+            None
+        }
+    };
 
     // Storage backend registry. TOML config path is optional; absence is treated
     // as "no extra backends configured beyond the migration-seeded localfile-default".
@@ -179,6 +281,12 @@ async fn main() -> anyhow::Result<()> {
         sso_base_url,
         sso_frontend_url,
         sso_encryption_key,
+        registry_encryption_key,
+        ws_sessions: WsSessionTracker::default(),
+        create_progress: CreateProgressTracker::default(),
+        jobs: JobRegistry::default(),
+        login_throttle: LoginThrottle::default(),
+        background: TaskTracker::new(),
     };
 
     // Auto-register base images found in the image root directory
@@ -194,14 +302,13 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Allow disabling the reconciler via env for test/debug to avoid races during VM creation
-    let reconciler_disabled = std::env::var("MANAGER_RECONCILER_DISABLED")
-        .map(|v| matches_ignore_case(v.trim()))
-        .unwrap_or(false);
-    if !reconciler_disabled {
-        let _reconciler_handle = features::reconciler::spawn(state.clone());
+    let shutdown = tokio_util::sync::CancellationToken::new();
+    let reconciler_handle = if !features::reconciler::disabled_by_env() {
+        Some(features::reconciler::spawn(state.clone(), shutdown.clone()))
     } else {
         warn!("reconciler disabled by MANAGER_RECONCILER_DISABLED");
-    }
+        None
+    };
 
     let metrics_disabled = std::env::var("MANAGER_METRICS_DISABLED")
         .map(|v| matches_ignore_case(v.trim()))
@@ -212,6 +319,9 @@ async fn main() -> anyhow::Result<()> {
         warn!("metrics collector disabled by MANAGER_METRICS_DISABLED");
     }
 
+    // Only acts on VMs with a balloon policy set
+    let _balloon_policy_handle = features::vms::balloon_policy::spawn(state.clone());
+
     // Initial license check (non-fatal)
     {
         let s = state.clone();
@@ -291,13 +401,8 @@ async fn main() -> anyhow::Result<()> {
 
     let app = features::router(state.clone())
         .merge(docs::router(openapi))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any)
-                .max_age(std::time::Duration::from_secs(3600)),
-        );
+        .layer(axum::middleware::from_fn(core::request_id::middleware))
+        .layer(cors.layer());
     let bind = std::env::var("MANAGER_BIND").unwrap_or_else(|_| "127.0.0.1:18080".into());
     info!(%bind, "manager listening");
     if let Ok(host_id) = std::env::var("MANAGER_HOST_ID") {
@@ -310,7 +415,51 @@ async fn main() -> anyhow::Result<()> {
             .await;
     }
     let listener = tokio::net::TcpListener::bind(&bind).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+    .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result.map_err(Into::into),
+        _ = core::shutdown::signal() => {}
+    }
+    let drain_timeout = core::shutdown::timeout_from_env();
+    features::health::begin_shutdown();
+    let cancelled_jobs = state.jobs.cancel_running();
+    info!(
+        cancelled_jobs,
+        timeout_secs = drain_timeout.as_secs(),
+        "shutting down: draining in-flight requests"
+    );
+    // Stops the listener and the reconciler's next pass
+    shutdown.cancel();
+    let deadline = tokio::time::Instant::now() + drain_timeout;
+    match tokio::time::timeout_at(deadline, &mut server).await {
+        Ok(result) => result?,
+        Err(_) => warn!("in-flight requests still running at the shutdown timeout"),
+    }
+    if let Some(handle) = reconciler_handle {
+        if tokio::time::timeout_at(deadline, handle).await.is_err() {
+            warn!("reconciler pass still running at the shutdown timeout");
+        }
+    }
+    // Provisioning spawned by requests that already returned
+    state.background.close();
+    if tokio::time::timeout_at(deadline, state.background.wait())
+        .await
+        .is_err()
+    {
+        warn!(
+            tasks = state.background.len(),
+            "background tasks still running at the shutdown timeout"
+        );
+    }
+    state.db.close().await;
+    info!("manager stopped");
     Ok(())
 }
 
@@ -321,6 +470,48 @@ fn matches_ignore_case(value: &str) -> bool {
     )
 }
 
+#[cfg(test)]
+impl AppState {
+    /// A state on `pool` with every other field at its default: images under
+    /// `/srv/images`, direct image paths allowed, a fresh local storage root
+    /// and the storage backends registered in `pool`. Override fields with
+    /// struct update syntax.
+    pub async fn for_tests(pool: PgPool) -> Self {
+        let storage = LocalStorage::new();
+        storage.init().await.expect("storage root");
+        let registry = crate::features::storage::registry::Registry::load(&pool, None)
+            .await
+            .expect("registry");
+        Self {
+            db: pool.clone(),
+            hosts: HostRepository::new(pool.clone()),
+            images: ImageRepository::new(pool.clone(), "/srv/images"),
+            snapshots: SnapshotRepository::new(pool.clone()),
+            users: UserRepository::new(pool.clone()),
+            shell_repo: ShellRepository::new(pool.clone()),
+            allow_direct_image_paths: true,
+            storage,
+            registry,
+            licensing: LicensingRepository::new(pool.clone()),
+            download_progress: DownloadProgressTracker::default(),
+            license_state: Arc::new(RwLock::new(nexus_types::LicenseState::default())),
+            license_config: LicenseConfig::from_env(),
+            sso_providers: SsoProviderRepository::new(pool.clone()),
+            user_identities: UserIdentityRepository::new(pool.clone()),
+            auth_states: AuthStateRepository::new(pool.clone()),
+            sso_base_url: "http://localhost:18080".to_string(),
+            sso_frontend_url: "http://localhost:3000".to_string(),
+            sso_encryption_key: sso_crypto::derive_key("test-key"),
+            registry_encryption_key: Some(sso_crypto::derive_key("registry-test-key")),
+            ws_sessions: WsSessionTracker::default(),
+            create_progress: CreateProgressTracker::default(),
+            jobs: JobRegistry::default(),
+            login_throttle: LoginThrottle::default(),
+            background: TaskTracker::new(),
+        }
+    }
+}
+
 impl AppState {
     /// Returns the `host_id` to record on a LocalFile-backed volume.
     /// `vm_host_id` is the host the VM is being created on; LocalFile volumes
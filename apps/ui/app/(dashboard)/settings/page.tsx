@@ -67,9 +67,9 @@ import type { AuditLogQueryParams } from "@/lib/types"
 const ACTION_GROUPS: Record<string, string[]> = {
   "All": [],
   "Auth": ["login", "logout", "login_failed"],
-  "VM": ["create_vm", "start_vm", "stop_vm", "pause_vm", "resume_vm", "delete_vm", "update_vm"],
-  "Container": ["create_container", "start_container", "stop_container", "delete_container"],
-  "Function": ["create_function", "invoke_function", "update_function", "delete_function"],
+  "VM": ["create_vm", "start_vm", "stop_vm", "pause_vm", "resume_vm", "delete_vm", "update_vm", "migrate_vm", "reschedule_vm", "backup_vm", "delete_vm_snapshot"],
+  "Container": ["create_container", "start_container", "stop_container", "delete_container", "update_container", "restart_container", "pause_container", "resume_container", "exec_container"],
+  "Function": ["create_function", "invoke_function", "update_function", "delete_function", "publish_function_version", "update_function_alias", "delete_function_alias"],
   "System": ["system_event"],
 }
 
@@ -19,6 +19,11 @@ export function ContainerOverview({ container, vmId }: ContainerOverviewProps) {
           </CardHeader>
           <CardContent>
             <code className="text-sm font-medium">{container.image}</code>
+            {container.image_digest && !container.image.includes("@") && (
+              <p className="mt-1 truncate text-xs text-muted-foreground" title={container.image_digest}>
+                <code>{container.image_digest}</code>
+              </p>
+            )}
           </CardContent>
         </Card>
 
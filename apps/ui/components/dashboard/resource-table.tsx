@@ -32,6 +32,7 @@ import { useDateFormat } from "@/lib/hooks/use-date-format"
 import { useTheme } from "next-themes"
 import dynamic from "next/dynamic"
 import { toast } from "sonner"
+import { isRunningState } from "@/lib/utils"
 
 const Editor = dynamic(() => import("@monaco-editor/react"), { ssr: false })
 
@@ -439,7 +440,7 @@ export function ResourceTable({ resources }: ResourceTableProps) {
                                 <Play className="h-4 w-4 " />
                               </Button>
                             )}
-                            {resource.state === "running" && (
+                            {isRunningState(resource.state) && (
                               <>
                                 <Button variant="ghost" size="icon" onClick={() => handleAction(resource.id, "pause", resource.name)}>
                                   <Pause className="h-4 w-4" />
@@ -460,7 +461,7 @@ export function ResourceTable({ resources }: ResourceTableProps) {
                         {/* Delete action for all types */}
                         {canDeleteResource(user, (resource as any).created_by_user_id) && (
                           <>
-                            {resource.type === "vm" && resource.state === "running" ? (
+                            {resource.type === "vm" && isRunningState(resource.state) ? (
                               <TooltipProvider>
                                 <Tooltip>
                                   <TooltipTrigger asChild>
@@ -23,7 +23,8 @@ import { Label } from "@/components/ui/label"
 import { Input } from "@/components/ui/input"
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select"
 import { ConfirmDialog } from "@/components/shared/confirm-dialog"
-import type { VmNic, PortForward } from "@/lib/types"
+import type { VmNic, PortForward, CreateNicReq } from "@/lib/types"
+import { isRunningState } from "@/lib/utils"
 
 interface VMNetworkProps {
   vmId: string
@@ -71,7 +72,7 @@ export function VMNetwork({ vmId }: VMNetworkProps) {
 
   // QEMU hot-plugs NICs live; Firecracker needs a restart.
   const isQemu = vm?.vmm_kind === "qemu"
-  const mutationsBlocked = vm?.state === "running" && !isQemu
+  const mutationsBlocked = isRunningState(vm?.state) && !isQemu
 
   const [showAddDialog, setShowAddDialog] = useState(false)
   const [showDeleteDialog, setShowDeleteDialog] = useState(false)
@@ -114,7 +115,7 @@ export function VMNetwork({ vmId }: VMNetworkProps) {
   }
 
   const handleSubmitAdd = () => {
-    const payload: any = {
+    const payload: CreateNicReq = {
       network_id: formData.network_id,
       // iface_id is not provided - backend will auto-assign next sequential interface
     }
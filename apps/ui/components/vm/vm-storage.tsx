@@ -24,6 +24,7 @@ import { Input } from "@/components/ui/input"
 import { Checkbox } from "@/components/ui/checkbox"
 import { ConfirmDialog } from "@/components/shared/confirm-dialog"
 import type { VmDrive } from "@/lib/types"
+import { isRunningState } from "@/lib/utils"
 
 interface VMStorageProps {
   vmId: string
@@ -73,7 +74,7 @@ export function VMStorage({ vmId }: VMStorageProps) {
   // QEMU hot-plugs disks live (no restart). Firecracker needs a restart, so its
   // add/remove stays disabled while running.
   const isQemu = vm?.vmm_kind === "qemu"
-  const mutationsBlocked = vm?.state === "running" && !isQemu
+  const mutationsBlocked = isRunningState(vm?.state) && !isQemu
 
   const [showAddDialog, setShowAddDialog] = useState(false)
   const [showDeleteDialog, setShowDeleteDialog] = useState(false)
@@ -32,6 +32,7 @@ import {
 import { Checkbox } from "@/components/ui/checkbox"
 import { Label } from "@/components/ui/label"
 import { toast } from "sonner"
+import { isRunningState } from "@/lib/utils"
 
 interface VMTableProps {
   vms: Vm[]
@@ -269,7 +270,7 @@ export function VMTable({ vms }: VMTableProps) {
                               <Play className="h-4 w-4" />
                             </Button>
                           )}
-                          {vm.state === "running" && (
+                          {isRunningState(vm.state) && (
                             <>
                               <Button
                                 variant="ghost"
@@ -303,7 +304,7 @@ export function VMTable({ vms }: VMTableProps) {
                       )}
                       {canDeleteResource(user, vm.created_by_user_id) && (
                         <>
-                          {vm.state === "running" ? (
+                          {isRunningState(vm.state) ? (
                             <TooltipProvider>
                               <Tooltip>
                                 <TooltipTrigger asChild>
@@ -25,6 +25,8 @@ import type {
   CreateSnapshotRequest,
   CreateSnapshotResponse,
   ListSnapshotsResponse,
+  ListSnapshotsParams,
+  DeleteSnapshotResponse,
   Snapshot,
   InstantiateSnapshotReq,
   InstantiateSnapshotResp,
@@ -63,6 +65,8 @@ import type {
   CreateContainerResp,
   ListContainersResp,
   GetContainerResp,
+  InspectContainerResp,
+  ContainerChangesResp,
   ContainerStatsResp,
   ContainerLogsResp,
   ContainerExecReq,
@@ -77,6 +81,7 @@ import type {
   Host,
   ListHostsResponse,
   GetHostResponse,
+  HostSnapshotAllResp,
   Network,
   CreateNetworkRequest,
   UpdateNetworkRequest,
@@ -109,6 +114,16 @@ import type {
   PciDevice,
 } from "@/lib/types"
 
+function snapshotQuery(params: ListSnapshotsParams): string {
+  const query = new URLSearchParams();
+  if (params.vm_id) query.append("vm_id", params.vm_id);
+  if (params.snapshot_type) query.append("snapshot_type", params.snapshot_type);
+  if (params.name) query.append("name", params.name);
+  if (params.sort) query.append("sort", params.sort);
+  if (params.order) query.append("order", params.order);
+  return query.toString();
+}
+
 /**
  * Composite façade endpoints for VM orchestration
  */
@@ -257,9 +272,24 @@ export class FacadeApi {
   /**
    * Get VM snapshots
    */
-  async getVMSnapshots(vmId: string): Promise<Snapshot[]> {
+  async getVMSnapshots(
+    vmId: string,
+    params: ListSnapshotsParams = {}
+  ): Promise<Snapshot[]> {
+    const qs = snapshotQuery(params);
     const res = await apiClient.get<ListSnapshotsResponse>(
-      `/vms/${vmId}/snapshots`
+      qs ? `/vms/${vmId}/snapshots?${qs}` : `/vms/${vmId}/snapshots`
+    );
+    return res.items;
+  }
+
+  /**
+   * List snapshots across VMs, filtered and sorted server-side
+   */
+  async listSnapshots(params: ListSnapshotsParams = {}): Promise<Snapshot[]> {
+    const qs = snapshotQuery(params);
+    const res = await apiClient.get<ListSnapshotsResponse>(
+      qs ? `/snapshots?${qs}` : "/snapshots"
     );
     return res.items;
   }
@@ -269,16 +299,23 @@ export class FacadeApi {
    */
   async restoreSnapshot(
     snapshotId: string,
-    params: InstantiateSnapshotReq = {}
+    params: InstantiateSnapshotReq = {},
+    force = false
   ): Promise<InstantiateSnapshotResp> {
     return apiClient.post<InstantiateSnapshotResp>(
-      `/snapshots/${snapshotId}/instantiate`,
+      `/snapshots/${snapshotId}/instantiate${force ? "?force=true" : ""}`,
       params
     );
   }
 
-  async deleteVMSnapshot(vmId: string, snapshotId: string): Promise<void> {
-    await apiClient.delete<OkResponse>(`/snapshots/${snapshotId}`);
+  async deleteVMSnapshot(
+    vmId: string,
+    snapshotId: string,
+    force = false
+  ): Promise<DeleteSnapshotResponse> {
+    return apiClient.delete<DeleteSnapshotResponse>(
+      `/snapshots/${snapshotId}${force ? "?force=true" : ""}`
+    );
   }
 
   /**
@@ -386,6 +423,8 @@ export class FacadeApi {
     imageKind?: "linux_kernel" | "linux_disk" | "uefi_disk" | "installer_iso",
     /** For uefi_disk: OVMF_VARS template path the agent copies per-VM. */
     nvramTemplatePath?: string,
+    /** Hex digest the file must have; a mismatch is refused with 400. */
+    sha256?: string,
   ): Promise<CreateImageResp> {
     const formData = new FormData();
     formData.append("file", file);
@@ -394,6 +433,7 @@ export class FacadeApi {
     if (project) formData.append("project", project);
     if (imageKind) formData.append("image_kind", imageKind);
     if (nvramTemplatePath) formData.append("nvram_template_path", nvramTemplatePath);
+    if (sha256) formData.append("sha256", sha256);
 
     const response = await fetch(`${apiClient.baseURL}/images/upload`, {
       method: "POST",
@@ -716,6 +756,14 @@ export class FacadeApi {
     return apiClient.get<ContainerLogsResp>(url);
   }
 
+  async inspectContainer(id: string): Promise<InspectContainerResp> {
+    return apiClient.get<InspectContainerResp>(`/containers/${id}/inspect`);
+  }
+
+  async getContainerChanges(id: string): Promise<ContainerChangesResp> {
+    return apiClient.get<ContainerChangesResp>(`/containers/${id}/changes`);
+  }
+
   async getContainerStats(id: string): Promise<ContainerStatsResp> {
     return apiClient.get<ContainerStatsResp>(`/containers/${id}/stats`);
   }
@@ -743,8 +791,13 @@ export class FacadeApi {
     return res.item;
   }
 
-  async deleteHost(id: string): Promise<void> {
-    await apiClient.delete<OkResponse>(`/hosts/${id}`);
+  async deleteHost(id: string, force = false): Promise<void> {
+    await apiClient.delete<OkResponse>(`/hosts/${id}${force ? "?force=true" : ""}`);
+  }
+
+  /** Snapshots every running VM on the host, e.g. before maintenance. */
+  async snapshotAllOnHost(id: string): Promise<HostSnapshotAllResp> {
+    return apiClient.post<HostSnapshotAllResp>(`/hosts/${id}/snapshot-all`);
   }
 
   // ==============
@@ -902,6 +955,10 @@ export class FacadeApi {
     await apiClient.delete<OkResponse>(`/users/${id}`);
   }
 
+  async unlockUser(id: string): Promise<import("@/lib/types").User> {
+    return apiClient.post<import("@/lib/types").User>(`/users/${id}/unlock`);
+  }
+
   // User Preferences
   async getPreferences(): Promise<import("@/lib/types").UserPreferences> {
     const res = await apiClient.get<import("@/lib/types").GetPreferencesResponse>("/auth/me/preferences");
@@ -1,6 +1,6 @@
 import { useQuery, useMutation, useQueryClient } from "@tanstack/react-query"
 import { facadeApi } from "./api"
-import type { CreateVmReq, CreateFunction, UpdateFunction, InvokeFunction, TestFunction, Image, UpdateTemplateReq, AuditLogQueryParams, MetricsQueryParams, CreatePortForwardReq, StorageBackend, ImportP2vRequest } from "@/lib/types"
+import type { CreateVmReq, CreateFunction, UpdateFunction, InvokeFunction, TestFunction, Image, UpdateTemplateReq, AuditLogQueryParams, MetricsQueryParams, CreatePortForwardReq, StorageBackend, ImportP2vRequest, CreateNicReq, ListSnapshotsParams } from "@/lib/types"
 import { useNotificationStore } from "@/lib/stores/notification-store"
 import { toast } from "sonner"
 
@@ -81,6 +81,7 @@ export const queryKeys = {
   container: (id: string) => ["containers", id] as const,
   containerLogs: (id: string) => ["containers", id, "logs"] as const,
   containerStats: (id: string) => ["containers", id, "stats"] as const,
+  containerChanges: (id: string) => ["containers", id, "changes"] as const,
 
   // hosts
   hosts: ["hosts"] as const,
@@ -485,10 +486,10 @@ export function useInitializeVM() {
 }
 
 // Snapshots
-export function useSnapshots(vmId: string) {
+export function useSnapshots(vmId: string, params: ListSnapshotsParams = {}) {
   return useQuery({
-    queryKey: queryKeys.snapshots(vmId),
-    queryFn: () => facadeApi.getVMSnapshots(vmId),
+    queryKey: [...queryKeys.snapshots(vmId), params],
+    queryFn: () => facadeApi.getVMSnapshots(vmId, params),
     enabled: !!vmId,
     staleTime: 30 * 1000, // 30 seconds
   });
@@ -544,8 +545,15 @@ export function useDeleteSnapshot() {
   const queryClient = useQueryClient();
 
   return useMutation({
-    mutationFn: ({ vmId, snapshotId }: { vmId: string; snapshotId: string }) =>
-      facadeApi.deleteVMSnapshot(vmId, snapshotId),
+    mutationFn: ({
+      vmId,
+      snapshotId,
+      force = false,
+    }: {
+      vmId: string;
+      snapshotId: string;
+      force?: boolean;
+    }) => facadeApi.deleteVMSnapshot(vmId, snapshotId, force),
     onSuccess: (_, { vmId }) => {
       queryClient.invalidateQueries({ queryKey: queryKeys.snapshots(vmId) });
     }
@@ -756,7 +764,7 @@ export function useCreateVMNic() {
   const queryClient = useQueryClient();
 
   return useMutation({
-    mutationFn: ({ vmId, nic }: { vmId: string; nic: any }) =>
+    mutationFn: ({ vmId, nic }: { vmId: string; nic: CreateNicReq }) =>
       facadeApi.createVMNic(vmId, nic),
     onSuccess: (_, { vmId }) => {
       queryClient.invalidateQueries({ queryKey: queryKeys.vmNics(vmId) });
@@ -1007,6 +1015,14 @@ export function useContainerStats(id: string) {
   });
 }
 
+export function useContainerChanges(id: string, enabled = true) {
+  return useQuery({
+    queryKey: queryKeys.containerChanges(id),
+    queryFn: () => facadeApi.getContainerChanges(id),
+    enabled: !!id && enabled,
+  });
+}
+
 // ==============
 // Host Management Queries
 // ==============
@@ -1047,6 +1063,18 @@ export function useDeleteHost() {
   });
 }
 
+export function useSnapshotAllOnHost() {
+  const queryClient = useQueryClient();
+  return useMutation({
+    mutationFn: (id: string) => facadeApi.snapshotAllOnHost(id),
+    onSuccess: (resp) => {
+      resp.results.forEach(({ vm_id }) =>
+        queryClient.invalidateQueries({ queryKey: queryKeys.snapshots(vm_id) })
+      );
+    },
+  });
+}
+
 // ==============
 // Network Management Queries
 // ==============
@@ -1284,6 +1312,17 @@ export function useDeleteUser() {
   });
 }
 
+export function useUnlockUser() {
+  const queryClient = useQueryClient();
+
+  return useMutation({
+    mutationFn: (id: string) => facadeApi.unlockUser(id),
+    onSuccess: () => {
+      queryClient.invalidateQueries({ queryKey: queryKeys.users });
+    }
+  });
+}
+
 // ======================
 // User Preferences & Profile Queries
 // ======================
@@ -6,6 +6,7 @@ export interface OkResponse {
 
 export interface CreateVmResponse {
   id: string;
+  job_id?: string;
 }
 
 export interface ImageResponse {
@@ -50,6 +51,12 @@ export interface Vm {
   vnc_listen?: string;
   created_at: string;
   updated_at: string;
+  /** When `state` last changed. */
+  state_changed_at: string;
+  /** When the VMM was last booted (create, start, restart, reschedule). */
+  started_at: string | null;
+  /** Seconds since the VMM was booted; null when not running. */
+  uptime_seconds: number | null;
   // Runtime metrics (populated separately, not from REST list)
   cpu_usage_percent?: number;
   memory_usage_percent?: number;
@@ -105,6 +112,8 @@ export interface CreateVmReq {
   password?: string;
   rootfs_size_mb?: number;
   network_id?: string;
+  /** A named host bridge (agent `FC_BRIDGES`) for eth0 when no network_id is given. Firecracker only. */
+  bridge?: string;
   port_forwards?: CreatePortForwardReq[];
   backend_id?: string;
   /** Tags for the VM (informational filters, RBAC scoping, etc). */
@@ -132,8 +141,19 @@ export interface CreateVmReq {
   vfio_devices?: string[];
   /** QEMU CPU model, e.g. "host", "kvm64", "x86-64-v3". */
   cpu_type?: string;
+  /** Rootfs drive rate limiter (Firecracker); falls back to the manager default. */
+  rootfs_rate_limiter?: any;
+  entropy_rate_limiter?: any;
+  /** Firecracker only — "shared" boots read-only from the image with a writable overlay drive. */
+  rootfs_mode?: RootfsMode;
+  /** Overlay drive size for a shared rootfs (default 1024). */
+  overlay_size_mb?: number;
+  cpu_quota?: number;
+  io_weight?: number;
 }
 
+export type RootfsMode = "copy" | "shared";
+
 /** A host PCI device available for VFIO passthrough. */
 export interface PciDevice {
   bdf: string;
@@ -152,6 +172,10 @@ export interface TemplateSpec {
   rootfs_image_id?: string;
   kernel_path?: string;
   rootfs_path?: string;
+  boot_args?: string;
+  init?: string;
+  rootfs_rate_limiter?: any;
+  entropy_rate_limiter?: any;
 }
 
 export interface CreateTemplateReq {
@@ -194,6 +218,9 @@ export interface GetTemplateResp {
 
 export interface InstantiateTemplateReq {
   name: string;
+  vcpu?: number;
+  mem_mib?: number;
+  tags?: string[];
 }
 
 export interface InstantiateTemplateResp {
@@ -214,28 +241,68 @@ export interface Snapshot {
   size_bytes: number;
   state: string;
   name?: string;
+  firecracker_version?: string;
+  vcpu?: number;
+  mem_mib?: number;
+  cpu_template?: string;
+  /** Memory file stored zstd-compressed; size_bytes is the compressed size. */
+  compressed?: boolean;
   created_at: string;
   updated_at: string;
 }
 
 export interface CreateSnapshotRequest {
-  // Empty for now, backend doesn't require any fields
+  name?: string;
+  snapshot_type?: string;
+  parent_id?: string;
+  track_dirty_pages?: boolean;
+  /** zstd-compress the memory file (Full Firecracker snapshots only). */
+  compress?: boolean;
+  /** Pause a running VM for the snapshot and resume it after (default true). */
+  pause?: boolean;
 }
 
 export interface CreateSnapshotResponse {
   id: string;
 }
 
+export interface HostSnapshotResult {
+  vm_id: string;
+  ok: boolean;
+  snapshot_id?: string;
+  error?: string;
+}
+
+export interface HostSnapshotAllResp {
+  snapshot_ids: string[];
+  results: HostSnapshotResult[];
+}
+
 export interface ListSnapshotsResponse {
   items: Snapshot[];
 }
 
+export interface DeleteSnapshotResponse {
+  freed_bytes: number;
+}
+
+export interface ListSnapshotsParams {
+  vm_id?: string;
+  snapshot_type?: "Full" | "Diff";
+  name?: string;
+  sort?: "created_at" | "size_bytes";
+  order?: "asc" | "desc";
+}
+
 export interface GetSnapshotResponse {
   item: Snapshot;
 }
 
 export interface InstantiateSnapshotReq {
   name?: string
+  vcpu?: number
+  mem_mib?: number
+  host_id?: string
   snapshot_path?: any,
   mem_file_path?: string
 }
@@ -322,6 +389,23 @@ export interface TailLogResponse {
   text: string;
 }
 
+export interface VmConsoleResponse {
+  text: string;
+  available: boolean;
+}
+
+export interface GuestProcess {
+  pid: number;
+  name: string;
+  rss_kb: number;
+  cpu_ticks: number;
+  state: string;
+}
+
+export interface ListGuestProcessesResponse {
+  items: GuestProcess[];
+}
+
 // Audit Logs
 export interface AuditLog {
   id: string;
@@ -440,8 +524,8 @@ export interface VmNic {
 }
 
 export interface CreateNicReq {
-  iface_id: string;
-  host_dev_name: string;
+  iface_id?: string;
+  network_id: string;
   guest_mac?: string;
   rx_rate_limiter?: {
     size?: number;
@@ -506,6 +590,14 @@ export interface BalloonStatsConfig {
   stats_polling_interval_s?: number;
 }
 
+export interface BalloonPolicy {
+  high_watermark_mib: number;
+  low_watermark_mib: number;
+  step_mib: number;
+  sustain_secs?: number;
+  max_balloon_mib?: number;
+}
+
 // Entropy device types
 export interface EntropyConfigReq {
   // Add fields as needed from OpenAPI spec
@@ -576,6 +668,8 @@ export interface Function {
   vcpu: number;
   memory_mb: number;
   env_vars?: Record<string, string>;
+  /** Secret env var names; every value is "***" */
+  secret_env_vars?: Record<string, string>;
   created_by_user_id?: string;
   created_at: string;
   updated_at: string;
@@ -598,6 +692,8 @@ export interface FunctionInvocation {
   response?: any
   logs: string[]
   error?: string
+  /** Published version that served it; absent for $LATEST */
+  version?: number
   invoked_at: string
 }
 
@@ -617,6 +713,8 @@ export interface InvokeFunction {
   event: {
     [key: string]: JSONValue;
   }
+  /** "$LATEST" (default), a version number or an alias name */
+  qualifier?: string
 }
 
 export interface CreateFunction {
@@ -625,7 +723,9 @@ export interface CreateFunction {
   "handler": string,
   "code": string,
   "vcpu": number,
-  "memory_mb": number
+  "memory_mb": number,
+  "env_vars"?: Record<string, string>,
+  "secret_env_vars"?: Record<string, string>
 }
 
 export interface UpdateFunction {
@@ -635,7 +735,36 @@ export interface UpdateFunction {
   "code": string,
   // "vcpu": number,
   "memory_mb": number,
-  "timeout_seconds": number
+  "timeout_seconds": number,
+  "env_vars"?: Record<string, string>,
+  "secret_env_vars"?: Record<string, string>,
+  "publish"?: boolean
+}
+
+export interface FunctionVersion {
+  function_id: string
+  version: number
+  code: string
+  handler: string
+  timeout_seconds: number
+  env_vars?: Record<string, string>
+  secret_env_vars?: Record<string, string>
+  description?: string
+  created_at: string
+}
+
+export interface FunctionAlias {
+  name: string
+  version: number
+  canary_version?: number
+  canary_percent?: number
+  updated_at: string
+}
+
+export interface PutFunctionAlias {
+  version: number
+  canary_version?: number
+  canary_percent?: number
 }
 
 // Container Types (matching backend API)
@@ -643,6 +772,7 @@ export interface Container {
   id: string;
   name: string;
   image: string;
+  image_digest?: string;
   command?: string;
   args: string[];
   env_vars: Record<string, string>;
@@ -651,6 +781,7 @@ export interface Container {
   cpu_limit?: number;
   memory_limit_mb?: number;
   restart_policy: string;
+  restart_count: number;
   state: "creating" | "booting" | "initializing" | "running" | "stopped" | "paused" | "error";
   container_runtime_id?: string;
   error_message?: string;
@@ -695,6 +826,7 @@ export interface CreateContainerReq {
   memory_limit_mb?: number;
   restart_policy?: string;
   registry_auth?: RegistryAuth;
+  registry?: string;
 }
 
 export interface CreateContainerResp {
@@ -709,6 +841,12 @@ export interface GetContainerResp {
   item: Container;
 }
 
+export interface InspectContainerResp {
+  item: Container;
+  runtime?: Record<string, unknown>;
+  runtime_error?: string;
+}
+
 export interface ContainerStats {
   cpu_percent?: number;
   memory_used_mb?: number;
@@ -750,6 +888,39 @@ export interface UpdateContainerReq {
   restart_policy?: string;
 }
 
+export interface ContainerImageDigestResp {
+  image: string;
+  digest?: string;
+  pinned: boolean;
+}
+
+export type ContainerChangeKind = "modified" | "added" | "deleted";
+
+export interface ContainerChange {
+  path: string;
+  kind: ContainerChangeKind;
+}
+
+export interface ContainerChangesResp {
+  items: ContainerChange[];
+  note?: string;
+}
+
+export interface ContainerBatchReq {
+  action: "start" | "stop" | "delete";
+  ids: string[];
+}
+
+export interface ContainerBatchResult {
+  id: string;
+  ok: boolean;
+  error?: string;
+}
+
+export interface ContainerBatchResp {
+  results: ContainerBatchResult[];
+}
+
 // Dashboard Stats
 export interface DashboardStats {
   total_vms: number;
@@ -794,6 +965,7 @@ export interface DockerImageTagsResp {
 export interface DownloadDockerImageReq {
   image: string; // e.g., "nginx:latest"
   registry_auth?: RegistryAuth;
+  registry?: string;
 }
 
 export interface DownloadDockerImageResp {
@@ -808,6 +980,7 @@ export interface DownloadProgress {
   total_bytes: number;
   completed: boolean;
   error?: string;
+  attempt: number;
 }
 
 // Host Management Types
@@ -818,6 +991,8 @@ export interface Host {
   status: "healthy" | "degraded" | "offline";
   capabilities_json?: {
     bridge?: string;
+    /** Named bridges the agent advertises (`FC_BRIDGES`), name to device. */
+    bridges?: Record<string, string>;
     run_dir?: string;
     cpus?: number;
     total_memory_mb?: number;
@@ -831,6 +1006,8 @@ export interface Host {
   vm_count: number;
   last_seen_at: string;
   last_metrics_at?: string;
+  unreachable_since?: string | null;
+  draining_since?: string | null;
 }
 
 export interface ListHostsResponse {
@@ -983,6 +1160,7 @@ export interface User {
   avatar_path?: string;
   timezone?: string;
   theme?: string;
+  locked_until?: string;
 }
 
 export interface CreateUserRequest {
@@ -1009,6 +1187,26 @@ export interface CreateUserResponse {
   id: string;
 }
 
+/** Unset limits are unlimited */
+export interface UserQuota {
+  max_vms?: number;
+  max_vcpu?: number;
+  max_mem_mib?: number;
+  max_containers?: number;
+}
+
+export interface UserQuotaUsage {
+  vms: number;
+  vcpu: number;
+  mem_mib: number;
+  containers: number;
+}
+
+export interface GetUserQuotaResponse {
+  quota: UserQuota;
+  usage: UserQuotaUsage;
+}
+
 // User Preferences Types
 export interface NotificationPreferences {
   email: boolean;
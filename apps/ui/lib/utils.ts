@@ -4,3 +4,9 @@ import { twMerge } from 'tailwind-merge'
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+/** Whether a VM's VMM is up: `running`, or `running-degraded` when its guest
+ * agent never answered the readiness probe. */
+export function isRunningState(state?: string) {
+  return state === 'running' || state === 'running-degraded'
+}
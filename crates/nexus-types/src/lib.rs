@@ -41,9 +41,23 @@ impl Default for OkResponse {
     }
 }
 
+/// Result of `POST /v1/vms/validate`: the request would be accepted by
+/// `POST /v1/vms`. Failures come back as a 400 instead.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct ValidateVmResponse {
+    pub ok: bool,
+    pub would_place_on_host: uuid::Uuid,
+    /// Things the create would do or skip that are worth knowing up front.
+    pub warnings: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
 pub struct CreateVmResponse {
     pub id: uuid::Uuid,
+    /// The create's entry in `/v1/jobs`. Absent when an `Idempotency-Key`
+    /// replay finds the original job already expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<uuid::Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -89,6 +103,14 @@ pub struct Vm {
     pub cpu_type: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    /// When `state` last changed.
+    pub state_changed_at: chrono::DateTime<chrono::Utc>,
+    /// When the VMM was last booted (create, start, restart, reschedule).
+    #[serde(default)]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Seconds since the VMM was booted while the VM runs; null otherwise.
+    #[serde(default)]
+    pub uptime_seconds: Option<i64>,
 }
 
 fn default_vmm_kind() -> String {
@@ -104,6 +126,18 @@ fn default_console_kind() -> String {
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ListVmsResponse {
     pub items: Vec<Vm>,
+    /// Number of VMs across all pages
+    #[serde(default)]
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct ListVmsParams {
+    /// Page size (default 100, max 1000, 0 returns all)
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub offset: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -144,6 +178,11 @@ pub struct CreateVmReq {
     pub rootfs_size_mb: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub network_id: Option<uuid::Uuid>,
+    /// One of the host's named bridges (the agent's `FC_BRIDGES`, by name or
+    /// device) for eth0 when no `network_id` is given. Defaults to the
+    /// host's default bridge. Firecracker only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<String>,
     /// Additional networks to attach (QEMU multi-NIC). Each entry gets a
     /// dedicated TAP + virtio-net-pci device in the guest. The first NIC
     /// remains `network_id`.
@@ -208,6 +247,69 @@ pub struct CreateVmReq {
     /// defaults to "host" (all host features; needed for nested virt).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cpu_type: Option<String>,
+    /// Extra kernel command-line parameters (Firecracker). Merged into the
+    /// base args; a token whose key matches a base token replaces it. The
+    /// `vm_id=` token is always set by the manager.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_args: Option<String>,
+    /// Enable simultaneous multithreading in the guest (Firecracker).
+    /// Defaults to off.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smt: Option<bool>,
+    /// Guest memory backing page size (Firecracker): "None" or "2M".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub huge_pages: Option<String>,
+    /// Rate limiter for the rootfs drive (Firecracker). Precedence: a
+    /// `rootfs` drive's own `rate_limiter` > this field (or the template's)
+    /// > `MANAGER_DEFAULT_ROOTFS_RATE_LIMITER` > unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rootfs_rate_limiter: Option<serde_json::Value>,
+    /// Rate limiter for the virtio-rng entropy device attached at boot
+    /// (Firecracker). Unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy_rate_limiter: Option<serde_json::Value>,
+    /// How the rootfs image is attached (Firecracker). Defaults to `copy`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rootfs_mode: Option<RootfsMode>,
+    /// Size of the writable overlay disk a `shared` rootfs gets. Default 1024.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay_size_mb: Option<u32>,
+    /// Host CPU limit for the VM's `fc-{id}.scope`, in percent of one CPU
+    /// (`150` = one and a half CPUs), at most 100 per vCPU. Firecracker only.
+    /// Unlimited when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<u32>,
+    /// cgroup IO weight of the VM's scope, 1-10000; systemd's default is 100.
+    /// Firecracker only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<u16>,
+    /// Caller-chosen VM id. Lets the caller open `/v1/vms/{id}/create/ws`
+    /// before the create request returns. Generated when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<uuid::Uuid>,
+}
+
+/// How a Firecracker VM gets its rootfs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RootfsMode {
+    /// A private copy of the image, allocated through the storage backend.
+    #[default]
+    Copy,
+    /// The image itself, attached read-only, plus a small writable `overlay`
+    /// drive for the guest's changes. No copy, so many VMs can boot from one
+    /// golden image; the guest has to mount the overlay (e.g. overlayroot),
+    /// and credentials and the guest agent are not injected into the image.
+    Shared,
+}
+
+impl RootfsMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RootfsMode::Copy => "copy",
+            RootfsMode::Shared => "shared",
+        }
+    }
 }
 
 /// A blank data disk requested at VM creation time.
@@ -231,6 +333,36 @@ pub struct TemplateSpec {
     pub rootfs_path: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub rootfs_size_mb: Option<u32>,
+    /// Extra kernel command-line parameters, merged into the base args the
+    /// same way as `CreateVmReq::boot_args` (e.g. `ro` for a read-only root).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_args: Option<String>,
+    /// Path of the guest init process. Overrides any `init=` in `boot_args`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init: Option<String>,
+    /// Rootfs drive rate limiter for VMs made from this template, overriding
+    /// the manager-wide default. See `CreateVmReq::rootfs_rate_limiter`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rootfs_rate_limiter: Option<serde_json::Value>,
+    /// Entropy device rate limiter for VMs made from this template.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy_rate_limiter: Option<serde_json::Value>,
+}
+
+/// Fold a template's `init` path into its boot args as an `init=` token.
+fn boot_args_with_init(boot_args: Option<String>, init: Option<String>) -> Option<String> {
+    let Some(init) = init else {
+        return boot_args;
+    };
+    let init_token = format!("init={init}");
+    let mut tokens: Vec<&str> = boot_args
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|token| !token.starts_with("init="))
+        .collect();
+    tokens.push(&init_token);
+    Some(tokens.join(" "))
 }
 
 impl TemplateSpec {
@@ -249,6 +381,7 @@ impl TemplateSpec {
             tags: vec![],
             rootfs_size_mb: self.rootfs_size_mb,
             network_id: None,
+            bridge: None,
             extra_network_ids: vec![],
             port_forwards: vec![],
             backend_id: None,
@@ -265,6 +398,16 @@ impl TemplateSpec {
             data_disks: vec![],
             vfio_devices: vec![],
             cpu_type: None,
+            boot_args: boot_args_with_init(self.boot_args, self.init),
+            smt: None,
+            huge_pages: None,
+            rootfs_rate_limiter: self.rootfs_rate_limiter,
+            entropy_rate_limiter: self.entropy_rate_limiter,
+            rootfs_mode: None,
+            overlay_size_mb: None,
+            cpu_quota: None,
+            io_weight: None,
+            id: None,
         }
     }
 }
@@ -341,6 +484,9 @@ pub struct CreateNicReq {
     /// Optional interface ID (e.g., "eth1"). If not provided, will auto-assign next sequential interface (eth1, eth2, eth3, etc.)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub iface_id: Option<String>,
+    /// Network to join. The host tap is named from the VM and interface index
+    /// and created on the VM's host on this network's bridge, so callers never
+    /// pass a `host_dev_name`.
     pub network_id: uuid::Uuid,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub guest_mac: Option<String>,
@@ -368,6 +514,80 @@ pub struct ListNicsResponse {
     pub items: Vec<VmNic>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct VmEvent {
+    pub id: i64,
+    /// Event level, e.g. "info" or "error"
+    pub kind: String,
+    pub message: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct ListVmEventsParams {
+    /// Page size (default 50, max 500)
+    #[serde(default)]
+    pub limit: Option<i64>,
+    /// Number of newest events to skip
+    #[serde(default)]
+    pub offset: Option<i64>,
+    /// Only return events at or after this time
+    #[serde(default)]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct VmConsoleParams {
+    /// Lines from the end of the console log (default 200, max 10000)
+    #[serde(default)]
+    pub tail: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct GuestProcessesParams {
+    /// How many processes to return (default 20, max 200)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// `memory` (default) or `cpu`
+    #[serde(default)]
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GuestProcess {
+    pub pid: u32,
+    pub name: String,
+    /// Resident memory; 0 for kernel threads
+    pub rss_kb: u64,
+    /// User + system CPU time since the process started, in clock ticks
+    pub cpu_ticks: u64,
+    /// One-letter process state (R, S, D, Z, ...)
+    pub state: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListGuestProcessesResponse {
+    /// Biggest first by the requested sort
+    pub items: Vec<GuestProcess>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VmConsoleResponse {
+    /// The last `tail` lines of the VM's serial console output
+    pub text: String,
+    /// False until the VM has written to its console log
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListVmEventsResponse {
+    /// Newest first
+    pub items: Vec<VmEvent>,
+    /// Offset of the next page, if there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MachineConfigPatchReq {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -378,6 +598,8 @@ pub struct MachineConfigPatchReq {
     pub smt: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub track_dirty_pages: Option<bool>,
+    /// One of Firecracker's static templates: C3, T2, T2S, T2CL, T2A, V1N1,
+    /// or None.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cpu_template: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -415,6 +637,17 @@ pub struct MmdsConfigReq {
     pub imds_compat: Option<bool>,
 }
 
+/// MMDS config and data stored for a VM; reapplied whenever it is rebooted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct VmMmds {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<MmdsConfigReq>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct VsockConfigReq {
     pub guest_cid: u32,
@@ -540,9 +773,47 @@ pub struct GetTemplateResp {
     pub item: Template,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct InstantiateTemplateReq {
     pub name: String,
+    /// Overrides the template's `vcpu` for this VM only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu: Option<u8>,
+    /// Overrides the template's `mem_mib` for this VM only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_mib: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl InstantiateTemplateReq {
+    /// Spec fields this request overrides, in declaration order.
+    pub fn overridden_fields(&self) -> Vec<&'static str> {
+        [
+            ("vcpu", self.vcpu.is_some()),
+            ("mem_mib", self.mem_mib.is_some()),
+            ("tags", self.tags.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(field, set)| set.then_some(field))
+        .collect()
+    }
+
+    /// The VM request for `spec` with this request's overrides merged over
+    /// it. The template itself is not touched.
+    pub fn into_vm_req(self, spec: TemplateSpec) -> CreateVmReq {
+        let mut req = spec.into_vm_req(self.name);
+        if let Some(vcpu) = self.vcpu {
+            req.vcpu = vcpu;
+        }
+        if let Some(mem_mib) = self.mem_mib {
+            req.mem_mib = mem_mib;
+        }
+        if let Some(tags) = self.tags {
+            req.tags = tags;
+        }
+        req
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -573,6 +844,20 @@ pub struct Snapshot {
     pub parent_id: Option<uuid::Uuid>,
     #[serde(default)]
     pub track_dirty_pages: bool,
+    /// Firecracker release the snapshot was taken with. Restores onto a
+    /// host running another release are refused unless forced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firecracker_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_mib: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_template: Option<String>,
+    /// The memory file is stored zstd-compressed; `size_bytes` is the
+    /// compressed size.
+    #[serde(default)]
+    pub compressed: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -587,6 +872,16 @@ pub struct CreateSnapshotRequest {
     pub parent_id: Option<uuid::Uuid>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub track_dirty_pages: Option<bool>,
+    /// zstd-compress the memory file (Full Firecracker snapshots only). Saves
+    /// storage at the cost of CPU on create and restore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+    /// Pause a running VM for the snapshot and resume it afterwards
+    /// (default true). With `false` the VM is left as it is; Firecracker can
+    /// only snapshot a paused VM, so a running one is refused. QEMU's agent
+    /// always stops the guest while it saves state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -596,11 +891,55 @@ pub struct CreateSnapshotResponse {
     pub name: Option<String>,
 }
 
+/// One VM's outcome within `POST /v1/hosts/{id}/snapshot-all`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostSnapshotResult {
+    pub vm_id: uuid::Uuid,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<uuid::Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct HostSnapshotAllResp {
+    /// The snapshots that were created, in `results` order.
+    pub snapshot_ids: Vec<uuid::Uuid>,
+    /// One per VM that was running on the host, newest VM first.
+    pub results: Vec<HostSnapshotResult>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct ListSnapshotsParams {
+    /// Only this VM's snapshots; the path's VM wins on `/v1/vms/{id}/snapshots`
+    #[serde(default)]
+    pub vm_id: Option<uuid::Uuid>,
+    /// `Full` or `Diff`
+    #[serde(default)]
+    pub snapshot_type: Option<String>,
+    /// Case-insensitive substring of the snapshot name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `created_at` (default) or `size_bytes`
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// `desc` (default) or `asc`
+    #[serde(default)]
+    pub order: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ListSnapshotsResponse {
     pub items: Vec<Snapshot>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct DeleteSnapshotResponse {
+    /// On-disk bytes the snapshot's files held; 0 when they were already gone.
+    pub freed_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct GetSnapshotResponse {
     pub item: Snapshot,
@@ -610,6 +949,19 @@ pub struct GetSnapshotResponse {
 pub struct InstantiateSnapshotReq {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// vCPU count of the restored VM. Firecracker restores with the vCPUs
+    /// the snapshot was taken with, so any other value is rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu: Option<u8>,
+    /// Guest memory to expose after restore. Memory held back by the
+    /// snapshotted VM's balloon is released (the balloon deflates) until the
+    /// guest sees this much; it cannot exceed the source VM's `mem_mib`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_mib: Option<u32>,
+    /// Host to restore on instead of the source VM's. It must be healthy and
+    /// able to read the snapshot's files (shared storage); Firecracker only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_id: Option<uuid::Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -673,11 +1025,44 @@ pub struct ImageFilter {
     pub project: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Page size (default 100, max 1000, 0 returns all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+pub struct ImageQuotaParams {
+    pub project: String,
+}
+
+/// Current usage of a project against its image quota. A `None` limit is
+/// unlimited.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImageQuota {
+    pub project: String,
+    pub max_bytes: Option<i64>,
+    pub max_count: Option<i64>,
+    pub used_bytes: i64,
+    pub used_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SetImageQuotaReq {
+    pub project: String,
+    #[serde(default)]
+    pub max_bytes: Option<i64>,
+    #[serde(default)]
+    pub max_count: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ListImagesResp {
     pub items: Vec<Image>,
+    /// Number of images matching the filter across all pages
+    #[serde(default)]
+    pub total: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -726,6 +1111,10 @@ pub struct DownloadDockerImageReq {
     pub image: String, // e.g., "nginx:latest" or "library/nginx:1.25"
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub registry_auth: Option<RegistryAuth>,
+    /// Name of one of the caller's stored registry credentials; takes precedence
+    /// over `registry_auth`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -734,6 +1123,49 @@ pub struct DownloadDockerImageResp {
     pub path: String,
 }
 
+/// One image to seed from a preload manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreloadManifestEntry {
+    /// "docker", "kernel" or "rootfs".
+    pub kind: String,
+    pub name: String,
+    /// Image reference (`nginx:latest`) for docker entries, otherwise an
+    /// http(s) URL to download. Download progress is tracked under this key.
+    pub source: String,
+    /// Expected SHA-256 of the stored file. Entries matching an existing
+    /// image are skipped; downloaded files must match it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreloadManifestReq {
+    pub images: Vec<PreloadManifestEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PreloadEntryStatus {
+    Imported,
+    Exists,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreloadEntryResult {
+    pub name: String,
+    pub status: PreloadEntryStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<uuid::Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PreloadManifestResp {
+    pub items: Vec<PreloadEntryResult>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RegisterHostRequest {
     pub name: String,
@@ -761,6 +1193,184 @@ pub struct HostHeartbeatRequest {
     pub supported_backend_kinds: Option<Vec<String>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub vmm_kinds_installed: Option<Vec<String>>,
+    /// Sent on the agent's last heartbeat before it shuts down. The manager
+    /// stops placing new VMs on the host until it heartbeats normally again.
+    #[serde(default)]
+    pub draining: bool,
+}
+
+/// A stage of `POST /v1/vms`, in the order the manager runs them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VmCreateStep {
+    Resolve,
+    Credentials,
+    GuestAgent,
+    Tap,
+    Storage,
+    Spawn,
+    Configure,
+    Start,
+    Register,
+    Readiness,
+    /// Terminal: the create finished, successfully or not.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VmCreateStepStatus {
+    Started,
+    Ok,
+    Warning,
+    Failed,
+}
+
+/// One line of a VM's creation log, streamed over `/v1/vms/{id}/create/ws`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct VmCreateProgressEvent {
+    pub vm_id: uuid::Uuid,
+    /// Position in this create's log, starting at 1.
+    pub seq: u32,
+    pub step: VmCreateStep,
+    pub status: VmCreateStepStatus,
+    pub message: String,
+    pub at: chrono::DateTime<chrono::Utc>,
+}
+
+impl VmCreateProgressEvent {
+    /// No further events will follow this one.
+    pub fn is_finished(&self) -> bool {
+        self.step == VmCreateStep::Done
+    }
+}
+
+/// What the agent did with one host artifact while tearing a VM down.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TeardownOutcome {
+    Removed,
+    Absent,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct ArtifactTeardown {
+    pub outcome: TeardownOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ArtifactTeardown {
+    /// `Ok(true)` means the artifact was removed, `Ok(false)` that it was
+    /// already gone.
+    pub fn from_result(result: Result<bool, String>) -> Self {
+        match result {
+            Ok(true) => Self {
+                outcome: TeardownOutcome::Removed,
+                error: None,
+            },
+            Ok(false) => Self {
+                outcome: TeardownOutcome::Absent,
+                error: None,
+            },
+            Err(error) => Self {
+                outcome: TeardownOutcome::Failed,
+                error: Some(error),
+            },
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        self.outcome == TeardownOutcome::Failed
+    }
+}
+
+/// Agent response to `POST /agent/v1/vms/{id}/stop`, one entry per artifact.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct StopVmReport {
+    pub ok: bool,
+    pub scope: ArtifactTeardown,
+    pub tap: ArtifactTeardown,
+    pub socket: ArtifactTeardown,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<ArtifactTeardown>,
+}
+
+impl StopVmReport {
+    pub fn new(
+        scope: ArtifactTeardown,
+        tap: ArtifactTeardown,
+        socket: ArtifactTeardown,
+        storage: Option<ArtifactTeardown>,
+    ) -> Self {
+        let mut report = Self {
+            ok: true,
+            scope,
+            tap,
+            socket,
+            storage,
+        };
+        report.ok = report.failures().is_empty();
+        report
+    }
+
+    /// Artifacts the agent failed to remove, by name.
+    pub fn failures(&self) -> Vec<(&'static str, &ArtifactTeardown)> {
+        [
+            ("scope", Some(&self.scope)),
+            ("tap", Some(&self.tap)),
+            ("socket", Some(&self.socket)),
+            ("storage", self.storage.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, artifact)| artifact.filter(|a| a.failed()).map(|a| (name, a)))
+        .collect()
+    }
+}
+
+/// A long-running manager operation that can be cancelled through
+/// `POST /v1/jobs/{id}/cancel`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    CreateVm,
+    MigrateVm,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    /// Cancel was requested; the job is rolling back.
+    Cancelling,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct Job {
+    pub id: uuid::Uuid,
+    pub kind: JobKind,
+    pub vm_id: uuid::Uuid,
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct ListJobsResponse {
+    pub items: Vec<Job>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -768,6 +1378,16 @@ pub struct TailLogResponse {
     pub text: String,
 }
 
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct StopVmParams {
+    /// Send Ctrl-Alt-Del and wait for the guest to shut down before force-stopping
+    #[serde(default)]
+    pub graceful: Option<bool>,
+    /// Seconds to wait for a graceful shutdown (default 30)
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
 #[derive(Debug, Clone, Deserialize, IntoParams)]
 pub struct VmPathParams {
     pub id: uuid::Uuid,
@@ -807,6 +1427,26 @@ pub struct BalloonStatsConfig {
     pub stats_polling_interval_s: Option<u64>,
 }
 
+/// Automatic balloon sizing from the guest agent's `memory_available_kb`.
+/// While the guest has more than `high_watermark_mib` available for
+/// `sustain_secs`, the balloon inflates by up to `step_mib`; below
+/// `low_watermark_mib` it deflates by `step_mib`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct BalloonPolicy {
+    pub high_watermark_mib: u64,
+    pub low_watermark_mib: u64,
+    pub step_mib: u64,
+    #[serde(default = "default_balloon_sustain_secs")]
+    pub sustain_secs: u64,
+    /// Largest balloon the policy inflates to. Defaults to half the VM's memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_balloon_mib: Option<u64>,
+}
+
+fn default_balloon_sustain_secs() -> u64 {
+    120
+}
+
 // ========================================
 // Functions (Serverless Lambda)
 // ========================================
@@ -823,6 +1463,9 @@ pub struct Function {
     pub vcpu: i32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env_vars: Option<serde_json::Value>,
+    /// Names of the secret env vars; every value reads `***`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env_vars: Option<std::collections::HashMap<String, String>>,
     // MicroVM information
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub vm_id: Option<uuid::Uuid>,
@@ -854,6 +1497,9 @@ pub struct FunctionInvocation {
     pub logs: Vec<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Published version that served the invocation; absent for `$LATEST`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
     pub invoked_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -871,6 +1517,10 @@ pub struct CreateFunctionReq {
     pub vcpu: i32,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env_vars: Option<serde_json::Value>,
+    /// Env vars encrypted at rest and redacted in responses, e.g. API keys.
+    /// They reach the function's environment at invoke time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env_vars: Option<std::collections::HashMap<String, String>>,
 }
 
 fn default_timeout() -> i32 {
@@ -901,11 +1551,21 @@ pub struct UpdateFunctionReq {
     pub memory_mb: Option<i32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env_vars: Option<serde_json::Value>,
+    /// Replaces the whole set of secret env vars. A value of `***` keeps the
+    /// stored secret of that name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env_vars: Option<std::collections::HashMap<String, String>>,
+    /// Publish the updated function as a new immutable version.
+    #[serde(default)]
+    pub publish: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct InvokeFunctionReq {
     pub event: serde_json::Value,
+    /// `$LATEST` (the default), a published version number, or an alias name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qualifier: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
@@ -954,6 +1614,69 @@ pub struct ListInvocationsParams {
     pub limit: Option<i64>,
 }
 
+/// An immutable snapshot of a function's code and config.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FunctionVersion {
+    pub function_id: uuid::Uuid,
+    pub version: i32,
+    pub code: String,
+    pub handler: String,
+    pub timeout_seconds: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_vars: Option<serde_json::Value>,
+    /// Names of the secret env vars; every value reads `***`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env_vars: Option<std::collections::HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PublishFunctionVersionReq {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListFunctionVersionsResp {
+    pub items: Vec<FunctionVersion>,
+}
+
+/// A named pointer at a published version, optionally splitting traffic
+/// with a canary version.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct FunctionAlias {
+    pub name: String,
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary_version: Option<i32>,
+    /// Share of invocations (1-99) routed to `canary_version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary_percent: Option<i32>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PutFunctionAliasReq {
+    pub version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary_version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canary_percent: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListFunctionAliasesResp {
+    pub items: Vec<FunctionAlias>,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+pub struct FunctionAliasPathParams {
+    pub id: uuid::Uuid,
+    pub name: String,
+}
+
 // ========================================
 // Containers (Docker/OCI)
 // ========================================
@@ -962,7 +1685,11 @@ pub struct ListInvocationsParams {
 pub struct Container {
     pub id: uuid::Uuid,
     pub name: String,
+    /// The reference as given at create time.
     pub image: String,
+    /// `sha256:...` the reference resolved to, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_digest: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub command: Option<String>,
     #[serde(default)]
@@ -978,6 +1705,10 @@ pub struct Container {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub memory_limit_mb: Option<i32>,
     pub restart_policy: String,
+    /// Restarts the reconciler has made under `restart_policy` since the
+    /// container was last started by hand.
+    #[serde(default)]
+    pub restart_count: i32,
     pub state: String, // creating, running, stopped, restarting, error, paused
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub host_id: Option<uuid::Uuid>,
@@ -1027,9 +1758,37 @@ pub struct RegistryAuth {
     pub server_address: Option<String>, // e.g., "registry.example.com" or leave None for Docker Hub
 }
 
+/// A stored, named registry credential. The password is write-only and is
+/// never returned.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegistryCredential {
+    pub id: uuid::Uuid,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_address: Option<String>,
+    pub username: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateRegistryReq {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_address: Option<String>,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListRegistriesResp {
+    pub items: Vec<RegistryCredential>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CreateContainerReq {
     pub name: String,
+    /// Prefer pinning by digest (`nginx@sha256:...`); a tag is resolved to
+    /// its current digest at create time and recorded on the container.
     pub image: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub command: Option<String>,
@@ -1049,6 +1808,10 @@ pub struct CreateContainerReq {
     pub restart_policy: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub registry_auth: Option<RegistryAuth>,
+    /// Name of one of the caller's stored registry credentials; takes precedence
+    /// over `registry_auth`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
 }
 
 fn default_restart_policy() -> String {
@@ -1077,6 +1840,9 @@ pub struct CreateContainerResp {
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ListContainersResp {
     pub items: Vec<Container>,
+    /// Number of containers matching the filter across all pages
+    #[serde(default)]
+    pub total: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -1084,6 +1850,30 @@ pub struct GetContainerResp {
     pub item: Container,
 }
 
+/// `GET /v1/containers/{id}/digest`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContainerImageDigestResp {
+    pub image: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// The reference itself names a digest, so it can't drift.
+    pub pinned: bool,
+}
+
+/// `GET /v1/containers/{id}/inspect`: our stored view next to what the
+/// runtime actually reports, for spotting drift between the two.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InspectContainerResp {
+    pub item: Container,
+    /// Raw `docker inspect` JSON; absent when the container was never started
+    /// or the runtime couldn't be reached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<serde_json::Value>,
+    /// Why `runtime` is absent for a container that has a runtime id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ContainerStats {
     pub id: uuid::Uuid,
@@ -1110,6 +1900,9 @@ pub struct ContainerStats {
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ContainerStatsResp {
     pub items: Vec<ContainerStats>,
+    /// Seconds since the container started, while it is running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_seconds: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -1127,6 +1920,32 @@ pub struct ContainerLogsResp {
     pub items: Vec<ContainerLog>,
 }
 
+/// How a path differs from the container's image, as `docker diff` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerChangeKind {
+    Modified,
+    Added,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContainerChange {
+    pub path: String,
+    pub kind: ContainerChangeKind,
+}
+
+/// `GET /v1/containers/{id}/changes`: the container's filesystem changes
+/// against its image.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ContainerChangesResp {
+    pub items: Vec<ContainerChange>,
+    /// Why `items` is empty without being compared, e.g. the container was
+    /// never started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, IntoParams)]
 pub struct ContainerPathParams {
     pub id: uuid::Uuid,
@@ -1138,6 +1957,11 @@ pub struct ListContainersParams {
     pub state: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub host_id: Option<uuid::Uuid>,
+    /// Page size (default 100, max 1000, 0 returns all)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
 }
 
 #[derive(Debug, Clone, Deserialize, IntoParams)]
@@ -1174,6 +1998,37 @@ pub struct ExecCommandResp {
     pub exit_code: Option<i32>,
 }
 
+/// What `POST /v1/containers/batch` does to each container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerBatchAction {
+    Start,
+    Stop,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContainerBatchReq {
+    pub action: ContainerBatchAction,
+    /// At most 100; duplicates are ignored.
+    pub ids: Vec<uuid::Uuid>,
+}
+
+/// One container's outcome within a batch.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContainerBatchResult {
+    pub id: uuid::Uuid,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ContainerBatchResp {
+    /// In request order (for stop-all, newest container first).
+    pub results: Vec<ContainerBatchResult>,
+}
+
 // User Management Types
 
 /// User role for role-based access control (RBAC)
@@ -1230,6 +2085,7 @@ pub enum AuditAction {
     CreateUser,
     UpdateUser,
     DeleteUser,
+    ChangePassword,
 
     // VM actions
     CreateVm,
@@ -1241,25 +2097,51 @@ pub enum AuditAction {
     UpdateVm,
     CreateVmSnapshot,
     RestoreVmSnapshot,
+    DeleteVmSnapshot,
+    MigrateVm,
+    RescheduleVm,
+    BackupVm,
+
+    // Template actions
+    CreateTemplate,
+    UpdateTemplate,
+    DeleteTemplate,
+
+    // Image actions
+    CreateImage,
+    UploadImage,
+    ImportImage,
+    DeleteImage,
 
     // Function actions
     CreateFunction,
     InvokeFunction,
     UpdateFunction,
     DeleteFunction,
+    PublishFunctionVersion,
+    UpdateFunctionAlias,
+    DeleteFunctionAlias,
 
     // Container actions
     CreateContainer,
     StartContainer,
     StopContainer,
     DeleteContainer,
+    UpdateContainer,
+    RestartContainer,
+    PauseContainer,
+    ResumeContainer,
+    ExecContainer,
 
     // Network actions
     CreateNetwork,
     UpdateNetwork,
     DeleteNetwork,
     CreateNic,
+    UpdateNic,
     DeleteNic,
+    CreateNetworkRule,
+    DeleteNetworkRule,
 
     // Volume actions
     CreateVolume,
@@ -1267,6 +2149,23 @@ pub enum AuditAction {
     DetachVolume,
     DeleteVolume,
 
+    // Host actions
+    DeleteHost,
+
+    // Storage, registry and backup settings
+    CreateRegistry,
+    UpdateRegistry,
+    DeleteRegistry,
+    CreateStorageBackend,
+    UpdateStorageBackend,
+    DeleteStorageBackend,
+    CreateBackupTarget,
+    UpdateBackupTarget,
+    DeleteBackupTarget,
+
+    // Job actions
+    CancelJob,
+
     // System/lifecycle events
     SystemEvent,
 
@@ -1284,7 +2183,7 @@ pub enum AuditAction {
 }
 
 impl AuditAction {
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             AuditAction::Login => "login",
             AuditAction::Logout => "logout",
@@ -1292,6 +2191,7 @@ impl AuditAction {
             AuditAction::CreateUser => "create_user",
             AuditAction::UpdateUser => "update_user",
             AuditAction::DeleteUser => "delete_user",
+            AuditAction::ChangePassword => "change_password",
             AuditAction::CreateVm => "create_vm",
             AuditAction::StartVm => "start_vm",
             AuditAction::StopVm => "stop_vm",
@@ -1301,23 +2201,56 @@ impl AuditAction {
             AuditAction::UpdateVm => "update_vm",
             AuditAction::CreateVmSnapshot => "create_vm_snapshot",
             AuditAction::RestoreVmSnapshot => "restore_vm_snapshot",
+            AuditAction::DeleteVmSnapshot => "delete_vm_snapshot",
+            AuditAction::MigrateVm => "migrate_vm",
+            AuditAction::RescheduleVm => "reschedule_vm",
+            AuditAction::BackupVm => "backup_vm",
+            AuditAction::CreateTemplate => "create_template",
+            AuditAction::UpdateTemplate => "update_template",
+            AuditAction::DeleteTemplate => "delete_template",
+            AuditAction::CreateImage => "create_image",
+            AuditAction::UploadImage => "upload_image",
+            AuditAction::ImportImage => "import_image",
+            AuditAction::DeleteImage => "delete_image",
             AuditAction::CreateFunction => "create_function",
             AuditAction::InvokeFunction => "invoke_function",
             AuditAction::UpdateFunction => "update_function",
             AuditAction::DeleteFunction => "delete_function",
+            AuditAction::PublishFunctionVersion => "publish_function_version",
+            AuditAction::UpdateFunctionAlias => "update_function_alias",
+            AuditAction::DeleteFunctionAlias => "delete_function_alias",
             AuditAction::CreateContainer => "create_container",
             AuditAction::StartContainer => "start_container",
             AuditAction::StopContainer => "stop_container",
             AuditAction::DeleteContainer => "delete_container",
+            AuditAction::UpdateContainer => "update_container",
+            AuditAction::RestartContainer => "restart_container",
+            AuditAction::PauseContainer => "pause_container",
+            AuditAction::ResumeContainer => "resume_container",
+            AuditAction::ExecContainer => "exec_container",
             AuditAction::CreateNetwork => "create_network",
             AuditAction::UpdateNetwork => "update_network",
             AuditAction::DeleteNetwork => "delete_network",
             AuditAction::CreateNic => "create_nic",
+            AuditAction::UpdateNic => "update_nic",
+            AuditAction::CreateNetworkRule => "create_network_rule",
+            AuditAction::DeleteNetworkRule => "delete_network_rule",
             AuditAction::DeleteNic => "delete_nic",
             AuditAction::CreateVolume => "create_volume",
             AuditAction::AttachVolume => "attach_volume",
             AuditAction::DetachVolume => "detach_volume",
             AuditAction::DeleteVolume => "delete_volume",
+            AuditAction::DeleteHost => "delete_host",
+            AuditAction::CreateRegistry => "create_registry",
+            AuditAction::UpdateRegistry => "update_registry",
+            AuditAction::DeleteRegistry => "delete_registry",
+            AuditAction::CreateStorageBackend => "create_storage_backend",
+            AuditAction::UpdateStorageBackend => "update_storage_backend",
+            AuditAction::DeleteStorageBackend => "delete_storage_backend",
+            AuditAction::CreateBackupTarget => "create_backup_target",
+            AuditAction::UpdateBackupTarget => "update_backup_target",
+            AuditAction::DeleteBackupTarget => "delete_backup_target",
+            AuditAction::CancelJob => "cancel_job",
             AuditAction::SystemEvent => "system_event",
             AuditAction::AcceptEula => "accept_eula",
             AuditAction::ActivateLicense => "activate_license",
@@ -1348,6 +2281,9 @@ pub struct User {
     pub timezone: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub theme: Option<String>,
+    /// Set while the account is locked out after repeated failed logins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
@@ -1390,6 +2326,36 @@ pub struct GetUserResponse {
     pub item: User,
 }
 
+/// Per-user resource limits; an unset limit is unlimited. Functions and
+/// containers each run in their own microVM, so their vCPU and memory count
+/// towards `max_vcpu` and `max_mem_mib`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct UserQuota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_vms: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_vcpu: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_mem_mib: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_containers: Option<i32>,
+}
+
+/// What a user's resources currently add up to, in the units of [`UserQuota`].
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct UserQuotaUsage {
+    pub vms: i64,
+    pub vcpu: i64,
+    pub mem_mib: i64,
+    pub containers: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GetUserQuotaResponse {
+    pub quota: UserQuota,
+    pub usage: UserQuotaUsage,
+}
+
 // User Preferences
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Default)]
 pub struct NotificationPreferences {
@@ -1999,3 +2965,27 @@ pub struct CreateBackupRequest {
 pub struct RestoreRequest {
     pub target_backend_id: uuid::Uuid,
 }
+
+// Dashboard overview
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StateCount {
+    pub state: String,
+    pub count: i64,
+}
+
+/// Cluster-wide totals for the dashboard, computed in a single request.
+/// Capacity figures only cover hosts that are currently healthy.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClusterOverview {
+    pub vms: Vec<StateCount>,
+    pub containers: Vec<StateCount>,
+    pub functions: i64,
+    pub healthy_hosts: i64,
+    pub total_memory_mb: i64,
+    pub used_memory_mb: i64,
+    pub total_disk_gb: i64,
+    pub used_disk_gb: i64,
+    /// Audit log entries recorded in the last 24 hours
+    pub recent_audit_events: i64,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+}